@@ -0,0 +1,15 @@
+//! Finds the commit being built when building from a git checkout,
+//! so that worlds can record exactly which code generated them (see `src/world_info.rs`).
+use std::process::Command;
+
+fn main() {
+  println!("cargo:rerun-if-changed=.git/HEAD");
+  println!("cargo:rerun-if-changed=.git/refs/heads");
+  let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output();
+  if let Ok(output) = output {
+    let hash = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && !hash.trim().is_empty() {
+      println!("cargo:rustc-env=GLT_GIT_HASH={}", hash.trim());
+    };
+  };
+}
@@ -0,0 +1,79 @@
+//! Counts of the blocks placed by each kind of feature, sampled from a generator without rendering anything,
+//! for spotting changes to what is generated that are hard to see by eye, like pillars that have doubled in volume.
+use std::collections::BTreeMap;
+
+use crate::generation::feature_tag::FeatureTag;
+use crate::generation::Block;
+
+
+
+/// Which positions of the world are sampled for statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsSampling {
+  /// Every block of every nth chunk, in the order the chunks are rendered
+  EveryNthChunk(usize),
+  /// This many positions picked at random from the whole bounding box, seeded by the world's seed
+  Random(usize)
+}
+
+/// The number of each block placed by each kind of feature, out of some number of sampled positions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockStats {
+  counts: BTreeMap<(FeatureTag, Block), u64>,
+  /// The number of positions sampled, including those left empty
+  sampled: u64
+}
+
+impl BlockStats {
+  /// Records a sampled position, along with the block placed there and the feature that placed it, if any
+  pub fn record(&mut self, traced: Option<(Block, FeatureTag)>) {
+    self.sampled += 1;
+    if let Some((block, tag)) = traced {
+      *self.counts.entry((tag, block)).or_insert(0) += 1;
+    };
+  }
+
+  pub fn merge(mut self, other: BlockStats) -> BlockStats {
+    for (key, count) in other.counts {
+      *self.counts.entry(key).or_insert(0) += count;
+    };
+
+    self.sampled += other.sampled;
+    self
+  }
+
+  pub fn sampled(&self) -> u64 {
+    self.sampled
+  }
+
+  /// The number of blocks placed by each kind of feature
+  pub fn feature_totals(&self) -> BTreeMap<FeatureTag, u64> {
+    let mut totals = BTreeMap::new();
+    for (&(tag, _), &count) in self.counts.iter() {
+      *totals.entry(tag).or_insert(0) += count;
+    };
+
+    totals
+  }
+
+  /// A table of the blocks placed by each kind of feature, most common first, with each feature's total above them.
+  /// Shares are out of every sampled position, so that they can be compared between runs sampling the same way.
+  pub fn to_table_string(&self) -> String {
+    let share = |count: u64| if self.sampled == 0 { 0.0 } else { count as f64 / self.sampled as f64 * 100.0 };
+    let mut out = format!("{:<48} {:>12} {:>9}\n", "feature / block", "count", "share");
+    for (tag, total) in self.feature_totals() {
+      out += &format!("{:<48} {:>12} {:>8.3}%\n", tag.name(), total, share(total));
+      let mut blocks = self.counts.iter()
+        .filter(|((block_tag, _), _)| *block_tag == tag)
+        .map(|((_, block), &count)| (block, count))
+        .collect::<Vec<(&Block, u64)>>();
+      blocks.sort_by(|(block1, count1), (block2, count2)| count2.cmp(count1).then_with(|| block1.cmp(block2)));
+      for (block, count) in blocks {
+        out += &format!("  {:<46} {:>12} {:>8.3}%\n", block.base_blockstate(), count, share(count));
+      };
+    };
+
+    out += &format!("{:<48} {:>12}\n", "sampled positions", self.sampled);
+    out
+  }
+}
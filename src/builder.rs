@@ -0,0 +1,1208 @@
+//! The one way of configuring and creating a `Generator`. Settings can be given one at a time,
+//! or read from a TOML configuration file, and are all checked together when the generator is built,
+//! so that every problem with a configuration is reported at once.
+//!
+//! Configuration files mirror the generation flags, without their leading dashes:
+//!
+//! ```toml
+//! seed = 0
+//! layers = 3
+//! layer-spacing = 48
+//! # The bottom of the world and its build limit, both multiples of 16
+//! world-height = [-64, 512]
+//! foundation = "pillars"
+//! landmass-thickness = 5
+//! pillar-radius = 3
+//! skyline = "uniform"
+//! symmetry = "none"
+//! lighting = "minimal"
+//! collisions = "remove"
+//! edge-style = "fade"
+//! fade-width = 32
+//! # Either a single fill for every landmass, or "random" to draw one for each with the given weights
+//! slab-fill = "random"
+//! slab-fill-weights = [4, 1, 1, 2, 2]
+//! center = false
+//! # Present only if there is a trench around the city
+//! trench-distance = 64
+//! # Present only if there is a wall around the lowest layer
+//! wall-distance = 10
+//!
+//! # Present only if the shapes of the landmasses are varied, with percentages for the elongation and lobe depth
+//! [shapes]
+//! max-elongation = 100
+//! max-lobe-depth = 30
+//! max-lobes = 5
+//!
+//! # Present only if the landmasses are eroded
+//! [erosion]
+//! iterations = 2
+//! neighbors = 3
+//!
+//! # Present only if the landmasses are paved, with weights for their own material, the trim and the weathered trim
+//! [paving]
+//! mix = [70, 20, 10]
+//! patch-mix = [50, 15, 35]
+//! patches = true
+//!
+//! [features]
+//! caves = true
+//! weathering = false
+//!
+//! # Present only if any materials are themed, giving material roles blocks as in a theme file
+//! [theme]
+//! name = "harbor"
+//! dark-shade = "minecraft:cyan_terracotta"
+//! light = "minecraft:glowstone"
+//! ```
+//!
+//! Files are read as any other TOML, so tables can as well be written inline or as dotted keys like
+//! `shapes.max-lobes = 4`. Weights can also be written as strings, as for their flags, and so can seeds too large
+//! for a TOML integer. Every setting which can't be read is reported, along with every key which isn't a setting.
+//!
+//! Theme files, given with `--theme`, are laid out like the `[theme]` table on its own, without the table header.
+//! Their name defaults to the name of the file. See `generation::theme` for every role.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::generation::city::{
+  City, CollisionPolicy, ErosionConfig, Foundation, LandmassParams, Layer, Lighting, PavingConfig, PavingMix,
+  ShapeConfig, Skyline, SlabFillChoice, SlabFillWeights, Symmetry
+};
+use crate::generation::fade_bounds::EdgeStyle;
+use crate::generation::theme::{Theme, DEFAULT_THEME_NAME};
+use crate::generation::MaterialGeometry;
+use crate::{Generator, GeneratorOptions, WorldHeight, MINECRAFT_MAX_Z, MINECRAFT_MIN_Z, WORLD_MIN_Z};
+
+
+
+/// The features which can be switched on and off with `GeneratorBuilder::feature`.
+/// All of them are off by default apart from weathering and the altitude gradient.
+pub const FEATURES: [&str; 14] = [
+  "caves", "baked-shadows", "details", "wavy-ocean", "flat-bedrock", "weathering", "altitude-gradient", "deep-ocean",
+  "edge-beach", "seabed-response", "strata", "scatter", "pillar-clusters",
+  "bubble-lifts"
+];
+
+/// The trench is 24 blocks wide, and the ocean only extends 128 blocks past the city
+const MIN_TRENCH_DISTANCE: u32 = 12;
+const MAX_TRENCH_DISTANCE: u32 = 116;
+/// Any further out and the wall would stand apart from the city, rather than enclosing it
+const MAX_WALL_DISTANCE: u32 = 64;
+
+/// Collects the seed and options of a generator, which are only checked once they have all been given,
+/// so that every problem with them can be reported at once
+#[derive(Debug, Clone)]
+pub struct GeneratorBuilder {
+  seed: u64,
+  options: GeneratorOptions,
+  /// Problems found while the settings were given, such as unknown features, reported along with the rest by `build`
+  problems: Vec<ConfigProblem>
+}
+
+impl GeneratorBuilder {
+  /// Starts from the default settings
+  pub fn new(seed: u64) -> Self {
+    GeneratorBuilder { seed, options: GeneratorOptions::default(), problems: Vec::new() }
+  }
+
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// The settings given so far, which may not be valid
+  pub fn options(&self) -> &GeneratorOptions {
+    &self.options
+  }
+
+  /// Replaces the seed, keeping every other setting
+  pub fn reseed(&mut self, seed: u64) -> &mut Self {
+    self.seed = seed;
+    self
+  }
+
+  pub fn layers(&mut self, count: usize) -> &mut Self {
+    self.options.layer_count = count;
+    self
+  }
+
+  /// The vertical distance between the upper slabs of consecutive layers
+  pub fn layer_spacing(&mut self, blocks: i32) -> &mut Self {
+    self.options.layer_spacing = blocks;
+    self
+  }
+
+  /// The z values of the bottom of the world and of its build limit, one block above the highest block in it
+  pub fn world_height(&mut self, min_z: i32, max_z: i32) -> &mut Self {
+    self.options.world_height = WorldHeight { min_z, max_z };
+    self
+  }
+
+  /// The thickness of each landmass, the radius of the pillars holding it up and the heights of its buildings, together
+  pub fn building_config(&mut self, landmass: LandmassParams) -> &mut Self {
+    self.options.landmass = landmass;
+    self
+  }
+
+  pub fn landmass_thickness(&mut self, blocks: u32) -> &mut Self {
+    self.options.landmass.thickness = blocks;
+    self
+  }
+
+  /// How the heights of the buildings on every layer are spread
+  pub fn skyline(&mut self, skyline: Skyline) -> &mut Self {
+    self.options.landmass.skyline = skyline;
+    self
+  }
+
+  pub fn foundation(&mut self, foundation: Foundation) -> &mut Self {
+    self.options.foundation = foundation;
+    self
+  }
+
+  /// Which parts of the city have light sources placed in them
+  pub fn lighting(&mut self, lighting: Lighting) -> &mut Self {
+    self.options.lighting = lighting;
+    self
+  }
+
+  /// What happens to the features of each layer that the pillars of the layer above pass through
+  pub fn collisions(&mut self, policy: CollisionPolicy) -> &mut Self {
+    self.options.collisions = policy;
+    self
+  }
+
+  pub fn shapes(&mut self, shapes: Option<ShapeConfig>) -> &mut Self {
+    self.options.shapes = shapes;
+    self
+  }
+
+  /// Sets how much longer a landmass can be along one axis than the other, as a percentage,
+  /// varying the shapes of the landmasses if they weren't already
+  pub fn max_elongation(&mut self, percent: u32) -> &mut Self {
+    self.options.shapes.get_or_insert_with(ShapeConfig::default).max_elongation = percent;
+    self
+  }
+
+  /// Sets how far the lobes of a landmass can reach in and out, as a percentage of its size,
+  /// varying the shapes of the landmasses if they weren't already
+  pub fn max_lobe_depth(&mut self, percent: u32) -> &mut Self {
+    self.options.shapes.get_or_insert_with(ShapeConfig::default).max_lobe_depth = percent;
+    self
+  }
+
+  /// Sets the most lobes a landmass can have, varying the shapes of the landmasses if they weren't already
+  pub fn max_lobes(&mut self, count: u32) -> &mut Self {
+    self.options.shapes.get_or_insert_with(ShapeConfig::default).max_lobes = count;
+    self
+  }
+
+  /// Mirrors the outline of every landmass across one or both of its axes
+  pub fn symmetry(&mut self, symmetry: Symmetry) -> &mut Self {
+    self.options.symmetry = symmetry;
+    self
+  }
+
+  pub fn erosion(&mut self, erosion: Option<ErosionConfig>) -> &mut Self {
+    self.options.erosion = erosion;
+    self
+  }
+
+  /// Sets the number of erosion passes, eroding the landmasses if they weren't already
+  pub fn erosion_iterations(&mut self, iterations: u32) -> &mut Self {
+    self.options.erosion.get_or_insert_with(ErosionConfig::default).iterations = iterations;
+    self
+  }
+
+  /// Sets the number of neighbors a cell needs to survive erosion, eroding the landmasses if they weren't already
+  pub fn erosion_neighbors(&mut self, min_neighbors: u32) -> &mut Self {
+    self.options.erosion.get_or_insert_with(ErosionConfig::default).min_neighbors = min_neighbors;
+    self
+  }
+
+  pub fn paving(&mut self, paving: Option<PavingConfig>) -> &mut Self {
+    self.options.paving = paving;
+    self
+  }
+
+  /// Sets how likely each block of the paving is, paving the landmasses if they weren't already
+  pub fn paving_mix(&mut self, mix: PavingMix) -> &mut Self {
+    self.options.paving.get_or_insert_with(PavingConfig::default).mix = mix;
+    self
+  }
+
+  /// Sets the mix used in patches across the paving, or leaves out the patches if none is given,
+  /// paving the landmasses if they weren't already
+  pub fn paving_patch_mix(&mut self, patch_mix: Option<PavingMix>) -> &mut Self {
+    self.options.paving.get_or_insert_with(PavingConfig::default).patch_mix = patch_mix;
+    self
+  }
+
+  /// How the interiors of the landmasses are filled in between their slabs
+  pub fn slab_fill(&mut self, slab_fill: SlabFillChoice) -> &mut Self {
+    self.options.slab_fill = slab_fill;
+    self
+  }
+
+  /// Sets how likely each kind of fill is, drawing a fill for each landmass if they weren't already
+  pub fn slab_fill_weights(&mut self, weights: SlabFillWeights) -> &mut Self {
+    self.options.slab_fill = SlabFillChoice::Random(weights);
+    self
+  }
+
+  /// Switches one of the `FEATURES` on or off
+  pub fn feature(&mut self, name: &str, enabled: bool) -> &mut Self {
+    let switch = match name {
+      "caves" => &mut self.options.caves,
+      "baked-shadows" => &mut self.options.baked_shadows,
+      "details" => &mut self.options.details,
+      "wavy-ocean" => &mut self.options.wavy_ocean,
+      "flat-bedrock" => &mut self.options.flat_bedrock,
+      "weathering" => &mut self.options.weathering,
+      "altitude-gradient" => &mut self.options.altitude_gradient,
+      "deep-ocean" => &mut self.options.deep_ocean,
+      "edge-beach" => &mut self.options.edge_beach,
+      "seabed-response" => &mut self.options.seabed_response,
+      "strata" => &mut self.options.strata,
+      "scatter" => &mut self.options.scatter,
+      "pillar-clusters" => &mut self.options.pillar_clusters,
+      "bubble-lifts" => &mut self.options.bubble_lifts,
+      _ => {
+        let message = format!("unknown feature, expected one of {}", FEATURES.join(", "));
+        self.problems.push(ConfigProblem::new(format!("features.{}", name), message));
+        return self;
+      }
+    };
+
+    *switch = enabled;
+    self
+  }
+
+  /// Digs a trench around the city, this many blocks away from it
+  pub fn trench_distance(&mut self, distance: Option<u32>) -> &mut Self {
+    self.options.trench_distance = distance;
+    self
+  }
+
+  /// Surrounds the lowest layer with a wall, this many blocks out from the edge of its landmass
+  pub fn wall_distance(&mut self, distance: Option<u32>) -> &mut Self {
+    self.options.wall_distance = distance;
+    self
+  }
+
+  pub fn edge_style(&mut self, edge_style: EdgeStyle) -> &mut Self {
+    self.options.edge_style = edge_style;
+    self
+  }
+
+  /// Swaps the generator's materials for those of a theme, replacing any theme given before
+  pub fn palette_set(&mut self, theme: Theme) -> &mut Self {
+    self.options.theme = theme;
+    self
+  }
+
+  /// Keeps the city centered on 0,0 and aligned as generated, instead of moving and rotating it at random
+  pub fn center(&mut self, center: bool) -> &mut Self {
+    self.options.center = center;
+    self
+  }
+
+  /// Whether layers are loaded from and saved to the on-disk layer cache
+  pub fn cache(&mut self, enabled: bool) -> &mut Self {
+    self.options.no_cache = !enabled;
+    self
+  }
+
+  pub fn clear_cache(&mut self, clear: bool) -> &mut Self {
+    self.options.clear_cache = clear;
+    self
+  }
+
+  pub fn profile_geometry(&mut self, profile: bool) -> &mut Self {
+    self.options.profile_geometry = profile;
+    self
+  }
+
+  /// Applies a theme file, laid out as in the module documentation, replacing any theme given before.
+  /// The theme is given the name passed in unless the file names it. Problems with the file are reported by `build`.
+  pub fn apply_theme(&mut self, name: &str, toml: &str) -> &mut Self {
+    self.palette_set(Theme::new(name));
+    match parse_toml::<ThemeTable>(toml) {
+      Ok(table) => {
+        let mut reader = ConfigReader::default();
+        self.apply_theme_table(table, &mut reader);
+        self.problems.extend(reader.problems);
+      },
+      Err(problem) => self.problems.push(ConfigProblem::new(format!("theme {}", problem.field), problem.message))
+    };
+
+    self
+  }
+
+  /// Applies the name and material roles of a theme, on top of the roles given before
+  fn apply_theme_table(&mut self, table: ThemeTable, reader: &mut ConfigReader) {
+    if let Some(name) = reader.read("theme.name", table.name) {
+      self.options.theme.name = name;
+    };
+
+    for (role, block) in table.roles {
+      let field = format!("theme.{}", role);
+      if let Some(block) = reader.read(&field, Some(block)) {
+        reader.check(&field, self.options.theme.set_role(&role, &block));
+      };
+    };
+  }
+
+  /// Applies the settings in a TOML configuration file, laid out as in the module documentation.
+  /// Settings the file leaves out are left as they are. Problems with the file are reported by `build`.
+  pub fn apply_config(&mut self, toml: &str) -> &mut Self {
+    let config = match parse_toml::<ConfigFile>(toml) {
+      Ok(config) => config,
+      Err(problem) => {
+        self.problems.push(problem);
+        return self;
+      }
+    };
+
+    let mut reader = ConfigReader::default();
+    if let Some(Seed(seed)) = reader.read("seed", config.seed) {
+      self.reseed(seed);
+    };
+
+    if let Some(count) = reader.read("layers", config.layers) {
+      self.layers(count);
+    };
+
+    if let Some(blocks) = reader.read("layer-spacing", config.layer_spacing) {
+      self.layer_spacing(blocks);
+    };
+
+    if let Some([min_z, max_z]) = reader.read("world-height", config.world_height) {
+      self.world_height(min_z, max_z);
+    };
+
+    if let Some(foundation) = reader.parse("foundation", config.foundation) {
+      self.foundation(foundation);
+    };
+
+    let mut landmass = self.options.landmass;
+    if let Some(blocks) = reader.read("landmass-thickness", config.landmass_thickness) {
+      landmass.thickness = blocks;
+    };
+
+    if let Some(blocks) = reader.read("pillar-radius", config.pillar_radius) {
+      landmass.pillar_radius = blocks;
+    };
+
+    if let Some(skyline) = reader.parse("skyline", config.skyline) {
+      landmass.skyline = skyline;
+    };
+
+    self.building_config(landmass);
+
+    if let Some(symmetry) = reader.parse("symmetry", config.symmetry) {
+      self.symmetry(symmetry);
+    };
+
+    if let Some(lighting) = reader.parse("lighting", config.lighting) {
+      self.lighting(lighting);
+    };
+
+    if let Some(policy) = reader.parse("collisions", config.collisions) {
+      self.collisions(policy);
+    };
+
+    // The fade width implies a fading edge, and the fill weights random fills, whether or not they are given too.
+    // Likewise, leaving out the patches overrides any patch mix.
+    if let Some(edge_style) = reader.parse("edge-style", config.edge_style) {
+      self.edge_style(edge_style);
+    };
+
+    if let Some(width) = reader.read("fade-width", config.fade_width) {
+      self.edge_style(EdgeStyle::Fade(width));
+    };
+
+    if let Some(slab_fill) = reader.parse("slab-fill", config.slab_fill) {
+      self.slab_fill(slab_fill);
+    };
+
+    if let Some(weights) = reader.parse_weights("slab-fill-weights", config.slab_fill_weights) {
+      self.slab_fill_weights(weights);
+    };
+
+    if let Some(center) = reader.read("center", config.center) {
+      self.center(center);
+    };
+
+    if let Some(distance) = reader.read("trench-distance", config.trench_distance) {
+      self.trench_distance(Some(distance));
+    };
+
+    if let Some(distance) = reader.read("wall-distance", config.wall_distance) {
+      self.wall_distance(Some(distance));
+    };
+
+    if let Some(shapes) = reader.read("shapes", config.shapes) {
+      self.options.shapes.get_or_insert_with(ShapeConfig::default);
+      if let Some(percent) = reader.read("shapes.max-elongation", shapes.max_elongation) {
+        self.max_elongation(percent);
+      };
+
+      if let Some(percent) = reader.read("shapes.max-lobe-depth", shapes.max_lobe_depth) {
+        self.max_lobe_depth(percent);
+      };
+
+      if let Some(count) = reader.read("shapes.max-lobes", shapes.max_lobes) {
+        self.max_lobes(count);
+      };
+
+      reader.unknown("shapes.", shapes.unknown);
+    };
+
+    if let Some(erosion) = reader.read("erosion", config.erosion) {
+      self.options.erosion.get_or_insert_with(ErosionConfig::default);
+      if let Some(iterations) = reader.read("erosion.iterations", erosion.iterations) {
+        self.erosion_iterations(iterations);
+      };
+
+      if let Some(neighbors) = reader.read("erosion.neighbors", erosion.neighbors) {
+        self.erosion_neighbors(neighbors);
+      };
+
+      reader.unknown("erosion.", erosion.unknown);
+    };
+
+    if let Some(paving) = reader.read("paving", config.paving) {
+      self.options.paving.get_or_insert_with(PavingConfig::default);
+      if let Some(mix) = reader.parse_weights("paving.mix", paving.mix) {
+        self.paving_mix(mix);
+      };
+
+      if let Some(mix) = reader.parse_weights("paving.patch-mix", paving.patch_mix) {
+        self.paving_patch_mix(Some(mix));
+      };
+
+      if let Some(false) = reader.read("paving.patches", paving.patches) {
+        self.paving_patch_mix(None);
+      };
+
+      reader.unknown("paving.", paving.unknown);
+    };
+
+    for (name, enabled) in reader.read("features", config.features).unwrap_or_default() {
+      if let Some(enabled) = reader.read(&format!("features.{}", name), Some(enabled)) {
+        self.feature(&name, enabled);
+      };
+    };
+
+    if let Some(theme) = reader.read("theme", config.theme) {
+      self.apply_theme_table(theme, &mut reader);
+    };
+
+    reader.unknown("", config.unknown);
+    self.problems.extend(reader.problems);
+    self
+  }
+
+  /// Writes every setting which affects what is generated as a TOML configuration file,
+  /// which `apply_config` reads back into the same settings
+  pub fn to_toml(&self) -> String {
+    let options = &self.options;
+    let foundation = match options.foundation {
+      Foundation::Pillars => "pillars",
+      Foundation::Skirt => "skirt"
+    };
+
+    let (edge_style, fade_width) = match options.edge_style {
+      EdgeStyle::Hard => ("hard", None),
+      EdgeStyle::Fade(width) => ("fade", Some(width))
+    };
+
+    let (slab_fill, slab_fill_weights) = match options.slab_fill {
+      SlabFillChoice::Fixed(fill) => (fill.to_string(), None),
+      SlabFillChoice::Random(weights) => ("random".to_owned(), Some(Weights(weights.to_string())))
+    };
+
+    let switches = [
+      options.caves, options.baked_shadows, options.details, options.wavy_ocean, options.flat_bedrock,
+      options.weathering, options.altitude_gradient, options.deep_ocean, options.edge_beach, options.seabed_response,
+      options.strata, options.scatter, options.pillar_clusters, options.bubble_lifts
+    ];
+    let features = FEATURES.iter().zip(switches).map(|(name, enabled)| (name.to_string(), Setting(Ok(enabled))));
+    let themed = !options.theme.is_empty() || options.theme.name != DEFAULT_THEME_NAME;
+    let config = ConfigFile {
+      seed: setting(Seed(self.seed)),
+      layers: setting(options.layer_count),
+      layer_spacing: setting(options.layer_spacing),
+      world_height: setting([options.world_height.min_z, options.world_height.max_z]),
+      foundation: setting(foundation.to_owned()),
+      landmass_thickness: setting(options.landmass.thickness),
+      pillar_radius: setting(options.landmass.pillar_radius),
+      skyline: setting(options.landmass.skyline.name().to_owned()),
+      symmetry: setting(options.symmetry.name().to_owned()),
+      lighting: setting(options.lighting.name().to_owned()),
+      collisions: setting(options.collisions.name().to_owned()),
+      edge_style: setting(edge_style.to_owned()),
+      fade_width: fade_width.and_then(setting),
+      slab_fill: setting(slab_fill),
+      slab_fill_weights: slab_fill_weights.and_then(setting),
+      center: setting(options.center),
+      trench_distance: options.trench_distance.and_then(setting),
+      wall_distance: options.wall_distance.and_then(setting),
+      shapes: options.shapes.and_then(|shapes| setting(ShapesTable {
+        max_elongation: setting(shapes.max_elongation),
+        max_lobe_depth: setting(shapes.max_lobe_depth),
+        max_lobes: setting(shapes.max_lobes),
+        unknown: BTreeMap::new()
+      })),
+      erosion: options.erosion.and_then(|erosion| setting(ErosionTable {
+        iterations: setting(erosion.iterations),
+        neighbors: setting(erosion.min_neighbors),
+        unknown: BTreeMap::new()
+      })),
+      paving: options.paving.and_then(|paving| setting(PavingTable {
+        mix: setting(Weights(paving.mix.to_string())),
+        patch_mix: paving.patch_mix.and_then(|patch_mix| setting(Weights(patch_mix.to_string()))),
+        patches: setting(paving.patch_mix.is_some()),
+        unknown: BTreeMap::new()
+      })),
+      features: setting(features.collect()),
+      theme: themed.then(|| ThemeTable {
+        name: setting(options.theme.name.clone()),
+        roles: options.theme.roles()
+          .map(|(role, block)| (role.to_owned(), Setting(Ok(block.base_blockstate().to_owned()))))
+          .collect()
+      }).and_then(setting),
+      unknown: BTreeMap::new()
+    };
+
+    toml::to_string(&config).expect("every setting can be written as TOML")
+  }
+
+  /// Checks every setting, returning all of the problems found if any of them are invalid
+  pub fn validate(&self) -> Result<(), ConfigError> {
+    let options = &self.options;
+    let mut problems = self.problems.clone();
+    let mut problem = |field: &str, message: String| problems.push(ConfigProblem::new(field, message));
+    if options.layer_count == 0 {
+      problem("layers", "must be at least 1".to_owned());
+    };
+
+    if options.landmass.thickness == 0 {
+      problem("landmass-thickness", "must be at least 1".to_owned());
+    };
+
+    if options.landmass.pillar_radius == 0 {
+      problem("pillar-radius", "must be at least 1".to_owned());
+    };
+
+    if options.layer_spacing <= options.landmass.thickness as i32 {
+      let message = format!("must be greater than the landmass thickness ({})", options.landmass.thickness);
+      problem("layer-spacing", message);
+    } else {
+      let (top, max_z) = (options.layer_count as i64 * options.layer_spacing as i64, options.world_height.max_z);
+      // The build limit itself is one block above the highest block that can be placed
+      if top >= max_z as i64 {
+        let message = format!("the top layer would be at z {}, not beneath the build limit at z {}", top, max_z);
+        problem("layers", message);
+      };
+    };
+
+    let world_height = options.world_height;
+    if world_height.min_z % 16 != 0 || world_height.max_z % 16 != 0 {
+      problem("world-height", "the bottom of the world and its build limit must both be multiples of 16".to_owned());
+    };
+
+    if world_height.max_z <= world_height.min_z {
+      problem("world-height", "the build limit must be above the bottom of the world".to_owned());
+    };
+
+    if world_height.min_z > WORLD_MIN_Z {
+      let message = format!("the bottom of the world must be at most z {}, to leave room for the ocean", WORLD_MIN_Z);
+      problem("world-height", message);
+    };
+
+    let (lowest, highest) = (MINECRAFT_MIN_Z, MINECRAFT_MAX_Z + 1);
+    if world_height.min_z < lowest || world_height.max_z > highest {
+      let message = format!("must be within z {} to {}, as far as Minecraft allows", lowest, highest);
+      problem("world-height", message);
+    };
+
+    if options.edge_style == EdgeStyle::Fade(0) {
+      problem("fade-width", "must be at least 1".to_owned());
+    };
+
+    if let Some(distance) = options.trench_distance {
+      if !(MIN_TRENCH_DISTANCE..=MAX_TRENCH_DISTANCE).contains(&distance) {
+        let message = format!(
+          "must be from {} to {}, to keep the trench clear of the city and within the world",
+          MIN_TRENCH_DISTANCE, MAX_TRENCH_DISTANCE
+        );
+        problem("trench-distance", message);
+      };
+    };
+
+    if let Some(distance) = options.wall_distance {
+      if !(1..=MAX_WALL_DISTANCE).contains(&distance) {
+        problem("wall-distance", format!("must be from 1 to {}", MAX_WALL_DISTANCE));
+      };
+    };
+
+    match options.slab_fill {
+      SlabFillChoice::Fixed(fill) => if let Some(message) = fill.problem() {
+        problem("slab-fill", message);
+      },
+      SlabFillChoice::Random(weights) => if weights.to_array().iter().all(|&weight| weight == 0) {
+        problem("slab-fill-weights", "at least one kind of fill must have a weight above 0".to_owned());
+      }
+    };
+
+    if let Some(shapes) = options.shapes {
+      if shapes.max_elongation > 400 {
+        let message = "must be at most 400, or landmasses would become too thin to build on";
+        problem("shapes.max-elongation", message.to_owned());
+      };
+
+      if shapes.max_lobe_depth >= 100 {
+        problem("shapes.max-lobe-depth", "must be less than 100, or the lobes would pinch landmasses apart".to_owned());
+      };
+
+      if shapes.max_lobes < 2 {
+        problem("shapes.max-lobes", "must be at least 2".to_owned());
+      };
+    };
+
+    if options.erosion.map_or(false, |erosion| erosion.min_neighbors > 8) {
+      problem("erosion.neighbors", "must be at most 8, since that is all of a cell's neighbors".to_owned());
+    };
+
+    if let Some(paving) = options.paving {
+      let mixes = [("paving.mix", Some(paving.mix)), ("paving.patch-mix", paving.patch_mix)];
+      for (field, mix) in mixes {
+        if mix.map_or(false, |mix| mix.to_array().iter().all(|&weight| weight == 0)) {
+          problem(field, "at least one block must have a weight above 0".to_owned());
+        };
+      };
+    };
+
+    match problems.is_empty() {
+      true => Ok(()),
+      false => Err(ConfigError { problems })
+    }
+  }
+
+  /// Validates the settings and creates the generator they describe
+  pub fn build(&self) -> Result<Generator, ConfigError> {
+    self.build_with_hooks(GenerationHooks::default())
+  }
+
+  /// Validates the settings and creates the generator they describe, running the given hooks along the way.
+  /// The settings alone no longer describe the world, so `to_toml` only reproduces it if the same hooks are given.
+  pub fn build_with_hooks(&self, hooks: GenerationHooks) -> Result<Generator, ConfigError> {
+    self.validate()?;
+    Ok(Generator::new(self.seed, &self.options, hooks))
+  }
+}
+
+impl Default for GeneratorBuilder {
+  fn default() -> Self {
+    GeneratorBuilder::new(0)
+  }
+}
+
+/// Changes to the world which can't be made through the settings, run at fixed points while a generator is created.
+/// The default runs none of them, leaving the world exactly as the settings describe it.
+#[derive(Default)]
+pub struct GenerationHooks {
+  /// Run once the city's layers have been generated, before any of them are resolved against the pillars of the layer
+  /// above or decorated, with the layers from the bottom up. See `City::edit_layers` for what it may change.
+  pub after_layers: Option<Box<dyn FnMut(&mut Vec<Layer>)>>,
+  /// Run on the city after `after_layers`, before it is moved into place in the world and the sea floor is shaped
+  /// around it, and so before the world is cut off at its edge
+  pub after_city: Option<Box<dyn Fn(City) -> City>>,
+  /// Run last, on the whole world once it is cut off at its edge. The geometry it returns is sampled in place of the
+  /// world, and is cut off at the same edge. Chunks are then always sampled through it block by block,
+  /// so none are rendered as far-field chunks.
+  pub wrap_final: Option<Box<dyn Fn(BoxedWorld) -> BoxedWorld>>
+}
+
+impl fmt::Debug for GenerationHooks {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("GenerationHooks")
+      .field("after_layers", &self.after_layers.is_some())
+      .field("after_city", &self.after_city.is_some())
+      .field("wrap_final", &self.wrap_final.is_some())
+      .finish()
+  }
+}
+
+/// A whole world, as handed to and returned from `GenerationHooks::wrap_final`
+pub type BoxedWorld = Box<dyn MaterialGeometry + Send + Sync>;
+
+/// Every problem with a configuration, as found by `GeneratorBuilder::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConfigError {
+  pub problems: Vec<ConfigProblem>
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid configuration:")?;
+    for problem in &self.problems {
+      write!(f, "\n  {}: {}", problem.field, problem.message)?;
+    };
+
+    Ok(())
+  }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConfigProblem {
+  /// The setting at fault, named as in a configuration file, or the line of the file which couldn't be read
+  pub field: String,
+  pub message: String
+}
+
+impl ConfigProblem {
+  fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+    ConfigProblem { field: field.into(), message: message.into() }
+  }
+}
+
+
+
+/// A configuration file, laid out as in the module documentation.
+/// Keys that aren't settings are kept in `unknown`, to be reported rather than rejecting the whole file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ConfigFile {
+  seed: Option<Setting<Seed>>,
+  layers: Option<Setting<usize>>,
+  layer_spacing: Option<Setting<i32>>,
+  world_height: Option<Setting<[i32; 2]>>,
+  foundation: Option<Setting<String>>,
+  landmass_thickness: Option<Setting<u32>>,
+  pillar_radius: Option<Setting<u32>>,
+  skyline: Option<Setting<String>>,
+  symmetry: Option<Setting<String>>,
+  lighting: Option<Setting<String>>,
+  collisions: Option<Setting<String>>,
+  edge_style: Option<Setting<String>>,
+  fade_width: Option<Setting<u32>>,
+  slab_fill: Option<Setting<String>>,
+  slab_fill_weights: Option<Setting<Weights>>,
+  center: Option<Setting<bool>>,
+  trench_distance: Option<Setting<u32>>,
+  wall_distance: Option<Setting<u32>>,
+  shapes: Option<Setting<ShapesTable>>,
+  erosion: Option<Setting<ErosionTable>>,
+  paving: Option<Setting<PavingTable>>,
+  features: Option<Setting<BTreeMap<String, Setting<bool>>>>,
+  theme: Option<Setting<ThemeTable>>,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, toml::Value>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", expecting = "a table")]
+struct ShapesTable {
+  max_elongation: Option<Setting<u32>>,
+  max_lobe_depth: Option<Setting<u32>>,
+  max_lobes: Option<Setting<u32>>,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, toml::Value>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", expecting = "a table")]
+struct ErosionTable {
+  iterations: Option<Setting<u32>>,
+  neighbors: Option<Setting<u32>>,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, toml::Value>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", expecting = "a table")]
+struct PavingTable {
+  mix: Option<Setting<Weights>>,
+  patch_mix: Option<Setting<Weights>>,
+  patches: Option<Setting<bool>>,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, toml::Value>
+}
+
+/// A theme file, or the `[theme]` table of a configuration file, with every key apart from the name a material role
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(expecting = "a table")]
+struct ThemeTable {
+  name: Option<Setting<String>>,
+  #[serde(flatten)]
+  roles: BTreeMap<String, Setting<String>>
+}
+
+/// A single setting, or the reason its value couldn't be read. Reading a value of the wrong type fails this setting
+/// alone, so that the rest of the file is still read and every problem with it is reported together.
+#[derive(Debug)]
+struct Setting<T>(Result<T, String>);
+
+/// A setting with the given value, as written by `GeneratorBuilder::to_toml`
+fn setting<T>(value: T) -> Option<Setting<T>> {
+  Some(Setting(Ok(value)))
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Setting<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let value = toml::Value::deserialize(deserializer)?;
+    Ok(Setting(value.try_into().map_err(|err: toml::de::Error| err.message().to_owned())))
+  }
+}
+
+impl<T: Serialize> Serialize for Setting<T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match &self.0 {
+      Ok(value) => value.serialize(serializer),
+      Err(message) => Err(S::Error::custom(message))
+    }
+  }
+}
+
+/// A seed, which is written as a string if it is too large for a TOML integer
+#[derive(Debug, Clone, Copy)]
+struct Seed(u64);
+
+impl<'de> Deserialize<'de> for Seed {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let seed = match toml::Value::deserialize(deserializer)? {
+      toml::Value::Integer(seed) => u64::try_from(seed).ok(),
+      toml::Value::String(seed) => seed.parse().ok(),
+      _ => None
+    };
+
+    seed.map(Seed).ok_or_else(|| D::Error::custom(format!("expected a whole number from 0 to {}", u64::MAX)))
+  }
+}
+
+impl Serialize for Seed {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match i64::try_from(self.0) {
+      Ok(seed) => serializer.serialize_i64(seed),
+      Err(_) => serializer.serialize_str(&self.0.to_string())
+    }
+  }
+}
+
+/// Weights written either as an array of integers or as a string, as for their flags,
+/// kept in the form of the flag to be parsed by the type they are weights for
+#[derive(Debug, Clone)]
+struct Weights(String);
+
+impl<'de> Deserialize<'de> for Weights {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let weights = match toml::Value::deserialize(deserializer)? {
+      toml::Value::String(weights) => Some(weights),
+      toml::Value::Array(weights) => weights.iter()
+        .map(|weight| weight.as_integer().map(|weight| weight.to_string()))
+        .collect::<Option<Vec<_>>>()
+        .map(|weights| weights.join(",")),
+      _ => None
+    };
+
+    weights.map(Weights).ok_or_else(|| D::Error::custom("expected an array of integers, like [70, 20, 10]"))
+  }
+}
+
+impl Serialize for Weights {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match self.0.split(',').map(str::parse).collect::<Result<Vec<u32>, _>>() {
+      Ok(weights) => weights.serialize(serializer),
+      Err(_) => serializer.serialize_str(&self.0)
+    }
+  }
+}
+
+/// Collects the problems with the settings of a file as they are read
+#[derive(Debug, Default)]
+struct ConfigReader {
+  problems: Vec<ConfigProblem>
+}
+
+impl ConfigReader {
+  /// The value of a setting, if it was given and could be read
+  fn read<T>(&mut self, field: &str, setting: Option<Setting<T>>) -> Option<T> {
+    self.check(field, setting?.0)
+  }
+
+  /// The value of a setting written as for the flag of the same name, if it was given and could be parsed
+  fn parse<T: FromStr<Err = String>>(&mut self, field: &str, setting: Option<Setting<String>>) -> Option<T> {
+    let value = self.read(field, setting)?;
+    self.check(field, value.parse())
+  }
+
+  /// The value of a setting giving weights, if it was given and could be parsed
+  fn parse_weights<T: FromStr<Err = String>>(&mut self, field: &str, setting: Option<Setting<Weights>>) -> Option<T> {
+    let Weights(weights) = self.read(field, setting)?;
+    self.check(field, weights.parse())
+  }
+
+  fn check<T>(&mut self, field: &str, result: Result<T, String>) -> Option<T> {
+    result.map_err(|message| self.problems.push(ConfigProblem::new(field, message))).ok()
+  }
+
+  /// Reports every key of a table which isn't a setting, given the prefix of the table's keys
+  fn unknown(&mut self, prefix: &str, keys: BTreeMap<String, toml::Value>) {
+    for key in keys.into_keys() {
+      self.problems.push(ConfigProblem::new(format!("{}{}", prefix, key), "unknown setting"));
+    };
+  }
+}
+
+/// Reads a TOML file laid out as `T`, or returns the problem with the line which couldn't be read
+fn parse_toml<T: DeserializeOwned>(toml: &str) -> Result<T, ConfigProblem> {
+  toml::from_str(toml).map_err(|err: toml::de::Error| {
+    let field = match err.span() {
+      Some(span) => format!("line {}", toml[..span.start].matches('\n').count() + 1),
+      None => "config".to_owned()
+    };
+
+    ConfigProblem::new(field, err.message())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::borrow::Cow;
+
+  use glam::IVec3;
+
+  use super::*;
+
+  fn problem_fields(builder: &GeneratorBuilder) -> Vec<String> {
+    let err = builder.validate().expect_err("expected the configuration to be invalid");
+    err.problems.into_iter().map(|problem| problem.field).collect()
+  }
+
+  /// Settings which differ from the defaults in every way that a configuration file can write
+  fn configured_builder() -> GeneratorBuilder {
+    let mut theme = Theme::new("harbor");
+    theme.set_role("dark-shade", "minecraft:cyan_terracotta").unwrap();
+    theme.set_role("light", "minecraft:glowstone").unwrap();
+    let mut builder = GeneratorBuilder::new(u64::MAX);
+    builder
+      .layers(4)
+      .layer_spacing(64)
+      .world_height(-128, 384)
+      .building_config(LandmassParams { thickness: 7, pillar_radius: 4, skyline: Skyline::Towers })
+      .palette_set(theme)
+      .foundation(Foundation::Skirt)
+      .symmetry(Symmetry::FourWay)
+      .lighting(Lighting::None)
+      .collisions(CollisionPolicy::Ignore)
+      .edge_style(EdgeStyle::Fade(20))
+      .slab_fill_weights("1,0,2,0,3".parse().unwrap())
+      .trench_distance(Some(40))
+      .wall_distance(Some(12))
+      .max_lobes(4)
+      .erosion_neighbors(5)
+      .paving_mix("60,30,10".parse().unwrap())
+      .paving_patch_mix(None)
+      .feature("caves", true)
+      .feature("weathering", false)
+      .center(true);
+    builder
+  }
+
+  /// The builder's defaults create the same world as the generator's own default options. Fewer layers are
+  /// generated than by default to keep it quick, which the golden samples of seed 0 are recorded with too.
+  #[test]
+  fn the_defaults_generate_the_default_world() {
+    let built = GeneratorBuilder::new(0).layers(1).cache(false).build().expect("the default settings are valid");
+    let options = GeneratorOptions { layer_count: 1, no_cache: true, ..GeneratorOptions::default() };
+    let expected = Generator::new(0, &options, GenerationHooks::default());
+    assert_eq!(built.bounding_box, expected.bounding_box);
+    assert_eq!(built.world_height, expected.world_height);
+
+    let probes = expected.feature_probes();
+    assert_eq!(built.feature_probes(), probes);
+    for (feature, pos) in probes {
+      assert_eq!(built.block_at(pos), expected.block_at(pos), "{} at {}", feature, pos);
+    };
+  }
+
+  #[test]
+  fn settings_round_trip_through_toml() {
+    let builder = configured_builder();
+    builder.validate().expect("the settings are valid");
+    let toml = builder.to_toml();
+    let mut read = GeneratorBuilder::new(0);
+    read.apply_config(&toml);
+    read.validate().unwrap_or_else(|err| panic!("{} in:\n{}", err, toml));
+    assert_eq!(read.seed(), u64::MAX);
+    assert_eq!(read.options().world_height, WorldHeight { min_z: -128, max_z: 384 });
+    assert_eq!(read.options().landmass, builder.options().landmass);
+    assert_eq!(read.options().symmetry, Symmetry::FourWay);
+    assert_eq!(read.options().theme, builder.options().theme);
+    assert_eq!(read.options().paving, builder.options().paving);
+    assert_eq!(read.to_toml(), toml);
+
+    let defaults = GeneratorBuilder::new(0);
+    let mut read = GeneratorBuilder::new(1);
+    read.apply_config(&defaults.to_toml());
+    assert_eq!(read.to_toml(), defaults.to_toml());
+  }
+
+  #[test]
+  fn any_toml_layout_of_the_settings_is_read() {
+    let mut builder = GeneratorBuilder::new(0);
+    builder.apply_config(r#"
+      seed = "18446744073709551615"
+      world-height = [-128, 320]
+      slab-fill-weights = "1,0,0,0,0"
+      shapes.max-lobes = 4
+      paving = { mix = [60, 30, 10], patches = false }
+      features = { caves = true }
+
+      [theme]
+      "light" = "minecraft:glowstone"
+    "#);
+    builder.validate().expect("the settings are valid");
+    let options = builder.options();
+    assert_eq!(builder.seed(), u64::MAX);
+    assert_eq!(options.world_height, WorldHeight { min_z: -128, max_z: 320 });
+    assert_eq!(options.slab_fill, SlabFillChoice::Random("1,0,0,0,0".parse().unwrap()));
+    assert_eq!(options.shapes.map(|shapes| shapes.max_lobes), Some(4));
+    assert_eq!(options.paving, Some(PavingConfig { mix: "60,30,10".parse().unwrap(), patch_mix: None }));
+    assert!(options.caves);
+    assert_eq!(options.theme.roles().count(), 1);
+  }
+
+  #[test]
+  fn every_problem_with_a_config_is_reported() {
+    let mut builder = GeneratorBuilder::new(0);
+    builder.apply_config(r#"
+      layers = 0
+      pillar-radius = -1
+      skyline = "jagged"
+      fade-width = 2.5
+      world-height = [-60, 512]
+      colour = "red"
+
+      [shapes]
+      max-lobes = 1
+      spikes = 3
+
+      [paving]
+      mix = [0, 0, 0]
+
+      [features]
+      flying = true
+      caves = "yes"
+    "#);
+    let fields = problem_fields(&builder);
+    let expected = [
+      "layers", "pillar-radius", "skyline", "fade-width", "world-height", "colour", "shapes.max-lobes", "shapes.spikes",
+      "paving.mix", "features.flying", "features.caves"
+    ];
+    for field in expected {
+      assert!(fields.iter().any(|found| found == field), "{} wasn't reported, only {:?}", field, fields);
+    };
+  }
+
+  #[test]
+  fn problems_with_the_world_height_are_reported() {
+    assert_eq!(problem_fields(GeneratorBuilder::new(0).world_height(-64, 500)), ["world-height"]);
+    assert_eq!(problem_fields(GeneratorBuilder::new(0).world_height(0, 512)), ["world-height"]);
+    assert_eq!(problem_fields(GeneratorBuilder::new(0).world_height(-4096, 512)), ["world-height"]);
+    assert_eq!(problem_fields(GeneratorBuilder::new(0).world_height(-64, 96)), ["layers"]);
+    // The top layer of three, 48 blocks apart, would be at the build limit itself
+    assert_eq!(problem_fields(GeneratorBuilder::new(0).world_height(-64, 144)), ["layers"]);
+    GeneratorBuilder::new(0).world_height(-64, 160).validate().expect("the top layer is beneath the build limit");
+    GeneratorBuilder::new(0).world_height(-2032, 2032).validate().expect("the whole of Minecraft's height is valid");
+  }
+
+  #[test]
+  fn unreadable_lines_are_reported() {
+    let mut builder = GeneratorBuilder::new(0);
+    builder.apply_config("layers = 2\nlayer-spacing = = 3\n");
+    assert_eq!(problem_fields(&builder), ["line 2"]);
+
+    builder = GeneratorBuilder::new(0);
+    let theme = "light = \"minecraft:glowstone\"\nbrick = minecraft:stone\nwall = \"minecraft:bricks\"\n";
+    builder.apply_theme("harbor", theme);
+    assert_eq!(problem_fields(&builder), ["theme line 2"]);
+  }
+
+  /// Both example themes are valid, and generate the same world as the default theme with their blocks swapped in
+  #[test]
+  fn the_example_themes_swap_their_blocks_into_the_world() {
+    let default = GeneratorBuilder::new(0).layers(1).cache(false).build().expect("the default settings are valid");
+    let themes = [
+      ("cyberpunk", include_str!("../themes/cyberpunk.toml")),
+      ("sandstone", include_str!("../themes/sandstone.toml"))
+    ];
+    for (name, toml) in themes {
+      let mut builder = GeneratorBuilder::new(0);
+      builder.layers(1).cache(false).apply_theme(name, toml);
+      let themed = builder.build().unwrap_or_else(|err| panic!("{}: {}", name, err));
+      let theme = &builder.options().theme;
+      assert_eq!(theme.name, name);
+      let role_count = toml.lines().filter(|line| line.contains("= \"minecraft:")).count();
+      assert_eq!(theme.roles().count(), role_count, "{}", name);
+
+      let mut swapped = 0;
+      for (feature, pos) in default.feature_probes() {
+        for z in pos.z - 8..=pos.z + 8 {
+          let pos = pos.truncate().extend(z);
+          let block = default.block_at(pos);
+          let expected = block.clone().map(|block| theme.apply(Cow::Owned(block)).into_owned());
+          assert_eq!(themed.block_at(pos), expected, "{}: {} at {}", name, feature, pos);
+          swapped += (expected != block) as usize;
+        };
+      };
+
+      assert!(swapped > 0, "{}: no blocks were swapped", name);
+    };
+  }
+
+  #[test]
+  fn problems_with_a_theme_name_the_role_and_what_is_wrong() {
+    let mut builder = GeneratorBuilder::new(0);
+    builder.apply_theme("broken", r#"
+      light = "minecraft:glowstone"
+      trim = "minecraft:stone[axis]"
+      road = "minecraft:black concrete"
+      wall = "minecraft:bricks[facing=north"
+      colour = "minecraft:red_wool"
+    "#);
+    let err = builder.validate().expect_err("expected the theme to be invalid");
+    let problems = err.problems.iter()
+      .map(|problem| (problem.field.as_str(), problem.message.as_str()))
+      .collect::<BTreeMap<&str, &str>>();
+    let expected = [
+      ("theme.colour", "unknown material role"),
+      ("theme.road", "`black concrete` is not a valid block id"),
+      ("theme.trim", "expected a property like `name=value`, found `axis`"),
+      ("theme.wall", "expected `]` after the properties")
+    ];
+    for (field, message) in expected {
+      let found = problems.get(field).unwrap_or_else(|| panic!("{} wasn't reported, only {:?}", field, problems));
+      assert!(found.contains(message), "{} was reported as {:?}", field, found);
+    };
+
+    assert!(!problems.contains_key("theme.light"), "{:?}", problems);
+    // Blocks from other namespaces are only warned about
+    let mut builder = GeneratorBuilder::new(0);
+    builder.apply_theme("modded", "light = \"create:andesite_casing\"\n");
+    builder.validate().expect("blocks from other namespaces are allowed");
+  }
+}
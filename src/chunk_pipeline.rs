@@ -0,0 +1,158 @@
+//! Samples chunks on the Rust side ahead of the thread writing them, so that sampling the generator overlaps with
+//! Amulet's writes instead of taking turns with them while the GIL is held.
+//!
+//! Chunks are sampled in parallel a window at a time and sent to the writer over a bounded channel, so that at most
+//! a couple of windows of sampled chunks are held in memory. Chunks within a window can finish sampling in any order,
+//! so the writer keeps the ones that arrive early until the chunks before them have been written, and always writes
+//! chunks in the order they were given. The chunks themselves are taken from an iterator a window at a time, so even
+//! a world of millions of chunks never has its whole list of chunks held in memory.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::chunk_sections::ChunkSections;
+use crate::chunk_stats::ChunkStats;
+use crate::far_field::FarFieldChunk;
+use crate::generation::block_entity::BlockEntity;
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::MaterialGeometry;
+use crate::Generator;
+
+
+
+/// The number of chunks sampled ahead of the one being written, unless given
+pub const DEFAULT_LOOKAHEAD: usize = 16;
+
+/// Everything needed to write a chunk, sampled without holding the GIL
+#[derive(Debug, Clone)]
+pub enum SampledChunk {
+  Full {
+    sections: ChunkSections,
+    /// The block entities whose blocks weren't covered up by some other feature
+    block_entities: Vec<BlockEntity>
+  },
+  /// A chunk for which `Generator::is_far_field_chunk` is true, sampled into the far-field palette
+  FarField(FarFieldChunk)
+}
+
+impl SampledChunk {
+  /// Samples every block and block entity in a chunk, collecting its statistics along the way
+  pub fn sample(generator: &Generator, chunk_pos: ChunkPos) -> (Self, ChunkStats) {
+    if generator.is_far_field_chunk(chunk_pos) {
+      let (far_field_chunk, stats) = FarFieldChunk::sample(generator, chunk_pos);
+      return (SampledChunk::FarField(far_field_chunk), stats);
+    };
+
+    let context = generator.chunk_context(chunk_pos);
+    let (sections, stats) = ChunkSections::sample(generator, &context, chunk_pos);
+    let block_entities = generator.block_entities_in_chunk(chunk_pos).into_iter()
+      .filter(|block_entity| context.block_material_at(block_entity.pos).as_deref() == Some(&block_entity.block))
+      .collect::<Vec<BlockEntity>>();
+    (SampledChunk::Full { sections, block_entities }, stats)
+  }
+}
+
+/// How far along a pipeline is, as seen by the writer
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineProgress {
+  pub sampled: usize,
+  pub written: usize,
+  pub total: usize
+}
+
+/// Where a pipeline spent its time, for checking that sampling and writing overlapped
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineTimings {
+  /// From the start of the pipeline until the last chunk was sampled
+  pub sampling: Duration,
+  /// The time spent in the writer, not counting time spent waiting for chunks to be sampled
+  pub writing: Duration,
+  pub total: Duration
+}
+
+impl PipelineTimings {
+  /// Logs the timings, which approach the greater of sampling and writing when they overlap fully,
+  /// rather than their sum
+  pub fn report(&self, chunk_count: usize) {
+    log::info!(
+      "sampled {} chunks in {:.1}s and spent {:.1}s writing them, taking {:.1}s in total",
+      chunk_count, self.sampling.as_secs_f64(), self.writing.as_secs_f64(), self.total.as_secs_f64()
+    );
+  }
+}
+
+/// Splits chunks into windows of up to `size` chunks each, collecting only one window at a time
+pub fn chunk_windows(
+  mut chunks: impl Iterator<Item = ChunkPos>, size: usize
+) -> impl Iterator<Item = Vec<ChunkPos>> {
+  std::iter::from_fn(move || {
+    let window = chunks.by_ref().take(size).collect::<Vec<ChunkPos>>();
+    (!window.is_empty()).then(|| window)
+  })
+}
+
+/// Samples each of the given chunks with `sample` on the rayon thread pool, up to `lookahead` chunks ahead of the
+/// last one written, while writing them in order on the calling thread with `write`.
+/// If `write` fails, sampling stops and the error is returned once the samplers have finished their current chunks.
+pub fn pipeline_chunks<T, E>(
+  chunks: impl ExactSizeIterator<Item = ChunkPos> + Send, lookahead: usize,
+  sample: impl Fn(ChunkPos) -> T + Sync,
+  mut write: impl FnMut(T, PipelineProgress) -> Result<(), E>
+) -> Result<PipelineTimings, E>
+where T: Send {
+  let lookahead = lookahead.max(1);
+  let total = chunks.len();
+  let started = Instant::now();
+  let sampled = AtomicUsize::new(0);
+  let stopped = AtomicBool::new(false);
+  let (sender, receiver) = mpsc::sync_channel::<(usize, T)>(lookahead);
+
+  thread::scope(|scope| {
+    let sampler = scope.spawn(|| {
+      for (i, window) in chunk_windows(chunks, lookahead).enumerate() {
+        window.par_iter().enumerate().for_each_with(sender.clone(), |sender, (j, &chunk_pos)| {
+          if stopped.load(Ordering::Relaxed) { return };
+          let chunk = sample(chunk_pos);
+          sampled.fetch_add(1, Ordering::Relaxed);
+          // Only fails once the writer has given up, in which case the chunk isn't needed
+          let _ = sender.send((i * lookahead + j, chunk));
+        });
+
+        if stopped.load(Ordering::Relaxed) { break };
+      };
+
+      // Hangs up, so that the writer stops waiting once every chunk has been received
+      drop(sender);
+      started.elapsed()
+    });
+
+    let mut writing = Duration::ZERO;
+    let mut pending = HashMap::new();
+    let mut written = 0;
+    let result = receiver.iter().try_for_each(|(index, chunk)| {
+      pending.insert(index, chunk);
+      while let Some(chunk) = pending.remove(&written) {
+        let progress = PipelineProgress { sampled: sampled.load(Ordering::Relaxed), written, total };
+        let write_started = Instant::now();
+        write(chunk, progress)?;
+        writing += write_started.elapsed();
+        written += 1;
+      };
+
+      Ok(())
+    });
+
+    if result.is_err() {
+      stopped.store(true, Ordering::Relaxed);
+    };
+
+    // Unblocks any samplers waiting to send, now that nothing more will be received
+    drop(receiver);
+    let sampling = sampler.join().expect("a chunk sampler panicked");
+    result.map(|()| PipelineTimings { sampling, writing, total: started.elapsed() })
+  })
+}
@@ -0,0 +1,105 @@
+//! A chunk's blocks sampled a section at a time into arrays of palette indices, laid out the way Amulet stores
+//! a section, so that each section can be handed to Amulet in one call instead of setting every block individually.
+//!
+//! Amulet stores a section as a `[x, y, z]` array in C order in Minecraft's coordinates, where y is the vertical axis.
+//! So the index of a block within its section is `x * 256 + y * 16 + z` in Minecraft's coordinates, which
+//! `world_coords` turns into the generator's own.
+use std::borrow::Cow;
+
+use glam::IVec3;
+
+use crate::chunk_stats::{ChunkStats, ChunkStatsRecorder, SECTION_HEIGHT};
+use crate::generation::chunk_context::ChunkContext;
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::{blocks, Block, MaterialGeometry};
+use crate::world_coords::from_minecraft;
+use crate::Generator;
+
+/// The number of blocks in a chunk section
+pub const SECTION_VOLUME: usize = 16 * 16 * SECTION_HEIGHT as usize;
+
+/// The position, within its section, of the block at the given index of a section's indices
+pub fn section_block_pos(index: usize) -> IVec3 {
+  let index = index as i32;
+  from_minecraft(index / 256, index / 16 % 16, index % 16)
+}
+
+/// A chunk's blocks, as indices into a palette of the blocks found in it
+#[derive(Debug, Clone)]
+pub struct ChunkSections {
+  /// Every distinct block in the chunk, in the order they were found, after air, which is always first
+  pub palette: Vec<Block>,
+  /// The index of the lowest section
+  pub min_section: i32,
+  /// Each section in turn, from the bottom up, as `SECTION_VOLUME` indices into `palette`
+  pub indices: Vec<u32>
+}
+
+impl ChunkSections {
+  /// Samples every block of a chunk from its context, collecting its statistics along the way.
+  /// Every section from the bottom of the generator to its top is filled in, with air wherever nothing is placed.
+  pub fn sample(generator: &Generator, context: &ChunkContext, chunk_pos: ChunkPos) -> (Self, ChunkStats) {
+    let mut recorder = ChunkStatsRecorder::new(chunk_pos);
+    let mut palette = SmallPalette::new(blocks::AIR);
+    let (min_z, max_z) = (generator.bounding_box.min.z, generator.bounding_box.max.z);
+    let min_section = min_z.div_euclid(SECTION_HEIGHT);
+    let max_section = max_z.div_euclid(SECTION_HEIGHT);
+
+    let section_count = (max_section - min_section + 1).max(0) as usize;
+    let mut indices = vec![0; section_count * SECTION_VOLUME];
+    for (section, section_indices) in (min_section..=max_section).zip(indices.chunks_exact_mut(SECTION_VOLUME)) {
+      for (i, index) in section_indices.iter_mut().enumerate() {
+        let block_pos = section_block_pos(i) + IVec3::new(0, 0, section * SECTION_HEIGHT);
+        if block_pos.z < min_z || block_pos.z > max_z { continue };
+        let block = match context.block_material_at(block_pos + chunk_pos.min_block().extend(0)) {
+          Some(block) => block,
+          None => continue
+        };
+
+        recorder.record(block_pos, &block);
+        *index = palette.index_of(block);
+      };
+    };
+
+    (ChunkSections { palette: palette.into_blocks(), min_section, indices }, recorder.finish())
+  }
+
+  /// Each section's index along with its indices, skipping sections made up entirely of air
+  pub fn sections(&self) -> impl Iterator<Item = (i32, &[u32])> {
+    (self.min_section..)
+      .zip(self.indices.chunks_exact(SECTION_VOLUME))
+      .filter(|(_, indices)| indices.iter().any(|&index| index != 0))
+  }
+}
+
+/// The distinct blocks of a chunk, in the order they were first found, looked up by a linear scan rather than by
+/// hashing their blockstate strings. A chunk rarely holds more than a dozen or so distinct blocks, and nearly every
+/// block sampled is a copy of a constant in `blocks`, which is found by comparing pointers before comparing strings.
+#[derive(Debug, Clone)]
+pub struct SmallPalette {
+  blocks: Vec<Block>
+}
+
+impl SmallPalette {
+  /// A palette holding only the given block, at index 0
+  pub fn new(first: Block) -> Self {
+    SmallPalette { blocks: vec![first] }
+  }
+
+  /// The index of a block, which is added to the end of the palette if it isn't in it already
+  pub fn index_of(&mut self, block: Cow<'_, Block>) -> u32 {
+    let found = self.blocks.iter().position(|palette_block| palette_block.ptr_eq(&block))
+      .or_else(|| self.blocks.iter().position(|palette_block| *palette_block == *block));
+    match found {
+      Some(index) => index as u32,
+      None => {
+        self.blocks.push(block.into_owned());
+        self.blocks.len() as u32 - 1
+      }
+    }
+  }
+
+  pub fn into_blocks(self) -> Vec<Block> {
+    self.blocks
+  }
+}
@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashSet};
+
+use glam::IVec3;
+
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::Block;
+
+
+
+/// The height of a Minecraft chunk section
+pub const SECTION_HEIGHT: i32 = 16;
+
+/// Thresholds past which a chunk is considered pathological
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLimits {
+  /// Maximum number of distinct blocks within a single 16-block section.
+  /// Past 256, Java edition stops giving the section a palette of its own and falls back to the global one.
+  pub max_section_palette: usize,
+  /// Maximum number of distinct blocks within an entire chunk
+  pub max_chunk_palette: usize
+}
+
+impl Default for ChunkLimits {
+  fn default() -> Self {
+    ChunkLimits {
+      max_section_palette: 256,
+      max_chunk_palette: 1024
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkStats {
+  pub chunk: ChunkPos,
+  /// The number of distinct blocks in each section, keyed by section index
+  pub section_palette_sizes: BTreeMap<i32, usize>,
+  /// The number of distinct blocks in the whole chunk
+  pub chunk_palette_size: usize,
+  /// The number of non-air blocks in the whole chunk
+  pub block_count: usize,
+  /// The z coordinate of the topmost block in each column, indexed by `y * 16 + x`
+  pub heightmap: Vec<Option<i32>>
+}
+
+impl ChunkStats {
+  pub fn max_section_palette_size(&self) -> usize {
+    self.section_palette_sizes.values().copied().max().unwrap_or(0)
+  }
+
+  pub fn exceeds(&self, limits: &ChunkLimits) -> bool {
+    self.max_section_palette_size() > limits.max_section_palette ||
+    self.chunk_palette_size > limits.max_chunk_palette
+  }
+}
+
+/// Accumulates the blocks placed into a chunk, producing a `ChunkStats` when finished
+#[derive(Debug, Clone)]
+pub struct ChunkStatsRecorder {
+  chunk: ChunkPos,
+  sections: BTreeMap<i32, HashSet<Block>>,
+  block_count: usize,
+  heightmap: Vec<Option<i32>>
+}
+
+impl ChunkStatsRecorder {
+  pub fn new(chunk: ChunkPos) -> Self {
+    ChunkStatsRecorder {
+      chunk,
+      sections: BTreeMap::new(),
+      block_count: 0,
+      heightmap: vec![None; 256]
+    }
+  }
+
+  pub fn record(&mut self, pos: IVec3, block: &Block) {
+    let section = pos.z.div_euclid(SECTION_HEIGHT);
+    let palette = self.sections.entry(section).or_default();
+    if !palette.contains(block) {
+      palette.insert(block.clone());
+    };
+
+    let height = &mut self.heightmap[(pos.y * 16 + pos.x) as usize];
+    *height = Some(height.map_or(pos.z, |height| height.max(pos.z)));
+
+    self.block_count += 1;
+  }
+
+  pub fn finish(self) -> ChunkStats {
+    let section_palette_sizes = self.sections.iter()
+      .map(|(&section, palette)| (section, palette.len()))
+      .collect();
+    let chunk_palette_size = self.sections.into_values()
+      .flatten()
+      .collect::<HashSet<Block>>()
+      .len();
+    ChunkStats {
+      chunk: self.chunk,
+      section_palette_sizes,
+      chunk_palette_size,
+      block_count: self.block_count,
+      heightmap: self.heightmap
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Records a distinct block for each of the given ids into the given section, one per position
+  fn record_distinct_blocks(recorder: &mut ChunkStatsRecorder, section: i32, ids: std::ops::Range<i32>) {
+    for (i, id) in ids.enumerate() {
+      let i = i as i32;
+      let pos = IVec3::new(i % 16, (i / 16) % 16, section * SECTION_HEIGHT + i / 256);
+      recorder.record(pos, &Block::from(format!("minecraft:test_block_{}", id)));
+    };
+  }
+
+  #[test]
+  fn default_limits_are_reachable() {
+    let limits = ChunkLimits::default();
+    let max = limits.max_section_palette as i32;
+
+    let mut recorder = ChunkStatsRecorder::new(ChunkPos::new(0, 0));
+    record_distinct_blocks(&mut recorder, 0, 0..max);
+    assert!(!recorder.clone().finish().exceeds(&limits));
+    record_distinct_blocks(&mut recorder, 0, max..max + 1);
+    assert!(recorder.finish().exceeds(&limits));
+  }
+
+  #[test]
+  fn chunk_palette_spans_sections() {
+    let limits = ChunkLimits { max_section_palette: 4096, max_chunk_palette: 300 };
+    let mut recorder = ChunkStatsRecorder::new(ChunkPos::new(0, 0));
+    record_distinct_blocks(&mut recorder, 0, 0..200);
+    record_distinct_blocks(&mut recorder, 3, 100..301);
+    let stats = recorder.finish();
+    assert_eq!(stats.section_palette_sizes, BTreeMap::from([(0, 200), (3, 201)]));
+    assert_eq!(stats.chunk_palette_size, 301);
+    assert_eq!(stats.block_count, 401);
+    assert!(stats.exceeds(&limits));
+  }
+}
@@ -0,0 +1,1215 @@
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::block_stats::StatsSampling;
+use crate::builder::GeneratorBuilder;
+use crate::chunk_stats::ChunkLimits;
+use crate::generation::city::{
+  CollisionPolicy, Foundation, Lighting, PavingMix, Skyline, SlabFill, SlabFillChoice, SlabFillWeights, Symmetry,
+  DEFAULT_WALL_DISTANCE
+};
+use crate::generation::fade_bounds::{EdgeStyle, DEFAULT_FADE_WIDTH};
+use crate::keep::KeepPattern;
+use crate::logger::LogFilter;
+use crate::split_output::SplitGrid;
+use crate::world_format::WorldFormat;
+use crate::{GeneratorOptions, WorldHeight};
+
+
+
+const USAGE: &str = "\
+usage: glt-mc-generator <command> [options]
+
+commands:
+  generate    renders the city into a Minecraft world using Amulet
+  preview     writes a heightmap of the city to a PNG image, without rendering a world
+  inspect     reads what the generator recorded about a world it rendered
+  validate    checks that the features of the world agree with each other, without rendering it
+  export      writes files describing the world, like its datapack, without rendering it
+  goldens     checks that generation still matches the recorded golden samples
+  explore     flips through seeds in the terminal, showing their landmasses, requires the `explore` feature
+
+run `glt-mc-generator <command> --help` to see the options for a command";
+
+const GENERATION_OPTIONS: &str =
+  "  --config <path>                  reads generation options from a TOML file, see `src/builder.rs` for its
+                                   layout, options given after it override the file's
+  --seed <seed>                    the seed to generate the city from (default: 0)
+  --layers <count>                 the number of layers in the city (default: 3)
+  --layer-spacing <blocks>         the vertical distance between consecutive layers (default: 48)
+  --world-height <min,max>         the bottom of the world and its build limit, both multiples of 16 and
+                                   with the bottom at most -64 (default: -64,512)
+  --foundation <skirt|pillars>     how the lowest layer meets the ocean (default: pillars)
+  --landmass-thickness <blocks>    the thickness of each landmass, from the bottom of its lower slab to the
+                                   top of its upper slab (default: 5)
+  --skyline <uniform|organic|towers>
+                                   how the heights of buildings are spread: evenly, mostly short with a
+                                   few tall, or low-rise with distinct towers (default: uniform)
+  --vary-shapes                    stretches each landmass into an ellipse and gives it lobes, differently
+                                   for each layer
+  --max-elongation <percent>       how much longer a landmass can be along one axis than the other,
+                                   implies --vary-shapes (default: 100)
+  --max-lobe-depth <percent>       how far the lobes of a landmass can reach in and out, as a percentage of
+                                   its size, implies --vary-shapes (default: 30)
+  --max-lobes <count>              the most lobes a landmass can have, implies --vary-shapes (default: 5)
+  --symmetry <none|mirror|four-way>
+                                   mirrors the outline of each landmass across one or both of its axes
+                                   (default: none)
+  --erode-landmasses               wears away the outlines of the landmasses
+  --erosion-iterations <count>     the number of erosion passes, implies --erode-landmasses (default: 2)
+  --erosion-neighbors <count>      the number of neighbors a cell needs to survive erosion, implies
+                                   --erode-landmasses (default: 3)
+  --slab-fill <fill>               how the interior of each landmass is filled in between its slabs, one of
+                                   checkered[:size], solid, hollow, columns[:spacing] with spacing at most
+                                   16, noise[:threshold] from 0 to 1, or random to draw a fill for each
+                                   landmass (default: checkered:2)
+  --slab-fill-weights <c,s,h,col,n>
+                                   how likely checkered, solid, hollow, columns and noise fills are to be
+                                   drawn, implies --slab-fill random (default: 4,1,1,2,2)
+  --caves                          carves caves out of the interior of the lowest landmass, with entrances
+                                   through its rim
+  --pillar-clusters                groups some of the pillars beneath the largest landmasses into braced
+                                   clusters of two or three
+  --baked-shadows                  darkens the surfaces of lower layers beneath higher layers
+  --details                        adds slabs around the rims of landmasses and stairs on the roofs of
+                                   buildings
+  --scatter                        scatters heaps of rubble, barrels and lamps over the open parts of the
+                                   landmasses
+  --pave                           paves the surfaces of the landmasses with a mix of their own material,
+                                   polished andesite and andesite, or the theme's trim blocks, in patches
+  --paving-mix <own,trim,worn>     how likely each block of the paving is, implies --pave (default: 70,20,10)
+  --paving-patch-mix <own,trim,worn>
+                                   how likely each block of the paving is in patches, implies --pave
+                                   (default: 50,15,35)
+  --no-paving-patches              paves the landmasses with one mix throughout, implies --pave
+  --wavy-ocean                     lets the surface of the ocean dip down by a block in places
+  --deep-ocean                     deepens the sea floor away from the city, to about 55 blocks deep at the
+                                   edge of the world
+  --edge-beach                     ramps the sea floor up into a sandy beach over the last 24 blocks before
+                                   the edge of the world, instead of ending the ocean in a wall of water
+  --seabed-response                leaves the sea floor bare and mixed with coarse dirt in the shadows of the
+                                   landmasses, and compacts it into mounds around the pillars' footings
+  --strata                         bands the deepslate beneath the sea floor with tuff and basalt, which
+                                   shows on the walls of trenches
+  --bubble-lifts                   stands bubble column lifts on the sea floor beside the pillars of the
+                                   lowest layer, carrying players up on their north faces and down on
+                                   their south faces
+  --wall                           surrounds the lowest layer with a crenellated wall, with a single gate
+  --wall-distance <blocks>         how far out from the edge of the lowest landmass the wall stands,
+                                   implies --wall (default: 10)
+  --trench <blocks>                digs a trench around the city this many blocks away from it, where the
+                                   sea floor plunges 8 to 12 blocks deeper and the gravel gives way
+  --no-weathering                  leaves out the stains beneath windows and on the tops of pillars
+  --altitude-gradient              shades the concrete of each layer lighter the higher up it is, with
+                                   buildings a shade lighter than their landmass (default)
+  --legacy-materials               makes every layer out of the same gray concrete, as in older worlds
+  --theme <path>                   swaps the city's materials for those given by a TOML theme file, see
+                                   `themes/` for examples and `src/generation/theme.rs` for every role
+  --lighting <none|minimal|full>   where lights are placed to keep hostile mobs from spawning: nowhere, in
+                                   buildings and beneath landmasses, or in the shafts too (default: minimal)
+  --collisions <remove|ignore>     what happens to buildings, props and pools that the pillars of the layer
+                                   above pass through: removed, or kept as they are (default: remove)
+  --center                         keeps the city centered on 0,0 instead of moving and rotating it at
+                                   random
+  --edge-style <hard|fade>         how the city is cut off at the edge of the world (default: hard)
+  --fade-width <blocks>            the width of the band the city dissolves over at the edge of the
+                                   world, implies --edge-style fade (default: 32)
+  --flat-bedrock                   generates a flat bedrock floor instead of a noisy one
+  --no-cache                       disables the on-disk layer cache
+  --clear-cache                    clears the on-disk layer cache before generating
+  --profile-geometry               prints the time spent sampling each feature at the end
+  --log-level <filter>             which messages to log, either a level (off, error, warn, info, debug or
+                                   trace) or a list like `warn,glt_mc_generator::generation=debug`
+                                   (default: $RUST_LOG, or info)";
+
+const GENERATE_OPTIONS: &str =
+  "  --output <path>                  the world directory to render into (default: $GLT_OUTPUT, or ./output)
+  --format <java|bedrock>          the edition of Minecraft to render the world for (default: java)
+  --serve <address>                serves rendering progress over HTTP, requires the `serve` feature
+  --render-workers <count>         renders with this many Python worker processes, each saving its own
+                                   region files, run with $PYTHON or python3 (default: 0, renders in-process)
+  --lookahead <chunks>             the number of chunks sampled ahead of the one being written when
+                                   rendering in-process (default: 16)
+  --max-section-palette <blocks>   warns about chunks with a section of more than this many distinct blocks
+                                   (default: 256)
+  --max-chunk-palette <blocks>     warns about chunks of more than this many distinct blocks (default: 1024)
+  --split-output <grid:NxM>        splits the world into N by M smaller worlds, rendered into `part_x_y`
+                                   directories in the output directory along with a `parts.json` manifest
+  --confirm-above <chunks>         asks before rendering a world of more than this many chunks, after printing
+                                   an estimate of how long it will take (default: 100000)
+  --yes, -y                        renders without asking, however large the world is
+  --keep <glob>                    keeps paths in the old world matching this pattern, like
+                                   `resources.zip` or `datapacks/my-*`, may be given more than once
+  --keep-datapacks                 keeps every datapack added to the old world, alongside the generator's own";
+
+const PREVIEW_OPTIONS: &str = "  --output <path>                  the heightmap image to write (default: ./preview.png)
+  --iso-preview <path>             also writes an isometric view of the city to the given image
+  --iso-scale <scale>              the size of each block in the isometric view (default: 1)
+  --stats <every:N|random:N>       prints how many of each block every kind of feature placed, sampling
+                                   every block of every Nth chunk or N random positions";
+
+const VALIDATE_OPTIONS: &str =
+  "  --samples <count>                the number of positions to check, half of them within the city
+                                   (default: 100000)";
+
+const EXPORT_USAGE: &str = "\
+usage: glt-mc-generator export <kind> [options]
+
+kinds:
+  datapack    the datapack giving the world its height and world border, as rendered worlds include";
+
+const EXPORT_DATAPACK_OPTIONS: &str =
+  "  --output <path>                  the datapack to write (default: ./glt-mc-world-base.zip)";
+
+const EXPLORE_KEYS: &str = "  n, p                             shows the next or previous seed
+  g                                jumps to a typed seed
+  enter                            renders the seed being shown, with the given options
+  q                                quits without rendering anything";
+
+const GOLDENS_USAGE: &str = "\
+usage: glt-mc-generator goldens [options]
+
+options:
+  --file <path>                    the golden sample file to check against (default: ./goldens.json)
+  --regenerate-goldens             rewrites the golden sample file from the current generator, for when a
+                                   change to what is generated is intentional";
+
+const INSPECT_USAGE: &str = "\
+usage: glt-mc-generator inspect <command> <world>
+
+commands:
+  info        prints the generator version, seed and options that the world was generated with";
+
+#[derive(Debug, Clone)]
+pub enum Command {
+  Generate(GenerateArgs),
+  Preview(PreviewArgs),
+  Inspect(InspectArgs),
+  Validate(ValidateArgs),
+  Export(ExportArgs),
+  Goldens(GoldensArgs),
+  /// Explores seeds starting from the given seed, then renders the chosen one with the given arguments
+  Explore(GenerateArgs),
+  /// Prints the given usage text and exits
+  Help(String)
+}
+
+impl Command {
+  /// Parses a command from the program's arguments, excluding the program name.
+  /// For compatibility, `glt-mc-generator <seed>` is treated as `glt-mc-generator generate --seed <seed>`,
+  /// and running without any arguments generates with the default options.
+  pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, UsageError> {
+    let mut args = Arguments::new(args, USAGE.to_owned());
+    match args.peek() {
+      Some("generate") => {
+        args.next();
+        GenerateArgs::parse(args, false)
+      },
+      Some("preview") => {
+        args.next();
+        PreviewArgs::parse(args)
+      },
+      Some("inspect") => {
+        args.next();
+        InspectArgs::parse(args)
+      },
+      Some("validate") => {
+        args.next();
+        ValidateArgs::parse(args)
+      },
+      Some("export") => {
+        args.next();
+        ExportArgs::parse(args)
+      },
+      Some("goldens") => {
+        args.next();
+        GoldensArgs::parse(args)
+      },
+      Some("explore") => {
+        args.next();
+        GenerateArgs::parse_explore(args)
+      },
+      Some("help" | "--help" | "-h") => Ok(Command::Help(USAGE.to_owned())),
+      Some(arg) if arg.starts_with('-') || arg.parse::<u64>().is_ok() => GenerateArgs::parse(args, true),
+      Some(arg) => {
+        let message = format!("unknown command `{}`", arg);
+        Err(args.error(message))
+      },
+      None => GenerateArgs::parse(args, true)
+    }
+  }
+}
+
+/// The arguments which determine what is generated and what gets logged, shared by every command
+#[derive(Debug, Clone, Default)]
+pub struct GenerationArgs {
+  /// The seed and options, which are only validated once every flag has been given
+  pub builder: GeneratorBuilder,
+  /// The filter given with `--log-level`, if any
+  pub log_filter: Option<LogFilter>
+}
+
+impl GenerationArgs {
+  /// Consumes the given flag (and its value) if it is one of the shared generation flags
+  fn parse_flag(&mut self, flag: &str, args: &mut Arguments) -> Result<bool, UsageError> {
+    let builder = &mut self.builder;
+    match flag {
+      "--config" => {
+        let path = args.value::<PathBuf>(flag)?;
+        let config = fs::read_to_string(&path)
+          .map_err(|err| args.error(format!("failed to read {}: {}", path.display(), err)))?;
+        builder.apply_config(&config)
+      },
+      "--seed" => builder.reseed(args.value(flag)?),
+      "--layers" => builder.layers(args.value(flag)?),
+      "--layer-spacing" => builder.layer_spacing(args.value(flag)?),
+      "--world-height" => {
+        let WorldHeight { min_z, max_z } = args.value(flag)?;
+        builder.world_height(min_z, max_z)
+      },
+      "--foundation" => builder.foundation(args.value(flag)?),
+      "--landmass-thickness" => builder.landmass_thickness(args.value(flag)?),
+      "--skyline" => builder.skyline(args.value(flag)?),
+      "--vary-shapes" => {
+        let shapes = builder.options().shapes.unwrap_or_default();
+        builder.shapes(Some(shapes))
+      },
+      "--max-elongation" => builder.max_elongation(args.value(flag)?),
+      "--max-lobe-depth" => builder.max_lobe_depth(args.value(flag)?),
+      "--max-lobes" => builder.max_lobes(args.value(flag)?),
+      "--symmetry" => builder.symmetry(args.value(flag)?),
+      "--erode-landmasses" => {
+        let erosion = builder.options().erosion.unwrap_or_default();
+        builder.erosion(Some(erosion))
+      },
+      "--erosion-iterations" => builder.erosion_iterations(args.value(flag)?),
+      "--erosion-neighbors" => builder.erosion_neighbors(args.value(flag)?),
+      "--slab-fill" => match args.value(flag)? {
+        // Keeps the weights given with `--slab-fill-weights`, whichever order they come in
+        SlabFillChoice::Random(_) if matches!(builder.options().slab_fill, SlabFillChoice::Random(_)) => builder,
+        slab_fill => builder.slab_fill(slab_fill)
+      },
+      "--slab-fill-weights" => builder.slab_fill_weights(args.value(flag)?),
+      "--caves" => builder.feature("caves", true),
+      "--pillar-clusters" => builder.feature("pillar-clusters", true),
+      "--baked-shadows" => builder.feature("baked-shadows", true),
+      "--details" => builder.feature("details", true),
+      "--scatter" => builder.feature("scatter", true),
+      "--pave" => {
+        let paving = builder.options().paving.unwrap_or_default();
+        builder.paving(Some(paving))
+      },
+      "--paving-mix" => builder.paving_mix(args.value(flag)?),
+      "--paving-patch-mix" => builder.paving_patch_mix(Some(args.value(flag)?)),
+      "--no-paving-patches" => builder.paving_patch_mix(None),
+      "--wavy-ocean" => builder.feature("wavy-ocean", true),
+      "--deep-ocean" => builder.feature("deep-ocean", true),
+      "--edge-beach" => builder.feature("edge-beach", true),
+      "--seabed-response" => builder.feature("seabed-response", true),
+      "--strata" => builder.feature("strata", true),
+      "--bubble-lifts" => builder.feature("bubble-lifts", true),
+      "--trench" => builder.trench_distance(Some(args.value(flag)?)),
+      "--wall" => {
+        let distance = builder.options().wall_distance.unwrap_or(DEFAULT_WALL_DISTANCE);
+        builder.wall_distance(Some(distance))
+      },
+      "--wall-distance" => builder.wall_distance(Some(args.value(flag)?)),
+      "--flat-bedrock" => builder.feature("flat-bedrock", true),
+      "--no-weathering" => builder.feature("weathering", false),
+      "--altitude-gradient" => builder.feature("altitude-gradient", true),
+      "--legacy-materials" => builder.feature("altitude-gradient", false),
+      "--theme" => {
+        let path = args.value::<PathBuf>(flag)?;
+        let theme = fs::read_to_string(&path)
+          .map_err(|err| args.error(format!("failed to read {}: {}", path.display(), err)))?;
+        let name = path.file_stem().map_or_else(|| path.to_string_lossy(), |stem| stem.to_string_lossy());
+        builder.apply_theme(&name, &theme)
+      },
+      "--lighting" => builder.lighting(args.value(flag)?),
+      "--collisions" => builder.collisions(args.value(flag)?),
+      "--center" => builder.center(true),
+      "--edge-style" => match args.value(flag)? {
+        // Keeps the width given with `--fade-width`, whichever order they come in
+        EdgeStyle::Fade(_) if matches!(builder.options().edge_style, EdgeStyle::Fade(_)) => builder,
+        edge_style => builder.edge_style(edge_style)
+      },
+      "--fade-width" => builder.edge_style(EdgeStyle::Fade(args.value(flag)?)),
+      "--no-cache" => builder.cache(false),
+      "--clear-cache" => builder.clear_cache(true),
+      "--profile-geometry" => builder.profile_geometry(true),
+      "--log-level" => {
+        self.log_filter = Some(args.value(flag)?);
+        builder
+      },
+      _ => return Ok(false)
+    };
+
+    Ok(true)
+  }
+
+  /// Rejects invalid configurations, listing everything wrong with them
+  fn check(&self, args: &Arguments) -> Result<(), UsageError> {
+    self.builder.validate().map_err(|err| args.error(err.to_string()))
+  }
+}
+
+/// The flags which reproduce what the given options generate, leaving out any which are already the default.
+/// Flags which don't change what is generated, like `--no-cache`, are always left out, as is `--theme`,
+/// since the theme file it names may have changed or be missing.
+pub fn generation_flags(options: &GeneratorOptions) -> Vec<String> {
+  let defaults = GeneratorOptions::default();
+  let mut flags = Vec::new();
+  if options.layer_count != defaults.layer_count {
+    flags.push(format!("--layers {}", options.layer_count));
+  };
+
+  if options.layer_spacing != defaults.layer_spacing {
+    flags.push(format!("--layer-spacing {}", options.layer_spacing));
+  };
+
+  if options.world_height != defaults.world_height {
+    flags.push(format!("--world-height {}", options.world_height));
+  };
+
+  if options.foundation == Foundation::Skirt {
+    flags.push("--foundation skirt".to_owned());
+  };
+
+  if options.landmass.thickness != defaults.landmass.thickness {
+    flags.push(format!("--landmass-thickness {}", options.landmass.thickness));
+  };
+
+  if options.landmass.skyline != defaults.landmass.skyline {
+    flags.push(format!("--skyline {}", options.landmass.skyline.name()));
+  };
+
+  if let Some(shapes) = options.shapes {
+    flags.push(format!(
+      "--max-elongation {} --max-lobe-depth {} --max-lobes {}",
+      shapes.max_elongation, shapes.max_lobe_depth, shapes.max_lobes
+    ));
+  };
+
+  if options.symmetry != defaults.symmetry {
+    flags.push(format!("--symmetry {}", options.symmetry.name()));
+  };
+
+  if let Some(erosion) = options.erosion {
+    flags.push(format!(
+      "--erosion-iterations {} --erosion-neighbors {}",
+      erosion.iterations, erosion.min_neighbors
+    ));
+  };
+
+  if options.slab_fill != defaults.slab_fill {
+    flags.push(match options.slab_fill {
+      SlabFillChoice::Fixed(fill) => format!("--slab-fill {}", fill),
+      SlabFillChoice::Random(weights) => format!("--slab-fill-weights {}", weights)
+    });
+  };
+
+  if let Some(paving) = options.paving {
+    flags.push(match paving.patch_mix {
+      Some(patch_mix) => format!("--paving-mix {} --paving-patch-mix {}", paving.mix, patch_mix),
+      None => format!("--paving-mix {} --no-paving-patches", paving.mix)
+    });
+  };
+
+  let switches = [
+    (options.caves, "--caves"),
+    (options.pillar_clusters, "--pillar-clusters"),
+    (options.baked_shadows, "--baked-shadows"),
+    (options.details, "--details"),
+    (options.scatter, "--scatter"),
+    (options.wavy_ocean, "--wavy-ocean"),
+    (options.deep_ocean, "--deep-ocean"),
+    (options.edge_beach, "--edge-beach"),
+    (options.seabed_response, "--seabed-response"),
+    (options.strata, "--strata"),
+    (options.bubble_lifts, "--bubble-lifts"),
+    (options.center, "--center")
+  ];
+  flags.extend(switches.into_iter().filter(|&(enabled, _)| enabled).map(|(_, flag)| flag.to_owned()));
+
+  if let Some(distance) = options.trench_distance {
+    flags.push(format!("--trench {}", distance));
+  };
+
+  if let Some(distance) = options.wall_distance {
+    flags.push(format!("--wall-distance {}", distance));
+  };
+
+  if let EdgeStyle::Fade(width) = options.edge_style {
+    flags.push(format!("--fade-width {}", width));
+  };
+
+  if options.flat_bedrock {
+    flags.push("--flat-bedrock".to_owned());
+  };
+
+  if !options.weathering {
+    flags.push("--no-weathering".to_owned());
+  };
+
+  if !options.altitude_gradient {
+    flags.push("--legacy-materials".to_owned());
+  };
+
+  if options.lighting != defaults.lighting {
+    flags.push(format!("--lighting {}", options.lighting.name()));
+  };
+
+  if options.collisions != defaults.collisions {
+    flags.push(format!("--collisions {}", options.collisions.name()));
+  };
+
+  flags
+}
+
+/// Parses a list of generation flags, like those returned by `generation_flags`, into a builder with the options they
+/// describe. The options are validated, but the builder's seed is left at 0 for the caller to replace.
+pub fn parse_generation_flags(flags: &str) -> Result<GeneratorBuilder, String> {
+  let mut args = Arguments::new(flags.split_whitespace().map(str::to_owned), String::new());
+  let mut generation = GenerationArgs::default();
+  while let Some(arg) = args.next() {
+    if !generation.parse_flag(&arg, &mut args).map_err(|err| err.message)? {
+      return Err(format!("unknown generation flag `{}`", arg));
+    };
+  };
+
+  generation.builder.validate().map_err(|err| err.to_string())?;
+  Ok(generation.builder)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GenerateArgs {
+  pub generation: GenerationArgs,
+  /// The directory of the world to render into, replacing anything already there
+  pub output: Option<PathBuf>,
+  /// The address to serve rendering progress on, if any
+  pub serve: Option<SocketAddr>,
+  /// The number of Python worker processes to render with, or 0 to render in-process
+  pub render_workers: usize,
+  /// The number of chunks sampled ahead of the one being written when rendering in-process, if not the default
+  pub lookahead: Option<NonZeroUsize>,
+  /// The palette sizes past which a rendered chunk is reported as oversized
+  pub limits: ChunkLimits,
+  /// The edition of Minecraft to render the world for
+  pub format: WorldFormat,
+  /// Whether the seed was given using the deprecated `glt-mc-generator <seed>` form
+  pub legacy_seed: bool,
+  /// The grid to split the world into, if it is split into parts
+  pub split_output: Option<SplitGrid>,
+  /// The number of chunks above which rendering waits for confirmation, if not the default
+  pub confirm_above: Option<u64>,
+  /// Renders without waiting for confirmation, however many chunks there are
+  pub yes: bool,
+  /// The paths in the old world to move into the new one, rather than removing them
+  pub keep: Vec<KeepPattern>
+}
+
+impl GenerateArgs {
+  /// In legacy mode, a bare seed may be given in place of `--seed <seed>`
+  fn parse(mut args: Arguments, legacy: bool) -> Result<Command, UsageError> {
+    args.usage = format!(
+      "usage: glt-mc-generator generate [options]\n\noptions:\n{}\n{}",
+      GENERATION_OPTIONS, GENERATE_OPTIONS
+    );
+
+    let mut generate = GenerateArgs::default();
+    while let Some(arg) = args.next() {
+      if generate.parse_flag(&arg, &mut args)? { continue };
+      match arg.as_str() {
+        "--help" | "-h" => return Ok(Command::Help(args.usage)),
+        _ if legacy && !arg.starts_with('-') => {
+          generate.generation.builder.reseed(parse_value("seed", &arg).map_err(|message| args.error(message))?);
+          generate.legacy_seed = true;
+        },
+        _ => return Err(args.error(format!("unknown argument `{}`", arg)))
+      };
+    };
+
+    generate.check(&args)?;
+    Ok(Command::Generate(generate))
+  }
+
+  /// Takes the same options as `generate`, with `--seed` giving the first seed to show
+  fn parse_explore(mut args: Arguments) -> Result<Command, UsageError> {
+    args.usage = format!(
+      "usage: glt-mc-generator explore [options]\n\nkeys:\n{}\n\noptions:\n{}\n{}",
+      EXPLORE_KEYS, GENERATION_OPTIONS, GENERATE_OPTIONS
+    );
+
+    let mut generate = GenerateArgs::default();
+    while let Some(arg) = args.next() {
+      if generate.parse_flag(&arg, &mut args)? { continue };
+      match arg.as_str() {
+        "--help" | "-h" => return Ok(Command::Help(args.usage)),
+        _ => return Err(args.error(format!("unknown argument `{}`", arg)))
+      };
+    };
+
+    generate.check(&args)?;
+    Ok(Command::Explore(generate))
+  }
+
+  /// Consumes the given flag (and its value) if it is one of the flags taken by every command which renders a world
+  fn parse_flag(&mut self, flag: &str, args: &mut Arguments) -> Result<bool, UsageError> {
+    if self.generation.parse_flag(flag, args)? { return Ok(true) };
+    match flag {
+      "--output" => self.output = Some(args.value(flag)?),
+      "--serve" => self.serve = Some(args.value(flag)?),
+      "--render-workers" => self.render_workers = args.value(flag)?,
+      "--lookahead" => self.lookahead = Some(args.value(flag)?),
+      "--max-section-palette" => self.limits.max_section_palette = args.value(flag)?,
+      "--max-chunk-palette" => self.limits.max_chunk_palette = args.value(flag)?,
+      "--format" => self.format = args.value(flag)?,
+      "--split-output" => self.split_output = Some(args.value(flag)?),
+      "--confirm-above" => self.confirm_above = Some(args.value(flag)?),
+      "--yes" | "-y" => self.yes = true,
+      "--keep" => self.keep.push(args.value(flag)?),
+      "--keep-datapacks" => self.keep.push(KeepPattern::datapacks()),
+      _ => return Ok(false)
+    };
+
+    Ok(true)
+  }
+
+  /// Rejects invalid configurations, and combinations of flags which can't be rendered
+  fn check(&self, args: &Arguments) -> Result<(), UsageError> {
+    self.generation.check(args)?;
+    // A LevelDB database can only be opened by one process at a time
+    if self.format == WorldFormat::Bedrock && self.render_workers > 0 {
+      return Err(args.error("`--render-workers` can only be used with java worlds"));
+    };
+
+    // The preview server shows the progress of a single world
+    if self.split_output.is_some() && self.serve.is_some() {
+      return Err(args.error("`--serve` can't be used with `--split-output`"));
+    };
+
+    // Split worlds are rendered into a directory which is cleared as a whole
+    if self.split_output.is_some() && !self.keep.is_empty() {
+      return Err(args.error("`--keep` can't be used with `--split-output`"));
+    };
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum InspectArgs {
+  /// Prints the information the generator recorded about the world at the given path
+  Info(PathBuf)
+}
+
+impl InspectArgs {
+  fn parse(mut args: Arguments) -> Result<Command, UsageError> {
+    args.usage = INSPECT_USAGE.to_owned();
+    let inspect = match args.next().as_deref() {
+      Some("info") => match args.next() {
+        Some(arg) if arg == "--help" || arg == "-h" => return Ok(Command::Help(args.usage)),
+        Some(world) => InspectArgs::Info(PathBuf::from(world)),
+        None => return Err(args.error("`inspect info` requires the path of a world"))
+      },
+      Some("--help" | "-h") => return Ok(Command::Help(args.usage)),
+      Some(arg) => return Err(args.error(format!("unknown inspect command `{}`", arg))),
+      None => return Err(args.error("`inspect` requires a command"))
+    };
+
+    if let Some(arg) = args.next() {
+      return Err(args.error(format!("unknown argument `{}`", arg)));
+    };
+
+    Ok(Command::Inspect(inspect))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidateArgs {
+  pub generation: GenerationArgs,
+  /// The number of positions to check
+  pub samples: usize
+}
+
+impl ValidateArgs {
+  fn parse(mut args: Arguments) -> Result<Command, UsageError> {
+    args.usage = format!(
+      "usage: glt-mc-generator validate [options]\n\noptions:\n{}\n{}",
+      GENERATION_OPTIONS, VALIDATE_OPTIONS
+    );
+
+    let mut generation = GenerationArgs::default();
+    let mut samples = 100_000;
+    while let Some(arg) = args.next() {
+      if generation.parse_flag(&arg, &mut args)? { continue };
+      match arg.as_str() {
+        "--samples" => samples = args.value(&arg)?,
+        "--help" | "-h" => return Ok(Command::Help(args.usage)),
+        _ => return Err(args.error(format!("unknown argument `{}`", arg)))
+      };
+    };
+
+    generation.check(&args)?;
+    Ok(Command::Validate(ValidateArgs { generation, samples }))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExportArgs {
+  /// Writes the datapack that rendered worlds include to the given path
+  Datapack {
+    generation: GenerationArgs,
+    output: PathBuf
+  }
+}
+
+impl ExportArgs {
+  fn parse(mut args: Arguments) -> Result<Command, UsageError> {
+    args.usage = EXPORT_USAGE.to_owned();
+    match args.next().as_deref() {
+      Some("datapack") => (),
+      Some("--help" | "-h") => return Ok(Command::Help(args.usage)),
+      Some(arg) => return Err(args.error(format!("unknown kind of export `{}`", arg))),
+      None => return Err(args.error("`export` requires the kind of file to write"))
+    };
+
+    args.usage = format!(
+      "usage: glt-mc-generator export datapack [options]\n\noptions:\n{}\n{}",
+      GENERATION_OPTIONS, EXPORT_DATAPACK_OPTIONS
+    );
+
+    let mut generation = GenerationArgs::default();
+    let mut output = PathBuf::from("./glt-mc-world-base.zip");
+    while let Some(arg) = args.next() {
+      if generation.parse_flag(&arg, &mut args)? { continue };
+      match arg.as_str() {
+        "--output" => output = args.value(&arg)?,
+        "--help" | "-h" => return Ok(Command::Help(args.usage)),
+        _ => return Err(args.error(format!("unknown argument `{}`", arg)))
+      };
+    };
+
+    generation.check(&args)?;
+    Ok(Command::Export(ExportArgs::Datapack { generation, output }))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct GoldensArgs {
+  /// The path of the golden sample file
+  pub file: PathBuf,
+  /// Whether to rewrite the file instead of checking against it
+  pub regenerate: bool
+}
+
+impl GoldensArgs {
+  fn parse(mut args: Arguments) -> Result<Command, UsageError> {
+    args.usage = GOLDENS_USAGE.to_owned();
+    let mut file = PathBuf::from("./goldens.json");
+    let mut regenerate = false;
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--file" => file = args.value(&arg)?,
+        "--regenerate-goldens" => regenerate = true,
+        "--help" | "-h" => return Ok(Command::Help(args.usage)),
+        _ => return Err(args.error(format!("unknown argument `{}`", arg)))
+      };
+    };
+
+    Ok(Command::Goldens(GoldensArgs { file, regenerate }))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreviewArgs {
+  pub generation: GenerationArgs,
+  /// The path of the heightmap image to write
+  pub output: PathBuf,
+  /// The path of the isometric image to write, if any
+  pub iso_output: Option<PathBuf>,
+  pub iso_scale: u32,
+  /// How to sample the world for block statistics, if they are printed
+  pub stats: Option<StatsSampling>
+}
+
+impl PreviewArgs {
+  fn parse(mut args: Arguments) -> Result<Command, UsageError> {
+    args.usage = format!(
+      "usage: glt-mc-generator preview [options]\n\noptions:\n{}\n{}",
+      GENERATION_OPTIONS, PREVIEW_OPTIONS
+    );
+
+    let mut generation = GenerationArgs::default();
+    let mut output = PathBuf::from("./preview.png");
+    let mut iso_output = None;
+    let mut iso_scale = 1;
+    let mut stats = None;
+    while let Some(arg) = args.next() {
+      if generation.parse_flag(&arg, &mut args)? { continue };
+      match arg.as_str() {
+        "--output" => output = args.value(&arg)?,
+        "--iso-preview" => iso_output = Some(args.value(&arg)?),
+        "--iso-scale" => {
+          iso_scale = args.value(&arg)?;
+          if iso_scale == 0 {
+            return Err(args.error("`--iso-scale` must be at least 1"));
+          };
+        },
+        "--stats" => stats = Some(args.value(&arg)?),
+        "--help" | "-h" => return Ok(Command::Help(args.usage)),
+        _ => return Err(args.error(format!("unknown argument `{}`", arg)))
+      };
+    };
+
+    generation.check(&args)?;
+    Ok(Command::Preview(PreviewArgs { generation, output, iso_output, iso_scale, stats }))
+  }
+}
+
+impl FromStr for WorldFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "java" => Ok(WorldFormat::Java),
+      "bedrock" => Ok(WorldFormat::Bedrock),
+      _ => Err("expected `java` or `bedrock`".to_owned())
+    }
+  }
+}
+
+impl FromStr for SplitGrid {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    const EXPECTED: &str = "expected `grid:NxM`, where N and M are at least 1";
+    let (columns, rows) = s.strip_prefix("grid:").and_then(|size| size.split_once('x')).ok_or(EXPECTED)?;
+    match (columns.parse::<u32>(), rows.parse::<u32>()) {
+      (Ok(columns @ 1..), Ok(rows @ 1..)) => Ok(SplitGrid { columns, rows }),
+      _ => Err(EXPECTED.to_owned())
+    }
+  }
+}
+
+impl FromStr for StatsSampling {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    const EXPECTED: &str = "expected `every:N` or `random:N`, where N is at least 1";
+    let (sampling, count) = s.split_once(':').ok_or(EXPECTED)?;
+    match (sampling, count.parse::<usize>()) {
+      ("every", Ok(n @ 1..)) => Ok(StatsSampling::EveryNthChunk(n)),
+      ("random", Ok(count @ 1..)) => Ok(StatsSampling::Random(count)),
+      _ => Err(EXPECTED.to_owned())
+    }
+  }
+}
+
+impl FromStr for EdgeStyle {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "hard" => Ok(EdgeStyle::Hard),
+      "fade" => Ok(EdgeStyle::Fade(DEFAULT_FADE_WIDTH)),
+      _ => Err("expected `hard` or `fade`".to_owned())
+    }
+  }
+}
+
+impl FromStr for SlabFillChoice {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    const EXPECTED: &str = concat!(
+      "expected `checkered[:size]`, `solid`, `hollow`, `columns[:spacing]`, `noise[:threshold]` or `random`, ",
+      "with a whole size and spacing and a decimal threshold"
+    );
+    let (kind, value) = match s.split_once(':') {
+      Some((kind, value)) => (kind, Some(value)),
+      None => (s, None)
+    };
+
+    let fill = match (kind, value) {
+      ("random", None) => return Ok(SlabFillChoice::Random(SlabFillWeights::default())),
+      ("checkered", None) => SlabFill::DEFAULT,
+      ("checkered", Some(size)) => SlabFill::Checkered { size: size.parse().map_err(|_| EXPECTED)? },
+      ("solid", None) => SlabFill::Solid,
+      ("hollow", None) => SlabFill::Hollow,
+      ("columns", None) => SlabFill::Columns { spacing: 8 },
+      ("columns", Some(spacing)) => SlabFill::Columns { spacing: spacing.parse().map_err(|_| EXPECTED)? },
+      ("noise", None) => SlabFill::Noise { threshold: 0.5 },
+      ("noise", Some(threshold)) => SlabFill::Noise { threshold: threshold.parse().map_err(|_| EXPECTED)? },
+      _ => return Err(EXPECTED.to_owned())
+    };
+
+    Ok(SlabFillChoice::Fixed(fill))
+  }
+}
+
+impl FromStr for SlabFillWeights {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    const EXPECTED: &str = "expected five weights separated by commas, for checkered, solid, hollow, columns and noise";
+    let weights = s.split(',')
+      .map(|weight| weight.trim().parse::<u32>())
+      .collect::<Result<Vec<u32>, _>>()
+      .map_err(|_| EXPECTED)?;
+    let weights = <[u32; 5]>::try_from(weights).map_err(|_| EXPECTED)?;
+    Ok(SlabFillWeights::from_array(weights))
+  }
+}
+
+impl FromStr for PavingMix {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    const EXPECTED: &str = "expected three weights separated by commas, for own material, trim and worn trim";
+    let weights = s.split(',')
+      .map(|weight| weight.trim().parse::<u32>())
+      .collect::<Result<Vec<u32>, _>>()
+      .map_err(|_| EXPECTED)?;
+    let weights = <[u32; 3]>::try_from(weights).map_err(|_| EXPECTED)?;
+    Ok(PavingMix::from_array(weights))
+  }
+}
+
+impl FromStr for Foundation {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "pillars" => Ok(Foundation::Pillars),
+      "skirt" => Ok(Foundation::Skirt),
+      _ => Err("expected `skirt` or `pillars`".to_owned())
+    }
+  }
+}
+
+impl FromStr for Lighting {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "none" => Ok(Lighting::None),
+      "minimal" => Ok(Lighting::Minimal),
+      "full" => Ok(Lighting::Full),
+      _ => Err("expected `none`, `minimal` or `full`".to_owned())
+    }
+  }
+}
+
+impl FromStr for CollisionPolicy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "remove" => Ok(CollisionPolicy::Remove),
+      "ignore" => Ok(CollisionPolicy::Ignore),
+      _ => Err("expected `remove` or `ignore`".to_owned())
+    }
+  }
+}
+
+impl FromStr for WorldHeight {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    const EXPECTED: &str = "expected `min,max`, the bottom of the world and its build limit";
+    let (min_z, max_z) = s.split_once(',').ok_or(EXPECTED)?;
+    match (min_z.trim().parse(), max_z.trim().parse()) {
+      (Ok(min_z), Ok(max_z)) => Ok(WorldHeight { min_z, max_z }),
+      _ => Err(EXPECTED.to_owned())
+    }
+  }
+}
+
+impl FromStr for Symmetry {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "none" => Ok(Symmetry::None),
+      "mirror" => Ok(Symmetry::Mirror),
+      "four-way" => Ok(Symmetry::FourWay),
+      _ => Err("expected `none`, `mirror` or `four-way`".to_owned())
+    }
+  }
+}
+
+impl FromStr for Skyline {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    match s {
+      "uniform" => Ok(Skyline::Uniform),
+      "organic" => Ok(Skyline::Organic),
+      "towers" => Ok(Skyline::Towers),
+      _ => Err("expected `uniform`, `organic` or `towers`".to_owned())
+    }
+  }
+}
+
+
+
+/// An invalid command line, along with the usage text of the command it was meant for
+#[derive(Debug, Clone)]
+pub struct UsageError {
+  message: String,
+  usage: String
+}
+
+impl fmt::Display for UsageError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "error: {}\n\n{}", self.message, self.usage)
+  }
+}
+
+struct Arguments {
+  args: std::iter::Peekable<std::vec::IntoIter<String>>,
+  usage: String
+}
+
+impl Arguments {
+  fn new(args: impl IntoIterator<Item = String>, usage: String) -> Self {
+    let args = args.into_iter().collect::<Vec<String>>().into_iter().peekable();
+    Arguments { args, usage }
+  }
+
+  fn peek(&mut self) -> Option<&str> {
+    self.args.peek().map(String::as_str)
+  }
+
+  fn next(&mut self) -> Option<String> {
+    self.args.next()
+  }
+
+  /// Takes and parses the value following the given flag
+  fn value<T>(&mut self, flag: &str) -> Result<T, UsageError>
+  where T: FromStr, T::Err: fmt::Display {
+    match self.args.next() {
+      Some(value) => parse_value(flag, &value).map_err(|message| self.error(message)),
+      None => Err(self.error(format!("`{}` requires a value", flag)))
+    }
+  }
+
+  fn error(&self, message: impl Into<String>) -> UsageError {
+    UsageError { message: message.into(), usage: self.usage.clone() }
+  }
+}
+
+fn parse_value<T>(name: &str, value: &str) -> Result<T, String>
+where T: FromStr, T::Err: fmt::Display {
+  value.parse::<T>().map_err(|err| format!("invalid value `{}` for `{}`: {}", value, name, err))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &str) -> Result<Command, UsageError> {
+    Command::parse(args.split_whitespace().map(str::to_owned))
+  }
+
+  fn parse_generate(args: &str) -> GenerateArgs {
+    match parse(args) {
+      Ok(Command::Generate(generate)) => generate,
+      other => panic!("`{}` parsed as {:?}", args, other)
+    }
+  }
+
+  fn parse_preview(args: &str) -> PreviewArgs {
+    match parse(args) {
+      Ok(Command::Preview(preview)) => preview,
+      other => panic!("`{}` parsed as {:?}", args, other)
+    }
+  }
+
+  fn parse_goldens(args: &str) -> GoldensArgs {
+    match parse(args) {
+      Ok(Command::Goldens(goldens)) => goldens,
+      other => panic!("`{}` parsed as {:?}", args, other)
+    }
+  }
+
+  fn parse_error(args: &str) -> UsageError {
+    match parse(args) {
+      Err(err) => err,
+      Ok(command) => panic!("`{}` parsed as {:?}", args, command)
+    }
+  }
+
+  #[test]
+  fn each_command_is_parsed_into_its_arguments() {
+    let generate = parse_generate("generate --seed 5 --layers 2 --output out --format bedrock --yes --keep icon.png");
+    assert_eq!(generate.generation.builder.seed(), 5);
+    assert_eq!(generate.generation.builder.options().layer_count, 2);
+    assert_eq!(generate.output, Some(PathBuf::from("out")));
+    assert_eq!(generate.format, WorldFormat::Bedrock);
+    assert!(generate.yes && !generate.legacy_seed);
+    assert_eq!(generate.keep.len(), 1);
+
+    let generate = parse_generate("generate -y --split-output grid:2x3 --confirm-above 10 --render-workers 4");
+    assert_eq!(generate.split_output, Some(SplitGrid { columns: 2, rows: 3 }));
+    assert_eq!((generate.confirm_above, generate.render_workers, generate.yes), (Some(10), 4, true));
+
+    let preview = parse_preview(
+      "preview --seed 7 --output map.png --iso-preview iso.png --iso-scale 2 --stats every:4"
+    );
+    assert_eq!(preview.generation.builder.seed(), 7);
+    assert_eq!(preview.output, PathBuf::from("map.png"));
+    assert_eq!(preview.iso_output, Some(PathBuf::from("iso.png")));
+    assert_eq!(preview.iso_scale, 2);
+    assert_eq!(preview.stats, Some(StatsSampling::EveryNthChunk(4)));
+
+    match parse("inspect info worlds/city") {
+      Ok(Command::Inspect(InspectArgs::Info(path))) => assert_eq!(path, PathBuf::from("worlds/city")),
+      other => panic!("parsed as {:?}", other)
+    };
+
+    let goldens = parse_goldens("goldens --file other.json --regenerate-goldens");
+    assert_eq!(goldens.file, PathBuf::from("other.json"));
+    assert!(goldens.regenerate);
+
+    match parse("validate --seed 3 --samples 500") {
+      Ok(Command::Validate(validate)) => {
+        assert_eq!(validate.generation.builder.seed(), 3);
+        assert_eq!(validate.samples, 500);
+      },
+      other => panic!("parsed as {:?}", other)
+    };
+
+    match parse("export datapack --seed 4 --world-height -64,320 --output pack.zip") {
+      Ok(Command::Export(ExportArgs::Datapack { generation, output })) => {
+        assert_eq!(generation.builder.seed(), 4);
+        assert_eq!(generation.builder.options().world_height, WorldHeight { min_z: -64, max_z: 320 });
+        assert_eq!(output, PathBuf::from("pack.zip"));
+      },
+      other => panic!("parsed as {:?}", other)
+    };
+
+    match parse("explore --seed 9 --layers 1") {
+      Ok(Command::Explore(explore)) => assert_eq!(explore.generation.builder.seed(), 9),
+      other => panic!("parsed as {:?}", other)
+    };
+  }
+
+  #[test]
+  fn omitted_options_are_left_at_their_defaults() {
+    let defaults = GeneratorOptions::default();
+    for args in ["", "generate"] {
+      let generate = parse_generate(args);
+      assert_eq!(generate.generation.builder.seed(), 0);
+      assert_eq!(generate.generation.builder.options().layer_count, defaults.layer_count);
+      assert_eq!(generate.output, None);
+      assert_eq!(generate.format, WorldFormat::Java);
+      assert_eq!(generate.limits, ChunkLimits::default());
+      assert_eq!((generate.render_workers, generate.lookahead, generate.confirm_above), (0, None, None));
+      assert!(!generate.yes && !generate.legacy_seed && generate.keep.is_empty());
+      assert!(generate.generation.log_filter.is_none());
+    };
+
+    let preview = parse_preview("preview");
+    assert_eq!(preview.output, PathBuf::from("./preview.png"));
+    assert_eq!((preview.iso_output, preview.iso_scale, preview.stats), (None, 1, None));
+
+    let goldens = parse_goldens("goldens");
+    assert_eq!(goldens.file, PathBuf::from("./goldens.json"));
+    assert!(!goldens.regenerate);
+
+    match parse("validate") {
+      Ok(Command::Validate(validate)) => assert_eq!(validate.samples, 100_000),
+      other => panic!("parsed as {:?}", other)
+    };
+
+    match parse("export datapack") {
+      Ok(Command::Export(ExportArgs::Datapack { output, .. })) => {
+        assert_eq!(output, PathBuf::from("./glt-mc-world-base.zip"));
+      },
+      other => panic!("parsed as {:?}", other)
+    };
+  }
+
+  #[test]
+  fn a_bare_seed_generates_with_that_seed() {
+    for args in ["42", "42 --layers 1", "--layers 1 42"] {
+      let generate = parse_generate(args);
+      assert_eq!(generate.generation.builder.seed(), 42, "`{}`", args);
+      assert!(generate.legacy_seed, "`{}`", args);
+    };
+
+    // Only without a command
+    assert!(parse_error("generate 42").message.contains("unknown argument `42`"));
+  }
+
+  #[test]
+  fn help_is_given_for_each_command() {
+    for (args, usage) in [
+      ("help", "usage: glt-mc-generator <command>"),
+      ("--help", "usage: glt-mc-generator <command>"),
+      ("generate --help", "usage: glt-mc-generator generate"),
+      ("preview -h", "usage: glt-mc-generator preview"),
+      ("inspect --help", "usage: glt-mc-generator inspect"),
+      ("goldens --help", "usage: glt-mc-generator goldens"),
+      ("validate --help", "usage: glt-mc-generator validate"),
+      ("export --help", "usage: glt-mc-generator export <kind>"),
+      ("export datapack --help", "usage: glt-mc-generator export datapack"),
+      ("explore --help", "usage: glt-mc-generator explore")
+    ] {
+      match parse(args) {
+        Ok(Command::Help(text)) => assert!(text.starts_with(usage), "`{}` gave the wrong usage", args),
+        other => panic!("`{}` parsed as {:?}", args, other)
+      };
+    };
+  }
+
+  #[test]
+  fn unknown_arguments_are_rejected_with_the_usage_of_their_command() {
+    for (args, message, usage) in [
+      ("frobnicate", "unknown command `frobnicate`", "usage: glt-mc-generator <command>"),
+      ("generate --bogus", "unknown argument `--bogus`", "usage: glt-mc-generator generate"),
+      ("--bogus", "unknown argument `--bogus`", "usage: glt-mc-generator generate"),
+      // Flags of one command aren't taken by the others
+      ("preview --yes", "unknown argument `--yes`", "usage: glt-mc-generator preview"),
+      ("generate --iso-scale 2", "unknown argument `--iso-scale`", "usage: glt-mc-generator generate"),
+      ("goldens --seed 1", "unknown argument `--seed`", "usage: glt-mc-generator goldens"),
+      ("inspect diff a b", "unknown inspect command `diff`", "usage: glt-mc-generator inspect"),
+      ("inspect info a b", "unknown argument `b`", "usage: glt-mc-generator inspect"),
+      ("inspect", "`inspect` requires a command", "usage: glt-mc-generator inspect"),
+      ("inspect info", "`inspect info` requires the path of a world", "usage: glt-mc-generator inspect"),
+      ("validate --output x", "unknown argument `--output`", "usage: glt-mc-generator validate"),
+      ("export", "`export` requires the kind of file to write", "usage: glt-mc-generator export <kind>"),
+      ("export schematic", "unknown kind of export `schematic`", "usage: glt-mc-generator export <kind>"),
+      ("export datapack --yes", "unknown argument `--yes`", "usage: glt-mc-generator export datapack")
+    ] {
+      let err = parse_error(args);
+      assert_eq!(err.message, message, "`{}`", args);
+      assert!(err.usage.starts_with(usage), "`{}` gave the wrong usage", args);
+    };
+  }
+
+  #[test]
+  fn missing_and_invalid_values_are_rejected() {
+    for (args, message) in [
+      ("generate --seed", "`--seed` requires a value"),
+      ("generate --layers 2 --output", "`--output` requires a value"),
+      ("preview --stats", "`--stats` requires a value"),
+      ("goldens --file", "`--file` requires a value"),
+      ("validate --samples", "`--samples` requires a value"),
+      ("validate --samples many", "invalid value `many` for `--samples`"),
+      ("generate --seed x", "invalid value `x` for `--seed`"),
+      ("generate --format nether", "invalid value `nether` for `--format`: expected `java` or `bedrock`"),
+      ("preview --iso-scale 0", "`--iso-scale` must be at least 1"),
+      ("generate --format bedrock --render-workers 2", "`--render-workers` can only be used with java worlds")
+    ] {
+      let err = parse_error(args);
+      assert!(err.message.starts_with(message), "`{}` gave `{}`", args, err.message);
+    };
+  }
+}
@@ -0,0 +1,216 @@
+use crate::generation::BoundingBox;
+use crate::utility::crc32;
+use crate::world_coords::column_to_minecraft;
+use crate::WorldHeight;
+
+
+
+const PACK_MCMETA: &str = include_str!("../world-template/datapack/pack.mcmeta");
+const OVERWORLD_DIMENSION_TYPE: &str = include_str!("../world-template/datapack/overworld.json");
+
+/// The file name of the datapack written into every generated world, which is overwritten each time it is rendered
+pub const WORLD_DATAPACK_FILE_NAME: &str = "glt-mc-world-base.zip";
+/// The start of the file name of any copy of that datapack, as earlier versions might have named it
+pub const WORLD_DATAPACK_PREFIX: &str = "glt-mc-world-base";
+
+/// The distance left between the generated area and the world border on each side
+const WORLD_BORDER_MARGIN: i32 = 32;
+
+/// Builds the datapack included with every generated world, as a zip archive.
+/// It gives the overworld the height of the generated world, and sets a world border
+/// enclosing the given bounding box whenever the world is loaded.
+pub fn build_world_datapack(bounding_box: BoundingBox, world_height: WorldHeight) -> Vec<u8> {
+  let mut archive = ZipWriter::new();
+  archive.add_file("pack.mcmeta", PACK_MCMETA.as_bytes());
+  let dimension_type = overworld_dimension_type(world_height);
+  archive.add_file("data/minecraft/dimension_type/overworld.json", dimension_type.as_bytes());
+  archive.add_file("data/minecraft/tags/functions/load.json", br#"{"values":["glt:world_border"]}"#);
+  archive.add_file("data/glt/functions/world_border.mcfunction", world_border_function(bounding_box).as_bytes());
+  archive.finish()
+}
+
+/// The template's overworld dimension type, spanning the given height instead of the default world's
+fn overworld_dimension_type(world_height: WorldHeight) -> String {
+  let default = WorldHeight::default();
+  let height = world_height.height();
+  OVERWORLD_DIMENSION_TYPE
+    .replace(&format!("\"min_y\": {},", default.min_z), &format!("\"min_y\": {},", world_height.min_z))
+    .replace(&format!("\"height\": {},", default.height()), &format!("\"height\": {},", height))
+    .replace(&format!("\"logical_height\": {},", default.height()), &format!("\"logical_height\": {},", height))
+}
+
+/// Commands centering a square world border on the bounding box, just large enough to contain it.
+fn world_border_function(bounding_box: BoundingBox) -> String {
+  let size = bounding_box.max - bounding_box.min + 1;
+  let diameter = size.x.max(size.y) + WORLD_BORDER_MARGIN * 2;
+  let (min_x, min_z) = column_to_minecraft(bounding_box.min.truncate());
+  let (max_x, max_z) = column_to_minecraft(bounding_box.max.truncate());
+  let center_x = (min_x + max_x + 1) as f64 / 2.0;
+  let center_z = (min_z + max_z + 1) as f64 / 2.0;
+  format!("worldborder center {} {}\nworldborder set {}\n", center_x, center_z, diameter)
+}
+
+
+
+/// Version 1.0 of the zip format, which is all that stored files need
+const VERSION: u16 = 10;
+/// 1980-01-01, the earliest date that can be represented
+const DOS_DATE: u16 = 0x21;
+
+/// Writes a zip archive with every file stored uncompressed
+struct ZipWriter {
+  out: Vec<u8>,
+  central_directory: Vec<u8>,
+  entry_count: u16
+}
+
+impl ZipWriter {
+  fn new() -> Self {
+    ZipWriter { out: Vec::new(), central_directory: Vec::new(), entry_count: 0 }
+  }
+
+  fn add_file(&mut self, name: &str, data: &[u8]) {
+    let offset = self.out.len() as u32;
+    let crc = crc32(data);
+    let len = data.len() as u32;
+
+    // Local file header, with no extra field
+    put_u32(&mut self.out, 0x04034b50);
+    put_common_header(&mut self.out, crc, len, name);
+    put_u16(&mut self.out, 0);
+    self.out.extend_from_slice(name.as_bytes());
+    self.out.extend_from_slice(data);
+
+    // Central directory file header
+    let header = &mut self.central_directory;
+    put_u32(header, 0x02014b50);
+    put_u16(header, VERSION);
+    put_common_header(header, crc, len, name);
+    // Extra field and comment lengths, disk number, internal and external attributes
+    put_u16(header, 0);
+    put_u16(header, 0);
+    put_u16(header, 0);
+    put_u16(header, 0);
+    put_u32(header, 0);
+    put_u32(header, offset);
+    header.extend_from_slice(name.as_bytes());
+
+    self.entry_count += 1;
+  }
+
+  fn finish(mut self) -> Vec<u8> {
+    let offset = self.out.len() as u32;
+    let size = self.central_directory.len() as u32;
+    self.out.extend_from_slice(&self.central_directory);
+
+    // End of central directory record
+    put_u32(&mut self.out, 0x06054b50);
+    put_u16(&mut self.out, 0);
+    put_u16(&mut self.out, 0);
+    put_u16(&mut self.out, self.entry_count);
+    put_u16(&mut self.out, self.entry_count);
+    put_u32(&mut self.out, size);
+    put_u32(&mut self.out, offset);
+    put_u16(&mut self.out, 0);
+    self.out
+  }
+}
+
+/// The fields shared by local and central file headers, up to the length of the file name
+fn put_common_header(out: &mut Vec<u8>, crc: u32, len: u32, name: &str) {
+  put_u16(out, VERSION);
+  // Flags, compression method (stored) and modification time
+  put_u16(out, 0);
+  put_u16(out, 0);
+  put_u16(out, 0);
+  put_u16(out, DOS_DATE);
+  put_u32(out, crc);
+  put_u32(out, len);
+  put_u32(out, len);
+  put_u16(out, name.len() as u16);
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use glam::IVec3;
+
+  /// Reads back the files of an archive made by `ZipWriter`, in order, checking each of their checksums
+  fn read_stored_files(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let u16_at = |offset: usize| u16::from_le_bytes([archive[offset], archive[offset + 1]]);
+    let u32_at = |offset: usize| u32::from_le_bytes(archive[offset..offset + 4].try_into().unwrap());
+    let mut files = Vec::new();
+    let mut offset = 0;
+    while u32_at(offset) == 0x04034b50 {
+      assert_eq!(u16_at(offset + 8), 0, "the file at {} is compressed", offset);
+      let (crc, len, name_len) = (u32_at(offset + 14), u32_at(offset + 18) as usize, u16_at(offset + 26) as usize);
+      let name = std::str::from_utf8(&archive[offset + 30..offset + 30 + name_len]).unwrap().to_owned();
+      let data = archive[offset + 30 + name_len..offset + 30 + name_len + len].to_vec();
+      assert_eq!(crc32(&data), crc, "the checksum of {}", name);
+      offset += 30 + name_len + len;
+      files.push((name, data));
+    };
+
+    // The end of central directory record counts every file
+    let end = archive.len() - 22;
+    assert_eq!(u32_at(end), 0x06054b50);
+    assert_eq!(u16_at(end + 10) as usize, files.len());
+    files
+  }
+
+  fn read_file<'a>(files: &'a [(String, Vec<u8>)], name: &str) -> &'a str {
+    let (_, data) = files.iter().find(|(file_name, _)| file_name == name).unwrap_or_else(|| panic!("no {}", name));
+    std::str::from_utf8(data).unwrap()
+  }
+
+  #[test]
+  fn the_world_border_is_fitted_to_the_bounding_box() {
+    let cases = [
+      // A square box centered on the origin
+      (BoundingBox::new(IVec3::new(-100, -100, 0), IVec3::new(99, 99, 300)), "0 0", 264),
+      // A box longer along y than x, away from the origin
+      (BoundingBox::new(IVec3::new(10, -50, 0), IVec3::new(20, 149, 300)), "15.5 50", 264),
+      // A box a single column deep, centered on the middle of a block
+      (BoundingBox::new(IVec3::new(-7, 3, 0), IVec3::new(7, 3, 300)), "0.5 3.5", 79)
+    ];
+
+    for (bounding_box, center, diameter) in cases {
+      let files = read_stored_files(&build_world_datapack(bounding_box, WorldHeight::default()));
+      let function = read_file(&files, "data/glt/functions/world_border.mcfunction");
+      let expected = format!("worldborder center {}\nworldborder set {}\n", center, diameter);
+      assert_eq!(function, expected, "for {:?}", bounding_box);
+    };
+  }
+
+  #[test]
+  fn the_datapack_loads_the_world_border_and_spans_the_world_height() {
+    let world_height = WorldHeight { min_z: -128, max_z: 256 };
+    let bounding_box = BoundingBox::new(IVec3::new(-100, -100, 0), IVec3::new(99, 99, 300));
+    let files = read_stored_files(&build_world_datapack(bounding_box, world_height));
+    assert_eq!(read_file(&files, "pack.mcmeta"), PACK_MCMETA);
+    let dimension_type = read_file(&files, "data/minecraft/dimension_type/overworld.json");
+    assert_eq!(dimension_type, overworld_dimension_type(world_height));
+    assert!(read_file(&files, "data/minecraft/tags/functions/load.json").contains("\"glt:world_border\""));
+  }
+
+  #[test]
+  fn the_default_height_leaves_the_template_as_it_is() {
+    assert_eq!(overworld_dimension_type(WorldHeight::default()), OVERWORLD_DIMENSION_TYPE);
+  }
+
+  #[test]
+  fn the_dimension_type_spans_the_world_height() {
+    let dimension_type = overworld_dimension_type(WorldHeight { min_z: -128, max_z: 256 });
+    for line in ["\"min_y\": -128,", "\"height\": 384,", "\"logical_height\": 384,"] {
+      assert!(dimension_type.contains(line), "missing `{}` in:\n{}", line, dimension_type);
+    };
+  }
+}
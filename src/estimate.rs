@@ -0,0 +1,129 @@
+//! How much work rendering a world will be, worked out before rendering starts,
+//! so that a render which would take days can be called off before it begins.
+use std::fmt;
+use std::time::Duration;
+
+use crate::generation::block_entity::Nbt;
+
+
+
+/// The number of chunks, spread across the world, which are sampled to measure how quickly blocks are sampled
+pub const TIMED_CHUNK_COUNT: usize = 20;
+/// The rough size of a rendered chunk once compressed into its region file, which is dominated by the chunks of open
+/// ocean making up most of every world
+pub const TYPICAL_CHUNK_BYTES: u64 = 6 * 1024;
+/// The number of chunks above which rendering waits for confirmation, unless given
+pub const DEFAULT_CONFIRM_ABOVE: u64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationEstimate {
+  /// The exact number of chunks that will be rendered
+  pub chunk_count: u64,
+  /// How many of those chunks take the far-field fast path, see `Generator::is_far_field_chunk`
+  pub far_field_chunk_count: u64,
+  /// The number of positions that will be sampled, which only reaches up to the top of the ocean in far-field chunks
+  pub block_samples: u64,
+  /// The rough size of the rendered world on disk
+  pub output_bytes: u64,
+  /// How many blocks a single thread sampled per second across the timed far-field chunks,
+  /// or 0 if there were no far-field chunks to time
+  pub blocks_per_second: u64
+}
+
+impl GenerationEstimate {
+  /// The time it would take a single thread to sample every block. Rendering samples on every thread,
+  /// but is held up by writing chunks as well, so this is only a rough guide to how long rendering will take.
+  pub fn duration(&self) -> Duration {
+    Duration::from_secs_f64(self.block_samples as f64 / self.blocks_per_second.max(1) as f64)
+  }
+
+  /// Whether rendering should wait for confirmation before starting
+  pub fn needs_confirmation(&self, confirm_above: u64) -> bool {
+    self.chunk_count > confirm_above
+  }
+
+  pub fn to_nbt(&self) -> Nbt {
+    Nbt::compound([
+      ("chunk_count", Nbt::Long(self.chunk_count as i64)),
+      ("far_field_chunk_count", Nbt::Long(self.far_field_chunk_count as i64)),
+      ("block_samples", Nbt::Long(self.block_samples as i64)),
+      ("output_bytes", Nbt::Long(self.output_bytes as i64)),
+      ("blocks_per_second", Nbt::Long(self.blocks_per_second as i64))
+    ])
+  }
+
+  pub fn from_nbt(nbt: &Nbt) -> Option<Self> {
+    match (
+      nbt.get("chunk_count")?, nbt.get("far_field_chunk_count")?, nbt.get("block_samples")?,
+      nbt.get("output_bytes")?, nbt.get("blocks_per_second")?
+    ) {
+      (
+        &Nbt::Long(chunk_count), &Nbt::Long(far_field_chunk_count), &Nbt::Long(block_samples),
+        &Nbt::Long(output_bytes), &Nbt::Long(blocks_per_second)
+      ) => Some(GenerationEstimate {
+        chunk_count: chunk_count as u64,
+        far_field_chunk_count: far_field_chunk_count as u64,
+        block_samples: block_samples as u64,
+        output_bytes: output_bytes as u64,
+        blocks_per_second: blocks_per_second as u64
+      }),
+      _ => None
+    }
+  }
+
+  pub fn to_json(&self) -> String {
+    format!(
+      concat!(
+        "{{ \"chunk_count\": {}, \"far_field_chunk_count\": {}, \"block_samples\": {}, ",
+        "\"output_bytes\": {}, \"blocks_per_second\": {} }}"
+      ),
+      self.chunk_count, self.far_field_chunk_count, self.block_samples, self.output_bytes, self.blocks_per_second
+    )
+  }
+}
+
+impl fmt::Display for GenerationEstimate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f, "{} chunks ({} far-field), {} blocks to sample, about {} on disk",
+      self.chunk_count, self.far_field_chunk_count, self.block_samples, DisplayBytes(self.output_bytes)
+    )?;
+    match self.blocks_per_second {
+      0 => write!(f, ", with no far-field chunks to time sampling on"),
+      blocks_per_second => {
+        write!(f, " and {} to sample at {} blocks/s", DisplayDuration(self.duration()), blocks_per_second)
+      }
+    }
+  }
+}
+
+struct DisplayBytes(u64);
+
+impl fmt::Display for DisplayBytes {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let mut size = self.0 as f64;
+    let mut unit = "B";
+    for next_unit in UNITS {
+      if size < 1024.0 { break };
+      size /= 1024.0;
+      unit = next_unit;
+    };
+
+    write!(f, "{:.1} {}", size, unit)
+  }
+}
+
+struct DisplayDuration(Duration);
+
+impl fmt::Display for DisplayDuration {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let seconds = self.0.as_secs();
+    match seconds {
+      0..=59 => write!(f, "{}s", seconds),
+      60..=3599 => write!(f, "{}m {}s", seconds / 60, seconds % 60),
+      3600..=86399 => write!(f, "{}h {}m", seconds / 3600, seconds / 60 % 60),
+      _ => write!(f, "{}d {}h", seconds / 86400, seconds / 3600 % 24)
+    }
+  }
+}
@@ -0,0 +1,208 @@
+//! An interactive view for choosing a seed, which shows the landmasses of each seed's city from above in the terminal.
+//! Only the shapes of the landmasses are generated, which is quick enough to flip through seeds one after another,
+//! especially since the next seed is generated in the background while the current one is shown.
+//!
+//! The terminal is driven with ANSI escape codes and put into raw mode with `stty`, so this only works on
+//! Unix-like systems, in terminals supporting 256 colors.
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+use glam::IVec2;
+
+use crate::generation::city::{City, LandmassShape};
+use crate::utility::CARDINAL4;
+use crate::GeneratorOptions;
+
+/// The colors of each layer's landmass from the bottom up, from the 256 color palette, repeating for taller cities
+const LAYER_COLORS: [u8; 6] = [240, 245, 250, 66, 108, 151];
+/// The color of the outline of each landmass, where it isn't covered by a higher one
+const EDGE_COLOR: u8 = 231;
+const OCEAN_COLOR: u8 = 17;
+
+/// Shows the landmasses of seeds one at a time, starting from the given seed, until one is chosen to be rendered.
+/// Returns the seed chosen, or `None` if the user quit without choosing one.
+pub fn explore(seed: u64, options: &GeneratorOptions) -> io::Result<Option<u64>> {
+  let terminal = RawTerminal::enter()?;
+  let mut stdin = io::stdin().lock();
+  let mut seed = seed;
+  let mut shapes = None;
+  // The shapes of the next seed, generated in the background while the current one is being looked at
+  let mut next_shapes: Option<(u64, JoinHandle<Vec<LandmassShape>>)> = None;
+  // The digits typed so far after pressing `g`
+  let mut typed_seed: Option<String> = None;
+  loop {
+    let size = terminal.size();
+    if shapes.is_none() {
+      draw_status(size, &format!("generating the landmasses of seed {}...", seed))?;
+    };
+
+    let current_shapes = shapes.get_or_insert_with(|| match next_shapes.take() {
+      Some((next_seed, handle)) if next_seed == seed => handle.join().expect("landmass generation panicked"),
+      _ => City::preview_shapes(seed, options.layer_count, options.shapes, options.symmetry, options.erosion)
+    });
+    if next_shapes.is_none() {
+      let (next_seed, layer_count) = (seed.wrapping_add(1), options.layer_count);
+      let (shapes, symmetry, erosion) = (options.shapes, options.symmetry, options.erosion);
+      let handle = thread::spawn(move || City::preview_shapes(next_seed, layer_count, shapes, symmetry, erosion));
+      next_shapes = Some((next_seed, handle));
+    };
+
+    let status = match &typed_seed {
+      Some(typed_seed) => format!("go to seed: {}_", typed_seed),
+      None => format!(
+        "seed {}    [n] next  [p] previous  [g] go to seed  [enter] render  [q] quit",
+        seed
+      )
+    };
+    draw_map(current_shapes, size)?;
+    draw_status(size, &status)?;
+
+    let mut key = [0];
+    stdin.read_exact(&mut key)?;
+    let next_seed = match (&mut typed_seed, key[0]) {
+      (Some(typed), digit @ b'0'..=b'9') => {
+        typed.push(digit as char);
+        None
+      },
+      // Backspace and delete
+      (Some(typed), 0x08 | 0x7f) => {
+        typed.pop();
+        None
+      },
+      (Some(_), b'\r' | b'\n') => typed_seed.take().and_then(|typed| typed.parse::<u64>().ok()),
+      // Escape
+      (Some(_), 0x1b) => {
+        typed_seed = None;
+        None
+      },
+      (Some(_), _) => None,
+      (None, b'n') => Some(seed.wrapping_add(1)),
+      (None, b'p') => Some(seed.wrapping_sub(1)),
+      (None, b'g') => {
+        typed_seed = Some(String::new());
+        None
+      },
+      (None, b'\r' | b'\n') => return Ok(Some(seed)),
+      // Ctrl-C arrives as a key, since raw mode stops the terminal from turning it into a signal
+      (None, b'q' | 0x03) => return Ok(None),
+      (None, _) => None
+    };
+
+    if let Some(next_seed) = next_seed {
+      seed = next_seed;
+      shapes = None;
+    };
+  }
+}
+
+/// Draws the landmasses over every line of the terminal but the last, which is left for the status line
+fn draw_map(shapes: &[LandmassShape], size: IVec2) -> io::Result<()> {
+  let lines = render_map(shapes, IVec2::new(size.x, size.y - 1));
+  let mut stdout = io::stdout().lock();
+  for (row, line) in lines.iter().enumerate() {
+    write!(stdout, "\x1b[{};1H{}", row + 1, line)?;
+  };
+
+  stdout.flush()
+}
+
+/// Replaces the last line of the terminal with the given text
+fn draw_status(size: IVec2, status: &str) -> io::Result<()> {
+  let mut stdout = io::stdout().lock();
+  let status = status.chars().take(size.x as usize).collect::<String>();
+  write!(stdout, "\x1b[{};1H\x1b[2K{}", size.y, status)?;
+  stdout.flush()
+}
+
+/// Renders the landmasses from above, as lines of the given number of characters, scaled down to fit.
+/// Each character is split into two square pixels with a half block, which each show the highest landmass
+/// at the middle of the columns they cover.
+fn render_map(shapes: &[LandmassShape], size: IVec2) -> Vec<String> {
+  let pixels = IVec2::new(size.x, size.y * 2).max(IVec2::ONE);
+  let (min, max) = shapes.iter().fold((IVec2::ZERO, IVec2::ZERO), |(min, max), shape| {
+    (min.min(shape.min()), max.max(shape.max()))
+  });
+  let span = max - min + 1;
+  let scale = ((span.x + pixels.x - 1) / pixels.x).max((span.y + pixels.y - 1) / pixels.y).max(1);
+  let origin = (min + max) / 2 - pixels * scale / 2;
+
+  // Rows of pixels run north to south, so that the map is oriented the same way as Minecraft's
+  let layers = (0..pixels.y)
+    .flat_map(|y| (0..pixels.x).map(move |x| IVec2::new(x, y)))
+    .map(|pixel| {
+      let pos = origin + pixel * scale + scale / 2;
+      shapes.iter().rposition(|shape| shape.sample(pos).is_some())
+    })
+    .collect::<Vec<Option<usize>>>();
+  let layer_at = |pixel: IVec2| match pixel.cmpge(IVec2::ZERO).all() && pixel.cmplt(pixels).all() {
+    true => layers[(pixel.y * pixels.x + pixel.x) as usize],
+    false => None
+  };
+  let color_at = |pixel: IVec2| match layer_at(pixel) {
+    // A landmass' edge is wherever it borders something lower than it, including the ocean
+    Some(layer) if CARDINAL4.iter().any(|&offset| layer_at(pixel + offset) < Some(layer)) => EDGE_COLOR,
+    Some(layer) => LAYER_COLORS[layer % LAYER_COLORS.len()],
+    None => OCEAN_COLOR
+  };
+
+  (0..size.y)
+    .map(|row| {
+      let mut line = (0..size.x)
+        .map(|x| {
+          let (top, bottom) = (color_at(IVec2::new(x, row * 2)), color_at(IVec2::new(x, row * 2 + 1)));
+          format!("\x1b[38;5;{}m\x1b[48;5;{}m\u{2580}", top, bottom)
+        })
+        .collect::<String>();
+      line.push_str("\x1b[0m");
+      line
+    })
+    .collect()
+}
+
+/// Keeps the terminal in raw mode on its alternate screen, restoring it when dropped
+struct RawTerminal {
+  /// The terminal's settings before entering raw mode, as given by `stty -g`
+  saved_settings: String
+}
+
+impl RawTerminal {
+  fn enter() -> io::Result<Self> {
+    let saved_settings = stty(&["-g"])?.trim().to_owned();
+    stty(&["raw", "-echo"])?;
+    // Switches to the alternate screen, and hides the cursor
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+    stdout.flush()?;
+    Ok(RawTerminal { saved_settings })
+  }
+
+  /// The number of columns and rows of the terminal, checked every time since the terminal may have been resized
+  fn size(&self) -> IVec2 {
+    let size = stty(&["size"]).ok().and_then(|size| {
+      let (rows, columns) = size.trim().split_once(' ')?;
+      Some(IVec2::new(columns.parse().ok()?, rows.parse().ok()?))
+    });
+
+    size.filter(|size| size.cmpge(IVec2::splat(2)).all()).unwrap_or(IVec2::new(80, 24))
+  }
+}
+
+impl Drop for RawTerminal {
+  fn drop(&mut self) {
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[?1049l");
+    let _ = stdout.flush();
+    let _ = stty(&[&self.saved_settings]);
+  }
+}
+
+/// Runs `stty` on the terminal this process was started from, returning what it printed
+fn stty(args: &[&str]) -> io::Result<String> {
+  let output = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::inherit()).output()?;
+  if !output.status.success() {
+    return Err(io::Error::other("`stty` failed, `explore` must be run in a terminal"));
+  };
+
+  Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,73 @@
+//! A fast path for rendering chunks so far from the city that only the bedrock, ocean and outcrops reach them.
+//!
+//! The blocks of these chunks are sampled the same way as `ChunkSections`, but into a fixed palette
+//! which only needs to be handed to Amulet once, rather than once per chunk.
+use glam::IVec3;
+
+use crate::chunk_sections::{section_block_pos, SECTION_VOLUME};
+use crate::chunk_stats::{ChunkStats, ChunkStatsRecorder, SECTION_HEIGHT};
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::{blocks, Block, MaterialGeometry};
+use crate::Generator;
+/// Every block that the bedrock, ocean and outcrops place, along with air, which is always first.
+/// The indices in a `FarFieldChunk`'s sections refer to this palette,
+/// once the generator's theme has been applied to it as in `Generator::far_field_palette`.
+pub const FAR_FIELD_PALETTE: [Block; 11] = [
+  blocks::AIR,
+  blocks::BEDROCK,
+  blocks::DEEPSLATE,
+  blocks::GRAVEL,
+  blocks::SAND,
+  blocks::WATER,
+  blocks::SEAGRASS_SHORT,
+  blocks::SEAGRASS_TALL_LOWER,
+  blocks::SEAGRASS_TALL_UPPER,
+  blocks::TUFF,
+  blocks::BASALT
+];
+
+/// A far-field chunk's blocks, as indices into `Generator::far_field_palette`
+#[derive(Debug, Clone)]
+pub struct FarFieldChunk {
+  pub pos: ChunkPos,
+  /// The index of the lowest section
+  pub min_section: i32,
+  /// Each section in turn, from the bottom up, as `SECTION_VOLUME` indices, laid out as described in `chunk_sections`
+  pub indices: Vec<u32>
+}
+
+impl FarFieldChunk {
+  /// Samples a chunk for which `Generator::is_far_field_chunk` is true, collecting its statistics along the way.
+  /// Every section from the bottom of the generator up to the top of the ocean, or of any outcrops in the chunk,
+  /// is filled in, with air wherever the slow path would set nothing.
+  pub fn sample(generator: &Generator, chunk_pos: ChunkPos) -> (Self, ChunkStats) {
+    debug_assert!(generator.is_far_field_chunk(chunk_pos));
+    let context = generator.chunk_context(chunk_pos);
+    let mut recorder = ChunkStatsRecorder::new(chunk_pos);
+    let (min_z, max_z) = (generator.bounding_box.min.z, generator.far_field_max_z(chunk_pos));
+    let min_section = min_z.div_euclid(SECTION_HEIGHT);
+    let max_section = max_z.div_euclid(SECTION_HEIGHT);
+
+    let section_count = (max_section - min_section + 1).max(0) as usize;
+    let mut indices = vec![0; section_count * SECTION_VOLUME];
+    for (section, section_indices) in (min_section..=max_section).zip(indices.chunks_exact_mut(SECTION_VOLUME)) {
+      for (i, index) in section_indices.iter_mut().enumerate() {
+        let block_pos = section_block_pos(i) + IVec3::new(0, 0, section * SECTION_HEIGHT);
+        if block_pos.z < min_z || block_pos.z > max_z { continue };
+        if let Some(block) = context.block_material_at(block_pos + chunk_pos.min_block().extend(0)) {
+          recorder.record(block_pos, &block);
+          *index = generator.far_field_palette().iter()
+            .position(|palette_block| *palette_block == *block)
+            .expect("the bedrock, ocean and outcrops only place blocks from the far-field palette") as u32;
+        };
+      };
+    };
+
+    (FarFieldChunk { pos: chunk_pos, min_section, indices }, recorder.finish())
+  }
+
+  /// Each section's index along with its indices
+  pub fn sections(&self) -> impl Iterator<Item = (i32, &[u32])> {
+    (self.min_section..).zip(self.indices.chunks_exact(SECTION_VOLUME))
+  }
+}
@@ -0,0 +1,297 @@
+//! A C interface to the generator, so that other programs (such as server plugins) can generate chunks on demand
+//! instead of rendering a whole world up front.
+//!
+//! # Ownership
+//!
+//! - A generator is created by `glt_generator_new` and must be freed by passing it to `glt_generator_free` exactly
+//!   once.
+//! - The palette written by `glt_generator_fill_chunk` is owned by the generator. It stays valid until the next call
+//!   to `glt_generator_fill_chunk` with the same generator, or until the generator is freed, whichever comes first.
+//! - The index buffer is owned by the caller, who must allocate `glt_chunk_index_count(generator)` indices for it.
+//!
+//! A generator may not be used from more than one thread at a time, but separate generators are independent.
+//!
+//! # Chunk layout
+//!
+//! Coordinates here are Minecraft's, where y is up. A chunk covers the full height of the generator's world,
+//! from y = `min_z` up to but not including the build limit at y = `max_z`, and is written in YZX order:
+//! the block at chunk-relative `x`, `z` and world `y` is at index `((y - min_z) * 16 + z) * 16 + x`.
+//!
+//! Each index refers to an entry in the palette, which is a buffer of NUL-terminated blockstate strings laid end to
+//! end, like `minecraft:air\0minecraft:gray_concrete\0`. Entry 0 is always `minecraft:air`.
+//! Waterlogging is implied by the blocks that have it, such as seagrass, so only the base blockstate is given.
+//! Block entities, such as the contents of chests, are not included.
+//!
+//! Building with the `ffi` feature produces a C library exporting these functions alongside the binary.
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::slice;
+
+#[cfg(any(test, feature = "test-fixtures"))]
+use glam::IVec3;
+
+use crate::builder::GeneratorBuilder;
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::MaterialGeometry;
+#[cfg(any(test, feature = "test-fixtures"))]
+use crate::generation::Block;
+use crate::world_coords::{chunk_from_minecraft, from_minecraft};
+#[cfg(any(test, feature = "test-fixtures"))]
+use crate::world_coords::chunk_to_minecraft;
+use crate::{Generator, WorldHeight};
+
+
+
+/// The chunk was filled successfully
+pub const GLT_OK: i32 = 0;
+/// One of the pointers passed in was null
+pub const GLT_ERROR_NULL_POINTER: i32 = -1;
+/// `indices_len` was not equal to `glt_chunk_index_count(generator)`
+pub const GLT_ERROR_BUFFER_SIZE: i32 = -2;
+/// The chunk had more distinct blocks than a `u16` index can refer to
+pub const GLT_ERROR_PALETTE_OVERFLOW: i32 = -3;
+/// The generator panicked while filling the chunk. The generator may still be freed, but should not be used otherwise.
+pub const GLT_ERROR_PANIC: i32 = -4;
+
+const AIR: &str = "minecraft:air";
+
+/// A generator along with the palette of the last chunk it filled, which the caller may still be reading
+pub struct GltGenerator {
+  generator: Generator,
+  palette: Vec<u8>
+}
+
+impl GltGenerator {
+  /// The number of indices in each chunk of this generator's world
+  fn index_count(&self) -> usize {
+    let WorldHeight { min_z, max_z } = self.generator.world_height;
+    16 * 16 * (max_z - min_z) as usize
+  }
+
+  fn fill_chunk(&mut self, chunk_pos: ChunkPos, indices: &mut [u16]) -> Result<(), i32> {
+    let context = self.generator.chunk_context(chunk_pos);
+    let mut palette = vec![AIR.to_owned()];
+    let mut palette_indices = HashMap::new();
+    let min_z = self.generator.world_height.min_z;
+    for (i, index) in indices.iter_mut().enumerate() {
+      let local = from_minecraft(i as i32 % 16, i as i32 / 256 + min_z, (i as i32 / 16) % 16);
+      *index = match context.block_material_at(local + chunk_pos.min_block().extend(0)) {
+        Some(block) => match palette_indices.get(block.base_blockstate()) {
+          Some(&index) => index,
+          None => {
+            let index = u16::try_from(palette.len()).map_err(|_| GLT_ERROR_PALETTE_OVERFLOW)?;
+            palette_indices.insert(block.base_blockstate().to_owned(), index);
+            palette.push(block.base_blockstate().to_owned());
+            index
+          }
+        },
+        None => 0
+      };
+    };
+
+    self.palette = palette.into_iter()
+      .flat_map(|blockstate| CString::new(blockstate).expect("blockstates contain no NUL bytes").into_bytes_with_nul())
+      .collect();
+    Ok(())
+  }
+}
+
+/// The number of indices in a chunk of the given generator's world, which the buffer passed to
+/// `glt_generator_fill_chunk` must hold. Returns 0 if given null.
+///
+/// # Safety
+/// The pointer must be null or a live generator from `glt_generator_new`.
+#[no_mangle]
+pub unsafe extern "C" fn glt_chunk_index_count(generator: *const GltGenerator) -> usize {
+  generator.as_ref().map_or(0, GltGenerator::index_count)
+}
+
+/// Creates a generator for the world with the given seed, using the default options.
+/// Returns null if generation panicked.
+#[no_mangle]
+pub extern "C" fn glt_generator_new(seed: u64) -> *mut GltGenerator {
+  catch_unwind(|| GeneratorBuilder::new(seed).build().expect("the default options are valid"))
+    .map_or(std::ptr::null_mut(), |generator| Box::into_raw(Box::new(GltGenerator { generator, palette: Vec::new() })))
+}
+
+/// Frees a generator created by `glt_generator_new`, along with its palette. Does nothing if given null.
+///
+/// # Safety
+/// The pointer must be null or have come from `glt_generator_new`, and must not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn glt_generator_free(generator: *mut GltGenerator) {
+  if !generator.is_null() {
+    drop(Box::from_raw(generator));
+  };
+}
+
+/// Whether the chunk at the given chunk coordinates is within the bounds of the world. Chunks outside of it are
+/// entirely air, but chunks within it may be too, such as where the world's edge only just reaches into them.
+/// Returns false if given null.
+///
+/// # Safety
+/// The pointer must be null or a live generator from `glt_generator_new`.
+#[no_mangle]
+pub unsafe extern "C" fn glt_generator_chunk_exists(generator: *const GltGenerator, x: i32, z: i32) -> bool {
+  match generator.as_ref() {
+    Some(generator) => generator.generator.chunk_exists(chunk_from_minecraft(x, z)),
+    None => false
+  }
+}
+
+/// Fills the chunk at the given chunk coordinates, as laid out in the documentation of this module.
+/// The palette is written to `out_palette`, with its length in bytes (including every NUL) written to
+/// `out_palette_len`, and the blocks are written to `out_indices`.
+/// Returns `GLT_OK`, or one of the `GLT_ERROR_*` codes, in which case the palette is not written
+/// and the contents of `out_indices` are unspecified.
+///
+/// # Safety
+/// `generator` must be a live generator from `glt_generator_new`, `out_palette` and `out_palette_len` must be valid
+/// for writes, and `out_indices` must be valid for writes of `indices_len` consecutive `u16`s.
+#[no_mangle]
+pub unsafe extern "C" fn glt_generator_fill_chunk(
+  generator: *mut GltGenerator, x: i32, z: i32,
+  out_palette: *mut *const c_char, out_palette_len: *mut usize,
+  out_indices: *mut u16, indices_len: usize
+) -> i32 {
+  let generator = match generator.as_mut() {
+    Some(generator) => generator,
+    None => return GLT_ERROR_NULL_POINTER
+  };
+
+  if out_palette.is_null() || out_palette_len.is_null() || out_indices.is_null() {
+    return GLT_ERROR_NULL_POINTER;
+  };
+
+  if indices_len != generator.index_count() {
+    return GLT_ERROR_BUFFER_SIZE;
+  };
+
+  let indices = slice::from_raw_parts_mut(out_indices, indices_len);
+  let result = catch_unwind(AssertUnwindSafe(|| generator.fill_chunk(chunk_from_minecraft(x, z), indices)));
+  match result {
+    Ok(Ok(())) => {
+      *out_palette = generator.palette.as_ptr() as *const c_char;
+      *out_palette_len = generator.palette.len();
+      GLT_OK
+    },
+    Ok(Err(code)) => code,
+    Err(_) => GLT_ERROR_PANIC
+  }
+}
+
+/// Fills a chunk through the C interface and rebuilds its blocks from the palette and indices as laid out above,
+/// returning the first position (if any) at which they differ from `Generator::block_at`.
+#[cfg(any(test, feature = "test-fixtures"))]
+pub fn validate_fill_chunk(seed: u64, chunk_pos: ChunkPos) -> Result<(), IVec3> {
+  let generator = glt_generator_new(seed);
+  assert!(!generator.is_null(), "failed to create a generator");
+  let (cx, cz) = chunk_to_minecraft(chunk_pos);
+
+  let mut indices = vec![0; unsafe { glt_chunk_index_count(generator) }];
+  let mut palette_ptr = std::ptr::null();
+  let mut palette_len = 0;
+  let code = unsafe {
+    glt_generator_fill_chunk(
+      generator, cx, cz,
+      &mut palette_ptr, &mut palette_len,
+      indices.as_mut_ptr(), indices.len()
+    )
+  };
+  assert_eq!(code, GLT_OK, "failed to fill chunk {}", chunk_pos.0);
+
+  // Every entry ends in a NUL, so the last one is left off to avoid an empty entry at the end
+  let palette = unsafe { slice::from_raw_parts(palette_ptr as *const u8, palette_len - 1) }
+    .split(|&byte| byte == 0)
+    .map(|blockstate| std::str::from_utf8(blockstate).expect("blockstates are utf-8").to_owned())
+    .collect::<Vec<String>>();
+
+  let generator = unsafe { Box::from_raw(generator) };
+  let min_z = generator.generator.world_height.min_z;
+  for (i, &index) in indices.iter().enumerate() {
+    let local = from_minecraft(i as i32 % 16, i as i32 / 256 + min_z, (i as i32 / 16) % 16);
+    let pos = local + chunk_pos.min_block().extend(0);
+    let expected = generator.generator.block_at(pos);
+    if palette[index as usize] != expected.as_ref().map_or(AIR, Block::base_blockstate) {
+      return Err(pos);
+    };
+  };
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::{IVec2, Vec3Swizzles};
+
+  use super::*;
+
+  /// A chunk of open ocean at the corner of the world, far from the city
+  fn ocean_chunk(seed: u64) -> ChunkPos {
+    let generator = GeneratorBuilder::new(seed).build().expect("the default options are valid");
+    ChunkPos::containing(generator.bounding_box.min.xy())
+  }
+
+  #[test]
+  fn filled_ocean_chunks_match_the_generator() {
+    assert_eq!(validate_fill_chunk(0, ocean_chunk(0)), Ok(()));
+  }
+
+  #[test]
+  #[ignore = "generates every layer of a full size city"]
+  fn filled_city_chunks_match_the_generator() {
+    assert_eq!(validate_fill_chunk(0, ChunkPos::containing(IVec2::ZERO)), Ok(()));
+  }
+
+  #[test]
+  fn chunks_span_the_world_height_beneath_the_build_limit() {
+    let generator = glt_generator_new(0);
+    unsafe {
+      let WorldHeight { min_z, max_z } = (*generator).generator.world_height;
+      let count = glt_chunk_index_count(generator);
+      assert_eq!(count, 16 * 16 * (max_z - min_z) as usize);
+      // The last index is the highest block that can be placed, one beneath the build limit
+      assert_eq!((count as i32 - 1) / 256 + min_z, max_z - 1);
+      assert_eq!(glt_chunk_index_count(std::ptr::null()), 0);
+      glt_generator_free(generator);
+    };
+  }
+
+  #[test]
+  fn chunks_exist_only_within_the_world() {
+    let generator = glt_generator_new(0);
+    let (x, z) = chunk_to_minecraft(ocean_chunk(0));
+    unsafe {
+      assert!(glt_generator_chunk_exists(generator, x, z));
+      assert!(!glt_generator_chunk_exists(generator, x - 1, z - 1));
+      assert!(!glt_generator_chunk_exists(std::ptr::null(), x, z));
+      glt_generator_free(generator);
+    };
+  }
+
+  #[test]
+  fn filling_a_chunk_reports_bad_arguments() {
+    let generator = glt_generator_new(0);
+    let (x, z) = chunk_to_minecraft(ocean_chunk(0));
+    let mut indices = vec![0; unsafe { glt_chunk_index_count(generator) }];
+    let (mut palette_ptr, mut palette_len) = (std::ptr::null(), 0);
+    unsafe {
+      let code = glt_generator_fill_chunk(
+        std::ptr::null_mut(), x, z, &mut palette_ptr, &mut palette_len, indices.as_mut_ptr(), indices.len()
+      );
+      assert_eq!(code, GLT_ERROR_NULL_POINTER);
+      let code = glt_generator_fill_chunk(
+        generator, x, z, std::ptr::null_mut(), &mut palette_len, indices.as_mut_ptr(), indices.len()
+      );
+      assert_eq!(code, GLT_ERROR_NULL_POINTER);
+      let code = glt_generator_fill_chunk(
+        generator, x, z, &mut palette_ptr, &mut palette_len, indices.as_mut_ptr(), indices.len() - 1
+      );
+      assert_eq!(code, GLT_ERROR_BUFFER_SIZE);
+      assert!(palette_ptr.is_null());
+      glt_generator_free(generator);
+    };
+  }
+}
@@ -1,30 +1,143 @@
 pub mod bedrock;
+pub mod block_entity;
 pub mod blocks;
+pub mod bubble_lift;
+pub mod checked;
+pub mod chunk_context;
+pub mod chunk_pos;
+pub mod deferred;
+pub mod encoding;
+pub mod enclosure;
+pub mod fade_bounds;
+pub mod feature_tag;
 pub mod city;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod fixtures;
 pub mod intersection;
 pub mod limit_bounds;
+pub mod material_union;
 pub mod materialize;
+pub mod noise_util;
 pub mod ocean;
+pub mod outcrops;
 pub mod pillar;
+pub mod primitives;
+pub mod profiled;
+pub mod theme;
+pub mod transform;
 pub mod union_threaded;
 pub mod union;
 
 use std::borrow::Cow;
 use std::cmp::PartialOrd;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
 
 use glam::{IVec2, IVec3};
 use pyo3::{PyObject, PyResult, Python};
 
+use self::chunk_pos::ChunkPos;
 
 
+
+/// The volume above which a bounding box is suspiciously large: many times the size of even the largest city,
+/// but far smaller than any box with a swapped or overflowed corner
+const SUSPICIOUS_VOLUME: u64 = 1 << 36;
+
 pub trait Geometry {
+  /// A box containing every block of this geometry. `block_at` must return false everywhere outside of it,
+  /// since `Union` skips members at positions outside of their bounding boxes.
   fn bounding_box(&self) -> BoundingBox;
 
   fn block_at(&self, pos: IVec3) -> bool;
 }
 
+/// A geometry made of specific blocks.
+///
+/// Implementors must keep `block_material_at` consistent with `block_at`, returning `Some` exactly where `block_at`
+/// returns true, since combinators freely mix the two. `CheckedGeometry` asserts this in debug builds.
+///
+/// Blocks which are constants or are stored in the geometry should be borrowed rather than cloned,
+/// since this is called for every block of the world. Only blocks computed on the fly need to be owned.
+///
+/// Every block that `block_material_at` can ever return must be added by `possible_blocks`, so that the whole palette
+/// of the world can be registered before rendering starts. Rendering fails on any block that was left out.
 pub trait MaterialGeometry: Geometry {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block>;
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>>;
+
+  /// Adds every block that this geometry can place to `out`
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>);
+}
+
+/// A geometry that knows roughly how high it reaches in each column, without sampling the column block by block
+pub trait ColumnTop: Geometry {
+  /// A z value at or above the topmost block of this geometry in the given column, or `None` if it can do no better
+  /// than its bounding box. A column that this geometry has no blocks in may give any z value beneath the world.
+  fn column_top(&self, _xy: IVec2) -> Option<i32> {
+    None
+  }
+}
+
+impl<G: Geometry + ?Sized> Geometry for &G {
+  fn bounding_box(&self) -> BoundingBox {
+    G::bounding_box(self)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    G::block_at(self, pos)
+  }
+}
+
+impl<G: MaterialGeometry + ?Sized> MaterialGeometry for &G {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    G::block_material_at(self, pos)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    G::possible_blocks(self, out)
+  }
+}
+
+impl<G: Geometry + ?Sized> Geometry for Arc<G> {
+  fn bounding_box(&self) -> BoundingBox {
+    G::bounding_box(self)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    G::block_at(self, pos)
+  }
+}
+
+impl<G: MaterialGeometry + ?Sized> MaterialGeometry for Arc<G> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    G::block_material_at(self, pos)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    G::possible_blocks(self, out)
+  }
+}
+
+impl<G: Geometry + ?Sized> Geometry for Box<G> {
+  fn bounding_box(&self) -> BoundingBox {
+    G::bounding_box(self)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    G::block_at(self, pos)
+  }
+}
+
+impl<G: MaterialGeometry + ?Sized> MaterialGeometry for Box<G> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    G::block_material_at(self, pos)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    G::possible_blocks(self, out)
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -34,6 +147,77 @@ pub struct Block {
 }
 
 impl Block {
+  /// The namespaced id of this block, without any of its block state properties
+  pub fn base_name(&self) -> &str {
+    self.base_block.split('[').next().unwrap_or(&self.base_block)
+  }
+
+  /// The full blockstate string of this block, like `minecraft:chain[axis=y]`
+  pub fn base_blockstate(&self) -> &str {
+    &self.base_block
+  }
+
+  /// The blockstate of the block sharing this block's position, such as the water around seagrass
+  pub fn extra_blockstate(&self) -> Option<&str> {
+    self.extra_block.as_deref()
+  }
+
+  /// Whether both blocks point to the same blockstate strings, as copies of the same constant in `blocks` do.
+  /// Blocks for which this is true are equal, but equal blocks may have been built separately, so this can be false.
+  pub fn ptr_eq(&self, other: &Block) -> bool {
+    fn str_ptr_eq(a: &str, b: &str) -> bool {
+      a.as_ptr() == b.as_ptr() && a.len() == b.len()
+    }
+
+    str_ptr_eq(&self.base_block, &other.base_block) && match (&self.extra_block, &other.extra_block) {
+      (Some(a), Some(b)) => str_ptr_eq(a, b),
+      (None, None) => true,
+      _ => false
+    }
+  }
+
+  /// Reads a blockstate string like `minecraft:chain[axis=y]`, checking its syntax but not whether the block exists.
+  /// A block without a namespace is taken to be in the minecraft namespace.
+  pub fn parse(blockstate: &str) -> Result<Block, String> {
+    let (id, properties) = match blockstate.split_once('[') {
+      Some((id, properties)) => {
+        let properties = properties.strip_suffix(']').ok_or_else(|| "expected `]` after the properties".to_owned())?;
+        (id, Some(properties))
+      },
+      None => (blockstate, None)
+    };
+
+    let (namespace, path) = id.split_once(':').unwrap_or(("minecraft", id));
+    let is_id_char = |c: char| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.');
+    if namespace.is_empty() || !namespace.chars().all(is_id_char) {
+      return Err(format!("`{}` is not a valid namespace", namespace));
+    };
+
+    if path.is_empty() || !path.chars().all(|c| is_id_char(c) || c == '/') {
+      return Err(format!("`{}` is not a valid block id", path));
+    };
+
+    let mut names = Vec::new();
+    for property in properties.into_iter().flat_map(|properties| properties.split(',')) {
+      let is_property_char = |c: char| matches!(c, 'a'..='z' | '0'..='9' | '_');
+      match property.split_once('=') {
+        Some((name, value)) if [name, value].iter().all(|s| !s.is_empty() && s.chars().all(is_property_char)) => {
+          if names.contains(&name) {
+            return Err(format!("the property `{}` is given more than once", name));
+          };
+
+          names.push(name);
+        },
+        _ => return Err(format!("expected a property like `name=value`, found `{}`", property))
+      };
+    };
+
+    Ok(Block::from(match properties {
+      Some(properties) => format!("{}:{}[{}]", namespace, path, properties),
+      None => format!("{}:{}", namespace, path)
+    }))
+  }
+
   pub fn into_amulet_block(self, py: Python) -> PyResult<PyObject> {
     let amulet = py.import("amulet")?;
     let block_class = amulet.getattr("api")?.getattr("block")?.getattr("Block")?;
@@ -85,11 +269,56 @@ pub struct BoundingBox {
 }
 
 impl BoundingBox {
+  /// A bounding box between two corners given in any order, which are sorted along each axis.
+  /// Only use this for corners that are genuinely unordered, such as those of a rotated box,
+  /// since sorting silently turns a swapped corner into a box covering far more than intended.
+  /// Corners computed to be in order should go through `try_new` instead.
   pub fn new(min: IVec3, max: IVec3) -> Self {
     let (min, max) = (IVec3::min(min, max), IVec3::max(min, max));
     BoundingBox { min, max }
   }
 
+  /// A bounding box between two corners, failing if `min` is above `max` on any axis instead of swapping them
+  pub fn try_new(min: IVec3, max: IVec3) -> Result<Self, BoxError> {
+    match min.cmple(max).all() {
+      true => Ok(BoundingBox { min, max }),
+      false => Err(BoxError { min, max })
+    }
+  }
+
+  /// A bounding box which extends infinitely across the XY plane, between two z values.
+  /// Its XY corners lie at the limits of `i32`, so any arithmetic on them must saturate,
+  /// which `translated`, `inflated` and `to_chunks` all take care of.
+  pub fn unbounded_xy(min_z: i32, max_z: i32) -> Self {
+    let min = IVec3::new(i32::MIN, i32::MIN, min_z);
+    let max = IVec3::new(i32::MAX, i32::MAX, max_z);
+    BoundingBox::new(min, max)
+  }
+
+  pub fn is_unbounded_xy(self) -> bool {
+    self.min.x == i32::MIN || self.min.y == i32::MIN ||
+    self.max.x == i32::MAX || self.max.y == i32::MAX
+  }
+
+  /// Moves this bounding box by the given offset, saturating at the limits of `i32`
+  pub fn translated(self, offset: IVec3) -> Self {
+    BoundingBox {
+      min: saturating_add(self.min, offset),
+      max: saturating_add(self.max, offset)
+    }
+  }
+
+  /// Grows this bounding box outwards by the given amount on each side, saturating at the limits of `i32`.
+  /// Shrinking a box by more than half of its size on any axis is an error.
+  pub fn inflated(self, amount: IVec3) -> Self {
+    BoundingBox::try_new(saturating_add(self.min, -amount), saturating_add(self.max, amount)).unwrap()
+  }
+
+  /// The range of chunks (inclusive) which this bounding box intersects
+  pub fn to_chunks(self) -> (ChunkPos, ChunkPos) {
+    (ChunkPos::containing(self.min.truncate()), ChunkPos::containing(self.max.truncate()))
+  }
+
   pub fn join(self, other: Self) -> Self {
     let min = IVec3::min(self.min, other.min);
     let max = IVec3::max(self.max, other.max);
@@ -102,6 +331,11 @@ impl BoundingBox {
     pos.z >= self.min.z && pos.z <= self.max.z
   }
 
+  pub fn contains_column(self, xy: IVec2) -> bool {
+    xy.x >= self.min.x && xy.x <= self.max.x &&
+    xy.y >= self.min.y && xy.y <= self.max.y
+  }
+
   pub fn intersects(self, other: BoundingBox) -> bool {
     let (b1, b2) = (self, other);
     let x_overlap = value_in_range(b1.min.x, b2.min.x, b2.max.x) || value_in_range(b2.min.x, b1.min.x, b1.max.x);
@@ -111,16 +345,82 @@ impl BoundingBox {
     x_overlap && y_overlap && z_overlap
   }
 
-  pub fn in_chunk(self, chunk: IVec2) -> bool {
-    let chunk_min = chunk * 16 + 0;
-    let chunk_max = chunk * 16 + 15;
+  /// The region shared by both bounding boxes, if they intersect
+  pub fn intersection(self, other: BoundingBox) -> Option<BoundingBox> {
+    self.intersects(other).then(|| {
+      BoundingBox::try_new(IVec3::max(self.min, other.min), IVec3::min(self.max, other.max)).unwrap()
+    })
+  }
+
+  /// The number of blocks within this bounding box, saturating for unbounded boxes
+  pub fn volume(self) -> u64 {
+    let len = |min: i32, max: i32| (max as i64 - min as i64 + 1) as u64;
+    len(self.min.x, self.max.x)
+      .saturating_mul(len(self.min.y, self.max.y))
+      .saturating_mul(len(self.min.z, self.max.z))
+  }
+
+  /// Whether this bounding box is so large that one of its corners was most likely swapped or overflowed,
+  /// which would silently defeat every check against it.
+  /// Boxes unbounded across the XY plane are meant to be this large.
+  pub fn is_suspiciously_large(self) -> bool {
+    !self.is_unbounded_xy() && self.volume() > SUSPICIOUS_VOLUME
+  }
+
+  pub fn intersects_chunk(self, chunk: ChunkPos) -> bool {
     let min = self.min.truncate();
     let max = self.max.truncate();
 
-    boxes_intersect(min, max, chunk_min, chunk_max)
+    boxes_intersect(min, max, chunk.min_block(), chunk.max_block())
   }
 }
 
+/// The corners of a bounding box which were given the wrong way around, with `min` above `max` on at least one axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BoxError {
+  pub min: IVec3,
+  pub max: IVec3
+}
+
+impl fmt::Display for BoxError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "bounding box corners are swapped: min {} is above max {} on at least one axis", self.min, self.max)
+  }
+}
+
+impl Error for BoxError {}
+
+/// Logs a warning, in debug builds only, for a member of a union whose bounding box is suspiciously large.
+/// Such a box makes the member be sampled everywhere, which shows up as a sudden slowdown rather than an error.
+fn warn_if_suspiciously_large(bounding_box: BoundingBox) {
+  if cfg!(debug_assertions) && bounding_box.is_suspiciously_large() {
+    log::warn!("union member has a suspiciously large bounding box, from {} to {}", bounding_box.min, bounding_box.max);
+  };
+}
+
+/// Finds a position where a geometry places a block, for sampling a representative block of it.
+/// The column through the middle of its bounding box is searched first, from the top down, then every other column.
+/// Geometries whose bounding boxes span more than `max_columns` columns only have their middle column searched.
+pub fn find_block(geometry: &impl Geometry, max_columns: usize) -> Option<IVec3> {
+  let BoundingBox { min, max } = geometry.bounding_box();
+  let find_in_column = |column: IVec2| {
+    (min.z..=max.z).rev().map(|z| column.extend(z)).find(|&pos| geometry.block_at(pos))
+  };
+  let middle = IVec2::new(((min.x as i64 + max.x as i64) / 2) as i32, ((min.y as i64 + max.y as i64) / 2) as i32);
+  ret_if_some!(find_in_column(middle));
+
+  let columns = (max.x as i64 - min.x as i64 + 1) * (max.y as i64 - min.y as i64 + 1);
+  if columns > max_columns as i64 { return None };
+  (min.x..=max.x)
+    .flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+    .find_map(find_in_column)
+}
+
+fn saturating_add(a: IVec3, b: IVec3) -> IVec3 {
+  IVec3::new(a.x.saturating_add(b.x), a.y.saturating_add(b.y), a.z.saturating_add(b.z))
+}
+
 fn boxes_intersect(min1: IVec2, max1: IVec2, min2: IVec2, max2: IVec2) -> bool {
   #[inline]
   fn value_in_range<T>(value: T, min: T, max: T) -> bool
@@ -142,3 +442,110 @@ where T: std::cmp::PartialOrd {
 
 type AddConstant<Source> = noise::Add<f64, Source, noise::Constant, 2>;
 type MultiplyConstant<Source> = noise::Multiply<f64, Source, noise::Constant, 2>;
+
+#[cfg(test)]
+pub mod tests {
+  use std::cell::RefCell;
+  use std::sync::Once;
+
+  use super::*;
+  use crate::generation::chunk_pos::ChunkPos;
+
+  thread_local! {
+    static WARNINGS: RefCell<Vec<String>> = RefCell::new(Vec::new());
+  }
+
+  /// Collects the warnings logged on each thread separately, so that tests running at the same time don't see each
+  /// other's warnings
+  struct WarningCollector;
+
+  impl log::Log for WarningCollector {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+      metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+      if self.enabled(record.metadata()) {
+        WARNINGS.with(|warnings| warnings.borrow_mut().push(record.args().to_string()));
+      };
+    }
+
+    fn flush(&self) {}
+  }
+
+  /// The warnings logged on the current thread while running `f`
+  pub fn warnings_logged_by(f: impl FnOnce()) -> Vec<String> {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+      log::set_logger(&WarningCollector).expect("no other logger is installed in tests");
+      log::set_max_level(log::LevelFilter::Warn);
+    });
+
+    WARNINGS.with(|warnings| warnings.borrow_mut().clear());
+    f();
+    WARNINGS.with(|warnings| warnings.take())
+  }
+
+  fn bounding_box(min: [i32; 3], max: [i32; 3]) -> BoundingBox {
+    BoundingBox::try_new(IVec3::from(min), IVec3::from(max)).unwrap()
+  }
+
+  #[test]
+  fn inverted_boxes_are_rejected() {
+    for axis in 0..3 {
+      let (mut min, mut max) = (IVec3::new(-4, 2, 10), IVec3::new(6, 3, 12));
+      std::mem::swap(&mut min[axis], &mut max[axis]);
+      assert_eq!(BoundingBox::try_new(min, max), Err(BoxError { min, max }), "swapped on axis {}", axis);
+      assert_eq!(BoundingBox::new(min, max), bounding_box([-4, 2, 10], [6, 3, 12]));
+    };
+
+    let point = IVec3::new(i32::MIN, 0, i32::MAX);
+    assert_eq!(BoundingBox::try_new(point, point), Ok(BoundingBox { min: point, max: point }));
+    let err = BoundingBox::try_new(IVec3::ONE, IVec3::ZERO).unwrap_err();
+    let message = "bounding box corners are swapped: min [1, 1, 1] is above max [0, 0, 0] on at least one axis";
+    assert_eq!(err.to_string(), message);
+  }
+
+  #[test]
+  fn only_large_bounded_boxes_are_suspicious() {
+    assert!(bounding_box([-200_000, -200_000, 0], [200_000, 200_000, 300]).is_suspiciously_large());
+    assert!(bounding_box([0, 0, 0], [i32::MAX - 1, 10, 1_000_000]).is_suspiciously_large());
+    assert!(!bounding_box([-1000, -1000, -64], [1000, 1000, 320]).is_suspiciously_large());
+    assert!(!BoundingBox::unbounded_xy(-64, 320).is_suspiciously_large());
+  }
+
+  #[test]
+  fn translating_an_unbounded_box_saturates() {
+    let unbounded = BoundingBox::unbounded_xy(-64, 0);
+    assert!(unbounded.is_unbounded_xy());
+    let moved = unbounded.translated(IVec3::new(100, -100, 5));
+    assert_eq!(moved, bounding_box([i32::MIN + 100, i32::MIN, -59], [i32::MAX, i32::MAX - 100, 5]));
+    let moved = unbounded.translated(IVec3::new(i32::MAX, i32::MIN, 0));
+    assert_eq!(moved, bounding_box([-1, i32::MIN, -64], [i32::MAX, -1, 0]));
+  }
+
+  #[test]
+  fn inflating_a_box_at_the_limits_saturates() {
+    let unbounded = BoundingBox::unbounded_xy(-64, 0);
+    assert_eq!(unbounded.inflated(IVec3::new(128, 128, 1)), BoundingBox::unbounded_xy(-65, 1));
+    let at_edges = bounding_box([i32::MIN + 5, -10, 0], [10, i32::MAX - 5, 0]);
+    assert_eq!(at_edges.inflated(IVec3::new(16, 16, 0)), bounding_box([i32::MIN, -26, 0], [26, i32::MAX, 0]));
+  }
+
+  #[test]
+  fn the_chunks_of_an_unbounded_box_reach_the_limits() {
+    let (min, max) = BoundingBox::unbounded_xy(-64, 0).to_chunks();
+    assert_eq!((min, max), (ChunkPos::new(i32::MIN / 16, i32::MIN / 16), ChunkPos::new(i32::MAX / 16, i32::MAX / 16)));
+    assert_eq!((min.min_block(), max.max_block()), (IVec2::splat(i32::MIN), IVec2::splat(i32::MAX)));
+    assert_eq!(ChunkPos::rings_in_range(min, max).next(), Some(ChunkPos::new(0, 0)));
+  }
+
+  #[test]
+  fn measuring_an_unbounded_box_saturates() {
+    let unbounded = BoundingBox::unbounded_xy(-64, 0);
+    assert_eq!(unbounded.volume(), u64::MAX);
+    let inner = bounding_box([-3, 4, -10], [7, 9, 20]);
+    assert_eq!(unbounded.intersection(inner), Some(bounding_box([-3, 4, -10], [7, 9, 0])));
+    assert_eq!(unbounded.join(inner), BoundingBox::unbounded_xy(-64, 20));
+  }
+}
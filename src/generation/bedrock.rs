@@ -1,71 +1,213 @@
-use glam::{IVec2, IVec3, Vec3Swizzles};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{DVec2, IVec2, IVec3, Vec3Swizzles};
 use noise::{NoiseFn, Perlin};
 use rand::Rng;
 
-use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+use super::noise_util::{CacheCounters, CachedColumnSampler, ColumnSampler, DynNoise};
+use super::{Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+use crate::utility::{quantized_floor, CARDINAL4};
+
+/// How far above the bottom of the world the bedrock reaches at most
+const MAX_BEDROCK_HEIGHT: i32 = 5;
 
 
 
 #[derive(Debug, Clone)]
 pub struct Bedrock {
-  inner: BedrockGenerator
+  /// The noise used to vary the height of the bedrock, or `None` if the bedrock is flat
+  inner: Option<CachedColumnSampler<BedrockGenerator>>,
+  /// The z value of the bottom of the world, where the bedrock starts
+  min_z: i32
 }
 
 impl Bedrock {
-  pub fn new<R: Rng>(source_rng: &mut R) -> Self {
-    let inner = BedrockGenerator::new(source_rng.gen());
-    Bedrock { inner }
+  /// Bedrock starting at `min_z`, the bottom of the world
+  pub fn new<R: Rng>(source_rng: &mut R, flat: bool, min_z: i32) -> Self {
+    // The seed is always drawn so that the features generated after
+    // the bedrock are unaffected by whether or not it is flat
+    let noise = DynNoise::new(default_bedrock_noise(source_rng.gen()));
+    match flat {
+      true => Bedrock { inner: None, min_z },
+      false => Bedrock::with_noise(noise, min_z)
+    }
+  }
+
+  /// Bedrock whose height is given by a noise function instead of the one it would build from a seed, for constructing
+  /// particular bedrock floors. The bedrock reaches `noise` blocks above `min_z`, floored to a whole block and capped
+  /// at `MAX_BEDROCK_HEIGHT`, apart from single-column pits, which are filled in as usual.
+  pub fn with_noise(noise: DynNoise, min_z: i32) -> Self {
+    let inner = ColumnSampler::new(BedrockGenerator { inner: noise })
+      .with_offset(min_z as f64)
+      .cached();
+    Bedrock { inner: Some(inner), min_z }
+  }
+
+  /// The z value of the topmost bedrock block in the given column
+  pub fn height_at(&self, pos: IVec2) -> i32 {
+    match &self.inner {
+      Some(inner) => inner.height_at(pos).clamp(self.min_z, self.max_z()),
+      None => self.min_z
+    }
+  }
+
+  /// The highest that the bedrock can reach
+  fn max_z(&self) -> i32 {
+    self.min_z + MAX_BEDROCK_HEIGHT
   }
 
-  fn sample(&self, pos: IVec2) -> i32 {
-    (self.inner.get(pos.as_dvec2()) - 64.0).floor() as i32
+  /// The counters of this bedrock's noise cache, if it isn't flat
+  pub fn cache_counters(&self) -> impl Iterator<Item = (&'static str, &CacheCounters)> {
+    self.inner.iter().map(|inner| ("bedrock", inner.counters()))
   }
 }
 
 impl Geometry for Bedrock {
   fn bounding_box(&self) -> BoundingBox {
-    let min = IVec3::new(i32::MIN, i32::MIN, -64);
-    let max = IVec3::new(i32::MAX, i32::MAX, -60);
-    BoundingBox::new(min, max)
+    let max_z = if self.inner.is_some() { self.max_z() } else { self.min_z };
+    BoundingBox::unbounded_xy(self.min_z, max_z)
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    pos.z < -50 &&
-    pos.z <= self.sample(pos.xy()).max(-64)
+    pos.z >= self.min_z &&
+    pos.z <= self.height_at(pos.xy())
+  }
+}
+
+impl ColumnTop for Bedrock {
+  fn column_top(&self, xy: IVec2) -> Option<i32> {
+    Some(self.height_at(xy))
   }
 }
 
 impl MaterialGeometry for Bedrock {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     if self.block_at(pos) {
-      Some(super::blocks::BEDROCK)
+      Some(Cow::Borrowed(&super::blocks::BEDROCK))
     } else {
       None
     }
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(super::blocks::BEDROCK);
+  }
 }
 
 
 
+/// The default noise of the height of the bedrock
+fn default_bedrock_noise(seed: u32) -> noise::ScalePoint<super::MultiplyConstant<super::AddConstant<Perlin>>> {
+  const PHI: f64 = 1.61803398874989484820458683436563811;
+  Perlin::new(seed)
+    .add_constant(1.0)
+    .multiply_constant(2.5)
+    .scale_point_by(PHI * 10.0)
+}
+
+/// The height of the bedrock, with its single-column pits filled in
 #[derive(Debug, Clone)]
 struct BedrockGenerator {
-  inner: noise::ScalePoint<super::MultiplyConstant<super::AddConstant<noise::Perlin>>>
+  inner: DynNoise
 }
 
-impl BedrockGenerator {
-  fn new(seed: u32) -> Self {
-    const PHI: f64 = 1.61803398874989484820458683436563811;
-    let inner = Perlin::new(seed)
-      .add_constant(1.0)
-      .multiply_constant(2.5)
-      .scale_point_by(PHI * 10.0);
-    BedrockGenerator { inner }
+impl NoiseFn<f64, 2> for BedrockGenerator {
+  /// The noise is floored to a whole height at each column, and any column lying 2 or more blocks beneath all four of
+  /// its neighbors is raised to 1 block beneath the lowest of them, so that the bedrock has no single-column pits.
+  /// Raising a column to within 1 of its lowest neighbor can never leave that neighbor 2 or more blocks above it,
+  /// so one pass is enough.
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    let point = DVec2::from(point.into());
+    let height_at = |point: DVec2| quantized_floor(self.inner.get(point.to_array())) as f64;
+    let lowest_neighbor = CARDINAL4.iter()
+      .map(|&offset| height_at(point + offset.as_dvec2()))
+      .fold(f64::INFINITY, f64::min);
+    height_at(point).max(lowest_neighbor - 1.0)
   }
 }
 
-impl NoiseFn<f64, 2> for BedrockGenerator {
-  #[inline]
-  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
-    self.inner.get(point)
+
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+  use rand_xoshiro::Xoshiro256PlusPlus;
+
+  use super::*;
+  use crate::generation::fixtures::{ConstantNoise, LookupNoise, StepNoise};
+  use crate::generation::noise_util::tests::assert_cache_matches_sampler;
+
+  const MIN_Z: i32 = -64;
+
+  #[test]
+  fn constant_noise_gives_a_level_bedrock() {
+    let bedrock = Bedrock::with_noise(DynNoise::new(ConstantNoise { value: 2.5 }), MIN_Z);
+    for pos in [IVec2::ZERO, IVec2::new(-100, 37), IVec2::new(5000, -5000)] {
+      assert_eq!(bedrock.height_at(pos), MIN_Z + 2, "at {}", pos);
+      assert!(bedrock.block_at(pos.extend(MIN_Z)) && bedrock.block_at(pos.extend(MIN_Z + 2)), "at {}", pos);
+      assert!(!bedrock.block_at(pos.extend(MIN_Z + 3)) && !bedrock.block_at(pos.extend(MIN_Z - 1)), "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn the_bedrock_is_capped_at_its_maximum_height() {
+    let bedrock = Bedrock::with_noise(DynNoise::new(ConstantNoise { value: 40.0 }), MIN_Z);
+    assert_eq!(bedrock.height_at(IVec2::ZERO), MIN_Z + MAX_BEDROCK_HEIGHT);
+    assert_eq!(bedrock.bounding_box().max.z, MIN_Z + MAX_BEDROCK_HEIGHT);
+
+    let bedrock = Bedrock::with_noise(DynNoise::new(ConstantNoise { value: -3.0 }), MIN_Z);
+    assert_eq!(bedrock.height_at(IVec2::ZERO), MIN_Z);
+  }
+
+  #[test]
+  fn the_bedrock_steps_where_its_noise_does() {
+    let noise = StepNoise { step_x: 0.0, below: 1.0, above: 4.0 };
+    let bedrock = Bedrock::with_noise(DynNoise::new(noise), MIN_Z);
+    assert_eq!(bedrock.height_at(IVec2::new(-1, 0)), MIN_Z + 1);
+    assert_eq!(bedrock.height_at(IVec2::new(0, 0)), MIN_Z + 4);
+    assert_eq!(bedrock.height_at(IVec2::new(-20, 9)), MIN_Z + 1);
+    assert_eq!(bedrock.height_at(IVec2::new(20, 9)), MIN_Z + 4);
+  }
+
+  #[test]
+  fn single_column_pits_are_filled_in() {
+    // A single-column pit at 1,1 and a two-column trough at 1..=2,3, surrounded by bedrock 4 blocks high
+    let rows = vec![
+      vec![4.0, 4.0, 4.0, 4.0],
+      vec![4.0, 0.0, 4.0, 4.0],
+      vec![4.0, 4.0, 4.0, 4.0],
+      vec![4.0, 0.0, 0.0, 4.0]
+    ];
+    let bedrock = Bedrock::with_noise(DynNoise::new(LookupNoise::new(IVec2::ZERO, rows, 4.0)), MIN_Z);
+    assert_eq!(bedrock.height_at(IVec2::new(1, 1)), MIN_Z + 3);
+    assert_eq!(bedrock.height_at(IVec2::new(0, 1)), MIN_Z + 4);
+    // Each column of the trough has a neighbor as low as itself, so the trough is left as it is
+    assert_eq!(bedrock.height_at(IVec2::new(1, 3)), MIN_Z);
+    assert_eq!(bedrock.height_at(IVec2::new(2, 3)), MIN_Z);
+  }
+
+  #[test]
+  fn the_bedrock_is_watertight_across_a_large_area() {
+    for seed in 0..4 {
+      let bedrock = Bedrock::new(&mut Xoshiro256PlusPlus::seed_from_u64(seed), false, MIN_Z);
+      for y in -128..128 {
+        for x in -128..128 {
+          let pos = IVec2::new(x, y);
+          assert!(bedrock.block_at(pos.extend(MIN_Z)), "seed {}: no bedrock at the bottom of {}", seed, pos);
+          let height = bedrock.height_at(pos);
+          assert!((MIN_Z..=MIN_Z + MAX_BEDROCK_HEIGHT).contains(&height), "seed {}: {} at {}", seed, height, pos);
+          let pit = CARDINAL4.iter().all(|&offset| bedrock.height_at(pos + offset) >= height + 2);
+          assert!(!pit, "seed {}: a pit at {}", seed, pos);
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn cached_heights_match_the_uncached_noise() {
+    let bedrock = Bedrock::new(&mut Xoshiro256PlusPlus::seed_from_u64(21), false, MIN_Z);
+    let inner = bedrock.inner.as_ref().unwrap();
+    assert_cache_matches_sampler(inner, IVec2::new(-50, -50), IVec2::new(49, 49));
   }
 }
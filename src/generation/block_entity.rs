@@ -0,0 +1,492 @@
+//! Blocks which carry extra data, like chests and signs, which must be attached to a chunk separately from its blocks.
+use std::collections::BTreeMap;
+
+use glam::IVec3;
+use pyo3::types::PyDict;
+use pyo3::{PyAny, PyObject, PyResult, Python, ToPyObject};
+
+use super::Block;
+use crate::world_coords::to_minecraft;
+
+/// How deeply lists and compounds may be nested in NBT that is read, which is far more than any real file needs
+const MAX_READ_DEPTH: usize = 512;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntity {
+  pub pos: IVec3,
+  /// The block that must be present at `pos` for this block entity to be valid
+  pub block: Block,
+  /// The namespaced id of this block entity, like `minecraft:chest`
+  pub id: &'static str,
+  pub nbt: BTreeMap<String, Nbt>
+}
+
+impl BlockEntity {
+  pub fn new(pos: IVec3, block: Block, id: &'static str) -> Self {
+    BlockEntity { pos, block, id, nbt: BTreeMap::new() }
+  }
+
+  pub fn with(mut self, key: &str, value: Nbt) -> Self {
+    self.nbt.insert(key.to_owned(), value);
+    self
+  }
+
+  /// The namespace and base name of this block entity's id, like `minecraft` and `chest`
+  pub fn split_id(&self) -> (&'static str, &'static str) {
+    self.id.split_once(':').unwrap_or(("minecraft", self.id))
+  }
+
+  /// Creates an `amulet.api.block_entity.BlockEntity` instance
+  pub fn into_amulet_block_entity(self, py: Python) -> PyResult<PyObject> {
+    let amulet = py.import("amulet")?;
+    let amulet_nbt = py.import("amulet_nbt")?;
+    let block_entity_class = amulet.getattr("api")?.getattr("block_entity")?.getattr("BlockEntity")?;
+    let (namespace, base_name) = self.split_id();
+    let (x, y, z) = to_minecraft(self.pos);
+    let nbt = Nbt::Compound(self.nbt).into_amulet_nbt(py, amulet_nbt)?;
+    let nbt_file = amulet_nbt.getattr("NBTFile")?.call1((nbt,))?;
+    Ok(block_entity_class.call1((namespace, base_name, x, y, z, nbt_file))?.into())
+  }
+}
+
+/// Every NBT tag, so that files like level.dat can be read and written back without losing anything
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+  Byte(i8),
+  Short(i16),
+  Int(i32),
+  Long(i64),
+  Float(f32),
+  Double(f64),
+  ByteArray(Vec<i8>),
+  String(String),
+  List(NbtList),
+  Compound(BTreeMap<String, Nbt>),
+  IntArray(Vec<i32>),
+  LongArray(Vec<i64>)
+}
+
+impl Nbt {
+  pub fn compound<const N: usize>(entries: [(&str, Nbt); N]) -> Self {
+    Nbt::Compound(entries.into_iter().map(|(key, value)| (key.to_owned(), value)).collect())
+  }
+
+  /// A list of the given tags, or `None` if they aren't all of the same type, which NBT requires
+  pub fn list(values: Vec<Nbt>) -> Option<Self> {
+    NbtList::new(values).map(Nbt::List)
+  }
+
+  /// The tag stored under the given key, if this is a compound containing one
+  pub fn get(&self, key: &str) -> Option<&Nbt> {
+    match self {
+      Nbt::Compound(entries) => entries.get(key),
+      _ => None
+    }
+  }
+
+  /// The entries of this tag, if it is a compound
+  pub fn entries_mut(&mut self) -> Option<&mut BTreeMap<String, Nbt>> {
+    match self {
+      Nbt::Compound(entries) => Some(entries),
+      _ => None
+    }
+  }
+
+  /// Writes this tag in Minecraft's uncompressed binary NBT format, as an unnamed root tag
+  pub fn write_binary(&self, out: &mut Vec<u8>) {
+    self.write_root(out, ByteOrder::BigEndian);
+  }
+
+  /// Writes this tag in the little endian variant of the binary NBT format used by Bedrock edition's files
+  pub fn write_binary_little_endian(&self, out: &mut Vec<u8>) {
+    self.write_root(out, ByteOrder::LittleEndian);
+  }
+
+  /// Reads a tag in Minecraft's uncompressed binary NBT format, discarding the name of the root tag
+  pub fn read_binary(data: &[u8]) -> Option<Self> {
+    Nbt::read_root(data, ByteOrder::BigEndian)
+  }
+
+  /// Reads a tag in the little endian variant of the binary NBT format, discarding the name of the root tag
+  pub fn read_binary_little_endian(data: &[u8]) -> Option<Self> {
+    Nbt::read_root(data, ByteOrder::LittleEndian)
+  }
+
+  fn read_root(data: &[u8], order: ByteOrder) -> Option<Self> {
+    let mut reader = NbtReader { data, order };
+    let tag_id = reader.u8()?;
+    reader.string()?;
+    Nbt::read_payload(&mut reader, tag_id, 0)
+  }
+
+  fn read_payload(reader: &mut NbtReader, tag_id: u8, depth: usize) -> Option<Self> {
+    if depth > MAX_READ_DEPTH { return None };
+    Some(match tag_id {
+      1 => Nbt::Byte(reader.u8()? as i8),
+      2 => Nbt::Short(reader.i16()?),
+      3 => Nbt::Int(reader.i32()?),
+      4 => Nbt::Long(reader.i64()?),
+      5 => Nbt::Float(f32::from_bits(reader.i32()? as u32)),
+      6 => Nbt::Double(f64::from_bits(reader.i64()? as u64)),
+      7 => Nbt::ByteArray(reader.array(|reader| Some(reader.u8()? as i8))?),
+      8 => Nbt::String(reader.string()?),
+      9 => {
+        let element_id = reader.u8()?;
+        // Every element is read as the same type, so the list can't be mixed
+        Nbt::List(NbtList(reader.array(|reader| Nbt::read_payload(reader, element_id, depth + 1))?))
+      },
+      10 => {
+        let mut entries = BTreeMap::new();
+        loop {
+          match reader.u8()? {
+            0 => break,
+            tag_id => {
+              let key = reader.string()?;
+              entries.insert(key, Nbt::read_payload(reader, tag_id, depth + 1)?);
+            }
+          };
+        };
+
+        Nbt::Compound(entries)
+      },
+      11 => Nbt::IntArray(reader.array(NbtReader::i32)?),
+      12 => Nbt::LongArray(reader.array(NbtReader::i64)?),
+      _ => return None
+    })
+  }
+
+  fn write_root(&self, out: &mut Vec<u8>, order: ByteOrder) {
+    out.push(self.tag_id());
+    write_nbt_string(out, "", order);
+    self.write_payload(out, order);
+  }
+
+  fn tag_id(&self) -> u8 {
+    match self {
+      Nbt::Byte(_) => 1,
+      Nbt::Short(_) => 2,
+      Nbt::Int(_) => 3,
+      Nbt::Long(_) => 4,
+      Nbt::Float(_) => 5,
+      Nbt::Double(_) => 6,
+      Nbt::ByteArray(_) => 7,
+      Nbt::String(_) => 8,
+      Nbt::List(_) => 9,
+      Nbt::Compound(_) => 10,
+      Nbt::IntArray(_) => 11,
+      Nbt::LongArray(_) => 12
+    }
+  }
+
+  fn write_payload(&self, out: &mut Vec<u8>, order: ByteOrder) {
+    match self {
+      Nbt::Byte(value) => out.push(*value as u8),
+      Nbt::Short(value) => out.extend_from_slice(&order.u16_bytes(*value as u16)),
+      Nbt::Int(value) => out.extend_from_slice(&order.i32_bytes(*value)),
+      Nbt::Long(value) => out.extend_from_slice(&order.i64_bytes(*value)),
+      Nbt::Float(value) => out.extend_from_slice(&order.i32_bytes(value.to_bits() as i32)),
+      Nbt::Double(value) => out.extend_from_slice(&order.i64_bytes(value.to_bits() as i64)),
+      Nbt::ByteArray(values) => {
+        out.extend_from_slice(&order.i32_bytes(values.len() as i32));
+        out.extend(values.iter().map(|&value| value as u8));
+      },
+      Nbt::String(value) => write_nbt_string(out, value, order),
+      Nbt::List(list) => {
+        out.push(list.element_tag_id());
+        out.extend_from_slice(&order.i32_bytes(list.0.len() as i32));
+        for value in &list.0 {
+          value.write_payload(out, order);
+        };
+      },
+      Nbt::Compound(entries) => {
+        for (key, value) in entries {
+          out.push(value.tag_id());
+          write_nbt_string(out, key, order);
+          value.write_payload(out, order);
+        };
+
+        out.push(0);
+      },
+      Nbt::IntArray(values) => {
+        out.extend_from_slice(&order.i32_bytes(values.len() as i32));
+        for &value in values {
+          out.extend_from_slice(&order.i32_bytes(value));
+        };
+      },
+      Nbt::LongArray(values) => {
+        out.extend_from_slice(&order.i32_bytes(values.len() as i32));
+        for &value in values {
+          out.extend_from_slice(&order.i64_bytes(value));
+        };
+      }
+    };
+  }
+
+  fn into_amulet_nbt(self, py: Python, amulet_nbt: &PyAny) -> PyResult<PyObject> {
+    Ok(match self {
+      Nbt::Byte(value) => amulet_nbt.getattr("TAG_Byte")?.call1((value,))?.into(),
+      Nbt::Short(value) => amulet_nbt.getattr("TAG_Short")?.call1((value,))?.into(),
+      Nbt::Int(value) => amulet_nbt.getattr("TAG_Int")?.call1((value,))?.into(),
+      Nbt::Long(value) => amulet_nbt.getattr("TAG_Long")?.call1((value,))?.into(),
+      Nbt::Float(value) => amulet_nbt.getattr("TAG_Float")?.call1((value,))?.into(),
+      Nbt::Double(value) => amulet_nbt.getattr("TAG_Double")?.call1((value,))?.into(),
+      Nbt::ByteArray(values) => amulet_nbt.getattr("TAG_Byte_Array")?.call1((values.to_object(py),))?.into(),
+      Nbt::String(value) => amulet_nbt.getattr("TAG_String")?.call1((value,))?.into(),
+      Nbt::List(list) => {
+        let values = list.0.into_iter()
+          .map(|value| value.into_amulet_nbt(py, amulet_nbt))
+          .collect::<PyResult<Vec<PyObject>>>()?;
+        amulet_nbt.getattr("TAG_List")?.call1((values.to_object(py),))?.into()
+      },
+      Nbt::Compound(entries) => {
+        let dict = PyDict::new(py);
+        for (key, value) in entries {
+          dict.set_item(key, value.into_amulet_nbt(py, amulet_nbt)?)?;
+        };
+
+        amulet_nbt.getattr("TAG_Compound")?.call1((dict,))?.into()
+      },
+      Nbt::IntArray(values) => amulet_nbt.getattr("TAG_Int_Array")?.call1((values.to_object(py),))?.into(),
+      Nbt::LongArray(values) => amulet_nbt.getattr("TAG_Long_Array")?.call1((values.to_object(py),))?.into()
+    })
+  }
+}
+
+/// A list of NBT tags, which are all of the same type
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NbtList(Vec<Nbt>);
+
+impl NbtList {
+  /// A list of the given tags, or `None` if they aren't all of the same type
+  pub fn new(values: Vec<Nbt>) -> Option<Self> {
+    let tag_id = values.first().map(Nbt::tag_id);
+    values.iter().all(|value| Some(value.tag_id()) == tag_id).then(|| NbtList(values))
+  }
+
+  pub fn values(&self) -> &[Nbt] {
+    &self.0
+  }
+
+  /// Empty lists are written with the element type of `TAG_End`
+  fn element_tag_id(&self) -> u8 {
+    self.0.first().map_or(0, Nbt::tag_id)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+  BigEndian,
+  LittleEndian
+}
+
+impl ByteOrder {
+  fn u16_from(self, bytes: [u8; 2]) -> u16 {
+    match self {
+      ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+      ByteOrder::LittleEndian => u16::from_le_bytes(bytes)
+    }
+  }
+
+  fn i32_from(self, bytes: [u8; 4]) -> i32 {
+    match self {
+      ByteOrder::BigEndian => i32::from_be_bytes(bytes),
+      ByteOrder::LittleEndian => i32::from_le_bytes(bytes)
+    }
+  }
+
+  fn i64_from(self, bytes: [u8; 8]) -> i64 {
+    match self {
+      ByteOrder::BigEndian => i64::from_be_bytes(bytes),
+      ByteOrder::LittleEndian => i64::from_le_bytes(bytes)
+    }
+  }
+
+  fn u16_bytes(self, value: u16) -> [u8; 2] {
+    match self {
+      ByteOrder::BigEndian => value.to_be_bytes(),
+      ByteOrder::LittleEndian => value.to_le_bytes()
+    }
+  }
+
+  fn i32_bytes(self, value: i32) -> [u8; 4] {
+    match self {
+      ByteOrder::BigEndian => value.to_be_bytes(),
+      ByteOrder::LittleEndian => value.to_le_bytes()
+    }
+  }
+
+  fn i64_bytes(self, value: i64) -> [u8; 8] {
+    match self {
+      ByteOrder::BigEndian => value.to_be_bytes(),
+      ByteOrder::LittleEndian => value.to_le_bytes()
+    }
+  }
+}
+
+/// Reads the parts of a binary NBT tag in either byte order, returning `None` if the data ends too early
+struct NbtReader<'a> {
+  data: &'a [u8],
+  order: ByteOrder
+}
+
+impl<'a> NbtReader<'a> {
+  fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+    let (bytes, rest) = (self.data.get(..N)?, &self.data[N..]);
+    self.data = rest;
+    Some(bytes.try_into().unwrap())
+  }
+
+  fn u8(&mut self) -> Option<u8> {
+    self.take::<1>().map(|[byte]| byte)
+  }
+
+  fn i16(&mut self) -> Option<i16> {
+    self.take().map(|bytes| self.order.u16_from(bytes) as i16)
+  }
+
+  fn i32(&mut self) -> Option<i32> {
+    self.take().map(|bytes| self.order.i32_from(bytes))
+  }
+
+  fn i64(&mut self) -> Option<i64> {
+    self.take().map(|bytes| self.order.i64_from(bytes))
+  }
+
+  /// Strings are read as UTF-8, replacing anything invalid, which only differs from Java's modified UTF-8
+  /// for null and supplementary characters
+  fn string(&mut self) -> Option<String> {
+    let len = self.take().map(|bytes| self.order.u16_from(bytes))? as usize;
+    let bytes = self.data.get(..len)?;
+    self.data = &self.data[len..];
+    Some(String::from_utf8_lossy(bytes).into_owned())
+  }
+
+  /// Reads a length followed by that many elements
+  fn array<T>(&mut self, mut read: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+    let len = self.i32()?.max(0) as usize;
+    // Every element takes up at least a byte, which stops a corrupt length from allocating too much
+    let mut values = Vec::with_capacity(len.min(self.data.len()));
+    for _ in 0..len {
+      values.push(read(self)?);
+    };
+
+    Some(values)
+  }
+}
+
+/// Strings are written as UTF-8 rather than Java's modified UTF-8,
+/// which only differ for null and supplementary characters
+fn write_nbt_string(out: &mut Vec<u8>, value: &str, order: ByteOrder) {
+  out.extend_from_slice(&order.u16_bytes(value.len() as u16));
+  out.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A compound holding every type of tag, including lists of lists and of compounds
+  fn every_tag() -> Nbt {
+    let list = |values| Nbt::list(values).unwrap();
+    Nbt::compound([
+      ("byte", Nbt::Byte(-128)),
+      ("short", Nbt::Short(-12345)),
+      ("int", Nbt::Int(0x1234_5678)),
+      ("long", Nbt::Long(i64::MIN + 1)),
+      ("float", Nbt::Float(-1.5e-7)),
+      ("double", Nbt::Double(std::f64::consts::PI)),
+      ("byte array", Nbt::ByteArray(vec![-1, 0, 1, 127])),
+      ("string", Nbt::String("Tower 14-B, ☁ above".to_owned())),
+      ("empty string", Nbt::String(String::new())),
+      ("list", list(vec![Nbt::Short(1), Nbt::Short(-2)])),
+      ("empty list", list(Vec::new())),
+      ("nested lists", list(vec![list(vec![Nbt::Int(1)]), list(Vec::new())])),
+      ("compounds", list(vec![Nbt::compound([("a", Nbt::Byte(1))]), Nbt::compound([])])),
+      ("compound", Nbt::compound([("inner", Nbt::compound([("long array", Nbt::LongArray(vec![i64::MAX, -7]))]))])),
+      ("int array", Nbt::IntArray(vec![i32::MIN, 0, i32::MAX])),
+      ("long array", Nbt::LongArray(Vec::new()))
+    ])
+  }
+
+  fn write(nbt: &Nbt, order: ByteOrder) -> Vec<u8> {
+    let mut out = Vec::new();
+    nbt.write_root(&mut out, order);
+    out
+  }
+
+  /// A tag wrapped in the given number of lists
+  fn nested(depth: usize) -> Nbt {
+    (0..depth).fold(Nbt::Int(0), |nbt, _| Nbt::list(vec![nbt]).unwrap())
+  }
+
+  #[test]
+  fn every_tag_round_trips_in_both_byte_orders() {
+    let nbt = every_tag();
+    let mut big_endian = Vec::new();
+    nbt.write_binary(&mut big_endian);
+    assert_eq!(Nbt::read_binary(&big_endian), Some(nbt.clone()));
+    let mut little_endian = Vec::new();
+    nbt.write_binary_little_endian(&mut little_endian);
+    assert_eq!(Nbt::read_binary_little_endian(&little_endian), Some(nbt.clone()));
+    assert_ne!(big_endian, little_endian);
+
+    // Each tag on its own as the root, as well as within the compound
+    let entries = match &nbt {
+      Nbt::Compound(entries) => entries,
+      _ => unreachable!()
+    };
+
+    for (key, value) in entries {
+      for order in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+        assert_eq!(Nbt::read_root(&write(value, order), order).as_ref(), Some(value), "{} in {:?}", key, order);
+      };
+    };
+  }
+
+  #[test]
+  fn tags_are_written_in_the_given_byte_order() {
+    let nbt = Nbt::compound([("a", Nbt::Int(1))]);
+    assert_eq!(write(&nbt, ByteOrder::BigEndian), [10, 0, 0, 3, 0, 1, b'a', 0, 0, 0, 1, 0]);
+    assert_eq!(write(&nbt, ByteOrder::LittleEndian), [10, 0, 0, 3, 1, 0, b'a', 1, 0, 0, 0, 0]);
+  }
+
+  #[test]
+  fn empty_lists_have_the_element_type_of_tag_end() {
+    let empty = Nbt::list(Vec::new()).unwrap();
+    assert_eq!(write(&empty, ByteOrder::BigEndian), [9, 0, 0, 0, 0, 0, 0, 0]);
+    // Minecraft also writes empty lists of other types, which are read as the same empty list
+    assert_eq!(Nbt::read_binary(&[9, 0, 0, 10, 0, 0, 0, 0]), Some(empty));
+    // But a list of `TAG_End` can't have anything in it
+    assert_eq!(Nbt::read_binary(&[9, 0, 0, 0, 0, 0, 0, 1]), None);
+  }
+
+  #[test]
+  fn mixed_lists_are_rejected() {
+    assert_eq!(NbtList::new(vec![Nbt::Int(1), Nbt::Long(1)]), None);
+    assert_eq!(Nbt::list(vec![Nbt::Int(1), Nbt::String("1".to_owned()), Nbt::Int(2)]), None);
+    assert_eq!(Nbt::list(vec![nested(1), Nbt::Int(0)]), None);
+    assert!(Nbt::list(vec![nested(1), nested(2)]).is_some(), "lists of lists of different types are allowed");
+  }
+
+  #[test]
+  fn nesting_deeper_than_the_limit_is_rejected() {
+    for order in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+      let deepest = nested(MAX_READ_DEPTH);
+      assert_eq!(Nbt::read_root(&write(&deepest, order), order), Some(deepest));
+      assert_eq!(Nbt::read_root(&write(&nested(MAX_READ_DEPTH + 1), order), order), None);
+    };
+
+    // Compounds count towards the depth as well
+    let compounds = (0..MAX_READ_DEPTH + 1).fold(Nbt::Int(0), |nbt, _| Nbt::compound([("", nbt)]));
+    assert_eq!(Nbt::read_binary(&write(&compounds, ByteOrder::BigEndian)), None);
+  }
+
+  #[test]
+  fn truncated_tags_are_rejected() {
+    let data = write(&every_tag(), ByteOrder::BigEndian);
+    for len in 0..data.len() {
+      assert_eq!(Nbt::read_binary(&data[..len]), None, "{} of {} bytes", len, data.len());
+    };
+  }
+}
@@ -17,13 +17,98 @@ macro_rules! const_block {
   };
 }
 
+pub const AIR: Block = const_block!("minecraft:air");
+
 pub const GRAVEL: Block = const_block!("minecraft:gravel");
+pub const COARSE_DIRT: Block = const_block!("minecraft:coarse_dirt");
+pub const SAND: Block = const_block!("minecraft:sand");
 pub const DEEPSLATE: Block = const_block!("minecraft:deepslate");
+pub const TUFF: Block = const_block!("minecraft:tuff");
 pub const BEDROCK: Block = const_block!("minecraft:bedrock");
 
 pub const WATER: Block = const_block!("minecraft:water");
+pub const SOUL_SAND: Block = const_block!("minecraft:soul_sand");
+pub const MAGMA_BLOCK: Block = const_block!("minecraft:magma_block");
+pub const GLASS: Block = const_block!("minecraft:glass");
 pub const SEAGRASS_SHORT: Block = const_block!("minecraft:seagrass", "minecraft:water");
 pub const SEAGRASS_TALL_UPPER: Block = const_block!("minecraft:tall_seagrass[half=upper]", "minecraft:water");
 pub const SEAGRASS_TALL_LOWER: Block = const_block!("minecraft:tall_seagrass[half=lower]", "minecraft:water");
 
 pub const GRAY_CONCRETE: Block = const_block!("minecraft:gray_concrete");
+pub const LIGHT_GRAY_CONCRETE: Block = const_block!("minecraft:light_gray_concrete");
+pub const CALCITE: Block = const_block!("minecraft:calcite");
+pub const STONE_BRICKS: Block = const_block!("minecraft:stone_bricks");
+pub const POLISHED_ANDESITE: Block = const_block!("minecraft:polished_andesite");
+pub const DEEPSLATE_TILES: Block = const_block!("minecraft:deepslate_tiles");
+pub const CRACKED_STONE_BRICKS: Block = const_block!("minecraft:cracked_stone_bricks");
+pub const ANDESITE: Block = const_block!("minecraft:andesite");
+pub const SMOOTH_STONE_SLAB_BOTTOM: Block = const_block!("minecraft:smooth_stone_slab[type=bottom]");
+pub const DARK_OAK_FENCE_NORTH_SOUTH: Block = const_block!("minecraft:dark_oak_fence[north=true,south=true]");
+pub const DARK_OAK_FENCE_EAST_WEST: Block = const_block!("minecraft:dark_oak_fence[east=true,west=true]");
+pub const CHAIN_VERTICAL: Block = const_block!("minecraft:chain[axis=y]");
+pub const CHAIN_EAST_WEST: Block = const_block!("minecraft:chain[axis=x]");
+pub const CHAIN_NORTH_SOUTH: Block = const_block!("minecraft:chain[axis=z]");
+pub const IRON_BARS: Block = const_block!("minecraft:iron_bars");
+pub const SEA_LANTERN: Block = const_block!("minecraft:sea_lantern");
+pub const SHROOMLIGHT: Block = const_block!("minecraft:shroomlight");
+pub const WHITE_CONCRETE: Block = const_block!("minecraft:white_concrete");
+pub const DARK_OAK_FENCE: Block = const_block!("minecraft:dark_oak_fence");
+pub const LANTERN: Block = const_block!("minecraft:lantern");
+pub const POLISHED_DEEPSLATE: Block = const_block!("minecraft:polished_deepslate");
+pub const IRON_BLOCK: Block = const_block!("minecraft:iron_block");
+pub const SMOOTH_QUARTZ: Block = const_block!("minecraft:smooth_quartz");
+pub const CHEST_SOUTH: Block = const_block!("minecraft:chest[facing=south]");
+pub const OAK_WALL_SIGN_NORTH: Block = const_block!("minecraft:oak_wall_sign[facing=north]");
+pub const LANTERN_HANGING: Block = const_block!("minecraft:lantern[hanging=true]");
+pub const POLISHED_BASALT: Block = const_block!("minecraft:polished_basalt[axis=y]");
+pub const BASALT: Block = const_block!("minecraft:basalt[axis=y]");
+pub const SMOOTH_STONE_SLAB_TOP: Block = const_block!("minecraft:smooth_stone_slab[type=top]");
+pub const STONE_BRICK_STAIRS_NORTH: Block = const_block!("minecraft:stone_brick_stairs[facing=north]");
+pub const STONE_BRICK_STAIRS_EAST: Block = const_block!("minecraft:stone_brick_stairs[facing=east]");
+pub const STONE_BRICK_STAIRS_SOUTH: Block = const_block!("minecraft:stone_brick_stairs[facing=south]");
+pub const STONE_BRICK_STAIRS_WEST: Block = const_block!("minecraft:stone_brick_stairs[facing=west]");
+pub const LADDER_SOUTH: Block = const_block!("minecraft:ladder[facing=south]");
+pub const LADDER_NORTH: Block = const_block!("minecraft:ladder[facing=north]");
+pub const LADDER_EAST: Block = const_block!("minecraft:ladder[facing=east]");
+pub const LADDER_WEST: Block = const_block!("minecraft:ladder[facing=west]");
+pub const SPRUCE_TRAPDOOR_TOP_SOUTH: Block = const_block!("minecraft:spruce_trapdoor[facing=south,half=top]");
+pub const DEEPSLATE_BRICKS: Block = const_block!("minecraft:deepslate_bricks");
+pub const COBBLED_DEEPSLATE: Block = const_block!("minecraft:cobbled_deepslate");
+pub const COBBLED_DEEPSLATE_SLAB_BOTTOM: Block = const_block!("minecraft:cobbled_deepslate_slab[type=bottom]");
+pub const BARREL_NORTH: Block = const_block!("minecraft:barrel[facing=north]");
+pub const BARREL_EAST: Block = const_block!("minecraft:barrel[facing=east]");
+pub const BARREL_SOUTH: Block = const_block!("minecraft:barrel[facing=south]");
+pub const BARREL_WEST: Block = const_block!("minecraft:barrel[facing=west]");
+pub const SPRUCE_PLANKS: Block = const_block!("minecraft:spruce_planks");
+pub const BEACON: Block = const_block!("minecraft:beacon");
+pub const RED_STAINED_GLASS: Block = const_block!("minecraft:red_stained_glass");
+pub const YELLOW_STAINED_GLASS: Block = const_block!("minecraft:yellow_stained_glass");
+pub const BLUE_STAINED_GLASS: Block = const_block!("minecraft:blue_stained_glass");
+pub const LIME_STAINED_GLASS: Block = const_block!("minecraft:lime_stained_glass");
+pub const MAGENTA_STAINED_GLASS: Block = const_block!("minecraft:magenta_stained_glass");
+pub const CYAN_STAINED_GLASS: Block = const_block!("minecraft:cyan_stained_glass");
+pub const ORANGE_STAINED_GLASS: Block = const_block!("minecraft:orange_stained_glass");
+pub const PURPLE_STAINED_GLASS: Block = const_block!("minecraft:purple_stained_glass");
+
+pub const MOSS_BLOCK: Block = const_block!("minecraft:moss_block");
+pub const GRASS_BLOCK: Block = const_block!("minecraft:grass_block");
+pub const AZALEA: Block = const_block!("minecraft:azalea");
+pub const FLOWERING_AZALEA: Block = const_block!("minecraft:flowering_azalea");
+pub const OAK_LEAVES: Block = const_block!("minecraft:oak_leaves[persistent=true]");
+
+
+
+/// The shades that the city's concrete fades through from its lowest layer up to its highest, darkest first.
+/// Landmasses use one of the first three, and their concrete buildings the next shade up.
+pub static ALTITUDE_SHADES: [Block; 4] = [DEEPSLATE_TILES, GRAY_CONCRETE, LIGHT_GRAY_CONCRETE, CALCITE];
+
+/// The darker variant of a wall material used where it has been stained by weathering, if it has one
+pub fn stained(material: &Block) -> Option<Block> {
+  match material.base_blockstate() {
+    "minecraft:gray_concrete" => Some(DEEPSLATE_TILES),
+    "minecraft:light_gray_concrete" => Some(GRAY_CONCRETE),
+    "minecraft:stone_bricks" => Some(CRACKED_STONE_BRICKS),
+    "minecraft:polished_andesite" => Some(ANDESITE),
+    _ => None
+  }
+}
@@ -0,0 +1,252 @@
+//! This geometry module generates the following features:
+//!
+//! - Bubble column lifts standing on the sea floor beside each of the lowest layer's pillars, one carrying players up
+//!   on the pillar's north face and one carrying them down on its south face.
+//!
+//! Each lift is a single column of water source blocks, from just above the soul sand or magma at its foot up to the
+//! surface of the ocean, inside a ring of glass which keeps it apart from the water around it.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::ocean::Ocean;
+use super::{blocks, Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+
+/// The z value of the surface of the ocean, which every lift reaches up to
+const SEA_LEVEL: i32 = 0;
+/// The gap between the outline of a pillar and the water column of a lift beside it, which the glass fills
+const PILLAR_GAP: i32 = 1;
+
+/// Which way a lift carries whatever is inside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Current {
+  /// Soul sand at the foot of the column
+  Up,
+  /// Magma at the foot of the column
+  Down
+}
+
+#[derive(Debug, Clone)]
+struct BubbleLift {
+  column: IVec2,
+  /// The z value of the soul sand or magma at the foot of the column, level with the top of the sea floor
+  floor: i32,
+  current: Current
+}
+
+impl BubbleLift {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::try_new((self.column - 1).extend(self.floor), (self.column + 1).extend(SEA_LEVEL)).unwrap()
+  }
+
+  fn block_material_at(&self, pos: IVec3) -> Option<&'static Block> {
+    if !(self.floor..=SEA_LEVEL).contains(&pos.z) { return None };
+    match (pos.xy() - self.column).abs().max_element() {
+      0 if pos.z == self.floor => Some(match self.current {
+        Current::Up => &blocks::SOUL_SAND,
+        Current::Down => &blocks::MAGMA_BLOCK
+      }),
+      0 => Some(&blocks::WATER),
+      1 => Some(&blocks::GLASS),
+      _ => None
+    }
+  }
+}
+
+/// Every bubble lift in the world
+#[derive(Debug, Clone)]
+pub struct BubbleLifts {
+  lifts: Vec<BubbleLift>,
+  bounding_box: BoundingBox
+}
+
+impl BubbleLifts {
+  /// Places a lift on the north and south face of each of the given pillars, given by their middle and radius,
+  /// standing on the given sea floor. Lifts are left out where the sea floor reaches the surface of the ocean,
+  /// or where their glass would take the place of any of the columns for which `is_pillar_column` is true.
+  pub fn beside_pillars(footings: &[(IVec2, u32)], floor: &Ocean, is_pillar_column: impl Fn(IVec2) -> bool) -> Self {
+    let lifts = footings.iter()
+      .flat_map(|&(origin, radius)| {
+        // Far enough out that the ring of glass around the water just touches the pillar
+        let distance = radius as i32 + PILLAR_GAP + 1;
+        [(Current::Up, IVec2::new(0, -distance)), (Current::Down, IVec2::new(0, distance))]
+          .map(|(current, offset)| (current, origin + offset))
+      })
+      .filter_map(|(current, column)| {
+        let floor = floor.floor_height_at(column) - 1;
+        let is_clear = (-1..=1).all(|x| (-1..=1).all(|y| !is_pillar_column(column + IVec2::new(x, y))));
+        (floor < SEA_LEVEL && is_clear).then(|| BubbleLift { column, floor, current })
+      })
+      .collect::<Vec<BubbleLift>>();
+    BubbleLifts::new(lifts)
+  }
+
+  /// A box containing every lift that `beside_pillars` could place beside pillars standing within the given columns
+  pub fn bounding_box_around(pillars: BoundingBox) -> BoundingBox {
+    // The glass around a lift reaches one block further out than its water
+    let reach = IVec2::splat(PILLAR_GAP + 2);
+    let min = (pillars.min.xy() - reach).extend(crate::WORLD_MIN_Z);
+    let max = (pillars.max.xy() + reach).extend(SEA_LEVEL);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  /// No lifts at all
+  pub fn none() -> Self {
+    BubbleLifts::new(Vec::new())
+  }
+
+  fn new(lifts: Vec<BubbleLift>) -> Self {
+    // Without any lifts, the bounding box is a single block at the bottom of the world, which has nothing in it
+    let bottom = IVec2::ZERO.extend(crate::WORLD_MIN_Z);
+    let bounding_box = lifts.iter()
+      .map(BubbleLift::bounding_box)
+      .reduce(BoundingBox::join)
+      .unwrap_or_else(|| BoundingBox::new(bottom, bottom));
+    BubbleLifts { lifts, bounding_box }
+  }
+
+  /// The soul sand or magma at the foot of each lift
+  pub fn feet(&self) -> impl Iterator<Item = IVec3> + '_ {
+    self.lifts.iter().map(|lift| lift.column.extend(lift.floor))
+  }
+
+  fn lift_at(&self, pos: IVec3) -> Option<&'static Block> {
+    self.lifts.iter()
+      .filter(|lift| lift.bounding_box().contains(pos))
+      .find_map(|lift| lift.block_material_at(pos))
+  }
+}
+
+impl Geometry for BubbleLifts {
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.lift_at(pos).is_some()
+  }
+}
+
+impl ColumnTop for BubbleLifts {
+  fn column_top(&self, xy: IVec2) -> Option<i32> {
+    let covered = self.lifts.iter().any(|lift| lift.bounding_box().contains_column(xy));
+    Some(if covered { SEA_LEVEL } else { crate::WORLD_MIN_Z - 1 })
+  }
+}
+
+impl MaterialGeometry for BubbleLifts {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.lift_at(pos).map(Cow::Borrowed)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    for lift in &self.lifts {
+      out.insert(match lift.current {
+        Current::Up => blocks::SOUL_SAND,
+        Current::Down => blocks::MAGMA_BLOCK
+      });
+      out.extend([blocks::WATER, blocks::GLASS]);
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::bedrock::Bedrock;
+  use crate::generation::fixtures::ConstantNoise;
+  use crate::generation::noise_util::DynNoise;
+  use crate::generation::pillar::Pillar;
+
+  const ORIGIN: IVec2 = IVec2::new(10, 5);
+  const RADIUS: u32 = 3;
+  /// The sea floor of `level_ocean` is at -32, so the feet of the lifts sit a block beneath it
+  const FOOT: i32 = -33;
+
+  /// An ocean whose sea floor is level, `floor` blocks above z=-32
+  fn level_ocean(floor: f64) -> Ocean {
+    let constant = |value: f64| DynNoise::new(ConstantNoise { value });
+    Ocean::with_noise(Bedrock::with_noise(constant(0.0), -64), constant(floor), constant(-1.0), constant(-1.0))
+  }
+
+  fn lifts_beside(pillar: &Pillar, ocean: &Ocean) -> BubbleLifts {
+    BubbleLifts::beside_pillars(&[(pillar.origin(), pillar.radius())], ocean, |xy| pillar.contains_column(xy))
+  }
+
+  fn material_at(lifts: &BubbleLifts, pos: IVec3) -> Option<Block> {
+    lifts.block_material_at(pos).map(Cow::into_owned)
+  }
+
+  #[test]
+  fn lifts_are_water_sources_inside_glass_up_to_the_surface() {
+    let pillar = Pillar::new(ORIGIN, RADIUS);
+    let lifts = lifts_beside(&pillar, &level_ocean(0.0));
+    assert_eq!(lifts.lifts.len(), 2);
+    for lift in lifts.lifts.iter() {
+      let column = lift.column;
+      assert_eq!(lift.floor, FOOT, "at {}", column);
+      for z in FOOT + 1..=SEA_LEVEL {
+        assert_eq!(material_at(&lifts, column.extend(z)), Some(blocks::WATER), "no water at {}", column.extend(z));
+      };
+
+      for z in FOOT..=SEA_LEVEL {
+        for offset in [[-1, -1], [-1, 0], [-1, 1], [0, -1], [0, 1], [1, -1], [1, 0], [1, 1]].map(IVec2::from) {
+          let pos = (column + offset).extend(z);
+          assert_eq!(material_at(&lifts, pos), Some(blocks::GLASS), "no glass at {}", pos);
+        };
+      };
+
+      for pos in [column.extend(FOOT - 1), column.extend(SEA_LEVEL + 1), (column + IVec2::new(2, 0)).extend(-10)] {
+        assert_eq!(material_at(&lifts, pos), None, "at {}", pos);
+      };
+    };
+  }
+
+  #[test]
+  fn lifts_go_up_on_the_north_face_and_down_on_the_south_face() {
+    let pillar = Pillar::new(ORIGIN, RADIUS);
+    let lifts = lifts_beside(&pillar, &level_ocean(0.0));
+    let distance = RADIUS as i32 + PILLAR_GAP + 1;
+    let (north, south) = (ORIGIN - IVec2::new(0, distance), ORIGIN + IVec2::new(0, distance));
+    assert_eq!(material_at(&lifts, north.extend(FOOT)), Some(blocks::SOUL_SAND));
+    assert_eq!(material_at(&lifts, south.extend(FOOT)), Some(blocks::MAGMA_BLOCK));
+    let mut feet = lifts.feet().collect::<Vec<IVec3>>();
+    feet.sort_by_key(|pos| pos.y);
+    assert_eq!(feet, [north.extend(FOOT), south.extend(FOOT)]);
+  }
+
+  #[test]
+  fn lifts_never_take_the_place_of_their_pillar() {
+    for radius in 1..=6 {
+      let pillar = Pillar::new(ORIGIN, radius);
+      let lifts = lifts_beside(&pillar, &level_ocean(0.0));
+      assert_eq!(lifts.lifts.len(), 2, "radius {}", radius);
+      let BoundingBox { min, max } = lifts.bounding_box();
+      for x in min.x..=max.x {
+        for y in min.y..=max.y {
+          let xy = IVec2::new(x, y);
+          let is_lift = (FOOT..=SEA_LEVEL).any(|z| lifts.block_at(xy.extend(z)));
+          let overlaps = is_lift && pillar.contains_column(xy);
+          assert!(!overlaps, "radius {}: the lift runs into the pillar at {}", radius, xy);
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn lifts_are_left_out_where_they_would_not_fit() {
+    let pillar = Pillar::new(ORIGIN, RADIUS);
+    // The sea floor reaches above the surface
+    assert!(lifts_beside(&pillar, &level_ocean(40.0)).lifts.is_empty());
+
+    // Another pillar stands just south of this one, where the glass of the downward lift would be
+    let south = Pillar::new(ORIGIN + IVec2::new(0, 7), 1);
+    let is_pillar_column = |xy: IVec2| pillar.contains_column(xy) || south.contains_column(xy);
+    let lifts = BubbleLifts::beside_pillars(&[(ORIGIN, RADIUS)], &level_ocean(0.0), is_pillar_column);
+    assert_eq!(lifts.lifts.iter().map(|lift| lift.current).collect::<Vec<Current>>(), [Current::Up]);
+
+    assert!(BubbleLifts::none().lifts.is_empty());
+    assert!(!BubbleLifts::none().block_at(IVec3::new(0, 0, crate::WORLD_MIN_Z)));
+  }
+}
@@ -0,0 +1,201 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
+use glam::IVec3;
+
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::mix64;
+
+
+
+/// Wraps a geometry, asserting in debug builds that `block_at` and `block_material_at` agree at every position sampled.
+/// In release builds this does nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedGeometry<G> {
+  geometry: G
+}
+
+impl<G> CheckedGeometry<G> {
+  pub fn new(geometry: G) -> Self {
+    CheckedGeometry { geometry }
+  }
+}
+
+impl<G> Deref for CheckedGeometry<G> {
+  type Target = G;
+
+  #[inline]
+  fn deref(&self) -> &G {
+    &self.geometry
+  }
+}
+
+impl<G> Geometry for CheckedGeometry<G>
+where G: MaterialGeometry {
+  #[inline]
+  fn bounding_box(&self) -> BoundingBox {
+    self.geometry.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let result = self.geometry.block_at(pos);
+    if cfg!(debug_assertions) {
+      assert_consistent(pos, result, self.geometry.block_material_at(pos).as_deref());
+    };
+
+    result
+  }
+}
+
+impl<G> MaterialGeometry for CheckedGeometry<G>
+where G: MaterialGeometry {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let result = self.geometry.block_material_at(pos);
+    if cfg!(debug_assertions) {
+      assert_consistent(pos, self.geometry.block_at(pos), result.as_deref());
+    };
+
+    result
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometry.possible_blocks(out);
+  }
+}
+
+fn assert_consistent(pos: IVec3, block_at: bool, block_material_at: Option<&Block>) {
+  assert_eq!(
+    block_at, block_material_at.is_some(),
+    "`block_at` and `block_material_at` disagree at {} (`block_material_at` returned {:?})",
+    pos, block_material_at
+  );
+}
+
+/// Samples `samples` pseudorandom positions within `bounds`, returning the first position (if any)
+/// at which the geometry's `block_at` and `block_material_at` disagree.
+/// The same seed always samples the same positions.
+pub fn validate_consistency(
+  geometry: &impl MaterialGeometry, bounds: BoundingBox, samples: usize, seed: u64
+) -> Result<(), IVec3> {
+  let axis = |i: u64, min: i32, max: i32| {
+    let len = (max as i64 - min as i64 + 1) as u64;
+    (min as i64 + (mix64(seed ^ i) % len) as i64) as i32
+  };
+
+  for sample in 0..samples as u64 {
+    let i = sample * 3;
+    let pos = IVec3::new(
+      axis(i, bounds.min.x, bounds.max.x),
+      axis(i + 1, bounds.min.y, bounds.max.y),
+      axis(i + 2, bounds.min.z, bounds.max.z)
+    );
+
+    if geometry.block_at(pos) != geometry.block_material_at(pos).is_some() {
+      return Err(pos);
+    };
+  };
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{positions_in, Checkerboard, HollowBox, SingleBlock, SolidBox, Sphere};
+
+  /// A geometry whose `block_material_at` leaves out one of the blocks that `block_at` reports
+  struct Inconsistent {
+    sphere: Sphere,
+    missing: IVec3
+  }
+
+  impl Geometry for Inconsistent {
+    fn bounding_box(&self) -> BoundingBox {
+      self.sphere.bounding_box()
+    }
+
+    fn block_at(&self, pos: IVec3) -> bool {
+      self.sphere.block_at(pos)
+    }
+  }
+
+  impl MaterialGeometry for Inconsistent {
+    fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+      if pos == self.missing { return None };
+      self.sphere.block_material_at(pos)
+    }
+
+    fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+      self.sphere.possible_blocks(out);
+    }
+  }
+
+  fn inconsistent() -> Inconsistent {
+    Inconsistent { sphere: Sphere::new(IVec3::ZERO, 1, blocks::SAND), missing: IVec3::ZERO }
+  }
+
+  #[test]
+  fn consistent_fixtures_pass_validation() {
+    let bounds = BoundingBox::new(IVec3::splat(-20), IVec3::splat(20));
+    let hollow_box = HollowBox::new(BoundingBox::new(IVec3::splat(-5), IVec3::splat(5)), blocks::SAND);
+    assert_eq!(validate_consistency(&Sphere::new(IVec3::ZERO, 9, blocks::SAND), bounds, 10_000, 0), Ok(()));
+    assert_eq!(validate_consistency(&Checkerboard::new(3, -4, 4, blocks::SAND), bounds, 10_000, 1), Ok(()));
+    assert_eq!(validate_consistency(&hollow_box.with_hole(IVec3::new(5, 0, 0)), bounds, 10_000, 2), Ok(()));
+  }
+
+  #[test]
+  fn every_fixture_is_consistent_everywhere() {
+    let bounds = BoundingBox::new(IVec3::splat(-6), IVec3::splat(6));
+    let hollow_box = HollowBox::new(BoundingBox::new(IVec3::splat(-3), IVec3::splat(3)), blocks::SAND);
+    let fixtures: [&dyn MaterialGeometry; 6] = [
+      &SolidBox::new(BoundingBox::new(IVec3::new(-2, 0, 1), IVec3::new(3, 2, 4)), blocks::SAND),
+      &Sphere::new(IVec3::new(1, -1, 0), 4, blocks::SAND),
+      &Checkerboard::new(2, -1, 1, blocks::SAND),
+      &SingleBlock::new(IVec3::new(2, 2, -2), blocks::SAND),
+      &hollow_box.clone(),
+      &hollow_box.with_hole(IVec3::new(0, 3, 0))
+    ];
+
+    for (i, fixture) in fixtures.into_iter().enumerate() {
+      for pos in positions_in(bounds) {
+        assert_eq!(fixture.block_at(pos), fixture.block_material_at(pos).is_some(), "fixture {} at {}", i, pos);
+      };
+    };
+  }
+
+  #[test]
+  fn validation_finds_disagreements() {
+    // Sampling a single position box finds the one disagreement every time
+    let bounds = BoundingBox::new(IVec3::ZERO, IVec3::ZERO);
+    assert_eq!(validate_consistency(&inconsistent(), bounds, 1, 0), Err(IVec3::ZERO));
+    let bounds = BoundingBox::new(IVec3::splat(-1), IVec3::splat(1));
+    assert_eq!(validate_consistency(&inconsistent(), bounds, 1000, 0), Err(IVec3::ZERO));
+  }
+
+  #[test]
+  fn validation_is_deterministic() {
+    let bounds = BoundingBox::new(IVec3::splat(-1), IVec3::splat(1));
+    let geometry = Inconsistent { missing: IVec3::new(1, 0, 0), ..inconsistent() };
+    let found = validate_consistency(&geometry, bounds, 4, 7);
+    assert_eq!(validate_consistency(&geometry, bounds, 4, 7), found);
+  }
+
+  #[test]
+  fn checked_geometry_passes_consistent_samples_through() {
+    let sphere = Sphere::new(IVec3::ZERO, 2, blocks::SAND);
+    let checked = CheckedGeometry::new(sphere.clone());
+    for pos in [IVec3::ZERO, IVec3::new(2, 0, 0), IVec3::new(2, 2, 0)] {
+      assert_eq!(checked.block_at(pos), sphere.block_at(pos));
+      assert_eq!(checked.block_material_at(pos), sphere.block_material_at(pos));
+    };
+  }
+
+  #[test]
+  #[cfg_attr(debug_assertions, should_panic(expected = "disagree at"))]
+  fn checked_geometry_catches_disagreements() {
+    let checked = CheckedGeometry::new(inconsistent());
+    assert!(checked.block_at(IVec3::ZERO));
+  }
+}
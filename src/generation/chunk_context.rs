@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::IVec3;
+
+use super::theme::Theme;
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// Where a feature falls in the generator's priority order.
+/// When multiple features occupy the same position, the one with the lowest priority value takes precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FeaturePriority {
+  Bedrock,
+  /// The shafts connecting the layers of the city, which cut through every layer
+  Shaft {
+    index: usize
+  },
+  /// The openings cut through landmasses above the plazas beneath them
+  Letterbox {
+    index: usize
+  },
+  /// The beacons marking the middle of each layer, and the skylights cut through the landmasses above them
+  Beacon {
+    index: usize
+  },
+  City {
+    layer: usize,
+    /// The position of the feature in the layer's own priority order
+    member: usize,
+    /// The index of the feature within its member, for members made up of many geometries
+    index: usize
+  },
+  /// The rocks scattered around the city, which break the surface of the ocean
+  Outcrops,
+  /// The lifts beside the lowest layer's pillars, whose glass and water take the place of the ocean's
+  BubbleLifts,
+  Ocean
+}
+
+/// A shortlist of only the features which are relevant to a single chunk,
+/// ordered by their priority so that they can be sampled in place of the whole generator.
+pub struct ChunkContext<'a> {
+  bounding_box: BoundingBox,
+  features: Vec<ShortlistEntry<'a>>,
+  /// Swaps the materials of every block sampled from the shortlist, if present
+  theme: Option<&'a Theme>
+}
+
+impl<'a> ChunkContext<'a> {
+  pub fn new(bounding_box: BoundingBox) -> Self {
+    ChunkContext {
+      bounding_box,
+      features: Vec::new(),
+      theme: None
+    }
+  }
+
+  /// Swaps the materials of every block sampled from this context, and every block it reports as possible.
+  /// Themes which leave every material as it is are skipped, so that sampling isn't slowed down by them.
+  pub fn set_theme(&mut self, theme: &'a Theme) {
+    self.theme = (!theme.is_empty()).then(|| theme);
+  }
+
+  /// Adds a feature made up of a single material to the shortlist, if it intersects this context
+  pub fn push_materialized(&mut self, priority: FeaturePriority, geometry: &'a dyn Geometry, material: &'a Block) {
+    self.insert(priority, geometry.bounding_box(), ShortlistedFeature::Materialized(geometry, material));
+  }
+
+  /// Adds a feature to the shortlist, if it intersects this context
+  pub fn push(&mut self, priority: FeaturePriority, geometry: &'a dyn MaterialGeometry) {
+    self.insert(priority, geometry.bounding_box(), ShortlistedFeature::Material(geometry));
+  }
+
+  /// Adds a feature owned by this context to the shortlist, if it intersects this context
+  pub fn push_boxed(&mut self, priority: FeaturePriority, geometry: Box<dyn MaterialGeometry + 'a>) {
+    self.insert(priority, geometry.bounding_box(), ShortlistedFeature::Boxed(geometry));
+  }
+
+  /// Adds a geometry to the shortlist which carves away every feature after it in the priority order,
+  /// if it intersects this context
+  pub fn push_carving(&mut self, priority: FeaturePriority, geometry: Box<dyn Geometry + 'a>) {
+    self.insert(priority, geometry.bounding_box(), ShortlistedFeature::Carving(geometry));
+  }
+
+  fn insert(&mut self, priority: FeaturePriority, bounding_box: BoundingBox, feature: ShortlistedFeature<'a>) {
+    if self.bounding_box.intersects(bounding_box) {
+      let i = self.features.partition_point(|entry| entry.priority < priority);
+      self.features.insert(i, ShortlistEntry { priority, bounding_box, feature });
+    };
+  }
+
+  fn candidates(&self, pos: IVec3) -> impl Iterator<Item = &ShortlistedFeature<'a>> {
+    self.features.iter()
+      .filter(move |entry| entry.bounding_box.contains(pos))
+      .map(|entry| &entry.feature)
+  }
+
+  fn unthemed_block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    for feature in self.candidates(pos) {
+      ret_if_some!(match feature {
+        ShortlistedFeature::Materialized(geometry, material) => {
+          geometry.block_at(pos).then(|| Cow::Borrowed(*material))
+        },
+        ShortlistedFeature::Material(geometry) => geometry.block_material_at(pos),
+        ShortlistedFeature::Boxed(geometry) => geometry.block_material_at(pos),
+        ShortlistedFeature::Carving(geometry) if geometry.block_at(pos) => return None,
+        ShortlistedFeature::Carving(_) => continue
+      });
+    };
+
+    None
+  }
+
+  fn unthemed_possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    for entry in self.features.iter() {
+      match &entry.feature {
+        ShortlistedFeature::Materialized(_, material) => { out.insert((*material).clone()); },
+        ShortlistedFeature::Material(geometry) => geometry.possible_blocks(out),
+        ShortlistedFeature::Boxed(geometry) => geometry.possible_blocks(out),
+        ShortlistedFeature::Carving(_) => ()
+      };
+    };
+  }
+}
+
+impl<'a> Geometry for ChunkContext<'a> {
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    for feature in self.candidates(pos) {
+      let has_block = match feature {
+        ShortlistedFeature::Materialized(geometry, _) => geometry.block_at(pos),
+        ShortlistedFeature::Material(geometry) => geometry.block_at(pos),
+        ShortlistedFeature::Boxed(geometry) => geometry.block_at(pos),
+        ShortlistedFeature::Carving(geometry) if geometry.block_at(pos) => return false,
+        ShortlistedFeature::Carving(_) => continue
+      };
+
+      if has_block { return true };
+    };
+
+    false
+  }
+}
+
+impl<'a> MaterialGeometry for ChunkContext<'a> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let block = self.unthemed_block_material_at(pos)?;
+    Some(match self.theme {
+      Some(theme) => theme.apply(block),
+      None => block
+    })
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    match self.theme {
+      Some(theme) => {
+        let mut unthemed = BTreeSet::new();
+        self.unthemed_possible_blocks(&mut unthemed);
+        out.extend(unthemed.into_iter().map(|block| theme.apply(Cow::Owned(block)).into_owned()));
+      },
+      None => self.unthemed_possible_blocks(out)
+    };
+  }
+}
+
+struct ShortlistEntry<'a> {
+  priority: FeaturePriority,
+  bounding_box: BoundingBox,
+  feature: ShortlistedFeature<'a>
+}
+
+enum ShortlistedFeature<'a> {
+  Materialized(&'a dyn Geometry, &'a Block),
+  Material(&'a dyn MaterialGeometry),
+  Boxed(Box<dyn MaterialGeometry + 'a>),
+  /// Nothing after a carving in the priority order has a block wherever the carving has one
+  Carving(Box<dyn Geometry + 'a>)
+}
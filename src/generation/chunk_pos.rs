@@ -0,0 +1,114 @@
+//! Chunk positions, kept as their own type so that they can't be mixed up with the block positions
+//! which are passed around as plain `IVec2`s and `IVec3`s everywhere else.
+use glam::IVec2;
+
+use crate::utility::rings_in_box;
+
+/// The width of a chunk along both the x and y axes, in blocks
+pub const CHUNK_SIZE: i32 = 16;
+
+/// The position of a chunk, measured in chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChunkPos(pub IVec2);
+
+impl ChunkPos {
+  pub fn new(x: i32, y: i32) -> Self {
+    ChunkPos(IVec2::new(x, y))
+  }
+
+  /// The chunk containing the given column, rounding towards negative infinity,
+  /// so that the column at -1 is in chunk -1 rather than chunk 0
+  pub fn containing(block: IVec2) -> Self {
+    ChunkPos::new(block.x.div_euclid(CHUNK_SIZE), block.y.div_euclid(CHUNK_SIZE))
+  }
+
+  /// The column of this chunk with the lowest x and y values
+  pub fn min_block(self) -> IVec2 {
+    self.0 * CHUNK_SIZE
+  }
+
+  /// The column of this chunk with the highest x and y values
+  pub fn max_block(self) -> IVec2 {
+    self.0 * CHUNK_SIZE + (CHUNK_SIZE - 1)
+  }
+
+  /// Iterates over every column of this chunk, in rows of increasing x, the same order as
+  /// the `y * 16 + x` indexing used for per-column data
+  pub fn block_range(self) -> impl ExactSizeIterator<Item = IVec2> {
+    let min = self.min_block();
+    (0..CHUNK_SIZE * CHUNK_SIZE).map(move |i| min + IVec2::new(i % CHUNK_SIZE, i / CHUNK_SIZE))
+  }
+
+  /// Iterates through every chunk in the given range (inclusive), in square rings expanding out from chunk 0,0
+  pub fn rings_in_range(min: ChunkPos, max: ChunkPos) -> impl ExactSizeIterator<Item = ChunkPos> {
+    rings_in_box(min.0, max.0).map(ChunkPos)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+  use crate::utility::tests::{ring_radius, test_boxes};
+
+  #[test]
+  fn negative_columns_are_in_negative_chunks() {
+    let cases = [(-1, -1), (-16, -1), (-17, -2), (-32, -2), (-33, -3), (0, 0), (15, 0), (16, 1), (-1000, -63)];
+    for (block, chunk) in cases {
+      assert_eq!(ChunkPos::containing(IVec2::new(block, 3)), ChunkPos::new(chunk, 0), "x {}", block);
+      assert_eq!(ChunkPos::containing(IVec2::new(3, block)), ChunkPos::new(0, chunk), "y {}", block);
+    };
+  }
+
+  #[test]
+  fn chunks_span_from_min_block_to_max_block() {
+    assert_eq!(ChunkPos::new(-1, -1).min_block(), IVec2::new(-16, -16));
+    assert_eq!(ChunkPos::new(-1, -1).max_block(), IVec2::new(-1, -1));
+    assert_eq!(ChunkPos::new(-2, 1).min_block(), IVec2::new(-32, 16));
+    assert_eq!(ChunkPos::new(-2, 1).max_block(), IVec2::new(-17, 31));
+
+    for x in -3..=2 {
+      for y in -3..=2 {
+        let chunk = ChunkPos::new(x, y);
+        let (min, max) = (chunk.min_block(), chunk.max_block());
+        assert_eq!(max - min, IVec2::splat(CHUNK_SIZE - 1), "chunk {}", chunk.0);
+        // Both corners, and the columns just outside of them, are in the chunks they should be
+        assert_eq!((ChunkPos::containing(min), ChunkPos::containing(max)), (chunk, chunk), "chunk {}", chunk.0);
+        assert_eq!(ChunkPos::containing(min - 1), ChunkPos(chunk.0 - 1), "chunk {}", chunk.0);
+        assert_eq!(ChunkPos::containing(max + 1), ChunkPos(chunk.0 + 1), "chunk {}", chunk.0);
+      };
+    };
+  }
+
+  #[test]
+  fn block_range_covers_the_chunk_in_rows() {
+    for chunk in [ChunkPos::new(0, 0), ChunkPos::new(-1, -1), ChunkPos::new(-3, 2), ChunkPos::new(5, -7)] {
+      let blocks = chunk.block_range();
+      assert_eq!(blocks.len(), (CHUNK_SIZE * CHUNK_SIZE) as usize);
+      for (i, block) in blocks.enumerate() {
+        let (x, y) = (i as i32 % CHUNK_SIZE, i as i32 / CHUNK_SIZE);
+        assert_eq!(block, chunk.min_block() + IVec2::new(x, y), "column {} of chunk {}", i, chunk.0);
+        assert_eq!(ChunkPos::containing(block), chunk, "column {} of chunk {}", i, chunk.0);
+      };
+    };
+  }
+
+  #[test]
+  fn rings_in_range_visits_every_chunk_once_expanding_outwards() {
+    for (min, max) in test_boxes() {
+      let chunks = ChunkPos::rings_in_range(ChunkPos(min), ChunkPos(max));
+      let len = chunks.len();
+      let chunks = chunks.collect::<Vec<ChunkPos>>();
+      let expected = (min.y..=max.y)
+        .flat_map(|y| (min.x..=max.x).map(move |x| ChunkPos::new(x, y)))
+        .collect::<HashSet<ChunkPos>>();
+
+      assert_eq!(len, expected.len(), "length of {} to {}", min, max);
+      assert_eq!(chunks.len(), expected.len(), "chunks visited in {} to {}", min, max);
+      assert_eq!(chunks.iter().copied().collect::<HashSet<ChunkPos>>(), expected, "chunks in {} to {}", min, max);
+      let radii = chunks.iter().map(|chunk| ring_radius(chunk.0)).collect::<Vec<i64>>();
+      assert!(radii.windows(2).all(|pair| pair[0] <= pair[1]), "rings out of order in {} to {}", min, max);
+    };
+  }
+}
@@ -1,73 +1,937 @@
+mod beacon;
 mod building;
+mod cantilever;
+mod caves;
+mod collisions;
+mod details;
+mod districts;
+mod footprint;
+mod lamp_post;
 mod landmass_shape;
 mod layer;
+pub mod layer_cache;
+mod letterbox;
+mod paving;
+mod pillar_access;
+mod plaza;
+mod point_of_interest;
+mod pool;
+mod props;
+mod scatter;
+mod shaft;
+mod skyline;
+mod slab_fill;
+mod spire;
+mod tile_grid;
+mod underside;
+mod vegetation;
+mod wall;
 
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::iter::repeat_with;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use glam::IVec3;
-use rand::{Rng, SeedableRng};
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use once_cell::sync::OnceCell;
+use rand::{Rng, RngCore, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 use rayon::prelude::*;
 
-use self::layer::Layer;
-use super::{Block, BoundingBox, Geometry, MaterialGeometry};
-use super::union::Union;
+pub use self::building::Building;
+pub use self::collisions::{CollisionPolicy, CollisionReport};
+pub use self::footprint::{CityFootprint, FOOTPRINT_FRINGE};
+pub use self::landmass_shape::{ErosionConfig, ShapeConfig, Symmetry};
+#[cfg(feature = "explore")]
+pub use self::landmass_shape::LandmassShape;
+pub use self::layer::{LandmassParams, Layer};
+pub use self::paving::{PavingConfig, PavingMix};
+pub use self::skyline::Skyline;
+pub use self::slab_fill::{SlabFill, SlabFillChoice, SlabFillWeights};
+pub use self::wall::DEFAULT_WALL_DISTANCE;
 
+use self::beacon::Beacon;
+#[cfg(feature = "explore")]
+use self::landmass_shape::ShapeParams;
+use self::layer::LayerParams;
+use self::layer_cache::{LayerCache, GENERATION_VERSION};
+use self::letterbox::Letterbox;
+use self::shaft::Shaft;
+use super::block_entity::BlockEntity;
+use super::chunk_context::{ChunkContext, FeaturePriority};
+use super::enclosure::{find_leak, Enclosure};
+use super::feature_tag::{tagged, FeatureTag};
+use super::ocean::Ocean;
+use super::pillar::PillarCluster;
+use super::transform::rotate_xy;
+use super::{find_block, Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+use crate::GeneratorOptions;
 
 
-#[derive(Debug, Clone)]
+
+/// How the lowest layer of the city meets the ocean
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Foundation {
+  /// The lowest layer stands on pillars reaching down to the bedrock
+  #[default]
+  Pillars,
+  /// The edge of the lowest layer extends down to the sea floor as a wall, with a single opening
+  Skirt
+}
+
+/// Which parts of the city have light sources placed in them, keeping hostile mobs from spawning there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lighting {
+  None,
+  /// A light in the ceiling of each storey of every building, and a grid of lights set into the undersides of the
+  /// landmasses beneath their walkable surfaces
+  #[default]
+  Minimal,
+  /// Everything lit by `Minimal`, along with the walls of the shafts
+  Full
+}
+
+impl Lighting {
+  pub fn name(self) -> &'static str {
+    match self {
+      Lighting::None => "none",
+      Lighting::Minimal => "minimal",
+      Lighting::Full => "full"
+    }
+  }
+}
+
+#[derive(Debug)]
 pub struct City {
-  layers: Union<Vec<Layer>>
+  layers: Vec<LazyLayer>,
+  cache: Option<LayerCache>,
+  /// The sea floor that the lowest layer's skirt extends down to, if it has one
+  skirt_floor: Option<Ocean>,
+  /// Where the city is moved to in the world, which the skirt needs in order to find the sea floor beneath it
+  placement: CityPlacement,
+  /// Whether the surfaces of lower layers are darkened beneath higher layers
+  baked_shadows: bool,
+  /// Whether the rims of landmasses and the roofs of buildings are decorated with slabs and stairs
+  details: bool,
+  /// Whether rubble, barrels and lamps are scattered over the surfaces of the landmasses
+  scatter: bool,
+  /// The mixes of blocks that the surfaces of the landmasses are paved with, if they are paved
+  paving: Option<PavingConfig>,
+  /// Whether the walls of buildings are streaked beneath their windows and pillars are stained beneath the landmasses
+  weathering: bool,
+  /// Whether the concrete of each layer gets lighter the higher up in the city the layer is
+  altitude_gradient: bool,
+  /// Which parts of the city have light sources placed in them
+  lighting: Lighting,
+  /// What happens to the features of each layer that the pillars of the layer above pass through
+  collisions: CollisionPolicy,
+  shaft_rng: Xoshiro256PlusPlus,
+  /// Ladders connecting every layer, which can only be placed once every layer has been generated
+  shafts: OnceCell<Vec<Shaft>>,
+  letterbox_seed: u64,
+  scatter_seed: u64,
+  paving_seed: u64,
+  /// Openings through landmasses above the plazas of the layers beneath them, placed once the shafts have been
+  letterboxes: OnceCell<Vec<Letterbox>>,
+  /// Beacons marking the middle of each layer, placed once the letterboxes have been
+  beacons: OnceCell<Vec<Beacon>>
 }
 
 impl City {
-  pub fn generate_new<R: Rng>(mut source_rng: R, layer_count: usize) -> Self {
-    let rngs = repeat_with(|| Xoshiro256PlusPlus::from_rng(&mut source_rng).unwrap())
-      .take(layer_count).collect::<Vec<_>>();
-    let mut layers = rngs.into_par_iter()
+  /// Prepares the parameters of each layer of the city without generating any of them, from the given options.
+  /// Layers are generated when they are first accessed, or all at once by `City::bounding_box`.
+  /// If a cache is provided, layers are loaded from it instead of being generated where possible.
+  ///
+  /// The city is generated around 0,0 regardless of its placement, which is only used to line up its skirt with the
+  /// sea floor. Moving the city into place is left to the caller.
+  pub fn generate_new<R: Rng>(
+    mut source_rng: R, options: &GeneratorOptions, cache: Option<LayerCache>, ocean: &Ocean, placement: CityPlacement
+  ) -> Self {
+    let layers = repeat_with(|| Xoshiro256PlusPlus::from_rng(&mut source_rng).unwrap())
+      .take(options.layer_count)
       .enumerate()
-      .map(|(i, mut rng)| {
-        let top = (i as i32 + 1) * 48;
-        let bottom = if i == 0 { crate::WORLD_MIN_Z } else { i as i32 * 48 };
-        let size = (layer_count - i) as f64;
-        Layer::generate_new(&mut rng, top, bottom, size)
-      })
-      .collect::<Vec<Layer>>();
-    windows_mut_each(&mut layers, |[ref mut below, ref above]| {
-      below.remove_buildings_colliding_with(above);
-    });
+      .map(|(i, rng)| LazyLayer::new(rng, LayerParams::new(options, i)))
+      .collect::<Vec<LazyLayer>>();
 
+    // Split off after the layers' rngs, so that the layers are the same as they were before shafts were added
+    let shaft_rng = Xoshiro256PlusPlus::from_rng(&mut source_rng).unwrap();
+    let letterbox_seed = source_rng.gen();
+    let scatter_seed = source_rng.gen();
+    let paving_seed = source_rng.gen();
+    let skirt_floor = (options.foundation == Foundation::Skirt).then(|| ocean.clone());
     City {
-      layers: Union::new(layers)
+      layers,
+      cache,
+      skirt_floor,
+      placement,
+      baked_shadows: options.baked_shadows,
+      details: options.details,
+      scatter: options.scatter,
+      paving: options.paving,
+      weathering: options.weathering,
+      altitude_gradient: options.altitude_gradient,
+      lighting: options.lighting,
+      collisions: options.collisions,
+      shaft_rng,
+      shafts: OnceCell::new(),
+      letterbox_seed,
+      scatter_seed,
+      paving_seed,
+      letterboxes: OnceCell::new(),
+      beacons: OnceCell::new()
+    }
+  }
+
+  /// Generates only the landmass shape of each layer of the city generated from the given seed, from the bottom up,
+  /// which takes a fraction of the time of generating the layers in full.
+  /// The shapes are in the city's own coordinates, before it is moved into place.
+  #[cfg(feature = "explore")]
+  pub fn preview_shapes(
+    seed: u64, layer_count: usize, shapes: Option<ShapeConfig>, symmetry: Symmetry, erosion: Option<ErosionConfig>
+  ) -> Vec<LandmassShape> {
+    // Draws from the same rngs as `City::generate_new` and `Layer::generate_new`,
+    // which draw the shape's seed first and then its shape
+    let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let shape_seeds = (0..layer_count)
+      .map(|_| {
+        let mut rng = Xoshiro256PlusPlus::from_rng(&mut source_rng).unwrap();
+        let shape_seed = rng.gen();
+        (shape_seed, shapes.map_or(ShapeParams::CIRCULAR, |shapes| ShapeParams::random(&mut rng, shapes)))
+      })
+      .collect::<Vec<(u32, ShapeParams)>>();
+    shape_seeds.into_par_iter()
+      .enumerate()
+      .map(|(i, (shape_seed, shape))| {
+        LandmassShape::generate_new(shape_seed, (layer_count - i) as f64, shape, symmetry, erosion)
+      })
+      .collect()
+  }
+
+  /// Returns the layer at the given index, generating it (and the layers above it) if it has not been generated yet
+  fn layer(&self, i: usize) -> &Layer {
+    self.layers[i].layer.get_or_init(|| {
+      let mut layer = self.layers[i].generate(self.cache.as_ref(), self.sea_floor(i));
+      if i + 1 < self.layers.len() {
+        layer.resolve_collisions_with(i, self.layer(i + 1), self.collisions);
+        let report = CollisionReport { collisions: layer.collisions().to_vec() };
+        log::debug!("layer {}: {}", i, report);
+      };
+
+      // Shaded before weathering, which stains the walls with a darker variant of their material
+      if self.altitude_gradient {
+        layer.shade_by_altitude(self.altitude_shade(i));
+      };
+
+      if self.weathering {
+        layer.weather();
+      };
+
+      if self.lighting != Lighting::None {
+        layer.light();
+      };
+
+      if let Some(paving) = self.paving {
+        layer.pave(crate::utility::mix64(self.paving_seed ^ i as u64), paving);
+      };
+
+      if self.baked_shadows {
+        layer.bake_shadows((i + 1..self.layers.len()).map(|j| self.layer(j)));
+      };
+
+      if self.details {
+        layer.add_details();
+      };
+
+      // Scattered after the details, so that the decorations keep clear of them
+      if self.scatter {
+        let above = (i + 1 < self.layers.len()).then(|| self.layer(i + 1));
+        layer.scatter_decorations(crate::utility::mix64(self.scatter_seed ^ i as u64), above);
+      };
+
+      if let Some(distance) = self.layers[i].params.wall_distance {
+        layer.build_wall(distance);
+      };
+
+      layer
+    })
+  }
+
+  /// The sea floor that the layer at the given index extends its skirt down to, if it has one
+  fn sea_floor(&self, i: usize) -> Option<SeaFloor<'_>> {
+    match (i, &self.skirt_floor) {
+      (0, Some(ocean)) => Some(SeaFloor { ocean, placement: self.placement }),
+      _ => None
     }
   }
+
+  /// The index into `blocks::ALTITUDE_SHADES` of the shade of the landmass of a layer, going from the darkest shade for
+  /// the lowest layer to the lightest for the highest by the height of each layer's surface, so that layers which are
+  /// spaced further apart differ more in shade. A city with one layer keeps the middle shade.
+  fn altitude_shade(&self, i: usize) -> usize {
+    let (lowest, highest) = (self.layers[0].params.top, self.layers[self.layers.len() - 1].params.top);
+    let span = highest - lowest;
+    if span <= 0 { return 1 };
+    // Rounded to the nearest of the three landmass shades
+    ((self.layers[i].params.top - lowest) * 4 + span) as usize / (span as usize * 2)
+  }
+
+  /// Generates every layer of the city in parallel
+  pub fn generate_all(&self) {
+    (0..self.layers.len()).into_par_iter().for_each(|i| {
+      self.layer(i);
+    });
+  }
+
+  /// Generates every layer of the city in parallel and hands them to `edit`, which may change them however it likes,
+  /// before any of them have been resolved against the layers above or decorated.
+  /// Each layer is finished from whatever `edit` leaves it as, just as it would have been from the layer as generated.
+  ///
+  /// This must be called before any layer is accessed. `edit` must leave the same number of layers,
+  /// and anything it adds to a layer must stay within that layer's estimated bounding box.
+  pub fn edit_layers(&mut self, edit: impl FnOnce(&mut Vec<Layer>)) {
+    assert!(self.layers.iter().all(|layer| layer.layer.get().is_none()), "layers must be edited before they are used");
+    let mut layers = (0..self.layers.len()).into_par_iter()
+      .map(|i| self.layers[i].generate(self.cache.as_ref(), self.sea_floor(i)))
+      .collect::<Vec<Layer>>();
+    edit(&mut layers);
+    assert_eq!(layers.len(), self.layers.len(), "editing the layers of a city cannot add or remove layers");
+    for (i, (lazy_layer, layer)) in self.layers.iter_mut().zip(layers).enumerate() {
+      let (bounding_box, estimated) = (layer.bounding_box(), lazy_layer.estimated_bounding_box);
+      assert!(
+        estimated.contains(bounding_box.min) && estimated.contains(bounding_box.max),
+        "layer {} was edited to reach outside of its estimated bounding box", i
+      );
+      lazy_layer.edited = Mutex::new(Some(layer));
+    };
+  }
+
+  /// The shafts connecting the layers of the city, generating every layer if they have not been placed yet
+  pub fn shafts(&self) -> &[Shaft] {
+    self.shafts.get_or_init(|| {
+      self.generate_all();
+      let layers = (0..self.layers.len()).map(|i| self.layer(i)).collect::<Vec<&Layer>>();
+      let mut shafts = Shaft::connecting(&mut self.shaft_rng.clone(), &layers);
+      if self.lighting == Lighting::Full {
+        shafts.iter_mut().for_each(Shaft::light);
+      };
+
+      shafts
+    })
+  }
+
+  /// The letterboxes looking down on the plazas of lower layers, placing the shafts if they have not been placed yet
+  pub fn letterboxes(&self) -> &[Letterbox] {
+    self.letterboxes.get_or_init(|| {
+      let layers = (0..self.layers.len()).map(|i| self.layer(i)).collect::<Vec<&Layer>>();
+      Letterbox::looking_down(self.letterbox_seed, &layers, self.shafts())
+    })
+  }
+
+  /// The beacons marking the middle of each layer, placing the letterboxes if they have not been placed yet
+  pub fn beacons(&self) -> &[Beacon] {
+    self.beacons.get_or_init(|| {
+      let layers = (0..self.layers.len()).map(|i| self.layer(i)).collect::<Vec<&Layer>>();
+      Beacon::marking_centroids(&layers, self.shafts(), self.letterboxes())
+    })
+  }
+
+  /// The spaces enclosed by the city's features which the ocean must not fill, placing the shafts if they have not been
+  /// placed yet. Any of these which the walls around them don't seal off completely are logged, but kept dry
+  /// regardless.
+  pub fn dry_volumes(&self) -> Vec<BoundingBox> {
+    let mut dry_volumes = Vec::new();
+    for shaft in self.shafts() {
+      for volume in shaft.dry_volumes() {
+        if let Some(pos) = find_leak(shaft, volume) {
+          log::warn!("the walls of a shaft leak at {}, letting water into the space inside them", pos);
+        };
+
+        dry_volumes.push(volume);
+      };
+    };
+
+    dry_volumes
+  }
+
+  /// The columns covered by every layer's landmass and the footings of the lowest layer's pillars,
+  /// moved into place in the world. Every layer is generated once a column near the city is sampled.
+  pub fn footprint(self: &Arc<Self>) -> CityFootprint {
+    let city = Arc::clone(self);
+    CityFootprint::new(self.placement.to_world_box(self.estimated_bounding_box()), move || {
+      city.generate_all();
+      let columns = (0..city.layers.len())
+        .flat_map(|i| city.layer(i).landmass_columns())
+        .map(|pos| city.placement.to_world(pos))
+        .collect();
+      let pillars = city.layer(0).pillars()
+        .map(|pillar| city.placement.to_world(pillar.origin()))
+        .collect();
+      (columns, pillars)
+    })
+  }
+
+  /// The middle and radius of each of the lowest layer's pillars, including those in clusters,
+  /// moved into place in the world. This generates the lowest layer.
+  pub fn pillar_footings(&self) -> Vec<(IVec2, u32)> {
+    self.layer(0).pillars()
+      .map(|pillar| (self.placement.to_world(pillar.origin()), pillar.radius()))
+      .collect()
+  }
+
+  /// Whether any of the lowest layer's pillars or the braces between them stand in the given column,
+  /// in the city's own coordinates
+  pub fn is_pillar_column(&self, xy: IVec2) -> bool {
+    self.layer(0).pillar_clusters().any(|cluster| cluster.contains_column(xy))
+  }
+
+  /// The number of columns covered by the landmass of each layer, from the bottom up
+  pub fn landmass_sizes(&self) -> Vec<u32> {
+    (0..self.layers.len()).map(|i| self.layer(i).landmass_columns().count() as u32).collect()
+  }
+
+  /// A cheap, conservative estimate of this city's bounding box that does not require generating any layers.
+  /// The shafts, letterboxes and beacons never reach beyond the layers they connect, so this contains them too.
+  pub fn estimated_bounding_box(&self) -> BoundingBox {
+    self.layers.iter()
+      .map(|layer| layer.estimated_bounding_box)
+      .reduce(BoundingBox::join)
+      .unwrap()
+  }
+
+  /// A cheap, conservative estimate of the columns that the lowest layer's pillars and the braces between them stand
+  /// in, which does not require generating any layers
+  pub fn estimated_pillar_bounding_box(&self) -> BoundingBox {
+    let lowest = &self.layers[0];
+    let reach = PillarCluster::max_reach(lowest.params.landmass.pillar_radius) as i32 + 1;
+    lowest.estimated_bounding_box.inflated(IVec3::new(reach, reach, 0))
+  }
+
+  /// Adds the features of every layer that intersects the given context to its shortlist.
+  /// Nothing is generated for a context which is nowhere near the city.
+  pub fn shortlist<'a>(&'a self, context: &mut ChunkContext<'a>) {
+    if !self.estimated_bounding_box().intersects(context.bounding_box()) { return };
+    for (index, shaft) in self.shafts().iter().enumerate() {
+      context.push(FeaturePriority::Shaft { index }, shaft);
+      context.push_carving(FeaturePriority::Shaft { index }, Box::new(shaft.openings()));
+    };
+
+    for (index, letterbox) in self.letterboxes().iter().enumerate() {
+      context.push(FeaturePriority::Letterbox { index }, letterbox);
+      context.push_carving(FeaturePriority::Letterbox { index }, Box::new(letterbox.opening()));
+    };
+
+    for (index, beacon) in self.beacons().iter().enumerate() {
+      context.push(FeaturePriority::Beacon { index }, beacon);
+      if let Some(skylight) = beacon.skylight() {
+        context.push_carving(FeaturePriority::Beacon { index }, Box::new(skylight));
+      };
+    };
+
+    for (i, layer) in self.layers_intersecting(context.bounding_box()) {
+      layer.shortlist(i, context);
+    };
+  }
+
+  pub fn layer_count(&self) -> usize {
+    self.layers.len()
+  }
+
+  /// The number of times each layer has been generated, from the bottom up
+  #[cfg(test)]
+  pub fn generation_counts(&self) -> Vec<usize> {
+    self.layers.iter().map(|layer| layer.generations.load(Ordering::Relaxed)).collect()
+  }
+
+  /// The number of times each layer has been loaded from the cache instead of being generated, from the bottom up
+  #[cfg(test)]
+  pub fn cache_load_counts(&self) -> Vec<usize> {
+    self.layers.iter().map(|layer| layer.loads.load(Ordering::Relaxed)).collect()
+  }
+
+  /// Iterates over the layers which may intersect the given bounding box, generating them if necessary
+  pub fn layers_intersecting(&self, bounding_box: BoundingBox) -> impl Iterator<Item = (usize, &Layer)> {
+    self.layers.iter().enumerate()
+      .filter(move |(_, layer)| layer.estimated_bounding_box.intersects(bounding_box))
+      .map(|(i, _)| (i, self.layer(i)))
+  }
+
+  /// The block entities of every layer within the given bounding box
+  pub fn block_entities_in(&self, bounding_box: BoundingBox) -> Vec<BlockEntity> {
+    self.layers_intersecting(bounding_box)
+      .flat_map(|(_, layer)| layer.block_entities_in(bounding_box))
+      .collect()
+  }
+
+  /// Positions of representative blocks of each kind of feature in the city, labelled with the feature they belong to.
+  /// This generates every layer, along with the shafts connecting them.
+  pub fn feature_probes(&self) -> Vec<(String, IVec3)> {
+    let mut probes = (0..self.layers.len())
+      .flat_map(|i| {
+        self.layer(i).feature_probes().into_iter()
+          .map(move |(label, pos)| (format!("layer {} {}", i, label), pos))
+      })
+      .collect::<Vec<(String, IVec3)>>();
+    let shaft_probe = self.shafts().first().and_then(|shaft| find_block(shaft, 1 << 18));
+    probes.extend(shaft_probe.map(|pos| ("shaft".to_owned(), pos)));
+    let letterbox_probe = self.letterboxes().first().and_then(|letterbox| find_block(letterbox, 1 << 18));
+    probes.extend(letterbox_probe.map(|pos| ("letterbox".to_owned(), pos)));
+    let beacon_probe = self.beacons().first().and_then(|beacon| find_block(beacon, 1 << 18));
+    probes.extend(beacon_probe.map(|pos| ("beacon".to_owned(), pos)));
+    probes
+  }
+
+  /// Iterates over the layers which may contain the given position, generating them if necessary
+  fn layers_containing(&self, pos: IVec3) -> impl Iterator<Item = &Layer> {
+    self.layers.iter().enumerate()
+      .filter(move |(_, layer)| layer.estimated_bounding_box.contains(pos))
+      .map(|(i, _)| self.layer(i))
+  }
+
+  /// The block at a position along with the kind of feature that placed it
+  pub fn block_material_at_traced(&self, pos: IVec3) -> Option<(Cow<'_, Block>, FeatureTag)> {
+    for shaft in self.shafts() {
+      ret_if_some!(shaft.block_material_at(pos).map(tagged(FeatureTag::Shaft)));
+      if shaft.openings().block_at(pos) { return None };
+    };
+
+    for letterbox in self.letterboxes() {
+      ret_if_some!(letterbox.block_material_at(pos).map(tagged(FeatureTag::Decoration)));
+      if letterbox.opening().block_at(pos) { return None };
+    };
+
+    for beacon in self.beacons() {
+      ret_if_some!(beacon.block_material_at(pos).map(tagged(FeatureTag::Decoration)));
+      if beacon.skylight().map_or(false, |skylight| skylight.block_at(pos)) { return None };
+    };
+
+    self.layers_containing(pos).find_map(|layer| layer.block_material_at_traced(pos))
+  }
 }
 
 impl Geometry for City {
+  /// The estimated bounding box of the city, which contains everything in it without generating any layers
   fn bounding_box(&self) -> BoundingBox {
-    self.layers.bounding_box()
+    self.estimated_bounding_box()
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    self.layers.block_at(pos)
+    for shaft in self.shafts() {
+      if shaft.block_at(pos) { return true };
+      if shaft.openings().block_at(pos) { return false };
+    };
+
+    for letterbox in self.letterboxes() {
+      if letterbox.block_at(pos) { return true };
+      if letterbox.opening().block_at(pos) { return false };
+    };
+
+    for beacon in self.beacons() {
+      if beacon.block_at(pos) { return true };
+      if beacon.skylight().map_or(false, |skylight| skylight.block_at(pos)) { return false };
+    };
+
+    self.layers_containing(pos).any(|layer| layer.block_at(pos))
+  }
+}
+
+impl ColumnTop for City {
+  /// The highest reach of whichever layers, shafts, letterboxes and beacons cover the column,
+  /// generating every layer unless the column is nowhere near the city
+  fn column_top(&self, xy: IVec2) -> Option<i32> {
+    if !self.estimated_bounding_box().contains_column(xy) { return Some(crate::WORLD_MIN_Z - 1) };
+    let layers = self.layers.iter().enumerate()
+      .filter(|(_, layer)| layer.estimated_bounding_box.contains_column(xy))
+      .filter_map(|(i, _)| self.layer(i).column_top(xy));
+    let bounding_boxes = self.shafts().iter().map(Shaft::bounding_box)
+      .chain(self.letterboxes().iter().map(Letterbox::bounding_box))
+      .chain(self.beacons().iter().map(Beacon::bounding_box))
+      .filter(|bounding_box| bounding_box.contains_column(xy))
+      .map(|bounding_box| bounding_box.max.z);
+    Some(layers.chain(bounding_boxes).max().unwrap_or(crate::WORLD_MIN_Z - 1))
   }
 }
 
 impl MaterialGeometry for City {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
-    self.layers.block_material_at(pos)
+  /// Shafts, then letterboxes and then beacons take precedence over every layer, carving their openings out of them
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_material_at_traced(pos).map(|(block, _)| block)
+  }
+
+  /// Generates every layer, along with the shafts, letterboxes and beacons
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.generate_all();
+    self.shafts().iter().for_each(|shaft| shaft.possible_blocks(out));
+    self.letterboxes().iter().for_each(|letterbox| letterbox.possible_blocks(out));
+    self.beacons().iter().for_each(|beacon| beacon.possible_blocks(out));
+    (0..self.layers.len()).for_each(|i| self.layer(i).possible_blocks(out));
+  }
+}
+
+
+
+/// Where the city is moved to in the world.
+/// The city is first rotated about 0,0, and then offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CityPlacement {
+  /// The number of quarter turns that the city is rotated by, each taking east to south
+  pub quarter_turns: u8,
+  pub offset: IVec2
+}
+
+impl CityPlacement {
+  /// The furthest that a random placement moves the city along each axis
+  const MAX_OFFSET: i32 = 256;
+
+  pub fn random<R: Rng>(rng: &mut R) -> Self {
+    CityPlacement {
+      quarter_turns: rng.gen_range(0..4),
+      offset: IVec2::new(
+        rng.gen_range(-Self::MAX_OFFSET..=Self::MAX_OFFSET),
+        rng.gen_range(-Self::MAX_OFFSET..=Self::MAX_OFFSET)
+      )
+    }
+  }
+
+  pub fn to_world(self, pos: IVec2) -> IVec2 {
+    rotate_xy(pos, self.quarter_turns) + self.offset
+  }
+
+  /// Moves a bounding box into place in the world, leaving its z values as they are
+  pub fn to_world_box(self, bounding_box: BoundingBox) -> BoundingBox {
+    let (min, max) = (self.to_world(bounding_box.min.xy()), self.to_world(bounding_box.max.xy()));
+    BoundingBox::try_new(min.min(max).extend(bounding_box.min.z), min.max(max).extend(bounding_box.max.z)).unwrap()
+  }
+}
+
+/// The sea floor beneath the city, sampled in the city's own coordinates
+#[derive(Debug, Clone, Copy)]
+struct SeaFloor<'a> {
+  ocean: &'a Ocean,
+  placement: CityPlacement
+}
+
+impl<'a> SeaFloor<'a> {
+  fn height_at(self, pos: IVec2) -> i32 {
+    self.ocean.floor_height_at(self.placement.to_world(pos))
+  }
+}
+
+
+
+/// A layer of the city that is only generated once it is needed.
+/// Each layer's rng is split off from the source rng up front, so that the order in which
+/// layers are generated does not affect the outcome.
+#[derive(Debug)]
+struct LazyLayer {
+  rng: Xoshiro256PlusPlus,
+  params: LayerParams,
+  estimated_bounding_box: BoundingBox,
+  /// The layer as left by `City::edit_layers`, which is finished in place of the layer as generated, if it was edited.
+  /// It is taken out once it has been finished.
+  edited: Mutex<Option<Layer>>,
+  /// The number of times `generate` has been called for this layer
+  generations: AtomicUsize,
+  /// The number of those times that the layer was loaded from the cache rather than generated
+  loads: AtomicUsize,
+  layer: OnceCell<Layer>
+}
+
+impl LazyLayer {
+  fn new(rng: Xoshiro256PlusPlus, params: LayerParams) -> Self {
+    LazyLayer {
+      rng,
+      params,
+      estimated_bounding_box: Layer::estimate_bounding_box(&params),
+      edited: Mutex::new(None),
+      generations: AtomicUsize::new(0),
+      loads: AtomicUsize::new(0),
+      layer: OnceCell::new()
+    }
+  }
+
+  /// Generates this layer, without resolving collisions with any other layers.
+  /// If a sea floor is given, the layer gets a skirt extending down to it instead of pillars.
+  /// If the layer has been edited, the edited layer is taken out and returned instead, so that it isn't kept alongside
+  /// the layer finished from it. Each layer is only finished once.
+  fn generate(&self, cache: Option<&LayerCache>, floor: Option<SeaFloor>) -> Layer {
+    self.generations.fetch_add(1, Ordering::Relaxed);
+    if let Some(layer) = self.edited.lock().unwrap().take() {
+      return layer;
+    };
+
+    let key = self.cache_key(floor.map(|floor| floor.placement));
+    if let Some(layer) = cache.and_then(|cache| cache.load(key)) {
+      self.loads.fetch_add(1, Ordering::Relaxed);
+      return layer;
+    };
+
+    let mut rng = self.rng.clone();
+    let layer = Layer::generate_new(&mut rng, &self.params, floor);
+    if let Some(cache) = cache {
+      cache.store(key, &layer);
+    };
+
+    layer
+  }
+
+  /// A hash of every input to this layer's generation.
+  /// The sea floor is derived from the same seed as the layer's rng, so only its presence and the part of it
+  /// beneath the city, given by the city's placement, need to be included.
+  fn cache_key(&self, skirt: Option<CityPlacement>) -> u64 {
+    let mut rng = self.rng.clone();
+    let rng_fingerprint = [rng.next_u64(), rng.next_u64(), rng.next_u64(), rng.next_u64()];
+    let params = &self.params;
+    let shapes = params.shapes.map_or([0; 4], |shapes| {
+      [1, shapes.max_elongation as u64, shapes.max_lobe_depth as u64, shapes.max_lobes as u64]
+    });
+    let erosion = params.erosion.map_or([0; 3], |erosion| [1, erosion.iterations as u64, erosion.min_neighbors as u64]);
+    let landmass = [
+      params.landmass.thickness as u64, params.landmass.pillar_radius as u64, params.landmass.skyline as u64
+    ];
+    let skirt = skirt.map_or([0; 4], |placement| {
+      [1, placement.quarter_turns as u64, placement.offset.x as u32 as u64, placement.offset.y as u32 as u64]
+    });
+    let layer = [
+      GENERATION_VERSION as u64, params.index as u64, params.top as u32 as u64, params.bottom as u32 as u64,
+      params.size.to_bits(), params.build_limit as u32 as u64
+    ];
+    layer.into_iter()
+      .chain(skirt)
+      .chain(landmass)
+      .chain(shapes)
+      .chain([params.symmetry as u64])
+      .chain(erosion)
+      .chain(params.slab_fill.fingerprint())
+      .chain([params.caves as u64, params.pillar_clusters as u64])
+      .chain(rng_fingerprint)
+      .fold(0, |hash, value| crate::utility::mix64(hash ^ value))
   }
 }
 
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use std::path::{Path, PathBuf};
+
+  use super::*;
+  use super::collisions::{CollidingFeature, Collision};
+  use crate::generation::bedrock::Bedrock;
+  use crate::generation::blocks;
+  use crate::utility::mix64;
+
+  /// A city with the default options and the given number of layers, which doesn't use the layer cache
+  fn generate_city(seed: u64, layer_count: usize) -> City {
+    generate_cached_city(seed, layer_count, None)
+  }
+
+  fn generate_cached_city(seed: u64, layer_count: usize, cache: Option<LayerCache>) -> City {
+    generate_city_with(seed, &GeneratorOptions { layer_count, ..Default::default() }, cache)
+  }
+
+  fn generate_city_with(seed: u64, options: &GeneratorOptions, cache: Option<LayerCache>) -> City {
+    let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let bedrock = Bedrock::new(&mut source_rng, options.flat_bedrock, options.world_height.min_z);
+    let ocean = Ocean::new(&mut source_rng, bedrock, options.wavy_ocean);
+    let source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    City::generate_new(source_rng, options, cache, &ocean, CityPlacement::default())
+  }
+
+  /// The directory of an empty layer cache of its own for each test
+  fn test_cache_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("glt-mc-generator-layer-cache-{}-{}", name, std::process::id()))
+  }
+
+  /// An empty layer cache in the given directory, with no limit on its size
+  fn test_cache(dir: &Path) -> LayerCache {
+    let cache = LayerCache::new(dir.to_owned(), u64::MAX);
+    cache.clear().unwrap();
+    cache
+  }
+
+  /// Generates the bottom layer of a city without finishing it, loading it from the city's cache if it can be
+  fn generate_bottom_layer(city: &City) {
+    city.layers[0].generate(city.cache.as_ref(), city.sea_floor(0));
+  }
+
+  /// The blocks of a layer at pseudorandom positions within its estimated bounding box
+  fn sample_layer(city: &City, i: usize, samples: u64) -> Vec<Option<Block>> {
+    let BoundingBox { min, max } = city.layers[i].estimated_bounding_box;
+    let axis = |hash: u64, min: i32, max: i32| min + (hash % (max - min + 1) as u64) as i32;
+    (0..samples)
+      .map(|n| {
+        let hash = mix64(n);
+        let (x, y, z) = (axis(hash, min.x, max.x), axis(hash >> 20, min.y, max.y), axis(hash >> 40, min.z, max.z));
+        city.layer(i).block_material_at(IVec3::new(x, y, z)).map(Cow::into_owned)
+      })
+      .collect()
+  }
 
+  #[test]
+  #[ignore = "generates the same two-layer city three times over, which takes minutes"]
+  fn layers_are_the_same_whichever_is_generated_first() {
+    let force_bottom_up = |city: &City| (0..city.layer_count()).for_each(|i| { city.layer(i); });
+    let force_top_down = |city: &City| (0..city.layer_count()).rev().for_each(|i| { city.layer(i); });
+    let orders: [&(dyn Fn(&City) + Sync); 3] = [&force_bottom_up, &force_top_down, &City::generate_all];
+    let cities = orders.into_par_iter()
+      .map(|force| {
+        let city = generate_city(5, 2);
+        force(&city);
+        city
+      })
+      .collect::<Vec<City>>();
 
-fn windows_mut_each<T, F, const N: usize>(slice: &mut [T], mut f: F)
-where F: FnMut(&mut [T; N]) {
-  if slice.len() < N { return };
-  for i in 0..(slice.len() + 1 - N) {
-    match (&mut slice[i..(i + N)]).try_into() {
-      Ok(window) => f(window),
-      Err(err) => unreachable!("{}", err)
+    for i in 0..cities[0].layer_count() {
+      let expected = sample_layer(&cities[0], i, 20000);
+      assert!(expected.iter().any(Option::is_some), "layer {} has no blocks to compare", i);
+      assert_eq!(sample_layer(&cities[1], i, 20000), expected, "layer {} differs when generated top down", i);
+      assert_eq!(sample_layer(&cities[2], i, 20000), expected, "layer {} differs when generated in parallel", i);
     };
-  };
+  }
+
+  #[test]
+  fn layers_loaded_from_the_cache_are_not_regenerated() {
+    let cache = test_cache(&test_cache_dir("hit"));
+    let generated = generate_cached_city(11, 1, Some(cache.clone()));
+    let expected = sample_layer(&generated, 0, 5000);
+    assert!(expected.iter().any(Option::is_some), "the layer has no blocks to compare");
+    assert_eq!((generated.generation_counts(), generated.cache_load_counts()), (vec![1], vec![0]));
+    drop(generated);
+
+    let loaded = generate_cached_city(11, 1, Some(cache.clone()));
+    assert_eq!(sample_layer(&loaded, 0, 5000), expected);
+    assert_eq!((loaded.generation_counts(), loaded.cache_load_counts()), (vec![1], vec![1]));
+    cache.clear().unwrap();
+  }
+
+  #[test]
+  fn caches_of_other_seeds_and_versions_are_ignored() {
+    let dir = test_cache_dir("miss");
+    let cache = test_cache(&dir);
+    generate_bottom_layer(&generate_cached_city(11, 1, Some(cache.clone())));
+    let other_seed = generate_cached_city(12, 1, Some(cache.clone()));
+    generate_bottom_layer(&other_seed);
+    assert_eq!(other_seed.cache_load_counts(), [0]);
+
+    // Leaves only the layer of seed 11 in the cache, then makes it look like another version of the generator wrote it
+    cache.clear().unwrap();
+    generate_bottom_layer(&generate_cached_city(11, 1, Some(cache.clone())));
+    let files = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+    assert_eq!(files.len(), 1);
+    let mut bytes = fs::read(&files[0]).unwrap();
+    // The version follows the four bytes of the file's magic number
+    bytes[4..8].copy_from_slice(&(GENERATION_VERSION + 1).to_le_bytes());
+    fs::write(&files[0], &bytes).unwrap();
+    let other_version = generate_cached_city(11, 1, Some(cache.clone()));
+    generate_bottom_layer(&other_version);
+    assert_eq!(other_version.cache_load_counts(), [0]);
+
+    // The layer was stored again by the current version
+    generate_bottom_layer(&other_version);
+    assert_eq!(other_version.cache_load_counts(), [1]);
+    cache.clear().unwrap();
+  }
+
+  /// Two layers, whose collisions are resolved with the given policy. Lighting and weathering are left off, since they
+  /// add to the blocks of the buildings once their collisions have been resolved.
+  fn collision_options(collisions: CollisionPolicy) -> GeneratorOptions {
+    GeneratorOptions {
+      layer_count: 2,
+      collisions,
+      lighting: Lighting::None,
+      weathering: false,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn collisions_between_layers_are_reported_and_resolved() {
+    let (seed, ignored) = (0..20)
+      .map(|seed| (seed, generate_city_with(seed, &collision_options(CollisionPolicy::Ignore), None)))
+      .find(|(_, city)| !city.layer(0).collisions().is_empty())
+      .expect("none of the cities have layers which collide");
+    let removed = generate_city_with(seed, &collision_options(CollisionPolicy::Remove), None);
+    let pillars = ignored.layer(1).pillar_clusters().collect::<Vec<_>>();
+    let (kept, resolved) = (ignored.layer(0), removed.layer(0));
+
+    // The same collisions are found whatever the policy, and only their resolution differs
+    assert!(kept.collisions().iter().all(|collision| collision.layer == 0 && collision.pillar < pillars.len()));
+    let expected = kept.collisions().iter()
+      .map(|&collision| Collision { policy: CollisionPolicy::Remove, ..collision })
+      .collect::<Vec<Collision>>();
+    assert_eq!(resolved.collisions(), expected);
+
+    // Ignored collisions are all still there, while removing them leaves none behind
+    assert_eq!(kept.find_collisions_with(0, &pillars, CollisionPolicy::Ignore), kept.collisions());
+    assert_eq!(resolved.find_collisions_with(0, &pillars, CollisionPolicy::Remove), Vec::<Collision>::new());
+    let kinds = [CollidingFeature::Building, CollidingFeature::Prop, CollidingFeature::Pool];
+    let collided = kinds.map(|kind| expected.iter().filter(|collision| collision.feature == kind).count());
+    let (kept_counts, resolved_counts) = (kept.feature_counts(), resolved.feature_counts());
+    assert_eq!(resolved_counts, [0, 1, 2].map(|i| kept_counts[i] - collided[i]));
+
+    let report = CollisionReport { collisions: expected.clone() }.to_string();
+    let mut lines = report.lines();
+    assert_eq!(lines.next(), Some(format!("{} collisions between layers", expected.len()).as_str()));
+    for collision in expected.iter() {
+      let line = format!(
+        "  layer 0 {} {} collides with pillar {} of layer 1, removed",
+        collision.feature.name(), collision.index, collision.pillar
+      );
+      assert_eq!(lines.next(), Some(line.as_str()));
+    };
+    assert_eq!(lines.next(), None);
+  }
+
+  #[test]
+  fn sampling_one_layer_generates_only_it() {
+    let city = generate_city(7, 3);
+    let top = city.layer_count() - 1;
+    city.bounding_box();
+    city.shortlist(&mut ChunkContext::new(BoundingBox::new(IVec3::splat(100000), IVec3::splat(100015))));
+    assert!(city.generation_counts().iter().all(|&count| count == 0), "nothing should be generated away from the city");
+
+    // Only the top layer reaches above the layer beneath it
+    let band_min = city.layers[top - 1].estimated_bounding_box.max.z + 1;
+    let mut band = city.layers[top].estimated_bounding_box;
+    assert!(band_min <= band.max.z);
+    band.min.z = band_min;
+    city.block_entities_in(band);
+    (band.min.z..=band.max.z).step_by(8).for_each(|z| { city.layers_containing(IVec3::new(0, 0, z)).count(); });
+    let mut expected = vec![0; city.layer_count()];
+    expected[top] = 1;
+    assert_eq!(city.generation_counts(), expected);
+  }
+
+  #[test]
+  fn shafts_have_an_unbroken_ladder_through_an_opening_in_every_layer() {
+    let city = generate_city(0, 2);
+    let material_at = |pos: IVec3| city.block_material_at(pos).map(Cow::into_owned);
+    let levels = (0..city.layer_count()).filter_map(|i| city.layer(i).level()).collect::<Vec<i32>>();
+    let (&top, lower) = levels.split_last().unwrap();
+    assert!(!city.shafts().is_empty());
+    for shaft in city.shafts() {
+      let bounding_box = shaft.bounding_box();
+      let center = (bounding_box.min.xy() + bounding_box.max.xy()) / 2;
+      assert_eq!(bounding_box.max.z, top);
+
+      // From just above the dock up to the surface of the top layer, passing through every slab on the way
+      for z in bounding_box.min.z + 1..=top {
+        assert_eq!(material_at(center.extend(z)), Some(blocks::LADDER_SOUTH), "at {}", center);
+      };
+
+      assert_eq!(material_at(center.extend(bounding_box.min.z)), Some(blocks::SPRUCE_PLANKS));
+      assert!(!city.block_at(center.extend(top + 1)) && !city.block_at(center.extend(top + 2)), "at {}", center);
+
+      // A doorway opens onto the dock and onto the surface of every lower layer
+      for &floor in [bounding_box.min.z].iter().chain(lower) {
+        for pos in [1, 2].map(|y| center + IVec2::new(0, y)) {
+          assert!(!city.block_at(pos.extend(floor + 1)), "the doorway at {} onto {} is blocked", pos, floor);
+          assert!(!city.block_at(pos.extend(floor + 2)), "the doorway at {} onto {} is blocked", pos, floor);
+        };
+
+        let beside = (center + IVec2::new(1, 1)).extend(floor + 1);
+        assert!(city.block_at(beside), "no wall beside the doorway onto {}", floor);
+      };
+    };
+  }
 }
@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::layer::Layer;
+use super::letterbox::Letterbox;
+use super::shaft::{Shaft, ShaftFit};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::rings_in_box;
+
+
+
+/// The furthest that a beacon is moved along each axis from the centroid of its landmass to find room for it
+const MAX_NUDGE: i32 = 16;
+/// The glass over the beacon of each layer from the bottom up, tinting its beam so that the layer can be told apart.
+/// Layers past the last color start over from the first.
+const LAYER_GLASS: [&Block; 8] = [
+  &blocks::RED_STAINED_GLASS,
+  &blocks::YELLOW_STAINED_GLASS,
+  &blocks::BLUE_STAINED_GLASS,
+  &blocks::LIME_STAINED_GLASS,
+  &blocks::MAGENTA_STAINED_GLASS,
+  &blocks::CYAN_STAINED_GLASS,
+  &blocks::ORANGE_STAINED_GLASS,
+  &blocks::PURPLE_STAINED_GLASS
+];
+
+/// A beacon marking the middle of a layer's landmass, on a 3x3 base of iron set into the landmass' upper slab,
+/// with stained glass on top of it coloring its beam by the layer's index
+#[derive(Debug, Clone)]
+pub struct Beacon {
+  /// The column of the beacon block, in the middle of its base
+  center: IVec2,
+  /// The z value of the base, in place of the landmass' upper slab
+  base: i32,
+  glass: &'static Block,
+  skylight: Option<Skylight>
+}
+
+impl Beacon {
+  /// Places a beacon as near as possible to the centroid of each layer's landmass, searching outwards from it,
+  /// wherever there is room for one clear of everything on the landmass' surface, of the shafts and of the letterboxes.
+  /// The beam only shows with nothing above it, so the column over the beacon must be clear of everything on the
+  /// layers above it, and a skylight is cut through the landmasses of those that cover it.
+  pub fn marking_centroids(layers: &[&Layer], shafts: &[Shaft], letterboxes: &[Letterbox]) -> Vec<Self> {
+    let mut beacons: Vec<Beacon> = Vec::new();
+    for (i, layer) in layers.iter().enumerate() {
+      let (level, centroid) = match (layer.level(), layer.landmass_centroid()) {
+        (Some(level), Some(centroid)) => (level, centroid),
+        _ => continue
+      };
+
+      let above = &layers[i + 1..];
+      let is_clear = |center: IVec2| {
+        let (min, max) = (center - 1, center + 1);
+        let overlaps = |bounding_box: BoundingBox| {
+          min.cmple(bounding_box.max.xy()).all() && max.cmpge(bounding_box.min.xy()).all()
+        };
+
+        layer.shaft_fit(min, max) == ShaftFit::Inside &&
+          above.iter().all(|upper| upper.shaft_fit(center, center) != ShaftFit::Blocked) &&
+          !shafts.iter().any(|shaft| overlaps(shaft.bounding_box())) &&
+          !letterboxes.iter().any(|letterbox| overlaps(letterbox.bounding_box())) &&
+          // The skylights of lower beacons pass up through this layer
+          !beacons.iter().any(|beacon| beacon.center.cmpge(min).all() && beacon.center.cmple(max).all())
+      };
+
+      let offsets = rings_in_box(IVec2::splat(-MAX_NUDGE), IVec2::splat(MAX_NUDGE));
+      match offsets.map(|offset| centroid + offset).find(|&center| is_clear(center)) {
+        Some(center) => {
+          let spans = above.iter()
+            .filter(|upper| upper.shaft_fit(center, center) == ShaftFit::Inside)
+            .filter_map(|upper| Some((upper.lower_slab_z()?, upper.level()? + 1)))
+            .collect::<Vec<(i32, i32)>>();
+          let skylight = (!spans.is_empty()).then(|| Skylight { column: center, spans });
+          let glass = LAYER_GLASS[i % LAYER_GLASS.len()];
+          beacons.push(Beacon { center, base: level, glass, skylight });
+        },
+        None => log::debug!("found no room for a beacon near the centroid {} of layer {}", centroid, i)
+      };
+    };
+
+    beacons
+  }
+
+  /// The column cut through the landmasses above this beacon, if any of them cover it
+  pub fn skylight(&self) -> Option<&Skylight> {
+    self.skylight.as_ref()
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<&'static Block> {
+    let offset = pos.xy() - self.center;
+    match pos.z - self.base {
+      0 if offset.abs().cmple(IVec2::ONE).all() => Some(&blocks::IRON_BLOCK),
+      1 if offset == IVec2::ZERO => Some(&blocks::BEACON),
+      2 if offset == IVec2::ZERO => Some(self.glass),
+      _ => None
+    }
+  }
+}
+
+impl Geometry for Beacon {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::try_new((self.center - 1).extend(self.base), (self.center + 1).extend(self.base + 2)).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Beacon {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(Cow::Borrowed)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::IRON_BLOCK, blocks::BEACON, self.glass.clone()]);
+  }
+}
+
+
+
+/// A single column cut through each landmass covering a beacon, from its lower slab up to just above its upper slab.
+/// Every other feature of the city is carved away wherever this has a block.
+#[derive(Debug, Clone)]
+pub struct Skylight {
+  column: IVec2,
+  /// The lowest and highest z values cut through each landmass, from the bottom up
+  spans: Vec<(i32, i32)>
+}
+
+impl Geometry for Skylight {
+  fn bounding_box(&self) -> BoundingBox {
+    let bottom = self.spans.first().expect("unreachable").0;
+    let top = self.spans.last().expect("unreachable").1;
+    BoundingBox::try_new(self.column.extend(bottom), self.column.extend(top)).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    pos.xy() == self.column && self.spans.iter().any(|&(bottom, top)| pos.z >= bottom && pos.z <= top)
+  }
+}
@@ -1,61 +1,561 @@
-use glam::{IVec2, IVec3};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
 
 use super::landmass_shape::BuildingShape;
-use crate::generation::{BoundingBox, Geometry};
+use crate::generation::block_entity::{BlockEntity, Nbt};
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{hash2, hash_to_unit, mix64};
+
 
 
+/// Number of blocks between each floor of a building
+const FLOOR_HEIGHT: i32 = 2;
+/// Number of floors between each row of balconies
+const BALCONY_FLOOR_INTERVAL: i32 = 4;
+/// Window gaps are left in every this many columns and rows of a wall,
+/// counting from the wall's nearest corner and from the building's level
+const WINDOW_SPACING: i32 = 2;
+/// The fraction of windows with a streak of stained wall running down from them, on weathered buildings
+const STREAK_CHANCE: f64 = 0.35;
+/// The longest a streak beneath a window can be, although it always stops short of the next window down
+const MAX_STREAK_LENGTH: u64 = 3;
+/// The shortest period of the recessed columns along each wall, counting from the wall's nearest corner
+const MIN_RECESS_PERIOD: u64 = 3;
+/// The longest period of the recessed columns, each building's period being drawn from its detail seed
+const MAX_RECESS_PERIOD: u64 = 5;
+/// The fraction of buildings which have a basement sunk into the landmass beneath them
+const BASEMENT_CHANCE: f64 = 0.3;
+/// The fraction of buildings which have a garden on their roof
+const ROOF_GARDEN_CHANCE: f64 = 0.2;
+/// The fraction of a roof garden taken up by leaves rather than grass
+const ROOF_LEAVES_CHANCE: f64 = 0.15;
+/// The fraction of buildings which have a chest on their ground floor
+const CHEST_CHANCE: f64 = 0.15;
+const CHEST_LOOT_TABLES: &[&str] = &[
+  "minecraft:chests/simple_dungeon",
+  "minecraft:chests/abandoned_mineshaft",
+  "minecraft:chests/village/village_toolsmith",
+  "minecraft:chests/village/village_cartographer"
+];
+/// The height above the building's level of the sign by its doorway, chosen so that the wall behind it is solid
+const SIGN_HEIGHT: i32 = 3;
+const NAME_PREFIXES: &[&str] = &["Tower", "Block", "Hall", "Court", "House", "Plaza", "Terrace", "Annex"];
+/// The narrowest footprint a building can have along either axis, in blocks.
+/// Anything narrower has no interior between its walls, so the opposing walls of the lattice would overlap.
+pub(super) const MIN_FOOTPRINT_SIZE: i32 = 3;
+/// The shortest a building can be, in blocks
+pub(super) const MIN_HEIGHT: u32 = 3;
 
 #[derive(Debug, Clone)]
 pub struct Building {
   pub(super) edge_min: IVec2,
   pub(super) edge_max: IVec2,
   pub(super) level: i32,
-  pub(super) height: u32
+  pub(super) height: u32,
+  /// The number of blocks that the walls of this building extend down into the landmass below `level`
+  pub(super) basement_depth: u32,
+  /// A seed used to decide the small details of this building's appearance
+  pub(super) detail_seed: u64,
+  /// Whether this building's roof is a garden, rimmed by the tops of its walls
+  pub(super) roof_garden: bool,
+  /// The material used for this building's walls
+  pub(super) material: Block,
+  /// The darker variant of the wall material used for streaks beneath the windows, if this building is weathered.
+  /// Weathering is applied after layers are loaded, so this isn't stored in the layer cache.
+  pub(super) stained_material: Option<Block>,
+  /// Whether each storey of this building has a light in the middle of its ceiling.
+  /// Lighting is applied after layers are loaded, so this isn't stored in the layer cache.
+  pub(super) lit: bool
 }
 
 impl Building {
-  pub fn new(edge1: IVec2, edge2: IVec2, level: i32, height: u32) -> Self {
+  pub fn new(edge1: IVec2, edge2: IVec2, level: i32, height: u32, detail_seed: u64, material: Block) -> Self {
     Building {
       edge_min: IVec2::min(edge1, edge2),
       edge_max: IVec2::max(edge1, edge2),
       level,
-      height
-    }
+      height,
+      basement_depth: 0,
+      detail_seed,
+      roof_garden: has_roof_garden(detail_seed),
+      material,
+      stained_material: None,
+      lit: false
+    }.validated()
   }
 
-  pub(super) fn from_shape(building_shape: BuildingShape, level: i32, height: u32) -> Self {
+  pub(super) fn from_shape(
+    building_shape: BuildingShape, level: i32, height: u32, basement_depth: u32, detail_seed: u64, material: Block
+  ) -> Self {
     Building {
       edge_min: building_shape.edge_min * 2,
       edge_max: building_shape.edge_max * 2,
       level,
-      height: height * 2 + 1
-    }
+      height: height * 2 + 1,
+      basement_depth: if has_basement(detail_seed) { basement_depth } else { 0 },
+      detail_seed,
+      roof_garden: has_roof_garden(detail_seed),
+      material,
+      stained_material: None,
+      lit: false
+    }.validated()
+  }
+
+  /// Checks in debug builds that this building is large enough for its walls not to overlap
+  fn validated(self) -> Self {
+    let size = self.edge_max - self.edge_min + IVec2::ONE;
+    debug_assert!(
+      size.min_element() >= MIN_FOOTPRINT_SIZE,
+      "building footprint {} is narrower than {} blocks", size, MIN_FOOTPRINT_SIZE
+    );
+    debug_assert!(self.height >= MIN_HEIGHT, "building height {} is less than {} blocks", self.height, MIN_HEIGHT);
+    self
   }
 
   pub fn top(&self) -> i32 {
     self.level + self.height as i32
   }
+
+  /// The lowest z value of this building, including its basement
+  pub fn bottom(&self) -> i32 {
+    self.level - self.basement_depth as i32
+  }
+
+  /// The area inside of this building's walls and the range of z values spanned by its basement,
+  /// which must be hollowed out of the landmass, if this building has a basement
+  pub fn basement_interior(&self) -> Option<(IVec2, IVec2, RangeInclusive<i32>)> {
+    if self.basement_depth == 0 { return None };
+    Some((self.edge_min + IVec2::ONE, self.edge_max - IVec2::ONE, self.bottom()..=self.level - 1))
+  }
+
+  /// This building moved by the given offset, without a basement, for moving it partly off of the landmass.
+  /// A basement would hang beneath the part of the building that the landmass no longer reaches.
+  pub(super) fn cantilevered(&self, offset: IVec2) -> Building {
+    Building {
+      edge_min: self.edge_min + offset,
+      edge_max: self.edge_max + offset,
+      basement_depth: 0,
+      ..self.clone()
+    }
+  }
+
+  /// Streaks the walls beneath some of this building's windows with a darker variant of its material, if it has one
+  pub(super) fn weather(&mut self) {
+    self.stained_material = blocks::stained(&self.material);
+  }
+
+  /// Places a light in the middle of the ceiling of each of this building's storeys
+  pub(super) fn light(&mut self) {
+    self.lit = true;
+  }
+
+  /// The z values above the building's level of the lights in its ceilings, one per storey with headroom beneath it.
+  /// The ground floor and the rows of balconies each start a storey, which reaches up to the next one or to the roof.
+  fn ceiling_light_heights(&self) -> impl Iterator<Item = i32> {
+    let height = self.height as i32;
+    let storey_height = FLOOR_HEIGHT * BALCONY_FLOOR_INTERVAL;
+    (0..height).step_by(storey_height as usize)
+      .map(move |floor| (floor, (floor + storey_height).min(height) - 1))
+      .filter(|&(floor, light)| light > floor)
+      .map(|(_, light)| light)
+  }
+
+  /// The column in the middle of this building's interior, which its ceiling lights hang in
+  fn ceiling_light_column(&self) -> IVec2 {
+    (self.edge_min + self.edge_max) / 2
+  }
+
+  /// Every column along a wall whose distance from the wall's nearest corner is a multiple of this period
+  /// is recessed by one block
+  fn recess_period(&self) -> i32 {
+    (MIN_RECESS_PERIOD + self.detail_seed % (MAX_RECESS_PERIOD - MIN_RECESS_PERIOD + 1)) as i32
+  }
+
+  /// Whether a column along a wall `column_size` blocks long is recessed
+  fn is_recessed(&self, column: i32, column_size: i32) -> bool {
+    column.min(column_size - column) % self.recess_period() == 0
+  }
+
+  /// A name for this building, like "Tower 14-B"
+  pub fn name(&self) -> String {
+    let hash = mix64(self.detail_seed ^ 0x6e616d65);
+    let prefix = NAME_PREFIXES[(hash % NAME_PREFIXES.len() as u64) as usize];
+    let number = (hash >> 16) % 99 + 1;
+    let letter = (b'A' + ((hash >> 32) % 6) as u8) as char;
+    format!("{} {}-{}", prefix, number, letter)
+  }
+
+  /// The position of this building's chest, if it has one, in a corner of its ground floor
+  fn chest_pos(&self) -> Option<IVec3> {
+    let hash = mix64(self.detail_seed ^ 0x63686573);
+    (hash_to_unit(hash) < CHEST_CHANCE).then(|| (self.edge_min + IVec2::ONE).extend(self.level + 1))
+  }
+
+  /// The position of the sign outside of this building's doorway, on its south wall,
+  /// in the column nearest to the middle of the wall which is not recessed
+  fn sign_pos(&self) -> IVec3 {
+    let size = self.edge_max.x - self.edge_min.x;
+    let column = (size / 2..size)
+      .find(|&column| !self.is_recessed(column, size))
+      .unwrap_or(size / 2);
+    IVec2::new(self.edge_min.x + column, self.edge_min.y - 1).extend(self.level + SIGN_HEIGHT)
+  }
+
+  /// Positions on the south side of this building's ground floor showing a corner post, a plain stretch of wall
+  /// and a window gap, for sampling representative blocks of the building
+  pub fn probes(&self) -> [(&'static str, IVec3); 3] {
+    // The window gaps of recessed columns are in the recessed wall, one block further in
+    let size = self.edge_max.x - self.edge_min.x;
+    let window_depth = self.is_recessed(2, size) as i32;
+    [
+      ("building corner", self.edge_min.extend(self.level + 1)),
+      ("building wall", (self.edge_min + IVec2::new(1, 0)).extend(self.level + 1)),
+      ("building window", (self.edge_min + IVec2::new(2, window_depth)).extend(self.level + 2))
+    ]
+  }
+
+  /// The chest and sign in this building, which need to be attached to the chunks they are in
+  pub fn block_entities(&self) -> Vec<BlockEntity> {
+    let mut block_entities = Vec::new();
+    if let Some(chest_pos) = self.chest_pos() {
+      let hash = mix64(self.detail_seed ^ 0x6c6f6f74);
+      let loot_table = CHEST_LOOT_TABLES[(hash % CHEST_LOOT_TABLES.len() as u64) as usize];
+      block_entities.push({
+        BlockEntity::new(chest_pos, blocks::CHEST_SOUTH, "minecraft:chest")
+          .with("LootTable", Nbt::String(loot_table.to_owned()))
+          .with("LootTableSeed", Nbt::Long(hash as i64))
+      });
+    };
+
+    let message = |text: &str| Nbt::String(format!("{{\"text\":\"{}\"}}", text));
+    let messages = ["", &self.name(), "", ""].map(message);
+    block_entities.push({
+      BlockEntity::new(self.sign_pos(), blocks::OAK_WALL_SIGN_NORTH, "minecraft:sign")
+        .with("front_text", Nbt::compound([
+          ("messages", Nbt::list(messages.to_vec()).expect("every message is a string")),
+          ("color", Nbt::String("black".to_owned())),
+          ("has_glowing_text", Nbt::Byte(0))
+        ]))
+        .with("is_waxed", Nbt::Byte(1))
+    });
+
+    block_entities
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<BuildingPart> {
+    if !(self.bottom()..=self.top()).contains(&pos.z) { return None };
+    if Some(pos) == self.chest_pos() { return Some(BuildingPart::Chest) };
+    if pos == self.sign_pos() { return Some(BuildingPart::Sign) };
+    let size = self.edge_max - self.edge_min;
+    let local = pos.xy() - self.edge_min;
+    let z = pos.z - self.level;
+
+    // Corner posts always stay at the original footprint
+    let corner_x = local.x == 0 || local.x == size.x;
+    let corner_y = local.y == 0 || local.y == size.y;
+    if corner_x && corner_y {
+      return Some(BuildingPart::Wall);
+    };
+
+    ret_if_some!(self.sample_wall(local.x, size.x, local.y, size.y, z, WallAxis::Y));
+    ret_if_some!(self.sample_wall(local.y, size.y, local.x, size.x, z, WallAxis::X));
+    ret_if_some!(self.sample_roof_garden(local, size, z));
+    ret_if_some!(self.sample_ceiling_light(pos.xy(), z));
+    None
+  }
+
+  /// Samples the light in the ceiling of each storey, if this building is lit
+  fn sample_ceiling_light(&self, column: IVec2, z: i32) -> Option<BuildingPart> {
+    if !self.lit || column != self.ceiling_light_column() { return None };
+    self.ceiling_light_heights().any(|light| light == z).then(|| BuildingPart::Light)
+  }
+
+  /// Samples the garden filling the roof inside of the walls, if this building has one
+  fn sample_roof_garden(&self, local: IVec2, size: IVec2, z: i32) -> Option<BuildingPart> {
+    if !self.roof_garden || z != self.height as i32 { return None };
+    if local.cmple(IVec2::ZERO).any() || local.cmpge(size).any() { return None };
+    let hash = hash2(mix64(self.detail_seed ^ 0x726f6f66), local);
+    Some(match hash_to_unit(hash) < ROOF_LEAVES_CHANCE {
+      true => BuildingPart::Leaves,
+      false => BuildingPart::Grass
+    })
+  }
+
+  /// Samples the pair of opposing walls running along `axis`.
+  /// `depth` is the local position across the walls and `column` is the local position along them.
+  ///
+  /// The window gaps and recessed columns are placed relative to the nearest corner of the wall rather than in world
+  /// space, so that the facades of a building do not depend on its position and each wall is symmetric.
+  fn sample_wall(
+    &self, depth: i32, depth_size: i32, column: i32, column_size: i32, z: i32, axis: WallAxis
+  ) -> Option<BuildingPart> {
+    if column <= 0 || column >= column_size { return None };
+    let recessed = self.is_recessed(column, column_size);
+    let balcony_spacing = FLOOR_HEIGHT * BALCONY_FLOOR_INTERVAL;
+    let corner_distance = column.min(column_size - column);
+    for (side, inset) in [depth, depth_size - depth].into_iter().enumerate() {
+      if inset == recessed as i32 {
+        if !(corner_distance % WINDOW_SPACING == 0 && z % WINDOW_SPACING == 0) {
+          let wall = (axis as u64) << 1 | side as u64;
+          return Some(match self.is_streaked(wall, column, corner_distance, z) {
+            true => BuildingPart::Streak,
+            false => BuildingPart::Wall
+          });
+        };
+      } else if inset == -1 && !recessed {
+        if z > 0 && z < self.height as i32 && z % balcony_spacing == 0 {
+          return Some(BuildingPart::Balcony);
+        } else if z > 1 && (z - 1) % balcony_spacing == 0 {
+          return Some(BuildingPart::Railing(axis));
+        };
+      };
+    };
+
+    None
+  }
+
+  /// Whether a block of wall is part of a streak running down from the window above it, on a weathered building.
+  /// The window is found from the same spacing that places the windows, so nothing else needs to be sampled.
+  /// `wall` distinguishes the four walls of the building from each other.
+  fn is_streaked(&self, wall: u64, column: i32, corner_distance: i32, z: i32) -> bool {
+    if self.stained_material.is_none() || corner_distance % WINDOW_SPACING != 0 { return false };
+    let window_z = z + WINDOW_SPACING - z.rem_euclid(WINDOW_SPACING);
+    if window_z > self.height as i32 { return false };
+    let hash = hash2(mix64(self.detail_seed ^ 0x73747265 ^ wall), IVec2::new(column, window_z));
+    // The low bits are left over by `hash_to_unit`
+    let length = 1 + (hash & 0x7ff) % MAX_STREAK_LENGTH;
+    hash_to_unit(hash) < STREAK_CHANCE && (window_z - z) as u64 <= length
+  }
 }
 
 impl Geometry for Building {
   fn bounding_box(&self) -> BoundingBox {
-    let min = self.edge_min.extend(self.level);
-    let max = self.edge_max.extend(self.top());
+    // Balconies protrude one block outwards from the walls
+    // The corners are always in order, since the edges are sorted when a building is made and checked when it is
+    // decoded, and its height and basement depth are unsigned
+    let min = (self.edge_min - IVec2::ONE).extend(self.bottom());
+    let max = (self.edge_max + IVec2::ONE).extend(self.top());
     BoundingBox::new(min, max)
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    if (self.level..=self.top()).contains(&pos.z) {
-      let matches_x = self.edge_min.x == pos.x || self.edge_max.x == pos.x;
-      let matches_y = self.edge_min.y == pos.y || self.edge_max.y == pos.y;
-      let within_x = pos.x >= self.edge_min.x && pos.x <= self.edge_max.x;
-      let within_y = pos.y >= self.edge_min.y && pos.y <= self.edge_max.y;
-      let z = pos.z - self.level;
-      (matches_x && matches_y) ||
-      (matches_x && within_y && !(pos.y.rem_euclid(2) == 0 && z.rem_euclid(2) == 0)) ||
-      (matches_y && within_x && !(pos.x.rem_euclid(2) == 0 && z.rem_euclid(2) == 0))
-    } else {
-      false
-    }
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Building {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      BuildingPart::Wall => &self.material,
+      BuildingPart::Streak => self.stained_material.as_ref().unwrap_or(&self.material),
+      BuildingPart::Balcony => &blocks::SMOOTH_STONE_SLAB_BOTTOM,
+      BuildingPart::Railing(WallAxis::X) => &blocks::DARK_OAK_FENCE_EAST_WEST,
+      BuildingPart::Railing(WallAxis::Y) => &blocks::DARK_OAK_FENCE_NORTH_SOUTH,
+      BuildingPart::Chest => &blocks::CHEST_SOUTH,
+      BuildingPart::Sign => &blocks::OAK_WALL_SIGN_NORTH,
+      BuildingPart::Grass => &blocks::GRASS_BLOCK,
+      BuildingPart::Leaves => &blocks::OAK_LEAVES,
+      BuildingPart::Light => &blocks::SEA_LANTERN
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.material.clone());
+    out.extend(self.stained_material.clone());
+    out.extend([
+      blocks::SMOOTH_STONE_SLAB_BOTTOM, blocks::DARK_OAK_FENCE_EAST_WEST, blocks::DARK_OAK_FENCE_NORTH_SOUTH,
+      blocks::CHEST_SOUTH, blocks::OAK_WALL_SIGN_NORTH
+    ]);
+    if self.roof_garden {
+      out.extend([blocks::GRASS_BLOCK, blocks::OAK_LEAVES]);
+    };
+
+    if self.lit {
+      out.insert(blocks::SEA_LANTERN);
+    };
+  }
+}
+
+impl Encode for Building {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.edge_min);
+    encoder.put(&self.edge_max);
+    encoder.put_i32(self.level);
+    encoder.put_u32(self.height);
+    encoder.put_u32(self.basement_depth);
+    encoder.put_u64(self.detail_seed);
+    encoder.put_bool(self.roof_garden);
+    encoder.put(&self.material);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let (edge_min, edge_max) = (decoder.take::<IVec2>()?, decoder.take::<IVec2>()?);
+    if !edge_min.cmple(edge_max).all() { return None };
+    Some(Building {
+      edge_min,
+      edge_max,
+      level: decoder.take_i32()?,
+      height: decoder.take_u32()?,
+      basement_depth: decoder.take_u32()?,
+      detail_seed: decoder.take_u64()?,
+      roof_garden: decoder.take_bool()?,
+      material: decoder.take()?,
+      stained_material: None,
+      lit: false
+    })
+  }
+}
+
+fn has_basement(detail_seed: u64) -> bool {
+  // The detail seed is scrambled first, since its low bits are already used to decide the recess period
+  hash_to_unit(mix64(detail_seed)) < BASEMENT_CHANCE
+}
+
+fn has_roof_garden(detail_seed: u64) -> bool {
+  hash_to_unit(mix64(detail_seed ^ 0x67617264)) < ROOF_GARDEN_CHANCE
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WallAxis {
+  X,
+  Y
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BuildingPart {
+  Wall,
+  /// A block of wall stained beneath a window
+  Streak,
+  Balcony,
+  Railing(WallAxis),
+  Chest,
+  Sign,
+  Grass,
+  Leaves,
+  Light
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The part of a building at a position, leaving out its chest and sign, which are only ever on one side of it
+  fn facade_part(building: &Building, pos: IVec3) -> Option<BuildingPart> {
+    building.sample(pos).filter(|part| !matches!(part, BuildingPart::Chest | BuildingPart::Sign))
+  }
+
+  /// Buildings seven blocks wide, with one for each recess period
+  fn buildings(edge_min: IVec2) -> impl Iterator<Item = Building> {
+    (0..3).map(move |seed| Building::new(edge_min, edge_min + IVec2::new(6, 10), 0, 17, seed, blocks::WHITE_CONCRETE))
+  }
+
+  #[test]
+  fn opposing_walls_mirror_each_other() {
+    for building in buildings(IVec2::ZERO) {
+      let size = building.edge_max - building.edge_min;
+      for z in 0..building.height as i32 {
+        for x in 0..=size.x {
+          // From the balconies in through the recessed columns
+          for depth in -1..=1 {
+            let part = facade_part(&building, IVec3::new(x, depth, z));
+            let opposite = facade_part(&building, IVec3::new(x, size.y - depth, z));
+            let mirrored = facade_part(&building, IVec3::new(size.x - x, depth, z));
+            assert_eq!(part, opposite, "x={} depth={} z={}", x, depth, z);
+            assert_eq!(part, mirrored, "x={} depth={} z={}", x, depth, z);
+          };
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn facades_do_not_depend_on_position() {
+    let offset = IVec2::new(13, -7);
+    for (building, moved) in buildings(IVec2::ZERO).zip(buildings(offset)) {
+      let bounding_box = building.bounding_box();
+      for x in bounding_box.min.x..=bounding_box.max.x {
+        for y in bounding_box.min.y..=bounding_box.max.y {
+          for z in bounding_box.min.z..=bounding_box.max.z {
+            let pos = IVec3::new(x, y, z);
+            assert_eq!(building.sample(pos), moved.sample(pos + offset.extend(0)), "at {}", pos);
+          };
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn corners_are_never_recessed() {
+    for building in buildings(IVec2::ZERO) {
+      let size = building.edge_max - building.edge_min;
+      for corner in [IVec2::ZERO, IVec2::new(size.x, 0), IVec2::new(0, size.y), size] {
+        for z in 0..building.height as i32 {
+          assert_eq!(building.sample(corner.extend(z)), Some(BuildingPart::Wall), "corner {} at z={}", corner, z);
+        };
+      };
+    };
+  }
+
+  #[test]
+  #[cfg_attr(debug_assertions, should_panic(expected = "narrower than"))]
+  fn one_block_wide_footprints_are_rejected() {
+    Building::new(IVec2::ZERO, IVec2::new(0, 6), 0, 9, 0, blocks::WHITE_CONCRETE);
+  }
+
+  #[test]
+  #[cfg_attr(debug_assertions, should_panic(expected = "narrower than"))]
+  fn two_block_wide_footprints_are_rejected() {
+    Building::new(IVec2::ZERO, IVec2::new(6, 1), 0, 9, 0, blocks::WHITE_CONCRETE);
+  }
+
+  #[test]
+  fn three_block_wide_footprints_have_a_hollow_column_between_their_walls() {
+    for seed in 0..8 {
+      let building = Building::new(IVec2::ZERO, IVec2::splat(2), 0, 9, seed, blocks::WHITE_CONCRETE);
+      for z in 0..building.height as i32 {
+        assert_eq!(facade_part(&building, IVec3::new(1, 1, z)), None, "seed {} at z={}", seed, z);
+        for side in [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(2, 1), IVec2::new(1, 2)] {
+          let part = facade_part(&building, side.extend(z));
+          let is_wall = matches!(part, Some(BuildingPart::Wall | BuildingPart::Streak));
+          assert!(is_wall, "seed {} at {}: {:?}", seed, side, part);
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn recessed_columns_and_balconies_are_evenly_spaced() {
+    let balcony_spacing = FLOOR_HEIGHT * BALCONY_FLOOR_INTERVAL;
+    let mut periods = BTreeSet::new();
+    for seed in 0..8 {
+      let building = Building::new(IVec2::ZERO, IVec2::new(20, 10), 0, 33, seed, blocks::WHITE_CONCRETE);
+      let size = building.edge_max - building.edge_min;
+      // The outer plane of a wall is never broken by windows on odd rows, so gaps in it are recessed columns
+      let recessed = (1..size.x)
+        .filter(|&x| facade_part(&building, IVec3::new(x, 0, 1)).is_none())
+        .collect::<Vec<i32>>();
+      let period = recessed[0];
+      assert!((MIN_RECESS_PERIOD as i32..=MAX_RECESS_PERIOD as i32).contains(&period), "seed {}: {}", seed, period);
+      let expected = (1..size.x).filter(|&x| x.min(size.x - x) % period == 0).collect::<Vec<i32>>();
+      assert_eq!(recessed, expected, "seed {}", seed);
+      periods.insert(period);
+
+      for x in 1..size.x {
+        let balconies = (0..=building.height as i32)
+          .filter(|&z| facade_part(&building, IVec3::new(x, -1, z)) == Some(BuildingPart::Balcony))
+          .collect::<Vec<i32>>();
+        let expected = match recessed.contains(&x) {
+          true => Vec::new(),
+          false => (balcony_spacing..building.height as i32).step_by(balcony_spacing as usize).collect()
+        };
+        assert_eq!(balconies, expected, "seed {} at x={}", seed, x);
+      };
+    };
+
+    assert_eq!(periods.len() as u64, MAX_RECESS_PERIOD - MIN_RECESS_PERIOD + 1);
   }
 }
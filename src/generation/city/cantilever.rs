@@ -0,0 +1,211 @@
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::building::Building;
+use super::landmass_shape::LandmassShape;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{BoundingBox, Geometry};
+use crate::utility::CARDINAL4;
+
+
+
+const MIN_CANTILEVERS_PER_LAYER: usize = 1;
+const MAX_CANTILEVERS_PER_LAYER: usize = 3;
+/// The furthest a cantilevered building's footprint can reach past the edge of the landmass, in blocks
+pub const MAX_OVERHANG: i32 = 6;
+/// The shortest overhang worth building a platform for
+const MIN_OVERHANG: i32 = 3;
+/// The furthest a building is moved towards the edge to make it overhang.
+/// The buildings closest to the edge are kept clear of the road, so they start out several blocks in from it.
+const MAX_SHIFT: i32 = 16;
+
+/// The platform carrying the part of a cantilevered building that overhangs the edge of its landmass,
+/// along with a strut bracing the platform against the landmass' lower slab.
+/// Both are made of the landmass' material, which is left to the layer.
+#[derive(Debug, Clone)]
+pub struct Cantilever {
+  /// The footprint of the building being carried, which ties the cantilever to that building
+  building_min: IVec2,
+  building_max: IVec2,
+  /// The z value of the surface of the landmass, at the top of the platform
+  level: i32,
+  /// The columns of the building's footprint which the landmass doesn't reach, filled in for the platform
+  platform: SparseGrid<()>,
+  /// The lowest and highest z values of the strut in each of its columns
+  strut: SparseGrid<(i32, i32)>
+}
+
+impl Cantilever {
+  /// Builds the platform beneath the columns of a building which the landmass doesn't reach,
+  /// and a strut running from the platform's outer edge down and back to the nearest edge of the landmass,
+  /// at its lower slab at `min_z`. The building overhangs the landmass in the given direction.
+  fn carrying(building: &Building, direction: IVec2, shape: &LandmassShape, min_z: i32) -> Self {
+    let platform = footprint_columns(building)
+      .filter(|&pos| shape.sample(pos).is_none())
+      .map(|pos| (pos, ()))
+      .collect::<SparseGrid<()>>();
+
+    let mut strut: SparseGrid<(i32, i32)> = SparseGrid::new();
+    // The strut hangs from the platform column furthest out, nearest the middle of the building's far side
+    let doubled_middle = building.edge_min + building.edge_max;
+    let outer = platform.cells().map(|(pos, ())| pos).max_by_key(|&pos| {
+      let off_center = (pos * 2 - doubled_middle).dot(direction.perp()).abs();
+      (pos.dot(direction), -off_center, pos.x, pos.y)
+    });
+    // With only one or two blocks of landmass there is nothing beneath the platform to brace it against
+    let start_z = building.level - 2;
+    if let Some(outer) = outer.filter(|_| start_z > min_z) {
+      let reach = IVec2::splat(MAX_OVERHANG + 1);
+      let edge = (outer.x - reach.x..=outer.x + reach.x)
+        .flat_map(|x| (outer.y - reach.y..=outer.y + reach.y).map(move |y| IVec2::new(x, y)))
+        .filter(|&pos| shape.is_edge_at(pos))
+        .min_by_key(|&pos| ((pos - outer).dot(pos - outer), pos.x, pos.y));
+      if let Some(edge) = edge {
+        for pos in stepped_path(outer.extend(start_z), edge.extend(min_z)) {
+          let range = strut.get(pos.xy())
+            .map_or((pos.z, pos.z), |&(bottom, top)| (bottom.min(pos.z), top.max(pos.z)));
+          strut.put(pos.xy(), range);
+        };
+      };
+    };
+
+    Cantilever {
+      building_min: building.edge_min,
+      building_max: building.edge_max,
+      level: building.level,
+      platform,
+      strut
+    }
+  }
+
+  /// Whether this cantilever carries the given building
+  pub fn carries(&self, building: &Building) -> bool {
+    self.building_min == building.edge_min && self.building_max == building.edge_max
+  }
+}
+
+impl Geometry for Cantilever {
+  fn bounding_box(&self) -> BoundingBox {
+    let (min, max) = (self.building_min.extend(self.level - 1), self.building_max.extend(self.level));
+    let platform = BoundingBox::try_new(min, max).unwrap();
+    self.strut.cells().fold(platform, |bounding_box, (pos, &(bottom, top))| {
+      bounding_box.join(BoundingBox::try_new(pos.extend(bottom), pos.extend(top)).unwrap())
+    })
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    ((pos.z == self.level || pos.z == self.level - 1) && self.platform.contains(pos.xy())) ||
+    self.strut.get(pos.xy()).map_or(false, |&(bottom, top)| pos.z >= bottom && pos.z <= top)
+  }
+}
+
+impl Encode for Cantilever {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.building_min);
+    encoder.put(&self.building_max);
+    encoder.put_i32(self.level);
+    encoder.put(&self.platform.cells().map(|(pos, ())| pos).collect::<Vec<IVec2>>());
+    encoder.put(&self.strut.cells().map(|(pos, &range)| (pos, range)).collect::<Vec<(IVec2, (i32, i32))>>());
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Cantilever {
+      building_min: decoder.take()?,
+      building_max: decoder.take()?,
+      level: decoder.take_i32()?,
+      platform: decoder.take::<Vec<IVec2>>()?.into_iter().map(|pos| (pos, ())).collect(),
+      strut: decoder.take::<Vec<(IVec2, (i32, i32))>>()?.into_iter().collect()
+    })
+  }
+}
+
+/// Moves a few of the buildings nearest the edge of the landmass outwards, until they overhang it,
+/// and builds a cantilever beneath each of them. Buildings are only moved where they stay clear of the other buildings
+/// and of anything `is_obstructed` rejects. Their basements are filled in, since they would hang beneath the platform.
+pub(super) fn cantilever_buildings(
+  seed: u64, shape: &LandmassShape, min_z: i32, buildings: &mut [Building], is_obstructed: impl Fn(&Building) -> bool
+) -> Vec<Cantilever> {
+  let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+  let count = rng.gen_range(MIN_CANTILEVERS_PER_LAYER..=MAX_CANTILEVERS_PER_LAYER);
+  let mut order = (0..buildings.len()).collect::<Vec<usize>>();
+  order.shuffle(&mut rng);
+
+  let mut cantilevers = Vec::new();
+  for i in order {
+    if cantilevers.len() == count { break };
+    if !is_near_edge(&buildings[i], shape) { continue };
+    let mut directions = CARDINAL4;
+    directions.shuffle(&mut rng);
+    let moved = directions.into_iter().find_map(|direction| {
+      let building = (1..=MAX_SHIFT)
+        .map(|shift| buildings[i].cantilevered(direction * shift))
+        .map(|building| (overhang(&building, direction, shape), building))
+        .take_while(|(overhang, _)| overhang.is_some())
+        .find(|(overhang, _)| *overhang >= Some(MIN_OVERHANG))
+        .map(|(_, building)| building)?;
+      let collides = buildings.iter().enumerate()
+        .any(|(j, other)| j != i && other.bounding_box().intersects(building.bounding_box()));
+      (!collides && !is_obstructed(&building)).then(|| (building, direction))
+    });
+
+    if let Some((building, direction)) = moved {
+      cantilevers.push(Cantilever::carrying(&building, direction, shape, min_z));
+      buildings[i] = building;
+    };
+  };
+
+  cantilevers
+}
+
+/// Whether a corner of the building is close enough to the edge of the landmass for it to be moved over the edge
+fn is_near_edge(building: &Building, shape: &LandmassShape) -> bool {
+  let (min, max) = (building.edge_min, building.edge_max);
+  [min, IVec2::new(min.x, max.y), IVec2::new(max.x, min.y), max].into_iter()
+    .filter_map(|corner| shape.sample(corner))
+    .any(|cell| cell.edge_distance() <= MAX_SHIFT as usize)
+}
+
+/// How far the building's footprint reaches past the edge of the landmass in the given direction,
+/// or `None` if any column of it is more than `MAX_OVERHANG` blocks from the landmass,
+/// measured back across the footprint
+fn overhang(building: &Building, direction: IVec2, shape: &LandmassShape) -> Option<i32> {
+  let in_footprint = |pos: IVec2| pos.cmpge(building.edge_min).all() && pos.cmple(building.edge_max).all();
+  footprint_columns(building).try_fold(0, |overhang, pos| {
+    let support = (0..=MAX_OVERHANG)
+      .map(|distance| (distance, pos - direction * distance))
+      .take_while(|&(_, column)| in_footprint(column))
+      .find(|&(_, column)| shape.sample(column).is_some())?;
+    Some(overhang.max(support.0))
+  })
+}
+
+fn footprint_columns(building: &Building) -> impl Iterator<Item = IVec2> {
+  let (min, max) = (building.edge_min, building.edge_max);
+  (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+}
+
+/// A path of blocks from `start` to `end`, each sharing a face with the one before it,
+/// stepping along whichever axis is furthest behind its share of the way there
+fn stepped_path(start: IVec3, end: IVec3) -> Vec<IVec3> {
+  let delta = end - start;
+  let (steps, length) = (delta.signum(), delta.abs());
+  let mut taken = IVec3::ZERO;
+  let mut path = vec![start];
+  while taken != length {
+    let axis = (0..3)
+      .filter(|&axis| taken[axis] < length[axis])
+      .map(|axis| (axis, (taken[axis] + 1) as f64 / length[axis] as f64))
+      .min_by(|(_, progress1), (_, progress2)| progress1.total_cmp(progress2))
+      .map(|(axis, _)| axis)
+      .unwrap();
+    taken[axis] += 1;
+    let mut offset = IVec3::ZERO;
+    offset[axis] = steps[axis];
+    path.push(*path.last().unwrap() + offset);
+  };
+
+  path
+}
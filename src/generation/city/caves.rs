@@ -0,0 +1,124 @@
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
+use noise::{NoiseFn, Perlin};
+
+use super::landmass_shape::{LandmassCell, LandmassShape};
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::utility::{quantize, rings_in_box};
+
+
+
+/// How far from the edge of the landmass a cell must be for caves to be carved out of it,
+/// leaving a shell around the rim that is only broken by entrances
+const CAVE_EDGE_DISTANCE: usize = 2;
+/// The horizontal and vertical scales of the noise shaping the caves,
+/// squashed vertically since the interior of a landmass is only a few blocks tall
+const NOISE_SCALE_XY: f64 = 10.0;
+const NOISE_SCALE_Z: f64 = 3.0;
+const NOISE_THRESHOLD: f64 = 0.25;
+/// How many cells away from an edge cell a cave can be and still get an entrance through the rim there
+const ENTRANCE_REACH: i32 = 2;
+/// The smallest distance between two entrances along the rim, so that the rim isn't riddled with them
+const ENTRANCE_SPACING: i32 = 24;
+
+/// Cave-like voids carved out of the interior of a landmass, between its slabs, by 3D noise.
+/// The slabs themselves are never carved, and neither is the rim of the landmass except at the entrances,
+/// which open the caves up to the outside wherever they come close to the edge.
+#[derive(Debug, Clone)]
+pub struct InteriorCaves {
+  seed: u32,
+  noise: Perlin,
+  /// The columns of the rim carved through for entrances, and the lowest and highest z values carved in each
+  entrances: SparseGrid<(i32, i32)>
+}
+
+impl InteriorCaves {
+  /// Carves caves out of a landmass whose lower and upper slabs are at `min_z` and `max_z`
+  pub fn generate_new(seed: u32, shape: &LandmassShape, min_z: i32, max_z: i32) -> Self {
+    let mut caves = InteriorCaves { seed, noise: Perlin::new(seed), entrances: SparseGrid::new() };
+    caves.entrances = caves.generate_entrances(shape, min_z, max_z);
+    caves
+  }
+
+  /// Whether the caves carve out the given position, which must lie between the landmass' slabs
+  pub fn is_carved_at(&self, pos: IVec3, cell: LandmassCell) -> bool {
+    self.entrances.get(pos.xy()).map_or(false, |&(bottom, top)| pos.z >= bottom && pos.z <= top) ||
+    (cell.edge_distance() >= CAVE_EDGE_DISTANCE && self.is_void_at(pos))
+  }
+
+  /// The first entrance along the rim, at the lowest z value carved through it
+  pub fn first_entrance(&self) -> Option<IVec3> {
+    self.entrances.cells()
+      .map(|(pos, &(bottom, _))| pos.extend(bottom))
+      .min_by_key(|pos| (pos.x, pos.y))
+  }
+
+  fn is_void_at(&self, pos: IVec3) -> bool {
+    let point = [pos.x as f64 / NOISE_SCALE_XY, pos.y as f64 / NOISE_SCALE_XY, pos.z as f64 / NOISE_SCALE_Z];
+    quantize(self.noise.get(point)) > NOISE_THRESHOLD
+  }
+
+  /// Finds the edge cells with a cave within `ENTRANCE_REACH` cells of them, and opens up a 2×2 square of columns
+  /// from each of them towards the cave, two blocks tall. Edge cells are visited in order around the rim,
+  /// skipping any too close to an entrance that has already been placed.
+  fn generate_entrances(&self, shape: &LandmassShape, min_z: i32, max_z: i32) -> SparseGrid<(i32, i32)> {
+    let (interior_min, interior_max) = (min_z + 1, max_z - 1);
+    let mut entrances = SparseGrid::new();
+    if interior_min > interior_max { return entrances };
+
+    let mut edge = shape.cells()
+      .filter(|(_, cell)| cell.is_edge())
+      .map(|(pos, cell)| (pos, cell.ordering()))
+      .collect::<Vec<(IVec2, usize)>>();
+    // Ties are broken by position, so that the entrances don't depend on the grid's iteration order
+    edge.sort_unstable_by_key(|&(pos, ordering)| (ordering, pos.x, pos.y));
+
+    let mut placed: Vec<IVec2> = Vec::new();
+    for (pos, _) in edge {
+      if placed.iter().any(|&other| (other - pos).abs().max_element() < ENTRANCE_SPACING) { continue };
+      let cave = rings_in_box(IVec2::splat(-ENTRANCE_REACH), IVec2::splat(ENTRANCE_REACH))
+        // Caves diagonal from the far corner of the opening would only touch it at a corner
+        .filter(|offset| offset.x.abs() + offset.y.abs() < ENTRANCE_REACH * 2)
+        .map(|offset| pos + offset)
+        .filter(|&column| shape.sample(column).map_or(false, |cell| cell.edge_distance() >= CAVE_EDGE_DISTANCE))
+        .find_map(|column| {
+          (interior_min..=interior_max).find(|&z| self.is_void_at(column.extend(z))).map(|z| (column, z))
+        });
+      let (column, z) = match cave {
+        Some(cave) => cave,
+        None => continue
+      };
+
+      let toward = column - pos;
+      let step = IVec2::new(toward.x.signum(), toward.y.signum());
+      let step = IVec2::new(if step.x == 0 { 1 } else { step.x }, if step.y == 0 { 1 } else { step.y });
+      let bottom = z.min(interior_max - 1).max(interior_min);
+      let top = (bottom + 1).min(interior_max);
+      for offset in [IVec2::ZERO, IVec2::new(step.x, 0), IVec2::new(0, step.y), step] {
+        if shape.sample(pos + offset).is_some() {
+          entrances.put(pos + offset, (bottom, top));
+        };
+      };
+
+      placed.push(pos);
+    };
+
+    entrances
+  }
+}
+
+impl Encode for InteriorCaves {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_u32(self.seed);
+    encoder.put(&self.entrances.cells().map(|(pos, &entrance)| (pos, entrance)).collect::<Vec<(IVec2, (i32, i32))>>());
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let seed = decoder.take_u32()?;
+    Some(InteriorCaves {
+      seed,
+      noise: Perlin::new(seed),
+      entrances: decoder.take::<Vec<(IVec2, (i32, i32))>>()?.into_iter().collect()
+    })
+  }
+}
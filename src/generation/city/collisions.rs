@@ -0,0 +1,152 @@
+//! How the features on each layer's landmass are reconciled with the pillars of the layer above, which reach down
+//! through them, and a record of every collision that was found.
+use std::fmt;
+
+use crate::generation::intersection::geometries_overlap;
+use crate::generation::pillar::PillarCluster;
+use crate::generation::Geometry;
+
+
+
+/// What happens to the buildings, props and pools of a layer that the pillars of the layer above pass through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+  /// Each colliding feature is removed
+  #[default]
+  Remove,
+  /// Colliding features are kept, with the pillars passing straight through them
+  Ignore
+}
+
+impl CollisionPolicy {
+  pub fn name(self) -> &'static str {
+    match self {
+      CollisionPolicy::Remove => "remove",
+      CollisionPolicy::Ignore => "ignore"
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollidingFeature {
+  Building,
+  Prop,
+  Pool
+}
+
+impl CollidingFeature {
+  pub fn name(self) -> &'static str {
+    match self {
+      CollidingFeature::Building => "building",
+      CollidingFeature::Prop => "prop",
+      CollidingFeature::Pool => "pool"
+    }
+  }
+}
+
+/// A feature of a layer that a pillar of the layer above passes through, and what was done about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Collision {
+  /// The index of the layer the feature is on, whose pillar is on the layer above it
+  pub layer: usize,
+  pub feature: CollidingFeature,
+  /// The index of the feature among those of its kind on its layer, as they were generated.
+  /// These are never renumbered when features are removed.
+  pub index: usize,
+  /// The index of the first pillar found passing through the feature, among the pillars of the layer above.
+  /// Clustered pillars and the braces between them share the index of their cluster.
+  pub pillar: usize,
+  pub policy: CollisionPolicy
+}
+
+impl fmt::Display for Collision {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let resolution = match self.policy {
+      CollisionPolicy::Remove => "removed",
+      CollisionPolicy::Ignore => "kept"
+    };
+
+    write!(
+      f, "layer {} {} {} collides with pillar {} of layer {}, {}",
+      self.layer, self.feature.name(), self.index, self.pillar, self.layer + 1, resolution
+    )
+  }
+}
+
+/// Every collision resolved between the layers of a city, from the bottom layer up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollisionReport {
+  pub collisions: Vec<Collision>
+}
+
+impl fmt::Display for CollisionReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} collisions between layers", self.collisions.len())?;
+    for collision in self.collisions.iter() {
+      write!(f, "\n  {}", collision)?;
+    };
+
+    Ok(())
+  }
+}
+
+/// Finds the features that any of the given pillars pass through, along with the index of the first such pillar
+pub(super) fn find_collisions<G: Geometry>(
+  features: &[G], pillars: &[&PillarCluster], layer: usize, feature: CollidingFeature, policy: CollisionPolicy
+) -> Vec<Collision> {
+  features.iter().enumerate()
+    .filter_map(|(index, geometry)| {
+      let pillar = pillars.iter().position(|pillar| geometries_overlap(geometry, *pillar))?;
+      Some(Collision { layer, feature, index, pillar, policy })
+    })
+    .collect()
+}
+
+/// Keeps only the features that the given collisions did not remove, by their indices as they were generated
+pub(super) fn retain_uncolliding<T>(features: &mut Vec<T>, collisions: &[Collision], feature: CollidingFeature) {
+  let mut index = 0;
+  features.retain(|_| {
+    index += 1;
+    !collisions.iter().any(|collision| collision.feature == feature && collision.index == index - 1)
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn collision(feature: CollidingFeature, index: usize) -> Collision {
+    Collision { layer: 2, feature, index, pillar: 7, policy: CollisionPolicy::Remove }
+  }
+
+  #[test]
+  fn only_the_collided_features_of_each_kind_are_removed() {
+    let collisions = [
+      collision(CollidingFeature::Building, 1),
+      collision(CollidingFeature::Building, 4),
+      collision(CollidingFeature::Pool, 0)
+    ];
+    let mut buildings = vec!['a', 'b', 'c', 'd', 'e'];
+    retain_uncolliding(&mut buildings, &collisions, CollidingFeature::Building);
+    assert_eq!(buildings, ['a', 'c', 'd']);
+    let mut props = vec!['a', 'b'];
+    retain_uncolliding(&mut props, &collisions, CollidingFeature::Prop);
+    assert_eq!(props, ['a', 'b']);
+  }
+
+  #[test]
+  fn reports_list_every_collision() {
+    let report = CollisionReport {
+      collisions: vec![
+        collision(CollidingFeature::Prop, 3),
+        Collision { policy: CollisionPolicy::Ignore, ..collision(CollidingFeature::Pool, 0) }
+      ]
+    };
+    assert_eq!(report.to_string(), concat!(
+      "2 collisions between layers\n",
+      "  layer 2 prop 3 collides with pillar 7 of layer 3, removed\n",
+      "  layer 2 pool 0 collides with pillar 7 of layer 3, kept"
+    ));
+    assert_eq!(CollisionReport::default().to_string(), "0 collisions between layers");
+  }
+}
@@ -0,0 +1,164 @@
+//! Partial blocks placed around existing geometry to soften its outline.
+//!
+//! - A lip of upper slabs runs around the rim of the landmass, one block outwards from each edge cell
+//!   on each side that has no landmass beyond it.
+//! - Stairs sit on top of the corner posts of each building, facing outwards along the x axis.
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+
+use glam::{IVec2, IVec3};
+
+use super::building::Building;
+use super::landmass_shape::LandmassShape;
+use super::props::Direction;
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+#[derive(Debug, Clone)]
+pub struct Details {
+  blocks: HashMap<IVec3, Block>,
+  bounding_box: BoundingBox
+}
+
+impl Details {
+  /// Decorates a landmass whose upper slab is at `level`, and the buildings on it.
+  /// Nothing is placed at positions where `is_occupied` returns true.
+  pub fn new(
+    shape: &LandmassShape, level: i32, buildings: &[Building], is_occupied: impl Fn(IVec3) -> bool
+  ) -> Self {
+    let mut blocks = HashMap::new();
+    for (pos, cell) in shape.cells().filter(|(_, cell)| cell.is_edge()) {
+      for direction in Direction::ALL {
+        if !cell.has_neighbor(direction.vector()) {
+          blocks.insert((pos + direction.vector()).extend(level), blocks::SMOOTH_STONE_SLAB_TOP);
+        };
+      };
+    };
+
+    for building in buildings {
+      for (corner, direction) in roof_corners(building) {
+        blocks.insert(corner.extend(building.top() + 1), stairs_facing(direction));
+      };
+    };
+
+    blocks.retain(|&pos, _| !is_occupied(pos));
+    let bounding_box = blocks.keys()
+      .map(|&pos| BoundingBox::new(pos, pos))
+      .reduce(BoundingBox::join)
+      .unwrap_or(BoundingBox::new(IVec3::ZERO, IVec3::ZERO));
+    Details { blocks, bounding_box }
+  }
+}
+
+impl Geometry for Details {
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.blocks.contains_key(&pos)
+  }
+}
+
+impl MaterialGeometry for Details {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.blocks.get(&pos).map(Cow::Borrowed)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend(self.blocks.values().cloned());
+  }
+}
+
+/// The four corner posts of a building, each with the direction along the x axis facing away from the building
+fn roof_corners(building: &Building) -> [(IVec2, Direction); 4] {
+  let (min, max) = (building.edge_min, building.edge_max);
+  [
+    (IVec2::new(min.x, min.y), Direction::West),
+    (IVec2::new(max.x, min.y), Direction::East),
+    (IVec2::new(min.x, max.y), Direction::West),
+    (IVec2::new(max.x, max.y), Direction::East)
+  ]
+}
+
+fn stairs_facing(direction: Direction) -> Block {
+  match direction {
+    Direction::North => blocks::STONE_BRICK_STAIRS_NORTH,
+    Direction::East => blocks::STONE_BRICK_STAIRS_EAST,
+    Direction::South => blocks::STONE_BRICK_STAIRS_SOUTH,
+    Direction::West => blocks::STONE_BRICK_STAIRS_WEST
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+  use crate::generation::fixtures::LookupNoise;
+
+  /// A landmass made from a 5 by 5 square around 0,0, whose edge cells are the 20 cells along each side of it
+  fn square_landmass() -> LandmassShape {
+    LandmassShape::from_noise(LookupNoise::new(IVec2::new(-2, -2), vec![vec![1.0; 5]; 5], -1.0), None)
+  }
+
+  #[test]
+  fn slabs_sit_one_step_outwards_from_the_edge_on_each_side() {
+    let shape = square_landmass();
+    let details = Details::new(&shape, 10, &[], |_| false);
+
+    // Five slabs beyond each side, and one in each corner, which is a step outwards from the ends of both sides
+    let mut expected = HashSet::new();
+    for i in -2..=2 {
+      expected.extend([IVec2::new(-4, i), IVec2::new(4, i), IVec2::new(i, -4), IVec2::new(i, 4)]);
+    };
+
+    expected.extend([IVec2::new(-3, -3), IVec2::new(3, -3), IVec2::new(-3, 3), IVec2::new(3, 3)]);
+    let found = details.blocks.keys().map(|pos| pos.truncate()).collect::<HashSet<IVec2>>();
+    assert_eq!(found, expected);
+
+    for (pos, block) in details.blocks.iter() {
+      assert_eq!(pos.z, 10, "the slab at {} isn't level with the surface", pos);
+      assert_eq!(*block, blocks::SMOOTH_STONE_SLAB_TOP, "at {}", pos);
+      assert!(shape.sample(pos.truncate()).is_none(), "the slab at {} is on the landmass", pos);
+      let inwards = Direction::ALL.iter().map(|direction| pos.truncate() - direction.vector());
+      assert!(inwards.filter_map(|pos| shape.sample(pos)).any(|cell| cell.is_edge()), "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn stairs_on_building_corners_face_outwards() {
+    let shape = square_landmass();
+    let building = Building::new(IVec2::new(-1, -1), IVec2::new(1, 2), 10, 6, 0, blocks::WHITE_CONCRETE);
+    let details = Details::new(&shape, 10, std::slice::from_ref(&building), |_| false);
+    let roof = building.top() + 1;
+    let expected = [
+      (IVec2::new(-1, -1), blocks::STONE_BRICK_STAIRS_WEST),
+      (IVec2::new(1, -1), blocks::STONE_BRICK_STAIRS_EAST),
+      (IVec2::new(-1, 2), blocks::STONE_BRICK_STAIRS_WEST),
+      (IVec2::new(1, 2), blocks::STONE_BRICK_STAIRS_EAST)
+    ];
+
+    for (corner, stairs) in expected {
+      assert_eq!(details.block_material_at(corner.extend(roof)).map(Cow::into_owned), Some(stairs), "at {}", corner);
+    };
+
+    assert_eq!(details.blocks.keys().filter(|pos| pos.z == roof).count(), 4);
+    assert_eq!(stairs_facing(Direction::North), blocks::STONE_BRICK_STAIRS_NORTH);
+    assert_eq!(stairs_facing(Direction::South), blocks::STONE_BRICK_STAIRS_SOUTH);
+  }
+
+  #[test]
+  fn nothing_is_placed_where_there_is_already_a_block() {
+    let shape = square_landmass();
+    let occupied = IVec3::new(-4, 0, 10);
+    let details = Details::new(&shape, 10, &[], |pos| pos == occupied);
+    assert!(!details.block_at(occupied));
+    assert!(details.block_at(IVec3::new(4, 0, 10)));
+    assert_eq!(details.blocks.len(), 23);
+    assert!(details.blocks.keys().all(|&pos| details.bounding_box().contains(pos)));
+  }
+}
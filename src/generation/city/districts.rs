@@ -0,0 +1,76 @@
+use glam::IVec2;
+
+use crate::generation::{blocks, Block};
+use crate::utility::{hash2, mix64};
+
+
+
+/// The approximate width of a district in blocks
+const DISTRICT_CELL_SIZE: i32 = 96;
+
+/// The largest `height_multiplier` of any of the `DISTRICT_PROFILES`
+pub const MAX_HEIGHT_MULTIPLIER: f64 = 1.5;
+
+pub static DISTRICT_PROFILES: [DistrictProfile; 4] = [
+  // Residential
+  DistrictProfile { density: 0.9, height_multiplier: 1.0, material: blocks::GRAY_CONCRETE },
+  // Downtown
+  DistrictProfile { density: 1.0, height_multiplier: 1.5, material: blocks::LIGHT_GRAY_CONCRETE },
+  // Industrial
+  DistrictProfile { density: 0.6, height_multiplier: 0.7, material: blocks::STONE_BRICKS },
+  // Outskirts
+  DistrictProfile { density: 0.5, height_multiplier: 0.8, material: blocks::POLISHED_ANDESITE }
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistrictProfile {
+  /// The probability that any given building in this district is kept
+  pub density: f64,
+  /// Multiplies the height of the buildings in this district
+  pub height_multiplier: f64,
+  /// The material that the buildings in this district are made of
+  pub material: Block
+}
+
+/// Partitions a landmass into districts using cellular (worley) noise,
+/// where each district is the area closest to one randomly placed feature point.
+/// Since the feature points are spaced out evenly, larger landmasses end up with more districts.
+#[derive(Debug, Clone)]
+pub struct Districts {
+  seed: u64
+}
+
+impl Districts {
+  pub fn new(seed: u64) -> Self {
+    Districts { seed }
+  }
+
+  /// Returns the id of the district containing the given position
+  pub fn district_id(&self, pos: IVec2) -> u32 {
+    mix64(hash2(self.seed, self.nearest_cell(pos))) as u32
+  }
+
+  pub fn profile(&self, pos: IVec2) -> &'static DistrictProfile {
+    &DISTRICT_PROFILES[self.district_id(pos) as usize % DISTRICT_PROFILES.len()]
+  }
+
+  /// Finds the cell whose feature point is nearest to the given position
+  fn nearest_cell(&self, pos: IVec2) -> IVec2 {
+    let cell = IVec2::new(pos.x.div_euclid(DISTRICT_CELL_SIZE), pos.y.div_euclid(DISTRICT_CELL_SIZE));
+    crate::utility::cardinal8(cell)
+      .chain(std::iter::once(cell))
+      .min_by_key(|&candidate| {
+        let offset = self.feature_point(candidate) - pos;
+        (offset.x as i64 * offset.x as i64 + offset.y as i64 * offset.y as i64, candidate.x, candidate.y)
+      })
+      .expect("unreachable")
+  }
+
+  /// Returns the position of the feature point within the given cell
+  fn feature_point(&self, cell: IVec2) -> IVec2 {
+    let hash = hash2(self.seed, cell);
+    let size = DISTRICT_CELL_SIZE as u64;
+    let jitter = IVec2::new((hash % size) as i32, ((hash >> 32) % size) as i32);
+    cell * DISTRICT_CELL_SIZE + jitter
+  }
+}
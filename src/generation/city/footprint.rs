@@ -0,0 +1,85 @@
+//! Where the city stands over the sea floor, for features of the ocean which respond to the city above them.
+use std::collections::{HashMap, VecDeque};
+
+use glam::{IVec2, IVec3};
+
+use crate::generation::deferred::Deferred;
+use crate::generation::BoundingBox;
+use crate::utility::cardinal4;
+
+
+
+/// How far beyond the edges of the landmasses their footprint reaches, with distances measured in steps along the axes
+pub const FOOTPRINT_FRINGE: u32 = 8;
+
+/// The columns covered by the landmasses of every layer and the footings of the lowest layer's pillars,
+/// in world coordinates. These are only located once a column near the city is sampled.
+/// Cloning one shares the same columns rather than copying them.
+#[derive(Debug, Clone)]
+pub struct CityFootprint {
+  inner: Deferred<FootprintInner>
+}
+
+#[derive(Debug)]
+struct FootprintInner {
+  /// The distance from every column within `FOOTPRINT_FRINGE` of a landmass to the nearest column it covers,
+  /// which is 0 for the columns it covers
+  distances: HashMap<IVec2, u32>,
+  /// The origin of each of the lowest layer's pillars
+  pillars: Vec<IVec2>
+}
+
+impl CityFootprint {
+  /// The footprint of the covered columns and pillar origins given by `locate`, which is only called once a column
+  /// within `FOOTPRINT_FRINGE` of the given bounding box is sampled. Every column it gives must be within the box.
+  pub fn new(
+    bounding_box: BoundingBox, locate: impl Fn() -> (Vec<IVec2>, Vec<IVec2>) + Send + Sync + 'static
+  ) -> Self {
+    let fringe = FOOTPRINT_FRINGE as i32;
+    let bounding_box = bounding_box.inflated(IVec3::new(fringe, fringe, 0));
+    let inner = Deferred::new(bounding_box, move || {
+      let (columns, pillars) = locate();
+      FootprintInner::new(columns, pillars)
+    });
+    CityFootprint { inner }
+  }
+
+  /// The distance from the given column to the nearest column covered by a landmass,
+  /// if it is no more than `FOOTPRINT_FRINGE`
+  pub fn distance(&self, pos: IVec2) -> Option<u32> {
+    self.inner.get_in_column(pos)?.distances.get(&pos).copied()
+  }
+
+  /// The distance from the given column to the origin of the nearest of the lowest layer's pillars, if it has any
+  pub fn nearest_pillar_distance(&self, pos: IVec2) -> Option<f32> {
+    self.inner.get().pillars.iter()
+      .map(|origin| origin.as_vec2().distance(pos.as_vec2()))
+      .min_by(f32::total_cmp)
+  }
+}
+
+impl FootprintInner {
+  fn new(columns: Vec<IVec2>, pillars: Vec<IVec2>) -> Self {
+    // A breadth-first search out from every covered column at once, stopping at the edge of the fringe
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    for pos in columns {
+      if distances.insert(pos, 0).is_none() {
+        queue.push_back(pos);
+      };
+    };
+
+    while let Some(pos) = queue.pop_front() {
+      let distance = distances[&pos];
+      if distance >= FOOTPRINT_FRINGE { continue };
+      for neighbor in cardinal4(pos) {
+        if !distances.contains_key(&neighbor) {
+          distances.insert(neighbor, distance + 1);
+          queue.push_back(neighbor);
+        };
+      };
+    };
+
+    FootprintInner { distances, pillars }
+  }
+}
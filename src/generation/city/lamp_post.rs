@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// Height of the fence post holding the lantern
+const POST_HEIGHT: i32 = 3;
+
+/// A fence post standing on a landmass with a lantern on top of it
+#[derive(Debug, Clone)]
+pub struct LampPost {
+  origin: IVec2,
+  /// The z value of the surface the post stands on
+  level: i32
+}
+
+impl LampPost {
+  pub fn new(origin: IVec2, level: i32) -> Self {
+    LampPost { origin, level }
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<LampPostPart> {
+    if pos.xy() != self.origin { return None };
+    match pos.z - self.level {
+      z if (1..=POST_HEIGHT).contains(&z) => Some(LampPostPart::Post),
+      z if z == POST_HEIGHT + 1 => Some(LampPostPart::Lantern),
+      _ => None
+    }
+  }
+}
+
+impl Geometry for LampPost {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.origin.extend(self.level + 1);
+    let max = self.origin.extend(self.level + POST_HEIGHT + 1);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for LampPost {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      LampPostPart::Post => &blocks::DARK_OAK_FENCE,
+      LampPostPart::Lantern => &blocks::LANTERN
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::DARK_OAK_FENCE, blocks::LANTERN]);
+  }
+}
+
+impl Encode for LampPost {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.origin);
+    encoder.put_i32(self.level);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(LampPost {
+      origin: decoder.take()?,
+      level: decoder.take_i32()?
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LampPostPart {
+  Post,
+  Lantern
+}
@@ -1,49 +1,260 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::num::NonZeroU64;
+use std::ops::RangeInclusive;
 
-use glam::{DVec2, IVec2, Vec2};
+use glam::{DVec2, IVec2};
 use grid::SparseGrid;
 use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
 use rand::Rng;
 
-use crate::utility::{cardinal4, cardinal8};
+use super::plaza::{Plaza, MAX_PLAZA_RADIUS, MIN_PLAZA_RADIUS};
+use super::tile_grid::TileGrid;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::utility::{atan2_turns, cardinal4, cardinal8, quantize, sin_cos_turns, CARDINAL8};
 
 
 
 const MIN_BUILDING_SIZE: u32 = 5;
 const MAX_BUILDING_SIZE: u32 = 9;
-const MIN_BUILDING_HEIGHT: u32 = 2;
-const MAX_BUILDING_HEIGHT: u32 = 8;
+
+const LANDMASS_RESOLUTION: f64 = 128.0;
+
+/// The fewest lobes a landmass with lobes can have, since a single lobe would only push it off-center
+const MIN_LOBES: u32 = 2;
 
 const PILLAR_EDGE_DISTANCE: usize = 12;
 const PILLAR_SPACING: usize = 32;
 
+/// The range of edge distances covered by the road running around the perimeter of the landmass
+const ROAD_EDGE_DISTANCE: RangeInclusive<usize> = 2..=4;
+const LAMP_POST_EDGE_DISTANCE: usize = 3;
+const LAMP_POST_SPACING: usize = 20;
+
+/// Props are placed just inside of the road
+const PROP_EDGE_DISTANCE: usize = 6;
+const PROP_SPACING: usize = 48;
+
+/// Plazas are kept at least this far from the edge of the landmass, past the pillars
+const PLAZA_EDGE_DISTANCE: usize = 16;
+/// The landmass area (in blocks) per plaza
+const PLAZA_AREA: usize = 8192;
+const PLAZA_PLACEMENT_ATTEMPTS: usize = 16;
+
+/// Pools are kept this far from the edge of the landmass, well inside of the road and pillars
+const POOL_EDGE_DISTANCE: usize = 16;
+/// The landmass area (in blocks) per pool
+const POOL_AREA: usize = 8192;
+/// The least distance between the centers of two pools
+const POOL_SPACING: f32 = 32.0;
+const POOL_PLACEMENT_ATTEMPTS: usize = 32;
+
+/// Parameters for wearing away the outline of a landmass after its shape is discovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErosionConfig {
+  /// The number of times cells are removed from the outline
+  pub iterations: u32,
+  /// Cells with fewer than this many of their eight neighbors present are removed in each iteration
+  pub min_neighbors: u32
+}
+
+impl Default for ErosionConfig {
+  fn default() -> Self {
+    ErosionConfig {
+      iterations: 2,
+      min_neighbors: 3
+    }
+  }
+}
+
+/// The limits that the shape of each landmass is drawn within, so that different layers have different silhouettes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShapeConfig {
+  /// How much longer a landmass can be along one axis than along the other, as a percentage
+  pub max_elongation: u32,
+  /// How far the lobes around the outline of a landmass can reach in and out, as a percentage of its size.
+  /// This must be less than 100, or the lobes would pinch the landmass apart.
+  pub max_lobe_depth: u32,
+  /// The most lobes that a landmass can have around its outline
+  pub max_lobes: u32
+}
+
+impl Default for ShapeConfig {
+  fn default() -> Self {
+    ShapeConfig {
+      max_elongation: 100,
+      max_lobe_depth: 30,
+      max_lobes: 5
+    }
+  }
+}
+
+/// Which axes the outline of every landmass is mirrored across, through the middle of the landmass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symmetry {
+  /// The outline is left as it was drawn
+  #[default]
+  None,
+  /// The outline is mirrored across the y axis, so that its two halves match
+  Mirror,
+  /// The outline is mirrored across both axes, so that its four quarters match
+  FourWay
+}
+
+impl Symmetry {
+  pub fn name(self) -> &'static str {
+    match self {
+      Symmetry::None => "none",
+      Symmetry::Mirror => "mirror",
+      Symmetry::FourWay => "four-way"
+    }
+  }
+
+  /// Moves a point into the part of the outline which is drawn, and which the rest mirrors
+  fn fold(self, point: DVec2) -> DVec2 {
+    match self {
+      Symmetry::None => point,
+      Symmetry::Mirror => DVec2::new(point.x.abs(), point.y),
+      Symmetry::FourWay => point.abs()
+    }
+  }
+}
+
+/// The shape of a single landmass: an ellipse, turned to some angle, with lobes around its outline.
+/// Whatever the shape, a landmass covers about the same area as a circular one of the same size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeParams {
+  /// The ratio of the length of the landmass along its major axis to its length along its minor axis
+  pub elongation: f64,
+  /// The angle of the major axis, in turns
+  pub rotation: f64,
+  /// How far the lobes reach in and out, as a fraction of the landmass' size
+  pub lobe_depth: f64,
+  pub lobes: u32
+}
+
+impl ShapeParams {
+  /// The shape of every landmass when shapes aren't varied
+  pub const CIRCULAR: Self = ShapeParams { elongation: 1.0, rotation: 0.0, lobe_depth: 0.0, lobes: 0 };
+
+  pub fn random<R: Rng>(rng: &mut R, config: ShapeConfig) -> Self {
+    let elongation = 1.0 + rng.gen_range(0..=config.max_elongation) as f64 / 100.0;
+    // An ellipse looks the same turned half way around
+    let rotation = rng.gen_range(0.0..0.5);
+    let lobe_depth = rng.gen_range(0..=config.max_lobe_depth) as f64 / 100.0;
+    let lobes = rng.gen_range(MIN_LOBES..=config.max_lobes.max(MIN_LOBES));
+    ShapeParams { elongation, rotation, lobe_depth, lobes }
+  }
+
+  /// The shape reaching the furthest of any that `random` can draw with the given config
+  pub fn widest(config: ShapeConfig) -> Self {
+    ShapeParams {
+      elongation: 1.0 + config.max_elongation as f64 / 100.0,
+      rotation: 0.0,
+      lobe_depth: config.max_lobe_depth as f64 / 100.0,
+      lobes: config.max_lobes.max(MIN_LOBES)
+    }
+  }
+
+  /// Scales the size of a landmass so that the lobes neither add to nor take away from its area.
+  /// A circle with lobes of depth `a` has `1 + a^2 / 2` times the area of the circle.
+  fn lobe_normalization(self) -> f64 {
+    (1.0 + self.lobe_depth * self.lobe_depth / 2.0).sqrt().recip()
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct LandmassShape {
-  grid: SparseGrid<LandmassCell>
+  grid: TileGrid<LandmassCell>
 }
 
 impl LandmassShape {
-  pub fn generate_new(seed: u32, size: f64) -> Self {
-    let grid = generate_landmass_shape(seed, size);
-    LandmassShape { grid }
+  pub fn generate_new(
+    seed: u32, size: f64, shape: ShapeParams, symmetry: Symmetry, erosion: Option<ErosionConfig>
+  ) -> Self {
+    assert!(size >= 1.0, "landmass size may not be less than 1");
+    let noise = landmass_generator(seed, size, shape, LANDMASS_RESOLUTION);
+    LandmassShape::from_noise(Folded { inner: noise, symmetry }, erosion)
+  }
+
+  /// A landmass covering the columns where a noise function is positive, found by flood-filling out from 0,0,
+  /// instead of the noise it would build from a seed. The noise must be positive at 0,0,
+  /// and must not stay positive forever in any direction, or this will never finish.
+  pub fn from_noise(noise: impl NoiseFn<f64, 2>, erosion: Option<ErosionConfig>) -> Self {
+    let grid = discover(noise);
+    let grid = match erosion {
+      Some(erosion) => erode(&grid, erosion),
+      None => grid
+    };
+
+    LandmassShape { grid: classify(grid) }
   }
 
   pub fn generate_pillar_points(&self) -> Vec<IVec2> {
     generate_mount_points(&self.grid, PILLAR_EDGE_DISTANCE, PILLAR_SPACING)
   }
 
-  pub fn generate_building_shapes<R: Rng>(&self, rng: &mut R) -> Vec<BuildingShape> {
-    generate_building_shapes(rng, &self.grid)
+  pub fn generate_lamp_post_points(&self) -> Vec<IVec2> {
+    generate_mount_points(&self.grid, LAMP_POST_EDGE_DISTANCE, LAMP_POST_SPACING)
+  }
+
+  /// Candidate points for props, spaced out along the edge of the landmass
+  pub fn generate_prop_points(&self) -> Vec<IVec2> {
+    generate_mount_points(&self.grid, PROP_EDGE_DISTANCE, PROP_SPACING)
+  }
+
+  /// Picks a number of points in the interior of the landmass, scaling with its area,
+  /// and returns them along with the radius of the plaza at each of them
+  pub fn generate_plaza_sites<R: Rng>(&self, rng: &mut R) -> Vec<(IVec2, u32)> {
+    generate_plaza_sites(rng, &self.grid)
+  }
+
+  /// Picks a number of points in the interior of the landmass, scaling with its area and spaced apart from each other,
+  /// along with whether the pool at each of them runs along y. Only points where `is_clear` is true are picked.
+  pub fn generate_pool_sites<R: Rng>(&self, rng: &mut R, is_clear: impl Fn(IVec2, bool) -> bool) -> Vec<(IVec2, bool)> {
+    generate_pool_sites(rng, &self.grid, is_clear)
+  }
+
+  /// Populates the landmass with buildings, leaving the given plazas empty
+  pub fn generate_building_shapes<R: Rng>(&self, rng: &mut R, plazas: &[Plaza]) -> Vec<BuildingShape> {
+    generate_building_shapes(rng, &self.grid, plazas)
+  }
+
+  /// The cell of the landmass nearest the mean position of all of its cells, which may itself lie outside the landmass.
+  /// Ties are broken towards the lowest x and then y, so the result does not depend on the order of the cells.
+  pub fn centroid(&self) -> IVec2 {
+    let (sum_x, sum_y, count) = self.grid.cells().fold((0i64, 0i64, 0i64), |(sum_x, sum_y, count), (pos, _)| {
+      (sum_x + pos.x as i64, sum_y + pos.y as i64, count + 1)
+    });
+    // Compared as multiples of the count, keeping the distances exact
+    let distance_squared = |pos: IVec2| {
+      let (dx, dy) = (pos.x as i64 * count - sum_x, pos.y as i64 * count - sum_y);
+      dx as i128 * dx as i128 + dy as i128 * dy as i128
+    };
+
+    self.grid.cells()
+      .map(|(pos, _)| pos)
+      .min_by_key(|&pos| (distance_squared(pos), pos.x, pos.y))
+      .expect("unreachable")
   }
 
   #[inline]
   pub fn sample(&self, pos: IVec2) -> Option<LandmassCell> {
-    self.grid.get(pos).copied()
+    self.grid.get(pos)
+  }
+
+  pub fn cells(&self) -> impl Iterator<Item = (IVec2, LandmassCell)> + '_ {
+    self.grid.cells()
   }
 
   #[inline]
   pub fn is_edge_at(&self, pos: IVec2) -> bool {
-    self.grid.get(pos).map_or(false, |cell| cell.edge)
+    self.grid.get(pos).map_or(false, |cell| cell.is_edge())
+  }
+
+  /// An estimate of the memory used by this shape's cells, in bytes
+  pub fn memory_usage(&self) -> usize {
+    self.grid.memory_usage()
   }
 
   #[inline]
@@ -57,45 +268,165 @@ impl LandmassShape {
   }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Encode for LandmassShape {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_u32(self.grid.cells().count() as u32);
+    for (pos, cell) in self.grid.cells() {
+      encoder.put(&pos);
+      encoder.put_u64(cell.bits.get());
+    };
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let len = decoder.take_u32()?;
+    let grid = (0..len)
+      .map(|_| {
+        let pos = decoder.take()?;
+        let bits = NonZeroU64::new(decoder.take_u64()?)?;
+        (bits.get() & PRESENT_BIT != 0).then(|| (pos, LandmassCell { bits }))
+      })
+      .collect::<Option<TileGrid<LandmassCell>>>()?;
+    // Code elsewhere assumes that the grid is never empty
+    if grid.cells().next().is_none() { return None };
+    Some(LandmassShape { grid })
+  }
+}
+
+/// Orderings are only ever compared with each other, but nearby edge cells differ by very little,
+/// so they are kept at full precision to keep distinct cells from sharing an ordering
+const ORDERING_BITS: u32 = 32;
+/// Edge distances are only ever compared against small thresholds (the largest being a plaza's furthest reach
+/// from the edge), so anything further than this can be capped without changing the landmass
+const EDGE_DISTANCE_BITS: u32 = 6;
+/// One bit for each of the cell's eight neighbors, in the order of `CARDINAL8`, set if the neighbor is present
+const NEIGHBORS_SHIFT: u32 = ORDERING_BITS + EDGE_DISTANCE_BITS;
+const EDGE_BIT: u64 = 1 << (NEIGHBORS_SHIFT + 8);
+/// Always set, so that a cell is never zero and `Option<LandmassCell>` takes up no more space than a cell
+const PRESENT_BIT: u64 = 1 << 63;
+
+/// A cell of a landmass, packed into 64 bits since large landmasses have millions of them
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct LandmassCell {
+  bits: NonZeroU64
+}
+
+impl LandmassCell {
+  /// Creates a cell, capping its edge distance at the largest that can be stored.
+  /// The ordering must fit in `ORDERING_BITS`.
+  fn new(ordering: usize, edge_distance: usize, edge: bool, neighbors: u8) -> Self {
+    debug_assert!(ordering < 1 << ORDERING_BITS, "ordering {} does not fit in a landmass cell", ordering);
+    let edge_distance = edge_distance.min((1 << EDGE_DISTANCE_BITS) - 1);
+    let bits = ordering as u64 | (edge_distance as u64) << ORDERING_BITS | (neighbors as u64) << NEIGHBORS_SHIFT |
+      if edge { EDGE_BIT } else { 0 } | PRESENT_BIT;
+    LandmassCell { bits: NonZeroU64::new(bits).expect("unreachable") }
+  }
+
   /// An ordering value relating to the point's position along the edge of the shape.
   /// Nearby landmass cells will have similar values.
-  pub ordering: usize,
-  pub edge_distance: usize,
-  pub edge: bool
+  #[inline]
+  pub fn ordering(self) -> usize {
+    (self.bits.get() & ((1 << ORDERING_BITS) - 1)) as usize
+  }
+
+  #[inline]
+  pub fn edge_distance(self) -> usize {
+    (self.bits.get() >> ORDERING_BITS & ((1 << EDGE_DISTANCE_BITS) - 1)) as usize
+  }
+
+  #[inline]
+  pub fn is_edge(self) -> bool {
+    self.bits.get() & EDGE_BIT != 0
+  }
+
+  /// Which of the cell's eight neighbors are part of the landmass, one bit each in the order of `CARDINAL8`
+  #[inline]
+  pub fn neighbors(self) -> u8 {
+    (self.bits.get() >> NEIGHBORS_SHIFT) as u8
+  }
+
+  /// Whether the neighbor at the given offset, one of `CARDINAL8`, is part of the landmass
+  #[inline]
+  pub fn has_neighbor(self, offset: IVec2) -> bool {
+    let index = CARDINAL8.iter().position(|&neighbor| neighbor == offset).expect("not a neighboring offset");
+    self.neighbors() & 1 << index != 0
+  }
+
+  /// The direction pointing out of the landmass from this cell, or `None` if all of its neighbors are present.
+  /// This is the average of the directions of its missing neighbors, snapped to the nearest of `CARDINAL8`.
+  pub fn outward_direction(self) -> Option<IVec2> {
+    let outward = CARDINAL8.iter().enumerate()
+      .filter(|&(i, _)| self.neighbors() & 1 << i == 0)
+      .fold(IVec2::ZERO, |acc, (_, &offset)| acc + offset);
+    if outward == IVec2::ZERO { return None };
+    let eighths = (atan2_turns(outward.y as f64, outward.x as f64) * 8.0).round() as i32;
+    Some(CARDINAL8[eighths.rem_euclid(8) as usize])
+  }
+
+  /// Whether this is an edge cell at a corner of the landmass, where the edge points out diagonally
+  pub fn is_corner(self) -> bool {
+    self.is_edge() && self.outward_direction().map_or(false, |outward| outward.x != 0 && outward.y != 0)
+  }
+
+  #[inline]
+  pub fn is_road(self) -> bool {
+    ROAD_EDGE_DISTANCE.contains(&self.edge_distance())
+  }
 }
 
-impl LandmassCell {
-  fn new(ordering: usize, edge_distance: usize, edge: bool) -> Self {
-    LandmassCell {
-      ordering,
-      edge_distance,
-      edge
-    }
+impl fmt::Debug for LandmassCell {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LandmassCell")
+      .field("ordering", &self.ordering())
+      .field("edge_distance", &self.edge_distance())
+      .field("edge", &self.is_edge())
+      .field("neighbors", &format_args!("{:08b}", self.neighbors()))
+      .finish()
   }
 }
 
 
 
-const DISTANCE_POWER: i32 = 4;
+const MAX_ORDERING: f64 = ((1u64 << ORDERING_BITS) - 1) as f64;
 
-const MAX_ORDERING: f32 = u32::MAX as f32;
+#[inline]
+fn ordering_from_turns(turns: f64) -> usize {
+  (quantize(turns) * MAX_ORDERING).floor() as usize
+}
+
+/// A cell of a landmass that is still being discovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+  Present,
+  Boundary,
+  BoundaryFinal { index: usize }
+}
+
+#[inline]
+fn boundary_at(grid: &SparseGrid<Value>, pos: IVec2) -> bool {
+  matches!(grid.get(pos), Some(&Value::Boundary))
+}
 
-fn generate_landmass_shape(seed: u32, size: f64) -> SparseGrid<LandmassCell> {
-  assert!(size >= 1.0, "landmass size may not be less than 1");
-  discover(landmass_generator(seed, size, 128.0))
+/// A conservative estimate of the furthest distance from the origin that a landmass of the given size and shape
+/// can reach.
+///
+/// Past `size + 1` (in units of `LANDMASS_RESOLUTION`, before the shape's lobes and elongation are applied),
+/// `ShapeBias` always yields -1, so this relies on the remaining terms of the landmass noise never summing to a
+/// magnitude of 1 or more.
+pub fn max_landmass_radius(size: f64, shape: ShapeParams) -> f64 {
+  let lobe_reach = (1.0 + shape.lobe_depth) * shape.lobe_normalization();
+  shape.elongation.sqrt() * (size * lobe_reach + 1.0) * LANDMASS_RESOLUTION
 }
 
 /// # Explanation
-/// This function does almost all of the heavy lifting related to generating the shape of the landmasses.
+/// Shape generation is split into two steps, `discover` and `classify`, so that
+/// the discovered shape can be modified (e.g. by `erode`) before being classified.
 ///
-/// It is first fed a noise function (code for which can be found below), which it flood-fill searches.
+/// `discover` is fed a noise function (code for which can be found below), which it flood-fill searches.
 /// When it encounters a positive value, it sets it in the grid as 'present' and adds their neighbors to
 /// the search queue while for negative values, it marks them as 'boundary' and does not add their
 /// neighbors to the search queue.
 ///
-/// Next, it loops over every element in the sparse grid, searching for the most distant 'boundary' element
+/// `classify` then loops over every element in the sparse grid, searching for the most distant 'boundary' element
 /// ("distance" meaning manhattan distance from the origin) and collecting all 'boundary' elements into an
 /// array.
 ///
@@ -108,42 +439,96 @@ fn generate_landmass_shape(seed: u32, size: f64) -> SparseGrid<LandmassCell> {
 /// marked 'boundary'. The array of elements from step 2 is then partitioned into two arrays, one with
 /// all 'boundary' elements, and one with all 'final boundary' elements. The 'boundary' elements array
 /// is consumed as the starting queue for a flood-fill that fills in all of the holes in the shape.
-fn discover(noise: impl NoiseFn<f64, 2>) -> SparseGrid<LandmassCell> {
-  use std::f32::consts::{PI, TAU};
+fn discover(noise: impl NoiseFn<f64, 2>) -> SparseGrid<Value> {
+  // Discover the basic shape that the noise function produces
+  let mut q = VecDeque::from([IVec2::ZERO]);
+  let mut grid: SparseGrid<Value> = SparseGrid::new();
+  while let Some(pos) = q.pop_front() {
+    let value = quantize(noise.get(pos.as_dvec2()));
+    if value > 0.0 {
+      grid.put(pos, Value::Present);
+      for candidate in cardinal4(pos) {
+        if !grid.contains(candidate) && !q.contains(&candidate) {
+          q.push_back(candidate);
+        };
+      };
+    } else {
+      grid.put(pos, Value::Boundary);
+    };
+  };
 
-  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-  enum Value {
-    Present,
-    Boundary,
-    BoundaryFinal { index: usize }
-  }
+  grid
+}
 
-  #[inline]
-  fn boundary_at(grid: &SparseGrid<Value>, pos: IVec2) -> bool {
-    matches!(grid.get(pos), Some(&Value::Boundary))
-  }
+/// Repeatedly removes present cells with too few present neighbors from a discovered shape,
+/// then keeps only the connected part of what remains closest to the origin.
+/// The result is marked up with boundaries in the same way as `discover`, ready to be classified.
+fn erode(grid: &SparseGrid<Value>, erosion: ErosionConfig) -> SparseGrid<Value> {
+  let original = grid.cells()
+    .filter(|&(_, value)| *value == Value::Present)
+    .map(|(pos, _)| pos)
+    .collect::<HashSet<IVec2>>();
 
-  // Discover the basic shape that the noise function produces
-  let grid = {
-    let mut q = VecDeque::from([IVec2::ZERO]);
-    let mut grid: SparseGrid<Value> = SparseGrid::new();
-    while let Some(pos) = q.pop_front() {
-      let value = noise.get(pos.as_dvec2());
-      if value > 0.0 {
-        grid.put(pos, Value::Present);
-        for candidate in cardinal4(pos) {
-          if !grid.contains(candidate) && !q.contains(&candidate) {
-            q.push_back(candidate);
-          };
-        };
-      } else {
-        grid.put(pos, Value::Boundary);
+  let mut present = original.clone();
+  for _ in 0..erosion.iterations {
+    present = present.iter().copied()
+      .filter(|&pos| {
+        let neighbors = cardinal8(pos).filter(|neighbor| present.contains(neighbor)).count();
+        neighbors as u32 >= erosion.min_neighbors
+      })
+      .collect();
+  };
+
+  // Erosion can pinch off parts of the shape, only the part closest to the origin is kept
+  let root = present.iter().copied()
+    .min_by_key(|&pos| ((pos.x as i64).pow(2) + (pos.y as i64).pow(2), pos.x, pos.y));
+  let root = match root {
+    Some(root) => root,
+    None => {
+      log::warn!("erosion removed an entire landmass, leaving it uneroded");
+      return grid.clone();
+    }
+  };
+
+  let mut connected = HashSet::from([root]);
+  let mut q = VecDeque::from([root]);
+  while let Some(pos) = q.pop_front() {
+    for candidate in cardinal4(pos) {
+      if present.contains(&candidate) && connected.insert(candidate) {
+        q.push_back(candidate);
       };
     };
+  };
 
-    grid
+  let discarded = present.len() - connected.len();
+  if discarded > 0 {
+    log::debug!("erosion discarded {} disconnected landmass cell(s)", discarded);
   };
 
+  let boundaries = connected.iter()
+    .flat_map(|&pos| cardinal4(pos))
+    .filter(|pos| !connected.contains(pos))
+    .collect::<HashSet<IVec2>>();
+  let mut cells = connected.into_iter().map(|pos| (pos, Value::Present))
+    .chain(boundaries.into_iter().map(|pos| (pos, Value::Boundary)))
+    .collect::<Vec<(IVec2, Value)>>();
+  // Sets have no meaningful order, so the cells are sorted to keep the grid deterministic
+  cells.sort_unstable_by_key(|&(pos, _)| (pos.x, pos.y));
+  cells.into_iter().collect()
+}
+
+/// Finds the outer edge of a discovered shape, fills in its holes,
+/// and works out the ordering and edge distance of each of its cells
+fn classify(grid: SparseGrid<Value>) -> TileGrid<LandmassCell> {
+  classify_with(grid, |turns, edge_distance, edge, neighbors| {
+    LandmassCell::new(ordering_from_turns(turns), edge_distance, edge, neighbors)
+  })
+}
+
+/// Does the work of `classify`, handing each cell's position along the edge in turns, its edge distance,
+/// whether it is an edge cell and its neighbors to `new_cell` to build the cell from
+fn classify_with<T, C>(grid: SparseGrid<Value>, new_cell: impl Fn(f64, usize, bool, u8) -> T) -> C
+where C: FromIterator<(IVec2, T)> {
   // Discover all of the shape's edges and the most distant edge element
   let (all_edges, outer_edge_root) = {
     let mut all_edges: Vec<IVec2> = Vec::new();
@@ -204,42 +589,50 @@ fn discover(noise: impl NoiseFn<f64, 2>) -> SparseGrid<LandmassCell> {
           _ => unreachable!()
         };
 
-        let a = (index as f32 / len as f32) * TAU;
-        (outer_edge, Vec2::new(a.cos(), a.sin()))
+        let (sin, cos) = sin_cos_turns(index as f64 / len as f64);
+        (outer_edge, DVec2::new(cos, sin))
       })
       .collect();
 
     (grid, outer_edges)
   };
 
-  fn get_ordering_and_dist(outer_edges: &[(IVec2, Vec2)], pos: IVec2) -> (usize, usize) {
-    const INIT: (Vec2, Option<f32>) = (Vec2::ZERO, None);
-    let (totaled_vector, dist) = outer_edges.into_iter()
-      .fold(INIT, |(acc_vector, acc_dist), &(outer_edge, vector)| {
-        let dist = outer_edge.as_vec2().distance(pos.as_vec2());
-        let acc_vector = acc_vector + vector * dist.powi(-DISTANCE_POWER);
-        let acc_dist = acc_dist.map_or(dist, |m| m.min(dist));
-        (acc_vector, Some(acc_dist))
+  /// Each outer edge's vector is weighted by the inverse fourth power of its distance from the position
+  fn get_turns_and_dist(outer_edges: &[(IVec2, DVec2)], pos: IVec2) -> (f64, usize) {
+    const INIT: (DVec2, Option<i64>) = (DVec2::ZERO, None);
+    let (totaled_vector, dist_squared) = outer_edges.iter()
+      .fold(INIT, |(acc_vector, acc_dist_squared), &(outer_edge, vector)| {
+        let offset = outer_edge - pos;
+        let dist_squared = (offset.x as i64).pow(2) + (offset.y as i64).pow(2);
+        let weight = ((dist_squared * dist_squared) as f64).recip();
+        let acc_vector = acc_vector + vector * weight;
+        let acc_dist_squared = acc_dist_squared.map_or(dist_squared, |m| m.min(dist_squared));
+        (acc_vector, Some(acc_dist_squared))
       });
-    let a = f32::atan2(-totaled_vector.y, -totaled_vector.x);
-    let ordering = ((a + PI) / TAU * MAX_ORDERING).floor() as usize;
-    (ordering, dist.expect("unreachable").floor() as usize)
+    let turns = atan2_turns(-totaled_vector.y, -totaled_vector.x);
+    let dist = (dist_squared.expect("unreachable") as f64).sqrt().floor() as usize;
+    (turns + 0.5, dist)
   }
 
-  #[inline]
-  fn get_ordering_from_index(index: usize, len: usize) -> usize {
-    (index as f32 / len as f32 * MAX_ORDERING).floor() as usize
-  }
+  // Every cell left in the grid is part of the landmass, so its neighbors can be read straight from it
+  let get_neighbors = |pos: IVec2| {
+    CARDINAL8.iter().enumerate()
+      .filter(|&(_, &offset)| grid.contains(pos + offset))
+      .fold(0u8, |acc, (i, _)| acc | 1 << i)
+  };
 
   grid
     .cells()
     .map(|(pos, value)| {
+      let neighbors = get_neighbors(pos);
       (pos, match *value {
         Value::Present => {
-          let (ordering, distance) = get_ordering_and_dist(&outer_edges, pos);
-          LandmassCell::new(ordering, distance, false)
+          let (turns, distance) = get_turns_and_dist(&outer_edges, pos);
+          new_cell(turns, distance, false, neighbors)
+        },
+        Value::BoundaryFinal { index } => {
+          new_cell(index as f64 / outer_edges.len() as f64, 0, true, neighbors)
         },
-        Value::BoundaryFinal { index } => LandmassCell::new(get_ordering_from_index(index, outer_edges.len()), 0, true),
         Value::Boundary => unreachable!()
       })
     })
@@ -248,14 +641,15 @@ fn discover(noise: impl NoiseFn<f64, 2>) -> SparseGrid<LandmassCell> {
 
 /// Uses data previously generated and stored in each `LandmassCell` to generate a number of
 /// mount points for pillars, offset from the edge of the shape by `distance` and spaced approximately by `spacing`
-fn generate_mount_points(grid: &SparseGrid<LandmassCell>, distance: usize, spacing: usize) -> Vec<IVec2> {
+fn generate_mount_points(grid: &TileGrid<LandmassCell>, distance: usize, spacing: usize) -> Vec<IVec2> {
   let mut points = grid.cells()
-    .filter(|&(_, value)| value.edge_distance == distance)
-    .map(|(pos, value)| (pos, value.ordering))
+    .filter(|&(_, value)| value.edge_distance() == distance)
+    .map(|(pos, value)| (pos, value.ordering()))
     .collect::<Vec<(IVec2, usize)>>();
   let mount_point_count = points.len() / spacing;
   let adjusted_spacing = points.len() as f32 / mount_point_count as f32;
-  points.sort_unstable_by_key(|&(_, ordering)| ordering);
+  // Ties are broken by position, so that the points don't depend on the grid's iteration order
+  points.sort_unstable_by_key(|&(pos, ordering)| (ordering, pos.x, pos.y));
   points.into_iter().enumerate()
     .filter_map(|(i, (pos, _))| {
       let i = (i as f32 % adjusted_spacing).floor() as usize;
@@ -266,38 +660,125 @@ fn generate_mount_points(grid: &SparseGrid<LandmassCell>, distance: usize, spaci
 
 
 
-fn landmass_generator(seed: u32, size: f64, resolution: f64) -> impl NoiseFn<f64, 2> {
+fn landmass_generator(seed: u32, size: f64, shape: ShapeParams, resolution: f64) -> impl NoiseFn<f64, 2> {
   Fbm::<Perlin>::new(seed)
     .set_octaves(8)
     .set_persistence(0.25)
     .multiply_constant(0.5)
     .scale_point_by(2.0)
-    .add(OriginDistance::new(size))
+    .add(ShapeBias::new(size, shape))
     .scale_point_by(resolution.recip())
 }
 
-struct OriginDistance {
-  offset: f64
+/// Mirrors a noise function as given by a symmetry
+struct Folded<N> {
+  inner: N,
+  symmetry: Symmetry
+}
+
+impl<N: NoiseFn<f64, 2>> NoiseFn<f64, 2> for Folded<N> {
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    self.inner.get(self.symmetry.fold(DVec2::from(point.into())).to_array())
+  }
+}
+
+/// Pulls the noise up inside the outline of the landmass' shape and down outside of it
+struct ShapeBias {
+  offset: f64,
+  /// The cosine and sine of the angle which turns the shape's major axis onto the x axis
+  unrotation: DVec2,
+  /// The amount that distances along each axis are scaled by, stretching the shape along its major axis.
+  /// The two multiply to 1, so the area of the shape is unchanged.
+  axis_scale: DVec2,
+  lobe_depth: f64,
+  lobes: f64
 }
 
-impl OriginDistance {
-  pub fn new(offset: f64) -> Self {
-    OriginDistance { offset }
+impl ShapeBias {
+  pub fn new(size: f64, shape: ShapeParams) -> Self {
+    let (sin, cos) = sin_cos_turns(-shape.rotation);
+    let stretch = shape.elongation.sqrt();
+    ShapeBias {
+      offset: size * shape.lobe_normalization(),
+      unrotation: DVec2::new(cos, sin),
+      axis_scale: DVec2::new(stretch.recip(), stretch),
+      lobe_depth: shape.lobe_depth,
+      lobes: shape.lobes as f64
+    }
   }
 }
 
-impl NoiseFn<f64, 2> for OriginDistance {
+impl NoiseFn<f64, 2> for ShapeBias {
   fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
-    let dist = DVec2::from(point.into()).length();
-    (self.offset - dist).clamp(-1.0, 1.0)
+    let point = DVec2::from(point.into());
+    let (sin, cos) = (self.unrotation.y, self.unrotation.x);
+    let point = DVec2::new(point.x * cos - point.y * sin, point.x * sin + point.y * cos) * self.axis_scale;
+    let offset = match self.lobe_depth == 0.0 {
+      true => self.offset,
+      false => {
+        let (_, cos) = sin_cos_turns(atan2_turns(point.y, point.x) * self.lobes);
+        self.offset * (1.0 + self.lobe_depth * cos)
+      }
+    };
+
+    (offset - point.length()).clamp(-1.0, 1.0)
   }
 }
 
 
 
-#[inline]
-pub(super) fn random_building_height<R: Rng>(rng: &mut R) -> u32 {
-  rng.gen_range(MIN_BUILDING_HEIGHT..MAX_BUILDING_HEIGHT)
+fn generate_plaza_sites<R: Rng>(rng: &mut R, grid: &TileGrid<LandmassCell>) -> Vec<(IVec2, u32)> {
+  let mut candidates = grid.cells()
+    .filter(|&(_, cell)| cell.edge_distance() >= PLAZA_EDGE_DISTANCE + MIN_PLAZA_RADIUS as usize)
+    .map(|(pos, cell)| (pos, cell.edge_distance()))
+    .collect::<Vec<(IVec2, usize)>>();
+  if candidates.is_empty() { return Vec::new() };
+  // The grid's iteration order is not meaningful, so the candidates are sorted to keep the choices deterministic
+  candidates.sort_unstable_by_key(|&(pos, _)| (pos.x, pos.y));
+
+  let plaza_count = grid.cells().count() / PLAZA_AREA;
+  let mut sites: Vec<(IVec2, u32)> = Vec::with_capacity(plaza_count);
+  for _ in 0..plaza_count * PLAZA_PLACEMENT_ATTEMPTS {
+    if sites.len() >= plaza_count { break };
+    let (pos, edge_distance) = candidates[rng.gen_range(0..candidates.len())];
+    let max_radius = (edge_distance - PLAZA_EDGE_DISTANCE).min(MAX_PLAZA_RADIUS as usize) as u32;
+    let radius = rng.gen_range(MIN_PLAZA_RADIUS..=max_radius);
+    let overlapping = sites.iter().any(|&(other_pos, other_radius)| {
+      other_pos.as_vec2().distance(pos.as_vec2()) <= (radius + other_radius) as f32
+    });
+
+    if !overlapping {
+      sites.push((pos, radius));
+    };
+  };
+
+  sites
+}
+
+fn generate_pool_sites<R: Rng>(
+  rng: &mut R, grid: &TileGrid<LandmassCell>, is_clear: impl Fn(IVec2, bool) -> bool
+) -> Vec<(IVec2, bool)> {
+  let mut candidates = grid.cells()
+    .filter(|&(_, cell)| cell.edge_distance() >= POOL_EDGE_DISTANCE)
+    .map(|(pos, _)| pos)
+    .collect::<Vec<IVec2>>();
+  if candidates.is_empty() { return Vec::new() };
+  // The grid's iteration order is not meaningful, so the candidates are sorted to keep the choices deterministic
+  candidates.sort_unstable_by_key(|pos| (pos.x, pos.y));
+
+  let pool_count = grid.cells().count() / POOL_AREA;
+  let mut sites: Vec<(IVec2, bool)> = Vec::with_capacity(pool_count);
+  for _ in 0..pool_count * POOL_PLACEMENT_ATTEMPTS {
+    if sites.len() >= pool_count { break };
+    let pos = candidates[rng.gen_range(0..candidates.len())];
+    let along_y = rng.gen_bool(0.5);
+    let crowded = sites.iter().any(|&(other_pos, _)| other_pos.as_vec2().distance(pos.as_vec2()) < POOL_SPACING);
+    if !crowded && is_clear(pos, along_y) {
+      sites.push((pos, along_y));
+    };
+  };
+
+  sites
 }
 
 #[derive(Debug, Clone)]
@@ -306,8 +787,18 @@ pub struct BuildingShape {
   pub(super) edge_max: IVec2
 }
 
-/// Populates the landmass with randomly sized buildings until it is completely full
-fn generate_building_shapes<R: Rng>(rng: &mut R, grid: &SparseGrid<LandmassCell>) -> Vec<BuildingShape> {
+impl BuildingShape {
+  /// The width of the footprint of a building built on this shape along each axis, in blocks
+  pub fn footprint_size(&self) -> IVec2 {
+    // Building shapes are at half resolution, with a cell at each end of the footprint
+    (self.edge_max - self.edge_min) * 2 + IVec2::ONE
+  }
+}
+
+/// Populates the landmass with randomly sized buildings until it is completely full, apart from the plazas
+fn generate_building_shapes<R: Rng>(
+  rng: &mut R, grid: &TileGrid<LandmassCell>, plazas: &[Plaza]
+) -> Vec<BuildingShape> {
   /// Finds the most optimal spot to place the next building if possible
   fn generate_next_building<R: Rng>(rng: &mut R, grid: &SparseGrid<Value>) -> Option<BuildingShape> {
     let size_x = rng.gen_range(MIN_BUILDING_SIZE..MAX_BUILDING_SIZE);
@@ -376,11 +867,16 @@ fn generate_building_shapes<R: Rng>(rng: &mut R, grid: &SparseGrid<LandmassCell>
     Occupied(usize)
   }
 
+  // Each cell covers the blocks within one block of it, which includes any balconies of the buildings placed on it,
+  // so removing every cell that touches a plaza keeps the buildings' bounding boxes clear of the plazas.
+  // Cells close enough to the edge to reach the road are removed for the same reason.
   let mut grid = grid.cells()
+    .filter(|&(_, cell)| cell.edge_distance() > ROAD_EDGE_DISTANCE.end() + 1)
     .filter_map(|(pos, _)| match ivec2_rem_euclid_2(pos) {
       true => Some((pos / 2, Value::Vacant)),
       false => None
     })
+    .filter(|&(pos, _)| !plazas.iter().any(|plaza| plaza.intersects_box(pos * 2 - 1, pos * 2 + 1)))
     .collect::<SparseGrid<Value>>();
   let mut i = 0;
   let mut buildings = Vec::new();
@@ -397,3 +893,260 @@ fn generate_building_shapes<R: Rng>(rng: &mut R, grid: &SparseGrid<LandmassCell>
 fn ivec2_rem_euclid_2(s: IVec2) -> bool {
   s.x.rem_euclid(2) == 0 && s.y.rem_euclid(2) == 0
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::fixtures::{LookupNoise, RadialRamp};
+
+  /// A landmass cell as it was stored before cells were packed, with its ordering spread over the full 32 bits
+  #[derive(Debug, Clone, Copy)]
+  struct UnpackedCell {
+    ordering: usize,
+    edge_distance: usize,
+    edge: bool
+  }
+
+  fn discover_landmass(seed: u32, size: f64) -> SparseGrid<Value> {
+    discover(landmass_generator(seed, size, ShapeParams::CIRCULAR, LANDMASS_RESOLUTION))
+  }
+
+  fn unpacked_cells(grid: SparseGrid<Value>) -> Vec<(IVec2, UnpackedCell)> {
+    classify_with(grid, |turns, edge_distance, edge, _| {
+      let ordering = (quantize(turns) * u32::MAX as f64).floor() as usize;
+      UnpackedCell { ordering, edge_distance, edge }
+    })
+  }
+
+  /// Places mount points the way `generate_mount_points` does, from unpacked cells
+  fn unpacked_mount_points(cells: &[(IVec2, UnpackedCell)], distance: usize, spacing: usize) -> Vec<IVec2> {
+    let mut points = cells.iter()
+      .filter(|(_, cell)| cell.edge_distance == distance)
+      .map(|&(pos, cell)| (pos, cell.ordering))
+      .collect::<Vec<(IVec2, usize)>>();
+    let adjusted_spacing = points.len() as f32 / (points.len() / spacing) as f32;
+    points.sort_unstable_by_key(|&(pos, ordering)| (ordering, pos.x, pos.y));
+    points.into_iter().enumerate()
+      .filter(|&(i, _)| (i as f32 % adjusted_spacing).floor() as usize == 0)
+      .map(|(_, (pos, _))| pos)
+      .collect()
+  }
+
+  #[test]
+  fn packed_cells_match_the_unpacked_representation() {
+    for seed in [0, 5, 17] {
+      let shape = LandmassShape::generate_new(seed, 1.0, ShapeParams::CIRCULAR, Symmetry::None, None);
+      let unpacked = unpacked_cells(discover_landmass(seed, 1.0));
+      assert_eq!(shape.cells().count(), unpacked.len());
+      for &(pos, expected) in unpacked.iter() {
+        let cell = shape.sample(pos).unwrap_or_else(|| panic!("seed {}: no cell at {}", seed, pos));
+        assert_eq!(cell.ordering(), expected.ordering, "seed {}: ordering at {}", seed, pos);
+        assert_eq!(cell.is_edge(), expected.edge, "seed {}: edge at {}", seed, pos);
+        let edge_distance = expected.edge_distance.min((1 << EDGE_DISTANCE_BITS) - 1);
+        assert_eq!(cell.edge_distance(), edge_distance, "seed {}: edge distance at {}", seed, pos);
+      };
+
+      let mount_points = [
+        (shape.generate_pillar_points(), PILLAR_EDGE_DISTANCE, PILLAR_SPACING),
+        (shape.generate_lamp_post_points(), LAMP_POST_EDGE_DISTANCE, LAMP_POST_SPACING),
+        (shape.generate_prop_points(), PROP_EDGE_DISTANCE, PROP_SPACING)
+      ];
+      for (points, distance, spacing) in mount_points {
+        assert_eq!(points, unpacked_mount_points(&unpacked, distance, spacing), "seed {}", seed);
+      };
+    };
+  }
+
+  #[test]
+  fn lamp_posts_are_evenly_spaced_along_the_road() {
+    for seed in 0..4 {
+      let shape = LandmassShape::generate_new(seed, 1.0, ShapeParams::CIRCULAR, Symmetry::None, None);
+      let mut band = shape.cells()
+        .filter(|(_, cell)| cell.edge_distance() == LAMP_POST_EDGE_DISTANCE)
+        .map(|(pos, cell)| (cell.ordering(), pos.x, pos.y))
+        .collect::<Vec<(usize, i32, i32)>>();
+      band.sort_unstable();
+
+      let points = shape.generate_lamp_post_points();
+      assert_eq!(points.len(), band.len() / LAMP_POST_SPACING, "seed {}", seed);
+      let indices = points.iter()
+        .map(|pos| band.iter().position(|&(_, x, y)| IVec2::new(x, y) == *pos).unwrap())
+        .collect::<Vec<usize>>();
+      // Within 20% of the target spacing along the band, counting the gap that wraps around from the last to the first
+      let (min, max) = (LAMP_POST_SPACING * 4 / 5, LAMP_POST_SPACING * 6 / 5);
+      let wrapped = indices[0] + band.len() - indices[indices.len() - 1];
+      for gap in indices.windows(2).map(|pair| pair[1] - pair[0]).chain([wrapped]) {
+        assert!((min..=max).contains(&gap), "seed {}: lamp posts {} cells apart", seed, gap);
+      };
+    };
+  }
+
+  #[test]
+  fn symmetric_outlines_are_mirrored() {
+    let mirrors: [(Symmetry, &[fn(IVec2) -> IVec2]); 2] = [
+      (Symmetry::Mirror, &[|pos| IVec2::new(-pos.x, pos.y)]),
+      (Symmetry::FourWay, &[|pos| IVec2::new(-pos.x, pos.y), |pos| IVec2::new(pos.x, -pos.y), |pos| -pos])
+    ];
+    for (symmetry, reflections) in mirrors {
+      let shape = LandmassShape::generate_new(3, 1.0, ShapeParams::CIRCULAR, symmetry, None);
+      for (pos, _) in shape.cells() {
+        for reflect in reflections {
+          assert!(shape.sample(reflect(pos)).is_some(), "{:?}: {} has no mirror at {}", symmetry, pos, reflect(pos));
+        };
+      };
+    };
+
+    // Without symmetry, the noise leaves the outline lopsided
+    let shape = LandmassShape::generate_new(3, 1.0, ShapeParams::CIRCULAR, Symmetry::None, None);
+    assert!(shape.cells().any(|(pos, _)| shape.sample(IVec2::new(-pos.x, pos.y)).is_none()));
+  }
+
+  #[test]
+  fn cells_round_trip_through_encoding() {
+    let shape = LandmassShape::generate_new(5, 1.0, ShapeParams::CIRCULAR, Symmetry::None, None);
+    let mut encoder = Encoder::new();
+    encoder.put(&shape);
+    let decoded = Decoder::new(&encoder.into_bytes()).take::<LandmassShape>().expect("failed to decode landmass");
+    assert!(shape.cells().eq(decoded.cells()));
+  }
+
+  /// The cells of a landmass where a noise function is positive at exactly the given columns,
+  /// which are ringed by the edge cells just beyond them
+  fn outlined(positive: &HashSet<IVec2>) -> (HashSet<IVec2>, HashSet<IVec2>) {
+    let edges = positive.iter()
+      .flat_map(|&pos| cardinal4(pos))
+      .filter(|pos| !positive.contains(pos))
+      .collect::<HashSet<IVec2>>();
+    (positive.union(&edges).copied().collect(), edges)
+  }
+
+  fn square(min: IVec2, size: i32) -> HashSet<IVec2> {
+    (0..size).flat_map(|y| (0..size).map(move |x| min + IVec2::new(x, y))).collect()
+  }
+
+  fn cells_of(shape: &LandmassShape) -> HashSet<IVec2> {
+    shape.cells().map(|(pos, _)| pos).collect()
+  }
+
+  #[test]
+  fn a_radial_ramp_gives_a_round_landmass() {
+    let radius = 10.5;
+    let shape = LandmassShape::from_noise(RadialRamp { center: DVec2::ZERO, radius }, None);
+    let positive = square(IVec2::splat(-11), 23).into_iter()
+      .filter(|pos| pos.as_dvec2().length() < radius)
+      .collect::<HashSet<IVec2>>();
+    let (cells, edges) = outlined(&positive);
+    assert_eq!(cells_of(&shape), cells);
+    for (pos, cell) in shape.cells() {
+      assert_eq!(cell.is_edge(), edges.contains(&pos), "edge at {}", pos);
+    };
+
+    assert_eq!(shape.centroid(), IVec2::ZERO);
+  }
+
+  #[test]
+  fn holes_in_a_landmass_are_filled_in() {
+    // A 5 by 5 square around 0,0 with a hole at 1,1
+    let mut rows = vec![vec![1.0; 5]; 5];
+    rows[3][3] = -1.0;
+    let shape = LandmassShape::from_noise(LookupNoise::new(IVec2::new(-2, -2), rows, -1.0), None);
+    assert_eq!(cells_of(&shape), outlined(&square(IVec2::new(-2, -2), 5)).0);
+    assert!(!shape.is_edge_at(IVec2::new(1, 1)));
+  }
+
+  #[test]
+  fn erosion_wears_away_thin_spurs() {
+    // A 7 by 7 square around 0,0 with a spur three cells long sticking out of its east side
+    let mut rows = vec![vec![1.0; 7]; 7];
+    rows[3].extend([1.0; 3]);
+    let noise = LookupNoise::new(IVec2::new(-3, -3), rows, -1.0);
+    let body = square(IVec2::new(-3, -3), 7);
+    let spur = [IVec2::new(4, 0), IVec2::new(5, 0), IVec2::new(6, 0)];
+
+    let uneroded = LandmassShape::from_noise(noise.clone(), None);
+    let positive = body.iter().chain(spur.iter()).copied().collect();
+    assert_eq!(cells_of(&uneroded), outlined(&positive).0);
+
+    // The outer two cells of the spur have fewer than 3 neighbors and are worn away,
+    // but the cell against the square has 3 neighbors in it, as do the square's corners
+    let eroded = LandmassShape::from_noise(noise, Some(ErosionConfig::default()));
+    let positive = body.iter().chain(spur[..1].iter()).copied().collect();
+    assert_eq!(cells_of(&eroded), outlined(&positive).0);
+  }
+
+  #[test]
+  fn eroded_shapes_are_connected_subsets_of_the_originals() {
+    let aggressive = ErosionConfig { iterations: 2, min_neighbors: 6 };
+    for seed in 0..4 {
+      let original = LandmassShape::generate_new(seed, 1.0, ShapeParams::CIRCULAR, Symmetry::None, None);
+      let original_cells = cells_of(&original);
+      for erosion in [ErosionConfig::default(), aggressive] {
+        let eroded = LandmassShape::generate_new(seed, 1.0, ShapeParams::CIRCULAR, Symmetry::None, Some(erosion));
+        let cells = cells_of(&eroded);
+        assert!(cells.is_subset(&original_cells), "seed {}: {:?} added cells", seed, erosion);
+        if erosion == aggressive {
+          assert!(cells.len() < original_cells.len(), "seed {}: {:?} removed nothing", seed, erosion);
+        };
+
+        let root = *cells.iter().next().unwrap();
+        let mut connected = HashSet::from([root]);
+        let mut q = VecDeque::from([root]);
+        while let Some(pos) = q.pop_front() {
+          q.extend(cardinal4(pos).filter(|&candidate| cells.contains(&candidate) && connected.insert(candidate)));
+        };
+
+        assert_eq!(connected.len(), cells.len(), "seed {}: {:?} left the shape disconnected", seed, erosion);
+
+        // Only edge cells are exposed, and every edge cell is exposed or runs along the edge to one that is
+        for (pos, cell) in eroded.cells() {
+          let exposed = cardinal8(pos).any(|neighbor| !cells.contains(&neighbor));
+          if cardinal4(pos).any(|neighbor| !cells.contains(&neighbor)) {
+            assert!(cell.is_edge(), "seed {}: {} is exposed but isn't an edge", seed, pos);
+          };
+
+          if cell.is_edge() {
+            assert_eq!(cell.edge_distance(), 0, "seed {}: the edge at {}", seed, pos);
+            let beside_edge = cardinal8(pos).any(|neighbor| eroded.is_edge_at(neighbor));
+            assert!(exposed || beside_edge, "seed {}: the edge at {} is surrounded by the interior", seed, pos);
+          };
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn erosion_keeps_the_part_closest_to_the_origin() {
+    // Two 7 by 7 squares, one around 0,0 and one to the east of it, joined by a bridge one cell wide
+    let rows = (-3..=3)
+      .map(|y| {
+        let bridge = if y == 0 { 1.0 } else { -1.0 };
+        [vec![1.0; 7], vec![bridge; 5], vec![1.0; 7]].concat()
+      })
+      .collect::<Vec<Vec<f64>>>();
+    let noise = LookupNoise::new(IVec2::new(-3, -3), rows, -1.0);
+    let uneroded = LandmassShape::from_noise(noise.clone(), None);
+    assert!(uneroded.sample(IVec2::new(12, 0)).is_some());
+
+    // The middle of the bridge is worn away, and the square to the east is discarded along with the end of the bridge
+    let eroded = LandmassShape::from_noise(noise, Some(ErosionConfig::default()));
+    let positive = square(IVec2::new(-3, -3), 7).into_iter().chain([IVec2::new(4, 0)]).collect();
+    assert_eq!(cells_of(&eroded), outlined(&positive).0);
+  }
+
+  /// Builds the largest landmass that a three layer city has, and checks that its cells take up
+  /// little more than the eight bytes each that a packed cell needs
+  #[test]
+  #[ignore = "builds a landmass with hundreds of thousands of cells"]
+  fn large_landmasses_are_stored_compactly() {
+    assert_eq!(std::mem::size_of::<Option<LandmassCell>>(), 8);
+    let shape = LandmassShape::generate_new(5, 3.0, ShapeParams::CIRCULAR, Symmetry::None, None);
+    let cell_count = shape.cells().count();
+    let memory_usage = shape.memory_usage();
+    assert!(cell_count > 100_000, "only {} cells", cell_count);
+    // Every cell has a slot in one of the tiles, so the estimate can't be less than the packed cells alone
+    assert!(memory_usage >= cell_count * 8, "{} bytes for {} cells", memory_usage, cell_count);
+    assert!(memory_usage < cell_count * 8 * 2, "{} bytes for {} cells", memory_usage, cell_count);
+  }
+}
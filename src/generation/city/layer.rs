@@ -1,63 +1,805 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
 use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 
-use super::building::Building;
+use super::building::{Building, MIN_FOOTPRINT_SIZE};
+use super::cantilever::{cantilever_buildings, Cantilever, MAX_OVERHANG};
+use super::caves::InteriorCaves;
+use super::collisions::{find_collisions, retain_uncolliding, CollidingFeature, Collision, CollisionPolicy};
+use super::details::Details;
+use super::districts::{Districts, MAX_HEIGHT_MULTIPLIER};
 use super::landmass_shape::*;
+use super::lamp_post::LampPost;
+use super::letterbox::MIN_LETTERBOX_EDGE_DISTANCE;
+use super::paving::{Paving, PavingConfig};
+use super::pillar_access::PillarAccess;
+use super::plaza::Plaza;
+use super::point_of_interest::PoiRecord;
+use super::pool::Pool;
+use super::props::{Crane, Direction, Prop, Turbine, MAX_PROP_HEIGHT, MAX_PROP_REACH};
+use super::scatter::{DecorationTable, Scatter, SCATTER_DENSITY};
+use super::shaft::ShaftFit;
+use super::skyline::{HeightDistribution, Skyline};
+use super::slab_fill::{SlabFill, SlabFillChoice};
+use super::spire::{Spire, MAX_SPIRE_HEIGHT};
+use super::underside::{Underside, MAX_CHAIN_LENGTH};
+use super::vegetation::Vegetation;
+use super::wall::Wall;
+use super::SeaFloor;
+use crate::generation::block_entity::BlockEntity;
 use crate::generation::blocks;
-use crate::generation::pillar::Pillar;
+use crate::generation::chunk_context::{ChunkContext, FeaturePriority};
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::feature_tag::{tagged, FeatureTag};
+use crate::generation::material_union::MaterialUnion;
+use crate::generation::pillar::{Pillar, PillarCluster};
 use crate::generation::union::Union;
 use crate::generation::union_threaded::UnionThreaded;
-use crate::generation::{Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::generation::{find_block, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{hash2, hash_to_unit, mix64, rings_in_box};
+use crate::GeneratorOptions;
 
 
 
-pub const LANDMASS_THICKNESS: u32 = 5;
-pub const PILLAR_RADIUS: u32 = 3;
+const MAX_PROPS_PER_LAYER: usize = 2;
+/// The number of edge cells left out of a skirt, making an opening large enough for a boat
+const SKIRT_OPENING_WIDTH: usize = 5;
+/// The number of cells a landmass must have for some of its pillars to be clustered, if clusters are enabled,
+/// which only the largest landmasses have
+const MIN_CLUSTERED_CELLS: usize = 100_000;
+/// How many columns in from the edge of a higher landmass its shadow takes to become solid
+const SHADOW_FADE_WIDTH: i32 = 6;
+/// The distance between the lights set into the underside of a lit landmass along each axis
+const UNDERSIDE_LIGHT_SPACING: i32 = 8;
+/// How far in from the edge of the landmass a column must be to have a light set into its underside
+const MIN_UNDERSIDE_LIGHT_EDGE_DISTANCE: usize = 2;
 
 #[derive(Debug, Clone)]
 pub struct Layer {
-  landmass: Landmass,
-  pillars: Union<Vec<Pillar>>,
+  /// Open regions of the landmass that are kept clear of buildings
+  plazas: Vec<Plaza>,
+  /// The features of this layer worth looking down on from the layer above, which are derived from the others,
+  /// so they are not stored in the layer cache
+  points_of_interest: Vec<PoiRecord>,
+  /// Pools recessed into the landmass' surface, in gaps between the buildings
+  pools: Vec<Pool>,
+  /// Patches of moss and grass on the landmass' surface, clear of every other feature on it
+  vegetation: Vegetation,
+  features: MaterialUnion<LayerFeature>,
+  /// The parts of the landmass' surface beneath higher layers, if shadows have been baked.
+  /// Shadows depend on the other layers of the city, so they are not stored in the layer cache.
+  shadow: Option<Shadow>,
+  lamp_posts: Vec<LampPost>,
+  /// The ways into the largest pillars holding up this layer, which take precedence over everything else in it
+  pillar_accesses: Vec<PillarAccess>,
+  underside: Underside,
   buildings: UnionThreaded<Vec<Building>>,
+  /// Sits on top of the tallest building in the layer
+  spire: Option<Spire>,
+  props: Vec<Prop>,
+  /// Slabs and stairs softening the outline of the layer, if they have been added.
+  /// These are only added once collisions with other layers have been resolved,
+  /// so they are not stored in the layer cache.
+  details: Option<Details>,
+  /// Rubble, barrels and lamps scattered over the open parts of the landmass' surface, if they have been added.
+  /// These are only added once collisions with other layers have been resolved,
+  /// so they are not stored in the layer cache.
+  scatter: Option<Scatter>,
+  /// The collisions between this layer's features and the pillars of the layer above, once they have been resolved.
+  /// These depend on the layer above, so they are not stored in the layer cache.
+  collisions: Vec<Collision>,
+  /// The wall around the landmass, if it has one, which only the lowest layer can.
+  /// Whether there is a wall doesn't change the rest of the layer, so it is not stored in the layer cache.
+  wall: Option<Wall>,
+  /// The z value of the world's build limit, which the spire is kept beneath wherever it is moved to
+  build_limit: i32,
   bounding_box: BoundingBox
 }
 
 impl Layer {
-  pub fn generate_new<R: Rng>(source_rng: &mut R, top: i32, bottom: i32, size: f64) -> Self {
-    //let shape = LandmassShape::generate_new(source_rng.gen(), size);
-    let landmass = Landmass::generate_new(source_rng, top, size);
+  /// Generates a layer of the city, as laid out by `layer_params`.
+  /// If a sea floor is given, the edge of the landmass extends down to it as a skirt in place of the pillars.
+  /// Caves are only carved out of the landmass if `caves` is set and the landmass is thick enough to have a lattice.
+  /// The landmass is circular unless `shapes` is given, in which case its shape is drawn within those limits.
+  /// The interior of the landmass is filled in with the fill chosen by `slab_fill`.
+  /// If `pillar_clusters` is set and the landmass is large enough, every other pillar is replaced by a braced cluster.
+  pub(super) fn generate_new<R: Rng>(source_rng: &mut R, layer_params: &LayerParams, floor: Option<SeaFloor>) -> Self {
+    let LayerParams { index, top, bottom, landmass: params, caves, pillar_clusters, .. } = *layer_params;
+    let mut landmass = Landmass::generate_new(source_rng, layer_params);
+    if log::log_enabled!(log::Level::Debug) {
+      let memory_usage = landmass.shape.memory_usage() as f64 / (1024.0 * 1024.0);
+      let cell_count = landmass.shape.cells().count();
+      log::debug!("the landmass of layer {} has {} cells, taking up about {:.1} MiB", index, cell_count, memory_usage);
+    };
+
+    let skirt = floor.map(|floor| Skirt::new(&landmass, floor));
+    let clustered = pillar_clusters && landmass.shape.cells().count() >= MIN_CLUSTERED_CELLS;
+    let pillars = match skirt {
+      Some(_) => Vec::new(),
+      None => landmass.shape.generate_pillar_points().into_iter().enumerate()
+        .map(|(i, origin)| match clustered && i % 2 == 1 {
+          // Braced up to just beneath the landmass, so that the braces never poke out of its top
+          true => PillarCluster::around(origin, params.pillar_radius, bottom, top, landmass.min_z() - 1),
+          false => PillarCluster::single(Pillar::new_bounded(origin, params.pillar_radius, Some(bottom), Some(top)))
+        })
+        .collect::<Vec<PillarCluster>>()
+    };
+    let mut lamp_posts = landmass.shape.generate_lamp_post_points().into_iter()
+      .map(|origin| LampPost::new(origin, top))
+      .collect::<Vec<LampPost>>();
+    let road = Road::new(&landmass);
 
-    let pillars = landmass.shape.generate_pillar_points().into_iter()
-      .map(|origin| Pillar::new_bounded(origin, PILLAR_RADIUS, Some(bottom), Some(top)))
-      .collect::<Vec<Pillar>>();
+    // Only the lowest layer gets lanterns, lighting the space between the city and the ocean
+    let underside = Underside::generate_new(
+      source_rng.gen(), &landmass.shape, landmass.min_z() - 1, index == 0, &pillars
+    );
 
+    let districts_seed = source_rng.gen();
+    let districts = Districts::new(districts_seed);
     let mut rng = Xoshiro256PlusPlus::from_rng(source_rng).unwrap();
-    let buildings = landmass.shape.generate_building_shapes(&mut rng).into_iter()
-      .map(|building_shape| Building::from_shape(building_shape, top, random_building_height(&mut rng)))
-      .collect::<Vec<Building>>();
+    let plazas = landmass.shape.generate_plaza_sites(&mut rng).into_iter()
+      .map(|(center, radius)| Plaza::new(center, radius, top))
+      .collect::<Vec<Plaza>>();
+    let building_shapes = landmass.shape.generate_building_shapes(&mut rng, &plazas);
+    let (mut buildings, undersized) = generate_buildings(
+      &mut rng, building_shapes, &districts, params.skyline.distribution(), top, params.basement_depth()
+    );
+    if undersized > 0 {
+      log::debug!("skipped {} building shape(s) too narrow to build on in layer {}", undersized, index);
+    };
+
+    // Seeded from the districts' seed rather than drawn, so that the rest of the layer is the same as it was
+    // before buildings were cantilevered. Lamp posts are cleared out of the way of the moved buildings.
+    let cantilever_seed = mix64(districts_seed ^ 0x63616e74);
+    let is_obstructed = |building: &Building| {
+      let bounding_box = building.bounding_box();
+      plazas.iter().any(|plaza| plaza.intersects_box(bounding_box.min.xy(), bounding_box.max.xy())) ||
+      pillars.iter().any(|pillar| do_geometries_intersect(pillar, building))
+    };
+    let cantilevers = cantilever_buildings(
+      cantilever_seed, &landmass.shape, landmass.min_z(), &mut buildings, is_obstructed
+    );
+    lamp_posts.retain(|lamp_post| !buildings.iter().any(|building| do_geometries_intersect(building, lamp_post)));
 
     let buildings_max_y = buildings.iter()
       .map(|building| building.top())
       .max().unwrap_or(top);
     let max = landmass.shape.max().extend(buildings_max_y);
-    let min = landmass.shape.min().extend(bottom);
-    let bounding_box = BoundingBox::new(min, max);
+    let bottom = skirt.as_ref().map_or(bottom, |skirt| skirt.bounding_box().min.z);
+    let min = landmass.shape.min().extend(bottom.min(landmass.min_z() - MAX_CHAIN_LENGTH as i32));
+
+    let spire = Spire::on_tallest(&buildings, layer_params.build_limit);
+    let props = generate_props(&mut rng, &landmass.shape, top, &buildings, &pillars);
+    // Drawn last, so that the rest of the layer is the same as it was before vegetation was added
+    let mut vegetation = Vegetation::generate_new(rng.gen(), &landmass.shape, top, |pos| {
+      landmass.shape.sample(pos).map_or(true, |cell| cell.is_road()) ||
+      plazas.iter().any(|plaza| plaza.intersects_box(pos, pos)) ||
+      pillars.iter().any(|cluster| cluster.contains_column(pos)) ||
+      covers_column(&lamp_posts, pos) || covers_column(&buildings, pos) || covers_column(&props, pos)
+    });
+
+    // Drawn after the vegetation for the same reason, with the vegetation cleared from around the pools afterwards.
+    // The basin beneath the water takes up two blocks of the landmass below its surface.
+    let pools = match params.thickness >= 3 {
+      true => generate_pools(rng.gen(), &landmass.shape, top, &plazas, &pillars, &lamp_posts, &buildings, &props),
+      false => Vec::new()
+    };
+    vegetation.exclude(|pos| covers_column(&pools, pos));
+    // Only where the opening through the landmass is clear of everything hollowed out of it or standing on it
+    let centroid = landmass.shape.centroid();
+    let pillar_accesses = pillars.iter()
+      .flat_map(PillarCluster::pillars)
+      .filter_map(|pillar| PillarAccess::new(pillar, centroid, landmass.min_z(), landmass.max_z()))
+      .filter(|access| {
+        let mut columns = rings_in_box(access.ladder_column() - 1, access.ladder_column() + 1);
+        !columns.any(|pos| {
+          covers_column(&buildings, pos) || covers_column(&pools, pos) ||
+          covers_column(&props, pos) || covers_column(&lamp_posts, pos)
+        })
+      })
+      .collect::<Vec<PillarAccess>>();
+    vegetation.exclude(|pos| pillar_accesses.iter().any(|access| access.ladder_column() == pos));
+    landmass.hollow_out(&buildings, &pools);
+    // Drawn after the pools for the same reason
+    if caves && params.thickness >= 3 {
+      let (min_z, max_z) = (landmass.min_z(), landmass.max_z());
+      landmass.caves = Some(InteriorCaves::generate_new(rng.gen(), &landmass.shape, min_z, max_z));
+    };
+
+    let bounding_box = props.iter().fold(
+      join_bounding_box(BoundingBox::try_new(min, max).unwrap(), spire.as_ref()),
+      |bounding_box, prop| bounding_box.join(prop.bounding_box())
+    ).join(vegetation.bounding_box());
+    // Cantilevered buildings reach past the edge of the landmass
+    let bounding_box = buildings.iter().map(Geometry::bounding_box)
+      .chain(cantilevers.iter().map(Geometry::bounding_box))
+      .fold(bounding_box, BoundingBox::join);
+
+    let mut features = MaterialUnion::default()
+      .push(LayerFeature::Road(road), blocks::POLISHED_DEEPSLATE)
+      .push(LayerFeature::Landmass(landmass), blocks::GRAY_CONCRETE);
+    // A union of nothing has no bounding box, so a layer standing on a skirt has no pillars at all,
+    // and likewise a layer with no cantilevered buildings has no cantilevers
+    if !pillars.is_empty() {
+      features = features.push(LayerFeature::Pillars(Union::new(pillars)), blocks::GRAY_CONCRETE);
+    };
+
+    if !cantilevers.is_empty() {
+      features = features.push(LayerFeature::Cantilevers(Union::new(cantilevers)), blocks::GRAY_CONCRETE);
+    };
+
+    if let Some(skirt) = skirt {
+      features = features.push(LayerFeature::Skirt(skirt), blocks::GRAY_CONCRETE);
+    };
 
     Layer {
-      landmass,
-      pillars: Union::new(pillars),
+      points_of_interest: plazas.iter().map(PoiRecord::plaza).collect(),
+      plazas,
+      pools,
+      vegetation,
+      features,
+      shadow: None,
+      lamp_posts,
+      pillar_accesses,
+      underside,
       buildings: UnionThreaded::new(buildings),
+      spire,
+      props,
+      details: None,
+      scatter: None,
+      collisions: Vec::new(),
+      wall: None,
+      build_limit: layer_params.build_limit,
       bounding_box
     }
   }
 
-  /// Removes all buildings from this layer that collide with the pillars of another layer
-  pub(super) fn remove_buildings_colliding_with(&mut self, above: &Layer) {
-    self.buildings.retain(|building| {
-      !above.pillars.iter().any(|pillar| do_geometries_intersect(building, pillar))
+  /// Cheaply estimates the bounding box of a layer without generating it, including its wall if it has one at the
+  /// given distance. This estimate is conservative, it will always contain the actual bounding box of the generated
+  /// layer.
+  pub(super) fn estimate_bounding_box(layer_params: &LayerParams) -> BoundingBox {
+    let LayerParams { top, bottom, size, landmass: params, shapes, wall_distance, .. } = *layer_params;
+    // The shape isn't drawn until the layer is generated, so the widest shape it could have is assumed
+    let shape = shapes.map_or(ShapeParams::CIRCULAR, ShapeParams::widest);
+    // Cantilevered buildings reach past the edge of the landmass, along with their balconies, and props can too.
+    // The wall is never further from the edge of the landmass than its distance.
+    let reach = MAX_PROP_REACH.max(MAX_OVERHANG + 1).max(wall_distance.map_or(0, |distance| distance as i32));
+    let radius = max_landmass_radius(size, shape).ceil() as i32 + reach;
+    // Mirrors the conversion from shape height to block height in `Building::from_shape`
+    let max_shape_height = params.skyline.distribution().max();
+    let max_building_height = (max_shape_height as f64 * MAX_HEIGHT_MULTIPLIER).round() as u32 * 2 + 1;
+    let min_chain_z = params.min_z(top) - MAX_CHAIN_LENGTH as i32;
+    let min = IVec2::splat(-radius).extend(bottom.min(min_chain_z));
+    let max_height = (max_building_height as i32 + MAX_SPIRE_HEIGHT as i32).max(MAX_PROP_HEIGHT);
+    let max = IVec2::splat(radius).extend(top + max_height);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  /// Adds all of the features in this layer that intersect the given context to its shortlist
+  pub fn shortlist<'a>(&'a self, layer: usize, context: &mut ChunkContext<'a>) {
+    if let Some(material) = self.pillar_material() {
+      for (index, access) in self.pillar_accesses.iter().enumerate() {
+        let priority = FeaturePriority::City { layer, member: 0, index };
+        context.push_boxed(priority, Box::new(access.with_material(material)));
+        context.push_carving(priority, Box::new(access.opening()));
+      };
+    };
+
+    for (index, plaza) in self.plazas.iter().enumerate() {
+      context.push(FeaturePriority::City { layer, member: 1, index }, plaza);
+    };
+
+    for (index, pool) in self.pools.iter().enumerate() {
+      context.push(FeaturePriority::City { layer, member: 2, index }, pool);
+    };
+
+    context.push(FeaturePriority::City { layer, member: 3, index: 0 }, &self.vegetation);
+
+    if let Some(shadow) = &self.shadow {
+      context.push(FeaturePriority::City { layer, member: 4, index: 0 }, shadow);
+    };
+
+    for (member, (feature, material)) in self.features.iter().enumerate() {
+      let member = member + 5;
+      match feature {
+        LayerFeature::Road(road) => {
+          let priority = FeaturePriority::City { layer, member, index: 0 };
+          context.push_materialized(priority, road, material);
+        },
+        LayerFeature::Landmass(landmass) => {
+          let priority = FeaturePriority::City { layer, member, index: 0 };
+          context.push_boxed(priority, Box::new(landmass.with_material(material)));
+        },
+        LayerFeature::Pillars(clusters) => {
+          for (index, cluster) in clusters.iter().enumerate() {
+            let priority = FeaturePriority::City { layer, member, index };
+            context.push_boxed(priority, Box::new(cluster.with_material(material)));
+          };
+        },
+        LayerFeature::Cantilevers(cantilevers) => {
+          for (index, cantilever) in cantilevers.iter().enumerate() {
+            let priority = FeaturePriority::City { layer, member, index };
+            context.push_materialized(priority, cantilever, material);
+          };
+        },
+        LayerFeature::Skirt(skirt) => {
+          let priority = FeaturePriority::City { layer, member, index: 0 };
+          context.push_materialized(priority, skirt, material);
+        }
+      };
+    };
+
+    let member = self.features.len() + 5;
+    for (index, lamp_post) in self.lamp_posts.iter().enumerate() {
+      context.push(FeaturePriority::City { layer, member, index }, lamp_post);
+    };
+
+    let member = member + 1;
+    context.push(FeaturePriority::City { layer, member, index: 0 }, &self.underside);
+
+    let member = member + 1;
+    for (index, building) in self.buildings.iter().enumerate() {
+      context.push(FeaturePriority::City { layer, member, index }, building);
+    };
+
+    if let Some(spire) = &self.spire {
+      context.push(FeaturePriority::City { layer, member: member + 1, index: 0 }, spire);
+    };
+
+    for (index, prop) in self.props.iter().enumerate() {
+      context.push(FeaturePriority::City { layer, member: member + 2, index }, prop);
+    };
+
+    if let Some(details) = &self.details {
+      context.push(FeaturePriority::City { layer, member: member + 3, index: 0 }, details);
+    };
+
+    if let Some(scatter) = &self.scatter {
+      context.push(FeaturePriority::City { layer, member: member + 4, index: 0 }, scatter);
+    };
+
+    if let Some(wall) = &self.wall {
+      context.push(FeaturePriority::City { layer, member: member + 5, index: 0 }, wall);
+    };
+  }
+
+  /// The block entities of every building in this layer that intersects the given bounding box
+  pub fn block_entities_in(&self, bounding_box: BoundingBox) -> impl Iterator<Item = BlockEntity> + '_ {
+    self.buildings.iter()
+      .filter(move |building| building.bounding_box().intersects(bounding_box))
+      .flat_map(Building::block_entities)
+      .filter(move |block_entity| bounding_box.contains(block_entity.pos))
+  }
+
+  pub fn plazas(&self) -> &[Plaza] {
+    &self.plazas
+  }
+
+  /// Every pillar holding up this layer, including each of the pillars of a cluster
+  pub fn pillars(&self) -> impl Iterator<Item = &Pillar> {
+    self.pillar_clusters().flat_map(PillarCluster::pillars)
+  }
+
+  /// The pillars holding up this layer in their clusters, along with the braces between them.
+  /// A pillar standing on its own is a cluster of one.
+  pub fn pillar_clusters(&self) -> impl Iterator<Item = &PillarCluster> {
+    self.features.iter()
+      .filter_map(|(feature, _)| match feature {
+        LayerFeature::Pillars(clusters) => Some(clusters.iter()),
+        _ => None
+      })
+      .flatten()
+  }
+
+  /// The material of the pillars holding up this layer, which also lines the openings above their ways in
+  fn pillar_material(&self) -> Option<&Block> {
+    self.features.iter().find_map(|(feature, material)| matches!(feature, LayerFeature::Pillars(_)).then(|| material))
+  }
+
+  pub fn cantilevers(&self) -> impl Iterator<Item = &Cantilever> {
+    self.features.iter()
+      .filter_map(|(feature, _)| match feature {
+        LayerFeature::Cantilevers(cantilevers) => Some(cantilevers.iter()),
+        _ => None
+      })
+      .flatten()
+  }
+
+  /// The z value of the surface of this layer's landmass
+  pub fn level(&self) -> Option<i32> {
+    self.landmass().map(|landmass| landmass.level)
+  }
+
+  /// The z value of the lower slab of this layer's landmass
+  pub fn lower_slab_z(&self) -> Option<i32> {
+    self.landmass().map(Landmass::min_z)
+  }
+
+  /// The size of this layer's landmass along each axis
+  pub fn landmass_extent(&self) -> Option<IVec2> {
+    self.landmass().map(|landmass| landmass.shape.max() - landmass.shape.min())
+  }
+
+  /// The column of this layer's landmass nearest its centroid
+  pub fn landmass_centroid(&self) -> Option<IVec2> {
+    self.landmass().map(|landmass| landmass.shape.centroid())
+  }
+
+  pub fn points_of_interest(&self) -> &[PoiRecord] {
+    &self.points_of_interest
+  }
+
+  /// Every column of this layer's landmass, in no particular order
+  pub fn landmass_columns(&self) -> impl Iterator<Item = IVec2> + '_ {
+    self.landmass().into_iter().flat_map(|landmass| landmass.shape.cells().map(|(pos, _)| pos))
+  }
+
+  /// A representative position of each kind of feature in this layer, labelled with the kind of feature.
+  /// The first of each kind of feature is used, so the positions are the same every time the layer is generated.
+  pub fn feature_probes(&self) -> Vec<(&'static str, IVec3)> {
+    let mut probes = Vec::new();
+    if let Some(landmass) = self.landmass() {
+      let mut cells = landmass.shape.cells().collect::<Vec<(IVec2, LandmassCell)>>();
+      cells.sort_unstable_by_key(|&(pos, _)| (pos.x, pos.y));
+      let find_cell = |is_wanted: &dyn Fn(IVec2, LandmassCell) -> bool| {
+        cells.iter().find(|&&(pos, cell)| is_wanted(pos, cell)).map(|&(pos, _)| pos)
+      };
+
+      let is_open = |pos: IVec2, cell: LandmassCell| {
+        !cell.is_edge() && !cell.is_road() && !landmass.is_hollow_at(pos.extend(landmass.max_z()))
+      };
+      let max_z = landmass.max_z();
+      probes.extend(find_cell(&is_open).map(|pos| ("landmass slab top", pos.extend(max_z))));
+      probes.extend(find_cell(&is_open).map(|pos| ("landmass slab bottom", pos.extend(landmass.min_z()))));
+      probes.extend({
+        find_cell(&|pos, cell| is_open(pos, cell) && landmass.fill.present(pos.extend(max_z - 1), &landmass.shape))
+          .map(|pos| ("landmass lattice", pos.extend(max_z - 1)))
+      });
+      probes.extend(find_cell(&|_, cell| cell.is_edge()).map(|pos| ("landmass edge", pos.extend(max_z - 1))));
+      probes.extend(find_cell(&|_, cell| cell.is_road() && !cell.is_edge()).map(|pos| ("road", pos.extend(max_z))));
+      // Just beneath the landmass, where nothing else overlaps the pillar
+      probes.extend(self.pillars().next().map(|pillar| {
+        let BoundingBox { min, max } = pillar.bounding_box();
+        ("pillar", ((min.xy() + max.xy()) / 2).extend(landmass.min_z() - 1))
+      }));
+      probes.extend(landmass.caves.as_ref().and_then(InteriorCaves::first_entrance).map(|pos| ("cave entrance", pos)));
+    };
+
+    const MAX_COLUMNS: usize = 1 << 18;
+    if let Some(building) = self.buildings.first() {
+      probes.extend(building.probes());
+    };
+
+    let geometries: [(&'static str, Option<&dyn Geometry>); 11] = [
+      ("plaza", self.plazas.first().map(|plaza| plaza as &dyn Geometry)),
+      ("pool", self.pools.first().map(|pool| pool as &dyn Geometry)),
+      ("vegetation", Some(&self.vegetation)),
+      ("lamp post", self.lamp_posts.first().map(|lamp_post| lamp_post as &dyn Geometry)),
+      ("pillar access", self.pillar_accesses.first().map(|access| access as &dyn Geometry)),
+      ("prop", self.props.first().map(|prop| prop as &dyn Geometry)),
+      ("spire", self.spire.as_ref().map(|spire| spire as &dyn Geometry)),
+      ("underside", Some(&self.underside)),
+      ("shadow", self.shadow.as_ref().map(|shadow| shadow as &dyn Geometry)),
+      ("cantilever", self.cantilevers().next().map(|cantilever| cantilever as &dyn Geometry)),
+      ("wall", self.wall.as_ref().map(|wall| wall as &dyn Geometry))
+    ];
+    for (label, geometry) in geometries {
+      probes.extend(geometry.and_then(|geometry| find_block(&geometry, MAX_COLUMNS)).map(|pos| (label, pos)));
+    };
+
+    probes
+  }
+
+  /// How a shaft taking up the given range of columns would sit in this layer.
+  /// Plazas and vegetation are not in the way, since the shaft only replaces the surface beneath it.
+  pub fn shaft_fit(&self, min: IVec2, max: IVec2) -> ShaftFit {
+    let overlaps = |bounding_box: BoundingBox| {
+      min.cmple(bounding_box.max.xy()).all() && max.cmpge(bounding_box.min.xy()).all()
+    };
+
+    let is_obstructed = self.buildings.iter().any(|building| overlaps(building.bounding_box())) ||
+      self.lamp_posts.iter().any(|lamp_post| overlaps(lamp_post.bounding_box())) ||
+      self.props.iter().any(|prop| overlaps(prop.bounding_box())) ||
+      self.pools.iter().any(|pool| overlaps(pool.bounding_box())) ||
+      self.pillar_clusters().any(|cluster| overlaps(cluster.bounding_box())) ||
+      self.cantilevers().any(|cantilever| overlaps(cantilever.bounding_box())) ||
+      self.spire.as_ref().map_or(false, |spire| overlaps(spire.bounding_box()));
+    if is_obstructed { return ShaftFit::Blocked };
+
+    let landmass = match self.landmass() {
+      Some(landmass) => landmass,
+      None => return ShaftFit::Outside
+    };
+
+    let columns = (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y)));
+    let covered = columns.filter(|&pos| landmass.shape.sample(pos).is_some()).count();
+    let area = ((max.x - min.x + 1) * (max.y - min.y + 1)) as usize;
+    match covered {
+      0 => ShaftFit::Outside,
+      covered if covered == area => ShaftFit::Inside,
+      _ => ShaftFit::Blocked
+    }
+  }
+
+  /// Whether a letterbox taking up the given range of columns, including its railing, can be cut through this layer's
+  /// landmass. The columns must fit a shaft, be clear of plazas, and be well in from the edge of the landmass.
+  pub fn letterbox_fit(&self, min: IVec2, max: IVec2) -> bool {
+    let landmass = match self.landmass() {
+      Some(landmass) => landmass,
+      None => return false
+    };
+
+    let is_deep = (min.x..=max.x)
+      .flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+      .all(|pos| landmass.shape.sample(pos).map_or(false, |cell| cell.edge_distance() >= MIN_LETTERBOX_EDGE_DISTANCE));
+    is_deep && self.shaft_fit(min, max) == ShaftFit::Inside &&
+      !self.plazas.iter().any(|plaza| plaza.intersects_box(min, max))
+  }
+
+  fn landmass(&self) -> Option<&Landmass> {
+    self.features.iter().find_map(|(feature, _)| match feature {
+      LayerFeature::Landmass(landmass) => Some(landmass),
+      _ => None
     })
   }
+
+  /// Darkens the surface of this layer's landmass wherever it lies beneath the landmass of a higher layer
+  pub(super) fn bake_shadows<'a>(&mut self, above: impl IntoIterator<Item = &'a Layer>) {
+    let covers = above.into_iter()
+      .filter_map(|layer| layer.landmass())
+      .map(|landmass| &landmass.shape)
+      .collect::<Vec<&LandmassShape>>();
+    self.shadow = self.landmass().map(|landmass| Shadow::new(landmass, &covers));
+  }
+
+  /// Adds slabs around the rim of the landmass and stairs on the roofs of buildings, wherever there is space for them
+  pub(super) fn add_details(&mut self) {
+    let details = match self.landmass() {
+      Some(landmass) => Details::new(&landmass.shape, landmass.max_z(), &self.buildings, |pos| self.block_at(pos)),
+      None => return
+    };
+
+    self.bounding_box = self.bounding_box.join(details.bounding_box());
+    self.details = Some(details);
+  }
+
+  /// Scatters decorations over the surface of the landmass, away from the road, plazas and pools,
+  /// anything standing on the surface, and the pillars of the layer above, if there is one
+  pub(super) fn scatter_decorations(&mut self, seed: u64, above: Option<&Layer>) {
+    let landmass = match self.landmass() {
+      Some(landmass) => landmass,
+      None => return
+    };
+
+    let level = landmass.max_z();
+    let columns = landmass.shape.cells()
+      .filter(|(_, cell)| !cell.is_road())
+      .map(|(pos, _)| pos)
+      .collect::<Vec<IVec2>>();
+    let scatter = Scatter::new(seed, level, columns, SCATTER_DENSITY, DecorationTable::default(), |pos| {
+      // Decorations stand on solid ground, and are never more than two blocks tall
+      !self.block_at(pos.extend(level)) || (1..=2).any(|height| self.block_at(pos.extend(level + height))) ||
+      self.plazas.iter().any(|plaza| plaza.intersects_box(pos, pos)) || covers_column(&self.pools, pos) ||
+      self.pillar_accesses.iter().any(|access| access.ladder_column() == pos) ||
+      above.map_or(false, |above| above.pillar_clusters().any(|cluster| cluster.contains_column(pos)))
+    });
+
+    self.bounding_box = self.bounding_box.join(scatter.bounding_box());
+    self.scatter = Some(scatter);
+  }
+
+  /// Paves the surface of the landmass with the given mixes, away from its edge
+  pub(super) fn pave(&mut self, seed: u64, config: PavingConfig) {
+    for (feature, _) in self.features.iter_mut() {
+      if let LayerFeature::Landmass(landmass) = feature {
+        landmass.paving = Some(Paving::new(seed, config));
+      };
+    };
+  }
+
+  /// Surrounds the landmass with a wall `distance` blocks out from its edge, reaching down as far as the layer does
+  pub(super) fn build_wall(&mut self, distance: u32) {
+    let wall = match self.landmass() {
+      Some(landmass) => Wall::new(&landmass.shape, landmass.max_z(), self.bounding_box.min.z, distance),
+      None => return
+    };
+
+    self.bounding_box = self.bounding_box.join(wall.bounding_box());
+    self.wall = Some(wall);
+  }
+
+  /// Streaks the walls of this layer's buildings beneath their windows, and stains the tops of its pillars
+  /// where they meet the underside of the landmass, leaving the braces between clustered pillars as they are
+  pub(super) fn weather(&mut self) {
+    let underside = match self.landmass() {
+      Some(landmass) => landmass.min_z(),
+      None => return
+    };
+
+    for building in self.buildings.iter_mut() {
+      building.weather();
+    };
+
+    for (feature, _) in self.features.iter_mut() {
+      if let LayerFeature::Pillars(pillars) = feature {
+        pillars.iter_mut().for_each(|cluster| cluster.weather(underside));
+      };
+    };
+  }
+
+  /// Shades the concrete of this layer by how high up in the city it is, taking the shade at the given index into
+  /// `blocks::ALTITUDE_SHADES` for the landmass and the structures holding it up, and the next shade up for the
+  /// concrete buildings so that they stand out against it. Buildings of other materials are left as they are.
+  pub(super) fn shade_by_altitude(&mut self, shade: usize) {
+    for (_, material) in self.features.iter_mut() {
+      if *material == blocks::GRAY_CONCRETE {
+        *material = blocks::ALTITUDE_SHADES[shade].clone();
+      };
+    };
+
+    for building in self.buildings.iter_mut() {
+      if building.material == blocks::GRAY_CONCRETE || building.material == blocks::LIGHT_GRAY_CONCRETE {
+        building.material = blocks::ALTITUDE_SHADES[shade + 1].clone();
+      };
+    };
+  }
+
+  /// Places a light in the ceiling of each storey of this layer's buildings,
+  /// and sets a grid of lights into the underside of the landmass
+  pub(super) fn light(&mut self) {
+    for building in self.buildings.iter_mut() {
+      building.light();
+    };
+
+    for (feature, _) in self.features.iter_mut() {
+      if let LayerFeature::Landmass(landmass) = feature {
+        landmass.lit = true;
+      };
+    };
+  }
+
+  /// Finds the buildings, props and pools of this layer, at the given index, that share blocks with the pillars of the
+  /// layer above it, recording each collision and resolving it according to `policy`. The spire is moved if the
+  /// building it was on was removed, and vegetation is cleared from beneath those pillars whatever the policy.
+  pub(super) fn resolve_collisions_with(&mut self, index: usize, above: &Layer, policy: CollisionPolicy) {
+    let pillars = above.pillar_clusters().collect::<Vec<&PillarCluster>>();
+    self.collisions = self.find_collisions_with(index, &pillars, policy);
+    if policy == CollisionPolicy::Remove && !self.collisions.is_empty() {
+      self.remove_collided();
+    };
+
+    self.vegetation.exclude(|pos| pillars.iter().any(|cluster| cluster.contains_column(pos)));
+
+    self.spire = Spire::on_tallest(&self.buildings, self.build_limit);
+    self.bounding_box = join_bounding_box(self.bounding_box, self.spire.as_ref());
+  }
+
+  /// Finds the buildings, props and pools of this layer, at the given index, that share blocks with any of the given
+  /// pillars, by their indices among the features currently on this layer
+  pub(super) fn find_collisions_with(
+    &self, index: usize, pillars: &[&PillarCluster], policy: CollisionPolicy
+  ) -> Vec<Collision> {
+    [
+      find_collisions(&self.buildings, pillars, index, CollidingFeature::Building, policy),
+      find_collisions(&self.props, pillars, index, CollidingFeature::Prop, policy),
+      find_collisions(&self.pools, pillars, index, CollidingFeature::Pool, policy)
+    ].concat()
+  }
+
+  /// The number of buildings, props and pools on this layer
+  #[cfg(test)]
+  pub(super) fn feature_counts(&self) -> [usize; 3] {
+    [self.buildings.len(), self.props.len(), self.pools.len()]
+  }
+
+  /// Removes the features named by this layer's collisions, along with the cantilevers carrying removed buildings
+  fn remove_collided(&mut self) {
+    retain_uncolliding(&mut self.buildings, &self.collisions, CollidingFeature::Building);
+    retain_uncolliding(&mut self.props, &self.collisions, CollidingFeature::Prop);
+    retain_uncolliding(&mut self.pools, &self.collisions, CollidingFeature::Pool);
+    self.fill_in_removed();
+  }
+
+  /// Keeps only the buildings for which `keep` is true, along with the cantilevers carrying them, for editing a layer
+  /// through `City::edit_layers` or `GenerationHooks::after_layers`.
+  /// The spire is moved onto the tallest building left, if there is one.
+  pub fn retain_buildings(&mut self, keep: impl FnMut(&Building) -> bool) {
+    self.buildings.retain(keep);
+    self.fill_in_removed();
+    self.spire = Spire::on_tallest(&self.buildings, self.build_limit);
+  }
+
+  /// Fills the basements of removed buildings and the recesses of removed pools back in,
+  /// and takes away the platforms beneath removed buildings
+  fn fill_in_removed(&mut self) {
+    for (feature, _) in self.features.iter_mut() {
+      match feature {
+        LayerFeature::Landmass(landmass) => landmass.hollow_out(&self.buildings, &self.pools),
+        LayerFeature::Cantilevers(cantilevers) => {
+          cantilevers.retain(|cantilever| self.buildings.iter().any(|building| cantilever.carries(building)));
+        },
+        _ => ()
+      };
+    };
+
+    // A union of no cantilevers has no bounding box, so it is taken away altogether
+    self.features.retain(|(feature, _)| {
+      !matches!(feature, LayerFeature::Cantilevers(cantilevers) if cantilevers.is_empty())
+    });
+  }
+
+  /// The collisions with the pillars of the layer above that were resolved in this layer
+  pub fn collisions(&self) -> &[Collision] {
+    &self.collisions
+  }
+
+  /// The highest z value reached by any member of this layer whose bounding box covers the given column,
+  /// or `None` if none of them do
+  pub fn column_top(&self, xy: IVec2) -> Option<i32> {
+    let bounding_boxes = self.plazas.iter().map(Plaza::bounding_box)
+      .chain(self.pools.iter().map(Pool::bounding_box))
+      .chain([self.vegetation.bounding_box(), self.underside.bounding_box()])
+      .chain(self.shadow.as_ref().map(Shadow::bounding_box))
+      .chain(self.features.iter().map(|(feature, _)| feature.bounding_box()))
+      .chain(self.lamp_posts.iter().map(LampPost::bounding_box))
+      .chain(self.buildings.iter().map(Building::bounding_box))
+      .chain(self.spire.as_ref().map(Spire::bounding_box))
+      .chain(self.props.iter().map(Prop::bounding_box))
+      .chain(self.details.as_ref().map(Details::bounding_box))
+      .chain(self.scatter.as_ref().map(Scatter::bounding_box))
+      .chain(self.wall.as_ref().map(Wall::bounding_box));
+    bounding_boxes
+      .filter(|bounding_box| bounding_box.contains_column(xy))
+      .map(|bounding_box| bounding_box.max.z)
+      .max()
+  }
+
+  /// The block at a position along with the kind of feature that placed it
+  pub fn block_material_at_traced(&self, pos: IVec3) -> Option<(Cow<'_, Block>, FeatureTag)> {
+    if let Some(material) = self.pillar_material() {
+      for access in self.pillar_accesses.iter() {
+        ret_if_some!(access.block_material_at(pos, material).map(tagged(FeatureTag::Pillar)));
+        if access.opening().block_at(pos) { return None };
+      };
+    };
+
+    ret_if_some!(self.plazas.iter().find_map(|plaza| plaza.block_material_at(pos)).map(tagged(FeatureTag::Decoration)));
+    ret_if_some!(self.pools.iter().find_map(|pool| pool.block_material_at(pos)).map(tagged(FeatureTag::Decoration)));
+    ret_if_some!(self.vegetation.block_material_at(pos).map(tagged(FeatureTag::Decoration)));
+    ret_if_some!({
+      self.shadow.as_ref()
+        .and_then(|shadow| shadow.block_material_at(pos))
+        .map(tagged(FeatureTag::LandmassSlab))
+    });
+    ret_if_some!(self.features.iter().find_map(|(feature, material)| {
+      feature.block_material_at(pos, material).map(tagged(feature.tag()))
+    }));
+    ret_if_some!({
+      self.lamp_posts.iter()
+        .find_map(|lamp_post| lamp_post.block_material_at(pos))
+        .map(tagged(FeatureTag::Decoration))
+    });
+    ret_if_some!(self.underside.block_material_at(pos).map(tagged(FeatureTag::Decoration)));
+    ret_if_some!(self.buildings.block_material_at(pos).map(tagged(FeatureTag::Building)));
+    ret_if_some!(self.spire.as_ref().and_then(|spire| spire.block_material_at(pos)).map(tagged(FeatureTag::Building)));
+    ret_if_some!(self.props.iter().find_map(|prop| prop.block_material_at(pos)).map(tagged(FeatureTag::Prop)));
+    ret_if_some!({
+      self.details.as_ref()
+        .and_then(|details| details.block_material_at(pos))
+        .map(tagged(FeatureTag::Decoration))
+    });
+    ret_if_some!({
+      self.scatter.as_ref()
+        .and_then(|scatter| scatter.block_material_at(pos))
+        .map(tagged(FeatureTag::Decoration))
+    });
+    ret_if_some!(self.wall.as_ref().and_then(|wall| wall.block_material_at(pos)).map(tagged(FeatureTag::Wall)));
+    None
+  }
 }
 
 impl Geometry for Layer {
@@ -66,35 +808,544 @@ impl Geometry for Layer {
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    self.landmass.block_at(pos) || self.pillars.block_at(pos) || self.buildings.block_at(pos)
+    for access in self.pillar_accesses.iter() {
+      if access.block_at(pos) { return true };
+      if access.opening().block_at(pos) { return false };
+    };
+
+    self.plazas.iter().any(|plaza| plaza.block_at(pos)) || self.pools.iter().any(|pool| pool.block_at(pos)) ||
+    self.vegetation.block_at(pos) ||
+    self.shadow.as_ref().map_or(false, |shadow| shadow.block_at(pos)) ||
+    self.features.block_at(pos) || self.lamp_posts.iter().any(|lamp_post| lamp_post.block_at(pos)) ||
+    self.underside.block_at(pos) || self.buildings.block_at(pos) ||
+    self.spire.as_ref().map_or(false, |spire| spire.block_at(pos)) ||
+    self.props.iter().any(|prop| prop.block_at(pos)) ||
+    self.details.as_ref().map_or(false, |details| details.block_at(pos)) ||
+    self.scatter.as_ref().map_or(false, |scatter| scatter.block_at(pos)) ||
+    self.wall.as_ref().map_or(false, |wall| wall.block_at(pos))
   }
 }
 
 impl MaterialGeometry for Layer {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
-    ret_if_some!(self.landmass.block_at(pos).then(|| blocks::GRAY_CONCRETE));
-    ret_if_some!(self.pillars.block_at(pos).then(|| blocks::GRAY_CONCRETE));
-    ret_if_some!(self.buildings.block_at(pos).then(|| blocks::GRAY_CONCRETE));
-    None
+  /// The ways into the pillars take precedence over everything else. After them, the surface of the landmass is decided
+  /// in order of priority: plazas, then pools, then vegetation, then shadows, then the road and the landmass itself.
+  /// Vegetation never grows on the road, in plazas or around pools in the first place.
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_material_at_traced(pos).map(|(block, _)| block)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    if let Some(material) = self.pillar_material() {
+      self.pillar_accesses.iter().for_each(|access| access.possible_blocks(material, out));
+    };
+
+    self.plazas.iter().for_each(|plaza| plaza.possible_blocks(out));
+    self.pools.iter().for_each(|pool| pool.possible_blocks(out));
+    self.vegetation.possible_blocks(out);
+    if let Some(shadow) = &self.shadow {
+      shadow.possible_blocks(out);
+    };
+
+    for (feature, material) in self.features.iter() {
+      feature.possible_blocks(material, out);
+    };
+
+    self.lamp_posts.iter().for_each(|lamp_post| lamp_post.possible_blocks(out));
+    self.underside.possible_blocks(out);
+    self.buildings.possible_blocks(out);
+    if let Some(spire) = &self.spire {
+      spire.possible_blocks(out);
+    };
+
+    self.props.iter().for_each(|prop| prop.possible_blocks(out));
+    if let Some(details) = &self.details {
+      details.possible_blocks(out);
+    };
+
+    if let Some(scatter) = &self.scatter {
+      scatter.possible_blocks(out);
+    };
+
+    if let Some(wall) = &self.wall {
+      wall.possible_blocks(out);
+    };
+  }
+}
+
+impl Encode for Layer {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.plazas);
+    encoder.put(&self.pools);
+    encoder.put(&self.vegetation);
+    encoder.put(&self.features);
+    encoder.put(&self.lamp_posts);
+    encoder.put(&self.pillar_accesses);
+    encoder.put(&self.underside);
+    encoder.put(&self.buildings);
+    encoder.put(&self.spire);
+    encoder.put(&self.props);
+    encoder.put(&self.build_limit);
+    encoder.put(&self.bounding_box);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let plazas = decoder.take::<Vec<Plaza>>()?;
+    Some(Layer {
+      points_of_interest: plazas.iter().map(PoiRecord::plaza).collect(),
+      plazas,
+      pools: decoder.take()?,
+      vegetation: decoder.take()?,
+      features: decoder.take()?,
+      shadow: None,
+      lamp_posts: decoder.take()?,
+      pillar_accesses: decoder.take()?,
+      underside: decoder.take()?,
+      buildings: decoder.take()?,
+      spire: decoder.take()?,
+      props: decoder.take()?,
+      details: None,
+      scatter: None,
+      collisions: Vec::new(),
+      wall: None,
+      build_limit: decoder.take()?,
+      bounding_box: decoder.take()?
+    })
   }
 }
 
+/// Builds on each of the given building shapes that the districts keep, at heights drawn from `heights`,
+/// skipping any too narrow to build on, and returns the buildings along with the number of shapes skipped
+fn generate_buildings<R: Rng>(
+  rng: &mut R, building_shapes: Vec<BuildingShape>, districts: &Districts, heights: HeightDistribution,
+  level: i32, basement_depth: u32
+) -> (Vec<Building>, usize) {
+  let mut undersized = 0;
+  let buildings = building_shapes.into_iter()
+    .filter_map(|building_shape| {
+      // Building shapes are at half resolution, so the sum of the edges is the centroid in block space
+      let profile = districts.profile(building_shape.edge_min + building_shape.edge_max);
+      let height = heights.sample(rng) as f64 * profile.height_multiplier;
+      let height = (height.round() as u32).max(1);
+      let detail_seed = rng.gen();
+      // Always drawn so that the buildings after this one are unaffected by whether this one is kept
+      let keep = rng.gen_bool(profile.density);
+      // Checked after every draw for the same reason
+      if building_shape.footprint_size().min_element() < MIN_FOOTPRINT_SIZE {
+        undersized += 1;
+        return None;
+      };
+
+      keep.then(|| {
+        let material = profile.material.clone();
+        Building::from_shape(building_shape, level, height, basement_depth, detail_seed, material)
+      })
+    })
+    .collect::<Vec<Building>>();
+  (buildings, undersized)
+}
+
+/// Places up to two props at points along the edge of the landmass, facing away from its center,
+/// skipping any that would collide with buildings or pillars
+fn generate_props<R: Rng>(
+  rng: &mut R, shape: &LandmassShape, level: i32, buildings: &[Building], pillars: &[PillarCluster]
+) -> Vec<Prop> {
+  let mut points = shape.generate_prop_points();
+  let center = (shape.min() + shape.max()) / 2;
+  let count = rng.gen_range(0..=MAX_PROPS_PER_LAYER);
+  let mut props = Vec::with_capacity(count);
+  for _ in 0..count {
+    if points.is_empty() { break };
+    let origin = points.swap_remove(rng.gen_range(0..points.len()));
+    let direction = Direction::from_vector(origin - center);
+    let prop = match rng.gen_bool(0.5) {
+      true => Prop::Crane(Crane::new(origin, direction, level)),
+      false => Prop::Turbine(Turbine::new(origin, direction, level))
+    };
+
+    let collides = buildings.iter().any(|building| do_geometries_intersect(building, &prop)) ||
+      pillars.iter().any(|pillar| do_geometries_intersect(pillar, &prop));
+    if !collides {
+      props.push(prop);
+    };
+  };
+
+  props
+}
+
+/// Picks sites for pools in the gaps between the other features on the surface of the landmass,
+/// rejecting any whose basin would overlap the road, a plaza or the bounding box of another feature
+#[allow(clippy::too_many_arguments)]
+fn generate_pools(
+  seed: u64, shape: &LandmassShape, level: i32, plazas: &[Plaza], pillars: &[PillarCluster],
+  lamp_posts: &[LampPost], buildings: &[Building], props: &[Prop]
+) -> Vec<Pool> {
+  let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+  shape.generate_pool_sites(&mut rng, |center, along_y| {
+    let pool = Pool::new(center, along_y, level);
+    let bounding_box = pool.bounding_box();
+    let (min, max) = (bounding_box.min.xy(), bounding_box.max.xy());
+    let columns = (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y)));
+    let on_open_ground = columns.into_iter().all(|pos| shape.sample(pos).map_or(false, |cell| !cell.is_road()));
+    on_open_ground &&
+      !plazas.iter().any(|plaza| plaza.intersects_box(min, max)) &&
+      !pillars.iter().any(|pillar| do_geometries_intersect(pillar, &pool)) &&
+      !lamp_posts.iter().any(|lamp_post| do_geometries_intersect(lamp_post, &pool)) &&
+      !buildings.iter().any(|building| do_geometries_intersect(building, &pool)) &&
+      !props.iter().any(|prop| do_geometries_intersect(prop, &pool))
+  })
+  .into_iter()
+  .map(|(center, along_y)| Pool::new(center, along_y, level))
+  .collect()
+}
+
 fn do_geometries_intersect(g1: &impl Geometry, g2: &impl Geometry) -> bool {
   BoundingBox::intersects(g1.bounding_box(), g2.bounding_box())
 }
 
+/// Whether the bounding box of any of the given geometries covers the given column
+fn covers_column(geometries: &[impl Geometry], pos: IVec2) -> bool {
+  geometries.iter().any(|geometry| {
+    let bounding_box = geometry.bounding_box();
+    pos.cmpge(bounding_box.min.xy()).all() && pos.cmple(bounding_box.max.xy()).all()
+  })
+}
+
+fn join_bounding_box(bounding_box: BoundingBox, geometry: Option<&impl Geometry>) -> BoundingBox {
+  geometry.map_or(bounding_box, |geometry| bounding_box.join(geometry.bounding_box()))
+}
+
+
+
+/// The features of a layer that are made up of a single material
+#[derive(Debug, Clone)]
+enum LayerFeature {
+  Road(Road),
+  Landmass(Landmass),
+  Pillars(Union<Vec<PillarCluster>>),
+  Cantilevers(Union<Vec<Cantilever>>),
+  Skirt(Skirt)
+}
+
+impl Geometry for LayerFeature {
+  fn bounding_box(&self) -> BoundingBox {
+    match self {
+      LayerFeature::Road(road) => road.bounding_box(),
+      LayerFeature::Landmass(landmass) => landmass.bounding_box(),
+      LayerFeature::Pillars(pillars) => pillars.bounding_box(),
+      LayerFeature::Cantilevers(cantilevers) => cantilevers.bounding_box(),
+      LayerFeature::Skirt(skirt) => skirt.bounding_box()
+    }
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    match self {
+      LayerFeature::Road(road) => road.block_at(pos),
+      LayerFeature::Landmass(landmass) => landmass.block_at(pos),
+      LayerFeature::Pillars(pillars) => pillars.block_at(pos),
+      LayerFeature::Cantilevers(cantilevers) => cantilevers.block_at(pos),
+      LayerFeature::Skirt(skirt) => skirt.block_at(pos)
+    }
+  }
+}
+
+
+
+impl LayerFeature {
+  fn tag(&self) -> FeatureTag {
+    match self {
+      LayerFeature::Road(_) => FeatureTag::Road,
+      LayerFeature::Landmass(_) => FeatureTag::LandmassSlab,
+      LayerFeature::Pillars(_) => FeatureTag::Pillar,
+      LayerFeature::Cantilevers(_) => FeatureTag::Cantilever,
+      LayerFeature::Skirt(_) => FeatureTag::Skirt
+    }
+  }
+
+  /// The block at a position, made of the given material apart from where pillars are stained
+  fn block_material_at<'a>(&'a self, pos: IVec3, material: &'a Block) -> Option<Cow<'a, Block>> {
+    match self {
+      LayerFeature::Pillars(pillars) => {
+        // Owned, since the stained material belongs to the pillar paired with its material here
+        pillars.iter().find_map(|cluster| {
+          cluster.with_material(material).block_material_at(pos).map(|block| Cow::Owned(block.into_owned()))
+        })
+      },
+      LayerFeature::Landmass(landmass) => landmass.block_material_at(pos, material),
+      feature => feature.block_at(pos).then(|| Cow::Borrowed(material))
+    }
+  }
+
+  /// Adds every block that this feature can place when made of the given material to `out`
+  fn possible_blocks(&self, material: &Block, out: &mut BTreeSet<Block>) {
+    match self {
+      LayerFeature::Pillars(pillars) => {
+        pillars.iter().for_each(|cluster| cluster.with_material(material).possible_blocks(out));
+      },
+      LayerFeature::Landmass(landmass) => landmass.possible_blocks(material, out),
+      _ => { out.insert(material.clone()); }
+    };
+  }
+}
+
+impl Encode for LayerFeature {
+  fn encode(&self, encoder: &mut Encoder) {
+    match self {
+      LayerFeature::Landmass(landmass) => {
+        encoder.put_u8(0);
+        encoder.put(&landmass.shape);
+        encoder.put_i32(landmass.level);
+        encoder.put_u32(landmass.thickness);
+        let hollows = landmass.hollows.cells()
+          .map(|(pos, &hollow)| (pos, hollow))
+          .collect::<Vec<(IVec2, (i32, i32))>>();
+        encoder.put(&hollows);
+        encoder.put(&landmass.caves);
+        encoder.put(&landmass.fill);
+      },
+      LayerFeature::Pillars(pillars) => {
+        encoder.put_u8(1);
+        encoder.put(pillars);
+      },
+      LayerFeature::Road(road) => {
+        encoder.put_u8(2);
+        encoder.put_i32(road.level);
+        encoder.put(&road.columns.cells().map(|(pos, ())| pos).collect::<Vec<IVec2>>());
+      },
+      LayerFeature::Cantilevers(cantilevers) => {
+        encoder.put_u8(4);
+        encoder.put(cantilevers);
+      },
+      LayerFeature::Skirt(skirt) => {
+        encoder.put_u8(3);
+        encoder.put_i32(skirt.top);
+        encoder.put(&skirt.columns.cells().map(|(pos, &floor)| (pos, floor)).collect::<Vec<(IVec2, i32)>>());
+      }
+    };
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    match decoder.take_u8()? {
+      0 => Some(LayerFeature::Landmass(Landmass {
+        shape: decoder.take()?,
+        level: decoder.take_i32()?,
+        thickness: decoder.take_u32()?,
+        hollows: decoder.take::<Vec<(IVec2, (i32, i32))>>()?.into_iter().collect(),
+        caves: decoder.take()?,
+        fill: decoder.take()?,
+        lit: false,
+        paving: None
+      })),
+      1 => Some(LayerFeature::Pillars(decoder.take()?)),
+      2 => Some(LayerFeature::Road(Road {
+        level: decoder.take_i32()?,
+        columns: decoder.take::<Vec<IVec2>>()?.into_iter()
+          .map(|pos| (pos, ()))
+          .collect()
+      })),
+      3 => Some(LayerFeature::Skirt(Skirt {
+        top: decoder.take_i32()?,
+        columns: decoder.take::<Vec<(IVec2, i32)>>()?.into_iter().collect()
+      })),
+      4 => Some(LayerFeature::Cantilevers(decoder.take()?)),
+      _ => None
+    }
+  }
+}
+
+
+
+/// The dimensions of each landmass and of the pillars holding it up, along with how tall the buildings on it are,
+/// shared by every layer of the city
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LandmassParams {
+  /// The number of blocks from the bottom of the landmass' lower slab to the top of its upper slab.
+  /// Landmasses one or two blocks thick are solid, thicker ones have a lattice between their slabs.
+  pub thickness: u32,
+  pub pillar_radius: u32,
+  pub skyline: Skyline
+}
+
+impl LandmassParams {
+  /// The z value at which the lower slab of a landmass is located, given the z value of its upper slab
+  pub fn min_z(self, level: i32) -> i32 {
+    lower_slab_z(level, self.thickness)
+  }
+
+  /// How far the basements of buildings extend down into the landmass.
+  /// Basements stop short of the lower slab, so landmasses too thin to have a lattice have no basements.
+  pub fn basement_depth(self) -> u32 {
+    self.thickness.saturating_sub(2).min(3)
+  }
+}
+
+impl Default for LandmassParams {
+  fn default() -> Self {
+    LandmassParams {
+      thickness: 5,
+      pillar_radius: 3,
+      skyline: Skyline::default()
+    }
+  }
+}
+
+/// Everything that a single layer is generated from, apart from its rng and the sea floor beneath it
+#[derive(Debug, Clone, Copy)]
+pub(super) struct LayerParams {
+  /// The layer's place in the city, where index 0 is the lowest layer
+  pub index: usize,
+  /// The z value of the landmass' upper slab
+  pub top: i32,
+  /// The z value that the pillars holding the layer up stand on
+  pub bottom: i32,
+  /// How large the landmass is, which is 1 for the highest layer and grows by 1 for each layer beneath it
+  pub size: f64,
+  pub landmass: LandmassParams,
+  pub shapes: Option<ShapeConfig>,
+  pub symmetry: Symmetry,
+  pub erosion: Option<ErosionConfig>,
+  pub slab_fill: SlabFillChoice,
+  /// Whether caves are carved out of the landmass
+  pub caves: bool,
+  /// Whether some of the pillars holding the layer up are grouped into braced clusters, if the landmass is large enough
+  pub pillar_clusters: bool,
+  /// The distance from the edge of the landmass to the wall around it, if it has one
+  pub wall_distance: Option<u32>,
+  /// The z value of the world's build limit, one block above the highest block that can be placed
+  pub build_limit: i32
+}
 
+impl LayerParams {
+  /// The parameters of the layer at the given index of a city generated with the given options
+  pub fn new(options: &GeneratorOptions, index: usize) -> Self {
+    // The pillars of the lowest layer stand on the bottom of the world, and those of the rest on the layer beneath
+    let bottom = match index {
+      0 => options.world_height.min_z,
+      _ => index as i32 * options.layer_spacing
+    };
+
+    LayerParams {
+      index,
+      top: (index as i32 + 1) * options.layer_spacing,
+      bottom,
+      size: (options.layer_count - index) as f64,
+      landmass: options.landmass,
+      shapes: options.shapes,
+      symmetry: options.symmetry,
+      erosion: options.erosion,
+      slab_fill: options.slab_fill,
+      // Only the lowest landmass is thick enough beneath the rest of the city to be worth exploring
+      caves: options.caves && index == 0,
+      pillar_clusters: options.pillar_clusters,
+      // Only the lowest layer is walled in
+      wall_distance: options.wall_distance.filter(|_| index == 0),
+      build_limit: options.world_height.max_z
+    }
+  }
+}
 
 #[derive(Debug, Clone)]
 struct Landmass {
   shape: LandmassShape,
-  level: i32
+  level: i32,
+  thickness: u32,
+  /// How the landmass is filled in between its slabs
+  fill: SlabFill,
+  /// Columns hollowed out for the basements of buildings and the recesses of pools,
+  /// and the lowest and highest z values hollowed out in each of them.
+  /// Only the lattice between the slabs and the upper slab itself can be hollowed out.
+  hollows: SparseGrid<(i32, i32)>,
+  /// Voids carved out of the lattice between the slabs, if the landmass has caves
+  caves: Option<InteriorCaves>,
+  /// Whether lights are set into the lower slab beneath the walkable surface.
+  /// Lighting is applied after layers are loaded, so this isn't stored in the layer cache.
+  lit: bool,
+  /// The mix of blocks that the surface is paved with away from the edge, if it isn't all the landmass' material.
+  /// Like lighting, this is applied after layers are loaded.
+  paving: Option<Paving>
 }
 
 impl Landmass {
-  fn generate_new<R: Rng>(source_rng: &mut R, level: i32, size: f64) -> Self {
-    let shape = LandmassShape::generate_new(source_rng.gen(), size);
-    Landmass { shape, level }
+  /// The shape is drawn after the seed of its noise, and only if shapes are varied,
+  /// so that circular landmasses are the same as they were before shapes could be varied.
+  /// The fill is drawn last, and likewise only if fills are random.
+  fn generate_new<R: Rng>(source_rng: &mut R, params: &LayerParams) -> Self {
+    let seed = source_rng.gen();
+    let shape = params.shapes.map_or(ShapeParams::CIRCULAR, |shapes| ShapeParams::random(source_rng, shapes));
+    let shape = LandmassShape::generate_new(seed, params.size, shape, params.symmetry, params.erosion);
+    let fill = params.slab_fill.choose(source_rng);
+    Landmass {
+      shape,
+      level: params.top,
+      thickness: params.landmass.thickness,
+      fill,
+      hollows: SparseGrid::new(),
+      caves: None,
+      lit: false,
+      paving: None
+    }
+  }
+
+  /// Hollows out the regions of the landmass taken up by the basements of the given buildings,
+  /// which extend up to just beneath the upper slab, and removes the upper slab above the water of the given pools.
+  /// Any regions that were hollowed out previously are filled back in.
+  fn hollow_out(&mut self, buildings: &[Building], pools: &[Pool]) {
+    self.hollows = SparseGrid::new();
+    for (min, max, z_range) in buildings.iter().filter_map(Building::basement_interior) {
+      for x in min.x..=max.x {
+        for y in min.y..=max.y {
+          self.hollows.put(IVec2::new(x, y), (*z_range.start(), self.max_z() - 1));
+        };
+      };
+    };
+
+    for pos in pools.iter().flat_map(Pool::water_columns) {
+      self.hollows.put(pos, (self.max_z(), self.max_z()));
+    };
+  }
+
+  fn is_hollow_at(&self, pos: IVec3) -> bool {
+    self.hollows.get(pos.xy()).map_or(false, |&(bottom, top)| pos.z >= bottom && pos.z <= top)
+  }
+
+  fn is_cave_at(&self, pos: IVec3, cell: LandmassCell) -> bool {
+    self.caves.as_ref().map_or(false, |caves| caves.is_carved_at(pos, cell))
+  }
+
+  /// Whether the lower slab has a light set into it at a position, which it does on a grid beneath the interior of the
+  /// landmass wherever the upper slab above hasn't been hollowed out, if the landmass is lit and has a lattice
+  fn is_light_at(&self, pos: IVec3, cell: LandmassCell) -> bool {
+    self.lit && pos.z == self.min_z() && self.min_z() < self.max_z() &&
+      pos.x.rem_euclid(UNDERSIDE_LIGHT_SPACING) == 0 && pos.y.rem_euclid(UNDERSIDE_LIGHT_SPACING) == 0 &&
+      cell.edge_distance() >= MIN_UNDERSIDE_LIGHT_EDGE_DISTANCE && !self.is_hollow_at(pos.xy().extend(self.max_z()))
+  }
+
+  /// The block at a position, made of the given material apart from the lights set into the lower slab
+  /// and the paving of the upper slab, which leaves the edge alone
+  fn block_material_at<'a>(&self, pos: IVec3, material: &'a Block) -> Option<Cow<'a, Block>> {
+    if !self.block_at(pos) { return None };
+    let cell = self.shape.sample(pos.xy())?;
+    Some(match &self.paving {
+      _ if self.is_light_at(pos, cell) => Cow::Borrowed(&blocks::SHROOMLIGHT),
+      Some(paving) if pos.z == self.max_z() && !cell.is_edge() => paving.block_at(pos.xy(), material),
+      _ => Cow::Borrowed(material)
+    })
+  }
+
+  /// Adds every block that this landmass can place when made of the given material to `out`
+  fn possible_blocks(&self, material: &Block, out: &mut BTreeSet<Block>) {
+    out.insert(material.clone());
+    if self.lit {
+      out.insert(blocks::SHROOMLIGHT);
+    };
+
+    if let Some(paving) = &self.paving {
+      paving.possible_blocks(material, out);
+    };
+  }
+
+  /// Pairs this landmass with the material it is made of, so that its lights can stand out from it
+  fn with_material<'a>(&'a self, material: &'a Block) -> MaterialLandmass<'a> {
+    MaterialLandmass { landmass: self, material }
   }
 
   /// The z value at which the landmass' upper slab is located
@@ -104,33 +1355,685 @@ impl Landmass {
 
   /// The z value at which the landmass' lower slab is located
   fn min_z(&self) -> i32 {
-    self.level - LANDMASS_THICKNESS as i32 + 1
+    lower_slab_z(self.level, self.thickness)
   }
 }
 
+fn lower_slab_z(level: i32, thickness: u32) -> i32 {
+  level - thickness as i32 + 1
+}
+
 impl Geometry for Landmass {
   fn bounding_box(&self) -> BoundingBox {
     let min = self.shape.min().extend(self.min_z());
     let max = self.shape.max().extend(self.max_z());
-    BoundingBox::new(min, max)
+    BoundingBox::try_new(min, max).unwrap()
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
     let max = self.max_z();
     let min = self.min_z();
-    self.shape.sample(pos.xy()).is_some() && (
-      (pos.z == min || pos.z == max) ||
-      ((pos.z > min && pos.z < max) && (
-        sample_checkered(2, pos.xy()) ||
-        self.shape.is_edge_at(pos.xy())
-      ))
-    )
+    let cell = match self.shape.sample(pos.xy()) {
+      Some(cell) => cell,
+      None => return false
+    };
+
+    // With a thickness of one or two, the slabs make up the whole landmass and there is no lattice between them
+    pos.z == min || (pos.z == max && !self.is_hollow_at(pos)) ||
+    ((pos.z > min && pos.z < max) && (
+      (self.fill.present(pos, &self.shape) && !self.is_hollow_at(pos)) ||
+      // The landmass is solid beneath the road, so that it reads as a solid band from the side
+      cell.is_edge() || cell.is_road()
+    ) && !self.is_cave_at(pos, cell))
+  }
+}
+
+
+
+/// A landmass along with its material, as returned by `Landmass::with_material`
+struct MaterialLandmass<'a> {
+  landmass: &'a Landmass,
+  material: &'a Block
+}
+
+impl Geometry for MaterialLandmass<'_> {
+  fn bounding_box(&self) -> BoundingBox {
+    self.landmass.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.landmass.block_at(pos)
+  }
+}
+
+impl MaterialGeometry for MaterialLandmass<'_> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.landmass.block_material_at(pos, self.material)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.landmass.possible_blocks(self.material, out);
+  }
+}
+
+
+
+/// The surface of the road running around the perimeter of a landmass
+#[derive(Debug, Clone)]
+struct Road {
+  level: i32,
+  columns: SparseGrid<()>
+}
+
+impl Road {
+  fn new(landmass: &Landmass) -> Self {
+    let columns = landmass.shape.cells()
+      .filter(|(_, cell)| cell.is_road())
+      .map(|(pos, _)| (pos, ()))
+      .collect();
+    Road { level: landmass.max_z(), columns }
+  }
+}
+
+impl Geometry for Road {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.columns.min().unwrap_or(IVec2::ZERO).extend(self.level);
+    let max = self.columns.max().unwrap_or(IVec2::ZERO).extend(self.level);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    pos.z == self.level && self.columns.contains(pos.xy())
+  }
+}
+
+/// The columns of a landmass' upper slab that are shaded by the landmasses of higher layers.
+/// Within `SHADOW_FADE_WIDTH` columns of a higher landmass' edge, columns are dithered between shaded and unshaded,
+/// becoming more likely to be shaded the further they are from the edge.
+#[derive(Debug, Clone)]
+struct Shadow {
+  level: i32,
+  columns: SparseGrid<()>
+}
+
+impl Shadow {
+  fn new(landmass: &Landmass, covers: &[&LandmassShape]) -> Self {
+    let is_covered = |pos: IVec2| covers.iter().any(|shape| shape.sample(pos).is_some());
+    let seed = landmass.level as u32 as u64;
+    let columns = landmass.shape.cells()
+      // The road is already darker than the rest of the surface, and pools have no surface to darken
+      .filter(|&(pos, cell)| !cell.is_road() && !landmass.is_hollow_at(pos.extend(landmass.max_z())) && is_covered(pos))
+      .filter(|&(pos, _)| {
+        let depth = covered_depth(pos, is_covered);
+        depth > SHADOW_FADE_WIDTH || hash_to_unit(hash2(seed, pos)) * ((SHADOW_FADE_WIDTH + 1) as f64) < depth as f64
+      })
+      .map(|(pos, _)| (pos, ()))
+      .collect();
+    Shadow { level: landmass.max_z(), columns }
+  }
+}
+
+impl Geometry for Shadow {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.columns.min().unwrap_or(IVec2::ZERO).extend(self.level);
+    let max = self.columns.max().unwrap_or(IVec2::ZERO).extend(self.level);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    pos.z == self.level && self.columns.contains(pos.xy())
+  }
+}
+
+impl MaterialGeometry for Shadow {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&blocks::POLISHED_BASALT))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(blocks::POLISHED_BASALT);
+  }
+}
+
+/// The distance from a covered column to the nearest uncovered column, up to `SHADOW_FADE_WIDTH + 1`
+fn covered_depth(pos: IVec2, is_covered: impl Fn(IVec2) -> bool) -> i32 {
+  rings_in_box(IVec2::splat(-SHADOW_FADE_WIDTH), IVec2::splat(SHADOW_FADE_WIDTH))
+    .find(|&offset| !is_covered(pos + offset))
+    .map_or(SHADOW_FADE_WIDTH + 1, |offset| offset.x.abs().max(offset.y.abs()))
+}
+
+/// A wall running down from the bottom of a landmass' edge to the sea floor beneath it,
+/// leaving an opening at the start of the edge
+#[derive(Debug, Clone)]
+struct Skirt {
+  top: i32,
+  /// The edge columns covered by the wall, and the height of the sea floor beneath each of them
+  columns: SparseGrid<i32>
+}
+
+impl Skirt {
+  fn new(landmass: &Landmass, floor: SeaFloor) -> Self {
+    let mut edge = landmass.shape.cells()
+      .filter(|(_, cell)| cell.is_edge())
+      .map(|(pos, cell)| (pos, cell.ordering()))
+      .collect::<Vec<(IVec2, usize)>>();
+    // Ties are broken by position, so that the opening doesn't depend on the grid's iteration order
+    edge.sort_unstable_by_key(|&(pos, ordering)| (ordering, pos.x, pos.y));
+    let columns = edge.into_iter()
+      .skip(SKIRT_OPENING_WIDTH)
+      .map(|(pos, _)| (pos, floor.height_at(pos)))
+      .collect();
+    Skirt { top: landmass.min_z() - 1, columns }
+  }
+}
+
+impl Geometry for Skirt {
+  fn bounding_box(&self) -> BoundingBox {
+    let min_z = self.columns.cells().map(|(_, &floor)| floor).min().unwrap_or(self.top);
+    let min = self.columns.min().unwrap_or(IVec2::ZERO).extend(min_z);
+    let max = self.columns.max().unwrap_or(IVec2::ZERO).extend(self.top);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.columns.get(pos.xy()).map_or(false, |&floor| pos.z >= floor && pos.z <= self.top)
   }
 }
 
-fn sample_checkered(size: u32, pos: IVec2) -> bool {
-  let size = (size + 1) as i32;
-  let xp = pos.x.rem_euclid(size * 2);
-  let yp = pos.y.rem_euclid(size * 2);
-  (xp == 0 && yp == 0) || (xp - size == 0 && yp - size == 0)
+
+
+
+#[cfg(test)]
+mod tests {
+  use std::cmp::Ordering;
+
+  use glam::DVec2;
+
+  use super::*;
+  use crate::generation::city::{CityPlacement, Foundation};
+  use crate::generation::city::letterbox::Letterbox;
+  use crate::generation::city::point_of_interest::PoiKind;
+  use crate::generation::bedrock::Bedrock;
+  use crate::generation::fixtures::RadialRamp;
+  use crate::generation::ocean::Ocean;
+  use crate::utility::CARDINAL8;
+
+  /// The landmass of a single layer of the given thickness, filled with the default lattice
+  fn landmass_of_thickness(thickness: u32) -> (LayerParams, Landmass) {
+    let options = GeneratorOptions {
+      layer_count: 1,
+      landmass: LandmassParams { thickness, ..LandmassParams::default() },
+      ..GeneratorOptions::default()
+    };
+
+    let params = LayerParams::new(&options, 0);
+    let landmass = Landmass::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(0), &params);
+    (params, landmass)
+  }
+
+  #[test]
+  fn landmass_cross_sections_follow_the_thickness() {
+    for (thickness, basement_depth) in [(1, 0), (2, 0), (3, 1), (5, 3)] {
+      let (params, landmass) = landmass_of_thickness(thickness);
+      let (min, max) = (landmass.min_z(), landmass.max_z());
+      assert_eq!(max - min + 1, thickness as i32);
+      assert_eq!(params.landmass.min_z(params.top), min);
+      assert_eq!(params.landmass.basement_depth(), basement_depth);
+
+      let bounding_box = landmass.bounding_box();
+      assert_eq!((bounding_box.min.z, bounding_box.max.z), (min, max));
+      let estimate = Layer::estimate_bounding_box(&params);
+      assert!(estimate.min.cmple(bounding_box.min).all() && estimate.max.cmpge(bounding_box.max).all());
+
+      let mut gaps = 0;
+      for (pos, cell) in landmass.shape.cells() {
+        assert!(!landmass.block_at(pos.extend(min - 1)) && !landmass.block_at(pos.extend(max + 1)));
+        assert!(landmass.block_at(pos.extend(min)), "thickness {}: no lower slab at {}", thickness, pos);
+        assert!(landmass.block_at(pos.extend(max)), "thickness {}: no upper slab at {}", thickness, pos);
+
+        // Only the lattice between the slabs has gaps, and never beneath the edge or the road
+        let column_gaps = (min + 1..max).filter(|&z| !landmass.block_at(pos.extend(z))).count();
+        if cell.is_edge() || cell.is_road() {
+          assert_eq!(column_gaps, 0, "thickness {}: gaps beneath the edge at {}", thickness, pos);
+        };
+
+        gaps += column_gaps;
+      };
+
+      assert_eq!(gaps > 0, thickness >= 3, "thickness {}: {} gaps", thickness, gaps);
+    };
+  }
+
+  #[test]
+  fn building_shapes_too_narrow_to_build_on_are_skipped() {
+    let districts = Districts::new(0);
+    let heights = Skyline::default().distribution();
+    let wide = (0..16)
+      .map(|i| BuildingShape { edge_min: IVec2::new(i * 8, 0), edge_max: IVec2::new(i * 8 + 2, 3) })
+      .collect::<Vec<BuildingShape>>();
+    // Every other shape is a single cell across one of its axes, which makes a footprint a single block wide
+    let narrow = wide.iter().enumerate()
+      .map(|(i, shape)| match i % 4 {
+        1 => BuildingShape { edge_max: IVec2::new(shape.edge_min.x, shape.edge_max.y), ..shape.clone() },
+        3 => BuildingShape { edge_max: IVec2::new(shape.edge_max.x, shape.edge_min.y), ..shape.clone() },
+        _ => shape.clone()
+      })
+      .collect::<Vec<BuildingShape>>();
+
+    let mut wide_rng = Xoshiro256PlusPlus::seed_from_u64(0);
+    let mut narrow_rng = Xoshiro256PlusPlus::seed_from_u64(0);
+    let (wide_buildings, wide_undersized) = generate_buildings(&mut wide_rng, wide, &districts, heights, 0, 0);
+    let (narrow_buildings, undersized) = generate_buildings(&mut narrow_rng, narrow, &districts, heights, 0, 0);
+    assert_eq!((wide_undersized, undersized), (0, 8));
+    assert!(!narrow_buildings.is_empty());
+
+    // The skipped shapes drew as much from the rng as any other, so the rest are built as if they had been kept
+    let summary = |building: &Building| (building.edge_min, building.edge_max, building.height, building.detail_seed);
+    let kept = wide_buildings.iter()
+      .filter(|building| (building.edge_min.x / 16) % 2 == 0)
+      .map(summary)
+      .collect::<Vec<_>>();
+    assert_eq!(narrow_buildings.iter().map(summary).collect::<Vec<_>>(), kept);
+    assert_eq!(wide_rng.gen::<u64>(), narrow_rng.gen::<u64>());
+  }
+
+  #[test]
+  fn skirted_layers_reach_down_to_the_sea_floor() {
+    let options = GeneratorOptions { layer_count: 1, foundation: Foundation::Skirt, ..GeneratorOptions::default() };
+    let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(0);
+    let bedrock = Bedrock::new(&mut source_rng, options.flat_bedrock, options.world_height.min_z);
+    let ocean = Ocean::new(&mut source_rng, bedrock, options.wavy_ocean);
+    let floor = SeaFloor { ocean: &ocean, placement: CityPlacement::default() };
+    let layer = Layer::generate_new(&mut source_rng, &LayerParams::new(&options, 0), Some(floor));
+
+    assert_eq!(layer.pillar_clusters().count(), 0);
+    assert!(layer.features.iter().all(|(feature, _)| !matches!(feature, LayerFeature::Pillars(_))));
+    let landmass = layer.landmass().unwrap();
+    let skirt = layer.features.iter()
+      .find_map(|(feature, _)| match feature {
+        LayerFeature::Skirt(skirt) => Some(skirt),
+        _ => None
+      })
+      .unwrap();
+
+    // Every edge column but those of the opening is walled from the sea floor up to the bottom of the landmass
+    let edge = landmass.shape.cells().filter(|(_, cell)| cell.is_edge()).map(|(pos, _)| pos).collect::<Vec<IVec2>>();
+    let walled = edge.iter()
+      .filter(|&&pos| {
+        let wall = floor.height_at(pos)..landmass.min_z();
+        let is_walled = wall.clone().all(|z| layer.block_at(pos.extend(z)));
+        assert!(is_walled || wall.clone().all(|z| !skirt.block_at(pos.extend(z))), "partly walled at {}", pos);
+        is_walled
+      })
+      .count();
+    assert_eq!(walled, edge.len() - SKIRT_OPENING_WIDTH);
+
+    let bounding_box = layer.bounding_box();
+    assert!(bounding_box.min.cmple(skirt.bounding_box().min).all(), "{:?}", bounding_box);
+    for &pos in edge.iter() {
+      assert!(layer.column_top(pos).map_or(false, |top| top >= landmass.max_z()), "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn every_layer_feature_has_a_bounding_box() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    for seed in 0..8 {
+      let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+      let layer = Layer::generate_new(&mut source_rng, &LayerParams::new(&options, 0), None);
+      for (feature, _) in layer.features.iter() {
+        // A union of nothing has no bounding box to find
+        let is_empty = match feature {
+          LayerFeature::Pillars(pillars) => pillars.is_empty(),
+          LayerFeature::Cantilevers(cantilevers) => cantilevers.is_empty(),
+          _ => false
+        };
+        assert!(!is_empty, "seed {}: empty {:?}", seed, feature.tag());
+        let bounding_box = feature.bounding_box();
+        assert!(bounding_box.min.cmple(bounding_box.max).all(), "seed {}: {:?}", seed, bounding_box);
+      };
+    };
+  }
+
+  #[test]
+  fn the_spire_on_the_top_layer_stays_beneath_the_build_limit() {
+    let options = GeneratorOptions::default();
+    let params = LayerParams::new(&options, options.layer_count - 1);
+    let generate = |build_limit: i32| {
+      let params = LayerParams { build_limit, ..params };
+      Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(0), &params, None)
+    };
+
+    let layer = generate(options.world_height.max_z);
+    let tallest = layer.buildings.iter().map(Building::top).max().unwrap();
+    let spire = layer.spire.as_ref().unwrap().bounding_box();
+    assert_eq!(spire.min.z, tallest + 1);
+    assert!(spire.max.z < options.world_height.max_z, "{:?}", spire);
+
+    // The build limit only changes the spire, so the same buildings are generated beneath a lower one
+    let layer = generate(tallest + 5);
+    assert_eq!(layer.buildings.iter().map(Building::top).max(), Some(tallest));
+    assert_eq!(layer.spire.as_ref().unwrap().bounding_box().max.z, tallest + 4);
+
+    let layer = generate(tallest + 1);
+    assert!(layer.spire.is_none());
+  }
+
+  #[test]
+  fn retaining_buildings_moves_the_spire_onto_the_tallest_left() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(0);
+    let mut layer = Layer::generate_new(&mut source_rng, &LayerParams::new(&options, 0), None);
+    let tallest = layer.buildings.iter().map(Building::top).max().unwrap();
+    let count = layer.buildings.len();
+
+    layer.retain_buildings(|building| building.top() < tallest);
+    assert!(layer.buildings.len() < count);
+    assert!(layer.buildings.iter().all(|building| building.top() < tallest));
+    let left = layer.buildings.iter().map(Building::top).max().unwrap();
+    assert_eq!(layer.spire.as_ref().unwrap().bounding_box().min.z, left + 1);
+    assert!(layer.cantilevers().all(|cantilever| layer.buildings.iter().any(|building| cantilever.carries(building))));
+
+    layer.retain_buildings(|_| false);
+    assert!(layer.buildings.is_empty());
+    assert!(layer.spire.is_none());
+    assert!(layer.features.iter().all(|(feature, _)| !matches!(feature, LayerFeature::Cantilevers(_))));
+  }
+
+  #[test]
+  fn buildings_stay_clear_of_plazas() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let mut plaza_count = 0;
+    for seed in 0..4 {
+      let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+      let layer = Layer::generate_new(&mut source_rng, &LayerParams::new(&options, 0), None);
+      for building in layer.buildings.iter() {
+        let bounding_box = building.bounding_box();
+        for plaza in layer.plazas() {
+          assert!(
+            !plaza.intersects_box(bounding_box.min.xy(), bounding_box.max.xy()),
+            "seed {}: the building at {:?} is in the plaza at {}", seed, bounding_box, plaza.center()
+          );
+        };
+      };
+
+      plaza_count += layer.plazas().len();
+    };
+
+    assert!(plaza_count > 0, "no plazas were reserved to keep clear");
+  }
+
+  #[test]
+  fn plazas_are_decorated_around_their_centers() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let params = LayerParams::new(&options, 0);
+    let layer = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(0), &params, None);
+    let material_at = |pos: IVec3| layer.block_material_at(pos).map(Cow::into_owned);
+    assert!(!layer.plazas().is_empty());
+    for plaza in layer.plazas() {
+      let center = plaza.center();
+      assert!(layer.landmass().unwrap().shape.sample(center).is_some(), "the plaza at {} is off the landmass", center);
+      // A lantern on a post at the center, and a ring set into the surface two blocks inside the plaza's edge
+      let lantern_z = plaza.bounding_box().max.z;
+      assert_eq!(material_at(center.extend(lantern_z)), Some(blocks::LANTERN), "at {}", center);
+      assert_eq!(material_at(center.extend(lantern_z - 1)), Some(blocks::DARK_OAK_FENCE), "at {}", center);
+      let ring = center + IVec2::new(plaza.radius() as i32 - 2, 0);
+      assert_eq!(material_at(ring.extend(params.top)), Some(blocks::WHITE_CONCRETE), "at {}", ring);
+      assert_ne!(material_at((center + 1).extend(params.top)), Some(blocks::WHITE_CONCRETE), "at {}", center);
+    };
+  }
+
+  #[test]
+  fn the_road_is_paved_across_the_top_of_its_band() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let layer = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(0), &LayerParams::new(&options, 0), None);
+    let landmass = layer.landmass().unwrap();
+    let (top, bottom) = (landmass.max_z(), landmass.min_z());
+    let mut road_count = 0;
+    for (pos, cell) in landmass.shape.cells() {
+      let material = layer.features.block_material_at(pos.extend(top)).map(Cow::into_owned);
+      if cell.is_road() {
+        assert_eq!(material, Some(blocks::POLISHED_DEEPSLATE), "at {}", pos);
+        // The landmass is solid all the way down beneath the road
+        assert!((bottom..top).all(|z| landmass.block_at(pos.extend(z))), "not solid beneath {}", pos);
+        road_count += 1;
+      } else {
+        assert_ne!(material, Some(blocks::POLISHED_DEEPSLATE), "at {}", pos);
+      };
+    };
+
+    assert!(road_count > 0);
+  }
+
+  #[test]
+  fn basements_are_hollowed_out_of_the_slab_beneath_their_buildings_alone() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let layer = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(0), &LayerParams::new(&options, 0), None);
+    let landmass = layer.landmass().unwrap();
+    let (top, bottom) = (landmass.max_z(), landmass.min_z());
+    let basements = layer.buildings.iter().filter_map(Building::basement_interior).collect::<Vec<_>>();
+    assert!(!basements.is_empty() && basements.len() < layer.buildings.len());
+
+    for (min, max, z_range) in basements.iter() {
+      assert!(*z_range.start() > bottom && *z_range.end() == top - 1, "the basement at {} leaves the slab", min);
+      for pos in (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y))) {
+        let cell = match landmass.shape.sample(pos) {
+          Some(cell) => cell,
+          None => continue
+        };
+
+        // Only the lattice walls along the edges and beneath the road are left standing inside a basement
+        let walled = cell.is_edge() || cell.is_road();
+        for z in z_range.clone() {
+          assert!(walled || !landmass.block_at(pos.extend(z)), "the basement isn't hollowed out at {}", pos.extend(z));
+        };
+
+        assert!(landmass.block_at(pos.extend(top)), "there's no floor over the basement at {}", pos);
+        assert!(landmass.block_at(pos.extend(bottom)), "there's no slab under the basement at {}", pos);
+      };
+    };
+
+    // The slab beneath every other building is left as it was
+    let in_basement = |pos: IVec2| basements.iter().any(|(min, max, _)| pos.cmpge(*min).all() && pos.cmple(*max).all());
+    let in_pool = |pos: IVec2| layer.pools.iter().flat_map(Pool::water_columns).any(|column| column == pos);
+    for building in layer.buildings.iter().filter(|building| building.basement_interior().is_none()) {
+      let (edge_min, edge_max) = (building.edge_min, building.edge_max);
+      for pos in (edge_min.x..=edge_max.x).flat_map(|x| (edge_min.y..=edge_max.y).map(move |y| IVec2::new(x, y))) {
+        if in_basement(pos) || in_pool(pos) || landmass.shape.sample(pos).is_none() { continue };
+        assert!(landmass.block_at(pos.extend(top)), "the slab is missing under the building at {}", pos);
+        assert!((bottom..=top).all(|z| !landmass.is_hollow_at(pos.extend(z))), "the slab is hollowed out at {}", pos);
+      };
+    };
+  }
+
+  #[test]
+  fn props_are_placed_deterministically_clear_of_buildings() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let params = LayerParams::new(&options, 0);
+    let mut prop_count = 0;
+    for seed in 0..8 {
+      let layer = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(seed), &params, None);
+      let again = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(seed), &params, None);
+      assert_eq!(format!("{:?}", layer.props), format!("{:?}", again.props), "seed {}", seed);
+      assert!(layer.props.len() <= MAX_PROPS_PER_LAYER, "seed {}", seed);
+
+      for prop in layer.props.iter() {
+        let bounding_box = prop.bounding_box();
+        assert_eq!(bounding_box.min.z, params.top + 1, "seed {}: {:?}", seed, prop);
+        assert!(layer.buildings.iter().all(|building| !do_geometries_intersect(building, prop)), "seed {}", seed);
+      };
+
+      prop_count += layer.props.len();
+    };
+
+    assert!(prop_count > 0, "no props were placed");
+  }
+
+  #[test]
+  fn paving_only_changes_the_top_of_the_upper_slab_inside_the_edge() {
+    let (_, landmass) = landmass_of_thickness(5);
+    let mut paved = landmass.clone();
+    paved.paving = Some(Paving::new(0, PavingConfig::default()));
+    let material = blocks::GRAY_CONCRETE;
+    let mut changed = 0;
+    for (pos, cell) in landmass.shape.cells() {
+      for z in landmass.min_z()..=landmass.max_z() {
+        let pos = pos.extend(z);
+        let block = landmass.block_material_at(pos, &material);
+        let paved_block = paved.block_material_at(pos, &material);
+        if z == landmass.max_z() && !cell.is_edge() {
+          assert_eq!(paved_block.is_some(), block.is_some(), "at {}", pos);
+          changed += (paved_block != block) as usize;
+        } else {
+          assert_eq!(paved_block, block, "the paving reaches {}", pos);
+        };
+      };
+    };
+
+    assert!(changed > 0, "nothing was paved");
+  }
+
+  #[test]
+  fn only_the_widest_pillars_are_hollowed_out() {
+    let mut access_count = 0;
+    for (pillar_radius, seed) in [(3, 0), (3, 1), (4, 0), (4, 1), (5, 2)] {
+      let options = GeneratorOptions {
+        layer_count: 1,
+        landmass: LandmassParams { pillar_radius, ..LandmassParams::default() },
+        ..GeneratorOptions::default()
+      };
+      let params = LayerParams::new(&options, 0);
+      let layer = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(seed), &params, None);
+      let slab_bottom = layer.lower_slab_z().unwrap();
+      for access in layer.pillar_accesses.iter() {
+        let ladder = access.ladder_column();
+        let in_pillar = layer.pillars().any(|pillar| pillar.radius() >= 4 && pillar.contains_column(ladder));
+        assert!(in_pillar, "seed {}: the ladder at {} isn't in a wide pillar", seed, ladder);
+      };
+
+      // Every pillar without an access is left solid
+      for pillar in layer.pillars() {
+        let is_accessed = layer.pillar_accesses.iter().any(|access| pillar.contains_column(access.ladder_column()));
+        let is_solid = layer.block_at(pillar.origin().extend(slab_bottom - 2));
+        assert!(is_accessed || is_solid, "seed {}: the pillar at {} is hollow", seed, pillar.origin());
+        assert!(pillar_radius >= 4 || !is_accessed, "seed {}: the pillar at {} is too narrow", seed, pillar.origin());
+      };
+
+      access_count += layer.pillar_accesses.len();
+    };
+
+    assert!(access_count > 0, "no pillars were hollowed out");
+  }
+
+  #[test]
+  fn shadows_are_solid_beneath_a_higher_landmass_and_dithered_around_its_edge() {
+    let (_, landmass) = landmass_of_thickness(5);
+    let cover = LandmassShape::from_noise(RadialRamp { center: DVec2::ZERO, radius: 40.0 }, None);
+    let shadow = Shadow::new(&landmass, &[&cover]);
+    let is_covered = |pos: IVec2| cover.sample(pos).is_some();
+    let (mut shaded, mut unshaded) = (0, 0);
+    for pos in landmass.shape.cells().filter(|(_, cell)| !cell.is_road()).map(|(pos, _)| pos) {
+      let top = pos.extend(landmass.max_z());
+      let material = shadow.block_material_at(top).map(Cow::into_owned);
+      assert!(!shadow.block_at(top + IVec3::Z) && !shadow.block_at(top - IVec3::Z), "at {}", pos);
+      match is_covered(pos) {
+        false => assert_eq!(material, None, "the open column at {} is shaded", pos),
+        true if covered_depth(pos, is_covered) > SHADOW_FADE_WIDTH => {
+          assert_eq!(material, Some(blocks::POLISHED_BASALT), "the covered column at {} isn't shaded", pos);
+        },
+        true if material.is_some() => shaded += 1,
+        true => unshaded += 1
+      };
+    };
+
+    assert!(shaded > 0 && unshaded > 0, "{} shaded and {} unshaded columns around the edge", shaded, unshaded);
+  }
+
+  #[test]
+  fn pools_are_recessed_into_the_surface_clear_of_buildings() {
+    let options = GeneratorOptions { layer_count: 1, ..GeneratorOptions::default() };
+    let params = LayerParams::new(&options, 0);
+    let mut pool_count = 0;
+    for seed in 0..4 {
+      let layer = Layer::generate_new(&mut Xoshiro256PlusPlus::seed_from_u64(seed), &params, None);
+      let material_at = |pos: IVec3| layer.block_material_at(pos).map(Cow::into_owned);
+      for pool in layer.pools.iter() {
+        let water = pool.water_columns().collect::<Vec<IVec2>>();
+        for &pos in water.iter() {
+          assert_eq!(material_at(pos.extend(params.top)), None, "seed {}: the surface is intact at {}", seed, pos);
+          assert_eq!(material_at(pos.extend(params.top - 1)), Some(blocks::WATER), "seed {}: at {}", seed, pos);
+          assert_eq!(material_at(pos.extend(params.top - 2)), Some(blocks::SMOOTH_QUARTZ), "seed {}: at {}", seed, pos);
+        };
+
+        // Every column beside the water, apart from more water, is rimmed at the surface
+        let rim = water.iter()
+          .flat_map(|&pos| CARDINAL8.iter().map(move |&offset| pos + offset))
+          .filter(|pos| !water.contains(pos));
+        for pos in rim {
+          assert_eq!(material_at(pos.extend(params.top)), Some(blocks::SMOOTH_QUARTZ), "seed {}: at {}", seed, pos);
+        };
+
+        for building in layer.buildings.iter() {
+          assert!(!do_geometries_intersect(building, pool), "seed {}: a pool is in a building", seed);
+        };
+      };
+
+      pool_count += layer.pools.len();
+    };
+
+    assert!(pool_count > 0, "no pools were placed");
+  }
+
+  #[test]
+  fn letterboxes_are_cut_well_inside_the_landmass_above_with_a_railing_around_them() {
+    let options = GeneratorOptions { layer_count: 2, ..GeneratorOptions::default() };
+    let mut letterbox_count = 0;
+    for seed in 0..4 {
+      let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+      let mut lower = Layer::generate_new(&mut rng, &LayerParams::new(&options, 0), None);
+      let upper = Layer::generate_new(&mut rng, &LayerParams::new(&options, 1), None);
+      let landmass = upper.landmass().unwrap();
+      // A point of interest on every seventh column beneath the upper landmass, as if there were a plaza there
+      lower.points_of_interest = landmass.shape.cells()
+        .filter(|(pos, _)| pos.x.rem_euclid(7) == 0 && pos.y.rem_euclid(7) == 0)
+        .map(|(center, _)| PoiRecord { kind: PoiKind::Plaza, center })
+        .collect();
+
+      let letterboxes = Letterbox::looking_down(seed, &[&lower, &upper], &[]);
+      let again = Letterbox::looking_down(seed, &[&lower, &upper], &[]);
+      assert_eq!(format!("{:?}", letterboxes), format!("{:?}", again), "seed {}: letterboxes differ", seed);
+
+      let (level, extent) = (landmass.level, upper.landmass_extent().unwrap());
+      for letterbox in letterboxes.iter() {
+        let opening = letterbox.opening().bounding_box();
+        let (min, max) = (opening.min.xy(), opening.max.xy());
+        let is_above_poi = lower.points_of_interest().iter().any(|poi| poi.center == (min + max) / 2);
+        assert!(is_above_poi, "seed {}: the opening at {} is above no point of interest", seed, min);
+        assert_eq!((opening.min.z, opening.max.z), (landmass.min_z(), level + 1), "seed {}: at {}", seed, min);
+
+        let (along_x, along_y) = (IVec2::new(11, 3), IVec2::new(3, 11));
+        let size = max - min + 1;
+        match extent.x.cmp(&extent.y) {
+          Ordering::Greater => assert_eq!(size, along_x, "seed {}: at {}", seed, min),
+          Ordering::Less => assert_eq!(size, along_y, "seed {}: at {}", seed, min),
+          Ordering::Equal => assert!(size == along_x || size == along_y, "seed {}: at {}", seed, min)
+        };
+
+        for x in min.x - 1..=max.x + 1 {
+          for y in min.y - 1..=max.y + 1 {
+            let pos = IVec2::new(x, y);
+            let is_ring = x < min.x || x > max.x || y < min.y || y > max.y;
+            assert_eq!(letterbox.block_at(pos.extend(level + 1)), is_ring, "seed {}: the railing at {}", seed, pos);
+            assert!(!letterbox.block_at(pos.extend(level)) && !letterbox.block_at(pos.extend(level + 2)), "at {}", pos);
+            let edge_distance = landmass.shape.sample(pos).map(|cell| cell.edge_distance());
+            assert!(edge_distance > Some(4), "seed {}: {} is {:?} from the edge", seed, pos, edge_distance);
+          };
+        };
+      };
+
+      letterbox_count += letterboxes.len();
+    };
+
+    assert!(letterbox_count > 0, "no letterboxes were cut");
+  }
 }
@@ -0,0 +1,107 @@
+//! Caches generated layers on disk, so that they do not need to be regenerated between runs with the same inputs.
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use super::layer::Layer;
+use crate::generation::encoding::{Decoder, Encoder};
+use crate::utility::Ignore;
+
+
+
+/// Must be bumped whenever layer generation logic changes, invalidating all existing cache files
+pub const GENERATION_VERSION: u32 = 22;
+
+const MAGIC: &[u8; 4] = b"GLTL";
+const EXTENSION: &str = "layer";
+const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct LayerCache {
+  dir: PathBuf,
+  /// The maximum total size of the cache in bytes, past which the least recently used files are deleted
+  max_size: u64
+}
+
+impl LayerCache {
+  pub fn new(dir: PathBuf, max_size: u64) -> Self {
+    LayerCache { dir, max_size }
+  }
+
+  /// Uses `$XDG_CACHE_HOME/glt-mc-generator`, or `~/.cache/glt-mc-generator` if it is not set
+  pub fn new_default() -> Option<Self> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+      .map(PathBuf::from)
+      .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(LayerCache::new(cache_home.join("glt-mc-generator"), DEFAULT_MAX_SIZE))
+  }
+
+  /// Deletes every file in the cache
+  pub fn clear(&self) -> io::Result<()> {
+    fs::remove_dir_all(&self.dir).ignore_err(io::ErrorKind::NotFound)
+  }
+
+  /// Loads the layer with the given key, returning `None` if it is
+  /// missing, corrupt, or was generated by a different version
+  pub fn load(&self, key: u64) -> Option<Layer> {
+    let path = self.path(key);
+    let bytes = fs::read(&path).ok()?;
+    let mut decoder = Decoder::new(&bytes);
+    if decoder.take_bytes(MAGIC.len())? != MAGIC { return None };
+    if decoder.take_u32()? != GENERATION_VERSION { return None };
+    if decoder.take_u64()? != key { return None };
+    let layer = decoder.take::<Layer>()?;
+    if !decoder.is_finished() { return None };
+
+    // Marks this file as recently used
+    if let Ok(file) = fs::File::options().append(true).open(&path) {
+      let _ = file.set_modified(SystemTime::now());
+    };
+
+    Some(layer)
+  }
+
+  /// Stores the layer with the given key, failing silently since the cache is not essential
+  pub fn store(&self, key: u64, layer: &Layer) {
+    let mut encoder = Encoder::new();
+    encoder.put_bytes(MAGIC);
+    encoder.put_u32(GENERATION_VERSION);
+    encoder.put_u64(key);
+    encoder.put(layer);
+
+    let result = fs::create_dir_all(&self.dir)
+      .and_then(|()| fs::write(self.path(key), encoder.into_bytes()))
+      .and_then(|()| self.evict());
+    if let Err(err) = result {
+      log::warn!("failed to write to the layer cache: {}", err);
+    };
+  }
+
+  /// Deletes the least recently used files until the cache fits within its maximum size
+  fn evict(&self) -> io::Result<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(&self.dir)? {
+      let entry = entry?;
+      let path = entry.path();
+      if path.extension().map_or(false, |extension| extension == EXTENSION) {
+        let metadata = entry.metadata()?;
+        entries.push((metadata.modified()?, metadata.len(), path));
+      };
+    };
+
+    let mut total_size = entries.iter().map(|&(_, len, _)| len).sum::<u64>();
+    entries.sort_unstable_by_key(|&(modified, _, _)| modified);
+    for (_, len, path) in entries {
+      if total_size <= self.max_size { break };
+      fs::remove_file(path).ignore_err(io::ErrorKind::NotFound)?;
+      total_size -= len;
+    };
+
+    Ok(())
+  }
+
+  fn path(&self, key: u64) -> PathBuf {
+    self.dir.join(format!("{:016x}.{}", key, EXTENSION))
+  }
+}
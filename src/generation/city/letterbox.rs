@@ -0,0 +1,139 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::cmp::Ordering;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::layer::Layer;
+use super::shaft::Shaft;
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::hash2;
+
+
+
+/// The width of the opening of a letterbox
+const OPENING_WIDTH: i32 = 3;
+/// The length of the opening of a letterbox, along the longer axis of its landmass
+const OPENING_LENGTH: i32 = 11;
+/// The least edge distance of any column of a letterbox, including its railing,
+/// which keeps openings more than four columns in from the edge of their landmass
+pub const MIN_LETTERBOX_EDGE_DISTANCE: usize = 5;
+
+/// A narrow opening cut through the landmass of a layer, above a point of interest on the layer beneath it,
+/// with a railing around it on the landmass' surface for looking down through it
+#[derive(Debug, Clone)]
+pub struct Letterbox {
+  /// The z value of the railing, just above the surface of the landmass
+  railing: i32,
+  opening: LetterboxOpening
+}
+
+impl Letterbox {
+  /// Cuts a letterbox above each point of interest of every layer that lies beneath the landmass of the layer above it,
+  /// wherever there is room for one, clear of that landmass' edge, of everything on its surface and of the shafts.
+  /// Letterboxes run along the longer axis of their landmass, or one picked with the given seed if neither is longer.
+  pub fn looking_down(seed: u64, layers: &[&Layer], shafts: &[Shaft]) -> Vec<Self> {
+    let mut letterboxes: Vec<Letterbox> = Vec::new();
+    for (i, pair) in layers.windows(2).enumerate() {
+      let (lower, upper) = (pair[0], pair[1]);
+      let (bottom, top, extent) = match (upper.lower_slab_z(), upper.level(), upper.landmass_extent()) {
+        (Some(bottom), Some(top), Some(extent)) => (bottom, top, extent),
+        _ => continue
+      };
+
+      let along_x = match extent.x.cmp(&extent.y) {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => hash2(seed, IVec2::new(i as i32 + 1, 0)) & 1 == 0
+      };
+      let half_size = match along_x {
+        true => IVec2::new(OPENING_LENGTH / 2, OPENING_WIDTH / 2),
+        false => IVec2::new(OPENING_WIDTH / 2, OPENING_LENGTH / 2)
+      };
+
+      for poi in lower.points_of_interest() {
+        let (min, max) = (poi.center - half_size, poi.center + half_size);
+        let overlaps = |bounding_box: BoundingBox| {
+          (min - 1).cmple(bounding_box.max.xy()).all() && (max + 1).cmpge(bounding_box.min.xy()).all()
+        };
+
+        let is_clear = upper.letterbox_fit(min - 1, max + 1) &&
+          !shafts.iter().any(|shaft| overlaps(shaft.bounding_box())) &&
+          !letterboxes.iter().any(|letterbox| overlaps(letterbox.bounding_box()));
+        if is_clear {
+          // Vegetation grows a block above the surface, which would otherwise be left floating over the opening
+          let opening = LetterboxOpening { min, max, bottom, top: top + 1 };
+          letterboxes.push(Letterbox { railing: top + 1, opening });
+        } else {
+          log::debug!("found no room for a letterbox above the {} at {} in layer {}", poi.kind.name(), poi.center, i);
+        };
+      };
+    };
+
+    letterboxes
+  }
+
+  /// The opening cut through the landmass, which nothing else may fill
+  pub fn opening(&self) -> &LetterboxOpening {
+    &self.opening
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<&'static Block> {
+    if pos.z != self.railing { return None };
+    let LetterboxOpening { min, max, .. } = self.opening;
+    let (min, max) = (min - 1, max + 1);
+    let on_x_side = pos.x == min.x || pos.x == max.x;
+    let on_y_side = pos.y == min.y || pos.y == max.y;
+    let within = pos.xy().cmpge(min).all() && pos.xy().cmple(max).all();
+    match (within, on_x_side, on_y_side) {
+      (true, true, true) => Some(&blocks::DARK_OAK_FENCE),
+      (true, true, false) => Some(&blocks::DARK_OAK_FENCE_NORTH_SOUTH),
+      (true, false, true) => Some(&blocks::DARK_OAK_FENCE_EAST_WEST),
+      _ => None
+    }
+  }
+}
+
+impl Geometry for Letterbox {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = (self.opening.min - 1).extend(self.railing);
+    let max = (self.opening.max + 1).extend(self.railing);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Letterbox {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(Cow::Borrowed)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::DARK_OAK_FENCE, blocks::DARK_OAK_FENCE_NORTH_SOUTH, blocks::DARK_OAK_FENCE_EAST_WEST]);
+  }
+}
+
+
+
+/// The columns of a letterbox's opening, from the landmass' lower slab up to just above its upper slab.
+/// Every other feature of the city is carved away wherever this has a block.
+#[derive(Debug, Clone)]
+pub struct LetterboxOpening {
+  min: IVec2,
+  max: IVec2,
+  bottom: i32,
+  top: i32
+}
+
+impl Geometry for LetterboxOpening {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::try_new(self.min.extend(self.bottom), self.max.extend(self.top)).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.bounding_box().contains(pos)
+  }
+}
@@ -0,0 +1,208 @@
+//! Paving which breaks up the walkable surface of each landmass with a mix of related blocks.
+//!
+//! Each column of the surface picks its block from a weighted mix of the landmass' own material and the trim blocks,
+//! by hashing its position, so that the same column always gets the same block. If a patch mix is given, low-frequency
+//! noise lays out patches where it is used instead, so that the surface has larger regions of slightly different
+//! texture. The trim blocks are those of the `trim` and `weathered-trim` material roles, so a theme changes them too.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use glam::IVec2;
+use noise::{Perlin, ScalePoint};
+
+use crate::generation::noise_util::ColumnSampler;
+use crate::generation::{blocks, Block};
+use crate::utility::{hash2, weighted_pick};
+
+
+
+/// The rough width of the patches, in blocks
+const PATCH_SCALE: f64 = 24.0;
+/// How far above its middle the patch noise has to be for a column to be part of a patch,
+/// which leaves patches covering around a quarter of the surface
+const PATCH_THRESHOLD: f64 = 0.2;
+
+/// How likely each block of the paving is to be picked for a column, relative to the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PavingMix {
+  /// The landmass' own material
+  pub primary: u32,
+  /// Polished andesite, unless themed
+  pub trim: u32,
+  /// Andesite, unless themed
+  pub weathered: u32
+}
+
+impl PavingMix {
+  pub fn to_array(self) -> [u32; 3] {
+    [self.primary, self.trim, self.weathered]
+  }
+
+  pub fn from_array([primary, trim, weathered]: [u32; 3]) -> Self {
+    PavingMix { primary, trim, weathered }
+  }
+}
+
+/// Written the way `--paving-mix` takes it
+impl fmt::Display for PavingMix {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{},{},{}", self.primary, self.trim, self.weathered)
+  }
+}
+
+/// The mixes that the paving is picked from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PavingConfig {
+  pub mix: PavingMix,
+  /// The mix used in patches across the surface, if there are any
+  pub patch_mix: Option<PavingMix>
+}
+
+impl Default for PavingConfig {
+  fn default() -> Self {
+    PavingConfig {
+      mix: PavingMix { primary: 70, trim: 20, weathered: 10 },
+      patch_mix: Some(PavingMix { primary: 50, trim: 15, weathered: 35 })
+    }
+  }
+}
+
+/// The paving of one layer's landmass
+#[derive(Clone)]
+pub struct Paving {
+  seed: u64,
+  config: PavingConfig,
+  patches: ColumnSampler<ScalePoint<Perlin>>
+}
+
+impl Paving {
+  pub fn new(seed: u64, config: PavingConfig) -> Self {
+    let patches = ColumnSampler::new(ScalePoint::new(Perlin::new(seed as u32)).set_scale(PATCH_SCALE.recip()))
+      .with_offset(-PATCH_THRESHOLD);
+    Paving { seed, config, patches }
+  }
+
+  /// The block paving the given column of a landmass made of the given material
+  pub fn block_at<'a>(&self, pos: IVec2, material: &'a Block) -> Cow<'a, Block> {
+    let mix = match self.config.patch_mix {
+      Some(patch_mix) if self.patches.height_at(pos) >= 0 => patch_mix,
+      _ => self.config.mix
+    };
+
+    let [primary, trim, weathered] = mix.to_array();
+    let entries = [
+      (Cow::Borrowed(material), primary),
+      (Cow::Borrowed(&blocks::POLISHED_ANDESITE), trim),
+      (Cow::Borrowed(&blocks::ANDESITE), weathered)
+    ];
+    weighted_pick(hash2(self.seed, pos), &entries).map_or(Cow::Borrowed(material), Cow::clone)
+  }
+
+  /// Adds every block that the paving of a landmass made of the given material can place to `out`
+  pub fn possible_blocks(&self, material: &Block, out: &mut BTreeSet<Block>) {
+    for mix in [Some(self.config.mix), self.config.patch_mix].into_iter().flatten() {
+      if mix.trim > 0 {
+        out.insert(blocks::POLISHED_ANDESITE);
+      };
+
+      if mix.weathered > 0 {
+        out.insert(blocks::ANDESITE);
+      };
+    };
+
+    out.insert(material.clone());
+  }
+}
+
+impl fmt::Debug for Paving {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Paving")
+      .field("seed", &self.seed)
+      .field("config", &self.config)
+      .finish_non_exhaustive()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The fraction of the columns from -100 to 99 along each axis, which are in patches if `in_patch` is set,
+  /// paved with each of the landmass' own material, polished andesite and andesite
+  fn mix_fractions(paving: &Paving, in_patch: Option<bool>) -> [f64; 3] {
+    let material = blocks::GRAY_CONCRETE;
+    let mut counts = [0usize; 3];
+    for x in -100..100 {
+      for y in -100..100 {
+        let pos = IVec2::new(x, y);
+        if in_patch.map_or(false, |in_patch| (paving.patches.height_at(pos) >= 0) != in_patch) { continue };
+        let index = match paving.block_at(pos, &material).into_owned() {
+          block if block == material => 0,
+          block if block == blocks::POLISHED_ANDESITE => 1,
+          block if block == blocks::ANDESITE => 2,
+          block => panic!("paved with {:?} at {}", block, pos)
+        };
+        counts[index] += 1;
+      };
+    };
+
+    let total = counts.iter().sum::<usize>().max(1) as f64;
+    counts.map(|count| count as f64 / total)
+  }
+
+  fn assert_mix(fractions: [f64; 3], mix: PavingMix) {
+    let weights = mix.to_array();
+    let total = weights.iter().sum::<u32>() as f64;
+    for (fraction, weight) in fractions.into_iter().zip(weights) {
+      let expected = weight as f64 / total;
+      assert!((fraction - expected).abs() < 0.03, "{:?} paved in {:?} instead of {}", fractions, mix, expected);
+    };
+  }
+
+  #[test]
+  fn the_paving_matches_the_weights_of_its_mix() {
+    let mix = PavingMix { primary: 70, trim: 20, weathered: 10 };
+    let paving = Paving::new(3, PavingConfig { mix, patch_mix: None });
+    assert_mix(mix_fractions(&paving, None), mix);
+
+    let mix = PavingMix { primary: 0, trim: 1, weathered: 1 };
+    let paving = Paving::new(3, PavingConfig { mix, patch_mix: None });
+    assert_mix(mix_fractions(&paving, None), mix);
+  }
+
+  #[test]
+  fn patches_are_paved_with_the_patch_mix() {
+    let config = PavingConfig::default();
+    let paving = Paving::new(5, config);
+    let (in_patches, elsewhere) = (mix_fractions(&paving, Some(true)), mix_fractions(&paving, Some(false)));
+    assert_mix(in_patches, config.patch_mix.unwrap());
+    assert_mix(elsewhere, config.mix);
+
+    // Patches cover some, but not most, of the surface
+    let in_patch = (-100..100)
+      .flat_map(|x| (-100..100).map(move |y| IVec2::new(x, y)))
+      .filter(|&pos| paving.patches.height_at(pos) >= 0)
+      .count();
+    assert!(in_patch > 2000 && in_patch < 20000, "{} of 40000 columns are in patches", in_patch);
+  }
+
+  #[test]
+  fn each_column_is_always_paved_the_same() {
+    let material = blocks::GRAY_CONCRETE;
+    let (paving, again) = (Paving::new(9, PavingConfig::default()), Paving::new(9, PavingConfig::default()));
+    let other = Paving::new(10, PavingConfig::default());
+    let mut differs = false;
+    for x in -50..50 {
+      for y in -50..50 {
+        let pos = IVec2::new(x, y);
+        let block = paving.block_at(pos, &material);
+        assert_eq!(block, paving.block_at(pos, &material), "at {}", pos);
+        assert_eq!(block, again.block_at(pos, &material), "at {}", pos);
+        differs |= block != other.block_at(pos, &material);
+      };
+    };
+
+    assert!(differs, "the paving doesn't depend on its seed");
+  }
+}
@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::props::Direction;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::pillar::Pillar;
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// Only pillars at least this wide are hollowed out, leaving room for a ladder and space to climb it
+const MIN_ACCESS_RADIUS: u32 = 4;
+/// The z value of the surface of the ocean, which the floor inside a pillar of the lowest layer is level with
+const SEA_LEVEL: i32 = 0;
+/// The height of the doorway at the base of a pillar
+const DOORWAY_HEIGHT: i32 = 2;
+
+/// The way into one of the largest pillars, which is hollowed out inside a wall one block thick.
+/// A doorway at its base faces away from the middle of the landmass, and a ladder runs up the inside of the wall
+/// opposite it and on through the landmass to a trapdoor in its surface, directly above the pillar.
+#[derive(Debug, Clone)]
+pub struct PillarAccess {
+  origin: IVec2,
+  radius: u32,
+  door: Direction,
+  /// The z value of the floor inside the pillar, level with the surface that the doorway opens onto
+  base: i32,
+  /// The z value of the lower slab of the landmass, where the ladder leaves the inside of the pillar
+  slab_bottom: i32,
+  /// The z value of the surface of the landmass, where the trapdoor is
+  surface: i32
+}
+
+impl PillarAccess {
+  /// Hollows out the given pillar if it is wide enough, with its doorway facing away from `centroid`.
+  /// The pillar must hold up a landmass whose lower slab and surface are at the given z values.
+  pub fn new(pillar: &Pillar, centroid: IVec2, slab_bottom: i32, surface: i32) -> Option<Self> {
+    if pillar.radius() < MIN_ACCESS_RADIUS { return None };
+    // The pillars of the lowest layer reach down to the bedrock, so they are entered from the surface of the ocean
+    let base = pillar.bottom().max(SEA_LEVEL);
+    if base + DOORWAY_HEIGHT >= slab_bottom { return None };
+    let door = Direction::from_vector(pillar.origin() - centroid);
+    Some(PillarAccess { origin: pillar.origin(), radius: pillar.radius(), door, base, slab_bottom, surface })
+  }
+
+  /// The column the ladder runs up, against the inside of the wall opposite the doorway,
+  /// which is also the column of the opening through the landmass and the trapdoor at its top
+  pub fn ladder_column(&self) -> IVec2 {
+    self.origin - self.door.vector() * (self.radius as i32 - 1)
+  }
+
+  /// The column of the pillar's wall that the doorway passes through
+  fn doorway_column(&self) -> IVec2 {
+    self.origin + self.door.vector() * self.radius as i32
+  }
+
+  /// The space which is carved out of the pillar, and everything else in the layer, apart from the ladder
+  pub fn opening(&self) -> AccessOpening<'_> {
+    AccessOpening { access: self }
+  }
+
+  /// Whether the given column is inside the pillar's wall
+  fn is_inside(&self, pos: IVec2) -> bool {
+    let radius = self.radius as f32 - 0.5;
+    self.origin.as_vec2().distance(pos.as_vec2()) <= radius
+  }
+
+  /// The ladder facing the doorway, from the wall opposite it
+  fn ladder(&self) -> &'static Block {
+    match self.door {
+      Direction::North => &blocks::LADDER_NORTH,
+      Direction::East => &blocks::LADDER_EAST,
+      Direction::South => &blocks::LADDER_SOUTH,
+      Direction::West => &blocks::LADDER_WEST
+    }
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<AccessPart> {
+    let ladder_column = self.ladder_column();
+    let local = pos.xy() - ladder_column;
+    if local == IVec2::ZERO {
+      return match pos.z {
+        z if z == self.surface => Some(AccessPart::Trapdoor),
+        z if (self.base + 1..self.surface).contains(&z) => Some(AccessPart::Ladder),
+        _ => None
+      };
+    };
+
+    // Lines the opening through the landmass, which might otherwise open into a hollow fill or a cave
+    let is_lining = local.abs().max_element() == 1 && (self.slab_bottom..self.surface).contains(&pos.z);
+    is_lining.then(|| AccessPart::Lining)
+  }
+
+  /// The block at a position, with the lining around the opening through the landmass made of the given material
+  pub fn block_material_at<'a>(&self, pos: IVec3, material: &'a Block) -> Option<Cow<'a, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      AccessPart::Ladder => self.ladder(),
+      AccessPart::Trapdoor => &blocks::SPRUCE_TRAPDOOR_TOP_SOUTH,
+      AccessPart::Lining => material
+    }))
+  }
+
+  /// Adds every block this access can place to `out`, with its lining made of the given material
+  pub fn possible_blocks(&self, material: &Block, out: &mut BTreeSet<Block>) {
+    out.extend([self.ladder().clone(), blocks::SPRUCE_TRAPDOOR_TOP_SOUTH, material.clone()]);
+  }
+
+  /// Pairs this access with the material of the pillar it is in
+  pub fn with_material<'a>(&'a self, material: &'a Block) -> MaterialPillarAccess<'a> {
+    MaterialPillarAccess { access: self, material }
+  }
+}
+
+impl Geometry for PillarAccess {
+  fn bounding_box(&self) -> BoundingBox {
+    let radius = IVec2::splat(self.radius as i32);
+    let min = (self.origin - radius).extend(self.base + 1);
+    let max = (self.origin + radius).extend(self.surface);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl Encode for PillarAccess {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.origin);
+    encoder.put_u32(self.radius);
+    encoder.put_u8(self.door.to_u8());
+    encoder.put_i32(self.base);
+    encoder.put_i32(self.slab_bottom);
+    encoder.put_i32(self.surface);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(PillarAccess {
+      origin: decoder.take()?,
+      radius: decoder.take_u32()?,
+      door: Direction::from_u8(decoder.take_u8()?)?,
+      base: decoder.take_i32()?,
+      slab_bottom: decoder.take_i32()?,
+      surface: decoder.take_i32()?
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccessPart {
+  Ladder,
+  Trapdoor,
+  Lining
+}
+
+/// A pillar access along with the material of its pillar, as returned by `PillarAccess::with_material`
+pub struct MaterialPillarAccess<'a> {
+  access: &'a PillarAccess,
+  material: &'a Block
+}
+
+impl Geometry for MaterialPillarAccess<'_> {
+  fn bounding_box(&self) -> BoundingBox {
+    self.access.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.access.block_at(pos)
+  }
+}
+
+impl MaterialGeometry for MaterialPillarAccess<'_> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.access.block_material_at(pos, self.material)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.access.possible_blocks(self.material, out);
+  }
+}
+
+
+
+/// The inside of a hollowed out pillar and its doorway, leaving out the column with the ladder.
+/// Every other feature of the layer is carved away wherever this has a block.
+#[derive(Debug, Clone, Copy)]
+pub struct AccessOpening<'a> {
+  access: &'a PillarAccess
+}
+
+impl Geometry for AccessOpening<'_> {
+  fn bounding_box(&self) -> BoundingBox {
+    let access = self.access;
+    let radius = IVec2::splat(access.radius as i32);
+    let min = (access.origin - radius).extend(access.base + 1);
+    let max = (access.origin + radius).extend(access.slab_bottom - 1);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let access = self.access;
+    let is_doorway = pos.xy() == access.doorway_column() &&
+      (access.base + 1..=access.base + DOORWAY_HEIGHT).contains(&pos.z);
+    let is_inside = pos.xy() != access.ladder_column() && access.is_inside(pos.xy()) &&
+      (access.base + 1..access.slab_bottom).contains(&pos.z);
+    is_doorway || is_inside
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SLAB_BOTTOM: i32 = 60;
+  const SURFACE: i32 = 64;
+
+  /// A pillar of the given radius standing on the lower layer at z=20, holding up a landmass centered on `centroid`
+  fn access(radius: u32, centroid: IVec2) -> (Pillar, Option<PillarAccess>) {
+    let pillar = Pillar::new_bounded(IVec2::new(30, -10), radius, Some(20), Some(SLAB_BOTTOM - 1));
+    let access = PillarAccess::new(&pillar, centroid, SLAB_BOTTOM, SURFACE);
+    (pillar, access)
+  }
+
+  #[test]
+  fn only_wide_enough_pillars_are_hollowed_out() {
+    assert!(access(MIN_ACCESS_RADIUS - 1, IVec2::ZERO).1.is_none());
+    assert!(access(MIN_ACCESS_RADIUS, IVec2::ZERO).1.is_some());
+    assert!(access(MIN_ACCESS_RADIUS + 3, IVec2::ZERO).1.is_some());
+
+    // Pillars too short for a doorway are left alone as well
+    let pillar = Pillar::new_bounded(IVec2::ZERO, 5, Some(SLAB_BOTTOM - 2), Some(SLAB_BOTTOM - 1));
+    assert!(PillarAccess::new(&pillar, IVec2::new(20, 0), SLAB_BOTTOM, SURFACE).is_none());
+  }
+
+  #[test]
+  fn the_ladder_climbs_unbroken_from_the_base_to_the_trapdoor() {
+    for centroid in [[0, -10], [30, -40], [60, -10], [30, 20]].map(IVec2::from) {
+      let (pillar, access) = access(5, centroid);
+      let access = access.unwrap();
+      let ladder = access.ladder_column();
+      assert!(access.is_inside(ladder), "towards {}: the ladder is outside of the pillar", centroid);
+      // The wall behind the ladder holds it up
+      let behind = ladder - access.door.vector();
+      assert!(pillar.contains_column(behind) && !access.is_inside(behind), "towards {}", centroid);
+
+      let material_at = |z: i32| access.block_material_at(ladder.extend(z), &blocks::STONE_BRICKS).map(Cow::into_owned);
+      assert_eq!(material_at(20), None, "towards {}: the ladder starts in the floor", centroid);
+      for z in 21..SURFACE {
+        let material = material_at(z);
+        assert_eq!(material.as_ref(), Some(access.ladder()), "towards {}: the ladder is broken at {}", centroid, z);
+        assert!(!access.opening().block_at(ladder.extend(z)), "towards {}: the ladder is carved at {}", centroid, z);
+      };
+
+      assert_eq!(material_at(SURFACE), Some(blocks::SPRUCE_TRAPDOOR_TOP_SOUTH), "towards {}", centroid);
+      assert_eq!(material_at(SURFACE + 1), None, "towards {}", centroid);
+    };
+  }
+
+  #[test]
+  fn the_trapdoor_sits_in_a_lined_opening_through_the_slab() {
+    let access = access(4, IVec2::ZERO).1.unwrap();
+    let ladder = access.ladder_column();
+    for x in -1..=1 {
+      for y in -1..=1 {
+        let pos = ladder + IVec2::new(x, y);
+        let is_lining = pos != ladder;
+        for z in SLAB_BOTTOM..SURFACE {
+          let material = access.block_material_at(pos.extend(z), &blocks::STONE_BRICKS).map(Cow::into_owned);
+          match is_lining {
+            true => assert_eq!(material, Some(blocks::STONE_BRICKS), "no lining at {}", pos.extend(z)),
+            false => assert_eq!(material.as_ref(), Some(access.ladder()), "no ladder at {}", pos.extend(z))
+          };
+        };
+
+        // The surface itself is left to the landmass, apart from the trapdoor
+        assert_eq!(access.block_at(pos.extend(SURFACE)), !is_lining, "at {}", pos);
+      };
+    };
+  }
+
+  #[test]
+  fn the_doorway_opens_out_of_the_base_away_from_the_landmass() {
+    for centroid in [[0, -10], [30, -40], [60, -10], [30, 20], [-5, 15]].map(IVec2::from) {
+      let (pillar, access) = access(5, centroid);
+      let access = access.unwrap();
+      let door = access.door.vector();
+      assert!(door.dot(pillar.origin() - centroid) > 0, "towards {}: the doorway faces {}", centroid, door);
+
+      let doorway = access.doorway_column();
+      assert!(pillar.contains_column(doorway) && !pillar.contains_column(doorway + door), "towards {}", centroid);
+      // The doorway and the inside of the pillar are open from the floor up to the slab, and no higher
+      let is_open = |pos: IVec2, z: i32| access.opening().block_at(pos.extend(z));
+      assert!(is_open(doorway, 21) && is_open(doorway, 22), "towards {}", centroid);
+      assert!(!is_open(doorway, 20) && !is_open(doorway, 23), "towards {}", centroid);
+      assert!(is_open(pillar.origin(), 21) && is_open(pillar.origin(), SLAB_BOTTOM - 1), "towards {}", centroid);
+      assert!(!is_open(pillar.origin(), SLAB_BOTTOM), "towards {}", centroid);
+      // Only the doorway passes through the wall
+      let wall = pillar.origin() - IVec2::new(door.y, -door.x) * 5;
+      assert!(!is_open(wall, 21), "towards {}: the wall at {} is open", centroid, wall);
+    };
+  }
+}
@@ -0,0 +1,95 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::lamp_post::LampPost;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+pub const MIN_PLAZA_RADIUS: u32 = 8;
+pub const MAX_PLAZA_RADIUS: u32 = 16;
+/// How far inside of the ring the decorative ring is inset
+const RING_INSET: f32 = 2.0;
+
+/// An open, circular region of a landmass where no buildings may be placed,
+/// decorated with a ring set into the landmass' surface and a lantern post at its center
+#[derive(Debug, Clone)]
+pub struct Plaza {
+  center: IVec2,
+  radius: u32,
+  /// The z value of the surface of the landmass
+  level: i32,
+  lamp_post: LampPost
+}
+
+impl Plaza {
+  pub fn new(center: IVec2, radius: u32, level: i32) -> Self {
+    let lamp_post = LampPost::new(center, level);
+    Plaza { center, radius, level, lamp_post }
+  }
+
+  pub fn center(&self) -> IVec2 {
+    self.center
+  }
+
+  pub fn radius(&self) -> u32 {
+    self.radius
+  }
+
+  /// Whether or not the given box (inclusive) overlaps the circle reserved by this plaza
+  pub fn intersects_box(&self, min: IVec2, max: IVec2) -> bool {
+    let nearest = self.center.clamp(min, max);
+    let offset = (nearest - self.center).as_vec2();
+    offset.length() <= self.radius as f32
+  }
+
+  fn ring_at(&self, pos: IVec3) -> bool {
+    let ring_radius = self.radius as f32 - RING_INSET;
+    let distance = (pos.xy() - self.center).as_vec2().length();
+    pos.z == self.level && (distance - ring_radius).abs() < 0.5
+  }
+}
+
+impl Geometry for Plaza {
+  fn bounding_box(&self) -> BoundingBox {
+    let r = self.radius as i32;
+    let min = (self.center - r).extend(self.level);
+    let max = (self.center + r).extend(self.level);
+    BoundingBox::try_new(min, max).unwrap().join(self.lamp_post.bounding_box())
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.ring_at(pos) || self.lamp_post.block_at(pos)
+  }
+}
+
+impl MaterialGeometry for Plaza {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    ret_if_some!(self.ring_at(pos).then(|| Cow::Borrowed(&blocks::WHITE_CONCRETE)));
+    ret_if_some!(self.lamp_post.block_material_at(pos));
+    None
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(blocks::WHITE_CONCRETE);
+    self.lamp_post.possible_blocks(out);
+  }
+}
+
+impl Encode for Plaza {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.center);
+    encoder.put_u32(self.radius);
+    encoder.put_i32(self.level);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let center = decoder.take()?;
+    let radius = decoder.take_u32()?;
+    let level = decoder.take_i32()?;
+    Some(Plaza::new(center, radius, level))
+  }
+}
@@ -0,0 +1,32 @@
+use glam::IVec2;
+
+use super::plaza::Plaza;
+
+
+
+/// The kinds of feature that are worth looking down on from the layer above
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoiKind {
+  Plaza
+}
+
+impl PoiKind {
+  pub fn name(self) -> &'static str {
+    match self {
+      PoiKind::Plaza => "plaza"
+    }
+  }
+}
+
+/// A feature registered by a layer as worth looking at from above, centered on a column of the layer's surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoiRecord {
+  pub kind: PoiKind,
+  pub center: IVec2
+}
+
+impl PoiRecord {
+  pub fn plaza(plaza: &Plaza) -> Self {
+    PoiRecord { kind: PoiKind::Plaza, center: plaza.center() }
+  }
+}
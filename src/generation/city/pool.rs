@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// The size of the water in a pool along its short and long axes
+const POOL_WIDTH: i32 = 5;
+const POOL_LENGTH: i32 = 7;
+
+/// A shallow pool of rainwater recessed a block down into the surface of a landmass,
+/// with rounded corners and a rim of a lighter block around it.
+/// The landmass must be hollowed out above the water for the recess to show, see `Landmass::hollow_out`.
+#[derive(Debug, Clone)]
+pub struct Pool {
+  center: IVec2,
+  /// Whether the pool's long axis runs along y rather than x
+  along_y: bool,
+  /// The z value of the surface of the landmass
+  level: i32
+}
+
+impl Pool {
+  pub fn new(center: IVec2, along_y: bool, level: i32) -> Self {
+    Pool { center, along_y, level }
+  }
+
+  /// The corners of the water (inclusive)
+  fn interior(&self) -> (IVec2, IVec2) {
+    let size = match self.along_y {
+      true => IVec2::new(POOL_WIDTH, POOL_LENGTH),
+      false => IVec2::new(POOL_LENGTH, POOL_WIDTH)
+    };
+
+    let min = self.center - size / 2;
+    (min, min + size - IVec2::ONE)
+  }
+
+  /// Whether the column holds water, which is everywhere in the interior apart from its corners
+  pub fn is_water_column(&self, pos: IVec2) -> bool {
+    let (min, max) = self.interior();
+    let is_inside = pos.cmpge(min).all() && pos.cmple(max).all();
+    let is_corner = (pos.x == min.x || pos.x == max.x) && (pos.y == min.y || pos.y == max.y);
+    is_inside && !is_corner
+  }
+
+  /// Whether the column is part of the rim, surrounding the water on every side, including diagonally
+  fn is_rim_column(&self, pos: IVec2) -> bool {
+    !self.is_water_column(pos) && (-1..=1).any(|x| (-1..=1).any(|y| self.is_water_column(pos + IVec2::new(x, y))))
+  }
+
+  /// Every column holding water, from which the surface of the landmass is removed
+  pub fn water_columns(&self) -> impl Iterator<Item = IVec2> + '_ {
+    let (min, max) = self.interior();
+    (min.x..=max.x)
+      .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+      .filter(|&pos| self.is_water_column(pos))
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<PoolPart> {
+    if self.is_water_column(pos.xy()) {
+      match pos.z - self.level {
+        -1 => Some(PoolPart::Water),
+        -2 => Some(PoolPart::Basin),
+        _ => None
+      }
+    } else if (-1..=0).contains(&(pos.z - self.level)) && self.is_rim_column(pos.xy()) {
+      Some(PoolPart::Basin)
+    } else {
+      None
+    }
+  }
+}
+
+impl Geometry for Pool {
+  fn bounding_box(&self) -> BoundingBox {
+    let (min, max) = self.interior();
+    BoundingBox::try_new((min - IVec2::ONE).extend(self.level - 2), (max + IVec2::ONE).extend(self.level)).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Pool {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      PoolPart::Water => &blocks::WATER,
+      PoolPart::Basin => &blocks::SMOOTH_QUARTZ
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::WATER, blocks::SMOOTH_QUARTZ]);
+  }
+}
+
+impl Encode for Pool {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.center);
+    encoder.put(&self.along_y);
+    encoder.put_i32(self.level);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let center = decoder.take()?;
+    let along_y = decoder.take()?;
+    let level = decoder.take_i32()?;
+    Some(Pool::new(center, along_y, level))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PoolPart {
+  Water,
+  /// The rim around the water and the floor beneath it
+  Basin
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn water_sits_a_block_beneath_the_surface_inside_an_intact_rim() {
+    for along_y in [false, true] {
+      let pool = Pool::new(IVec2::new(10, -4), along_y, 64);
+      let (min, max) = pool.interior();
+      assert_eq!(max - min + IVec2::ONE, if along_y { IVec2::new(5, 7) } else { IVec2::new(7, 5) });
+      let material_at = |pos: IVec2, z: i32| pool.block_material_at(pos.extend(z)).map(Cow::into_owned);
+      for x in min.x - 2..=max.x + 2 {
+        for y in min.y - 2..=max.y + 2 {
+          let pos = IVec2::new(x, y);
+          let inside = pos.cmpge(min).all() && pos.cmple(max).all();
+          let corner = (x == min.x || x == max.x) && (y == min.y || y == max.y);
+          // The rim follows the rounded corners of the water, so it leaves out the corners of the box around it
+          let outer_corner = (x == min.x - 1 || x == max.x + 1) && (y == min.y - 1 || y == max.y + 1);
+          let beside = pos.cmpge(min - IVec2::ONE).all() && pos.cmple(max + IVec2::ONE).all() && !outer_corner;
+          let expected = match (inside && !corner, beside) {
+            // Water one block down, on the floor of the basin, with nothing above it at the surface
+            (true, _) => [None, Some(blocks::WATER), Some(blocks::SMOOTH_QUARTZ)],
+            // The rim runs from the surface down beside the water, filling the corners of the interior
+            (false, true) => [Some(blocks::SMOOTH_QUARTZ), Some(blocks::SMOOTH_QUARTZ), None],
+            (false, false) => [None, None, None]
+          };
+
+          assert_eq!([material_at(pos, 64), material_at(pos, 63), material_at(pos, 62)], expected, "at {}", pos);
+          assert_eq!(material_at(pos, 65), None, "at {}", pos);
+          assert_eq!(pool.water_columns().any(|column| column == pos), inside && !corner, "at {}", pos);
+        };
+      };
+    };
+  }
+}
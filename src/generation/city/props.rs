@@ -0,0 +1,375 @@
+//! Large props placed around the edges of landmasses to break up the skyline.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+const CRANE_MAST_HEIGHT: i32 = 20;
+const CRANE_JIB_LENGTH: i32 = 12;
+const CRANE_COUNTER_JIB_LENGTH: i32 = 3;
+
+const TURBINE_MAST_HEIGHT: i32 = 20;
+/// The offsets of each block of a turbine blade pointing 210° from the right, in the plane of the blades.
+/// The blade pointing 330° is this mirrored horizontally, and the blade pointing 90° is straight up.
+/// These are written out by hand to avoid depending on trigonometry.
+const TURBINE_BLADE_OFFSETS: [IVec2; 7] = [
+  glam::const_ivec2!([-1, 0]),
+  glam::const_ivec2!([-2, -1]),
+  glam::const_ivec2!([-3, -1]),
+  glam::const_ivec2!([-3, -2]),
+  glam::const_ivec2!([-4, -2]),
+  glam::const_ivec2!([-5, -3]),
+  glam::const_ivec2!([-6, -3])
+];
+const TURBINE_BLADE_LENGTH: i32 = TURBINE_BLADE_OFFSETS.len() as i32;
+
+/// The furthest that any prop reaches horizontally from its origin
+pub const MAX_PROP_REACH: i32 = CRANE_JIB_LENGTH;
+/// The furthest that any prop reaches above its base
+pub const MAX_PROP_HEIGHT: i32 = TURBINE_MAST_HEIGHT + 1 + TURBINE_BLADE_LENGTH;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  North,
+  East,
+  South,
+  West
+}
+
+impl Direction {
+  pub const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+  /// The direction closest to pointing along the given vector
+  pub fn from_vector(vector: IVec2) -> Self {
+    match vector.x.abs() >= vector.y.abs() {
+      true if vector.x >= 0 => Direction::East,
+      true => Direction::West,
+      false if vector.y >= 0 => Direction::South,
+      false => Direction::North
+    }
+  }
+
+  /// The unit vector pointing in this direction, where north is -Y
+  pub fn vector(self) -> IVec2 {
+    match self {
+      Direction::North => IVec2::new(0, -1),
+      Direction::East => IVec2::new(1, 0),
+      Direction::South => IVec2::new(0, 1),
+      Direction::West => IVec2::new(-1, 0)
+    }
+  }
+
+  /// The unit vector pointing 90° clockwise from this direction, when viewed from above
+  fn right(self) -> IVec2 {
+    let vector = self.vector();
+    IVec2::new(-vector.y, vector.x)
+  }
+
+  pub(super) fn to_u8(self) -> u8 {
+    self as u8
+  }
+
+  pub(super) fn from_u8(value: u8) -> Option<Self> {
+    Direction::ALL.get(value as usize).copied()
+  }
+}
+
+
+
+#[derive(Debug, Clone)]
+pub enum Prop {
+  Crane(Crane),
+  Turbine(Turbine)
+}
+
+impl Geometry for Prop {
+  fn bounding_box(&self) -> BoundingBox {
+    match self {
+      Prop::Crane(crane) => crane.bounding_box(),
+      Prop::Turbine(turbine) => turbine.bounding_box()
+    }
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    match self {
+      Prop::Crane(crane) => crane.block_at(pos),
+      Prop::Turbine(turbine) => turbine.block_at(pos)
+    }
+  }
+}
+
+impl MaterialGeometry for Prop {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    match self {
+      Prop::Crane(crane) => crane.block_material_at(pos),
+      Prop::Turbine(turbine) => turbine.block_material_at(pos)
+    }
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    match self {
+      Prop::Crane(crane) => crane.possible_blocks(out),
+      Prop::Turbine(turbine) => turbine.possible_blocks(out)
+    }
+  }
+}
+
+impl Encode for Prop {
+  fn encode(&self, encoder: &mut Encoder) {
+    let (tag, origin, direction, base) = match self {
+      Prop::Crane(Crane { origin, direction, base }) => (0, origin, direction, base),
+      Prop::Turbine(Turbine { origin, direction, base }) => (1, origin, direction, base)
+    };
+
+    encoder.put_u8(tag);
+    encoder.put(origin);
+    encoder.put_u8(direction.to_u8());
+    encoder.put_i32(*base);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let tag = decoder.take_u8()?;
+    let origin = decoder.take()?;
+    let direction = Direction::from_u8(decoder.take_u8()?)?;
+    let base = decoder.take_i32()?;
+    match tag {
+      0 => Some(Prop::Crane(Crane::new(origin, direction, base))),
+      1 => Some(Prop::Turbine(Turbine::new(origin, direction, base))),
+      _ => None
+    }
+  }
+}
+
+
+
+/// A mast with a long jib pointing in its direction, and a short counter-jib with a counterweight pointing backwards
+#[derive(Debug, Clone)]
+pub struct Crane {
+  origin: IVec2,
+  direction: Direction,
+  /// The z value of the surface the crane stands on
+  base: i32
+}
+
+impl Crane {
+  pub fn new(origin: IVec2, direction: Direction, base: i32) -> Self {
+    Crane { origin, direction, base }
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<CranePart> {
+    let z = pos.z - self.base;
+    let local = pos.xy() - self.origin;
+    // The distance along the jib, and the distance away from it
+    let along = local.dot(self.direction.vector());
+    let across = local.dot(self.direction.right());
+    if across != 0 { return None };
+
+    match (along, z) {
+      (0, 1..=CRANE_MAST_HEIGHT) => Some(CranePart::Frame),
+      (1..=CRANE_JIB_LENGTH, CRANE_MAST_HEIGHT) => Some(CranePart::Frame),
+      (along, CRANE_MAST_HEIGHT) if along == -CRANE_COUNTER_JIB_LENGTH => Some(CranePart::Counterweight),
+      (along, CRANE_MAST_HEIGHT) if along < 0 && along > -CRANE_COUNTER_JIB_LENGTH => Some(CranePart::Frame),
+      _ => None
+    }
+  }
+}
+
+impl Geometry for Crane {
+  fn bounding_box(&self) -> BoundingBox {
+    let vector = self.direction.vector();
+    let min = (self.origin + vector * CRANE_JIB_LENGTH).extend(self.base + 1);
+    let max = (self.origin - vector * CRANE_COUNTER_JIB_LENGTH).extend(self.base + CRANE_MAST_HEIGHT);
+    BoundingBox::new(min, max)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Crane {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      CranePart::Frame => &blocks::IRON_BARS,
+      CranePart::Counterweight => &blocks::IRON_BLOCK
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::IRON_BARS, blocks::IRON_BLOCK]);
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CranePart {
+  Frame,
+  Counterweight
+}
+
+
+
+/// A mast with a hub on its front, with three blades spaced 120° apart
+#[derive(Debug, Clone)]
+pub struct Turbine {
+  origin: IVec2,
+  /// The direction that the hub faces
+  direction: Direction,
+  /// The z value of the surface the turbine stands on
+  base: i32
+}
+
+impl Turbine {
+  pub fn new(origin: IVec2, direction: Direction, base: i32) -> Self {
+    Turbine { origin, direction, base }
+  }
+
+  fn hub_z(&self) -> i32 {
+    self.base + TURBINE_MAST_HEIGHT + 1
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<TurbinePart> {
+    let local = pos.xy() - self.origin;
+    let along = local.dot(self.direction.vector());
+    let across = local.dot(self.direction.right());
+    let z = pos.z - self.hub_z();
+
+    match along {
+      0 if across == 0 && (self.base + 1..=self.base + TURBINE_MAST_HEIGHT).contains(&pos.z) => Some(TurbinePart::Mast),
+      1 => {
+        let offset = IVec2::new(across, z);
+        if offset == IVec2::ZERO {
+          Some(TurbinePart::Hub)
+        } else if (offset.x == 0 && (1..=TURBINE_BLADE_LENGTH).contains(&offset.y)) ||
+          TURBINE_BLADE_OFFSETS.contains(&offset) ||
+          TURBINE_BLADE_OFFSETS.contains(&IVec2::new(-offset.x, offset.y)) {
+          Some(TurbinePart::Blade)
+        } else {
+          None
+        }
+      },
+      _ => None
+    }
+  }
+}
+
+impl Geometry for Turbine {
+  fn bounding_box(&self) -> BoundingBox {
+    let hub = self.origin + self.direction.vector();
+    let right = self.direction.right() * TURBINE_BLADE_LENGTH;
+    let min = (self.origin + right).extend(self.base + 1);
+    let max = (hub - right).extend(self.hub_z() + TURBINE_BLADE_LENGTH);
+    BoundingBox::new(min, max)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Turbine {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      TurbinePart::Mast => &blocks::SMOOTH_QUARTZ,
+      TurbinePart::Hub => &blocks::IRON_BLOCK,
+      TurbinePart::Blade => &blocks::WHITE_CONCRETE
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::SMOOTH_QUARTZ, blocks::IRON_BLOCK, blocks::WHITE_CONCRETE]);
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TurbinePart {
+  Mast,
+  Hub,
+  Blade
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+  use crate::generation::fixtures::positions_in;
+
+  const ORIGIN: IVec2 = glam::const_ivec2!([5, -3]);
+  const BASE: i32 = 64;
+
+  /// Turns a position relative to a prop, given as the distance along its direction, the distance to its right and the
+  /// height above its base, into a world position
+  fn place(direction: Direction, along: i32, across: i32, z: i32) -> IVec3 {
+    let offset = match direction {
+      Direction::North => IVec2::new(across, -along),
+      Direction::East => IVec2::new(along, across),
+      Direction::South => IVec2::new(-across, along),
+      Direction::West => IVec2::new(-along, -across)
+    };
+
+    (ORIGIN + offset).extend(BASE + z)
+  }
+
+  /// Every block of the given geometry, along with its material, found within reach of the origin
+  fn sampled_blocks<G: MaterialGeometry>(geometry: &G) -> HashSet<(IVec3, Block)> {
+    let reach = IVec3::new(MAX_PROP_REACH + 2, MAX_PROP_REACH + 2, 0);
+    let min = ORIGIN.extend(BASE - 2) - reach;
+    let max = ORIGIN.extend(BASE + MAX_PROP_HEIGHT + 2) + reach;
+    positions_in(BoundingBox::new(min, max))
+      .filter_map(|pos| geometry.block_material_at(pos).map(|block| (pos, block.into_owned())))
+      .collect()
+  }
+
+  #[test]
+  fn cranes_match_their_expected_blocks_in_every_direction() {
+    for direction in Direction::ALL {
+      let crane = Crane::new(ORIGIN, direction, BASE);
+      let mut expected = HashSet::new();
+      expected.extend((1..=20).map(|z| (place(direction, 0, 0, z), blocks::IRON_BARS)));
+      expected.extend((1..=12).map(|along| (place(direction, along, 0, 20), blocks::IRON_BARS)));
+      expected.extend((-2..=-1).map(|along| (place(direction, along, 0, 20), blocks::IRON_BARS)));
+      expected.insert((place(direction, -3, 0, 20), blocks::IRON_BLOCK));
+
+      let blocks = sampled_blocks(&crane);
+      assert_eq!(blocks, expected, "facing {:?}", direction);
+      assert!(blocks.iter().all(|&(pos, _)| crane.bounding_box().contains(pos)), "facing {:?}", direction);
+    };
+  }
+
+  #[test]
+  fn turbines_match_their_expected_blocks_in_every_direction() {
+    // The lower left blade, relative to the hub as seen from the front, and the lower right blade mirrors it
+    let lower_blade = [(-1, 0), (-2, -1), (-3, -1), (-3, -2), (-4, -2), (-5, -3), (-6, -3)];
+    for direction in Direction::ALL {
+      let turbine = Turbine::new(ORIGIN, direction, BASE);
+      let mut expected = HashSet::new();
+      expected.extend((1..=20).map(|z| (place(direction, 0, 0, z), blocks::SMOOTH_QUARTZ)));
+      expected.insert((place(direction, 1, 0, 21), blocks::IRON_BLOCK));
+      expected.extend((1..=7).map(|z| (place(direction, 1, 0, 21 + z), blocks::WHITE_CONCRETE)));
+      for (across, z) in lower_blade {
+        expected.insert((place(direction, 1, across, 21 + z), blocks::WHITE_CONCRETE));
+        expected.insert((place(direction, 1, -across, 21 + z), blocks::WHITE_CONCRETE));
+      };
+
+      let blocks = sampled_blocks(&turbine);
+      assert_eq!(blocks, expected, "facing {:?}", direction);
+      assert!(blocks.iter().all(|&(pos, _)| turbine.bounding_box().contains(pos)), "facing {:?}", direction);
+    };
+  }
+
+  #[test]
+  fn directions_round_trip_through_their_encoding() {
+    for direction in Direction::ALL {
+      assert_eq!(Direction::from_u8(direction.to_u8()), Some(direction));
+      assert_eq!(Direction::from_vector(direction.vector() * 5), direction);
+    };
+
+    assert_eq!(Direction::from_u8(4), None);
+  }
+}
@@ -0,0 +1,264 @@
+//! Small decorations scattered across the walkable surface of a landmass, like heaps of rubble, barrels and lamps.
+//!
+//! Decorations are placed one chunk at a time, only once a block in that chunk is asked for. Each chunk hashes its
+//! position with the scatter's seed to draw a few candidate positions and a decoration for each, keeping those whose
+//! whole footprint lies on open columns and clear of the decorations kept before them. Footprints never cross the edge
+//! of a chunk, so each block only depends on the chunk containing it.
+use std::borrow::Cow;
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::props::Direction;
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{hash2, weighted_pick};
+
+
+
+/// The number of positions tried in each chunk, of which only those on open columns are kept
+pub const SCATTER_DENSITY: u32 = 3;
+/// The number of independently locked caches that a `Scatter` spreads its chunks across
+const SHARD_COUNT: usize = 8;
+/// The number of chunks each shard remembers before forgetting the one used least recently
+const SHARD_CAPACITY: usize = 8;
+
+/// The decorations placed in a single chunk
+type Shard = Mutex<VecDeque<(IVec2, Arc<[Placement]>)>>;
+
+/// Decorations scattered over the open columns of a landmass whose upper slab is at `level`
+#[derive(Clone)]
+pub struct Scatter {
+  seed: u64,
+  level: i32,
+  /// The number of positions tried in each chunk
+  density: u32,
+  table: DecorationTable,
+  /// The columns that decorations may stand on
+  open: Arc<SparseGrid<()>>,
+  /// The chunks placed most recently, shared between clones
+  shards: Arc<[Shard]>
+}
+
+impl Scatter {
+  /// Scatters decorations over the given columns of a landmass' surface, leaving out every column where `is_excluded`
+  /// is true. Nothing is placed until it is asked for.
+  pub fn new(
+    seed: u64, level: i32, columns: impl IntoIterator<Item = IVec2>, density: u32, table: DecorationTable,
+    is_excluded: impl Fn(IVec2) -> bool
+  ) -> Self {
+    let open = columns.into_iter()
+      .filter(|&pos| !is_excluded(pos))
+      .map(|pos| (pos, ()))
+      .collect::<SparseGrid<()>>();
+    let shards = (0..SHARD_COUNT).map(|_| Mutex::default()).collect();
+    Scatter { seed, level, density, table, open: Arc::new(open), shards }
+  }
+
+  /// The decorations placed in the given chunk, placing them if they aren't cached
+  fn placements_in(&self, chunk: IVec2) -> Arc<[Placement]> {
+    let shard = &self.shards[hash2(0, chunk) as usize % SHARD_COUNT];
+    {
+      let mut shard = shard.lock().unwrap();
+      if let Some(i) = shard.iter().position(|&(cached, _)| cached == chunk) {
+        let entry = shard.remove(i).unwrap();
+        let placements = entry.1.clone();
+        shard.push_front(entry);
+        return placements;
+      };
+    }
+
+    // The lock is released while placing, so another thread may place the same chunk in the meantime,
+    // which is harmless since both will have placed the same decorations
+    let placements = Arc::<[Placement]>::from(self.place(chunk));
+    let mut shard = shard.lock().unwrap();
+    if !shard.iter().any(|&(cached, _)| cached == chunk) {
+      shard.push_front((chunk, placements.clone()));
+      shard.truncate(SHARD_CAPACITY);
+    };
+
+    placements
+  }
+
+  /// Draws the decorations of a chunk from its own rng, keeping those which stand on open columns without overlapping
+  fn place(&self, chunk: IVec2) -> Vec<Placement> {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(hash2(self.seed, chunk));
+    let mut placements = Vec::<Placement>::new();
+    for _ in 0..self.density {
+      // Always drawn in full, so that later positions are unaffected by whether this one is kept
+      let decoration = self.table.pick(&mut rng);
+      let direction = Direction::ALL[rng.gen_range(0..4)];
+      let size = decoration.footprint();
+      let offset = IVec2::new(rng.gen_range(0..=16 - size.x), rng.gen_range(0..=16 - size.y));
+      let placement = Placement { origin: chunk * 16 + offset, decoration, direction };
+      let is_open = placement.columns().all(|pos| self.open.get(pos).is_some());
+      let is_clear = !placements.iter().any(|other| other.overlaps(&placement));
+      if is_open && is_clear {
+        placements.push(placement);
+      };
+    };
+
+    placements
+  }
+}
+
+impl fmt::Debug for Scatter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Scatter")
+      .field("seed", &self.seed)
+      .field("level", &self.level)
+      .field("density", &self.density)
+      .field("table", &self.table)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Geometry for Scatter {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.open.min().unwrap_or(IVec2::ZERO).extend(self.level + 1);
+    let max = self.open.max().unwrap_or(IVec2::ZERO).extend(self.level + Decoration::MAX_HEIGHT);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.block_material_at(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Scatter {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let height = pos.z - self.level;
+    if !(1..=Decoration::MAX_HEIGHT).contains(&height) || self.open.get(pos.xy()).is_none() { return None };
+    let chunk = IVec2::new(pos.x.div_euclid(16), pos.y.div_euclid(16));
+    self.placements_in(chunk).iter()
+      .find_map(|placement| placement.block_at(pos.xy(), height))
+      .map(Cow::Borrowed)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    for &(decoration, _) in self.table.entries.iter() {
+      decoration.possible_blocks(out);
+    };
+  }
+}
+
+/// The decorations that a scatter draws from, each with its weight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecorationTable {
+  entries: Vec<(Decoration, u32)>
+}
+
+impl DecorationTable {
+  /// A table drawing the given decorations in proportion to their weights, which must not all be zero
+  pub fn new(entries: Vec<(Decoration, u32)>) -> Self {
+    assert!(entries.iter().any(|&(_, weight)| weight > 0), "decoration table has no weight");
+    DecorationTable { entries }
+  }
+
+  fn pick<R: Rng>(&self, rng: &mut R) -> Decoration {
+    // Rolled within the total weight, so that the same decorations are drawn as before picks were shared
+    let total = self.entries.iter().map(|&(_, weight)| weight).sum::<u32>();
+    *weighted_pick(rng.gen_range(0..total) as u64, &self.entries).unwrap()
+  }
+}
+
+impl Default for DecorationTable {
+  fn default() -> Self {
+    DecorationTable::new(vec![(Decoration::Rubble, 2), (Decoration::Crate, 3), (Decoration::Lamp, 1)])
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoration {
+  /// A 2×2 heap of cobbled deepslate, with a slab at the corner it is lowest towards
+  Rubble,
+  /// A single barrel lying on its side
+  Crate,
+  /// A lantern on a fence post
+  Lamp
+}
+
+impl Decoration {
+  /// The tallest that any decoration stands above the surface
+  const MAX_HEIGHT: i32 = 2;
+
+  fn footprint(self) -> IVec2 {
+    match self {
+      Decoration::Rubble => IVec2::new(2, 2),
+      Decoration::Crate | Decoration::Lamp => IVec2::ONE
+    }
+  }
+
+  /// The block at an offset from the decoration's corner, at a height above the surface,
+  /// where the decoration has been turned to face the given direction
+  fn block_at(self, offset: IVec2, height: i32, direction: Direction) -> Option<&'static Block> {
+    match (self, height) {
+      (Decoration::Rubble, 1) if offset == rubble_low_corner(direction) => Some(&blocks::COBBLED_DEEPSLATE_SLAB_BOTTOM),
+      (Decoration::Rubble, 1) => Some(&blocks::COBBLED_DEEPSLATE),
+      (Decoration::Crate, 1) => Some(barrel_facing(direction)),
+      (Decoration::Lamp, 1) => Some(&blocks::DARK_OAK_FENCE),
+      (Decoration::Lamp, 2) => Some(&blocks::LANTERN),
+      _ => None
+    }
+  }
+
+  fn possible_blocks(self, out: &mut BTreeSet<Block>) {
+    match self {
+      Decoration::Rubble => out.extend([blocks::COBBLED_DEEPSLATE, blocks::COBBLED_DEEPSLATE_SLAB_BOTTOM]),
+      Decoration::Crate => out.extend(Direction::ALL.map(|direction| barrel_facing(direction).clone())),
+      Decoration::Lamp => out.extend([blocks::DARK_OAK_FENCE, blocks::LANTERN])
+    };
+  }
+}
+
+/// A decoration placed with its corner at `origin`, extending towards +X and +Y
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Placement {
+  origin: IVec2,
+  decoration: Decoration,
+  direction: Direction
+}
+
+impl Placement {
+  fn max(self) -> IVec2 {
+    self.origin + self.decoration.footprint() - IVec2::ONE
+  }
+
+  fn columns(self) -> impl Iterator<Item = IVec2> {
+    let (min, max) = (self.origin, self.max());
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+  }
+
+  fn overlaps(&self, other: &Placement) -> bool {
+    self.origin.cmple(other.max()).all() && other.origin.cmple(self.max()).all()
+  }
+
+  fn block_at(&self, pos: IVec2, height: i32) -> Option<&'static Block> {
+    let offset = pos - self.origin;
+    let is_inside = offset.cmpge(IVec2::ZERO).all() && offset.cmplt(self.decoration.footprint()).all();
+    is_inside.then(|| self.decoration.block_at(offset, height, self.direction)).flatten()
+  }
+}
+
+/// The corner of a heap of rubble that it slopes down towards when facing the given direction
+fn rubble_low_corner(direction: Direction) -> IVec2 {
+  match direction {
+    Direction::North => IVec2::new(0, 0),
+    Direction::East => IVec2::new(1, 0),
+    Direction::South => IVec2::new(1, 1),
+    Direction::West => IVec2::new(0, 1)
+  }
+}
+
+fn barrel_facing(direction: Direction) -> &'static Block {
+  match direction {
+    Direction::North => &blocks::BARREL_NORTH,
+    Direction::East => &blocks::BARREL_EAST,
+    Direction::South => &blocks::BARREL_SOUTH,
+    Direction::West => &blocks::BARREL_WEST
+  }
+}
@@ -0,0 +1,225 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use rand::Rng;
+
+use super::layer::Layer;
+use crate::generation::enclosure::Enclosure;
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// The z value of the dock at the foot of each shaft, level with the surface of the ocean
+const DOCK_LEVEL: i32 = 0;
+/// The z value of the surface of the ocean, beneath which the inside of the shaft must be kept dry
+const SEA_LEVEL: i32 = 0;
+/// How far the dock and platforms reach out from the center of a shaft
+const PLATFORM_RADIUS: i32 = 2;
+/// The most shafts that a city can have
+const MAX_SHAFTS: usize = 2;
+/// The least distance between the centers of two shafts along either axis
+const MIN_SHAFT_SPACING: i32 = 48;
+/// Only every few columns are considered as the center of a shaft, to keep finding sites cheap
+const SITE_STEP: i32 = 3;
+/// The vertical distance between the rings of lights in the corners of a lit shaft's walls
+const LIGHT_SPACING: i32 = 8;
+
+/// How a shaft passing through a range of columns would sit in a layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaftFit {
+  /// The columns are all on the layer's landmass, with nothing in the way
+  Inside,
+  /// The columns are all clear of the layer's landmass and everything else in it
+  Outside,
+  /// The columns are partly on the landmass, or something else in the layer is in the way
+  Blocked
+}
+
+/// A walled ladder running from a dock on the water up through every layer of the city to the surface of the top one.
+/// The shaft is three blocks wide, with the ladder in the middle,
+/// and has a doorway onto the surface of every layer below the top.
+#[derive(Debug, Clone)]
+pub struct Shaft {
+  /// The column containing the ladder
+  center: IVec2,
+  /// The z value of the top of the ladder, level with the surface of the top layer
+  top: i32,
+  /// The surfaces of the lower layers that the shaft passes outside of, each of which gets a platform around the shaft
+  platforms: Vec<i32>,
+  openings: ShaftOpenings,
+  /// Whether the corners of the walls have lights set into them at regular heights
+  lit: bool
+}
+
+impl Shaft {
+  /// Picks one or two sites within the landmass of every layer, which are clear of buildings and other features,
+  /// and places a shaft on each of them. If no site is within every landmass, sites within the top landmass which pass
+  /// cleanly outside of the lower ones are used instead, with platforms hosting the shaft at those layers.
+  pub fn connecting<R: Rng>(rng: &mut R, layers: &[&Layer]) -> Vec<Self> {
+    let top_layer = match layers.last() {
+      Some(layer) => layer,
+      None => return Vec::new()
+    };
+
+    let mut candidates = top_layer.landmass_columns()
+      .filter(|pos| pos.x.rem_euclid(SITE_STEP) == 0 && pos.y.rem_euclid(SITE_STEP) == 0)
+      .filter_map(|center| {
+        let min = center - PLATFORM_RADIUS;
+        let max = center + PLATFORM_RADIUS;
+        let fits = layers.iter().map(|layer| layer.shaft_fit(min, max)).collect::<Vec<ShaftFit>>();
+        (fits.last() == Some(&ShaftFit::Inside) && !fits.contains(&ShaftFit::Blocked)).then(|| (center, fits))
+      })
+      .collect::<Vec<(IVec2, Vec<ShaftFit>)>>();
+    // Landmass cells are not visited in any particular order
+    candidates.sort_unstable_by_key(|&(center, _)| (center.x, center.y));
+
+    let inside_every_layer = candidates.iter()
+      .filter(|(_, fits)| fits.iter().all(|&fit| fit == ShaftFit::Inside))
+      .cloned()
+      .collect::<Vec<(IVec2, Vec<ShaftFit>)>>();
+    if !inside_every_layer.is_empty() {
+      candidates = inside_every_layer;
+    };
+
+    let count = rng.gen_range(1..=MAX_SHAFTS);
+    let mut shafts: Vec<Shaft> = Vec::with_capacity(count);
+    while shafts.len() < count {
+      let available = candidates.iter()
+        .filter(|(center, _)| {
+          shafts.iter().all(|shaft| (shaft.center - *center).abs().max_element() >= MIN_SHAFT_SPACING)
+        })
+        .collect::<Vec<&(IVec2, Vec<ShaftFit>)>>();
+      if available.is_empty() { break };
+
+      let (center, fits) = available[rng.gen_range(0..available.len())];
+      shafts.push(Shaft::new(*center, layers, fits));
+    };
+
+    if shafts.is_empty() {
+      log::warn!("found nowhere to place a shaft connecting the layers of the city");
+    };
+
+    shafts
+  }
+
+  /// Places a shaft at the given center, which must be within the landmass of the top layer
+  fn new(center: IVec2, layers: &[&Layer], fits: &[ShaftFit]) -> Self {
+    let levels = layers.iter().zip(fits)
+      .filter_map(|(layer, &fit)| Some((layer.level()?, fit)))
+      .collect::<Vec<(i32, ShaftFit)>>();
+    let (&(top, _), lower) = levels.split_last().expect("the top layer has a landmass");
+    let platforms = lower.iter()
+      .filter(|&&(_, fit)| fit == ShaftFit::Outside)
+      .map(|&(level, _)| level)
+      .collect();
+    let floors = std::iter::once(DOCK_LEVEL).chain(lower.iter().map(|&(level, _)| level)).collect();
+    let openings = ShaftOpenings { center, floors, top };
+    Shaft { center, top, platforms, openings, lit: false }
+  }
+
+  /// Sets lights into the corners of the walls at regular heights up the whole shaft
+  pub fn light(&mut self) {
+    self.lit = true;
+  }
+
+  /// The space that is kept clear around the shaft's doorways and above its top, which nothing else may fill
+  pub fn openings(&self) -> &ShaftOpenings {
+    &self.openings
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<ShaftPart> {
+    let local = pos.xy() - self.center;
+    let distance = local.abs().max_element();
+    if pos.z == DOCK_LEVEL {
+      return (distance <= PLATFORM_RADIUS).then(|| ShaftPart::Dock);
+    };
+
+    if !(DOCK_LEVEL + 1..=self.top).contains(&pos.z) { return None };
+    match distance {
+      0 => Some(ShaftPart::Ladder),
+      // The doorways are in the middle of the south wall, so they never take the place of a light
+      1 if self.lit && local.abs() == IVec2::ONE && (pos.z - DOCK_LEVEL) % LIGHT_SPACING == 0 => Some(ShaftPart::Light),
+      1 => (!self.openings.block_at(pos)).then(|| ShaftPart::Wall),
+      PLATFORM_RADIUS if self.platforms.contains(&pos.z) => Some(ShaftPart::Platform),
+      _ => None
+    }
+  }
+}
+
+impl Geometry for Shaft {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = (self.center - PLATFORM_RADIUS).extend(DOCK_LEVEL);
+    let max = (self.center + PLATFORM_RADIUS).extend(self.top);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl Enclosure for Shaft {
+  /// The part of the well inside the walls which is at or below the surface of the ocean.
+  /// The ladder only starts above the dock, which sits at the surface, so for now this is always empty.
+  fn dry_volumes(&self) -> Vec<BoundingBox> {
+    BoundingBox::try_new(self.center.extend(DOCK_LEVEL + 1), self.center.extend(SEA_LEVEL)).into_iter().collect()
+  }
+}
+
+impl MaterialGeometry for Shaft {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      ShaftPart::Ladder => &blocks::LADDER_SOUTH,
+      ShaftPart::Wall => &blocks::STONE_BRICKS,
+      ShaftPart::Dock => &blocks::SPRUCE_PLANKS,
+      ShaftPart::Platform => &blocks::POLISHED_ANDESITE,
+      ShaftPart::Light => &blocks::SEA_LANTERN
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::LADDER_SOUTH, blocks::STONE_BRICKS, blocks::SPRUCE_PLANKS, blocks::POLISHED_ANDESITE]);
+    if self.lit {
+      out.insert(blocks::SEA_LANTERN);
+    };
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShaftPart {
+  /// The ladder is on the north wall, so that the doorways can be in the south wall
+  Ladder,
+  Wall,
+  Dock,
+  Platform,
+  Light
+}
+
+
+
+/// The doorways of a shaft and the columns in front of them, along with the space above the top of its ladder.
+/// Every other feature of the city is carved away wherever this has a block.
+#[derive(Debug, Clone)]
+pub struct ShaftOpenings {
+  center: IVec2,
+  /// The z values of the floors that the doorways open onto, being the dock and the surfaces of the lower layers
+  floors: Vec<i32>,
+  top: i32
+}
+
+impl Geometry for ShaftOpenings {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.center.extend(DOCK_LEVEL + 1);
+    let max = (self.center + IVec2::new(0, PLATFORM_RADIUS)).extend(self.top + 2);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let local = pos.xy() - self.center;
+    let is_above_top = local == IVec2::ZERO && (self.top + 1..=self.top + 2).contains(&pos.z);
+    let is_doorway = local.x == 0 && (1..=PLATFORM_RADIUS).contains(&local.y) &&
+      self.floors.iter().any(|&floor| (floor + 1..=floor + 2).contains(&pos.z));
+    is_above_top || is_doorway
+  }
+}
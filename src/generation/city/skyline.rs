@@ -0,0 +1,175 @@
+use rand::Rng;
+
+
+
+/// How the heights of buildings are spread, in building shape units.
+/// Heights are multiplied by the height multiplier of each building's district after they are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeightDistribution {
+  /// Every height between `min` and `max` inclusive is equally likely
+  Uniform { min: u32, max: u32 },
+  /// Heights fall off exponentially from `min` at a rate of `lambda`, cut off at `max`,
+  /// giving many short buildings and a few tall ones
+  Exponential { min: u32, max: u32, lambda: f64 },
+  /// Mostly low-rise buildings with heights in `low`, and towers with heights in `high` at a chance of `high_chance`.
+  /// Both ranges are inclusive.
+  Bimodal { low: (u32, u32), high: (u32, u32), high_chance: f64 }
+}
+
+impl HeightDistribution {
+  pub fn sample<R: Rng>(&self, rng: &mut R) -> u32 {
+    match *self {
+      HeightDistribution::Uniform { min, max } => rng.gen_range(min..=max),
+      HeightDistribution::Exponential { min, max, lambda } => {
+        // Inverts the CDF of the exponential distribution truncated to the span from `min` up to just past `max`,
+        // so that each whole height takes up the unit interval above it
+        let span = (max - min + 1) as f64;
+        let u = rng.gen::<f64>();
+        let offset = -(1.0 - u * (1.0 - (-lambda * span).exp())).ln() / lambda;
+        min + (offset.floor() as u32).min(max - min)
+      },
+      HeightDistribution::Bimodal { low, high, high_chance } => {
+        let (min, max) = if rng.gen_bool(high_chance) { high } else { low };
+        rng.gen_range(min..=max)
+      }
+    }
+  }
+
+  /// The greatest height this distribution can draw
+  pub fn max(&self) -> u32 {
+    match *self {
+      HeightDistribution::Uniform { max, .. } => max,
+      HeightDistribution::Exponential { max, .. } => max,
+      HeightDistribution::Bimodal { low, high, .. } => low.1.max(high.1)
+    }
+  }
+}
+
+/// A named building height distribution, shared by every layer of the city
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Skyline {
+  /// Every height is equally likely, as buildings have always been generated
+  #[default]
+  Uniform,
+  /// Many short buildings and a few taller than any of the uniform skyline's
+  Organic,
+  /// Low-rise buildings broken up by distinct towers
+  Towers
+}
+
+impl Skyline {
+  pub fn distribution(self) -> HeightDistribution {
+    match self {
+      Skyline::Uniform => HeightDistribution::Uniform { min: 2, max: 7 },
+      Skyline::Organic => HeightDistribution::Exponential { min: 2, max: 9, lambda: 0.45 },
+      Skyline::Towers => HeightDistribution::Bimodal { low: (2, 4), high: (8, 11), high_chance: 0.12 }
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Skyline::Uniform => "uniform",
+      Skyline::Organic => "organic",
+      Skyline::Towers => "towers"
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+  use rand_xoshiro::Xoshiro256PlusPlus;
+
+  use super::*;
+
+  const DRAWS: u32 = 10_000;
+
+  fn distributions() -> [HeightDistribution; 5] {
+    [
+      Skyline::Uniform.distribution(),
+      Skyline::Organic.distribution(),
+      Skyline::Towers.distribution(),
+      HeightDistribution::Exponential { min: 1, max: 30, lambda: 0.1 },
+      HeightDistribution::Bimodal { low: (3, 3), high: (10, 20), high_chance: 0.5 }
+    ]
+  }
+
+  fn uniform_mean(min: u32, max: u32) -> f64 {
+    (min + max) as f64 / 2.0
+  }
+
+  /// The mean of the distribution, worked out from the chance of each height rather than by drawing them
+  fn analytic_mean(distribution: HeightDistribution) -> f64 {
+    match distribution {
+      HeightDistribution::Uniform { min, max } => uniform_mean(min, max),
+      HeightDistribution::Exponential { min, max, lambda } => {
+        let span = (max - min + 1) as f64;
+        let total = 1.0 - (-lambda * span).exp();
+        let offset = (0..=max - min)
+          .map(|k| k as f64 * ((-lambda * k as f64).exp() - (-lambda * (k + 1) as f64).exp()) / total)
+          .sum::<f64>();
+        min as f64 + offset
+      },
+      HeightDistribution::Bimodal { low, high, high_chance } => {
+        (1.0 - high_chance) * uniform_mean(low.0, low.1) + high_chance * uniform_mean(high.0, high.1)
+      }
+    }
+  }
+
+  #[test]
+  fn draws_average_out_to_the_analytic_mean() {
+    for distribution in distributions() {
+      let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+      let heights = (0..DRAWS).map(|_| distribution.sample(&mut rng) as f64).collect::<Vec<f64>>();
+      let mean = heights.iter().sum::<f64>() / DRAWS as f64;
+      let variance = heights.iter().map(|height| (height - mean).powi(2)).sum::<f64>() / (DRAWS - 1) as f64;
+      // Four standard errors either side, which a correct sampler strays past far less than once in ten thousand runs
+      let tolerance = 4.0 * (variance / DRAWS as f64).sqrt();
+      let expected = analytic_mean(distribution);
+      assert!((mean - expected).abs() < tolerance, "{:?}: mean {} instead of {}", distribution, mean, expected);
+    };
+  }
+
+  #[test]
+  fn draws_stay_within_the_distribution() {
+    for distribution in distributions() {
+      let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+      let min = match distribution {
+        HeightDistribution::Uniform { min, .. } | HeightDistribution::Exponential { min, .. } => min,
+        HeightDistribution::Bimodal { low, high, .. } => low.0.min(high.0)
+      };
+      let heights = (0..DRAWS).map(|_| distribution.sample(&mut rng)).collect::<Vec<u32>>();
+      assert!(heights.iter().all(|&height| (min..=distribution.max()).contains(&height)), "{:?}", distribution);
+      if let HeightDistribution::Bimodal { low, high, .. } = distribution {
+        let between = |height: &&u32| (low.1 + 1..high.0).contains(*height);
+        assert_eq!(heights.iter().find(between), None, "{:?}", distribution);
+      };
+    };
+  }
+
+  #[test]
+  fn organic_skylines_have_many_short_buildings_and_a_few_tall_ones() {
+    let distribution = Skyline::Organic.distribution();
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(2);
+    let mut counts = [0u32; 10];
+    for _ in 0..DRAWS {
+      counts[distribution.sample(&mut rng) as usize] += 1;
+    };
+
+    assert!(counts[2..].windows(2).all(|pair| pair[0] > pair[1]), "counts {:?}", counts);
+    assert!(counts[9] > 0, "counts {:?}", counts);
+  }
+
+  #[test]
+  fn draws_depend_only_on_the_state_of_the_rng() {
+    for distribution in distributions() {
+      for seed in 0..4 {
+        let draw = |rng: &mut Xoshiro256PlusPlus| (0..100).map(|_| distribution.sample(rng)).collect::<Vec<u32>>();
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+        let first = draw(&mut rng.clone());
+        assert_eq!(first, draw(&mut rng.clone()), "{:?}, seed {}", distribution, seed);
+        assert_eq!(first, draw(&mut rng), "{:?}, seed {}", distribution, seed);
+      };
+    };
+  }
+}
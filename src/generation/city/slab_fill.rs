@@ -0,0 +1,348 @@
+//! How the interior of a landmass is filled in between its upper and lower slabs.
+//!
+//! The edge of a landmass and the ground beneath its road are always solid, so that it reads as a solid band from the
+//! side. Only the rest of its interior is filled in according to its `SlabFill`.
+use std::fmt;
+
+use glam::IVec3;
+use rand::Rng;
+
+use super::landmass_shape::LandmassShape;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::utility::{hash3, hash_to_unit};
+
+
+
+/// The widest spacing that columns can have. Any 16 by 16 square of the interior holds at least one column when they
+/// are no further apart than this, so that no part of an upper slab goes unsupported for more than a chunk's width.
+pub const MAX_COLUMN_SPACING: u32 = 16;
+/// Mixed into the hash of each position for `SlabFill::Noise`
+const NOISE_SALT: u64 = 0x736c6162;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlabFill {
+  /// Single columns spaced `size + 1` blocks apart along each axis, staggered so that every other row is offset
+  /// by half of that. This is what every landmass was filled with before fills could be chosen, with a size of 2.
+  Checkered {
+    size: u32
+  },
+  /// Completely filled in
+  Solid,
+  /// Left empty, apart from the edge and the ground beneath the road
+  Hollow,
+  /// Single columns on a square grid, at most `MAX_COLUMN_SPACING` apart
+  Columns {
+    spacing: u32
+  },
+  /// Filled in wherever 3D hash noise is at or above the threshold, which goes from 0 (solid) to 1 (empty)
+  Noise {
+    threshold: f64
+  }
+}
+
+impl SlabFill {
+  /// The fill of every landmass unless another is chosen
+  pub const DEFAULT: Self = SlabFill::Checkered { size: 2 };
+
+  /// Whether the interior of a landmass has a block at a position between its slabs, which is never the case outside
+  /// of its shape. Every fill is laid out in the city's own coordinates rather than following the shape.
+  pub fn present(&self, pos: IVec3, shape: &LandmassShape) -> bool {
+    if shape.sample(pos.truncate()).is_none() { return false };
+    match *self {
+      SlabFill::Checkered { size } => {
+        let size = (size + 1) as i32;
+        let (xp, yp) = (pos.x.rem_euclid(size * 2), pos.y.rem_euclid(size * 2));
+        (xp == 0 && yp == 0) || (xp == size && yp == size)
+      },
+      SlabFill::Solid => true,
+      SlabFill::Hollow => false,
+      SlabFill::Columns { spacing } => {
+        let spacing = spacing.clamp(1, MAX_COLUMN_SPACING) as i32;
+        pos.x.rem_euclid(spacing) == 0 && pos.y.rem_euclid(spacing) == 0
+      },
+      SlabFill::Noise { threshold } => hash_to_unit(hash3(NOISE_SALT, pos)) >= threshold
+    }
+  }
+
+  /// Draws a fill with the given weights, choosing its size, spacing or threshold as well
+  pub fn random<R: Rng>(rng: &mut R, weights: SlabFillWeights) -> Self {
+    let weights = weights.to_array();
+    let total = weights.iter().sum::<u32>();
+    let mut pick = rng.gen_range(0..total.max(1));
+    let index = weights.iter().position(|&weight| {
+      let picked = pick < weight;
+      pick = pick.saturating_sub(weight);
+      picked
+    });
+
+    match index {
+      Some(0) | None => SlabFill::Checkered { size: rng.gen_range(1..=3) },
+      Some(1) => SlabFill::Solid,
+      Some(2) => SlabFill::Hollow,
+      Some(3) => SlabFill::Columns { spacing: rng.gen_range(4..=MAX_COLUMN_SPACING) },
+      Some(_) => SlabFill::Noise { threshold: rng.gen_range(0.2..0.7) }
+    }
+  }
+
+  /// Describes why this fill can't be used, if it can't
+  pub fn problem(&self) -> Option<String> {
+    match *self {
+      SlabFill::Checkered { size: 0 } => Some("checkered fills must have a size of at least 1".to_owned()),
+      SlabFill::Columns { spacing } if spacing == 0 || spacing > MAX_COLUMN_SPACING => {
+        Some(format!("columns must be from 1 to {} blocks apart", MAX_COLUMN_SPACING))
+      },
+      SlabFill::Noise { threshold } if !(0.0..=1.0).contains(&threshold) => {
+        Some("the noise threshold must be from 0 to 1".to_owned())
+      },
+      _ => None
+    }
+  }
+
+  /// The values making up this fill, for hashing into a cache key
+  pub fn fingerprint(&self) -> [u64; 2] {
+    match *self {
+      SlabFill::Checkered { size } => [0, size as u64],
+      SlabFill::Solid => [1, 0],
+      SlabFill::Hollow => [2, 0],
+      SlabFill::Columns { spacing } => [3, spacing as u64],
+      SlabFill::Noise { threshold } => [4, threshold.to_bits()]
+    }
+  }
+}
+
+impl Default for SlabFill {
+  fn default() -> Self {
+    SlabFill::DEFAULT
+  }
+}
+
+/// Written the way `--slab-fill` takes it
+impl fmt::Display for SlabFill {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      SlabFill::Checkered { size } => write!(f, "checkered:{}", size),
+      SlabFill::Solid => write!(f, "solid"),
+      SlabFill::Hollow => write!(f, "hollow"),
+      SlabFill::Columns { spacing } => write!(f, "columns:{}", spacing),
+      SlabFill::Noise { threshold } => write!(f, "noise:{}", threshold)
+    }
+  }
+}
+
+impl Encode for SlabFill {
+  fn encode(&self, encoder: &mut Encoder) {
+    let [kind, value] = self.fingerprint();
+    encoder.put_u8(kind as u8);
+    encoder.put_u64(value);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let (kind, value) = (decoder.take_u8()?, decoder.take_u64()?);
+    match kind {
+      0 => Some(SlabFill::Checkered { size: u32::try_from(value).ok()? }),
+      1 => Some(SlabFill::Solid),
+      2 => Some(SlabFill::Hollow),
+      3 => Some(SlabFill::Columns { spacing: u32::try_from(value).ok()? }),
+      4 => Some(SlabFill::Noise { threshold: f64::from_bits(value) }),
+      _ => None
+    }
+  }
+}
+
+/// How likely each kind of fill is to be drawn by `SlabFill::random`, relative to the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabFillWeights {
+  pub checkered: u32,
+  pub solid: u32,
+  pub hollow: u32,
+  pub columns: u32,
+  pub noise: u32
+}
+
+impl SlabFillWeights {
+  pub fn to_array(self) -> [u32; 5] {
+    [self.checkered, self.solid, self.hollow, self.columns, self.noise]
+  }
+
+  pub fn from_array([checkered, solid, hollow, columns, noise]: [u32; 5]) -> Self {
+    SlabFillWeights { checkered, solid, hollow, columns, noise }
+  }
+}
+
+impl Default for SlabFillWeights {
+  fn default() -> Self {
+    SlabFillWeights { checkered: 4, solid: 1, hollow: 1, columns: 2, noise: 2 }
+  }
+}
+
+/// Written the way `--slab-fill-weights` takes it
+impl fmt::Display for SlabFillWeights {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let [checkered, solid, hollow, columns, noise] = self.to_array();
+    write!(f, "{},{},{},{},{}", checkered, solid, hollow, columns, noise)
+  }
+}
+
+/// Whether every landmass is given the same fill, or each draws its own
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlabFillChoice {
+  Fixed(SlabFill),
+  /// Each landmass draws a fill from its layer's rng with these weights
+  Random(SlabFillWeights)
+}
+
+impl SlabFillChoice {
+  /// Picks the fill of a landmass, only drawing from the rng if fills are random,
+  /// so that layers with a fixed fill are generated the same as they were before fills could be chosen
+  pub fn choose<R: Rng>(self, rng: &mut R) -> SlabFill {
+    match self {
+      SlabFillChoice::Fixed(fill) => fill,
+      SlabFillChoice::Random(weights) => SlabFill::random(rng, weights)
+    }
+  }
+
+  /// The values making up this choice, for hashing into a cache key
+  pub fn fingerprint(&self) -> [u64; 6] {
+    match *self {
+      SlabFillChoice::Fixed(fill) => {
+        let [kind, value] = fill.fingerprint();
+        [0, kind, value, 0, 0, 0]
+      },
+      SlabFillChoice::Random(weights) => {
+        let [checkered, solid, hollow, columns, noise] = weights.to_array().map(u64::from);
+        [1, checkered, solid, hollow, columns, noise]
+      }
+    }
+  }
+}
+
+impl Default for SlabFillChoice {
+  fn default() -> Self {
+    SlabFillChoice::Fixed(SlabFill::DEFAULT)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeSet;
+
+  use glam::{DVec2, IVec2};
+  use rand::SeedableRng;
+  use rand_xoshiro::Xoshiro256PlusPlus;
+
+  use super::*;
+  use crate::generation::fixtures::RadialRamp;
+
+  /// A landmass 40 blocks in radius, whose middle is well within its interior
+  fn landmass() -> LandmassShape {
+    LandmassShape::from_noise(RadialRamp { center: DVec2::ZERO, radius: 40.0 }, None)
+  }
+
+  /// The columns of a 41 by 41 square in the middle of the landmass
+  fn interior() -> impl Iterator<Item = IVec2> {
+    (-20..=20).flat_map(|x| (-20..=20).map(move |y| IVec2::new(x, y)))
+  }
+
+  #[test]
+  fn checkered_fills_are_staggered_single_columns() {
+    let shape = landmass();
+    let fill = SlabFill::Checkered { size: 2 };
+    for pos in [[0, 0], [6, 0], [0, -6], [3, 3], [-3, 3], [9, -9]].map(IVec2::from) {
+      assert!(fill.present(pos.extend(0), &shape), "no column at {}", pos);
+    };
+    for pos in [[1, 0], [3, 0], [0, 3], [3, 6], [2, 2], [6, 3]].map(IVec2::from) {
+      assert!(!fill.present(pos.extend(0), &shape), "a column at {}", pos);
+    };
+
+    for size in 1..=3 {
+      let fill = SlabFill::Checkered { size };
+      let present = |pos: IVec2| fill.present(pos.extend(0), &shape);
+      let columns = interior().filter(|&pos| present(pos)).collect::<Vec<IVec2>>();
+      assert!(!columns.is_empty(), "size {}: no columns", size);
+      for pos in columns {
+        let spacing = size as i32 + 1;
+        assert!((1..spacing).all(|d| !present(pos + IVec2::new(d, 0)) && !present(pos + IVec2::new(0, d))), "{}", pos);
+        assert!(present(pos + IVec2::new(spacing, spacing)), "size {}: the column at {} isn't staggered", size, pos);
+      };
+    };
+  }
+
+  #[test]
+  fn solid_and_hollow_fills_are_full_and_empty() {
+    let shape = landmass();
+    for pos in interior() {
+      for z in [-3, 0, 7] {
+        assert!(SlabFill::Solid.present(pos.extend(z), &shape), "solid fill missing at {}", pos.extend(z));
+        assert!(!SlabFill::Hollow.present(pos.extend(z), &shape), "hollow fill present at {}", pos.extend(z));
+      };
+    };
+
+    // Nothing is filled in beyond the landmass
+    assert!(!SlabFill::Solid.present(IVec3::new(50, 0, 0), &shape));
+    assert!(!SlabFill::Noise { threshold: 0.0 }.present(IVec3::new(0, -50, 0), &shape));
+  }
+
+  #[test]
+  fn columns_are_on_a_grid_with_one_in_every_chunk_sized_area() {
+    let shape = landmass();
+    let fill = SlabFill::Columns { spacing: 4 };
+    for (pos, expected) in [([0, 0], true), ([4, -8], true), ([-12, 16], true), ([2, 0], false), ([4, 3], false)] {
+      let pos = IVec2::from(pos).extend(5);
+      assert_eq!(fill.present(pos, &shape), expected, "at {}", pos);
+    };
+
+    // Spacings wider than the widest allowed are narrowed to it
+    for spacing in (1..=MAX_COLUMN_SPACING).chain([100]) {
+      let fill = SlabFill::Columns { spacing };
+      for min in interior().filter(|pos| pos.x <= 5 && pos.y <= 5) {
+        let has_column = (0..16).any(|x| (0..16).any(|y| fill.present((min + IVec2::new(x, y)).extend(0), &shape)));
+        assert!(has_column, "spacing {}: no column in the 16 by 16 area from {}", spacing, min);
+      };
+    };
+  }
+
+  #[test]
+  fn noise_fills_are_filled_in_above_their_threshold() {
+    let shape = landmass();
+    let count = |threshold: f64, z: i32| {
+      let fill = SlabFill::Noise { threshold };
+      interior().filter(|pos| fill.present(pos.extend(z), &shape)).count()
+    };
+
+    let total = interior().count();
+    assert_eq!(count(0.0, 0), total);
+    assert_eq!(count(1.0, 0), 0);
+    let half = count(0.5, 0);
+    assert!(half > total * 2 / 5 && half < total * 3 / 5, "{} of {} filled in", half, total);
+    // The noise is 3D, so each level is filled in differently
+    let fill = SlabFill::Noise { threshold: 0.5 };
+    assert!(interior().any(|pos| fill.present(pos.extend(0), &shape) != fill.present(pos.extend(1), &shape)));
+  }
+
+  #[test]
+  fn random_fills_depend_only_on_the_seed() {
+    let mut kinds = BTreeSet::new();
+    for seed in 0..256 {
+      let choice = SlabFillChoice::Random(SlabFillWeights::default());
+      let choose = || choice.choose(&mut Xoshiro256PlusPlus::seed_from_u64(seed));
+      let fill = choose();
+      assert_eq!(fill, choose(), "seed {}", seed);
+      assert_eq!(fill.problem(), None, "seed {}: {}", seed, fill);
+      kinds.insert(fill.fingerprint()[0]);
+    };
+
+    assert_eq!(kinds, BTreeSet::from([0, 1, 2, 3, 4]));
+    let only_solid = SlabFillWeights::from_array([0, 1, 0, 0, 0]);
+    for seed in 0..8 {
+      assert_eq!(SlabFill::random(&mut Xoshiro256PlusPlus::seed_from_u64(seed), only_solid), SlabFill::Solid);
+    };
+  }
+
+  #[test]
+  fn fixed_fills_draw_nothing_from_the_rng() {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+    let fill = SlabFill::Columns { spacing: 7 };
+    assert_eq!(SlabFillChoice::Fixed(fill).choose(&mut rng), fill);
+    assert_eq!(rng.gen::<u64>(), Xoshiro256PlusPlus::seed_from_u64(0).gen::<u64>());
+  }
+}
@@ -0,0 +1,130 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::building::Building;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::mix64;
+
+
+
+const MIN_SPIRE_HEIGHT: u32 = 15;
+pub const MAX_SPIRE_HEIGHT: u32 = 25;
+/// Number of blocks between each set of cross-arms
+const CROSS_ARM_INTERVAL: i32 = 6;
+/// How far the cross-arms reach out from the mast
+const CROSS_ARM_LENGTH: i32 = 2;
+
+/// A tapering mast on the roof of a layer's tallest building, with a light at its tip
+#[derive(Debug, Clone)]
+pub struct Spire {
+  origin: IVec2,
+  /// The z value of the lowest block of the spire, just above the roof
+  base: i32,
+  height: u32
+}
+
+impl Spire {
+  /// Places a spire on the tallest of the given buildings, breaking ties by position.
+  /// The spire is cut short beneath `build_limit`, and left out if there is no room for it at all.
+  pub fn on_tallest(buildings: &[Building], build_limit: i32) -> Option<Self> {
+    let building = buildings.iter()
+      .max_by_key(|building| (building.top(), building.edge_min.x, building.edge_min.y))?;
+    let origin = (building.edge_min + building.edge_max) / 2;
+    let base = building.top() + 1;
+    let height_range = (MAX_SPIRE_HEIGHT - MIN_SPIRE_HEIGHT + 1) as u64;
+    let height = MIN_SPIRE_HEIGHT + (mix64(building.detail_seed) % height_range) as u32;
+    let height = height.min((build_limit - base).max(0) as u32);
+    (height > 0).then(|| Spire { origin, base, height })
+  }
+
+  fn top(&self) -> i32 {
+    self.base + self.height as i32 - 1
+  }
+
+  fn sample(&self, pos: IVec3) -> Option<SpirePart> {
+    if !(self.base..=self.top()).contains(&pos.z) { return None };
+    let local = pos.xy() - self.origin;
+    let z = pos.z - self.base;
+
+    if pos.z == self.top() {
+      return (local == IVec2::ZERO).then(|| SpirePart::Light);
+    };
+
+    // The lower third of the mast is three blocks wide, tapering to one block above it
+    if z < self.height as i32 / 3 {
+      if local.x.abs() <= 1 && local.y.abs() <= 1 {
+        return Some(SpirePart::Base);
+      };
+    } else if local == IVec2::ZERO {
+      return Some(SpirePart::Mast);
+    };
+
+    if z > 0 && z % CROSS_ARM_INTERVAL == 0 {
+      if local.y == 0 && local.x.abs() <= CROSS_ARM_LENGTH {
+        return Some(SpirePart::CrossArmX);
+      } else if local.x == 0 && local.y.abs() <= CROSS_ARM_LENGTH {
+        return Some(SpirePart::CrossArmY);
+      };
+    };
+
+    None
+  }
+}
+
+impl Geometry for Spire {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = (self.origin - CROSS_ARM_LENGTH).extend(self.base);
+    let max = (self.origin + CROSS_ARM_LENGTH).extend(self.top());
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.sample(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Spire {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.sample(pos).map(|part| Cow::Borrowed(match part {
+      SpirePart::Base => &blocks::IRON_BARS,
+      SpirePart::Mast => &blocks::CHAIN_VERTICAL,
+      SpirePart::CrossArmX => &blocks::CHAIN_EAST_WEST,
+      SpirePart::CrossArmY => &blocks::CHAIN_NORTH_SOUTH,
+      SpirePart::Light => &blocks::SEA_LANTERN
+    }))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([
+      blocks::IRON_BARS, blocks::CHAIN_VERTICAL, blocks::CHAIN_EAST_WEST, blocks::CHAIN_NORTH_SOUTH, blocks::SEA_LANTERN
+    ]);
+  }
+}
+
+impl Encode for Spire {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.origin);
+    encoder.put_i32(self.base);
+    encoder.put_u32(self.height);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Spire {
+      origin: decoder.take()?,
+      base: decoder.take_i32()?,
+      height: decoder.take_u32()?
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpirePart {
+  Base,
+  Mast,
+  CrossArmX,
+  CrossArmY,
+  Light
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use glam::IVec2;
+
+
+
+/// The width of each square tile of a `TileGrid`
+const TILE_SIZE: i32 = 16;
+const TILE_AREA: usize = (TILE_SIZE * TILE_SIZE) as usize;
+
+type Tile<T> = Box<[Option<T>; TILE_AREA]>;
+
+/// A grid which stores its cells densely in square tiles, only allocating the tiles that have cells in them.
+///
+/// A `SparseGrid` pays for a hash map entry for every one of its cells, which adds up quickly for landmasses with
+/// millions of them. This only pays for a single `Option<T>` per cell, plus a hash map entry per tile.
+/// Cells are iterated tile by tile, in the order that each tile was first written to.
+#[derive(Debug, Clone)]
+pub struct TileGrid<T> {
+  tiles: Vec<(IVec2, Tile<T>)>,
+  /// The index in `tiles` of the tile at each tile position
+  tile_indices: HashMap<IVec2, usize>,
+  bounds: Option<(IVec2, IVec2)>
+}
+
+impl<T: Copy> TileGrid<T> {
+  pub fn new() -> Self {
+    TileGrid {
+      tiles: Vec::new(),
+      tile_indices: HashMap::new(),
+      bounds: None
+    }
+  }
+
+  #[inline]
+  pub fn get(&self, pos: IVec2) -> Option<T> {
+    let (tile_pos, index) = split_pos(pos);
+    let &tile_index = self.tile_indices.get(&tile_pos)?;
+    self.tiles[tile_index].1[index]
+  }
+
+  pub fn put(&mut self, pos: IVec2, value: T) {
+    let (tile_pos, index) = split_pos(pos);
+    let tiles = &mut self.tiles;
+    let &mut tile_index = self.tile_indices.entry(tile_pos).or_insert_with(|| {
+      tiles.push((tile_pos, Box::new([None; TILE_AREA])));
+      tiles.len() - 1
+    });
+
+    self.tiles[tile_index].1[index] = Some(value);
+    self.bounds = Some(match self.bounds {
+      Some((min, max)) => (min.min(pos), max.max(pos)),
+      None => (pos, pos)
+    });
+  }
+
+  pub fn cells(&self) -> impl Iterator<Item = (IVec2, T)> + '_ {
+    self.tiles.iter().flat_map(|(tile_pos, tile)| {
+      let origin = *tile_pos * TILE_SIZE;
+      tile.iter().enumerate().filter_map(move |(index, value)| {
+        let offset = IVec2::new(index as i32 % TILE_SIZE, index as i32 / TILE_SIZE);
+        value.map(|value| (origin + offset, value))
+      })
+    })
+  }
+
+  /// The smallest x and y values of any cell in this grid
+  #[inline]
+  pub fn min(&self) -> Option<IVec2> {
+    self.bounds.map(|(min, _)| min)
+  }
+
+  /// The largest x and y values of any cell in this grid
+  #[inline]
+  pub fn max(&self) -> Option<IVec2> {
+    self.bounds.map(|(_, max)| max)
+  }
+
+  /// An estimate of the memory used by this grid's tiles and its table of tiles, in bytes
+  pub fn memory_usage(&self) -> usize {
+    let tiles = self.tiles.capacity() * std::mem::size_of::<(IVec2, Tile<T>)>() +
+      self.tiles.len() * std::mem::size_of::<[Option<T>; TILE_AREA]>();
+    // Each hash map bucket also has a control byte
+    let tile_indices = self.tile_indices.capacity() * (std::mem::size_of::<(IVec2, usize)>() + 1);
+    tiles + tile_indices
+  }
+}
+
+impl<T: Copy> Default for TileGrid<T> {
+  fn default() -> Self {
+    TileGrid::new()
+  }
+}
+
+impl<T: Copy> FromIterator<(IVec2, T)> for TileGrid<T> {
+  fn from_iter<I: IntoIterator<Item = (IVec2, T)>>(iter: I) -> Self {
+    let mut grid = TileGrid::new();
+    for (pos, value) in iter {
+      grid.put(pos, value);
+    };
+
+    grid
+  }
+}
+
+/// Splits a position into the position of the tile containing it, and its index within that tile
+#[inline]
+fn split_pos(pos: IVec2) -> (IVec2, usize) {
+  let tile_pos = IVec2::new(pos.x.div_euclid(TILE_SIZE), pos.y.div_euclid(TILE_SIZE));
+  let local = pos - tile_pos * TILE_SIZE;
+  (tile_pos, (local.y * TILE_SIZE + local.x) as usize)
+}
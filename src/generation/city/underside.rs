@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
+use noise::{NoiseFn, Perlin};
+
+use super::landmass_shape::LandmassShape;
+use crate::generation::blocks;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::pillar::PillarCluster;
+use crate::generation::{Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{hash2, hash_to_unit, quantize};
+
+
+
+pub const MIN_CHAIN_LENGTH: u32 = 2;
+pub const MAX_CHAIN_LENGTH: u32 = 6;
+
+/// How far from the edge of a landmass a non-edge cell may be and still be eligible for a chain
+const NEAR_EDGE_DISTANCE: usize = 4;
+/// The scale of the noise which selects the near-edge cells that get chains
+const NEAR_EDGE_NOISE_SCALE: f64 = 12.0;
+const NEAR_EDGE_NOISE_THRESHOLD: f64 = 0.2;
+/// The chance that any given chain ends in a lantern, on layers that have lanterns
+const LANTERN_CHANCE: f64 = 0.08;
+
+/// Chains hanging from the underside of a landmass, around its edges.
+/// Which columns have chains is decided once up front, but the contents of each column
+/// are derived from a hash of its position, so sampling a block is just a grid lookup.
+#[derive(Debug, Clone)]
+pub struct Underside {
+  seed: u64,
+  /// The z value of the first block below the landmass' lower slab
+  top: i32,
+  lanterns: bool,
+  columns: SparseGrid<()>
+}
+
+impl Underside {
+  pub fn generate_new(seed: u64, shape: &LandmassShape, top: i32, lanterns: bool, pillars: &[PillarCluster]) -> Self {
+    let noise = Perlin::new(seed as u32);
+    let columns = shape.cells()
+      .filter(|&(pos, cell)| {
+        cell.is_edge() || (cell.edge_distance() <= NEAR_EDGE_DISTANCE && {
+          let point = pos.as_dvec2() / NEAR_EDGE_NOISE_SCALE;
+          quantize(noise.get(point.to_array())) > NEAR_EDGE_NOISE_THRESHOLD
+        })
+      })
+      .filter(|&(pos, _)| !pillars.iter().any(|cluster| cluster.contains_column(pos)))
+      .map(|(pos, _)| (pos, ()))
+      .collect::<SparseGrid<()>>();
+
+    Underside { seed, top, lanterns, columns }
+  }
+
+  /// The length of the chain in the given column, and whether or not it ends in a lantern
+  fn chain_at(&self, pos: IVec2) -> Option<(i32, bool)> {
+    if !self.columns.contains(pos) { return None };
+    let hash = hash2(self.seed, pos);
+    let length = MIN_CHAIN_LENGTH + (hash % (MAX_CHAIN_LENGTH - MIN_CHAIN_LENGTH + 1) as u64) as u32;
+    let lantern = self.lanterns && hash_to_unit(hash) < LANTERN_CHANCE;
+    Some((length as i32, lantern))
+  }
+}
+
+impl Geometry for Underside {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.columns.min().unwrap_or(IVec2::ZERO).extend(self.top - MAX_CHAIN_LENGTH as i32 + 1);
+    let max = self.columns.max().unwrap_or(IVec2::ZERO).extend(self.top);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.block_material_at(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Underside {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    if pos.z > self.top { return None };
+    let (length, lantern) = self.chain_at(pos.xy())?;
+    let depth = self.top - pos.z;
+    match depth {
+      depth if depth == length - 1 && lantern => Some(Cow::Borrowed(&blocks::LANTERN_HANGING)),
+      depth if depth < length => Some(Cow::Borrowed(&blocks::CHAIN_VERTICAL)),
+      _ => None
+    }
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(blocks::CHAIN_VERTICAL);
+    if self.lanterns {
+      out.insert(blocks::LANTERN_HANGING);
+    };
+  }
+}
+
+impl Encode for Underside {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_u64(self.seed);
+    encoder.put_i32(self.top);
+    encoder.put_bool(self.lanterns);
+    encoder.put(&self.columns.cells().map(|(pos, ())| pos).collect::<Vec<IVec2>>());
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Underside {
+      seed: decoder.take_u64()?,
+      top: decoder.take_i32()?,
+      lanterns: decoder.take_bool()?,
+      columns: decoder.take::<Vec<IVec2>>()?.into_iter()
+        .map(|pos| (pos, ()))
+        .collect()
+    })
+  }
+}
@@ -0,0 +1,154 @@
+//! Patches of moss and grass growing on the surface of a landmass, with the odd azalea bush on top.
+//!
+//! Patches are picked out by thresholding a 2D noise, then split into connected clusters,
+//! keeping only the clusters between `MIN_PATCH_SIZE` and `MAX_PATCH_SIZE` blocks across.
+//! The cores of the patches are grass, fringed with moss.
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
+use noise::{NoiseFn, Perlin};
+
+use super::landmass_shape::LandmassShape;
+use crate::generation::encoding::{Decoder, Encode, Encoder};
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{cardinal4, hash2, hash_to_unit, quantize};
+
+
+
+/// The scale of the noise which selects the columns covered by patches
+const NOISE_SCALE: f64 = 7.0;
+/// The noise value above which a column is covered by a patch
+const PATCH_THRESHOLD: f64 = 0.45;
+/// The noise value above which a column in a patch is grass rather than moss
+const GRASS_THRESHOLD: f64 = 0.55;
+/// The smallest and largest a patch can be along either axis, in blocks
+pub const MIN_PATCH_SIZE: i32 = 3;
+pub const MAX_PATCH_SIZE: i32 = 8;
+/// The chance that a column of moss has an azalea bush on top of it
+const DECORATION_CHANCE: f64 = 0.12;
+
+#[derive(Debug, Clone)]
+pub struct Vegetation {
+  seed: u64,
+  /// The z value of the landmass' upper slab, which the patches replace
+  level: i32,
+  /// The columns covered by patches, and whether each of them is grass rather than moss
+  columns: SparseGrid<bool>
+}
+
+impl Vegetation {
+  /// Grows patches on a landmass whose upper slab is at `level`, leaving alone every column where `is_excluded` is true
+  pub fn generate_new(seed: u64, shape: &LandmassShape, level: i32, is_excluded: impl Fn(IVec2) -> bool) -> Self {
+    let noise = Perlin::new(seed as u32);
+    let sample = |pos: IVec2| quantize(noise.get((pos.as_dvec2() / NOISE_SCALE).to_array()));
+    let candidates = shape.cells()
+      .map(|(pos, _)| pos)
+      .filter(|&pos| sample(pos) > PATCH_THRESHOLD && !is_excluded(pos))
+      .collect::<Vec<IVec2>>();
+
+    let columns = patches(&candidates).into_iter()
+      .filter(|patch| {
+        let (min, max) = patch_bounds(patch);
+        let size = (max - min + IVec2::ONE).max_element();
+        (MIN_PATCH_SIZE..=MAX_PATCH_SIZE).contains(&size)
+      })
+      .flatten()
+      .map(|pos| (pos, sample(pos) > GRASS_THRESHOLD))
+      .collect::<SparseGrid<bool>>();
+
+    Vegetation { seed, level, columns }
+  }
+
+  /// Removes the patches from every column where `is_excluded` is true
+  pub fn exclude(&mut self, is_excluded: impl Fn(IVec2) -> bool) {
+    self.columns = self.columns.cells()
+      .filter(|&(pos, _)| !is_excluded(pos))
+      .map(|(pos, &grass)| (pos, grass))
+      .collect();
+  }
+
+  /// The bush on top of the given column, which only grows on moss
+  fn decoration_at(&self, pos: IVec2) -> Option<&'static Block> {
+    if self.columns.get(pos) != Some(&false) { return None };
+    let hash = hash2(self.seed, pos);
+    (hash_to_unit(hash) < DECORATION_CHANCE).then(|| match hash & 1 {
+      0 => &blocks::AZALEA,
+      _ => &blocks::FLOWERING_AZALEA
+    })
+  }
+}
+
+impl Geometry for Vegetation {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.columns.min().unwrap_or(IVec2::ZERO).extend(self.level);
+    let max = self.columns.max().unwrap_or(IVec2::ZERO).extend(self.level + 1);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.block_material_at(pos).is_some()
+  }
+}
+
+impl MaterialGeometry for Vegetation {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    match pos.z - self.level {
+      0 => self.columns.get(pos.xy()).map(|&grass| Cow::Borrowed(match grass {
+        true => &blocks::GRASS_BLOCK,
+        false => &blocks::MOSS_BLOCK
+      })),
+      1 => self.decoration_at(pos.xy()).map(Cow::Borrowed),
+      _ => None
+    }
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([blocks::GRASS_BLOCK, blocks::MOSS_BLOCK, blocks::AZALEA, blocks::FLOWERING_AZALEA]);
+  }
+}
+
+impl Encode for Vegetation {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_u64(self.seed);
+    encoder.put_i32(self.level);
+    encoder.put(&self.columns.cells().map(|(pos, &grass)| (pos, grass)).collect::<Vec<(IVec2, bool)>>());
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Vegetation {
+      seed: decoder.take_u64()?,
+      level: decoder.take_i32()?,
+      columns: decoder.take::<Vec<(IVec2, bool)>>()?.into_iter().collect()
+    })
+  }
+}
+
+/// Splits a set of columns into groups which are connected along the cardinal directions,
+/// in the order that the first column of each group appears
+fn patches(columns: &[IVec2]) -> Vec<Vec<IVec2>> {
+  let mut remaining = columns.iter().copied().collect::<HashSet<IVec2>>();
+  let mut patches = Vec::new();
+  for &start in columns {
+    if !remaining.remove(&start) { continue };
+    let mut patch = vec![start];
+    let mut queue = VecDeque::from([start]);
+    while let Some(pos) = queue.pop_front() {
+      for neighbor in cardinal4(pos) {
+        if remaining.remove(&neighbor) {
+          patch.push(neighbor);
+          queue.push_back(neighbor);
+        };
+      };
+    };
+
+    patches.push(patch);
+  };
+
+  patches
+}
+
+fn patch_bounds(patch: &[IVec2]) -> (IVec2, IVec2) {
+  patch.iter().fold((patch[0], patch[0]), |(min, max), &pos| (min.min(pos), max.max(pos)))
+}
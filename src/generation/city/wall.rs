@@ -0,0 +1,261 @@
+//! A wall running around the lowest layer of the city, some distance out from the edge of its landmass.
+//!
+//! The wall follows the edge of the landmass in the order of its cells, each pushed outwards by the wall's distance
+//! and joined up to the next into a closed loop. Around the inner corners of a lobed landmass the pushed loop folds
+//! back and crosses itself, so the wall is built along the outer edge of everything the loop encloses instead of
+//! the loop itself. A gate is left open where the loop starts, at the edge cell with the lowest ordering.
+use std::borrow::Cow;
+use std::collections::{BTreeSet, VecDeque};
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use grid::SparseGrid;
+
+use super::landmass_shape::LandmassShape;
+use crate::generation::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{cardinal4, cardinal8};
+
+
+
+/// The distance between the edge of the landmass and the wall when it isn't given
+pub const DEFAULT_WALL_DISTANCE: u32 = 10;
+/// The height of the wall-walk above the landmass' upper slab, with the merlons standing a block higher
+const WALL_HEIGHT: i32 = 8;
+/// The width of the opening left for the gate
+const GATE_WIDTH: i32 = 6;
+
+/// The top of each column of the wall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WallColumn {
+  Walk,
+  /// Every other column along the outside of the wall
+  Merlon,
+  /// Left open above the landmass' upper slab
+  Gate
+}
+
+/// A wall two blocks thick, standing on the sea floor and rising `WALL_HEIGHT` blocks above the upper slab of the
+/// landmass that it surrounds
+#[derive(Debug, Clone)]
+pub struct Wall {
+  /// The top of the landmass' upper slab, which the gate is left open above
+  level: i32,
+  bottom: i32,
+  columns: SparseGrid<WallColumn>
+}
+
+impl Wall {
+  /// Builds a wall `distance` blocks out from the edge of the given landmass, whose upper slab is at `level`,
+  /// reaching down to `bottom`
+  pub fn new(shape: &LandmassShape, level: i32, bottom: i32, distance: u32) -> Self {
+    let mut edge = shape.cells()
+      .filter(|(_, cell)| cell.is_edge())
+      .map(|(pos, cell)| (pos, cell.ordering(), cell.outward_direction().unwrap_or(IVec2::ZERO)))
+      .collect::<Vec<(IVec2, usize, IVec2)>>();
+    // Ties are broken by position, so that the gate doesn't depend on the grid's iteration order
+    edge.sort_unstable_by_key(|&(pos, ordering, _)| (ordering, pos.x, pos.y));
+    let outline = edge.into_iter()
+      .map(|(pos, _, outward)| push_out(pos, outward, distance))
+      .collect::<Vec<IVec2>>();
+
+    // The loop is drawn with diagonal steps, which a flood fill moving along the axes can't slip through
+    let mut blocked = shape.cells().map(|(pos, _)| (pos, ())).collect::<SparseGrid<()>>();
+    for (i, &from) in outline.iter().enumerate() {
+      let to = outline[(i + 1) % outline.len()];
+      for pos in line(from, to) {
+        blocked.put(pos, ());
+      };
+    };
+
+    let (min, max) = (blocked.min().unwrap() - IVec2::ONE, blocked.max().unwrap() + IVec2::ONE);
+    let outside = flood_outside(&blocked, min, max);
+    let is_enclosed = |pos: IVec2| outside.get(pos).is_none();
+
+    // The outer course is every enclosed column touching the outside, even only diagonally, so that it runs along
+    // the axes without gaps; the inner course is every column beside that
+    let mut courses = SparseGrid::<bool>::new();
+    for (pos, _) in outside.cells() {
+      for neighbor in cardinal8(pos).filter(|&neighbor| is_enclosed(neighbor)) {
+        courses.put(neighbor, true);
+      };
+    };
+
+    let outer = courses.cells().map(|(pos, _)| pos).collect::<Vec<IVec2>>();
+    for pos in outer {
+      for neighbor in cardinal4(pos).filter(|&neighbor| is_enclosed(neighbor)) {
+        if !courses.contains(neighbor) {
+          courses.put(neighbor, false);
+        };
+      };
+    };
+
+    let gate = outline.first().and_then(|&start| {
+      courses.cells()
+        .map(|(pos, _)| pos)
+        .min_by_key(|&pos| ((pos - start).dot(pos - start), pos.x, pos.y))
+    });
+    let is_gate = |pos: IVec2| gate.map_or(false, |gate| {
+      let offset = pos - gate;
+      (-GATE_WIDTH / 2..GATE_WIDTH - GATE_WIDTH / 2).contains(&offset.x) &&
+      (-GATE_WIDTH / 2..GATE_WIDTH - GATE_WIDTH / 2).contains(&offset.y)
+    });
+
+    let columns = courses.cells()
+      .map(|(pos, &is_outer)| {
+        let column = match is_gate(pos) {
+          true => WallColumn::Gate,
+          // Every step along the outer course is along an axis, so a checkerboard alternates the merlons around
+          // the corners as well as along the sides
+          false if is_outer && (pos.x + pos.y).rem_euclid(2) == 1 => WallColumn::Merlon,
+          false => WallColumn::Walk
+        };
+
+        (pos, column)
+      })
+      .collect();
+    Wall { level, bottom, columns }
+  }
+
+  fn top(&self, column: WallColumn) -> i32 {
+    match column {
+      WallColumn::Walk => self.level + WALL_HEIGHT,
+      WallColumn::Merlon => self.level + WALL_HEIGHT + 1,
+      WallColumn::Gate => self.level
+    }
+  }
+}
+
+impl Geometry for Wall {
+  fn bounding_box(&self) -> BoundingBox {
+    let min = self.columns.min().unwrap_or(IVec2::ZERO).extend(self.bottom);
+    let max = self.columns.max().unwrap_or(IVec2::ZERO).extend(self.level + WALL_HEIGHT + 1);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.columns.get(pos.xy()).map_or(false, |&column| pos.z >= self.bottom && pos.z <= self.top(column))
+  }
+}
+
+impl MaterialGeometry for Wall {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&blocks::DEEPSLATE_BRICKS))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(blocks::DEEPSLATE_BRICKS);
+  }
+}
+
+/// Moves an edge cell outwards by the given distance, which is shortened along each axis for diagonal directions so
+/// that corners are pushed as far as straight edges
+fn push_out(pos: IVec2, outward: IVec2, distance: u32) -> IVec2 {
+  let distance = match outward.x != 0 && outward.y != 0 {
+    true => (distance as f64 * FRAC_1_SQRT_2).round() as i32,
+    false => distance as i32
+  };
+
+  pos + outward * distance
+}
+
+/// The columns on a line between two columns, inclusive, stepping diagonally where the line isn't straight
+fn line(from: IVec2, to: IVec2) -> impl Iterator<Item = IVec2> {
+  let offset = to - from;
+  let steps = offset.abs().max_element().max(1);
+  (0..=steps).map(move |i| from + (offset.as_dvec2() * i as f64 / steps as f64).round().as_ivec2())
+}
+
+/// Every column from `min` to `max` which can be reached from `min` along the axes without crossing a blocked column
+fn flood_outside(blocked: &SparseGrid<()>, min: IVec2, max: IVec2) -> SparseGrid<()> {
+  let mut outside = SparseGrid::new();
+  let mut q = VecDeque::from([min]);
+  outside.put(min, ());
+  while let Some(pos) = q.pop_front() {
+    for neighbor in cardinal4(pos) {
+      let is_inside = neighbor.cmpge(min).all() && neighbor.cmple(max).all();
+      if is_inside && !blocked.contains(neighbor) && !outside.contains(neighbor) {
+        outside.put(neighbor, ());
+        q.push_back(neighbor);
+      };
+    };
+  };
+
+  outside
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::city::landmass_shape::{ShapeParams, Symmetry};
+
+  const LEVEL: i32 = 10;
+
+  /// Walls around a round landmass and around a stretched, lobed one, which has inner corners as well as outer ones
+  fn walls() -> Vec<(LandmassShape, Wall)> {
+    let lobed = ShapeParams { elongation: 1.4, rotation: 0.1, lobe_depth: 0.25, lobes: 4 };
+    [(3, ShapeParams::CIRCULAR), (7, lobed)].into_iter()
+      .map(|(seed, params)| {
+        let shape = LandmassShape::generate_new(seed, 1.0, params, Symmetry::None, None);
+        let wall = Wall::new(&shape, LEVEL, -30, DEFAULT_WALL_DISTANCE);
+        (shape, wall)
+      })
+      .collect()
+  }
+
+  /// Every column around the wall which can be reached from beyond it without crossing the wall
+  fn outside_of(wall: &Wall) -> SparseGrid<()> {
+    let blocked = wall.columns.cells().map(|(pos, _)| (pos, ())).collect::<SparseGrid<()>>();
+    flood_outside(&blocked, wall.columns.min().unwrap() - IVec2::ONE, wall.columns.max().unwrap() + IVec2::ONE)
+  }
+
+  #[test]
+  fn the_wall_closes_around_the_landmass() {
+    for (i, (shape, wall)) in walls().iter().enumerate() {
+      let outside = outside_of(wall);
+      for (pos, _) in shape.cells() {
+        assert!(outside.get(pos).is_none(), "wall {}: the landmass at {} can be reached from outside", i, pos);
+        assert!(wall.columns.get(pos).is_none(), "wall {}: the wall stands on the landmass at {}", i, pos);
+      };
+
+      // The gate is only open above the landmass, so it doesn't break the loop
+      let gate = wall.columns.cells().filter(|&(_, &column)| column == WallColumn::Gate).count();
+      assert!(gate > 0 && gate <= (GATE_WIDTH * GATE_WIDTH) as usize, "wall {}: {} gate columns", i, gate);
+    };
+  }
+
+  #[test]
+  fn merlons_alternate_around_corners() {
+    for (i, (_, wall)) in walls().iter().enumerate() {
+      let outside = outside_of(wall);
+      let is_outer = |pos: IVec2| {
+        matches!(wall.columns.get(pos), Some(WallColumn::Walk | WallColumn::Merlon)) &&
+        cardinal8(pos).any(|neighbor| outside.get(neighbor).is_some())
+      };
+      let beside_gate = |pos: IVec2| {
+        cardinal4(pos).any(|neighbor| wall.columns.get(neighbor) == Some(&WallColumn::Gate))
+      };
+
+      let mut corners = 0;
+      for (pos, &column) in wall.columns.cells() {
+        if !is_outer(pos) {
+          assert_ne!(column, WallColumn::Merlon, "wall {}: merlon on the inner course at {}", i, pos);
+          continue;
+        };
+
+        // The course carries on in both directions without ever stepping diagonally
+        let along = cardinal4(pos).filter(|&neighbor| is_outer(neighbor)).collect::<Vec<IVec2>>();
+        assert!(along.len() >= 2 || beside_gate(pos), "wall {}: the outer course breaks off at {}", i, pos);
+        for &neighbor in along.iter() {
+          let other = *wall.columns.get(neighbor).unwrap();
+          assert_ne!(column, other, "wall {}: {} and {} are both {:?}", i, pos, neighbor, column);
+        };
+
+        // Turning a corner, the course carries on along the other axis
+        corners += along.iter().any(|&a| along.iter().any(|&b| (a - pos).dot(b - pos) == 0)) as usize;
+      };
+
+      assert!(corners > 4, "wall {}: only {} corners", i, corners);
+    };
+  }
+}
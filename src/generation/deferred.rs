@@ -0,0 +1,102 @@
+//! Values which are only built once something within their bounding box asks for them.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+use glam::{IVec2, IVec3};
+use once_cell::sync::OnceCell;
+
+use super::{Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+
+
+
+/// A value which is built the first time a position within its bounding box asks for it, so that a feature which is
+/// never sampled nearby is never built. Clones share the value, which is built at most once.
+pub struct Deferred<T> {
+  bounding_box: BoundingBox,
+  inner: Arc<DeferredInner<T>>
+}
+
+struct DeferredInner<T> {
+  value: OnceCell<T>,
+  build: Box<dyn Fn() -> T + Send + Sync>
+}
+
+impl<T> Deferred<T> {
+  /// The value built by `build` must not reach outside of the given bounding box
+  pub fn new(bounding_box: BoundingBox, build: impl Fn() -> T + Send + Sync + 'static) -> Self {
+    let inner = Arc::new(DeferredInner { value: OnceCell::new(), build: Box::new(build) });
+    Deferred { bounding_box, inner }
+  }
+
+  /// The value, building it if it has not been built yet
+  pub fn get(&self) -> &T {
+    self.inner.value.get_or_init(|| (self.inner.build)())
+  }
+
+  /// The value, if the given position is within the bounding box
+  pub fn get_at(&self, pos: IVec3) -> Option<&T> {
+    self.bounding_box.contains(pos).then(|| self.get())
+  }
+
+  /// The value, if the given column passes through the bounding box
+  pub fn get_in_column(&self, xy: IVec2) -> Option<&T> {
+    self.bounding_box.contains_column(xy).then(|| self.get())
+  }
+
+  #[cfg(test)]
+  pub fn is_built(&self) -> bool {
+    self.inner.value.get().is_some()
+  }
+}
+
+impl<T> Clone for Deferred<T> {
+  fn clone(&self) -> Self {
+    Deferred { bounding_box: self.bounding_box, inner: Arc::clone(&self.inner) }
+  }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Deferred<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Deferred")
+      .field("bounding_box", &self.bounding_box)
+      .field("value", &self.inner.value.get())
+      .finish()
+  }
+}
+
+impl<G> Geometry for Deferred<G>
+where G: Geometry {
+  #[inline]
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.get_at(pos).map_or(false, |geometry| geometry.block_at(pos))
+  }
+}
+
+impl<G> ColumnTop for Deferred<G>
+where G: ColumnTop {
+  /// Columns outside of the bounding box are left empty without building the geometry
+  fn column_top(&self, xy: IVec2) -> Option<i32> {
+    match self.get_in_column(xy) {
+      Some(geometry) => geometry.column_top(xy),
+      None => Some(crate::WORLD_MIN_Z - 1)
+    }
+  }
+}
+
+impl<G> MaterialGeometry for Deferred<G>
+where G: MaterialGeometry {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.get_at(pos).and_then(|geometry| geometry.block_material_at(pos))
+  }
+
+  /// Builds the geometry, since there is no telling what it places until it has been
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.get().possible_blocks(out);
+  }
+}
@@ -0,0 +1,98 @@
+//! Hollow features which keep the ocean out of the space they enclose.
+//!
+//! The ocean claims every position at or below its surface that no earlier feature has claimed, so the inside of
+//! anything hollow beneath the surface would otherwise fill with water. Features which enclose such a space implement
+//! `Enclosure`, and the ocean is wrapped in `Drained`, which leaves those spaces empty while the features themselves
+//! provide the walls around them. `find_leak` checks that those walls don't have any holes in them.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
+use glam::IVec3;
+
+use super::deferred::Deferred;
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// The directions to each of a position's six neighbours
+const NEIGHBOURS: [IVec3; 6] = [
+  glam::const_ivec3!([1, 0, 0]),
+  glam::const_ivec3!([-1, 0, 0]),
+  glam::const_ivec3!([0, 1, 0]),
+  glam::const_ivec3!([0, -1, 0]),
+  glam::const_ivec3!([0, 0, 1]),
+  glam::const_ivec3!([0, 0, -1])
+];
+
+/// A feature whose blocks wall off spaces that must be kept dry
+pub trait Enclosure: Geometry {
+  /// The spaces inside this feature's walls which the ocean must not fill, in this feature's own coordinates
+  fn dry_volumes(&self) -> Vec<BoundingBox>;
+}
+
+/// Wraps a geometry, leaving every position inside any of a set of dry volumes empty.
+/// The dry volumes are only worked out once a position within the bounding box they were deferred with is sampled.
+#[derive(Debug, Clone)]
+pub struct Drained<G> {
+  geometry: G,
+  dry_volumes: Deferred<Vec<BoundingBox>>
+}
+
+impl<G> Drained<G> {
+  pub fn new(geometry: G, dry_volumes: Deferred<Vec<BoundingBox>>) -> Self {
+    Drained { geometry, dry_volumes }
+  }
+
+  /// Whether a position is inside any of the dry volumes
+  pub fn is_dry_at(&self, pos: IVec3) -> bool {
+    self.dry_volumes.get_at(pos).map_or(false, |volumes| volumes.iter().any(|volume| volume.contains(pos)))
+  }
+}
+
+impl<G> Deref for Drained<G> {
+  type Target = G;
+
+  #[inline]
+  fn deref(&self) -> &G {
+    &self.geometry
+  }
+}
+
+impl<G> Geometry for Drained<G>
+where G: Geometry {
+  /// The dry volumes are only ever carved out of the inner geometry, so its bounding box is unchanged
+  #[inline]
+  fn bounding_box(&self) -> BoundingBox {
+    self.geometry.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    !self.is_dry_at(pos) && self.geometry.block_at(pos)
+  }
+}
+
+impl<G> MaterialGeometry for Drained<G>
+where G: MaterialGeometry {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    if self.is_dry_at(pos) { return None };
+    self.geometry.block_material_at(pos)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometry.possible_blocks(out);
+  }
+}
+
+/// Finds an empty position just outside of a dry volume which is next to an empty position inside it, if any.
+/// Water flooding in from outside could get in through any such position, so it marks a hole in the walls.
+/// Only the walls themselves are considered,
+/// so a hole which happens to be plugged by some other feature is still found.
+pub fn find_leak(walls: &impl Geometry, volume: BoundingBox) -> Option<IVec3> {
+  let (min, max) = (volume.min, volume.max);
+  (min.z..=max.z)
+    .flat_map(|z| (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z))))
+    .filter(|&pos| !walls.block_at(pos))
+    .flat_map(|pos| NEIGHBOURS.iter().map(move |&offset| pos + offset))
+    .find(|&neighbour| !volume.contains(neighbour) && !walls.block_at(neighbour))
+}
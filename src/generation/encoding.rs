@@ -0,0 +1,244 @@
+//! A minimal binary encoding for persisting generated features to disk.
+//! All values are little-endian, and decoding returns `None` on malformed input rather than panicking.
+use std::borrow::Cow;
+
+use glam::{IVec2, IVec3};
+
+use super::{Block, BoundingBox};
+
+
+
+pub trait Encode: Sized {
+  fn encode(&self, encoder: &mut Encoder);
+
+  fn decode(decoder: &mut Decoder) -> Option<Self>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+  bytes: Vec<u8>
+}
+
+impl Encoder {
+  pub fn new() -> Self {
+    Encoder::default()
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.bytes
+  }
+
+  pub fn put_bytes(&mut self, bytes: &[u8]) {
+    self.bytes.extend_from_slice(bytes);
+  }
+
+  pub fn put_u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  pub fn put_u32(&mut self, value: u32) {
+    self.put_bytes(&value.to_le_bytes());
+  }
+
+  pub fn put_u64(&mut self, value: u64) {
+    self.put_bytes(&value.to_le_bytes());
+  }
+
+  pub fn put_i32(&mut self, value: i32) {
+    self.put_bytes(&value.to_le_bytes());
+  }
+
+  pub fn put_bool(&mut self, value: bool) {
+    self.put_u8(value as u8);
+  }
+
+  pub fn put_str(&mut self, value: &str) {
+    self.put_u32(value.len() as u32);
+    self.put_bytes(value.as_bytes());
+  }
+
+  pub fn put<T: Encode>(&mut self, value: &T) {
+    value.encode(self);
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+  bytes: &'a [u8]
+}
+
+impl<'a> Decoder<'a> {
+  pub fn new(bytes: &'a [u8]) -> Self {
+    Decoder { bytes }
+  }
+
+  /// Whether or not every byte has been consumed
+  pub fn is_finished(&self) -> bool {
+    self.bytes.is_empty()
+  }
+
+  pub fn take_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+    if len > self.bytes.len() { return None };
+    let (taken, rest) = self.bytes.split_at(len);
+    self.bytes = rest;
+    Some(taken)
+  }
+
+  fn take_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+    self.take_bytes(N)?.try_into().ok()
+  }
+
+  pub fn take_u8(&mut self) -> Option<u8> {
+    self.take_array::<1>().map(|[value]| value)
+  }
+
+  pub fn take_u32(&mut self) -> Option<u32> {
+    self.take_array().map(u32::from_le_bytes)
+  }
+
+  pub fn take_u64(&mut self) -> Option<u64> {
+    self.take_array().map(u64::from_le_bytes)
+  }
+
+  pub fn take_i32(&mut self) -> Option<i32> {
+    self.take_array().map(i32::from_le_bytes)
+  }
+
+  pub fn take_bool(&mut self) -> Option<bool> {
+    match self.take_u8()? {
+      0 => Some(false),
+      1 => Some(true),
+      _ => None
+    }
+  }
+
+  pub fn take_string(&mut self) -> Option<String> {
+    let len = self.take_u32()? as usize;
+    let bytes = self.take_bytes(len)?;
+    String::from_utf8(bytes.to_vec()).ok()
+  }
+
+  pub fn take<T: Encode>(&mut self) -> Option<T> {
+    T::decode(self)
+  }
+}
+
+impl Encode for i32 {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_i32(*self);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    decoder.take_i32()
+  }
+}
+
+impl Encode for bool {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_bool(*self);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    decoder.take_bool()
+  }
+}
+
+impl Encode for IVec2 {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_i32(self.x);
+    encoder.put_i32(self.y);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(IVec2::new(decoder.take_i32()?, decoder.take_i32()?))
+  }
+}
+
+impl Encode for IVec3 {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_i32(self.x);
+    encoder.put_i32(self.y);
+    encoder.put_i32(self.z);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(IVec3::new(decoder.take_i32()?, decoder.take_i32()?, decoder.take_i32()?))
+  }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_u32(self.len() as u32);
+    for value in self.iter() {
+      value.encode(encoder);
+    };
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let len = decoder.take_u32()? as usize;
+    // The length is untrusted, so don't preallocate based on it
+    (0..len).map(|_| T::decode(decoder)).collect()
+  }
+}
+
+impl<T: Encode> Encode for Option<T> {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_bool(self.is_some());
+    if let Some(value) = self {
+      value.encode(encoder);
+    };
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    match decoder.take_bool()? {
+      true => T::decode(decoder).map(Some),
+      false => Some(None)
+    }
+  }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+  fn encode(&self, encoder: &mut Encoder) {
+    self.0.encode(encoder);
+    self.1.encode(encoder);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some((A::decode(decoder)?, B::decode(decoder)?))
+  }
+}
+
+impl Encode for Block {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_str(&self.base_block);
+    match &self.extra_block {
+      Some(extra_block) => {
+        encoder.put_bool(true);
+        encoder.put_str(extra_block);
+      },
+      None => encoder.put_bool(false)
+    };
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    let base_block = Cow::Owned(decoder.take_string()?);
+    let extra_block = match decoder.take_bool()? {
+      true => Some(Cow::Owned(decoder.take_string()?)),
+      false => None
+    };
+
+    Some(Block { base_block, extra_block })
+  }
+}
+
+impl Encode for BoundingBox {
+  fn encode(&self, encoder: &mut Encoder) {
+    self.min.encode(encoder);
+    self.max.encode(encoder);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    // A swapped box can only come from a corrupted encoding
+    BoundingBox::try_new(decoder.take()?, decoder.take()?).ok()
+  }
+}
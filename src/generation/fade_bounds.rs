@@ -0,0 +1,185 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
+use glam::{IVec2, IVec3};
+
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{hash3, hash_to_unit};
+
+
+
+/// The width of the band in which features dissolve with `--edge-style fade`, unless given with `--fade-width`
+pub const DEFAULT_FADE_WIDTH: u32 = 32;
+
+/// How features are cut off at the edge of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeStyle {
+  /// Features end abruptly at the edge
+  #[default]
+  Hard,
+  /// Features dissolve over a band of the given width inside the edge
+  Fade(u32)
+}
+
+impl EdgeStyle {
+  fn fade_width(self) -> u32 {
+    match self {
+      EdgeStyle::Hard => 0,
+      EdgeStyle::Fade(width) => width
+    }
+  }
+}
+
+/// Clips a geometry to a range of columns, dissolving it within a band inside the edge of that range.
+/// Within the band, each block is kept with a chance that falls from 1 at the inner edge of the band to 0 at the
+/// edge of the range. Whether a block is kept depends only on its position, so every call agrees.
+#[derive(Debug, Clone)]
+pub struct FadeBounds<G> {
+  geometry: G,
+  bounds_min: IVec2,
+  bounds_max: IVec2,
+  fade_width: u32,
+  seed: u64
+}
+
+impl<G> FadeBounds<G> {
+  pub fn new(geometry: G, min: IVec2, max: IVec2, style: EdgeStyle, seed: u64) -> Self {
+    FadeBounds {
+      geometry,
+      bounds_min: min,
+      bounds_max: max,
+      fade_width: style.fade_width(),
+      seed
+    }
+  }
+
+  /// Wraps another geometry in the same bounds and fade as this one, keeping exactly the same blocks
+  pub fn apply_to<H>(&self, geometry: H) -> FadeBounds<H> {
+    FadeBounds {
+      geometry,
+      bounds_min: self.bounds_min,
+      bounds_max: self.bounds_max,
+      fade_width: self.fade_width,
+      seed: self.seed
+    }
+  }
+
+  /// Whether a block of the geometry at this position is kept
+  pub fn keeps(&self, pos: IVec3) -> bool {
+    let distance = IVec2::min(pos.truncate() - self.bounds_min, self.bounds_max - pos.truncate()).min_element();
+    if distance < 0 { return false };
+    if distance >= self.fade_width as i32 { return true };
+    hash_to_unit(hash3(self.seed, pos)) < distance as f64 / self.fade_width as f64
+  }
+}
+
+impl<G> Deref for FadeBounds<G> {
+  type Target = G;
+
+  #[inline]
+  fn deref(&self) -> &G {
+    &self.geometry
+  }
+}
+
+impl<G> Geometry for FadeBounds<G>
+where G: Geometry {
+  fn bounding_box(&self) -> BoundingBox {
+    let mut bounding_box = self.geometry.bounding_box();
+    bounding_box.min.x = bounding_box.min.x.max(self.bounds_min.x);
+    bounding_box.min.y = bounding_box.min.y.max(self.bounds_min.y);
+    bounding_box.max.x = bounding_box.max.x.min(self.bounds_max.x);
+    bounding_box.max.y = bounding_box.max.y.min(self.bounds_max.y);
+    bounding_box
+  }
+
+  #[inline]
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.keeps(pos) && self.geometry.block_at(pos)
+  }
+}
+
+impl<G> MaterialGeometry for FadeBounds<G>
+where G: MaterialGeometry {
+  #[inline]
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    if !self.keeps(pos) { return None };
+    self.geometry.block_material_at(pos)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometry.possible_blocks(out);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::SolidBox;
+
+  /// A solid slab running out past both ends of bounds 100 columns long, and so wide that only its ends are faded
+  fn faded_slab(style: EdgeStyle) -> FadeBounds<SolidBox> {
+    let solid = SolidBox::new(BoundingBox::new(IVec3::new(-10, 0, 0), IVec3::new(110, 19, 9)), blocks::SAND);
+    FadeBounds::new(solid, IVec2::new(0, -1000), IVec2::new(99, 1000), style, 7)
+  }
+
+  /// The number of blocks kept in each column of the slab along x, out of 200
+  fn kept_along_x(faded: &FadeBounds<SolidBox>) -> Vec<(i32, usize)> {
+    (-10..=110)
+      .map(|x| {
+        let kept = (0..20).flat_map(|y| (0..10).map(move |z| IVec3::new(x, y, z)))
+          .filter(|&pos| faded.block_at(pos))
+          .count();
+        (x, kept)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn retention_ramps_up_across_the_band() {
+    let faded = faded_slab(EdgeStyle::Fade(32));
+    let kept = kept_along_x(&faded);
+    for &(x, count) in kept.iter() {
+      match x {
+        // Nothing is kept outside of the bounds or in their outermost column, and everything is kept past the band
+        _ if x < 1 || x > 98 => assert_eq!(count, 0, "x={}", x),
+        32..=67 => assert_eq!(count, 200, "x={}", x),
+        _ => ()
+      };
+    };
+
+    // Counted four columns at a time from the boundary inwards, retention only rises
+    let buckets = (0..8)
+      .map(|bucket| kept.iter().filter(|&&(x, _)| x / 4 == bucket && x >= 0).map(|&(_, count)| count).sum::<usize>())
+      .collect::<Vec<usize>>();
+    assert!(buckets.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", buckets);
+    assert!(buckets[0] < 80, "{} of 800 blocks kept at the outer edge of the band", buckets[0]);
+    assert!(buckets[7] > 680, "{} of 800 blocks kept at the inner edge of the band", buckets[7]);
+  }
+
+  #[test]
+  fn the_fade_is_the_same_on_every_call() {
+    let faded = faded_slab(EdgeStyle::Fade(32));
+    assert_eq!(kept_along_x(&faded), kept_along_x(&faded.clone()));
+    let same = faded_slab(EdgeStyle::Fade(32)).apply_to(SolidBox::new(faded.bounding_box(), blocks::GRAVEL));
+    for x in -2..=34 {
+      for z in 0..10 {
+        let pos = IVec3::new(x, 3, z);
+        assert_eq!(faded.block_at(pos), faded.block_material_at(pos).is_some(), "at {}", pos);
+        assert_eq!(faded.block_at(pos), same.block_at(pos), "at {}", pos);
+      };
+    };
+  }
+
+  #[test]
+  fn hard_edges_keep_everything_within_the_bounds() {
+    let faded = faded_slab(EdgeStyle::Hard);
+    for (x, count) in kept_along_x(&faded) {
+      assert_eq!(count, if (0..=99).contains(&x) { 200 } else { 0 }, "x={}", x);
+    };
+
+    assert_eq!(faded.bounding_box(), BoundingBox::new(IVec3::new(0, 0, 0), IVec3::new(99, 19, 9)));
+  }
+}
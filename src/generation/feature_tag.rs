@@ -0,0 +1,51 @@
+/// The kind of feature which placed a block, as reported by the traced sampling paths.
+/// These are only used to attribute blocks to features in statistics, so they are coarser than the features themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FeatureTag {
+  Bedrock,
+  Ocean,
+  Outcrop,
+  BubbleLift,
+  /// The slabs and lattice of a landmass, along with the shadows baked onto its surface
+  LandmassSlab,
+  Road,
+  Pillar,
+  Skirt,
+  /// The platforms beneath buildings overhanging the edge of a landmass, and the struts bracing them
+  Cantilever,
+  /// Buildings and the spires on top of them
+  Building,
+  Shaft,
+  Prop,
+  /// The wall around the lowest layer
+  Wall,
+  /// Everything small on or beneath a landmass: plazas, pools, vegetation, lamp posts, the underside, details and
+  /// scattered decorations
+  Decoration
+}
+
+impl FeatureTag {
+  pub fn name(self) -> &'static str {
+    match self {
+      FeatureTag::Bedrock => "bedrock",
+      FeatureTag::Ocean => "ocean",
+      FeatureTag::Outcrop => "outcrop",
+      FeatureTag::BubbleLift => "bubble lift",
+      FeatureTag::LandmassSlab => "landmass slab",
+      FeatureTag::Road => "road",
+      FeatureTag::Pillar => "pillar",
+      FeatureTag::Skirt => "skirt",
+      FeatureTag::Cantilever => "cantilever",
+      FeatureTag::Building => "building",
+      FeatureTag::Shaft => "shaft",
+      FeatureTag::Prop => "prop",
+      FeatureTag::Wall => "wall",
+      FeatureTag::Decoration => "decoration"
+    }
+  }
+}
+
+/// Pairs a value with the given tag, for tagging the results of the untraced sampling methods
+pub fn tagged<T>(tag: FeatureTag) -> impl FnOnce(T) -> (T, FeatureTag) {
+  move |value| (value, tag)
+}
@@ -0,0 +1,286 @@
+//! Simple geometries with analytically known shapes, for exercising combinators and renderers.
+//!
+//! Each fixture is solid wherever its shape says it is, and made of a single configurable block,
+//! so the expected result of sampling any position can be worked out by hand:
+//!
+//! - `SolidBox` fills its bounding box completely.
+//! - `Sphere` fills every position within `radius` of its center, measured between block positions.
+//! - `Checkerboard` alternates between solid and empty squares of `period` by `period` columns,
+//!   extending infinitely across x and y between two z values. The square containing 0,0 is solid.
+//! - `SingleBlock` is a single solid position.
+//! - `HollowBox` is the one-block-thick shell of its bounding box, optionally with a single hole knocked through it.
+//!   As an `Enclosure`, it keeps everything inside the shell dry.
+//!
+//! Along with a few 2D noise functions with known values, for handing to the features which take their noise
+//! through `DynNoise`, like `Ocean::with_noise`, `Bedrock::with_noise` and `LandmassShape::from_noise`:
+//!
+//! - `ConstantNoise` is the same everywhere.
+//! - `StepNoise` is one value west of a given x and another from it eastwards.
+//! - `RadialRamp` falls by 1 per block away from its center, crossing 0 at its radius.
+//! - `LookupNoise` reads its values from a 2D array of columns, and is a fixed value outside of it.
+//!
+//! `positions_in` walks every position of a bounding box, for comparing geometries exhaustively over a small region.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{DVec2, IVec2, IVec3};
+use noise::NoiseFn;
+
+use super::enclosure::Enclosure;
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// Every position within a bounding box, which should be small enough to walk block by block
+pub fn positions_in(bounding_box: BoundingBox) -> impl Iterator<Item = IVec3> {
+  let (min, max) = (bounding_box.min, bounding_box.max);
+  (min.z..=max.z).flat_map(move |z| {
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+  })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolidBox {
+  pub bounding_box: BoundingBox,
+  pub block: Block
+}
+
+impl SolidBox {
+  pub fn new(bounding_box: BoundingBox, block: Block) -> Self {
+    SolidBox { bounding_box, block }
+  }
+}
+
+impl Geometry for SolidBox {
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.bounding_box.contains(pos)
+  }
+}
+
+impl MaterialGeometry for SolidBox {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&self.block))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.block.clone());
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sphere {
+  pub center: IVec3,
+  pub radius: i32,
+  pub block: Block
+}
+
+impl Sphere {
+  pub fn new(center: IVec3, radius: i32, block: Block) -> Self {
+    Sphere { center, radius, block }
+  }
+}
+
+impl Geometry for Sphere {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::try_new(self.center - self.radius, self.center + self.radius).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let offset = pos - self.center;
+    offset.dot(offset) <= self.radius * self.radius
+  }
+}
+
+impl MaterialGeometry for Sphere {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&self.block))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.block.clone());
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkerboard {
+  pub period: i32,
+  pub min_z: i32,
+  pub max_z: i32,
+  pub block: Block
+}
+
+impl Checkerboard {
+  pub fn new(period: i32, min_z: i32, max_z: i32, block: Block) -> Self {
+    assert!(period > 0, "checkerboard period must be positive");
+    Checkerboard { period, min_z, max_z, block }
+  }
+}
+
+impl Geometry for Checkerboard {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::unbounded_xy(self.min_z, self.max_z)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let square = pos.x.div_euclid(self.period) + pos.y.div_euclid(self.period);
+    pos.z >= self.min_z && pos.z <= self.max_z && square.rem_euclid(2) == 0
+  }
+}
+
+impl MaterialGeometry for Checkerboard {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&self.block))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.block.clone());
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingleBlock {
+  pub pos: IVec3,
+  pub block: Block
+}
+
+impl SingleBlock {
+  pub fn new(pos: IVec3, block: Block) -> Self {
+    SingleBlock { pos, block }
+  }
+}
+
+impl Geometry for SingleBlock {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::new(self.pos, self.pos)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    pos == self.pos
+  }
+}
+
+impl MaterialGeometry for SingleBlock {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&self.block))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.block.clone());
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HollowBox {
+  pub bounding_box: BoundingBox,
+  /// A position in the shell which is left empty, if any
+  pub hole: Option<IVec3>,
+  pub block: Block
+}
+
+impl HollowBox {
+  pub fn new(bounding_box: BoundingBox, block: Block) -> Self {
+    HollowBox { bounding_box, hole: None, block }
+  }
+
+  pub fn with_hole(self, hole: IVec3) -> Self {
+    HollowBox { hole: Some(hole), ..self }
+  }
+}
+
+impl Geometry for HollowBox {
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let (min, max) = (self.bounding_box.min, self.bounding_box.max);
+    let is_shell = pos.cmpeq(min).any() || pos.cmpeq(max).any();
+    self.bounding_box.contains(pos) && is_shell && self.hole != Some(pos)
+  }
+}
+
+impl MaterialGeometry for HollowBox {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.block_at(pos).then(|| Cow::Borrowed(&self.block))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.block.clone());
+  }
+}
+
+impl Enclosure for HollowBox {
+  /// Everything inside the shell, which is nothing if the box is too thin to have an inside
+  fn dry_volumes(&self) -> Vec<BoundingBox> {
+    BoundingBox::try_new(self.bounding_box.min + 1, self.bounding_box.max - 1).into_iter().collect()
+  }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstantNoise {
+  pub value: f64
+}
+
+impl NoiseFn<f64, 2> for ConstantNoise {
+  fn get(&self, _point: impl Into<[f64; 2]>) -> f64 {
+    self.value
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepNoise {
+  /// The x value from which `above` is given
+  pub step_x: f64,
+  pub below: f64,
+  pub above: f64
+}
+
+impl NoiseFn<f64, 2> for StepNoise {
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    let [x, _] = point.into();
+    if x < self.step_x { self.below } else { self.above }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadialRamp {
+  pub center: DVec2,
+  pub radius: f64
+}
+
+impl NoiseFn<f64, 2> for RadialRamp {
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    self.radius - self.center.distance(DVec2::from(point.into()))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupNoise {
+  /// The column that the first value of the first row is at. Rows run along x, and each row is a column further in y.
+  pub origin: IVec2,
+  pub rows: Vec<Vec<f64>>,
+  /// The value everywhere outside of the rows
+  pub outside: f64
+}
+
+impl LookupNoise {
+  pub fn new(origin: IVec2, rows: Vec<Vec<f64>>, outside: f64) -> Self {
+    LookupNoise { origin, rows, outside }
+  }
+}
+
+impl NoiseFn<f64, 2> for LookupNoise {
+  /// Each point takes the value of the column it falls in
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    let offset = DVec2::from(point.into()).floor().as_ivec2() - self.origin;
+    match (usize::try_from(offset.x), usize::try_from(offset.y)) {
+      (Ok(x), Ok(y)) => self.rows.get(y).and_then(|row| row.get(x)).copied().unwrap_or(self.outside),
+      _ => self.outside
+    }
+  }
+}
@@ -1,9 +1,42 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
 use glam::IVec3;
 
 use super::{Block, BoundingBox, Geometry, MaterialGeometry};
 
 
 
+/// The largest intersection volume which `geometries_overlap` will sample block by block
+const MAX_OVERLAP_SAMPLES: u64 = 1 << 16;
+
+
+
+/// Whether two geometries share any blocks.
+/// Only the region where their bounding boxes intersect is sampled, stopping at the first shared block.
+/// If that region is unreasonably large, this conservatively assumes that they overlap.
+pub fn geometries_overlap(g1: &impl Geometry, g2: &impl Geometry) -> bool {
+  let region = match g1.bounding_box().intersection(g2.bounding_box()) {
+    Some(region) => region,
+    None => return false
+  };
+
+  if region.volume() > MAX_OVERLAP_SAMPLES {
+    return true;
+  };
+
+  (region.min.z..=region.max.z).any(|z| {
+    (region.min.y..=region.max.y).any(|y| {
+      (region.min.x..=region.max.x).any(|x| {
+        let pos = IVec3::new(x, y, z);
+        g1.block_at(pos) && g2.block_at(pos)
+      })
+    })
+  })
+}
+
+
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Intersect<G1, G2> {
   geometry1: G1,
@@ -37,7 +70,7 @@ where
   G1: MaterialGeometry,
   G2: Geometry
 {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     let block = self.geometry1.block_material_at(pos)?;
     if self.geometry2.block_at(pos) {
       Some(block)
@@ -45,4 +78,116 @@ where
       None
     }
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometry1.possible_blocks(out);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{positions_in, Checkerboard, HollowBox, SingleBlock, SolidBox, Sphere};
+
+  fn solid() -> SolidBox {
+    SolidBox::new(BoundingBox::new(IVec3::ZERO, IVec3::new(7, 7, 3)), blocks::DEEPSLATE)
+  }
+
+  #[test]
+  fn intersection_has_blocks_only_where_both_do() {
+    let checkerboard = Checkerboard::new(2, 1, 2, blocks::SAND);
+    let intersect = Intersect::new(solid(), checkerboard.clone());
+    for pos in positions_in(BoundingBox::new(IVec3::splat(-2), IVec3::splat(9))) {
+      let expected = solid().block_at(pos) && checkerboard.block_at(pos);
+      assert_eq!(intersect.block_at(pos), expected, "at {}", pos);
+      assert_eq!(intersect.block_material_at(pos).is_some(), expected, "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn intersection_takes_the_material_of_the_first_geometry() {
+    let intersect = Intersect::new(solid(), Sphere::new(IVec3::new(3, 3, 2), 2, blocks::GRAVEL));
+    assert_eq!(intersect.block_material_at(IVec3::new(3, 3, 2)).as_deref(), Some(&blocks::DEEPSLATE));
+    assert_eq!(intersect.block_material_at(IVec3::new(0, 0, 0)), None);
+
+    let mut out = BTreeSet::new();
+    intersect.possible_blocks(&mut out);
+    assert_eq!(out, BTreeSet::from([blocks::DEEPSLATE]));
+  }
+
+  #[test]
+  fn intersection_bounding_box_covers_both() {
+    let single = SingleBlock::new(IVec3::new(12, 0, 0), blocks::SAND);
+    let intersect = Intersect::new(solid(), single);
+    assert_eq!(intersect.bounding_box(), BoundingBox::new(IVec3::ZERO, IVec3::new(12, 7, 3)));
+    assert!(!intersect.block_at(IVec3::new(12, 0, 0)));
+  }
+
+  #[test]
+  fn overlap_is_found_only_where_blocks_are_shared() {
+    let inside = SingleBlock::new(IVec3::new(7, 7, 3), blocks::SAND);
+    let outside = SingleBlock::new(IVec3::new(8, 7, 3), blocks::SAND);
+    assert!(geometries_overlap(&solid(), &inside));
+    assert!(!geometries_overlap(&solid(), &outside));
+
+    // The spheres' bounding boxes intersect at their corners, but the spheres themselves don't reach them
+    let sphere = Sphere::new(IVec3::new(0, 0, 0), 3, blocks::SAND);
+    let other = Sphere::new(IVec3::new(5, 5, 5), 3, blocks::SAND);
+    assert!(!geometries_overlap(&sphere, &other));
+    assert!(geometries_overlap(&sphere, &Sphere::new(IVec3::new(5, 0, 0), 3, blocks::SAND)));
+  }
+
+  #[test]
+  fn touching_geometries_overlap_only_where_they_share_a_face() {
+    // Side by side, the boxes meet without sharing a block
+    let beside = SolidBox::new(BoundingBox::new(IVec3::new(8, 0, 0), IVec3::new(12, 7, 3)), blocks::SAND);
+    assert!(!geometries_overlap(&solid(), &beside));
+    assert!(!geometries_overlap(&beside, &solid()));
+    // Moved back by one block, they share a single face
+    let sharing = SolidBox::new(BoundingBox::new(IVec3::new(7, 0, 0), IVec3::new(12, 7, 3)), blocks::SAND);
+    assert!(geometries_overlap(&solid(), &sharing));
+    assert!(geometries_overlap(&sharing, &solid()));
+    // Sharing only a corner block is enough
+    let corner = SolidBox::new(BoundingBox::new(IVec3::new(7, 7, 3), IVec3::new(9, 9, 5)), blocks::SAND);
+    assert!(geometries_overlap(&solid(), &corner));
+  }
+
+  #[test]
+  fn disjoint_geometries_dont_overlap() {
+    let far = SolidBox::new(BoundingBox::new(IVec3::splat(100), IVec3::splat(110)), blocks::SAND);
+    assert!(!geometries_overlap(&solid(), &far));
+    assert!(!geometries_overlap(&far, &solid()));
+    // Apart along only one axis, with the other two ranges shared
+    let above = SolidBox::new(BoundingBox::new(IVec3::new(0, 0, 5), IVec3::new(7, 7, 9)), blocks::SAND);
+    assert!(!geometries_overlap(&solid(), &above));
+  }
+
+  #[test]
+  fn nested_geometries_overlap_only_if_the_inner_one_meets_a_block_of_the_outer() {
+    // Anything within a solid box shares its blocks
+    let inner = SolidBox::new(BoundingBox::new(IVec3::new(2, 2, 1), IVec3::new(4, 4, 2)), blocks::SAND);
+    assert!(geometries_overlap(&solid(), &inner));
+    assert!(geometries_overlap(&inner, &solid()));
+    assert!(geometries_overlap(&solid(), &solid()));
+
+    // But a hollow box only has blocks at its walls, so a small enough box can float inside it
+    let shell = HollowBox::new(BoundingBox::new(IVec3::ZERO, IVec3::splat(10)), blocks::DEEPSLATE);
+    let floating = SolidBox::new(BoundingBox::new(IVec3::splat(3), IVec3::splat(7)), blocks::SAND);
+    assert!(!geometries_overlap(&shell, &floating));
+    assert!(!geometries_overlap(&floating, &shell));
+    let against_wall = SolidBox::new(BoundingBox::new(IVec3::new(0, 3, 3), IVec3::splat(7)), blocks::SAND);
+    assert!(geometries_overlap(&shell, &against_wall));
+  }
+
+  #[test]
+  fn overlap_is_assumed_over_huge_regions() {
+    // The sphere floats inside the shell without touching it, but the region they share is far too large to check
+    let shell = HollowBox::new(BoundingBox::new(IVec3::ZERO, IVec3::splat(100)), blocks::SAND);
+    let sphere = Sphere::new(IVec3::splat(50), 40, blocks::SAND);
+    assert!(geometries_overlap(&shell, &sphere));
+    // Unless their bounding boxes don't meet at all
+    let checkerboard = Checkerboard::new(1, 0, 0, blocks::SAND);
+    assert!(!geometries_overlap(&checkerboard, &Checkerboard::new(1, 1, 1, blocks::SAND)));
+  }
 }
@@ -1,3 +1,7 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
 use glam::{IVec2, IVec3};
 
 use super::{Block, BoundingBox, Geometry, MaterialGeometry};
@@ -8,7 +12,10 @@ use super::{Block, BoundingBox, Geometry, MaterialGeometry};
 pub struct LimitBounds<G> {
   geometry: G,
   bounds_min: IVec2,
-  bounds_max: IVec2
+  bounds_max: IVec2,
+  /// The lowest and highest z values within the bounds, which are unlimited unless set by `with_height`
+  min_z: i32,
+  max_z: i32
 }
 
 impl<G> LimitBounds<G> {
@@ -16,9 +23,31 @@ impl<G> LimitBounds<G> {
     LimitBounds {
       geometry,
       bounds_min: min,
-      bounds_max: max
+      bounds_max: max,
+      min_z: i32::MIN,
+      max_z: i32::MAX
     }
   }
+
+  /// Also cuts the geometry off beneath `min_z` and above `max_z`
+  pub fn with_height(self, min_z: i32, max_z: i32) -> Self {
+    LimitBounds { min_z, max_z, ..self }
+  }
+}
+
+/// The distance from a column to the nearest edge of the bounds from `min` to `max` inclusive,
+/// which is 0 in the outermost columns within them and negative outside of them
+pub fn distance_to_edge(min: IVec2, max: IVec2, pos: IVec2) -> i32 {
+  (pos - min).min(max - pos).min_element()
+}
+
+impl<G> Deref for LimitBounds<G> {
+  type Target = G;
+
+  #[inline]
+  fn deref(&self) -> &G {
+    &self.geometry
+  }
 }
 
 impl<G> Geometry for LimitBounds<G>
@@ -29,6 +58,8 @@ where G: Geometry {
     bounding_box.min.y = bounding_box.min.y.max(self.bounds_min.y);
     bounding_box.max.x = bounding_box.max.x.min(self.bounds_max.x);
     bounding_box.max.y = bounding_box.max.y.min(self.bounds_max.y);
+    bounding_box.min.z = bounding_box.min.z.max(self.min_z);
+    bounding_box.max.z = bounding_box.max.z.min(self.max_z);
     bounding_box
   }
 
@@ -41,7 +72,64 @@ where G: Geometry {
 impl<G> MaterialGeometry for LimitBounds<G>
 where G: MaterialGeometry {
   #[inline]
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     self.geometry.block_material_at(pos)
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometry.possible_blocks(out);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{Checkerboard, SolidBox};
+
+  #[test]
+  fn bounding_box_is_clipped_horizontally() {
+    let checkerboard = Checkerboard::new(3, -2, 5, blocks::SAND);
+    let limited = LimitBounds::new(checkerboard, IVec2::new(-10, 0), IVec2::new(20, 8));
+    assert_eq!(limited.bounding_box(), BoundingBox::new(IVec3::new(-10, 0, -2), IVec3::new(20, 8, 5)));
+
+    // A geometry which is smaller than the bounds keeps its own bounding box
+    let solid = SolidBox::new(BoundingBox::new(IVec3::new(2, 2, 0), IVec3::new(4, 4, 1)), blocks::SAND);
+    let limited = LimitBounds::new(solid, IVec2::new(-10, 0), IVec2::new(20, 8));
+    assert_eq!(limited.bounding_box(), BoundingBox::new(IVec3::new(2, 2, 0), IVec3::new(4, 4, 1)));
+  }
+
+  #[test]
+  fn bounding_box_is_clipped_vertically_if_given_a_height() {
+    let checkerboard = Checkerboard::new(3, -2, 5, blocks::SAND);
+    let limited = LimitBounds::new(checkerboard.clone(), IVec2::new(-10, 0), IVec2::new(20, 8)).with_height(0, 3);
+    assert_eq!(limited.bounding_box(), BoundingBox::new(IVec3::new(-10, 0, 0), IVec3::new(20, 8, 3)));
+
+    let limited = LimitBounds::new(checkerboard, IVec2::new(-10, 0), IVec2::new(20, 8)).with_height(-64, 512);
+    assert_eq!(limited.bounding_box(), BoundingBox::new(IVec3::new(-10, 0, -2), IVec3::new(20, 8, 5)));
+  }
+
+  #[test]
+  fn sampling_is_passed_through() {
+    let checkerboard = Checkerboard::new(3, -2, 5, blocks::SAND);
+    let limited = LimitBounds::new(checkerboard.clone(), IVec2::new(-10, 0), IVec2::new(20, 8));
+    for x in -12..=22 {
+      for z in -3..=6 {
+        let pos = IVec3::new(x, 4, z);
+        assert_eq!(limited.block_at(pos), checkerboard.block_at(pos), "at {}", pos);
+        assert_eq!(limited.block_material_at(pos), checkerboard.block_material_at(pos), "at {}", pos);
+      };
+    };
+  }
+
+  #[test]
+  fn distance_to_edge_is_zero_on_the_edge() {
+    let (min, max) = (IVec2::new(-4, 0), IVec2::new(4, 20));
+    assert_eq!(distance_to_edge(min, max, IVec2::new(-4, 10)), 0);
+    assert_eq!(distance_to_edge(min, max, IVec2::new(4, 20)), 0);
+    assert_eq!(distance_to_edge(min, max, IVec2::new(0, 10)), 4);
+    assert_eq!(distance_to_edge(min, max, IVec2::new(0, 2)), 2);
+    assert_eq!(distance_to_edge(min, max, IVec2::new(-6, 10)), -2);
+    assert_eq!(distance_to_edge(min, max, IVec2::new(0, 21)), -1);
+  }
 }
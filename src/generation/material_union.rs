@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::{Deref, DerefMut};
+
+use glam::IVec3;
+
+use super::encoding::{Decoder, Encode, Encoder};
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// A union of geometries where each member geometry is assigned its own material.
+/// Members are checked in the order they were added, so earlier members take priority over later ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialUnion<G> {
+  members: Vec<(G, Block)>
+}
+
+impl<G> MaterialUnion<G> {
+  pub fn new(members: Vec<(G, Block)>) -> Self {
+    MaterialUnion { members }
+  }
+
+  pub fn push<B>(mut self, geometry: G, material: B) -> Self
+  where B: Into<Block> {
+    self.members.push((geometry, material.into()));
+    self
+  }
+}
+
+impl<G> Default for MaterialUnion<G> {
+  fn default() -> Self {
+    MaterialUnion::new(Vec::new())
+  }
+}
+
+impl<G> Deref for MaterialUnion<G> {
+  type Target = Vec<(G, Block)>;
+
+  #[inline]
+  fn deref(&self) -> &Vec<(G, Block)> {
+    &self.members
+  }
+}
+
+impl<G> DerefMut for MaterialUnion<G> {
+  #[inline]
+  fn deref_mut(&mut self) -> &mut Vec<(G, Block)> {
+    &mut self.members
+  }
+}
+
+impl<G> Geometry for MaterialUnion<G>
+where G: Geometry {
+  fn bounding_box(&self) -> BoundingBox {
+    self
+      .members
+      .iter()
+      .map(|(geometry, _)| geometry.bounding_box())
+      .reduce(BoundingBox::join)
+      .unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.members.iter().any(|(geometry, _)| geometry.block_at(pos))
+  }
+}
+
+impl<G> MaterialGeometry for MaterialUnion<G>
+where G: Geometry {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self
+      .members
+      .iter()
+      .find_map(|(geometry, material)| geometry.block_at(pos).then(|| Cow::Borrowed(material)))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend(self.members.iter().map(|(_, material)| material.clone()));
+  }
+}
+
+impl<G> Encode for MaterialUnion<G>
+where G: Encode {
+  fn encode(&self, encoder: &mut Encoder) {
+    self.members.encode(encoder);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(MaterialUnion::new(decoder.take()?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{positions_in, SolidBox};
+
+  /// Three boxes in a row along x, each overlapping the next by two blocks
+  fn boxes() -> [SolidBox; 3] {
+    [0, 3, 6].map(|x| SolidBox::new(BoundingBox::new(IVec3::new(x, 0, 0), IVec3::new(x + 4, 2, 2)), blocks::AIR))
+  }
+
+  fn union() -> MaterialUnion<SolidBox> {
+    let [first, second, third] = boxes();
+    MaterialUnion::default()
+      .push(first, blocks::SAND)
+      .push(second, blocks::GRAVEL)
+      .push(third, blocks::DEEPSLATE)
+  }
+
+  #[test]
+  fn earlier_members_take_priority_where_they_overlap() {
+    let union = union();
+    let material_at = |x: i32| union.block_material_at(IVec3::new(x, 1, 1)).map(Cow::into_owned);
+    let expected = [
+      (-1, None), (0, Some(blocks::SAND)), (4, Some(blocks::SAND)), (5, Some(blocks::GRAVEL)),
+      (7, Some(blocks::GRAVEL)), (8, Some(blocks::DEEPSLATE)), (10, Some(blocks::DEEPSLATE)), (11, None)
+    ];
+
+    for (x, material) in expected {
+      assert_eq!(material_at(x), material, "at x = {}", x);
+    };
+
+    // The members' own order is all that matters, not the order they are found in space
+    let [first, second, third] = boxes();
+    let reversed = MaterialUnion::default()
+      .push(third, blocks::DEEPSLATE)
+      .push(second, blocks::GRAVEL)
+      .push(first, blocks::SAND);
+    assert_eq!(reversed.block_material_at(IVec3::new(4, 1, 1)).as_deref(), Some(&blocks::GRAVEL));
+    assert_eq!(reversed.block_material_at(IVec3::new(6, 1, 1)).as_deref(), Some(&blocks::DEEPSLATE));
+  }
+
+  #[test]
+  fn bounding_box_joins_every_member() {
+    let union = union();
+    assert_eq!(union.bounding_box(), BoundingBox::new(IVec3::ZERO, IVec3::new(10, 2, 2)));
+    for pos in positions_in(BoundingBox::new(IVec3::splat(-1), IVec3::new(11, 3, 3))) {
+      let expected = boxes().iter().any(|member| member.block_at(pos));
+      assert_eq!(union.block_at(pos), expected, "at {}", pos);
+      assert_eq!(union.block_material_at(pos).is_some(), expected, "at {}", pos);
+      assert_eq!(union.bounding_box().contains(pos), expected, "at {}", pos);
+    };
+
+    let mut out = BTreeSet::new();
+    union.possible_blocks(&mut out);
+    assert_eq!(out, BTreeSet::from([blocks::SAND, blocks::GRAVEL, blocks::DEEPSLATE]));
+  }
+}
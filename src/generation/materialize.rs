@@ -1,3 +1,6 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
 use glam::IVec3;
 
 use super::{Block, BoundingBox, Geometry, MaterialGeometry};
@@ -35,11 +38,43 @@ where G: Geometry {
 
 impl<G> MaterialGeometry for Materialize<G>
 where G: Geometry {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     if self.block_at(pos) {
-      Some(self.material.clone())
+      Some(Cow::Borrowed(&self.material))
     } else {
       None
     }
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.material.clone());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{positions_in, Sphere};
+
+  #[test]
+  fn material_replaces_the_geometry_blocks() {
+    let sphere = Sphere::new(IVec3::ZERO, 3, blocks::SAND);
+    let materialized = Materialize::new(blocks::CALCITE, sphere.clone());
+    assert_eq!(materialized.bounding_box(), sphere.bounding_box());
+    for pos in positions_in(BoundingBox::new(IVec3::splat(-4), IVec3::splat(4))) {
+      let expected = sphere.block_at(pos).then(|| blocks::CALCITE);
+      assert_eq!(materialized.block_at(pos), sphere.block_at(pos), "at {}", pos);
+      assert_eq!(materialized.block_material_at(pos).map(Cow::into_owned), expected, "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn material_is_the_only_possible_block() {
+    let materialized = Materialize::new("minecraft:chain[axis=z]", Sphere::new(IVec3::ZERO, 1, blocks::SAND));
+    let mut out = BTreeSet::new();
+    materialized.possible_blocks(&mut out);
+    assert_eq!(out, BTreeSet::from([Block::from("minecraft:chain[axis=z]")]));
+    assert!(matches!(materialized.block_material_at(IVec3::ZERO), Some(Cow::Borrowed(_))));
+  }
 }
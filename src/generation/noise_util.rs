@@ -0,0 +1,249 @@
+//! Helpers for sampling 2D noise functions as integer column heights.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use glam::IVec2;
+use noise::NoiseFn;
+
+use crate::utility::{hash2, quantized_floor};
+
+
+
+/// The number of independently locked maps that a `CachedColumnSampler` spreads its chunks across
+const SHARD_COUNT: usize = 16;
+
+/// The heights of every column within each sampled chunk
+type Shard = Mutex<HashMap<IVec2, Box<[i32; 256]>>>;
+
+/// Samples a noise function at integer column positions, transforming its output into a height.
+/// The transform is applied as `floor((value + offset) * scale)`.
+#[derive(Debug, Clone)]
+pub struct ColumnSampler<N> {
+  noise: N,
+  offset: f64,
+  scale: f64
+}
+
+impl<N: NoiseFn<f64, 2>> ColumnSampler<N> {
+  pub fn new(noise: N) -> Self {
+    ColumnSampler { noise, offset: 0.0, scale: 1.0 }
+  }
+
+  pub fn with_offset(self, offset: f64) -> Self {
+    ColumnSampler { offset, ..self }
+  }
+
+  pub fn with_scale(self, scale: f64) -> Self {
+    ColumnSampler { scale, ..self }
+  }
+
+  /// Wraps this sampler in a cache, so that each column is only ever sampled once
+  pub fn cached(self) -> CachedColumnSampler<N> {
+    CachedColumnSampler::new(self)
+  }
+
+  pub fn height_at(&self, pos: IVec2) -> i32 {
+    quantized_floor((self.noise.get(pos.as_dvec2()) + self.offset) * self.scale)
+  }
+}
+
+/// A `ColumnSampler` which remembers every height it has produced, one chunk at a time.
+/// The first lookup in a chunk samples all 256 of its columns.
+///
+/// Clones share the same cache, which is safe to use from multiple threads at once.
+/// The cache is never evicted, so this should only be used where the sampled area is bounded.
+#[derive(Debug, Clone)]
+pub struct CachedColumnSampler<N> {
+  sampler: ColumnSampler<N>,
+  shards: Arc<[Shard]>,
+  counters: Arc<CacheCounters>
+}
+
+impl<N: NoiseFn<f64, 2>> CachedColumnSampler<N> {
+  pub fn new(sampler: ColumnSampler<N>) -> Self {
+    let shards = (0..SHARD_COUNT).map(|_| Mutex::default()).collect();
+    CachedColumnSampler { sampler, shards, counters: Arc::default() }
+  }
+
+  pub fn height_at(&self, pos: IVec2) -> i32 {
+    let chunk = IVec2::new(pos.x.div_euclid(16), pos.y.div_euclid(16));
+    let index = (pos.y.rem_euclid(16) * 16 + pos.x.rem_euclid(16)) as usize;
+    let shard = &self.shards[hash2(0, chunk) as usize % SHARD_COUNT];
+    if let Some(heights) = shard.lock().unwrap().get(&chunk) {
+      self.counters.record(true);
+      return heights[index];
+    };
+
+    // The lock is released while sampling, so another thread may fill in the same chunk in the meantime,
+    // which is harmless since both will have sampled the same heights
+    self.counters.record(false);
+    let mut heights = Box::new([0; 256]);
+    for (i, height) in heights.iter_mut().enumerate() {
+      let offset = IVec2::new(i as i32 % 16, i as i32 / 16);
+      *height = self.sampler.height_at(chunk * 16 + offset);
+    };
+
+    let height = heights[index];
+    shard.lock().unwrap().entry(chunk).or_insert(heights);
+    height
+  }
+
+  /// The sampler behind this cache, which samples the noise afresh every time
+  pub fn sampler(&self) -> &ColumnSampler<N> {
+    &self.sampler
+  }
+
+  pub fn counters(&self) -> &CacheCounters {
+    &self.counters
+  }
+}
+
+/// A 2D noise function behind a shared pointer, so that a feature can be handed any noise function in place of the one
+/// it would build from its seed without having to be generic over it. `NoiseFn` itself can't be made into a trait
+/// object, since its points are generic.
+#[derive(Clone)]
+pub struct DynNoise(Arc<dyn PointNoise>);
+
+impl DynNoise {
+  pub fn new(noise: impl NoiseFn<f64, 2> + Send + Sync + 'static) -> Self {
+    DynNoise(Arc::new(noise))
+  }
+}
+
+impl fmt::Debug for DynNoise {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("DynNoise")
+  }
+}
+
+impl NoiseFn<f64, 2> for DynNoise {
+  #[inline]
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    self.0.get_point(point.into())
+  }
+}
+
+/// `NoiseFn`, with its point taken as an array so that it can be made into a trait object
+trait PointNoise: Send + Sync {
+  fn get_point(&self, point: [f64; 2]) -> f64;
+}
+
+impl<N: NoiseFn<f64, 2> + Send + Sync> PointNoise for N {
+  #[inline]
+  fn get_point(&self, point: [f64; 2]) -> f64 {
+    self.get(point)
+  }
+}
+
+/// Counts the lookups made in a `CachedColumnSampler` and how many of them were already cached
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+  lookups: AtomicU64,
+  hits: AtomicU64
+}
+
+impl CacheCounters {
+  fn record(&self, hit: bool) {
+    self.lookups.fetch_add(1, Ordering::Relaxed);
+    self.hits.fetch_add(hit as u64, Ordering::Relaxed);
+  }
+
+  pub fn lookups(&self) -> u64 {
+    self.lookups.load(Ordering::Relaxed)
+  }
+
+  pub fn hits(&self) -> u64 {
+    self.hits.load(Ordering::Relaxed)
+  }
+}
+
+/// Formats the counters of a number of labelled caches as a table
+pub fn to_cache_table_string<'a>(entries: impl IntoIterator<Item = (&'static str, &'a CacheCounters)>) -> String {
+  let mut out = format!("{:<12} {:>12} {:>9}\n", "noise cache", "lookups", "hit rate");
+  for (label, counters) in entries {
+    let (lookups, hits) = (counters.lookups(), counters.hits());
+    let hit_rate = if lookups == 0 { 0.0 } else { hits as f64 / lookups as f64 * 100.0 };
+    out += &format!("{:<12} {:>12} {:>8.2}%\n", label, lookups, hit_rate);
+  };
+
+  out
+}
+
+
+#[cfg(test)]
+pub mod tests {
+  use noise::Perlin;
+  use rayon::prelude::*;
+
+  use std::collections::BTreeSet;
+
+  use super::*;
+
+  /// Every column from `min` to `max` (inclusive), in an order which jumps between chunks at random
+  fn scattered_columns(min: IVec2, max: IVec2, seed: u64) -> Vec<IVec2> {
+    let mut columns = (min.y..=max.y).flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y))).collect::<Vec<_>>();
+    columns.sort_by_key(|&pos| hash2(seed, pos));
+    columns
+  }
+
+  /// Asserts that the cached heights of every column from `min` to `max` (inclusive) are the same as the heights
+  /// sampled without the cache, both when each chunk is first sampled and once the whole region is cached
+  pub fn assert_cache_matches_sampler<N: NoiseFn<f64, 2>>(cached: &CachedColumnSampler<N>, min: IVec2, max: IVec2) {
+    for seed in 0..2 {
+      for pos in scattered_columns(min, max, seed) {
+        assert_eq!(cached.height_at(pos), cached.sampler().height_at(pos), "at {}", pos);
+      };
+    };
+  }
+
+  fn perlin_sampler(seed: u32) -> CachedColumnSampler<DynNoise> {
+    let noise = DynNoise::new(noise::ScalePoint::new(Perlin::new(seed)).set_scale(0.07));
+    ColumnSampler::new(noise).with_offset(0.5).with_scale(12.0).cached()
+  }
+
+  #[test]
+  fn cached_heights_match_the_sampler() {
+    let cached = perlin_sampler(3);
+    let (min, max) = (IVec2::new(-40, -40), IVec2::new(39, 39));
+    assert_cache_matches_sampler(&cached, min, max);
+
+    // Each of the 6 by 6 chunks of the region is sampled once, on its first lookup
+    let lookups = 80 * 80 * 2;
+    assert_eq!((cached.counters().lookups(), cached.counters().hits()), (lookups, lookups - 36));
+    let heights = scattered_columns(min, max, 0).into_iter().map(|pos| cached.height_at(pos)).collect::<BTreeSet<_>>();
+    assert!(heights.len() > 5, "the noise barely varies: {:?}", heights);
+  }
+
+  #[test]
+  fn clones_share_their_cache() {
+    let cached = perlin_sampler(4);
+    let clone = cached.clone();
+    assert_eq!(cached.height_at(IVec2::new(-17, 3)), clone.height_at(IVec2::new(-17, 3)));
+    assert_eq!((clone.counters().lookups(), clone.counters().hits()), (2, 1));
+  }
+
+  #[test]
+  fn concurrent_lookups_match_the_sampler() {
+    let cached = perlin_sampler(5);
+    let (min, max) = (IVec2::new(-64, -64), IVec2::new(63, 63));
+    // Every thread races through the same region in a different order, so that chunks are often sampled by several
+    // threads at once
+    let passes: u64 = 16;
+    (0..passes).into_par_iter().for_each(|seed| {
+      for pos in scattered_columns(min, max, seed) {
+        assert_eq!(cached.height_at(pos), cached.sampler().height_at(pos), "at {} in pass {}", pos, seed);
+      };
+    });
+
+    let lookups = 128 * 128 * passes;
+    assert_eq!(cached.counters().lookups(), lookups);
+    assert!(cached.counters().hits() <= lookups - 64, "{} hits", cached.counters().hits());
+    let before = cached.counters().hits();
+    for pos in scattered_columns(min, max, passes) {
+      assert_eq!(cached.height_at(pos), cached.sampler().height_at(pos), "at {} once cached", pos);
+    };
+    assert_eq!(cached.counters().hits() - before, 128 * 128, "every chunk should be cached by now");
+  }
+}
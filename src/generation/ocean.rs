@@ -1,118 +1,484 @@
 //! This geometry module generates the following features:
 //!
 //! - An ocean spanning from y=0 downwards until it meets the sea floor.
-//! - A seafloor that starts at roughly y=-32, with roughly 2 block of gravel and deepslate underneath.
+//!   Optionally, the surface gently dips down to y=-1 in places, leaving air above it.
+//! - A seafloor that starts at roughly y=-32, with roughly 2 block of gravel and deepslate underneath,
+//!   extending all the way down to meet the bedrock.
+//!   Optionally, the sea floor deepens away from the city, and a trench runs around it.
+//!   Optionally, the sea floor ramps up into a sandy beach at the edge of the world, so that the ocean doesn't end
+//!   in a wall of water.
+//!   Optionally, the deepslate is banded with tuff and basalt, in strata which undulate gently.
 //! - Randomly placed seagrass and tall seagrass on the gravel sea floor.
-use glam::{IVec2, IVec3, Vec3Swizzles};
+//!   Optionally, the sea floor responds to the city above it: nothing grows in the shadows of the landmasses, where the
+//!   gravel is mixed with coarse dirt, and the floor is compacted into low mounds around the footings of the pillars.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+use std::iter::repeat;
+
+use glam::{DVec2, IVec2, IVec3, Vec3Swizzles};
 use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::bedrock::Bedrock;
+use super::city::{CityFootprint, FOOTPRINT_FRINGE};
+use super::limit_bounds::distance_to_edge;
+use super::noise_util::{CacheCounters, CachedColumnSampler, ColumnSampler, DynNoise};
+use super::{Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+use crate::utility::{hash2, hash3, hash_to_unit, mix64};
 
-use super::{Block, BoundingBox, Geometry, MaterialGeometry};
 
 
+/// How much deeper the sea floor is at the edge of the world than next to the city, when it deepens away from it
+const MAX_GRADIENT_DEPTH: f64 = 23.0;
+/// Half the width of the band that the trench plunges over
+const TRENCH_HALF_WIDTH: f64 = 12.0;
+/// How much deeper the sea floor is at the middle of the trench,
+/// varying by up to `TRENCH_DEPTH_VARIATION` either way along its length
+const TRENCH_DEPTH: f64 = 10.0;
+const TRENCH_DEPTH_VARIATION: f64 = 2.0;
+/// Gravel doesn't settle beneath this z value, leaving the deepslate of the deepest parts of the sea floor bare
+const ABYSSAL_Z: i32 = -60;
+/// The width of the band at the edge of the world over which the sea floor ramps up into a beach
+const BEACH_WIDTH: i32 = 24;
+/// The number of outermost columns in which the beach is at its full height, keeping them clear of water
+const BEACH_DRY_WIDTH: i32 = 2;
+/// The z value of the top of the beach at the edge of the world, a block above the surface of the ocean
+const BEACH_TOP_Z: i32 = 1;
+/// The sea floor of a beach is sand rather than gravel wherever its top is at or above this z value
+const BEACH_SAND_Z: i32 = -6;
+/// The thickness of the gravel and sand over the deepslate at the top of the beach
+const BEACH_GRAVEL_DEPTH: i32 = 3;
+/// The distance from the middle of a pillar within which the sea floor is compacted and raised into a mound
+const PILLAR_COMPACTION_RADIUS: f32 = 6.0;
+/// Mixed into the hashes that dither the edges of the landmasses' shadows and mix the blocks of the sea floor
+const SHADOW_SALT: u64 = 0x736861646f77;
+const FLOOR_MIX_SALT: u64 = 0x6d6978;
+/// The range of thicknesses of each band of rock beneath the sea floor, when it is banded
+const STRATUM_THICKNESS: std::ops::RangeInclusive<usize> = 3..=6;
+/// How far the boundaries between bands of rock rise and fall either way from column to column
+const STRATA_PERTURBATION: i32 = 2;
+/// Mixed into the seed of the sea floor to lay out the bands of rock
+const STRATA_SALT: u64 = 0x737472617461;
 
 #[derive(Debug, Clone)]
 pub struct Ocean {
-  ocean1: OceanGenerator,
-  ocean2: OceanGenerator,
-  seagrass: SeagrassGenerator
+  /// The seed of the sea floor's noise, which the trench and strata are laid out from
+  seed: u32,
+  /// The noise of the top of the sea floor and of the top of the deepslate beneath it,
+  /// kept so that the sea floor can be deepened after the city is placed
+  floor_noise: (DynNoise, DynNoise),
+  ocean1: CachedColumnSampler<OceanGenerator>,
+  ocean2: CachedColumnSampler<OceanGenerator>,
+  seagrass: CachedColumnSampler<DynNoise>,
+  /// Decides whether the surface is at z=0 or z=-1 in each column, if the surface is wavy
+  surface: Option<CachedColumnSampler<SurfaceGenerator>>,
+  /// Used to find the height of the bedrock, so that the deepslate can be extended down to meet it
+  bedrock: Bedrock,
+  /// The edge of the world that the sea floor ramps up to meet, if it does
+  beach: Option<Beach>,
+  /// The city above the sea floor, if the sea floor responds to it
+  footprint: Option<CityFootprint>,
+  /// The bands of rock beneath the gravel, if the deepslate is banded
+  strata: Option<Strata>
 }
 
 impl Ocean {
-  pub fn new<R: Rng>(source_rng: &mut R) -> Self {
+  pub fn new<R: Rng>(source_rng: &mut R, bedrock: Bedrock, wavy: bool) -> Self {
     let seed = source_rng.gen();
-    let ocean1 = OceanGenerator::new_v1(seed);
-    let ocean2 = OceanGenerator::new_v2(seed);
-    let seagrass = SeagrassGenerator::new(source_rng.gen());
+    let floor_noise = (DynNoise::new(floor_generator(seed)), DynNoise::new(deepslate_generator(seed)));
+    let seagrass = DynNoise::new(SeagrassGenerator::new(source_rng.gen()));
+    let mut ocean = Ocean::with_noise(bedrock, floor_noise.0, floor_noise.1, seagrass);
+    ocean.seed = seed;
+    // Only drawn for wavy oceans, so that the flat ocean is unchanged
+    ocean.surface = wavy.then(|| ColumnSampler::new(SurfaceGenerator::new(source_rng.gen())).cached());
+    ocean
+  }
+
+  /// An ocean built from the given noise functions instead of those it would build from a seed, for constructing
+  /// particular sea floors. The sea floor is at `floor` blocks above z=-32, the top of the deepslate is at `deepslate`
+  /// blocks above z=-34, and seagrass grows where `seagrass` is from 0.06 up to 0.09 above -1, in steps of 0.01.
+  /// The surface is flat, and the trench and strata, if added, are laid out as if from a seed of 0.
+  pub fn with_noise(bedrock: Bedrock, floor: DynNoise, deepslate: DynNoise, seagrass: DynNoise) -> Self {
+    let floor_noise = (floor, deepslate);
+    let (ocean1, ocean2) = floor_samplers(&floor_noise, None);
+    let seagrass = ColumnSampler::new(seagrass)
+      .with_offset(1.0)
+      .with_scale(100.0)
+      .cached();
     Ocean {
+      seed: 0,
+      floor_noise,
       ocean1,
       ocean2,
-      seagrass
+      seagrass,
+      surface: None,
+      bedrock,
+      beach: None,
+      footprint: None,
+      strata: None
     }
   }
 
+  /// A copy of this ocean whose deepslate is banded with tuff and occasional basalt, in strata 3 to 6 blocks thick
+  /// laid out from the seed of the sea floor. Their boundaries are level, apart from rising and falling by up to
+  /// `STRATA_PERTURBATION` blocks from column to column.
+  pub fn with_strata(&self) -> Self {
+    Ocean { strata: Some(Strata::new(self.seed)), ..self.clone() }
+  }
+
+  /// A copy of this ocean whose sea floor responds to the city standing over it. Nothing grows in the shadows of its
+  /// landmasses, whose edges are dithered out to `FOOTPRINT_FRINGE` blocks beyond them, and the gravel there is mixed
+  /// with coarse dirt. Within `PILLAR_COMPACTION_RADIUS` of the lowest layer's pillars, the sea floor is compacted
+  /// into tuff and deepslate and raised by a block.
+  pub fn with_city_footprint(&self, footprint: CityFootprint) -> Self {
+    Ocean { footprint: Some(footprint), ..self.clone() }
+  }
+
+  /// A copy of this ocean whose sea floor ramps up into a beach within `BEACH_WIDTH` blocks of the edge of the world,
+  /// which spans from `min` to `max` inclusive. Its outermost columns are kept above the surface, so no water is left
+  /// standing at the edge of the world.
+  pub fn with_beach(&self, min: IVec2, max: IVec2) -> Self {
+    Ocean { beach: Some(Beach { min, max }), ..self.clone() }
+  }
+
+  /// A copy of this ocean whose sea floor deepens away from the city in `city_box` towards the edge of the world at
+  /// `world_box`, if `gradient` is set, and plunges into a trench `trench_distance` blocks away from the city,
+  /// if given.
+  /// The sea floor within the city's bounding box is unchanged.
+  pub fn deepened(
+    &self, city_box: BoundingBox, world_box: BoundingBox, gradient: bool, trench_distance: Option<u32>
+  ) -> Self {
+    let depth = FloorDepth {
+      city_min: city_box.min.truncate().as_dvec2(),
+      city_max: city_box.max.truncate().as_dvec2(),
+      reach: (city_box.min.truncate() - world_box.min.truncate())
+        .min(world_box.max.truncate() - city_box.max.truncate())
+        .min_element().max(1) as f64,
+      gradient,
+      trench_distance: trench_distance.map(|distance| distance as f64),
+      trench_noise: noise::ScalePoint::new(Perlin::new(self.seed.wrapping_add(1))).set_scale(96f64.recip())
+    };
+
+    let (ocean1, ocean2) = floor_samplers(&self.floor_noise, Some(depth));
+    Ocean { ocean1, ocean2, ..self.clone() }
+  }
+
+  /// The height of the sea floor at the given position, which is the lowest z value of the water above it
+  pub fn floor_height_at(&self, pos: IVec2) -> i32 {
+    self.sample_ocean1(pos)
+  }
+
+  /// The height of the topmost water block at the given position, which is either 0 or -1
+  pub fn surface_height_at(&self, pos: IVec2) -> i32 {
+    self.surface.as_ref().map_or(0, |surface| surface.height_at(pos).clamp(-1, 0))
+  }
+
+  /// The counters of each of this ocean's noise caches
+  pub fn cache_counters(&self) -> impl Iterator<Item = (&'static str, &CacheCounters)> {
+    [
+      ("ocean 1", self.ocean1.counters()),
+      ("ocean 2", self.ocean2.counters()),
+      ("seagrass", self.seagrass.counters())
+    ].into_iter()
+      .chain(self.surface.as_ref().map(|surface| ("ocean surface", surface.counters())))
+      .chain(self.strata.as_ref().map(|strata| ("ocean strata", strata.perturbation.counters())))
+  }
+
+  /// Kept a block above the bedrock, so that even the deepest parts of the sea floor have deepslate beneath them
   fn sample_ocean1(&self, pos: IVec2) -> i32 {
-    (self.ocean1.get(pos.as_dvec2()) - 32.0).floor() as i32
+    let height = self.ocean1.height_at(pos);
+    let height = self.beach.map_or(height, |beach| beach.raise(pos, height, BEACH_TOP_Z + 1));
+    let height = height + self.is_compacted(pos) as i32;
+    height.max(self.bedrock.height_at(pos) + 2)
+  }
+
+  /// Whether the sea floor is in the shadow of a landmass, which only happens if it responds to the city
+  fn is_shadowed(&self, pos: IVec2) -> bool {
+    let distance = match self.footprint.as_ref().and_then(|footprint| footprint.distance(pos)) {
+      Some(distance) => distance,
+      None => return false
+    };
+
+    // Thins out from fully shadowed beneath the landmass to not at all just beyond the fringe
+    hash_to_unit(hash2(SHADOW_SALT, pos)) < 1.0 - distance as f64 / (FOOTPRINT_FRINGE + 1) as f64
+  }
+
+  /// Whether the sea floor is compacted around the footing of a pillar, which only happens if it responds to the city
+  fn is_compacted(&self, pos: IVec2) -> bool {
+    // Every pillar stands on a landmass, so only the columns near one are checked against each pillar
+    self.footprint.as_ref()
+      .filter(|footprint| footprint.distance(pos).is_some())
+      .and_then(|footprint| footprint.nearest_pillar_distance(pos))
+      .map_or(false, |distance| distance <= PILLAR_COMPACTION_RADIUS)
   }
 
   fn sample_ocean2(&self, pos: IVec2) -> i32 {
-    (self.ocean2.get(pos.as_dvec2()) - 34.0).floor() as i32
+    let height = self.ocean2.height_at(pos);
+    self.beach.map_or(height, |beach| beach.raise(pos, height, BEACH_TOP_Z + 1 - BEACH_GRAVEL_DEPTH))
   }
 
   fn sample_seagrass(&self, pos: IVec2) -> SeagrassPresence {
-    self.seagrass.sample(pos.as_dvec2())
+    match self.seagrass.height_at(pos) as u32 % 10 {
+      0..=5 => SeagrassPresence::None,
+      6..=8 => SeagrassPresence::Short,
+      9 => SeagrassPresence::Tall,
+      _ => SeagrassPresence::None
+    }
   }
 }
 
 impl Geometry for Ocean {
+  /// The surface never rises above z=0, even when it is wavy, but a beach does
   fn bounding_box(&self) -> BoundingBox {
-    let min = IVec3::new(i32::MIN, i32::MIN, -64);
-    let max = IVec3::new(i32::MAX, i32::MAX, 0);
-    BoundingBox::new(min, max)
+    let min_z = self.bedrock.bounding_box().min.z;
+    BoundingBox::unbounded_xy(min_z, if self.beach.is_some() { BEACH_TOP_Z } else { 0 })
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    pos.z <= 0
+    if pos.z <= self.bedrock.height_at(pos.xy()) { return false };
+    // Beaches are the only place where the sea floor rises above the surface
+    pos.z <= self.surface_height_at(pos.xy()) || (self.beach.is_some() && pos.z < self.sample_ocean1(pos.xy()))
+  }
+}
+
+impl ColumnTop for Ocean {
+  fn column_top(&self, xy: IVec2) -> Option<i32> {
+    // Beaches are the only place where the sea floor rises above the surface
+    let surface = self.surface_height_at(xy);
+    Some(if self.beach.is_some() { surface.max(self.sample_ocean1(xy) - 1) } else { surface })
   }
 }
 
 impl MaterialGeometry for Ocean {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
-    if pos.z > 0 { return None };
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    if !self.block_at(pos) { return None };
     let ocean1 = self.sample_ocean1(pos.xy());
     let ocean2 = self.sample_ocean2(pos.xy());
     if pos.z >= ocean1 {
       use SeagrassPresence::{Short, Tall};
-      let seagrass = self.sample_seagrass(pos.xy());
+      let seagrass = match self.sample_seagrass(pos.xy()) {
+        // Seagrass only grows on gravel, and only where light reaches it
+        _ if ocean1 - 1 < ABYSSAL_Z => SeagrassPresence::None,
+        _ if self.is_shadowed(pos.xy()) || self.is_compacted(pos.xy()) => SeagrassPresence::None,
+        // Tall seagrass would poke out of the water where the sea floor is just beneath the surface
+        Tall if ocean1 + 1 > self.surface_height_at(pos.xy()) => Short,
+        seagrass => seagrass
+      };
       if seagrass == Short && pos.z == ocean1 {
-        Some(super::blocks::SEAGRASS_SHORT)
+        Some(Cow::Borrowed(&super::blocks::SEAGRASS_SHORT))
       } else if seagrass == Tall && pos.z == ocean1 {
-        Some(super::blocks::SEAGRASS_TALL_LOWER)
+        Some(Cow::Borrowed(&super::blocks::SEAGRASS_TALL_LOWER))
       } else if seagrass == Tall && pos.z == ocean1 + 1 {
-        Some(super::blocks::SEAGRASS_TALL_UPPER)
+        Some(Cow::Borrowed(&super::blocks::SEAGRASS_TALL_UPPER))
+      } else {
+        Some(Cow::Borrowed(&super::blocks::WATER))
+      }
+    } else if pos.z < ocean1 && pos.z >= ocean2 && pos.z >= ABYSSAL_Z {
+      let mixed = || hash_to_unit(hash3(FLOOR_MIX_SALT, pos)) < 0.5;
+      if self.beach.is_some() && ocean1 - 1 >= BEACH_SAND_Z {
+        Some(Cow::Borrowed(&super::blocks::SAND))
+      } else if self.is_compacted(pos.xy()) {
+        Some(Cow::Borrowed(if mixed() { &super::blocks::TUFF } else { &super::blocks::DEEPSLATE }))
+      } else if self.is_shadowed(pos.xy()) && mixed() {
+        Some(Cow::Borrowed(&super::blocks::COARSE_DIRT))
       } else {
-        Some(super::blocks::WATER)
+        Some(Cow::Borrowed(&super::blocks::GRAVEL))
       }
-    } else if pos.z < ocean1 && pos.z >= ocean2 {
-      Some(super::blocks::GRAVEL)
     } else if pos.z < ocean1 || pos.z < ocean2 {
-      Some(super::blocks::DEEPSLATE)
+      match &self.strata {
+        Some(strata) => Some(Cow::Borrowed(strata.rock_at(pos))),
+        None => Some(Cow::Borrowed(&super::blocks::DEEPSLATE))
+      }
     } else {
       None
     }
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    use super::blocks::{DEEPSLATE, GRAVEL, SAND, SEAGRASS_SHORT, SEAGRASS_TALL_LOWER, SEAGRASS_TALL_UPPER, WATER};
+    out.extend([SEAGRASS_SHORT, SEAGRASS_TALL_LOWER, SEAGRASS_TALL_UPPER, WATER, GRAVEL, DEEPSLATE]);
+    if self.beach.is_some() {
+      out.insert(SAND);
+    };
+
+    if self.footprint.is_some() {
+      out.extend([super::blocks::TUFF, super::blocks::COARSE_DIRT]);
+    };
+
+    if self.strata.is_some() {
+      out.extend([super::blocks::TUFF, super::blocks::BASALT]);
+    };
+  }
+}
+
+/// The samplers of the top of the sea floor, beneath the water, and of the top of the deepslate, beneath the gravel
+fn floor_samplers(
+  (floor, deepslate): &(DynNoise, DynNoise), depth: Option<FloorDepth>
+) -> (CachedColumnSampler<OceanGenerator>, CachedColumnSampler<OceanGenerator>) {
+  let ocean1 = ColumnSampler::new(OceanGenerator { inner: floor.clone(), depth: depth.clone() })
+    .with_offset(-32.0)
+    .cached();
+  let ocean2 = ColumnSampler::new(OceanGenerator { inner: deepslate.clone(), depth })
+    .with_offset(-34.0)
+    .cached();
+  (ocean1, ocean2)
+}
+
+/// The default noise of the top of the sea floor
+fn floor_generator(seed: u32) -> impl NoiseFn<f64, 2> + Send + Sync {
+  Fbm::<Perlin>::new(seed)
+    .set_octaves(5)
+    .set_frequency(128f64.recip())
+    .multiply_constant(4.0)
 }
 
+/// The default noise of the top of the deepslate, beneath the gravel
+fn deepslate_generator(seed: u32) -> impl NoiseFn<f64, 2> + Send + Sync {
+  Fbm::<Perlin>::new(seed)
+    .set_octaves(3)
+    .set_frequency(128f64.recip())
+    .multiply_constant(4.0)
+}
+
+/// A sea floor noise, pushed down by the sea floor's depth if it is deepened
 #[derive(Debug, Clone)]
 struct OceanGenerator {
-  inner: noise::Multiply<f64, Fbm<Perlin>, noise::Constant, 2>
+  inner: DynNoise,
+  depth: Option<FloorDepth>
 }
 
-impl OceanGenerator {
-  fn new_v1(seed: u32) -> Self {
-    let inner = Fbm::new(seed)
-      .set_octaves(5)
-      .set_frequency(128f64.recip())
-      .multiply_constant(4.0);
-    OceanGenerator { inner }
+impl NoiseFn<f64, 2> for OceanGenerator {
+  #[inline]
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    let point = point.into();
+    let depth = self.depth.as_ref().map_or(0.0, |depth| depth.at(DVec2::from(point)));
+    self.inner.get(point) - depth
   }
+}
+
+/// How far beneath its usual height the sea floor is pushed down, depending on the distance from the city
+#[derive(Debug, Clone)]
+struct FloorDepth {
+  city_min: DVec2,
+  city_max: DVec2,
+  /// The least distance from the city to the edge of the world, over which the sea floor reaches its greatest depth
+  reach: f64,
+  /// Whether the sea floor deepens steadily away from the city
+  gradient: bool,
+  /// The distance from the city of the middle of the trench, if there is one
+  trench_distance: Option<f64>,
+  /// Varies the depth of the trench along its length
+  trench_noise: noise::ScalePoint<Perlin>
+}
+
+impl FloorDepth {
+  fn at(&self, point: DVec2) -> f64 {
+    // The distance to the nearest point of the city's bounding box, which is 0 inside of it
+    let distance = (self.city_min - point).max(point - self.city_max).max(DVec2::ZERO).length();
+    let gradient = match self.gradient {
+      true => MAX_GRADIENT_DEPTH * smoothstep((distance / self.reach).min(1.0)),
+      false => 0.0
+    };
 
-  fn new_v2(seed: u32) -> Self {
-    let inner = Fbm::new(seed)
-      .set_octaves(3)
-      .set_frequency(128f64.recip())
-      .multiply_constant(4.0);
-    OceanGenerator { inner }
+    let trench = self.trench_distance.map_or(0.0, |trench_distance| {
+      let offset = (distance - trench_distance).abs();
+      if offset >= TRENCH_HALF_WIDTH { return 0.0 };
+      // A smooth bump, falling from 1 at the middle of the trench to 0 at its edges
+      let bump = ((offset / TRENCH_HALF_WIDTH * PI).cos() + 1.0) / 2.0;
+      let depth = TRENCH_DEPTH + TRENCH_DEPTH_VARIATION * self.trench_noise.get(point.to_array()).clamp(-1.0, 1.0);
+      depth * bump
+    });
+
+    gradient + trench
   }
 }
 
-impl NoiseFn<f64, 2> for OceanGenerator {
+/// Bands of rock beneath the gravel of the sea floor, alternating between deepslate and either tuff or basalt
+#[derive(Debug, Clone)]
+struct Strata {
+  /// The rock at each z value from the bottom of the world up, before the boundaries between bands are perturbed
+  bands: Vec<&'static Block>,
+  /// How far the bands are shifted up or down in each column, from 0 to twice `STRATA_PERTURBATION`
+  perturbation: CachedColumnSampler<StrataGenerator>
+}
+
+impl Strata {
+  fn new(seed: u32) -> Self {
+    use super::blocks::{BASALT, DEEPSLATE, TUFF};
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(mix64(seed as u64 ^ STRATA_SALT));
+    // Covers every z value that rock can be found at, however far the bands are shifted
+    let height = (BEACH_TOP_Z + STRATA_PERTURBATION - crate::WORLD_MIN_Z + 1) as usize;
+    let mut bands = Vec::with_capacity(height + STRATUM_THICKNESS.end());
+    let mut rock = &DEEPSLATE;
+    while bands.len() < height {
+      bands.extend(repeat(rock).take(rng.gen_range(STRATUM_THICKNESS)));
+      rock = match *rock == DEEPSLATE {
+        true if rng.gen_ratio(1, 4) => &BASALT,
+        true => &TUFF,
+        false => &DEEPSLATE
+      };
+    };
+
+    let perturbation = ColumnSampler::new(StrataGenerator::new(seed.wrapping_add(2)))
+      .with_offset(1.0)
+      .with_scale(STRATA_PERTURBATION as f64 + 0.5)
+      .cached();
+    Strata { bands, perturbation }
+  }
+
+  fn rock_at(&self, pos: IVec3) -> &'static Block {
+    let shift = self.perturbation.height_at(pos.xy()).clamp(0, STRATA_PERTURBATION * 2) - STRATA_PERTURBATION;
+    let index = (pos.z + shift - crate::WORLD_MIN_Z).clamp(0, self.bands.len() as i32 - 1);
+    self.bands[index as usize]
+  }
+}
+
+/// The gentle undulation of the boundaries between strata
+#[derive(Debug, Clone)]
+struct StrataGenerator {
+  inner: noise::ScalePoint<Perlin>
+}
+
+impl StrataGenerator {
+  fn new(seed: u32) -> Self {
+    let inner = noise::ScalePoint::new(Perlin::new(seed))
+      .set_scale(40f64.recip());
+    StrataGenerator { inner }
+  }
+}
+
+impl NoiseFn<f64, 2> for StrataGenerator {
   #[inline]
   fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
     self.inner.get(point)
   }
 }
 
+/// The band at the edge of the world over which the sea floor ramps up into a beach
+#[derive(Debug, Clone, Copy)]
+struct Beach {
+  min: IVec2,
+  max: IVec2
+}
+
+impl Beach {
+  /// Raises a height towards `top`, linearly from the inner edge of the beach
+  /// to its outermost `BEACH_DRY_WIDTH` columns, where it reaches it. Heights already above `top` are left as they are.
+  fn raise(&self, pos: IVec2, height: i32, top: i32) -> i32 {
+    let distance = distance_to_edge(self.min, self.max, pos) - BEACH_DRY_WIDTH;
+    let rise = 1.0 - (distance as f64 / (BEACH_WIDTH - BEACH_DRY_WIDTH) as f64).clamp(0.0, 1.0);
+    if rise <= 0.0 || height >= top { return height };
+    height + ((top - height) as f64 * rise).round() as i32
+  }
+}
+
+#[inline]
+fn smoothstep(t: f64) -> f64 {
+  t * t * (3.0 - 2.0 * t)
+}
+
 #[derive(Debug, Clone)]
 struct SeagrassGenerator {
   inner: noise::ScalePoint<Perlin>
@@ -128,16 +494,33 @@ impl SeagrassGenerator {
       inner
     }
   }
+}
 
-  fn sample(&self, point: impl Into<[f64; 2]>) -> SeagrassPresence {
-    let value = self.inner.get(point);
-    let value = f64::floor((value + 1.0) * 100.0) as u32 % 10;
-    match value {
-      0..=5 => SeagrassPresence::None,
-      6..=8 => SeagrassPresence::Short,
-      9 => SeagrassPresence::Tall,
-      _ => SeagrassPresence::None
-    }
+impl NoiseFn<f64, 2> for SeagrassGenerator {
+  #[inline]
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    self.inner.get(point)
+  }
+}
+
+/// Gentle, long waves on the surface of the ocean
+#[derive(Debug, Clone)]
+struct SurfaceGenerator {
+  inner: noise::ScalePoint<Perlin>
+}
+
+impl SurfaceGenerator {
+  fn new(seed: u32) -> Self {
+    let inner = noise::ScalePoint::new(Perlin::new(seed))
+      .set_scale(48f64.recip());
+    SurfaceGenerator { inner }
+  }
+}
+
+impl NoiseFn<f64, 2> for SurfaceGenerator {
+  #[inline]
+  fn get(&self, point: impl Into<[f64; 2]>) -> f64 {
+    self.inner.get(point)
   }
 }
 
@@ -147,3 +530,236 @@ enum SeagrassPresence {
   Short,
   Tall
 }
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks::{DEEPSLATE, GRAVEL, SEAGRASS_SHORT, SEAGRASS_TALL_LOWER, SEAGRASS_TALL_UPPER, WATER};
+  use crate::generation::fixtures::{ConstantNoise, StepNoise};
+  use crate::generation::noise_util::tests::assert_cache_matches_sampler;
+  use crate::utility::{CARDINAL4, CARDINAL8};
+
+  const MIN_Z: i32 = -64;
+
+  fn constant(value: f64) -> DynNoise {
+    DynNoise::new(ConstantNoise { value })
+  }
+
+  /// An ocean over level bedrock at the bottom of the world, with no seagrass unless `seagrass` says otherwise
+  fn new_ocean(floor: DynNoise, deepslate: DynNoise, seagrass: DynNoise) -> Ocean {
+    Ocean::with_noise(Bedrock::with_noise(constant(0.0), MIN_Z), floor, deepslate, seagrass)
+  }
+
+  fn material_at(ocean: &Ocean, x: i32, z: i32) -> Option<Block> {
+    ocean.block_material_at(IVec3::new(x, 0, z)).map(Cow::into_owned)
+  }
+
+  #[test]
+  fn the_sea_floor_follows_its_noise() {
+    let floor = DynNoise::new(StepNoise { step_x: 0.0, below: 0.0, above: -8.0 });
+    let ocean = new_ocean(floor, constant(-1.0), constant(-1.0));
+    assert_eq!(ocean.floor_height_at(IVec2::new(-1, 0)), -32);
+    assert_eq!(ocean.floor_height_at(IVec2::new(0, 0)), -40);
+
+    // West of the step, the gravel reaches from the top of the deepslate at -35 up to the sea floor
+    assert_eq!(material_at(&ocean, -1, 1), None);
+    assert_eq!(material_at(&ocean, -1, 0), Some(WATER));
+    assert_eq!(material_at(&ocean, -1, -32), Some(WATER));
+    assert_eq!(material_at(&ocean, -1, -33), Some(GRAVEL));
+    assert_eq!(material_at(&ocean, -1, -35), Some(GRAVEL));
+    assert_eq!(material_at(&ocean, -1, -36), Some(DEEPSLATE));
+    assert_eq!(material_at(&ocean, -1, MIN_Z + 1), Some(DEEPSLATE));
+    assert_eq!(material_at(&ocean, -1, MIN_Z), None);
+
+    // East of it, the sea floor has sunk beneath the top of the deepslate, leaving no gravel
+    assert_eq!(material_at(&ocean, 0, -40), Some(WATER));
+    assert_eq!(material_at(&ocean, 0, -41), Some(DEEPSLATE));
+  }
+
+  #[test]
+  fn the_sea_floor_stays_above_the_bedrock() {
+    let ocean = new_ocean(constant(-100.0), constant(-100.0), constant(-1.0));
+    assert_eq!(ocean.floor_height_at(IVec2::ZERO), MIN_Z + 2);
+    assert_eq!(material_at(&ocean, 0, MIN_Z + 2), Some(WATER));
+    assert_eq!(material_at(&ocean, 0, MIN_Z + 1), Some(DEEPSLATE));
+  }
+
+  #[test]
+  fn there_is_no_gap_between_the_deepslate_and_the_bedrock() {
+    for (seed, flat) in [(0, false), (1, false), (2, true)] {
+      let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+      let bedrock = Bedrock::new(&mut rng, flat, MIN_Z);
+      let ocean = Ocean::new(&mut rng, bedrock.clone(), false);
+      for _ in 0..2000 {
+        let xy = IVec2::new(rng.gen_range(-5000..5000), rng.gen_range(-5000..5000));
+        // Everything from the bottom of the world up to the sea floor is either bedrock or the ocean's ground
+        for z in MIN_Z..=ocean.floor_height_at(xy) {
+          let pos = xy.extend(z);
+          assert!(bedrock.block_at(pos) || ocean.block_at(pos), "seed {}: nothing at {}", seed, pos);
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn a_wavy_surface_only_ever_dips_by_one_block() {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+    let bedrock = Bedrock::new(&mut rng, false, MIN_Z);
+    let ocean = Ocean::new(&mut rng, bedrock, true);
+    assert_eq!(ocean.bounding_box().max.z, 0);
+
+    let mut dips = 0;
+    for x in 0..2000 {
+      let xy = IVec2::new(x, x / 3);
+      let surface = ocean.surface_height_at(xy);
+      assert!(surface == 0 || surface == -1, "the surface is at {} at {}", surface, xy);
+      dips += (surface == -1) as usize;
+      assert_eq!(ocean.column_top(xy), Some(surface));
+      assert!(ocean.block_at(xy.extend(surface)), "no water at the surface at {}", xy);
+      assert!(!ocean.block_at(xy.extend(surface + 1)), "water above the surface at {}", xy);
+      assert!(!ocean.block_at(xy.extend(1)));
+    };
+
+    assert!(dips > 0 && dips < 2000, "the surface dips in {} of 2000 columns", dips);
+  }
+
+  #[test]
+  fn seagrass_never_pokes_out_of_a_wavy_surface() {
+    // The sea floor is at -1, just beneath the surface, and tall seagrass grows everywhere
+    let mut ocean = new_ocean(constant(31.0), constant(-1.0), constant(-0.91));
+    ocean.surface = Some(ColumnSampler::new(SurfaceGenerator::new(0)).cached());
+    let mut heights = BTreeSet::new();
+    for x in 0..2000 {
+      let surface = ocean.surface_height_at(IVec2::new(x, 0));
+      heights.insert(surface);
+      assert_eq!(ocean.floor_height_at(IVec2::new(x, 0)), -1);
+      assert_eq!(material_at(&ocean, x, surface + 1), None, "above the surface at {}", x);
+      let expected = match surface {
+        // Where the surface is level with the floor, tall seagrass would poke out of it, so it grows short instead
+        -1 => [Some(SEAGRASS_SHORT), None],
+        _ => [Some(SEAGRASS_TALL_LOWER), Some(SEAGRASS_TALL_UPPER)]
+      };
+
+      assert_eq!([material_at(&ocean, x, -1), material_at(&ocean, x, 0)], expected, "at {}", x);
+    };
+
+    assert_eq!(heights, BTreeSet::from([-1, 0]));
+  }
+
+  #[test]
+  fn seagrass_grows_where_its_noise_says() {
+    // Offset by 1 and scaled by 100, these are 7, taking short seagrass, and 9, taking tall seagrass
+    let seagrass = DynNoise::new(StepNoise { step_x: 0.0, below: -0.93, above: -0.91 });
+    let ocean = new_ocean(constant(0.0), constant(0.0), seagrass);
+    assert_eq!(material_at(&ocean, -1, -32), Some(SEAGRASS_SHORT));
+    assert_eq!(material_at(&ocean, -1, -31), Some(WATER));
+    assert_eq!(material_at(&ocean, 0, -32), Some(SEAGRASS_TALL_LOWER));
+    assert_eq!(material_at(&ocean, 0, -31), Some(SEAGRASS_TALL_UPPER));
+    assert_eq!(material_at(&ocean, 0, -30), Some(WATER));
+
+    // Nothing grows on a sea floor too deep for light to reach
+    let ocean = new_ocean(constant(-40.0), constant(-40.0), constant(-0.91));
+    assert_eq!(ocean.floor_height_at(IVec2::ZERO), MIN_Z + 2);
+    assert_eq!(material_at(&ocean, 0, MIN_Z + 2), Some(WATER));
+  }
+
+  #[test]
+  fn cached_heights_match_the_uncached_noise() {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(21);
+    let bedrock = Bedrock::new(&mut rng, false, MIN_Z);
+    let ocean = Ocean::new(&mut rng, bedrock, true).with_strata();
+    let city_box = BoundingBox::new(IVec3::new(-20, -20, 0), IVec3::new(20, 20, 100));
+    let world_box = BoundingBox::new(IVec3::new(-200, -200, MIN_Z), IVec3::new(200, 200, 320));
+    let deepened = ocean.deepened(city_box, world_box, true, Some(30));
+    let (min, max) = (IVec2::new(-60, -60), IVec2::new(59, 59));
+    for ocean in [&ocean, &deepened] {
+      assert_cache_matches_sampler(&ocean.ocean1, min, max);
+      assert_cache_matches_sampler(&ocean.ocean2, min, max);
+      assert_cache_matches_sampler(&ocean.seagrass, min, max);
+      assert_cache_matches_sampler(ocean.surface.as_ref().unwrap(), min, max);
+      assert_cache_matches_sampler(&ocean.strata.as_ref().unwrap().perturbation, min, max);
+    };
+  }
+
+  /// A level sea floor around a city spanning from -20 to 20, in a world spanning from -200 to 200,
+  /// which the sea floor deepens towards 180 blocks away from the city
+  fn deepened_ocean(gradient: bool, trench_distance: Option<u32>) -> Ocean {
+    let city_box = BoundingBox::new(IVec3::new(-20, -20, 0), IVec3::new(20, 20, 100));
+    let world_box = BoundingBox::new(IVec3::new(-200, -200, MIN_Z), IVec3::new(200, 200, 320));
+    new_ocean(constant(0.0), constant(-1.0), constant(-0.91)).deepened(city_box, world_box, gradient, trench_distance)
+  }
+
+  /// The heights of the sea floor going out from the middle of the city in the given direction, up to `steps` columns
+  fn transect(ocean: &Ocean, direction: IVec2, steps: i32) -> Vec<i32> {
+    (0..=steps).map(|step| ocean.floor_height_at(direction * step)).collect()
+  }
+
+  #[test]
+  fn the_sea_floor_deepens_steadily_away_from_the_city() {
+    let ocean = deepened_ocean(true, None);
+    for direction in CARDINAL8 {
+      let heights = transect(&ocean, direction, 200);
+      assert!(heights.windows(2).all(|pair| pair[1] <= pair[0]), "towards {}: {:?}", direction, heights);
+      assert_eq!(heights[20], -32, "towards {}: beside the city", direction);
+    };
+
+    for direction in CARDINAL4 {
+      assert_eq!(ocean.floor_height_at(direction * 200), -55, "towards {}: at the edge of the world", direction);
+    };
+  }
+
+  #[test]
+  fn the_trench_plunges_around_the_city_at_its_distance() {
+    let ocean = deepened_ocean(false, Some(40));
+    for direction in CARDINAL4 {
+      // The middle of the trench is 60 blocks from the middle of the city, and 40 from its edge
+      let heights = transect(&ocean, direction, 100);
+      assert!((-44..=-40).contains(&heights[60]), "towards {}: the trench is at {}", direction, heights[60]);
+      assert!(heights[..=48].iter().chain(&heights[72..]).all(|&height| height == -32), "towards {}", direction);
+      assert!(heights[48..=60].windows(2).all(|pair| pair[1] <= pair[0]), "towards {}: {:?}", direction, heights);
+      assert!(heights[60..=72].windows(2).all(|pair| pair[1] >= pair[0]), "towards {}: {:?}", direction, heights);
+    };
+  }
+
+  #[test]
+  fn a_deepened_sea_floor_has_no_steps_larger_than_two_blocks() {
+    let ocean = deepened_ocean(true, Some(60));
+    for x in -120..=120 {
+      for y in -120..=120 {
+        let pos = IVec2::new(x, y);
+        let height = ocean.floor_height_at(pos);
+        for neighbor in [pos + IVec2::X, pos + IVec2::Y] {
+          let step = (ocean.floor_height_at(neighbor) - height).abs();
+          assert!(step <= 2, "the sea floor steps by {} from {} to {}", step, pos, neighbor);
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn gravel_and_seagrass_give_way_to_bare_deepslate_in_the_depths() {
+    // The trench is near the edge of the world, where the sea floor is deep enough already to plunge to the bedrock
+    let ocean = deepened_ocean(true, Some(170));
+    let (mut abyssal, mut gravelled) = (0, 0);
+    for x in 0..=200 {
+      let floor = ocean.floor_height_at(IVec2::new(x, 0));
+      let below = material_at(&ocean, x, floor - 1);
+      if floor - 1 < ABYSSAL_Z {
+        abyssal += 1;
+        assert_eq!(below, Some(DEEPSLATE), "at {}, with the sea floor at {}", x, floor);
+        assert_eq!(material_at(&ocean, x, floor), Some(WATER), "at {}, with the sea floor at {}", x, floor);
+      } else {
+        gravelled += 1;
+        assert_eq!(below, Some(GRAVEL), "at {}, with the sea floor at {}", x, floor);
+      };
+
+      for z in MIN_Z + 1..ABYSSAL_Z {
+        assert_ne!(material_at(&ocean, x, z), Some(GRAVEL), "gravel at {} beneath {}", z, x);
+      };
+    };
+
+    assert!(abyssal > 0 && gravelled > 0, "{} abyssal and {} gravelled columns", abyssal, gravelled);
+  }
+}
@@ -0,0 +1,184 @@
+//! This geometry module generates the following features:
+//!
+//! - Small cones of rock breaking the surface of the ocean, scattered sparsely around the city.
+//! - Occasional taller sea stacks, with flat tops.
+//!
+//! Outcrops are placed at most one per 64x64 cell, jittered within the cell and kept well away from its edges,
+//! so that whether a block belongs to an outcrop can be decided from the cell containing it alone.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::ocean::Ocean;
+use super::{Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+use crate::utility::{hash2, hash3, hash_to_unit, mix64};
+
+/// The size of the cells outcrops are placed in
+const OUTCROP_CELL_SIZE: i32 = 64;
+/// The chance of a cell having an outcrop in it
+const OUTCROP_PROBABILITY: f64 = 0.2;
+/// The chance of an outcrop being a sea stack rather than a rock
+const SEA_STACK_PROBABILITY: f64 = 0.25;
+/// Outcrops are kept at least this far from the city's bounding box
+const CITY_MARGIN: i32 = 32;
+/// How far outcrops reach down into the sea floor, so that they never float above it
+const BURIAL_DEPTH: i32 = 2;
+
+const MIN_ROCK_HEIGHT: i32 = 3;
+const MAX_ROCK_HEIGHT: i32 = 9;
+const MAX_ROCK_RADIUS: f32 = 6.0;
+const MIN_SEA_STACK_HEIGHT: i32 = 12;
+const MAX_SEA_STACK_HEIGHT: i32 = 20;
+const MAX_SEA_STACK_RADIUS: f32 = 9.0;
+/// The furthest any block of an outcrop is from its center
+const MAX_OUTCROP_REACH: i32 = 10;
+
+
+
+#[derive(Debug, Clone)]
+pub struct Outcrops {
+  seed: u64,
+  /// The region kept clear of outcrops, which is the city's bounding box along with a margin around it
+  exclusion_min: IVec2,
+  exclusion_max: IVec2,
+  /// Used to find the height of the sea floor, which every outcrop rises up from
+  floor: Ocean
+}
+
+impl Outcrops {
+  pub fn new(seed: u64, city_bounding_box: BoundingBox, floor: Ocean) -> Self {
+    let exclusion_min = city_bounding_box.min.xy() - CITY_MARGIN;
+    let exclusion_max = city_bounding_box.max.xy() + CITY_MARGIN;
+    Outcrops { seed, exclusion_min, exclusion_max, floor }
+  }
+
+  /// The highest z value reached by any outcrop within the given columns, or `None` if there are none
+  pub fn max_z_in(&self, min: IVec2, max: IVec2) -> Option<i32> {
+    self.peaks_in(min, max).map(|peak| peak.z).max()
+  }
+
+  /// The highest block at the center of each outcrop that may reach into the given columns
+  pub fn peaks_in(&self, min: IVec2, max: IVec2) -> impl Iterator<Item = IVec3> + '_ {
+    let (cell_min, cell_max) = (cell_containing(min), cell_containing(max));
+    (cell_min.x..=cell_max.x)
+      .flat_map(move |x| (cell_min.y..=cell_max.y).map(move |y| IVec2::new(x, y)))
+      .filter_map(|cell| self.outcrop_in(cell))
+      .filter(move |outcrop| {
+        (outcrop.center - MAX_OUTCROP_REACH).cmple(max).all() && (outcrop.center + MAX_OUTCROP_REACH).cmpge(min).all()
+      })
+      .map(|outcrop| outcrop.center.extend(outcrop.peak))
+  }
+
+  /// The outcrop in the given cell, if it has one
+  fn outcrop_in(&self, cell: IVec2) -> Option<Outcrop> {
+    let hash = hash2(self.seed, cell);
+    if hash_to_unit(hash) >= OUTCROP_PROBABILITY { return None };
+
+    // Jittered within the part of the cell that keeps the whole outcrop inside of it
+    let range = (OUTCROP_CELL_SIZE - MAX_OUTCROP_REACH * 2) as u64;
+    let jitter = IVec2::new((mix64(hash ^ 1) % range) as i32, (mix64(hash ^ 2) % range) as i32);
+    let center = cell * OUTCROP_CELL_SIZE + MAX_OUTCROP_REACH + jitter;
+    let reach = IVec2::splat(MAX_OUTCROP_REACH);
+    let is_excluded = (center - reach).cmple(self.exclusion_max).all() &&
+      (center + reach).cmpge(self.exclusion_min).all();
+    if is_excluded { return None };
+
+    let kind_hash = mix64(hash ^ 3);
+    let outcrop = if hash_to_unit(kind_hash) < SEA_STACK_PROBABILITY {
+      let peak_range = (MAX_SEA_STACK_HEIGHT - MIN_SEA_STACK_HEIGHT + 1) as u64;
+      let peak = MIN_SEA_STACK_HEIGHT + (mix64(kind_hash) % peak_range) as i32;
+      let top_radius = 3.0 + (mix64(kind_hash ^ 1) % 3) as f32;
+      Outcrop { center, peak, kind: OutcropKind::SeaStack { top_radius }, material_seed: hash }
+    } else {
+      let peak = MIN_ROCK_HEIGHT + (mix64(kind_hash) % (MAX_ROCK_HEIGHT - MIN_ROCK_HEIGHT + 1) as u64) as i32;
+      Outcrop { center, peak, kind: OutcropKind::Rock, material_seed: hash }
+    };
+
+    Some(outcrop)
+  }
+
+  fn outcrop_at(&self, pos: IVec3) -> Option<Outcrop> {
+    if pos.z > MAX_SEA_STACK_HEIGHT { return None };
+    let outcrop = self.outcrop_in(cell_containing(pos.xy()))?;
+    let radius = outcrop.radius_at(pos.z)?;
+    let offset = (pos.xy() - outcrop.center).as_vec2();
+    let is_inside = offset.length_squared() <= radius * radius &&
+      pos.z >= self.floor.floor_height_at(pos.xy()) - BURIAL_DEPTH;
+    is_inside.then(|| outcrop)
+  }
+}
+
+impl Geometry for Outcrops {
+  fn bounding_box(&self) -> BoundingBox {
+    BoundingBox::unbounded_xy(-64, MAX_SEA_STACK_HEIGHT)
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.outcrop_at(pos).is_some()
+  }
+}
+
+impl ColumnTop for Outcrops {
+  fn column_top(&self, xy: IVec2) -> Option<i32> {
+    Some(self.max_z_in(xy, xy).unwrap_or(crate::WORLD_MIN_Z - 1))
+  }
+}
+
+impl MaterialGeometry for Outcrops {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.outcrop_at(pos).map(|outcrop| Cow::Borrowed(outcrop.material_at(pos)))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.extend([super::blocks::TUFF, super::blocks::DEEPSLATE]);
+  }
+}
+
+fn cell_containing(pos: IVec2) -> IVec2 {
+  IVec2::new(pos.x.div_euclid(OUTCROP_CELL_SIZE), pos.y.div_euclid(OUTCROP_CELL_SIZE))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Outcrop {
+  center: IVec2,
+  /// The z value of the outcrop's highest blocks
+  peak: i32,
+  kind: OutcropKind,
+  material_seed: u64
+}
+
+impl Outcrop {
+  /// The radius of the outcrop at the given height, which widens going down until it reaches its greatest radius
+  fn radius_at(self, z: i32) -> Option<f32> {
+    if z > self.peak { return None };
+    let depth = (self.peak - z) as f32;
+    Some(match self.kind {
+      OutcropKind::Rock => (depth * 0.5 + 0.5).min(MAX_ROCK_RADIUS),
+      OutcropKind::SeaStack { top_radius } => (top_radius + depth * 0.12).min(MAX_SEA_STACK_RADIUS)
+    })
+  }
+
+  /// Rocks are speckled with tuff, while sea stacks are banded with it
+  fn material_at(self, pos: IVec3) -> &'static Block {
+    let is_tuff = match self.kind {
+      OutcropKind::Rock => hash_to_unit(hash3(self.material_seed, pos)) < 0.3,
+      OutcropKind::SeaStack { .. } => (pos.z + (self.material_seed % 5) as i32).rem_euclid(5) < 2
+    };
+
+    match is_tuff {
+      true => &super::blocks::TUFF,
+      false => &super::blocks::DEEPSLATE
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum OutcropKind {
+  /// A cone coming to a point
+  Rock,
+  /// A steep column with a flat top of the given radius
+  SeaStack {
+    top_radius: f32
+  }
+}
@@ -1,15 +1,33 @@
-use glam::{IVec2, IVec3, Vec3Swizzles};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 
-use super::{BoundingBox, Geometry};
+use glam::{DVec2, IVec2, IVec3, Vec3Swizzles};
 
+use super::encoding::{Decoder, Encode, Encoder};
+use super::primitives::Brace;
+use super::{blocks, Block, BoundingBox, Geometry, MaterialGeometry};
+use crate::utility::{hash2, hash_to_unit, sin_cos_turns};
 
 
+
+/// The number of blocks of a weathered pillar that are stained, just beneath the landmass it holds up
+const STAINED_HEIGHT: i32 = 2;
+/// The height of each pair of crossed braces between two pillars of a cluster
+const BRACE_BAY_HEIGHT: i32 = 12;
+/// The gap left between the outlines of the pillars of a cluster and the point they are clustered around
+const CLUSTER_GAP: u32 = 3;
+/// Mixed into the hash of a cluster's position, which picks how many pillars it has and how they are turned
+const CLUSTER_SALT: u64 = 0x636c7573;
+
 #[derive(Debug, Clone)]
 pub struct Pillar {
   min_height: i32,
   max_height: i32,
   origin: IVec2,
-  radius: u32
+  radius: u32,
+  /// The z value of the underside of the landmass this pillar holds up, if the pillar is stained beneath it.
+  /// Weathering is applied after layers are loaded, so this isn't stored in the layer cache.
+  stained_below: Option<i32>
 }
 
 impl Pillar {
@@ -18,7 +36,8 @@ impl Pillar {
       min_height: crate::WORLD_MIN_Z,
       max_height: crate::WORLD_MAX_Z,
       origin,
-      radius
+      radius,
+      stained_below: None
     }
   }
 
@@ -27,9 +46,51 @@ impl Pillar {
       min_height: min.unwrap_or(crate::WORLD_MIN_Z),
       max_height: max.unwrap_or(crate::WORLD_MAX_Z),
       origin,
-      radius
+      radius,
+      stained_below: None
     }
   }
+
+  /// The column at the middle of this pillar
+  pub fn origin(&self) -> IVec2 {
+    self.origin
+  }
+
+  pub fn radius(&self) -> u32 {
+    self.radius
+  }
+
+  /// The z value of the lowest block of this pillar
+  pub fn bottom(&self) -> i32 {
+    self.min_height
+  }
+
+  /// Whether or not the given column falls within this pillar's footprint, regardless of height
+  pub fn contains_column(&self, pos: IVec2) -> bool {
+    let radius = self.radius as f32 + 0.5;
+    self.origin.as_vec2().distance(pos.as_vec2()) <= radius
+  }
+
+  /// Stains the ring of this pillar just beneath the given z value, where it meets the underside of a landmass
+  pub fn weather(&mut self, underside: i32) {
+    self.stained_below = Some(underside);
+  }
+
+  /// Pairs this pillar with the material it is made of, so that its stained ring can use a darker variant of it
+  pub fn with_material<'a>(&'a self, material: &'a Block) -> MaterialPillar<'a> {
+    let stained = self.stained_below.and_then(|_| blocks::stained(material));
+    MaterialPillar { pillar: self, material, stained }
+  }
+
+  fn is_stained_at(&self, z: i32) -> bool {
+    self.stained_below.map_or(false, |underside| z < underside && z >= underside - STAINED_HEIGHT)
+  }
+
+  /// The column on the outline of this pillar facing towards the given column
+  fn side_facing(&self, pos: IVec2) -> IVec2 {
+    let direction = (pos - self.origin).as_dvec2().normalize_or_zero();
+    self.origin + (direction * self.radius as f64).round().as_ivec2()
+  }
 }
 
 impl Geometry for Pillar {
@@ -37,12 +98,261 @@ impl Geometry for Pillar {
     let o = IVec2::splat((self.radius + 1) as i32);
     let min = (self.origin - o).extend(self.min_height);
     let max = (self.origin + o).extend(self.max_height);
-    BoundingBox::new(min, max)
+    BoundingBox::try_new(min, max).unwrap()
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    let radius = self.radius as f32 + 0.5;
     (self.min_height..=self.max_height).contains(&pos.z) &&
-    self.origin.as_vec2().distance(pos.xy().as_vec2()) <= radius
+    self.contains_column(pos.xy())
+  }
+}
+
+impl Encode for Pillar {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put_i32(self.min_height);
+    encoder.put_i32(self.max_height);
+    encoder.put(&self.origin);
+    encoder.put_u32(self.radius);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Pillar {
+      min_height: decoder.take_i32()?,
+      max_height: decoder.take_i32()?,
+      origin: decoder.take()?,
+      radius: decoder.take_u32()?,
+      stained_below: None
+    })
+  }
+}
+
+/// A pillar along with its material, as returned by `Pillar::with_material`
+pub struct MaterialPillar<'a> {
+  pillar: &'a Pillar,
+  material: &'a Block,
+  stained: Option<Block>
+}
+
+impl Geometry for MaterialPillar<'_> {
+  fn bounding_box(&self) -> BoundingBox {
+    self.pillar.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.pillar.block_at(pos)
+  }
+}
+
+impl MaterialGeometry for MaterialPillar<'_> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.pillar.block_at(pos).then(|| match &self.stained {
+      Some(stained) if self.pillar.is_stained_at(pos.z) => Cow::Borrowed(stained),
+      _ => Cow::Borrowed(self.material)
+    })
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    out.insert(self.material.clone());
+    out.extend(self.stained.clone());
+  }
+}
+
+
+
+/// A group of pillars standing close together, with crossed braces between each pair of them.
+/// A lone pillar is a cluster of one, with no braces.
+#[derive(Debug, Clone)]
+pub struct PillarCluster {
+  pillars: Vec<Pillar>,
+  braces: Vec<Brace>
+}
+
+impl PillarCluster {
+  pub fn single(pillar: Pillar) -> Self {
+    PillarCluster { pillars: vec![pillar], braces: Vec::new() }
+  }
+
+  /// Two or three pillars, thinner than a lone pillar of the given radius, spaced around `origin` in a line or a
+  /// triangle. How many there are and how they are turned is picked by hashing `origin`, so the same point always
+  /// gets the same cluster. The pillars reach from `bottom` to `top`, and are braced from `brace_top` downwards.
+  pub fn around(origin: IVec2, radius: u32, bottom: i32, top: i32, brace_top: i32) -> Self {
+    let hash = hash2(CLUSTER_SALT, origin);
+    let count = 2 + (hash & 1) as u32;
+    let turn = hash_to_unit(hash);
+    let radius = radius.saturating_sub(1).max(1);
+    let spacing = (radius + CLUSTER_GAP) as f64;
+    let pillars = (0..count)
+      .map(|i| {
+        let (sin, cos) = sin_cos_turns(turn + i as f64 / count as f64);
+        let offset = (DVec2::new(cos, sin) * spacing).round().as_ivec2();
+        Pillar::new_bounded(origin + offset, radius, Some(bottom), Some(top))
+      })
+      .collect::<Vec<Pillar>>();
+    let braces = brace_pairs(&pillars, brace_top);
+    PillarCluster { pillars, braces }
+  }
+
+  /// The furthest from its origin that any column of a cluster can be, whether it is a lone pillar of the given radius
+  /// or a cluster around the same origin
+  pub fn max_reach(radius: u32) -> u32 {
+    let clustered = radius.saturating_sub(1).max(1);
+    radius.max(clustered * 2 + CLUSTER_GAP)
+  }
+
+  pub fn pillars(&self) -> &[Pillar] {
+    &self.pillars
+  }
+
+  /// Whether the given column falls within the footprint of any of this cluster's pillars or braces
+  pub fn contains_column(&self, pos: IVec2) -> bool {
+    self.pillars.iter().any(|pillar| pillar.contains_column(pos)) ||
+    self.braces.iter().any(|brace| brace.contains_column(pos))
+  }
+
+  /// Stains the pillars of this cluster just beneath the given z value, leaving the braces as they are
+  pub fn weather(&mut self, underside: i32) {
+    self.pillars.iter_mut().for_each(|pillar| pillar.weather(underside));
+  }
+
+  /// Pairs this cluster with the material it is made of
+  pub fn with_material<'a>(&'a self, material: &'a Block) -> MaterialPillarCluster<'a> {
+    let pillars = self.pillars.iter().map(|pillar| pillar.with_material(material)).collect();
+    MaterialPillarCluster { cluster: self, pillars, material }
+  }
+}
+
+impl Geometry for PillarCluster {
+  fn bounding_box(&self) -> BoundingBox {
+    // The braces run between the sides of the pillars, so they never reach outside of them
+    self.pillars.iter().map(Pillar::bounding_box).reduce(BoundingBox::join).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.pillars.iter().any(|pillar| pillar.block_at(pos)) ||
+    self.braces.iter().any(|brace| brace.block_at(pos))
+  }
+}
+
+impl Encode for PillarCluster {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.pillars);
+    encoder.put(&self.braces);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(PillarCluster {
+      pillars: decoder.take()?,
+      braces: decoder.take()?
+    })
+  }
+}
+
+/// A pillar cluster along with its material, as returned by `PillarCluster::with_material`
+pub struct MaterialPillarCluster<'a> {
+  cluster: &'a PillarCluster,
+  pillars: Vec<MaterialPillar<'a>>,
+  material: &'a Block
+}
+
+impl Geometry for MaterialPillarCluster<'_> {
+  fn bounding_box(&self) -> BoundingBox {
+    self.cluster.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.cluster.block_at(pos)
+  }
+}
+
+impl MaterialGeometry for MaterialPillarCluster<'_> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.pillars.iter().find_map(|pillar| pillar.block_material_at(pos)).or_else(|| {
+      self.cluster.braces.iter().any(|brace| brace.block_at(pos)).then(|| Cow::Borrowed(self.material))
+    })
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.pillars.iter().for_each(|pillar| pillar.possible_blocks(out));
+  }
+}
+
+/// A pair of crossed braces between each pair of the given pillars for every `BRACE_BAY_HEIGHT` blocks,
+/// stacked from `top` down as far as both pillars reach, leaving out any bay that would reach past them
+fn brace_pairs(pillars: &[Pillar], top: i32) -> Vec<Brace> {
+  let mut braces = Vec::new();
+  for (i, a) in pillars.iter().enumerate() {
+    for b in pillars[i + 1..].iter() {
+      let (from, to) = (a.side_facing(b.origin), b.side_facing(a.origin));
+      let bottom = a.min_height.max(b.min_height);
+      let mut upper = top.min(a.max_height).min(b.max_height);
+      while upper - BRACE_BAY_HEIGHT >= bottom {
+        let lower = upper - BRACE_BAY_HEIGHT;
+        braces.push(Brace::new(from.extend(upper), to.extend(lower), 1));
+        braces.push(Brace::new(from.extend(lower), to.extend(upper), 1));
+        upper = lower;
+      };
+    };
+  };
+
+  braces
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BOTTOM: i32 = -40;
+  const TOP: i32 = 100;
+  /// Just beneath the lower slab of the landmass the clusters hold up
+  const BRACE_TOP: i32 = 80;
+
+  fn origins() -> impl Iterator<Item = IVec2> {
+    (-3..=3).flat_map(|x| (-3..=3).map(move |y| IVec2::new(x * 37, y * 53)))
+  }
+
+  #[test]
+  fn clusters_are_the_same_around_the_same_point() {
+    let mut counts = BTreeSet::new();
+    for origin in origins() {
+      let cluster = PillarCluster::around(origin, 5, BOTTOM, TOP, BRACE_TOP);
+      let again = PillarCluster::around(origin, 5, BOTTOM, TOP, BRACE_TOP);
+      assert_eq!(format!("{:?}", cluster), format!("{:?}", again), "around {}", origin);
+      counts.insert(cluster.pillars().len());
+
+      let reach = PillarCluster::max_reach(5) as i32;
+      for pillar in cluster.pillars() {
+        assert_eq!(pillar.radius(), 4, "around {}", origin);
+        let furthest = (pillar.origin() - origin).abs().max_element() + pillar.radius() as i32;
+        assert!(furthest <= reach, "a pillar around {} reaches {} out", origin, furthest);
+      };
+    };
+
+    assert_eq!(counts, BTreeSet::from([2, 3]));
+  }
+
+  #[test]
+  fn braces_stay_between_the_slab_and_the_bottoms_of_the_pillars() {
+    for origin in origins() {
+      let cluster = PillarCluster::around(origin, 5, BOTTOM, TOP, BRACE_TOP);
+      let pairs = cluster.pillars().len() * (cluster.pillars().len() - 1) / 2;
+      // Each pair is braced in ten whole bays from the slab down to the bottom of the pillars
+      assert_eq!(cluster.braces.len(), pairs * 2 * 10, "around {}", origin);
+      for brace in cluster.braces.iter() {
+        let BoundingBox { min, max } = brace.bounding_box();
+        assert!(min.z >= BOTTOM && max.z <= BRACE_TOP, "a brace around {} spans {} to {}", origin, min.z, max.z);
+        for point in brace.points() {
+          assert!(cluster.block_at(point), "around {}: the brace at {} isn't part of the cluster", origin, point);
+        };
+      };
+
+      let bounding_box = cluster.bounding_box();
+      assert_eq!((bounding_box.min.z, bounding_box.max.z), (BOTTOM, TOP), "around {}", origin);
+    };
+
+    // Pillars too short for a whole bay aren't braced at all
+    let cluster = PillarCluster::around(IVec2::ZERO, 5, 70, TOP, BRACE_TOP);
+    assert!(cluster.braces.is_empty());
+    // A lone pillar has nothing to be braced to
+    assert!(PillarCluster::single(Pillar::new(IVec2::ZERO, 5)).braces.is_empty());
   }
 }
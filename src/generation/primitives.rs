@@ -0,0 +1,201 @@
+//! Simple shapes that features are built up out of.
+use glam::{IVec2, IVec3};
+
+use super::encoding::{Decoder, Encode, Encoder};
+use super::{BoundingBox, Geometry};
+
+
+
+/// A blocky straight line between two blocks, inclusive, such as a strut between two pillars.
+///
+/// The line takes one step along its longest axis at a time, rounding its position along the other two axes,
+/// so it has exactly one block on each plane across its longest axis and each block touches the last at least
+/// by a corner. It is thickened across its longest axis to `thickness` blocks on each of the other two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Brace {
+  from: IVec3,
+  to: IVec3,
+  thickness: u32
+}
+
+impl Brace {
+  pub fn new(from: IVec3, to: IVec3, thickness: u32) -> Self {
+    Brace { from, to, thickness: thickness.max(1) }
+  }
+
+  /// The axis along which the line is longest, preferring x, then y, then z where it is equally long along several
+  fn major_axis(&self) -> usize {
+    let offset = (self.to - self.from).abs().to_array();
+    let longest = offset.into_iter().max().unwrap();
+    offset.iter().position(|&length| length == longest).unwrap()
+  }
+
+  fn steps(&self) -> i32 {
+    (self.to - self.from).abs().max_element()
+  }
+
+  /// The block on the line at the given step, from 0 at `from` to `steps()` at `to`
+  fn point(&self, step: i32) -> IVec3 {
+    let steps = self.steps().max(1);
+    let offset = (self.to - self.from).as_dvec3();
+    self.from + (offset * step as f64 / steps as f64).round().as_ivec3()
+  }
+
+  /// The blocks making up the middle of the line, from `from` to `to`
+  pub fn points(&self) -> impl Iterator<Item = IVec3> + '_ {
+    (0..=self.steps()).map(|step| self.point(step))
+  }
+
+  /// The range of offsets from the middle of the line which are within its thickness
+  fn spread(&self) -> (i32, i32) {
+    let thickness = self.thickness as i32;
+    (-(thickness - 1) / 2, thickness / 2)
+  }
+
+  /// Whether any block of the line lies in the given column
+  pub fn contains_column(&self, xy: IVec2) -> bool {
+    let (low, high) = self.spread();
+    let major = self.major_axis();
+    self.points().any(|point| {
+      let offset = (xy - point.truncate()).to_array();
+      (0..2).all(|axis| match axis == major {
+        true => offset[axis] == 0,
+        false => (low..=high).contains(&offset[axis])
+      })
+    })
+  }
+}
+
+impl Geometry for Brace {
+  fn bounding_box(&self) -> BoundingBox {
+    let (low, high) = self.spread();
+    let (min, max) = (self.from.min(self.to), self.from.max(self.to));
+    BoundingBox::try_new(min + IVec3::splat(low), max + IVec3::splat(high)).unwrap()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let major = self.major_axis();
+    // Each step moves exactly one block along the longest axis, so only one step can reach the plane of `pos`
+    let direction = match (self.to - self.from).to_array()[major] < 0 {
+      true => -1,
+      false => 1
+    };
+    let step = (pos - self.from).to_array()[major] * direction;
+    if !(0..=self.steps()).contains(&step) { return false };
+
+    let (low, high) = self.spread();
+    let offset = (pos - self.point(step)).to_array();
+    (0..3).filter(|&axis| axis != major).all(|axis| (low..=high).contains(&offset[axis]))
+  }
+}
+
+impl Encode for Brace {
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.put(&self.from);
+    encoder.put(&self.to);
+    encoder.put_u32(self.thickness);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Brace {
+      from: decoder.take()?,
+      to: decoder.take()?,
+      thickness: decoder.take_u32()?
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use glam::DVec3;
+
+  use super::*;
+
+  /// Every block of a brace, found by checking each position within its bounding box
+  fn brace_blocks(brace: &Brace) -> HashSet<IVec3> {
+    let BoundingBox { min, max } = brace.bounding_box();
+    (min.x..=max.x)
+      .flat_map(|x| (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z))))
+      .filter(|&pos| brace.block_at(pos))
+      .collect()
+  }
+
+  /// The distance from the middle of a block to the segment between the middles of two others
+  fn distance_to_segment(pos: IVec3, from: IVec3, to: IVec3) -> f64 {
+    let (pos, from, to) = (pos.as_dvec3(), from.as_dvec3(), to.as_dvec3());
+    let along = to - from;
+    let t = if along == DVec3::ZERO { 0.0 } else { ((pos - from).dot(along) / along.length_squared()).clamp(0.0, 1.0) };
+    pos.distance(from + along * t)
+  }
+
+  fn test_braces() -> Vec<Brace> {
+    let lines = [
+      ([0, 0, 0], [4, 2, 0]),
+      ([0, 0, 0], [0, 0, -5]),
+      ([3, -2, 12], [-3, 1, 0]),
+      ([5, 5, 5], [5, 5, 5]),
+      ([-7, 0, 24], [6, 0, 12]),
+      ([1, 2, 3], [10, -6, -4])
+    ];
+    lines.into_iter()
+      .flat_map(|(from, to)| (1..=3).map(move |thickness| Brace::new(from.into(), to.into(), thickness)))
+      .collect()
+  }
+
+  #[test]
+  fn braces_step_along_their_longest_axis() {
+    let points = |from: [i32; 3], to: [i32; 3]| Brace::new(from.into(), to.into(), 1).points().collect::<Vec<IVec3>>();
+    let expected = [[0, 0, 0], [1, 1, 0], [2, 1, 0], [3, 2, 0], [4, 2, 0]].map(IVec3::from);
+    assert_eq!(points([0, 0, 0], [4, 2, 0]), expected);
+    let expected = [[0, 0, 0], [0, 0, -1], [0, 0, -2], [0, 0, -3]].map(IVec3::from);
+    assert_eq!(points([0, 0, 0], [0, 0, -3]), expected);
+    let expected = [[3, 0, 6], [2, 0, 5], [2, 0, 4], [1, 0, 3], [1, 0, 2], [0, 0, 1], [0, 0, 0]].map(IVec3::from);
+    assert_eq!(points([3, 0, 6], [0, 0, 0]), expected);
+    assert_eq!(points([5, 5, 5], [5, 5, 5]), [IVec3::splat(5)]);
+  }
+
+  #[test]
+  fn thin_braces_are_exactly_their_points() {
+    for brace in test_braces().into_iter().filter(|brace| brace.thickness == 1) {
+      let points = brace.points().collect::<HashSet<IVec3>>();
+      assert_eq!(brace_blocks(&brace), points, "{:?}", brace);
+      assert!(points.contains(&brace.from) && points.contains(&brace.to), "{:?}", brace);
+    };
+  }
+
+  #[test]
+  fn brace_blocks_are_connected_and_near_the_ideal_segment() {
+    for brace in test_braces() {
+      let blocks = brace_blocks(&brace);
+      // Rounding moves the middle of the line by up to half a block on each of the other two axes,
+      // and the thickness spreads it by up to half the thickness more on each of them
+      let reach = (brace.thickness as f64 / 2.0 + 0.5) * 2f64.sqrt();
+      for &pos in blocks.iter() {
+        let distance = distance_to_segment(pos, brace.from, brace.to);
+        assert!(distance <= reach, "{:?}: {} is {} from the line", brace, pos, distance);
+      };
+
+      let mut reached = HashSet::from([brace.from]);
+      let mut frontier = vec![brace.from];
+      while let Some(pos) = frontier.pop() {
+        for x in -1..=1 {
+          for y in -1..=1 {
+            for z in -1..=1 {
+              let next = pos + IVec3::new(x, y, z);
+              if blocks.contains(&next) && reached.insert(next) {
+                frontier.push(next);
+              };
+            };
+          };
+        };
+      };
+
+      assert_eq!(reached.len(), blocks.len(), "{:?} is in pieces", brace);
+      for (xy, z) in blocks.iter().map(|pos| (pos.truncate(), pos.z)) {
+        assert!(brace.contains_column(xy), "{:?}: the column of {} isn't included", brace, xy.extend(z));
+      };
+    };
+  }
+}
@@ -0,0 +1,172 @@
+//! Opt-in instrumentation for finding out which geometries are responsible for slow generation.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use glam::IVec3;
+
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+/// Counts calls to a geometry's sampling methods and the time spent in them
+#[derive(Debug, Default)]
+pub struct ProfileCounters {
+  calls: AtomicU64,
+  hits: AtomicU64,
+  nanos: AtomicU64
+}
+
+impl ProfileCounters {
+  fn record(&self, start: Instant, hit: bool) {
+    let nanos = start.elapsed().as_nanos() as u64;
+    self.calls.fetch_add(1, Ordering::Relaxed);
+    self.hits.fetch_add(hit as u64, Ordering::Relaxed);
+    self.nanos.fetch_add(nanos, Ordering::Relaxed);
+  }
+
+  pub fn calls(&self) -> u64 {
+    self.calls.load(Ordering::Relaxed)
+  }
+
+  /// The number of calls which returned `true` or `Some`
+  pub fn hits(&self) -> u64 {
+    self.hits.load(Ordering::Relaxed)
+  }
+
+  pub fn nanos(&self) -> u64 {
+    self.nanos.load(Ordering::Relaxed)
+  }
+}
+
+/// Wraps a geometry, recording every call to `block_at` and `block_material_at` in a set of counters.
+/// Calls to `bounding_box` are not recorded.
+#[derive(Debug, Clone)]
+pub struct Profiled<G> {
+  inner: G,
+  counters: Arc<ProfileCounters>
+}
+
+impl<G> Profiled<G> {
+  pub fn new(inner: G, counters: Arc<ProfileCounters>) -> Self {
+    Profiled { inner, counters }
+  }
+}
+
+impl<G> Geometry for Profiled<G>
+where G: Geometry {
+  fn bounding_box(&self) -> BoundingBox {
+    self.inner.bounding_box()
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    let start = Instant::now();
+    let result = self.inner.block_at(pos);
+    self.counters.record(start, result);
+    result
+  }
+}
+
+impl<G> MaterialGeometry for Profiled<G>
+where G: MaterialGeometry {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let start = Instant::now();
+    let result = self.inner.block_material_at(pos);
+    self.counters.record(start, result.is_some());
+    result
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.inner.possible_blocks(out);
+  }
+}
+
+
+
+/// A labelled set of counters, which can be printed as a table once profiling is done
+#[derive(Debug, Clone, Default)]
+pub struct ProfileTable {
+  entries: Vec<(String, Arc<ProfileCounters>)>
+}
+
+impl ProfileTable {
+  pub fn new() -> Self {
+    ProfileTable::default()
+  }
+
+  /// Adds a new row to the table, returning the counters that feed into it
+  pub fn register(&mut self, label: impl Into<String>) -> Arc<ProfileCounters> {
+    let counters = Arc::new(ProfileCounters::default());
+    self.entries.push((label.into(), counters.clone()));
+    counters
+  }
+
+  pub fn to_table_string(&self) -> String {
+    let mut out = format!("{:<12} {:>12} {:>12} {:>10} {:>9}\n", "feature", "calls", "total ms", "ns/call", "hit rate");
+    for (label, counters) in self.entries.iter() {
+      let (calls, hits, nanos) = (counters.calls(), counters.hits(), counters.nanos());
+      let ns_per_call = if calls == 0 { 0.0 } else { nanos as f64 / calls as f64 };
+      let hit_rate = if calls == 0 { 0.0 } else { hits as f64 / calls as f64 * 100.0 };
+      out += &format!(
+        "{:<12} {:>12} {:>12.2} {:>10.1} {:>8.2}%\n",
+        label, calls, nanos as f64 / 1_000_000.0, ns_per_call, hit_rate
+      );
+    };
+
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::SolidBox;
+
+  #[test]
+  fn counters_record_every_sample() {
+    let counters = Arc::new(ProfileCounters::default());
+    let solid = SolidBox::new(BoundingBox::new(IVec3::ZERO, IVec3::splat(3)), blocks::DEEPSLATE);
+    let profiled = Profiled::new(solid, counters.clone());
+
+    // Four of the ten positions along the x axis are within the box
+    for x in 0..10 {
+      profiled.block_at(IVec3::new(x, 0, 0));
+    };
+    for x in 0..10 {
+      profiled.block_material_at(IVec3::new(x, 1, 1));
+    };
+    profiled.bounding_box();
+
+    assert_eq!(counters.calls(), 20);
+    assert_eq!(counters.hits(), 8);
+  }
+
+  #[test]
+  fn profiled_clones_share_counters() {
+    let counters = Arc::new(ProfileCounters::default());
+    let solid = SolidBox::new(BoundingBox::new(IVec3::ZERO, IVec3::ZERO), blocks::DEEPSLATE);
+    let profiled = Profiled::new(solid, counters);
+    let clone = profiled.clone();
+    profiled.block_at(IVec3::ZERO);
+    clone.block_at(IVec3::ONE);
+    assert_eq!((profiled.counters.calls(), profiled.counters.hits()), (2, 1));
+  }
+
+  #[test]
+  fn table_lists_each_row() {
+    let mut table = ProfileTable::new();
+    let bedrock = table.register("bedrock");
+    table.register("ocean");
+    bedrock.record(Instant::now(), true);
+    bedrock.record(Instant::now(), false);
+
+    let table = table.to_table_string();
+    let rows = table.lines().collect::<Vec<&str>>();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("bedrock") && rows[1].contains(" 2 ") && rows[1].ends_with("50.00%"));
+    assert!(rows[2].starts_with("ocean") && rows[2].ends_with("0.00%"));
+  }
+}
@@ -0,0 +1,106 @@
+//! Swaps the materials of the world for others, without changing its shapes.
+//!
+//! A theme maps material roles to blockstates. Each role stands for one of the generator's own blocks, and every
+//! block the generator would place is swapped for the block its role is given wherever it is placed, so a role covers
+//! every use of its block rather than one feature. The weathering and altitude shading still pick their blocks from
+//! the unthemed materials, so a themed world keeps the same pattern of stains and shades in its own materials.
+//!
+//! Blocks with properties that the generator picks for their orientation, like chains and stairs, have no roles,
+//! since a themed block couldn't be turned to match them.
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::{blocks, Block};
+
+
+
+/// The name of the theme that leaves every material as it is
+pub const DEFAULT_THEME_NAME: &str = "default";
+
+/// Every material role, named as in a theme file, along with the block that it stands for
+pub static ROLES: [(&str, Block); 25] = [
+  ("bedrock", blocks::BEDROCK),
+  ("water", blocks::WATER),
+  ("sea-floor", blocks::GRAVEL),
+  ("beach", blocks::SAND),
+  ("sea-bed", blocks::DEEPSLATE),
+  ("outcrop", blocks::TUFF),
+  ("darkest-shade", blocks::DEEPSLATE_TILES),
+  ("dark-shade", blocks::GRAY_CONCRETE),
+  ("light-shade", blocks::LIGHT_GRAY_CONCRETE),
+  ("lightest-shade", blocks::CALCITE),
+  ("white", blocks::WHITE_CONCRETE),
+  ("brick", blocks::STONE_BRICKS),
+  ("cracked-brick", blocks::CRACKED_STONE_BRICKS),
+  ("trim", blocks::POLISHED_ANDESITE),
+  ("weathered-trim", blocks::ANDESITE),
+  ("road", blocks::POLISHED_DEEPSLATE),
+  ("metal", blocks::IRON_BLOCK),
+  ("quartz", blocks::SMOOTH_QUARTZ),
+  ("planks", blocks::SPRUCE_PLANKS),
+  ("light", blocks::SEA_LANTERN),
+  ("underside-light", blocks::SHROOMLIGHT),
+  ("moss", blocks::MOSS_BLOCK),
+  ("grass", blocks::GRASS_BLOCK),
+  ("basalt", blocks::POLISHED_BASALT),
+  ("wall", blocks::DEEPSLATE_BRICKS)
+];
+
+/// The blocks given to each material role. Roles which haven't been given a block keep their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+  /// The name the theme is recorded under, usually the name of the file it was read from
+  pub name: String,
+  /// The block given to each role, keyed by the block that the role stands for
+  blocks: HashMap<Block, Block>
+}
+
+impl Theme {
+  /// A theme which leaves every material as it is until its roles are set
+  pub fn new(name: impl Into<String>) -> Self {
+    Theme { name: name.into(), blocks: HashMap::new() }
+  }
+
+  /// Whether this theme leaves every material as it is
+  pub fn is_empty(&self) -> bool {
+    self.blocks.is_empty()
+  }
+
+  /// Gives a role a block, checking that its blockstate is well-formed. Blocks from outside of the minecraft
+  /// namespace are allowed, but warned about, since they may not exist in the world.
+  pub fn set_role(&mut self, role: &str, blockstate: &str) -> Result<(), String> {
+    let (_, original) = ROLES.iter()
+      .find(|(name, _)| *name == role)
+      .ok_or_else(|| "unknown material role".to_owned())?;
+    let block = Block::parse(blockstate)?;
+    if !block.base_name().starts_with("minecraft:") {
+      log::warn!("the material role `{}` is given `{}`, which isn't a vanilla block", role, block.base_blockstate());
+    };
+
+    match block == *original {
+      true => self.blocks.remove(original),
+      false => self.blocks.insert(original.clone(), block)
+    };
+
+    Ok(())
+  }
+
+  /// The block given to each role that doesn't keep its own, in the order of `ROLES`
+  pub fn roles(&self) -> impl Iterator<Item = (&'static str, &Block)> + '_ {
+    ROLES.iter().filter_map(|(role, original)| self.blocks.get(original).map(|block| (*role, block)))
+  }
+
+  /// The block to place in place of the given one
+  pub fn apply<'b>(&'b self, block: Cow<'b, Block>) -> Cow<'b, Block> {
+    match self.blocks.get(&*block) {
+      Some(themed) => Cow::Borrowed(themed),
+      None => block
+    }
+  }
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Theme::new(DEFAULT_THEME_NAME)
+  }
+}
@@ -0,0 +1,247 @@
+//! Combinators which move a geometry around the world without it needing to know where it is.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+
+use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+
+
+
+const CARDINALS: [&str; 4] = ["east", "south", "west", "north"];
+
+/// Moves a geometry by a fixed offset
+#[derive(Debug, Clone)]
+pub struct Translate<G> {
+  geometry: G,
+  offset: IVec3
+}
+
+impl<G> Translate<G> {
+  pub fn new(geometry: G, offset: IVec3) -> Self {
+    Translate { geometry, offset }
+  }
+
+  pub fn offset(&self) -> IVec3 {
+    self.offset
+  }
+
+  /// Converts a position outside of this geometry into the inner geometry's own coordinates
+  #[inline]
+  pub fn to_local(&self, pos: IVec3) -> IVec3 {
+    pos - self.offset
+  }
+
+  #[inline]
+  pub fn to_world(&self, pos: IVec3) -> IVec3 {
+    pos + self.offset
+  }
+
+  pub fn to_local_box(&self, bounding_box: BoundingBox) -> BoundingBox {
+    bounding_box.translated(-self.offset)
+  }
+
+  pub fn to_world_box(&self, bounding_box: BoundingBox) -> BoundingBox {
+    bounding_box.translated(self.offset)
+  }
+}
+
+impl<G> Deref for Translate<G> {
+  type Target = G;
+
+  #[inline]
+  fn deref(&self) -> &G {
+    &self.geometry
+  }
+}
+
+impl<G> Geometry for Translate<G>
+where G: Geometry {
+  fn bounding_box(&self) -> BoundingBox {
+    self.geometry.bounding_box().translated(self.offset)
+  }
+
+  #[inline]
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.geometry.block_at(self.to_local(pos))
+  }
+}
+
+impl<G> MaterialGeometry for Translate<G>
+where G: MaterialGeometry {
+  #[inline]
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    self.geometry.block_material_at(self.to_local(pos))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometry.possible_blocks(out);
+  }
+}
+
+/// Rotates a geometry about the z axis through 0,0 by a number of quarter turns.
+/// Each quarter turn takes east to south, which is clockwise as seen from above in Minecraft.
+/// The blocks of the geometry are rotated along with it, so that stairs and fences still line up.
+#[derive(Debug, Clone)]
+pub struct Rotate90<G> {
+  geometry: G,
+  quarter_turns: u8
+}
+
+impl<G> Rotate90<G> {
+  pub fn new(geometry: G, quarter_turns: u8) -> Self {
+    Rotate90 { geometry, quarter_turns: quarter_turns % 4 }
+  }
+
+  pub fn quarter_turns(&self) -> u8 {
+    self.quarter_turns
+  }
+
+  /// Converts a position outside of this geometry into the inner geometry's own coordinates
+  #[inline]
+  pub fn to_local(&self, pos: IVec3) -> IVec3 {
+    rotate_xy(pos.xy(), 4 - self.quarter_turns).extend(pos.z)
+  }
+
+  #[inline]
+  pub fn to_world(&self, pos: IVec3) -> IVec3 {
+    rotate_xy(pos.xy(), self.quarter_turns).extend(pos.z)
+  }
+
+  pub fn to_local_box(&self, bounding_box: BoundingBox) -> BoundingBox {
+    rotate_box(bounding_box, 4 - self.quarter_turns)
+  }
+
+  pub fn to_world_box(&self, bounding_box: BoundingBox) -> BoundingBox {
+    rotate_box(bounding_box, self.quarter_turns)
+  }
+}
+
+impl<G> Deref for Rotate90<G> {
+  type Target = G;
+
+  #[inline]
+  fn deref(&self) -> &G {
+    &self.geometry
+  }
+}
+
+impl<G> Geometry for Rotate90<G>
+where G: Geometry {
+  fn bounding_box(&self) -> BoundingBox {
+    rotate_box(self.geometry.bounding_box(), self.quarter_turns)
+  }
+
+  #[inline]
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.geometry.block_at(self.to_local(pos))
+  }
+}
+
+impl<G> MaterialGeometry for Rotate90<G>
+where G: MaterialGeometry {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let block = self.geometry.block_material_at(self.to_local(pos))?;
+    Some(rotate_block(block, self.quarter_turns))
+  }
+
+  /// The inner geometry's blocks, rotated along with it
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    let mut blocks = BTreeSet::new();
+    self.geometry.possible_blocks(&mut blocks);
+    out.extend(blocks.into_iter().map(|block| rotate_block(Cow::Owned(block), self.quarter_turns).into_owned()));
+  }
+}
+
+/// Rotates a position about 0,0 by a number of quarter turns, each taking east (+x) to south (+y)
+pub fn rotate_xy(pos: IVec2, quarter_turns: u8) -> IVec2 {
+  // Negation saturates so that unbounded bounding boxes stay unbounded
+  match quarter_turns % 4 {
+    0 => pos,
+    1 => IVec2::new(pos.y.saturating_neg(), pos.x),
+    2 => IVec2::new(pos.x.saturating_neg(), pos.y.saturating_neg()),
+    _ => IVec2::new(pos.y, pos.x.saturating_neg())
+  }
+}
+
+fn rotate_box(bounding_box: BoundingBox, quarter_turns: u8) -> BoundingBox {
+  let min = rotate_xy(bounding_box.min.xy(), quarter_turns).extend(bounding_box.min.z);
+  let max = rotate_xy(bounding_box.max.xy(), quarter_turns).extend(bounding_box.max.z);
+  BoundingBox::new(min, max)
+}
+
+/// Rotates the horizontal block state properties of a block, such as the direction that stairs face,
+/// the axis of a chain, or the sides that a fence connects on.
+/// The extra block is left as it is, since it is only ever water.
+/// Blocks which the rotation leaves unchanged are passed through as they are, without being cloned.
+pub fn rotate_block(block: Cow<'_, Block>, quarter_turns: u8) -> Cow<'_, Block> {
+  if quarter_turns % 4 == 0 { return block };
+  let (name, properties) = match block.base_blockstate().strip_suffix(']').and_then(|s| s.split_once('[')) {
+    Some(split) => split,
+    None => return block
+  };
+
+  let rotate_cardinal = |direction: &str| {
+    let index = CARDINALS.iter().position(|&cardinal| cardinal == direction)?;
+    Some(CARDINALS[(index + quarter_turns as usize) % 4])
+  };
+
+  let properties = properties.split(',')
+    .map(|property| match property.split_once('=') {
+      Some(("facing", value)) => match rotate_cardinal(value) {
+        Some(value) => format!("facing={}", value),
+        None => property.to_owned()
+      },
+      // Minecraft's x and z axes are this generator's x and y axes
+      Some(("axis", "x")) if quarter_turns % 2 == 1 => "axis=z".to_owned(),
+      Some(("axis", "z")) if quarter_turns % 2 == 1 => "axis=x".to_owned(),
+      Some((key, value)) => match rotate_cardinal(key) {
+        Some(key) => format!("{}={}", key, value),
+        None => property.to_owned()
+      },
+      None => property.to_owned()
+    })
+    .collect::<Vec<String>>()
+    .join(",");
+  let base_block = format!("{}[{}]", name, properties);
+  if base_block == block.base_blockstate() { return block };
+  Cow::Owned(match block.extra_blockstate() {
+    Some(extra_block) => Block::from((base_block, extra_block.to_owned())),
+    None => Block::from(base_block)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+
+  #[test]
+  fn rotating_a_borrowed_block_matches_rotating_an_owned_one() {
+    let cases = [
+      blocks::DEEPSLATE, blocks::CHAIN_VERTICAL, blocks::CHAIN_EAST_WEST, blocks::CHEST_SOUTH,
+      blocks::BASALT, blocks::STONE_BRICK_STAIRS_NORTH, blocks::OAK_WALL_SIGN_NORTH
+    ];
+    for block in cases {
+      for quarter_turns in 0..8 {
+        let borrowed = rotate_block(Cow::Borrowed(&block), quarter_turns);
+        let owned = rotate_block(Cow::Owned(block.clone()), quarter_turns);
+        let case = format!("{} turned {} times", block.base_blockstate(), quarter_turns);
+        assert_eq!(borrowed, owned, "{}", case);
+        // Only blocks which the rotation actually changes are cloned
+        assert_eq!(matches!(borrowed, Cow::Borrowed(_)), *borrowed == block, "{}", case);
+      };
+    };
+  }
+
+  #[test]
+  fn rotating_turns_directions_and_axes() {
+    let turned = |block: &Block, quarter_turns| rotate_block(Cow::Borrowed(block), quarter_turns).into_owned();
+    assert_eq!(turned(&blocks::CHEST_SOUTH, 1).base_blockstate(), "minecraft:chest[facing=west]");
+    assert_eq!(turned(&blocks::CHEST_SOUTH, 6).base_blockstate(), "minecraft:chest[facing=north]");
+    assert_eq!(turned(&blocks::CHAIN_EAST_WEST, 1), blocks::CHAIN_NORTH_SOUTH);
+    assert_eq!(turned(&blocks::CHAIN_EAST_WEST, 2), blocks::CHAIN_EAST_WEST);
+    assert_eq!(turned(&blocks::CHAIN_VERTICAL, 3), blocks::CHAIN_VERTICAL);
+  }
+}
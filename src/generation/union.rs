@@ -1,19 +1,41 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::ops::{Deref, DerefMut};
 
 use glam::IVec3;
+use once_cell::sync::OnceCell;
 
-use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+use super::encoding::{Decoder, Encode, Encoder};
+use super::{warn_if_suspiciously_large, Block, BoundingBox, Geometry, MaterialGeometry};
 
 
 
+/// The union of several geometries, where the first geometry to have a block at a position takes precedence.
+/// Members are only sampled at positions within their bounding boxes, which are computed once and then cached.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Union<G> {
-  geometries: G
+  geometries: G,
+  member_bounding_boxes: OnceCell<Vec<BoundingBox>>
 }
 
 impl<G> Union<G> {
   pub fn new(geometries: G) -> Self {
-    Union { geometries }
+    Union {
+      geometries,
+      member_bounding_boxes: OnceCell::new()
+    }
+  }
+}
+
+impl<G> Union<G>
+where G: Members {
+  #[inline]
+  fn member_bounding_boxes(&self) -> &[BoundingBox] {
+    self.member_bounding_boxes.get_or_init(|| {
+      let bounding_boxes = self.geometries.bounding_boxes();
+      bounding_boxes.iter().copied().for_each(warn_if_suspiciously_large);
+      bounding_boxes
+    })
   }
 }
 
@@ -27,8 +49,10 @@ impl<G> Deref for Union<G> {
 }
 
 impl<G> DerefMut for Union<G> {
+  /// The members may be changed through the returned reference, so their cached bounding boxes are discarded
   #[inline]
   fn deref_mut(&mut self) -> &mut G {
+    self.member_bounding_boxes = OnceCell::new();
     &mut self.geometries
   }
 }
@@ -39,15 +63,14 @@ where
   G2: Geometry
 {
   fn bounding_box(&self) -> BoundingBox {
-    let (g1, g2) = &self.geometries;
-    let b1 = g1.bounding_box();
-    let b2 = g2.bounding_box();
-    BoundingBox::join(b1, b2)
+    join_all(self.member_bounding_boxes())
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
     let (g1, g2) = &self.geometries;
-    g1.block_at(pos) || g2.block_at(pos)
+    let boxes = self.member_bounding_boxes();
+    member_block_at(g1, boxes[0], pos) ||
+    member_block_at(g2, boxes[1], pos)
   }
 }
 
@@ -58,16 +81,15 @@ where
   G3: Geometry
 {
   fn bounding_box(&self) -> BoundingBox {
-    let (g1, g2, g3) = &self.geometries;
-    let b1 = g1.bounding_box();
-    let b2 = g2.bounding_box();
-    let b3 = g3.bounding_box();
-    [b1, b2, b3].into_iter().reduce(BoundingBox::join).unwrap()
+    join_all(self.member_bounding_boxes())
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
     let (g1, g2, g3) = &self.geometries;
-    g1.block_at(pos) || g2.block_at(pos) || g3.block_at(pos)
+    let boxes = self.member_bounding_boxes();
+    member_block_at(g1, boxes[0], pos) ||
+    member_block_at(g2, boxes[1], pos) ||
+    member_block_at(g3, boxes[2], pos)
   }
 }
 
@@ -79,17 +101,16 @@ where
   G4: Geometry
 {
   fn bounding_box(&self) -> BoundingBox {
-    let (g1, g2, g3, g4) = &self.geometries;
-    let b1 = g1.bounding_box();
-    let b2 = g2.bounding_box();
-    let b3 = g3.bounding_box();
-    let b4 = g4.bounding_box();
-    [b1, b2, b3, b4].into_iter().reduce(BoundingBox::join).unwrap()
+    join_all(self.member_bounding_boxes())
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
     let (g1, g2, g3, g4) = &self.geometries;
-    g1.block_at(pos) || g2.block_at(pos) || g3.block_at(pos) || g4.block_at(pos)
+    let boxes = self.member_bounding_boxes();
+    member_block_at(g1, boxes[0], pos) ||
+    member_block_at(g2, boxes[1], pos) ||
+    member_block_at(g3, boxes[2], pos) ||
+    member_block_at(g4, boxes[3], pos)
   }
 }
 
@@ -102,50 +123,43 @@ where
   G5: Geometry
 {
   fn bounding_box(&self) -> BoundingBox {
-    let (g1, g2, g3, g4, g5) = &self.geometries;
-    let b1 = g1.bounding_box();
-    let b2 = g2.bounding_box();
-    let b3 = g3.bounding_box();
-    let b4 = g4.bounding_box();
-    let b5 = g5.bounding_box();
-    [b1, b2, b3, b4, b5].into_iter().reduce(BoundingBox::join).unwrap()
+    join_all(self.member_bounding_boxes())
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
     let (g1, g2, g3, g4, g5) = &self.geometries;
-    g1.block_at(pos) || g2.block_at(pos) || g3.block_at(pos) || g4.block_at(pos) | g5.block_at(pos)
+    let boxes = self.member_bounding_boxes();
+    member_block_at(g1, boxes[0], pos) ||
+    member_block_at(g2, boxes[1], pos) ||
+    member_block_at(g3, boxes[2], pos) ||
+    member_block_at(g4, boxes[3], pos) ||
+    member_block_at(g5, boxes[4], pos)
   }
 }
 
 impl<G, const N: usize> Geometry for Union<[G; N]>
 where G: Geometry {
   fn bounding_box(&self) -> BoundingBox {
-    self
-      .geometries
-      .iter()
-      .map(Geometry::bounding_box)
-      .reduce(BoundingBox::join)
-      .unwrap()
+    join_all(self.member_bounding_boxes())
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    self.geometries.iter().any(|geometry| geometry.block_at(pos))
+    let boxes = self.member_bounding_boxes();
+    self.geometries.iter().zip(boxes)
+      .any(|(geometry, &bounding_box)| member_block_at(geometry, bounding_box, pos))
   }
 }
 
 impl<G> Geometry for Union<Vec<G>>
 where G: Geometry {
   fn bounding_box(&self) -> BoundingBox {
-    self
-      .geometries
-      .iter()
-      .map(Geometry::bounding_box)
-      .reduce(BoundingBox::join)
-      .unwrap()
+    join_all(self.member_bounding_boxes())
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    self.geometries.iter().any(|geometry| geometry.block_at(pos))
+    let boxes = self.member_bounding_boxes();
+    self.geometries.iter().zip(boxes)
+      .any(|(geometry, &bounding_box)| member_block_at(geometry, bounding_box, pos))
   }
 }
 
@@ -154,12 +168,19 @@ where
   G1: MaterialGeometry,
   G2: MaterialGeometry
 {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     let (g1, g2) = &self.geometries;
-    ret_if_some!(g1.block_material_at(pos));
-    ret_if_some!(g2.block_material_at(pos));
+    let boxes = self.member_bounding_boxes();
+    ret_if_some!(member_block_material_at(g1, boxes[0], pos));
+    ret_if_some!(member_block_material_at(g2, boxes[1], pos));
     None
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    let (g1, g2) = &self.geometries;
+    g1.possible_blocks(out);
+    g2.possible_blocks(out);
+  }
 }
 
 impl<G1, G2, G3> MaterialGeometry for Union<(G1, G2, G3)>
@@ -168,13 +189,21 @@ where
   G2: MaterialGeometry,
   G3: MaterialGeometry
 {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     let (g1, g2, g3) = &self.geometries;
-    ret_if_some!(g1.block_material_at(pos));
-    ret_if_some!(g2.block_material_at(pos));
-    ret_if_some!(g3.block_material_at(pos));
+    let boxes = self.member_bounding_boxes();
+    ret_if_some!(member_block_material_at(g1, boxes[0], pos));
+    ret_if_some!(member_block_material_at(g2, boxes[1], pos));
+    ret_if_some!(member_block_material_at(g3, boxes[2], pos));
     None
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    let (g1, g2, g3) = &self.geometries;
+    g1.possible_blocks(out);
+    g2.possible_blocks(out);
+    g3.possible_blocks(out);
+  }
 }
 
 impl<G1, G2, G3, G4> MaterialGeometry for Union<(G1, G2, G3, G4)>
@@ -184,14 +213,23 @@ where
   G3: MaterialGeometry,
   G4: MaterialGeometry
 {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     let (g1, g2, g3, g4) = &self.geometries;
-    ret_if_some!(g1.block_material_at(pos));
-    ret_if_some!(g2.block_material_at(pos));
-    ret_if_some!(g3.block_material_at(pos));
-    ret_if_some!(g4.block_material_at(pos));
+    let boxes = self.member_bounding_boxes();
+    ret_if_some!(member_block_material_at(g1, boxes[0], pos));
+    ret_if_some!(member_block_material_at(g2, boxes[1], pos));
+    ret_if_some!(member_block_material_at(g3, boxes[2], pos));
+    ret_if_some!(member_block_material_at(g4, boxes[3], pos));
     None
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    let (g1, g2, g3, g4) = &self.geometries;
+    g1.possible_blocks(out);
+    g2.possible_blocks(out);
+    g3.possible_blocks(out);
+    g4.possible_blocks(out);
+  }
 }
 
 impl<G1, G2, G3, G4, G5> MaterialGeometry for Union<(G1, G2, G3, G4, G5)>
@@ -202,33 +240,271 @@ where
   G4: MaterialGeometry,
   G5: MaterialGeometry
 {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     let (g1, g2, g3, g4, g5) = &self.geometries;
-    ret_if_some!(g1.block_material_at(pos));
-    ret_if_some!(g2.block_material_at(pos));
-    ret_if_some!(g3.block_material_at(pos));
-    ret_if_some!(g4.block_material_at(pos));
-    ret_if_some!(g5.block_material_at(pos));
+    let boxes = self.member_bounding_boxes();
+    ret_if_some!(member_block_material_at(g1, boxes[0], pos));
+    ret_if_some!(member_block_material_at(g2, boxes[1], pos));
+    ret_if_some!(member_block_material_at(g3, boxes[2], pos));
+    ret_if_some!(member_block_material_at(g4, boxes[3], pos));
+    ret_if_some!(member_block_material_at(g5, boxes[4], pos));
     None
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    let (g1, g2, g3, g4, g5) = &self.geometries;
+    g1.possible_blocks(out);
+    g2.possible_blocks(out);
+    g3.possible_blocks(out);
+    g4.possible_blocks(out);
+    g5.possible_blocks(out);
+  }
 }
 
 impl<G, const N: usize> MaterialGeometry for Union<[G; N]>
 where G: MaterialGeometry {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
-    self
-      .geometries
-      .iter()
-      .find_map(|geometry| geometry.block_material_at(pos))
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let boxes = self.member_bounding_boxes();
+    self.geometries.iter().zip(boxes)
+      .find_map(|(geometry, &bounding_box)| member_block_material_at(geometry, bounding_box, pos))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometries.iter().for_each(|geometry| geometry.possible_blocks(out));
   }
 }
 
 impl<G> MaterialGeometry for Union<Vec<G>>
 where G: MaterialGeometry {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
-    self
-      .geometries
-      .iter()
-      .find_map(|geometry| geometry.block_material_at(pos))
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let boxes = self.member_bounding_boxes();
+    self.geometries.iter().zip(boxes)
+      .find_map(|(geometry, &bounding_box)| member_block_material_at(geometry, bounding_box, pos))
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometries.iter().for_each(|geometry| geometry.possible_blocks(out));
+  }
+}
+
+impl<G> Encode for Union<G>
+where G: Encode {
+  fn encode(&self, encoder: &mut Encoder) {
+    self.geometries.encode(encoder);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(Union::new(decoder.take()?))
+  }
+}
+
+/// The members of a union, whose bounding boxes can be listed in order
+pub trait Members {
+  fn bounding_boxes(&self) -> Vec<BoundingBox>;
+}
+
+impl<G1, G2> Members for (G1, G2)
+where
+  G1: Geometry,
+  G2: Geometry
+{
+  fn bounding_boxes(&self) -> Vec<BoundingBox> {
+    let (g1, g2) = self;
+    vec![g1.bounding_box(), g2.bounding_box()]
+  }
+}
+
+impl<G1, G2, G3> Members for (G1, G2, G3)
+where
+  G1: Geometry,
+  G2: Geometry,
+  G3: Geometry
+{
+  fn bounding_boxes(&self) -> Vec<BoundingBox> {
+    let (g1, g2, g3) = self;
+    vec![g1.bounding_box(), g2.bounding_box(), g3.bounding_box()]
+  }
+}
+
+impl<G1, G2, G3, G4> Members for (G1, G2, G3, G4)
+where
+  G1: Geometry,
+  G2: Geometry,
+  G3: Geometry,
+  G4: Geometry
+{
+  fn bounding_boxes(&self) -> Vec<BoundingBox> {
+    let (g1, g2, g3, g4) = self;
+    vec![g1.bounding_box(), g2.bounding_box(), g3.bounding_box(), g4.bounding_box()]
+  }
+}
+
+impl<G1, G2, G3, G4, G5> Members for (G1, G2, G3, G4, G5)
+where
+  G1: Geometry,
+  G2: Geometry,
+  G3: Geometry,
+  G4: Geometry,
+  G5: Geometry
+{
+  fn bounding_boxes(&self) -> Vec<BoundingBox> {
+    let (g1, g2, g3, g4, g5) = self;
+    vec![g1.bounding_box(), g2.bounding_box(), g3.bounding_box(), g4.bounding_box(), g5.bounding_box()]
+  }
+}
+
+impl<G, const N: usize> Members for [G; N]
+where G: Geometry {
+  fn bounding_boxes(&self) -> Vec<BoundingBox> {
+    self.iter().map(Geometry::bounding_box).collect()
+  }
+}
+
+impl<G> Members for Vec<G>
+where G: Geometry {
+  fn bounding_boxes(&self) -> Vec<BoundingBox> {
+    self.iter().map(Geometry::bounding_box).collect()
+  }
+}
+
+/// Joins the bounding boxes of a union's members, of which there must be at least one
+fn join_all(bounding_boxes: &[BoundingBox]) -> BoundingBox {
+  bounding_boxes.iter().copied().reduce(BoundingBox::join).unwrap()
+}
+
+/// Samples a member of a union, unless the position is outside of its bounding box.
+/// Debug builds sample the member regardless, asserting that it has no block there.
+#[inline]
+fn member_block_at(geometry: &impl Geometry, bounding_box: BoundingBox, pos: IVec3) -> bool {
+  if bounding_box.contains(pos) { return geometry.block_at(pos) };
+  debug_assert!(
+    !geometry.block_at(pos),
+    "geometry has a block at {} outside of its bounding box {:?}", pos, bounding_box
+  );
+  false
+}
+
+#[inline]
+fn member_block_material_at(
+  geometry: &impl MaterialGeometry, bounding_box: BoundingBox, pos: IVec3
+) -> Option<Cow<'_, Block>> {
+  if bounding_box.contains(pos) { return geometry.block_material_at(pos) };
+  debug_assert!(
+    !geometry.block_at(pos),
+    "geometry has a block at {} outside of its bounding box {:?}", pos, bounding_box
+  );
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{positions_in, Checkerboard, SingleBlock, SolidBox, Sphere};
+  use crate::generation::tests::warnings_logged_by;
+
+  fn solid() -> SolidBox {
+    SolidBox::new(BoundingBox::new(IVec3::ZERO, IVec3::new(4, 4, 2)), blocks::DEEPSLATE)
+  }
+
+  fn sphere() -> Sphere {
+    Sphere::new(IVec3::new(5, 2, 2), 3, blocks::GRAVEL)
+  }
+
+  fn region() -> BoundingBox {
+    BoundingBox::new(IVec3::splat(-2), IVec3::new(10, 6, 6))
+  }
+
+  #[test]
+  fn earlier_members_take_precedence() {
+    let union = Union::new((solid(), sphere()));
+    // Inside both, inside only the sphere, and inside neither
+    assert_eq!(union.block_material_at(IVec3::new(4, 2, 2)).as_deref(), Some(&blocks::DEEPSLATE));
+    assert_eq!(union.block_material_at(IVec3::new(7, 2, 2)).as_deref(), Some(&blocks::GRAVEL));
+    assert_eq!(union.block_material_at(IVec3::new(9, 6, 6)), None);
+
+    let reversed = Union::new((sphere(), solid()));
+    assert_eq!(reversed.block_material_at(IVec3::new(4, 2, 2)).as_deref(), Some(&blocks::GRAVEL));
+    assert_eq!(reversed.block_material_at(IVec3::new(0, 0, 0)).as_deref(), Some(&blocks::DEEPSLATE));
+  }
+
+  #[test]
+  fn union_has_a_block_wherever_any_member_does() {
+    let union = Union::new((solid(), sphere(), SingleBlock::new(IVec3::new(-1, 5, 5), blocks::SAND)));
+    for pos in positions_in(region()) {
+      let expected = solid().block_at(pos) || sphere().block_at(pos) || pos == IVec3::new(-1, 5, 5);
+      assert_eq!(union.block_at(pos), expected, "at {}", pos);
+      assert_eq!(union.block_material_at(pos).is_some(), expected, "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn bounding_box_joins_the_members() {
+    let union = Union::new((solid(), sphere()));
+    assert_eq!(union.bounding_box(), BoundingBox::new(IVec3::new(0, -1, -1), IVec3::new(8, 5, 5)));
+    let checkerboard = Checkerboard::new(2, 0, 3, blocks::SAND);
+    let union = Union::new((solid(), checkerboard));
+    assert!(union.bounding_box().is_unbounded_xy());
+  }
+
+  #[test]
+  fn suspiciously_large_members_are_warned_about_once() {
+    let huge_box = BoundingBox::new(IVec3::new(-200_000, -200_000, 0), IVec3::new(200_000, 200_000, 300));
+    let huge = SolidBox::new(huge_box, blocks::SAND);
+    let warnings = warnings_logged_by(|| {
+      let union = Union::new((solid(), huge));
+      union.block_at(IVec3::ZERO);
+      union.block_at(IVec3::new(5000, 0, 10));
+      union.bounding_box();
+    });
+    match cfg!(debug_assertions) {
+      true => assert_eq!(warnings, [
+        "union member has a suspiciously large bounding box, from [-200000, -200000, 0] to [200000, 200000, 300]"
+      ]),
+      false => assert_eq!(warnings, Vec::<String>::new())
+    };
+
+    // Members unbounded across the XY plane are meant to be that large
+    let warnings = warnings_logged_by(|| {
+      Union::new((solid(), sphere(), Checkerboard::new(2, 0, 3, blocks::SAND))).block_at(IVec3::ZERO);
+    });
+    assert_eq!(warnings, Vec::<String>::new());
+  }
+
+  #[test]
+  fn every_kind_of_union_agrees() {
+    let members = || {
+      let shifted = SolidBox::new(BoundingBox::new(IVec3::new(2, 2, 0), IVec3::new(7, 5, 4)), blocks::SAND);
+      (solid(), shifted, SolidBox { block: blocks::GRAVEL, ..solid() })
+    };
+    let tuple = Union::new(members());
+    let (m1, m2, m3) = members();
+    let array = Union::new([m1.clone(), m2.clone(), m3.clone()]);
+    let vec = Union::new(vec![m1, m2, m3]);
+    for pos in positions_in(region()) {
+      let expected = tuple.block_material_at(pos);
+      assert_eq!(array.block_material_at(pos), expected, "at {}", pos);
+      assert_eq!(vec.block_material_at(pos), expected, "at {}", pos);
+      assert_eq!(vec.block_at(pos), expected.is_some(), "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn changing_the_members_discards_their_bounding_boxes() {
+    let mut union = Union::new(vec![solid()]);
+    let far = IVec3::new(40, 40, 40);
+    assert!(!union.block_at(far));
+    union.push(SolidBox::new(BoundingBox::new(far, far), blocks::SAND));
+    assert!(union.block_at(far));
+    assert_eq!(union.block_material_at(far).as_deref(), Some(&blocks::SAND));
+    assert_eq!(union.bounding_box(), BoundingBox::new(IVec3::ZERO, far));
+  }
+
+  #[test]
+  fn possible_blocks_covers_every_member() {
+    let union = Union::new((solid(), sphere()));
+    let mut out = BTreeSet::new();
+    union.possible_blocks(&mut out);
+    assert_eq!(out, BTreeSet::from([blocks::DEEPSLATE, blocks::GRAVEL]));
   }
 }
@@ -1,10 +1,13 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::ops::{Deref, DerefMut};
 
 use glam::IVec3;
 use once_cell::sync::OnceCell;
 use rayon::prelude::*;
 
-use super::{Block, BoundingBox, Geometry, MaterialGeometry};
+use super::encoding::{Decoder, Encode, Encoder};
+use super::{warn_if_suspiciously_large, Block, BoundingBox, Geometry, MaterialGeometry};
 
 
 
@@ -33,8 +36,10 @@ impl<G> Deref for UnionThreaded<G> {
 }
 
 impl<G> DerefMut for UnionThreaded<G> {
+  /// The members may be changed through the returned reference, so their cached bounding box is discarded
   #[inline]
   fn deref_mut(&mut self) -> &mut G {
+    self.bounding_box = OnceCell::new();
     &mut self.geometries
   }
 }
@@ -45,6 +50,7 @@ where G: Geometry + Sync {
     *self.bounding_box.get_or_init(|| {
       self.geometries.iter()
         .map(Geometry::bounding_box)
+        .inspect(|&bounding_box| warn_if_suspiciously_large(bounding_box))
         .reduce(BoundingBox::join)
         .unwrap()
     })
@@ -56,26 +62,28 @@ where G: Geometry + Sync {
   }
 }
 
+/// A union of no geometries has no bounding box, but is empty everywhere
 impl<G> Geometry for UnionThreaded<Vec<G>>
 where G: Geometry + Sync {
   fn bounding_box(&self) -> BoundingBox {
     *self.bounding_box.get_or_init(|| {
       self.geometries.iter()
         .map(Geometry::bounding_box)
+        .inspect(|&bounding_box| warn_if_suspiciously_large(bounding_box))
         .reduce(BoundingBox::join)
         .unwrap()
     })
   }
 
   fn block_at(&self, pos: IVec3) -> bool {
-    self.bounding_box().contains(pos) &&
+    !self.geometries.is_empty() && self.bounding_box().contains(pos) &&
     self.geometries.par_iter().any(|geometry| geometry.block_at(pos))
   }
 }
 
 impl<G, const N: usize> MaterialGeometry for UnionThreaded<[G; N]>
 where G: MaterialGeometry + Sync {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
     if self.bounding_box().contains(pos) {
       self.geometries.par_iter()
         .find_map_first(|geometry| geometry.block_material_at(pos))
@@ -83,16 +91,89 @@ where G: MaterialGeometry + Sync {
       None
     }
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometries.iter().for_each(|geometry| geometry.possible_blocks(out));
+  }
 }
 
 impl<G> MaterialGeometry for UnionThreaded<Vec<G>>
 where G: MaterialGeometry + Sync {
-  fn block_material_at(&self, pos: IVec3) -> Option<Block> {
-    if self.bounding_box().contains(pos) {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    if !self.geometries.is_empty() && self.bounding_box().contains(pos) {
       self.geometries.par_iter()
         .find_map_first(|geometry| geometry.block_material_at(pos))
     } else {
       None
     }
   }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.geometries.iter().for_each(|geometry| geometry.possible_blocks(out));
+  }
+}
+
+impl<G> Encode for UnionThreaded<G>
+where G: Encode {
+  fn encode(&self, encoder: &mut Encoder) {
+    self.geometries.encode(encoder);
+  }
+
+  fn decode(decoder: &mut Decoder) -> Option<Self> {
+    Some(UnionThreaded::new(decoder.take()?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::generation::blocks;
+  use crate::generation::fixtures::{positions_in, Sphere};
+  use crate::generation::union::Union;
+
+  /// Overlapping spheres of different blocks, so that the order they are sampled in matters
+  fn spheres() -> [Sphere; 4] {
+    [
+      Sphere::new(IVec3::new(0, 0, 0), 4, blocks::SAND),
+      Sphere::new(IVec3::new(3, 0, 1), 3, blocks::GRAVEL),
+      Sphere::new(IVec3::new(0, 4, 0), 2, blocks::CALCITE),
+      Sphere::new(IVec3::new(-2, -2, -2), 5, blocks::TUFF)
+    ]
+  }
+
+  #[test]
+  fn threaded_union_matches_union() {
+    let union = Union::new(spheres());
+    let threaded = UnionThreaded::new(spheres());
+    let threaded_vec = UnionThreaded::new(spheres().to_vec());
+    assert_eq!(threaded.bounding_box(), union.bounding_box());
+    assert_eq!(threaded_vec.bounding_box(), union.bounding_box());
+    for pos in positions_in(BoundingBox::new(IVec3::splat(-8), IVec3::splat(8))) {
+      let expected = union.block_material_at(pos);
+      assert_eq!(threaded.block_at(pos), expected.is_some(), "at {}", pos);
+      assert_eq!(threaded.block_material_at(pos), expected, "at {}", pos);
+      assert_eq!(threaded_vec.block_material_at(pos), expected, "at {}", pos);
+    };
+  }
+
+  #[test]
+  fn threaded_unions_follow_changes_to_their_members() {
+    let mut threaded = UnionThreaded::new(spheres().to_vec());
+    assert!(threaded.block_at(IVec3::new(-6, -2, -2)));
+    threaded.truncate(3);
+    assert_eq!(threaded.bounding_box(), Union::new(spheres()[..3].to_vec()).bounding_box());
+    assert!(!threaded.block_at(IVec3::new(-6, -2, -2)));
+
+    // Without any members, nothing is anywhere
+    threaded.clear();
+    assert!(!threaded.block_at(IVec3::ZERO));
+    assert_eq!(threaded.block_material_at(IVec3::ZERO), None);
+  }
+
+  #[test]
+  fn threaded_union_has_every_possible_block() {
+    let mut out = BTreeSet::new();
+    UnionThreaded::new(spheres()).possible_blocks(&mut out);
+    assert_eq!(out, BTreeSet::from([blocks::SAND, blocks::GRAVEL, blocks::CALCITE, blocks::TUFF]));
+  }
 }
@@ -0,0 +1,384 @@
+//! Golden samples: the blocks generated at a representative position of every kind of feature, for a few seeds,
+//! recorded in a JSON file checked in alongside the source. Checking them catches changes to generation which
+//! alter what existing seeds generate, which are otherwise easy to let slip through a refactor.
+//!
+//! `glt-mc-generator goldens` checks the generator against the file, and `--regenerate-goldens` rewrites it for
+//! when a change to what is generated is intentional. The positions are chosen by `Generator::feature_probes`,
+//! so a new feature is covered once it is added there and the file is regenerated.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use glam::IVec3;
+
+use crate::cli::parse_generation_flags;
+use crate::generation::Block;
+use crate::utility::json_string;
+use crate::Generator;
+
+/// The seeds which golden samples are recorded for, along with the generation flags each is generated with
+const GOLDEN_CASES: [(u64, &str); 3] = [
+  (0, "--layers 1"),
+  (1, "--layers 2 --details --baked-shadows"),
+  (2, "--layers 1 --foundation skirt --wavy-ocean --erode-landmasses")
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenCase {
+  pub seed: u64,
+  /// The generation flags the seed is generated with, as taken by `parse_generation_flags`
+  pub flags: String,
+  pub samples: Vec<GoldenSample>
+}
+
+impl GoldenCase {
+  /// Generates the given seed and samples a representative block of each of its features
+  pub fn record(seed: u64, flags: &str) -> io::Result<Self> {
+    let generator = new_generator(seed, flags)?;
+    let samples = generator.feature_probes().into_iter()
+      .map(|(feature, pos)| GoldenSample { feature, pos, block: block_string(generator.block_at(pos)) })
+      .collect();
+    Ok(GoldenCase { seed, flags: flags.to_owned(), samples })
+  }
+
+  /// Generates this case's seed again, returning each sample whose block has changed along with the block found now
+  pub fn check(&self) -> io::Result<Vec<(&GoldenSample, Option<String>)>> {
+    let generator = new_generator(self.seed, &self.flags)?;
+    let changed = self.samples.iter()
+      .map(|sample| (sample, block_string(generator.block_at(sample.pos))))
+      .filter(|(sample, block)| sample.block != *block)
+      .collect();
+    Ok(changed)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenSample {
+  /// The feature this position was chosen to show
+  pub feature: String,
+  pub pos: IVec3,
+  /// The block at this position as written by `block_string`, or `None` if there is no block there
+  pub block: Option<String>
+}
+
+impl GoldenSample {
+  /// A line describing how this sample of the given case changed, given the block found there now
+  pub fn describe_change(&self, case: &GoldenCase, found: Option<&str>) -> String {
+    format!(
+      "seed {} ({}): {} at {}, {}, {}: expected {}, found {}",
+      case.seed, case.flags, self.feature, self.pos.x, self.pos.y, self.pos.z,
+      self.block.as_deref().unwrap_or("nothing"), found.unwrap_or("nothing")
+    )
+  }
+}
+
+/// Records every golden case
+pub fn record_goldens() -> io::Result<Vec<GoldenCase>> {
+  GOLDEN_CASES.iter().map(|&(seed, flags)| GoldenCase::record(seed, flags)).collect()
+}
+
+pub fn write_goldens(path: &Path, cases: &[GoldenCase]) -> io::Result<()> {
+  let cases = cases.iter()
+    .map(|case| {
+      let samples = case.samples.iter()
+        .map(|sample| format!(
+          "        {{\"feature\": {}, \"pos\": [{}, {}, {}], \"block\": {}}}",
+          json_string(&sample.feature), sample.pos.x, sample.pos.y, sample.pos.z,
+          sample.block.as_deref().map_or("null".to_owned(), json_string)
+        ))
+        .collect::<Vec<String>>();
+      format!(
+        "    {{\n      \"seed\": {},\n      \"flags\": {},\n      \"samples\": [\n{}\n      ]\n    }}",
+        case.seed, json_string(&case.flags), samples.join(",\n")
+      )
+    })
+    .collect::<Vec<String>>();
+
+  fs::write(path, format!("{{\n  \"cases\": [\n{}\n  ]\n}}\n", cases.join(",\n")))
+}
+
+pub fn read_goldens(path: &Path) -> io::Result<Vec<GoldenCase>> {
+  let invalid = |message: String| {
+    let message = format!("{} is not a valid golden sample file: {}", path.display(), message);
+    io::Error::new(io::ErrorKind::InvalidData, message)
+  };
+
+  let json = Json::parse(&fs::read_to_string(path)?).map_err(invalid)?;
+  cases_from_json(&json).ok_or_else(|| invalid("it is missing fields or has fields of the wrong type".to_owned()))
+}
+
+/// Writes a block as its blockstate, followed by ` + ` and its extra blockstate if it has one
+pub fn block_string(block: Option<Block>) -> Option<String> {
+  block.map(|block| match block.extra_blockstate() {
+    Some(extra_blockstate) => format!("{} + {}", block.base_blockstate(), extra_blockstate),
+    None => block.base_blockstate().to_owned()
+  })
+}
+
+fn new_generator(seed: u64, flags: &str) -> io::Result<Generator> {
+  let mut builder = parse_generation_flags(flags).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+  // The cache could hold layers generated by an older version of the generator
+  builder.reseed(seed).cache(false);
+  builder.build().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn cases_from_json(json: &Json) -> Option<Vec<GoldenCase>> {
+  json.get("cases")?.as_array()?.iter()
+    .map(|case| {
+      let samples = case.get("samples")?.as_array()?.iter()
+        .map(|sample| {
+          let pos = match sample.get("pos")?.as_array()? {
+            [x, y, z] => IVec3::new(x.as_number()?, y.as_number()?, z.as_number()?),
+            _ => return None
+          };
+          let block = match sample.get("block")? {
+            Json::Null => None,
+            Json::String(block) => Some(block.clone()),
+            _ => return None
+          };
+
+          Some(GoldenSample { feature: sample.get("feature")?.as_str()?.to_owned(), pos, block })
+        })
+        .collect::<Option<Vec<GoldenSample>>>()?;
+
+      let seed = case.get("seed")?.as_number()?;
+      Some(GoldenCase { seed, flags: case.get("flags")?.as_str()?.to_owned(), samples })
+    })
+    .collect()
+}
+
+
+
+/// Just enough of JSON to read back a golden sample file.
+/// Numbers are kept as they were written, so that they can be parsed into whichever type is expected.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+  Null,
+  Bool(bool),
+  Number(String),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>)
+}
+
+impl Json {
+  fn parse(src: &str) -> Result<Self, String> {
+    let mut parser = JsonParser { src, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.pos == src.len() {
+      true => Ok(value),
+      false => Err(parser.error("unexpected characters after the end of the document"))
+    }
+  }
+
+  fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, value)| value),
+      _ => None
+    }
+  }
+
+  fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(items) => Some(items),
+      _ => None
+    }
+  }
+
+  fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(s) => Some(s),
+      _ => None
+    }
+  }
+
+  fn as_number<T: FromStr>(&self) -> Option<T> {
+    match self {
+      Json::Number(number) => number.parse().ok(),
+      _ => None
+    }
+  }
+}
+
+struct JsonParser<'a> {
+  src: &'a str,
+  /// The byte offset of the next character to be read
+  pos: usize
+}
+
+impl<'a> JsonParser<'a> {
+  fn peek(&self) -> Option<u8> {
+    self.src.as_bytes().get(self.pos).copied()
+  }
+
+  fn skip_whitespace(&mut self) {
+    while self.peek().map_or(false, |c| c.is_ascii_whitespace()) {
+      self.pos += 1;
+    };
+  }
+
+  fn expect(&mut self, c: u8) -> Result<(), String> {
+    self.skip_whitespace();
+    if self.peek() != Some(c) {
+      return Err(self.error(format!("expected `{}`", c as char)));
+    };
+
+    self.pos += 1;
+    Ok(())
+  }
+
+  fn value(&mut self) -> Result<Json, String> {
+    self.skip_whitespace();
+    match self.peek() {
+      Some(b'n') => self.literal("null", Json::Null),
+      Some(b't') => self.literal("true", Json::Bool(true)),
+      Some(b'f') => self.literal("false", Json::Bool(false)),
+      Some(b'"') => self.string().map(Json::String),
+      Some(b'[') => {
+        self.pos += 1;
+        let items = self.list(b']', |parser| parser.value())?;
+        Ok(Json::Array(items))
+      },
+      Some(b'{') => {
+        self.pos += 1;
+        let entries = self.list(b'}', |parser| {
+          parser.skip_whitespace();
+          let key = parser.string()?;
+          parser.expect(b':')?;
+          Ok((key, parser.value()?))
+        })?;
+        Ok(Json::Object(entries))
+      },
+      Some(b'-' | b'0'..=b'9') => {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+          self.pos += 1;
+        };
+
+        Ok(Json::Number(self.src[start..self.pos].to_owned()))
+      },
+      _ => Err(self.error("expected a value"))
+    }
+  }
+
+  /// Parses comma separated items up to the given closing bracket, the opening bracket having already been read
+  fn list<T>(&mut self, close: u8, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let mut items = Vec::new();
+    self.skip_whitespace();
+    if self.peek() == Some(close) {
+      self.pos += 1;
+      return Ok(items);
+    };
+
+    loop {
+      items.push(item(self)?);
+      self.skip_whitespace();
+      match self.peek() {
+        Some(b',') => self.pos += 1,
+        Some(c) if c == close => {
+          self.pos += 1;
+          return Ok(items);
+        },
+        _ => return Err(self.error(format!("expected `,` or `{}`", close as char)))
+      };
+    }
+  }
+
+  fn literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+    match self.src[self.pos..].starts_with(literal) {
+      true => {
+        self.pos += literal.len();
+        Ok(value)
+      },
+      false => Err(self.error("expected a value"))
+    }
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    self.expect(b'"')?;
+    let mut out = String::new();
+    let mut chars = self.src[self.pos..].char_indices();
+    while let Some((i, c)) = chars.next() {
+      let c = match c {
+        '"' => {
+          self.pos += i + 1;
+          return Ok(out);
+        },
+        '\\' => match chars.next().map(|(_, escape)| escape) {
+          Some('"') => '"',
+          Some('\\') => '\\',
+          Some('/') => '/',
+          Some('n') => '\n',
+          Some('r') => '\r',
+          Some('t') => '\t',
+          Some('b') => '\u{8}',
+          Some('f') => '\u{c}',
+          Some('u') => {
+            let hex = chars.by_ref().take(4).map(|(_, c)| c).collect::<String>();
+            u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+              .ok_or_else(|| self.error(format!("invalid unicode escape `\\u{}`", hex)))?
+          },
+          _ => return Err(self.error("invalid escape in string"))
+        },
+        c => c
+      };
+
+      out.push(c);
+    };
+
+    Err(self.error("unterminated string"))
+  }
+
+  fn error(&self, message: impl Into<String>) -> String {
+    format!("{} at byte {}", message.into(), self.pos)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use super::*;
+
+  fn goldens_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("goldens.json")
+  }
+
+  #[test]
+  fn goldens_cover_every_case() {
+    let cases = read_goldens(&goldens_path()).expect("failed to read the golden samples");
+    let seeds_and_flags = cases.iter().map(|case| (case.seed, case.flags.as_str())).collect::<Vec<_>>();
+    assert_eq!(seeds_and_flags, GOLDEN_CASES, "the golden samples are out of date, regenerate them");
+    assert!(cases.iter().all(|case| !case.samples.is_empty()));
+  }
+
+  #[test]
+  fn goldens_round_trip_through_json() {
+    let cases = read_goldens(&goldens_path()).expect("failed to read the golden samples");
+    let path = std::env::temp_dir().join(format!("glt-goldens-{}.json", std::process::id()));
+    write_goldens(&path, &cases).expect("failed to write the golden samples");
+    let read = read_goldens(&path);
+    fs::remove_file(&path).expect("failed to remove the written golden samples");
+    assert_eq!(read.expect("failed to read back the golden samples"), cases);
+  }
+
+  /// Fails listing every sample which changed, when generation no longer matches the golden samples
+  #[test]
+  fn generation_matches_the_goldens() {
+    let cases = read_goldens(&goldens_path()).expect("failed to read the golden samples");
+    let mut changes = Vec::new();
+    for case in &cases {
+      for (sample, found) in case.check().expect("failed to generate a golden case") {
+        changes.push(sample.describe_change(case, found.as_deref()));
+      };
+    };
+
+    assert!(
+      changes.is_empty(),
+      "{} golden samples changed, run `glt-mc-generator goldens --regenerate-goldens` if this is intentional:\n{}",
+      changes.len(), changes.join("\n")
+    );
+  }
+}
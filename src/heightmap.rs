@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::BoundingBox;
+use crate::png;
+
+
+
+/// The topmost block of every column in a region of chunks, which can be drawn as a grayscale image
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+  chunk_min: ChunkPos,
+  chunk_max: ChunkPos,
+  min_z: i32,
+  max_z: i32,
+  chunks: HashMap<ChunkPos, Vec<Option<i32>>>
+}
+
+impl Heightmap {
+  /// Creates an empty heightmap covering the given bounding box, which also determines the range of its shading
+  pub fn new(bounding_box: BoundingBox) -> Self {
+    let (chunk_min, chunk_max) = bounding_box.to_chunks();
+    Heightmap {
+      chunk_min,
+      chunk_max,
+      min_z: bounding_box.min.z,
+      max_z: bounding_box.max.z,
+      chunks: HashMap::new()
+    }
+  }
+
+  /// Sets the heights of every column in a chunk, indexed by `y * 16 + x`
+  pub fn insert(&mut self, chunk: ChunkPos, heights: Vec<Option<i32>>) {
+    self.chunks.insert(chunk, heights);
+  }
+
+  /// Draws the heightmap as a grayscale PNG image, with north at the top.
+  /// Columns which haven't been inserted yet (or are empty) are black.
+  pub fn to_png(&self) -> Vec<u8> {
+    let size = self.chunk_max.max_block() - self.chunk_min.min_block() + 1;
+    let (width, height) = (size.x as usize, size.y as usize);
+    let range = (self.max_z - self.min_z).max(1) as i64;
+
+    let mut pixels = vec![0u8; width * height];
+    for (&chunk, heights) in self.chunks.iter() {
+      let origin = chunk.min_block() - self.chunk_min.min_block();
+      for (i, z) in heights.iter().enumerate() {
+        if let Some(z) = *z {
+          let (x, y) = (origin.x as usize + i % 16, origin.y as usize + i / 16);
+          let shade = 1 + (z - self.min_z).clamp(0, range as i32) as i64 * 254 / range;
+          pixels[y * width + x] = shade as u8;
+        };
+      };
+    };
+
+    png::encode_grayscale(width as u32, height as u32, &pixels)
+  }
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+use std::{fs, io};
+
+use glam::{IVec2, IVec3, UVec2};
+use rayon::prelude::*;
+
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::{blocks, Block, BoundingBox, MaterialGeometry};
+use crate::utility::hash2;
+use crate::{png, Generator};
+
+
+
+/// The number of chunks sampled in parallel before their blocks are painted
+const BATCH_SIZE: usize = 64;
+const BACKGROUND: [u8; 3] = [24, 26, 32];
+const WATER_COLOR: [u8; 3] = [40, 90, 200];
+/// The brightness of the top, +y and +x faces of each block
+const FACE_SHADES: [f64; 3] = [1.0, 0.6, 0.8];
+
+/// Renders the generator as seen from above its +x, +y corner and writes it to a PNG image.
+/// Each block covers `2 * scale` by `2 * scale` pixels.
+pub fn render_isometric(generator: &Generator, path: &Path, scale: u32) -> io::Result<()> {
+  fs::write(path, render_isometric_png(generator, scale))
+}
+
+pub fn render_isometric_png(generator: &Generator, scale: u32) -> Vec<u8> {
+  let chunks = generator.chunks().collect::<Vec<ChunkPos>>();
+  let (size, rgb) = render_chunks(generator, &chunks, scale);
+  png::encode_rgb(size.x, size.y, &rgb)
+}
+
+/// Draws the given chunks of the generator into an image framing its whole bounding box,
+/// returning the size of the image and its pixels
+fn render_chunks(generator: &Generator, chunks: &[ChunkPos], scale: u32) -> (UVec2, Vec<u8>) {
+  let projection = Projection::new(generator.bounding_box, scale.max(1) as i32);
+  let mut framebuffer = Framebuffer::new(projection.size);
+  for (i, batch) in chunks.chunks(BATCH_SIZE).enumerate() {
+    log::info!("drawing chunks: {:>5.2}%", (i * BATCH_SIZE) as f32 / chunks.len() as f32 * 100.0);
+    let draws = batch.par_iter()
+      .map(|&chunk_pos| sample_chunk(generator, chunk_pos))
+      .collect::<Vec<Vec<Draw>>>();
+    // Painting happens in chunk order, so that the image is the same regardless of scheduling
+    for draw in draws.into_iter().flatten() {
+      framebuffer.paint(&projection, draw);
+    };
+  };
+
+  (projection.size.as_uvec2(), framebuffer.to_rgb())
+}
+
+/// Samples every block in a chunk, returning the visible ones in no particular order.
+///
+/// Columns are sampled from the top down. Below the ocean's surface, sampling stops at the first solid block,
+/// since anything beneath it would only be seen through the side of the world.
+fn sample_chunk(generator: &Generator, chunk_pos: ChunkPos) -> Vec<Draw> {
+  let context = generator.chunk_context(chunk_pos);
+  let BoundingBox { min, max } = generator.bounding_box;
+  let height = (max.z - min.z + 1) as usize;
+  let index = |x: i32, y: i32, z: i32| ((y * 16 + x) as usize) * height + (z - min.z) as usize;
+
+  let mut voxels = vec![Voxel::Hidden; 256 * height];
+  for y in 0..16 {
+    for x in 0..16 {
+      let column = chunk_pos.min_block() + IVec2::new(x, y);
+      for z in (min.z..=max.z).rev() {
+        let voxel = Voxel::from_block(context.block_material_at(column.extend(z)).as_deref(), z, min.z, max.z);
+        voxels[index(x, y, z)] = voxel;
+        if z <= 0 && matches!(voxel, Voxel::Solid(_)) { break };
+      };
+    };
+  };
+
+  let opaque_at = |x: i32, y: i32, z: i32| {
+    x < 16 && y < 16 && z <= max.z && matches!(voxels[index(x, y, z)], Voxel::Solid(_) | Voxel::Hidden)
+  };
+
+  let mut draws = Vec::new();
+  for y in 0..16 {
+    for x in 0..16 {
+      for z in min.z..=max.z {
+        let pos = chunk_pos.min_block().extend(0) + IVec3::new(x, y, z);
+        match voxels[index(x, y, z)] {
+          // Blocks with solid blocks in front of all three of their visible faces are completely covered
+          Voxel::Solid(color) if !(opaque_at(x + 1, y, z) && opaque_at(x, y + 1, z) && opaque_at(x, y, z + 1)) => {
+            draws.push(Draw { pos, color, water: false });
+          },
+          Voxel::Water if z == max.z || voxels[index(x, y, z + 1)] != Voxel::Water => {
+            draws.push(Draw { pos, color: WATER_COLOR, water: true });
+          },
+          _ => ()
+        };
+      };
+    };
+  };
+
+  draws
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Voxel {
+  Empty,
+  Water,
+  Solid([u8; 3]),
+  /// Beneath the sea floor, and never sampled
+  Hidden
+}
+
+impl Voxel {
+  fn from_block(block: Option<&Block>, z: i32, min_z: i32, max_z: i32) -> Self {
+    let block = match block {
+      Some(block) => block,
+      None => return Voxel::Empty
+    };
+
+    if *block == blocks::WATER {
+      return Voxel::Water;
+    };
+
+    match block_color(block) {
+      Some(color) => {
+        // Higher blocks are brighter, making the layers easier to tell apart
+        let shade = 0.7 + 0.3 * (z - min_z) as f64 / (max_z - min_z).max(1) as f64;
+        Voxel::Solid(color.map(|channel| (channel as f64 * shade) as u8))
+      },
+      // Plants growing in the water are seen as the water they sit in
+      None if block.base_name().ends_with("seagrass") => Voxel::Water,
+      None => Voxel::Empty
+    }
+  }
+}
+
+/// The base color of a block, or `None` if the block should not be drawn
+fn block_color(block: &Block) -> Option<[u8; 3]> {
+  let color = match block.base_name() {
+    "minecraft:seagrass" | "minecraft:tall_seagrass" => return None,
+    "minecraft:gravel" => [130, 124, 120],
+    "minecraft:deepslate" => [80, 80, 85],
+    "minecraft:polished_deepslate" => [72, 72, 73],
+    "minecraft:polished_basalt" => [88, 88, 92],
+    "minecraft:bedrock" => [50, 50, 50],
+    "minecraft:gray_concrete" => [110, 112, 116],
+    "minecraft:light_gray_concrete" => [160, 160, 152],
+    "minecraft:white_concrete" => [207, 213, 214],
+    "minecraft:calcite" => [223, 224, 220],
+    "minecraft:stone_bricks" | "minecraft:stone_brick_stairs" => [122, 121, 122],
+    "minecraft:polished_andesite" => [132, 135, 134],
+    "minecraft:andesite" => [136, 136, 136],
+    "minecraft:deepslate_tiles" => [54, 54, 55],
+    "minecraft:deepslate_bricks" => [66, 66, 68],
+    "minecraft:cracked_stone_bricks" => [118, 117, 118],
+    "minecraft:smooth_stone_slab" => [158, 158, 158],
+    "minecraft:smooth_quartz" => [235, 229, 222],
+    "minecraft:iron_block" | "minecraft:iron_bars" => [200, 200, 200],
+    "minecraft:chain" => [50, 55, 70],
+    "minecraft:dark_oak_fence" => [66, 43, 20],
+    "minecraft:lantern" => [220, 170, 80],
+    "minecraft:sea_lantern" => [172, 200, 190],
+    "minecraft:chest" | "minecraft:oak_wall_sign" => [160, 120, 60],
+    "minecraft:barrel" => [134, 100, 58],
+    "minecraft:cobbled_deepslate" | "minecraft:cobbled_deepslate_slab" => [77, 77, 80],
+    // Anything else gets an arbitrary but consistent color
+    name => {
+      let hash = name.bytes().fold(0, |hash, byte| hash2(hash, IVec2::new(byte as i32, 0)));
+      [64 + (hash & 0x7f) as u8, 64 + ((hash >> 8) & 0x7f) as u8, 64 + ((hash >> 16) & 0x7f) as u8]
+    }
+  };
+
+  Some(color)
+}
+
+
+
+#[derive(Debug, Clone, Copy)]
+struct Draw {
+  pos: IVec3,
+  color: [u8; 3],
+  water: bool
+}
+
+/// Maps block positions onto the image, in a 2:1 dimetric projection
+#[derive(Debug, Clone, Copy)]
+struct Projection {
+  scale: i32,
+  origin: IVec2,
+  size: IVec2
+}
+
+impl Projection {
+  fn new(bounding_box: BoundingBox, scale: i32) -> Self {
+    let BoundingBox { min, max } = bounding_box;
+    let project = |x: i32, y: i32, z: i32| screen_pos(IVec3::new(x, y, z), scale);
+    let origin = IVec2::new(project(min.x, max.y, 0).x, project(min.x, min.y, max.z).y);
+    let extent = IVec2::new(project(max.x, min.y, 0).x, project(max.x, max.y, min.z).y);
+    let size = extent - origin + 2 * scale;
+    Projection { scale, origin, size }
+  }
+
+  /// The top left corner of the area covered by the block at the given position
+  fn project(&self, pos: IVec3) -> IVec2 {
+    screen_pos(pos, self.scale) - self.origin
+  }
+}
+
+fn screen_pos(pos: IVec3, scale: i32) -> IVec2 {
+  let x = (pos.x - pos.y) * scale;
+  let y = ((pos.x + pos.y - 2 * pos.z) * scale).div_euclid(2);
+  IVec2::new(x, y)
+}
+
+/// An image with a depth buffer, along with a separate translucent layer for the water's surface
+struct Framebuffer {
+  size: IVec2,
+  color: Vec<[u8; 3]>,
+  depth: Vec<i32>,
+  water_depth: Vec<i32>
+}
+
+impl Framebuffer {
+  fn new(size: IVec2) -> Self {
+    let len = (size.x * size.y) as usize;
+    Framebuffer {
+      size,
+      color: vec![BACKGROUND; len],
+      depth: vec![i32::MIN; len],
+      water_depth: vec![i32::MIN; len]
+    }
+  }
+
+  /// Draws the top face of a block over the upper half of its area, and its two side faces over the lower half
+  fn paint(&mut self, projection: &Projection, draw: Draw) {
+    let scale = projection.scale;
+    let corner = projection.project(draw.pos);
+    // Nearer blocks have a greater depth
+    let depth = draw.pos.x + draw.pos.y + draw.pos.z;
+    for dy in 0..scale * 2 {
+      for dx in 0..scale * 2 {
+        let pixel = corner + IVec2::new(dx, dy);
+        if pixel.cmplt(IVec2::ZERO).any() || pixel.cmpge(self.size).any() { continue };
+        let i = (pixel.y * self.size.x + pixel.x) as usize;
+        if draw.water {
+          self.water_depth[i] = self.water_depth[i].max(depth);
+        } else if depth >= self.depth[i] {
+          let face = if dy < scale { 0 } else if dx < scale { 1 } else { 2 };
+          self.depth[i] = depth;
+          self.color[i] = draw.color.map(|channel| (channel as f64 * FACE_SHADES[face]) as u8);
+        };
+      };
+    };
+  }
+
+  /// Flattens the image, blending the water's surface over whatever is beneath it
+  fn to_rgb(&self) -> Vec<u8> {
+    self.color.iter().zip(self.depth.iter().zip(self.water_depth.iter()))
+      .flat_map(|(&color, (&depth, &water_depth))| match water_depth > depth {
+        true => [0, 1, 2].map(|c| ((color[c] as u16 + WATER_COLOR[c] as u16) / 2) as u8),
+        false => color
+      })
+      .collect()
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use glam::Vec3Swizzles;
+
+  use super::*;
+  use crate::{GenerationHooks, GeneratorOptions};
+
+  #[test]
+  fn rendering_part_of_a_city_is_deterministic_and_not_uniform() {
+    let options = GeneratorOptions { layer_count: 1, no_cache: true, ..GeneratorOptions::default() };
+    let generator = Generator::new(0, &options, GenerationHooks::default());
+    // The three by three chunks around the middle of the city
+    let city = generator.city_bounding_box;
+    let center = ChunkPos::containing((city.min.xy() + city.max.xy()) / 2);
+    let chunks = (-1..=1)
+      .flat_map(|y| (-1..=1).map(move |x| ChunkPos(center.0 + IVec2::new(x, y))))
+      .collect::<Vec<ChunkPos>>();
+
+    let (size, rgb) = render_chunks(&generator, &chunks, 1);
+    assert_eq!(rgb.len(), (size.x * size.y * 3) as usize);
+    assert_eq!(render_chunks(&generator, &chunks, 1), (size, rgb.clone()), "the render changed between runs");
+
+    let pixels = rgb.chunks_exact(3).map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect::<Vec<[u8; 3]>>();
+    let drawn = pixels.iter().filter(|&&pixel| pixel != BACKGROUND).count();
+    assert!(drawn > 16 * 16 * 9, "only {} pixels were drawn", drawn);
+    let colors = pixels.iter().copied().collect::<HashSet<[u8; 3]>>();
+    assert!(colors.len() > 16, "only {} colors were drawn", colors.len());
+
+    // A larger scale covers each block with more pixels
+    let (larger_size, _) = render_chunks(&generator, &chunks, 2);
+    assert!(larger_size.cmpgt(size).all(), "{} at scale 2, {} at scale 1", larger_size, size);
+  }
+
+  #[test]
+  fn nearer_blocks_are_drawn_over_farther_ones() {
+    let bounding_box = BoundingBox::new(IVec3::ZERO, IVec3::splat(3));
+    let projection = Projection::new(bounding_box, 1);
+    let mut framebuffer = Framebuffer::new(projection.size);
+    let (near, far) = ([200, 0, 0], [0, 200, 0]);
+    // The block at 1,1,1 hides the top of the block beneath it and behind it on either side
+    framebuffer.paint(&projection, Draw { pos: IVec3::new(1, 1, 1), color: near, water: false });
+    framebuffer.paint(&projection, Draw { pos: IVec3::new(0, 0, 0), color: far, water: false });
+    let corner = projection.project(IVec3::new(1, 1, 1));
+    let i = (corner.y * projection.size.x + corner.x) as usize;
+    assert_eq!(framebuffer.color[i], near);
+
+    // Water is blended over whatever is beneath it
+    framebuffer.paint(&projection, Draw { pos: IVec3::new(1, 1, 2), color: WATER_COLOR, water: true });
+    let corner = projection.project(IVec3::new(1, 1, 2));
+    let i = (corner.y * projection.size.x + corner.x) as usize;
+    let expected = [0, 1, 2].map(|c| ((framebuffer.color[i][c] as u16 + WATER_COLOR[c] as u16) / 2) as u8);
+    assert_eq!(framebuffer.to_rgb()[i * 3..i * 3 + 3], expected);
+  }
+}
@@ -0,0 +1,216 @@
+//! Keeps files that were added to a world by hand, like datapacks and resource packs, when it is rendered again.
+//!
+//! Paths matching the keep patterns are moved out of the world directory before it is cleared, and moved back once the
+//! template has been written. Kept directories are merged into those of the template, so kept datapacks sit alongside
+//! the generator's own. Where a kept file and a file of the template collide, the template's level.dat, icon and level
+//! name win, and the kept file wins everywhere else.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::datapack::WORLD_DATAPACK_PREFIX;
+
+
+
+/// The pattern kept by `--keep-datapacks`
+const DATAPACKS_PATTERN: &str = "datapacks/*";
+/// The files written by the template which always replace kept copies of themselves
+const TEMPLATE_FILES: [&str; 3] = ["level.dat", "icon.png", "levelname.txt"];
+
+/// A path within a world directory to keep, where each component may contain `*` to match any run of characters and
+/// `?` to match any single character. Matching directories are kept whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepPattern {
+  components: Vec<String>
+}
+
+impl KeepPattern {
+  /// Every datapack added to the world, apart from the generator's own
+  pub fn datapacks() -> Self {
+    DATAPACKS_PATTERN.parse().unwrap()
+  }
+
+  fn matches(&self, path: &Path) -> bool {
+    let components = path.iter().map(|component| component.to_string_lossy()).collect::<Vec<_>>();
+    components.len() == self.components.len() &&
+    self.components.iter().zip(components.iter()).all(|(pattern, component)| glob_matches(pattern, component))
+  }
+
+  /// Whether this pattern could match something inside of the given directory, which it doesn't match itself
+  fn reaches_into(&self, path: &Path) -> bool {
+    let depth = path.iter().count();
+    depth < self.components.len() &&
+    self.components.iter().zip(path.iter())
+      .all(|(pattern, component)| glob_matches(pattern, &component.to_string_lossy()))
+  }
+}
+
+impl FromStr for KeepPattern {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    let components = s.replace('\\', "/").split('/')
+      .filter(|component| !component.is_empty() && *component != ".")
+      .map(str::to_owned)
+      .collect::<Vec<String>>();
+    if components.iter().any(|component| component == "..") {
+      return Err("expected a path within the world directory".to_owned());
+    };
+
+    match components.as_slice() {
+      [] => Err("expected a path within the world directory, not the directory itself".to_owned()),
+      // This would keep every file in the world, leaving nothing for the template to replace
+      [component] if component.chars().all(|c| c == '*') => Err("matches the whole world directory".to_owned()),
+      _ => Ok(KeepPattern { components })
+    }
+  }
+}
+
+impl fmt::Display for KeepPattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.components.join("/"))
+  }
+}
+
+/// Files moved out of a world directory while it is cleared, waiting to be moved back in
+#[derive(Debug)]
+pub struct KeptFiles {
+  stash: PathBuf,
+  /// The kept paths, relative to both the world directory and the stash
+  paths: Vec<PathBuf>
+}
+
+impl KeptFiles {
+  /// Moves everything in a world directory matching any of the given patterns into a directory beside it.
+  /// Patterns which match nothing are logged. Copies of the generator's own datapack are never kept,
+  /// since a new one is written with the template.
+  pub fn stash(world: &Path, patterns: &[KeepPattern]) -> io::Result<Option<Self>> {
+    if patterns.is_empty() { return Ok(None) };
+    let mut paths = Vec::new();
+    if world.is_dir() {
+      collect_matches(world, Path::new(""), patterns, &mut paths)?;
+    };
+
+    for pattern in patterns {
+      if !paths.iter().any(|path| pattern.matches(path)) {
+        log::warn!("`--keep {}` matches nothing in {}", pattern, world.display());
+      };
+    };
+
+    paths.retain(|path| !is_world_datapack(path));
+    if paths.is_empty() { return Ok(None) };
+
+    let mut name = world.file_name().unwrap_or_default().to_owned();
+    name.push(".keep");
+    let stash = world.with_file_name(name);
+    if stash.exists() {
+      let message = format!("{} was left behind by an earlier run, move its files back or remove it", stash.display());
+      return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+    };
+
+    for path in paths.iter() {
+      let to = stash.join(path);
+      fs::create_dir_all(to.parent().unwrap())?;
+      fs::rename(world.join(path), to)?;
+    };
+
+    log::info!("keeping {} path(s) from {}", paths.len(), world.display());
+    Ok(Some(KeptFiles { stash, paths }))
+  }
+
+  /// Moves the kept files back into a world directory, once its template has been written
+  pub fn restore(self, world: &Path) -> io::Result<()> {
+    for path in self.paths.iter() {
+      restore(&self.stash.join(path), world, path)?;
+    };
+
+    fs::remove_dir_all(&self.stash)
+  }
+}
+
+/// Finds every path beneath `world.join(dir)` matching any of the patterns, without descending into those that match
+fn collect_matches(world: &Path, dir: &Path, patterns: &[KeepPattern], out: &mut Vec<PathBuf>) -> io::Result<()> {
+  let mut entries = fs::read_dir(world.join(dir))?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+  entries.sort_by_key(fs::DirEntry::file_name);
+  for entry in entries {
+    let path = dir.join(entry.file_name());
+    if patterns.iter().any(|pattern| pattern.matches(&path)) {
+      out.push(path);
+    } else if entry.file_type()?.is_dir() && patterns.iter().any(|pattern| pattern.reaches_into(&path)) {
+      collect_matches(world, &path, patterns, out)?;
+    };
+  };
+
+  Ok(())
+}
+
+/// Moves a kept path back to the same place in the world, merging it into a directory that the template wrote there
+fn restore(from: &Path, world: &Path, path: &Path) -> io::Result<()> {
+  let to = world.join(path);
+  let existing = match fs::symlink_metadata(&to) {
+    Ok(metadata) => metadata,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+      fs::create_dir_all(to.parent().unwrap())?;
+      return fs::rename(from, &to);
+    },
+    Err(err) => return Err(err)
+  };
+
+  if TEMPLATE_FILES.iter().any(|file| path == Path::new(file)) {
+    log::info!("the template's {} replaces the kept copy", path.display());
+    Ok(())
+  } else if existing.is_dir() && from.is_dir() {
+    for entry in fs::read_dir(from)? {
+      let entry = entry?;
+      restore(&entry.path(), world, &path.join(entry.file_name()))?;
+    };
+
+    Ok(())
+  } else {
+    log::info!("the kept {} replaces the template's", path.display());
+    match existing.is_dir() {
+      true => fs::remove_dir_all(&to)?,
+      false => fs::remove_file(&to)?
+    };
+
+    fs::rename(from, &to)
+  }
+}
+
+/// Whether a path is a copy of the generator's own datapack, under its current name or an older one
+fn is_world_datapack(path: &Path) -> bool {
+  path.parent() == Some(Path::new("datapacks")) &&
+  path.file_name().map_or(false, |name| name.to_string_lossy().starts_with(WORLD_DATAPACK_PREFIX))
+}
+
+/// Matches a single path component against a pattern, where `*` matches any run of characters and `?` any one
+fn glob_matches(pattern: &str, text: &str) -> bool {
+  let (pattern, text) = (pattern.chars().collect::<Vec<char>>(), text.chars().collect::<Vec<char>>());
+  // The position in the pattern just past the last `*` seen, and the position in the text it was matched from
+  let (mut p, mut t, mut backtrack) = (0, 0, None);
+  while t < text.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        backtrack = Some((p + 1, t));
+        p += 1;
+      },
+      Some(&c) if c == '?' || c == text[t] => {
+        p += 1;
+        t += 1;
+      },
+      _ => match backtrack {
+        // Lets the last `*` swallow one more character and tries again from there
+        Some((star_p, star_t)) => {
+          backtrack = Some((star_p, star_t + 1));
+          p = star_p;
+          t = star_t + 1;
+        },
+        None => return false
+      }
+    };
+  };
+
+  pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,2278 @@
+extern crate glam;
+extern crate log;
+extern crate noise;
+extern crate pyo3;
+extern crate rand;
+extern crate rand_xoshiro;
+
+#[macro_use]
+mod utility;
+mod block_stats;
+mod builder;
+mod chunk_pipeline;
+mod chunk_sections;
+mod chunk_stats;
+mod cli;
+mod datapack;
+mod estimate;
+#[cfg(feature = "explore")]
+mod explore;
+mod far_field;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod generation;
+mod goldens;
+mod heightmap;
+mod isometric;
+mod keep;
+mod logger;
+mod output_path;
+mod png;
+pub mod prelude;
+mod render_workers;
+#[cfg(feature = "serve")]
+mod serve;
+mod split_output;
+mod world_coords;
+mod world_format;
+mod world_info;
+
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fs, io};
+
+use glam::{IVec2, IVec3, Vec3Swizzles};
+use log::LevelFilter;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::block_stats::{BlockStats, StatsSampling};
+use crate::builder::GenerationHooks;
+use crate::chunk_pipeline::{chunk_windows, pipeline_chunks, PipelineProgress, SampledChunk, DEFAULT_LOOKAHEAD};
+use crate::chunk_sections::{ChunkSections, SECTION_VOLUME};
+use crate::chunk_stats::{ChunkLimits, ChunkStats, ChunkStatsRecorder};
+use crate::cli::{
+  Command, ExportArgs, GenerateArgs, GenerationArgs, GoldensArgs, InspectArgs, PreviewArgs, ValidateArgs
+};
+use crate::datapack::{build_world_datapack, WORLD_DATAPACK_FILE_NAME};
+use crate::estimate::{GenerationEstimate, DEFAULT_CONFIRM_ABOVE, TIMED_CHUNK_COUNT, TYPICAL_CHUNK_BYTES};
+use crate::far_field::{FarFieldChunk, FAR_FIELD_PALETTE};
+use crate::generation::bedrock::Bedrock;
+use crate::generation::block_entity::BlockEntity;
+use crate::generation::bubble_lift::BubbleLifts;
+use crate::generation::checked::{validate_consistency, CheckedGeometry};
+use crate::generation::chunk_context::{ChunkContext, FeaturePriority};
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::deferred::Deferred;
+use crate::generation::enclosure::Drained;
+use crate::generation::fade_bounds::{EdgeStyle, FadeBounds};
+use crate::generation::feature_tag::FeatureTag;
+use crate::generation::city::layer_cache::LayerCache;
+use crate::generation::city::{
+  City, CityPlacement, CollisionPolicy, ErosionConfig, Foundation, LandmassParams, Lighting, PavingConfig,
+  ShapeConfig, SlabFillChoice, Symmetry
+};
+use crate::generation::limit_bounds::LimitBounds;
+use crate::generation::noise_util::to_cache_table_string;
+use crate::generation::ocean::Ocean;
+use crate::generation::outcrops::Outcrops;
+use crate::generation::profiled::{Profiled, ProfileCounters, ProfileTable};
+use crate::generation::theme::Theme;
+use crate::generation::transform::{rotate_block, Rotate90, Translate};
+use crate::generation::union::Union;
+use crate::generation::{blocks, Block, BoundingBox, ColumnTop, Geometry, MaterialGeometry};
+use crate::heightmap::Heightmap;
+use crate::isometric::render_isometric;
+use crate::keep::{KeepPattern, KeptFiles};
+use crate::logger::LogFilter;
+use crate::output_path::OutputPath;
+use crate::render_workers::{EncodedChunk, RenderWorkers};
+#[cfg(feature = "serve")]
+use crate::serve::PreviewServer;
+use crate::split_output::{is_part_name, manifest_json, OutputPart, MANIFEST_FILE_NAME};
+use crate::utility::*;
+use crate::world_coords::{chunk_to_minecraft, debug_assert_in_chunk, debug_assert_section_in_world, to_minecraft};
+use crate::world_format::{bedrock_level_dat, fits_bedrock_height, WorldFormat, BEDROCK_MAX_Z, BEDROCK_MIN_Z};
+use crate::world_info::WorldInfo;
+
+const WORLD_MIN_Z: i32 = -64;
+const WORLD_MAX_Z: i32 = WORLD_MIN_Z + 64 + 512;
+/// The lowest and highest z values that Minecraft lets the blocks of a world reach, whatever height it is given
+const MINECRAFT_MIN_Z: i32 = -2032;
+const MINECRAFT_MAX_Z: i32 = 2031;
+/// The Java edition version whose block states the generator uses, which Amulet translates from for other formats
+const JAVA_VERSION: (u32, u32, u32) = (1, 20, 1);
+/// How far past the city's bounding box a chunk must be to count as far-field,
+/// in case a feature of the city reaches slightly beyond the bounding box it reports
+const FAR_FIELD_MARGIN: i32 = 16;
+
+/// The range of z values that the world spans, from the bottom of the bedrock up to the build limit,
+/// which is one block above the highest block that can be placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldHeight {
+  pub min_z: i32,
+  pub max_z: i32
+}
+
+impl WorldHeight {
+  /// The number of blocks in each column of the world
+  pub fn height(self) -> i32 {
+    self.max_z - self.min_z
+  }
+}
+
+impl Default for WorldHeight {
+  fn default() -> Self {
+    WorldHeight { min_z: WORLD_MIN_Z, max_z: WORLD_MAX_Z }
+  }
+}
+
+/// Written as `min,max`, as given to `--world-height`
+impl fmt::Display for WorldHeight {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{},{}", self.min_z, self.max_z)
+  }
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct GeneratorOptions {
+  /// The number of layers in the city
+  pub layer_count: usize,
+  /// The vertical distance between the upper slabs of consecutive layers
+  pub layer_spacing: i32,
+  /// The range of z values that the world spans, which the bedrock sits at the bottom of
+  pub world_height: WorldHeight,
+  /// Mirrors the outline of every landmass across one or both of its axes, if not `Symmetry::None`
+  pub symmetry: Symmetry,
+  /// Generates a flat, 1-block thick bedrock floor instead of a noisy one
+  pub flat_bedrock: bool,
+  /// Disables loading and storing generated layers in the on-disk cache
+  pub no_cache: bool,
+  /// Clears the on-disk layer cache before generating
+  pub clear_cache: bool,
+  /// Records how much time is spent sampling each top-level feature, printing a table at the end
+  pub profile_geometry: bool,
+  /// How the lowest layer of the city meets the ocean
+  pub foundation: Foundation,
+  /// The thickness of each landmass, the radius of the pillars holding them up and the heights of their buildings
+  pub landmass: LandmassParams,
+  /// Stretches the landmasses into ellipses and gives them lobes, differently for each layer, if present
+  pub shapes: Option<ShapeConfig>,
+  /// Wears away the outlines of the landmasses, if present
+  pub erosion: Option<ErosionConfig>,
+  /// How the interiors of the landmasses are filled in, the same for every landmass or drawn for each
+  pub slab_fill: SlabFillChoice,
+  /// Carves caves out of the interior of the lowest landmass
+  pub caves: bool,
+  /// Groups some of the pillars beneath the largest landmasses into clusters of two or three, braced against each other
+  pub pillar_clusters: bool,
+  /// The distance between the edge of the lowest landmass and a wall running around it, if present
+  pub wall_distance: Option<u32>,
+  /// Darkens the surfaces of lower layers where they lie beneath higher layers
+  pub baked_shadows: bool,
+  /// Decorates the rims of landmasses and the roofs of buildings with slabs and stairs
+  pub details: bool,
+  /// Scatters heaps of rubble, barrels and lamps over the surfaces of the landmasses
+  pub scatter: bool,
+  /// Paves the surfaces of the landmasses with a mix of blocks, rather than all in their own material
+  pub paving: Option<PavingConfig>,
+  /// Streaks the walls of buildings beneath their windows and stains the tops of pillars, on by default
+  pub weathering: bool,
+  /// Shades the concrete of each layer lighter the higher up it is, on by default
+  pub altitude_gradient: bool,
+  /// Which parts of the city have light sources placed in them, keeping hostile mobs from spawning there
+  pub lighting: Lighting,
+  /// What happens to the buildings, props and pools of each layer that the pillars of the layer above pass through
+  pub collisions: CollisionPolicy,
+  /// Lets the surface of the ocean dip down by a block in places
+  pub wavy_ocean: bool,
+  /// Deepens the sea floor away from the city, down to about z=-55 at the edge of the world
+  pub deep_ocean: bool,
+  /// Ramps the sea floor up into a sandy beach at the edge of the world, instead of ending the ocean in a wall of water
+  pub edge_beach: bool,
+  /// Leaves the sea floor bare in the shadows of the landmasses and compacts it around the footings of the pillars
+  pub seabed_response: bool,
+  /// Bands the deepslate beneath the sea floor with tuff and basalt
+  pub strata: bool,
+  /// Stands bubble column lifts on the sea floor beside the pillars of the lowest layer, one up and one down each
+  pub bubble_lifts: bool,
+  /// The distance from the city of the middle of a trench running around it, if present
+  pub trench_distance: Option<u32>,
+  /// Keeps the city centered on 0,0 and aligned as generated, instead of moving and rotating it at random
+  pub center: bool,
+  /// How the city is cut off at the edge of the world
+  pub edge_style: EdgeStyle,
+  /// The blocks swapped in for the generator's own materials, which by default leaves them all as they are
+  pub theme: Theme
+}
+
+impl Default for GeneratorOptions {
+  fn default() -> Self {
+    GeneratorOptions {
+      layer_count: 3,
+      layer_spacing: 48,
+      world_height: WorldHeight::default(),
+      symmetry: Symmetry::None,
+      flat_bedrock: false,
+      no_cache: false,
+      clear_cache: false,
+      profile_geometry: false,
+      foundation: Foundation::default(),
+      landmass: LandmassParams::default(),
+      shapes: None,
+      erosion: None,
+      slab_fill: SlabFillChoice::default(),
+      caves: false,
+      pillar_clusters: false,
+      wall_distance: None,
+      baked_shadows: false,
+      details: false,
+      scatter: false,
+      paving: None,
+      weathering: true,
+      altitude_gradient: true,
+      lighting: Lighting::default(),
+      collisions: CollisionPolicy::default(),
+      wavy_ocean: false,
+      deep_ocean: false,
+      edge_beach: false,
+      seabed_response: false,
+      strata: false,
+      bubble_lifts: false,
+      trench_distance: None,
+      center: false,
+      edge_style: EdgeStyle::default(),
+      theme: Theme::default()
+    }
+  }
+}
+
+impl GeneratorOptions {
+  fn layer_cache(&self) -> Option<LayerCache> {
+    let cache = LayerCache::new_default()?;
+    if self.clear_cache {
+      if let Err(err) = cache.clear() {
+        log::warn!("failed to clear the layer cache: {}", err);
+      };
+    };
+
+    (!self.no_cache).then(|| cache)
+  }
+
+  /// Where the city is placed in the world for the given seed. This is drawn from a separate rng,
+  /// so that the rest of the world is the same whether or not the city is centered.
+  fn city_placement(&self, seed: u64) -> CityPlacement {
+    match self.center {
+      true => CityPlacement::default(),
+      false => CityPlacement::random(&mut Xoshiro256PlusPlus::seed_from_u64(mix64(seed ^ 0x706c6163)))
+    }
+  }
+}
+
+/// The city, moved into place in the world and cut off at its edge
+type PlacedCity = FadeBounds<Translate<Rotate90<Arc<City>>>>;
+/// Every top-level feature of the world, in order of priority
+type WorldFeatures = Union<(Bedrock, PlacedCity, Outcrops, Deferred<BubbleLifts>, Drained<Ocean>)>;
+
+#[derive(Debug, Clone)]
+pub struct Generator {
+  inner: LimitBounds<CheckedGeometry<WorldFeatures>>,
+  bounding_box: BoundingBox,
+  /// The bounding box of the city alone, in place in the world
+  city_bounding_box: BoundingBox,
+  /// The range of z values that the world spans, which its bounding box never reaches beyond
+  world_height: WorldHeight,
+  theme: Theme,
+  /// `FAR_FIELD_PALETTE` with the theme applied to it
+  far_field_palette: Vec<Block>,
+  profile: Option<GeneratorProfile>,
+  /// The world as returned by `GenerationHooks::wrap_final`, which is sampled in place of `inner`, if it was wrapped.
+  /// `inner` is still used to attribute blocks to features and to find where features are.
+  wrapped: Option<WrappedWorld>
+}
+
+impl Generator {
+  fn new(seed: u64, options: &GeneratorOptions, hooks: GenerationHooks) -> Generator {
+    let mut source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let bedrock = Bedrock::new(&mut source_rng, options.flat_bedrock, options.world_height.min_z);
+    let ocean_floor = Ocean::new(&mut source_rng, bedrock.clone(), options.wavy_ocean);
+
+    let placement = options.city_placement(seed);
+    let source_rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut city = City::generate_new(source_rng, options, options.layer_cache(), &ocean_floor, placement);
+    if let Some(after_layers) = hooks.after_layers {
+      city.edit_layers(after_layers);
+    };
+    let city = match hooks.after_city {
+      Some(after_city) => after_city(city),
+      None => city
+    };
+    // Nothing is generated here: the world's bounds come from the city's estimated bounding box, and everything
+    // derived from the generated layers is deferred until something near the city is sampled
+    let city = Arc::new(city);
+    let footprint = options.seabed_response.then(|| city.footprint());
+    let city = Translate::new(Rotate90::new(city, placement.quarter_turns), placement.offset.extend(0));
+    let city_bounds = city.bounding_box().inflated(IVec3::new(128, 128, 0));
+    let dry_volumes = {
+      let city = city.clone();
+      Deferred::new(city.bounding_box(), move || {
+        let rotated: &Rotate90<Arc<City>> = &city;
+        city.dry_volumes().into_iter()
+          .map(|volume| city.to_world_box(rotated.to_world_box(volume)))
+          .collect::<Vec<BoundingBox>>()
+      })
+    };
+    // The bedrock and ocean extend forever, so only the city is faded
+    let fade_seed = mix64(seed ^ 0x66616465);
+    let city = FadeBounds::new(city, city_bounds.min.xy(), city_bounds.max.xy(), options.edge_style, fade_seed);
+    let city_bounding_box = city.bounding_box();
+    // The city was generated over the sea floor as it was, which the deepening leaves unchanged beneath the city
+    let ocean_floor = match options.deep_ocean || options.trench_distance.is_some() {
+      true => ocean_floor.deepened(city_bounding_box, city_bounds, options.deep_ocean, options.trench_distance),
+      false => ocean_floor
+    };
+    // The beach meets the edge of the world, where `LimitBounds` cuts everything off
+    let ocean_floor = match options.edge_beach {
+      true => ocean_floor.with_beach(city_bounds.min.xy(), city_bounds.max.xy()),
+      false => ocean_floor
+    };
+    let ocean_floor = match options.strata {
+      true => ocean_floor.with_strata(),
+      false => ocean_floor
+    };
+    let ocean_floor = match footprint {
+      Some(footprint) => ocean_floor.with_city_footprint(footprint),
+      None => ocean_floor
+    };
+    // Seeded separately for the same reason as the placement
+    let outcrops = Outcrops::new(mix64(seed ^ 0x6f757463), city_bounding_box, ocean_floor.clone());
+    // Stood on the sea floor as it ends up, including any mounds raised around the pillars
+    let bubble_lifts = match options.bubble_lifts {
+      true => {
+        let rotated: &Rotate90<Arc<City>> = &city;
+        let pillars = city.to_world_box(rotated.to_world_box(rotated.estimated_pillar_bounding_box()));
+        let (city, ocean_floor) = ((**city).clone(), ocean_floor.clone());
+        Deferred::new(BubbleLifts::bounding_box_around(pillars), move || {
+          let rotated: &Rotate90<Arc<City>> = &city;
+          let is_pillar_column = |xy: IVec2| {
+            rotated.is_pillar_column(rotated.to_local(city.to_local(xy.extend(0))).xy())
+          };
+          BubbleLifts::beside_pillars(&rotated.pillar_footings(), &ocean_floor, is_pillar_column)
+        })
+      },
+      false => Deferred::new(BubbleLifts::none().bounding_box(), BubbleLifts::none)
+    };
+    // The spaces enclosed by the city are kept clear of the water that would otherwise fill them
+    let ocean_floor = Drained::new(ocean_floor, dry_volumes);
+
+    let profile = options.profile_geometry.then(|| GeneratorProfile::new(city.layer_count()));
+    let inner = CheckedGeometry::new(Union::new((bedrock, city, outcrops, bubble_lifts, ocean_floor)));
+    // The build limit is one block above the highest block that can be placed
+    let inner = LimitBounds::new(inner, city_bounds.min.xy(), city_bounds.max.xy())
+      .with_height(options.world_height.min_z, options.world_height.max_z - 1);
+    let bounding_box = inner.bounding_box();
+    let wrapped = hooks.wrap_final.map(|wrap_final| {
+      WrappedWorld { bounding_box, inner: Arc::from(wrap_final(Box::new(inner.clone()))) }
+    });
+    let theme = options.theme.clone();
+    let far_field_palette = FAR_FIELD_PALETTE.iter()
+      .map(|block| theme.apply(Cow::Borrowed(block)).into_owned())
+      .collect();
+    let world_height = options.world_height;
+    Generator { inner, bounding_box, city_bounding_box, world_height, theme, far_field_palette, profile, wrapped }
+  }
+
+  /// The geometry that the world's blocks are sampled from, which is the wrapped world if there is one
+  fn world(&self) -> &dyn MaterialGeometry {
+    match &self.wrapped {
+      Some(wrapped) => wrapped,
+      None => &self.inner
+    }
+  }
+
+  pub fn chunk_exists(&self, pos: ChunkPos) -> bool {
+    self.bounding_box.intersects_chunk(pos)
+  }
+
+  /// Iterates through every chunk which intersects this generator's bounding box,
+  /// in rings expanding out from 0,0, without collecting them up front
+  pub fn chunks(&self) -> impl ExactSizeIterator<Item = ChunkPos> + '_ {
+    let (min, max) = self.bounding_box.to_chunks();
+    ChunkPos::rings_in_range(min, max)
+  }
+
+  /// Every block that this generator can place, with its theme applied, along with air,
+  /// found by walking its features instead of sampling them. Far-field chunks are always sent with the whole far-field
+  /// palette, so that is included too, even where the features which would place some of its blocks are turned off.
+  /// This generates every layer of the city, if it hasn't been already.
+  pub fn block_census(&self) -> BTreeSet<Block> {
+    let mut census = BTreeSet::from([blocks::AIR]);
+    self.world().possible_blocks(&mut census);
+    census.into_iter()
+      .map(|block| self.theme.apply(Cow::Owned(block)).into_owned())
+      .chain(self.far_field_palette.iter().cloned())
+      .collect()
+  }
+
+  /// The number of chunks yielded by `Generator::chunks`
+  pub fn chunk_count(&self) -> usize {
+    self.chunks().len()
+  }
+
+  /// The blocks that the indices of a `FarFieldChunk` refer to, which are those of `FAR_FIELD_PALETTE` with the theme
+  /// applied to them
+  pub fn far_field_palette(&self) -> &[Block] {
+    &self.far_field_palette
+  }
+
+  /// Whether a chunk is far enough from the city that only the bedrock, ocean and outcrops reach it,
+  /// in which case it can be rendered from a `FarFieldChunk` rather than block by block.
+  /// A wrapped world could place anything anywhere, so none of its chunks are.
+  pub fn is_far_field_chunk(&self, chunk_pos: ChunkPos) -> bool {
+    let margin = IVec3::new(FAR_FIELD_MARGIN, FAR_FIELD_MARGIN, 0);
+    self.wrapped.is_none() && !self.city_bounding_box.inflated(margin).intersects_chunk(chunk_pos)
+  }
+
+  /// The highest z value that the bedrock, ocean or any outcrops reach in a chunk,
+  /// above which a far-field chunk is empty
+  pub fn far_field_max_z(&self, chunk_pos: ChunkPos) -> i32 {
+    let (bedrock, _, outcrops, _, ocean) = &***self.inner;
+    let max_z = bedrock.bounding_box().join(ocean.bounding_box()).max.z;
+    let outcrops_max_z = outcrops.max_z_in(chunk_pos.min_block(), chunk_pos.max_block()).unwrap_or(max_z);
+    max_z.max(outcrops_max_z).min(self.bounding_box.max.z)
+  }
+
+  /// The block at a position, owned so that it can outlive the generator
+  pub fn block_at(&self, pos: IVec3) -> Option<Block> {
+    self.world().block_material_at(pos).map(|block| self.theme.apply(block).into_owned())
+  }
+
+  /// The block at a position along with the kind of feature that placed it. The block is sampled as usual,
+  /// and then attributed to whichever top-level feature has a block there first, so this is slower than `block_at`.
+  pub fn block_material_at_traced(&self, pos: IVec3) -> Option<(Block, FeatureTag)> {
+    let block = self.block_at(pos)?;
+    let (bedrock, city, outcrops, bubble_lifts, _) = &***self.inner;
+    let rotated: &Rotate90<Arc<City>> = city;
+    let city_tag = || {
+      let local = rotated.to_local(city.to_local(pos));
+      city.keeps(pos).then(|| rotated.block_material_at_traced(local)).flatten().map(|(_, tag)| tag)
+    };
+
+    let tag = if bedrock.block_at(pos) {
+      FeatureTag::Bedrock
+    } else if let Some(tag) = city_tag() {
+      tag
+    } else if outcrops.block_at(pos) {
+      FeatureTag::Outcrop
+    } else if bubble_lifts.block_at(pos) {
+      FeatureTag::BubbleLift
+    } else {
+      FeatureTag::Ocean
+    };
+
+    Some((block, tag))
+  }
+
+  /// Counts the blocks placed by each kind of feature at the positions picked by the given sampling.
+  /// Random positions are drawn from an rng seeded by the given seed, so the same world is always sampled the same way.
+  pub fn block_stats(&self, sampling: StatsSampling, seed: u64) -> BlockStats {
+    const RANDOM_BATCH_SIZE: usize = 4096;
+    let (min, max) = (self.bounding_box.min, self.bounding_box.max);
+    match sampling {
+      StatsSampling::EveryNthChunk(n) => {
+        self.chunks().step_by(n).collect::<Vec<ChunkPos>>().into_par_iter()
+          .map(|chunk_pos| {
+            let mut stats = BlockStats::default();
+            for block_pos in iter_chunk_blocks(min.z, max.z) {
+              stats.record(self.block_material_at_traced(block_pos + chunk_pos.min_block().extend(0)));
+            };
+
+            stats
+          })
+          .reduce(BlockStats::default, BlockStats::merge)
+      },
+      StatsSampling::Random(count) => {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(mix64(seed ^ 0x7374617473));
+        let positions = (0..count)
+          .map(|_| IVec3::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y), rng.gen_range(min.z..=max.z)))
+          .collect::<Vec<IVec3>>();
+        positions.par_chunks(RANDOM_BATCH_SIZE)
+          .map(|positions| {
+            let mut stats = BlockStats::default();
+            for &pos in positions {
+              stats.record(self.block_material_at_traced(pos));
+            };
+
+            stats
+          })
+          .reduce(BlockStats::default, BlockStats::merge)
+      }
+    }
+  }
+
+  /// Positions of representative blocks of each kind of feature in the world, labelled with the feature they belong to,
+  /// for sampling with `Generator::block_at`. New features only need to be added here to be covered by the goldens.
+  pub fn feature_probes(&self) -> Vec<(String, IVec3)> {
+    let (bedrock, city, outcrops, bubble_lifts, ocean) = &***self.inner;
+    let rotated: &Rotate90<Arc<City>> = city;
+    // The corner of the world is always open ocean, well away from the city
+    let corner = self.bounding_box.min.xy() + 8;
+    let mut probes = vec![
+      ("bedrock surface".to_owned(), corner.extend(bedrock.height_at(corner))),
+      ("ocean floor".to_owned(), corner.extend(ocean.floor_height_at(corner) - 1)),
+      ("ocean water".to_owned(), corner.extend(-8)),
+      ("ocean surface".to_owned(), corner.extend(0)),
+      ("above ocean".to_owned(), corner.extend(10))
+    ];
+
+    let seagrass = (0..256)
+      .map(|i| corner + IVec2::new(i % 16, i / 16))
+      .map(|column| column.extend(ocean.floor_height_at(column)))
+      .find(|&pos| self.block_at(pos).map_or(false, |block| block.base_name().ends_with("seagrass")));
+    probes.extend(seagrass.map(|pos| ("seagrass".to_owned(), pos)));
+    let outcrop = outcrops.peaks_in(self.bounding_box.min.xy(), self.bounding_box.max.xy())
+      .find(|&pos| self.bounding_box.contains(pos));
+    probes.extend(outcrop.map(|pos| ("outcrop".to_owned(), pos)));
+    let bubble_lift = bubble_lifts.get().feet().find(|&pos| self.bounding_box.contains(pos));
+    probes.extend(bubble_lift.map(|pos| ("bubble lift".to_owned(), pos)));
+    let city_center = (self.city_bounding_box.min.xy() + self.city_bounding_box.max.xy()) / 2;
+    probes.push(("sky".to_owned(), city_center.extend(self.bounding_box.max.z)));
+
+    probes.extend({
+      rotated.feature_probes().into_iter()
+        .map(|(label, pos)| (label, city.to_world(rotated.to_world(pos))))
+    });
+    probes
+  }
+
+  /// The bounding box of a single chunk of this generator
+  fn chunk_bounding_box(&self, chunk_pos: ChunkPos) -> BoundingBox {
+    let min = chunk_pos.min_block().extend(self.bounding_box.min.z);
+    let max = chunk_pos.max_block().extend(self.bounding_box.max.z);
+    BoundingBox::try_new(min, max).unwrap()
+  }
+
+  /// The block entities within a chunk, such as chests and signs.
+  /// Some of these may be covered up by other features, so check that their blocks are actually present.
+  pub fn block_entities_in_chunk(&self, chunk_pos: ChunkPos) -> Vec<BlockEntity> {
+    let (_, city, _, _, _) = &***self.inner;
+    let rotated: &Rotate90<Arc<City>> = city;
+    city.block_entities_in(self.city_local_box(self.chunk_bounding_box(chunk_pos))).into_iter()
+      .map(|mut block_entity| {
+        block_entity.pos = city.to_world(rotated.to_world(block_entity.pos));
+        block_entity.block = rotate_block(Cow::Owned(block_entity.block), rotated.quarter_turns()).into_owned();
+        block_entity
+      })
+      .collect()
+  }
+
+  /// Converts a bounding box in the world into the city's own coordinates
+  fn city_local_box(&self, bounding_box: BoundingBox) -> BoundingBox {
+    let (_, city, _, _, _) = &***self.inner;
+    let rotated: &Rotate90<Arc<City>> = city;
+    rotated.to_local_box(city.to_local_box(bounding_box))
+  }
+
+  /// Moves a geometry from the city's own coordinates into place in the world alongside the city,
+  /// cutting it off at the same edge
+  fn place_with_city<G>(&self, geometry: G) -> FadeBounds<Translate<Rotate90<G>>> {
+    let (_, city, _, _, _) = &***self.inner;
+    city.apply_to(Translate::new(Rotate90::new(geometry, city.quarter_turns()), city.offset()))
+  }
+
+  /// Creates a shortlist of the features relevant to a chunk, which can be sampled in place of the whole generator
+  pub fn chunk_context(&self, chunk_pos: ChunkPos) -> ChunkContext<'_> {
+    let (bedrock, city, outcrops, bubble_lifts, ocean) = &***self.inner;
+    let mut context = ChunkContext::new(self.chunk_bounding_box(chunk_pos));
+    context.set_theme(&self.theme);
+    if let Some(wrapped) = &self.wrapped {
+      // What the wrapper did with the features isn't known, so the whole wrapped world is the only feature
+      context.push(FeaturePriority::Bedrock, wrapped);
+    } else if let Some(profile) = &self.profile {
+      // Each layer is pushed whole rather than shortlisted, so that its time can be attributed to it
+      context.push_boxed(FeaturePriority::Bedrock, Box::new(Profiled::new(bedrock, profile.bedrock.clone())));
+      for (layer, geometry) in city.layers_intersecting(self.city_local_box(context.bounding_box())) {
+        let priority = FeaturePriority::City { layer, member: 0, index: 0 };
+        let geometry = Profiled::new(geometry, profile.layers[layer].clone());
+        context.push_boxed(priority, Box::new(self.place_with_city(geometry)));
+      };
+
+      for (index, shaft) in city.shafts().iter().enumerate() {
+        context.push_boxed(FeaturePriority::Shaft { index }, Box::new(self.place_with_city(shaft)));
+        context.push_carving(FeaturePriority::Shaft { index }, Box::new(self.place_with_city(shaft.openings())));
+      };
+
+      for (index, letterbox) in city.letterboxes().iter().enumerate() {
+        let opening = self.place_with_city(letterbox.opening());
+        context.push_boxed(FeaturePriority::Letterbox { index }, Box::new(self.place_with_city(letterbox)));
+        context.push_carving(FeaturePriority::Letterbox { index }, Box::new(opening));
+      };
+
+      for (index, beacon) in city.beacons().iter().enumerate() {
+        context.push_boxed(FeaturePriority::Beacon { index }, Box::new(self.place_with_city(beacon)));
+        if let Some(skylight) = beacon.skylight() {
+          let skylight = self.place_with_city(skylight);
+          context.push_carving(FeaturePriority::Beacon { index }, Box::new(skylight));
+        };
+      };
+
+      context.push_boxed(FeaturePriority::Outcrops, Box::new(Profiled::new(outcrops, profile.outcrops.clone())));
+      let bubble_lifts = Profiled::new(bubble_lifts, profile.bubble_lifts.clone());
+      context.push_boxed(FeaturePriority::BubbleLifts, Box::new(bubble_lifts));
+      context.push_boxed(FeaturePriority::Ocean, Box::new(Profiled::new(ocean, profile.ocean.clone())));
+    } else {
+      context.push(FeaturePriority::Bedrock, bedrock);
+      // The city's features are shortlisted in its own coordinates, then moved into place together.
+      // Every feature of the city shares the city's place in the priority order, so this doesn't reorder anything.
+      let mut city_context = ChunkContext::new(self.city_local_box(context.bounding_box()));
+      city.shortlist(&mut city_context);
+      let priority = FeaturePriority::City { layer: 0, member: 0, index: 0 };
+      context.push_boxed(priority, Box::new(self.place_with_city(city_context)));
+      context.push(FeaturePriority::Outcrops, outcrops);
+      context.push(FeaturePriority::BubbleLifts, bubble_lifts);
+      context.push(FeaturePriority::Ocean, ocean);
+    };
+
+    context
+  }
+
+  /// The number of columns covered by the landmass of each layer of the city, from the bottom up
+  pub fn landmass_sizes(&self) -> Vec<u32> {
+    let (_, city, _, _, _) = &***self.inner;
+    city.landmass_sizes()
+  }
+
+  /// A table of the time spent sampling each top-level feature, along with
+  /// the hit rates of the bedrock and ocean noise caches, if profiling is enabled
+  pub fn profile_table(&self) -> Option<String> {
+    let profile = self.profile.as_ref()?;
+    let (bedrock, _, _, _, ocean) = &***self.inner;
+    let cache_table = to_cache_table_string(bedrock.cache_counters().chain(ocean.cache_counters()));
+    Some(format!("{}\n{}", profile.table.to_table_string(), cache_table))
+  }
+
+  /// A z value at or above the topmost block of a column, taken from what each top-level feature knows about its own
+  /// reach there, so that only the blocks beneath it need to be sampled to find the surface.
+  /// This generates every layer of the city, if it hasn't been already.
+  fn column_top(&self, xy: IVec2) -> i32 {
+    // A wrapped world could place anything anywhere, so the whole column is sampled
+    if self.wrapped.is_some() { return self.bounding_box.max.z };
+    let (bedrock, city, outcrops, bubble_lifts, ocean) = &***self.inner;
+    let rotated: &Rotate90<Arc<City>> = city;
+    // Moving the city into place only rotates and offsets it horizontally, and fading it only takes blocks away
+    let local = rotated.to_local(city.to_local(xy.extend(0))).xy();
+    let tops = [
+      bedrock.column_top(xy),
+      rotated.column_top(local),
+      outcrops.column_top(xy),
+      bubble_lifts.column_top(xy),
+      ocean.column_top(xy)
+    ];
+    tops.into_iter()
+      .map(|top| top.unwrap_or(self.bounding_box.max.z))
+      .max()
+      .unwrap()
+      .min(self.bounding_box.max.z)
+  }
+
+  /// The topmost block of a column, along with its height and the kind of feature that placed it,
+  /// or `None` if the column is empty or outside of the world
+  pub fn surface_height(&self, xy: IVec2) -> Option<SurfaceInfo> {
+    if !self.bounding_box.contains_column(xy) { return None };
+    let z = (self.bounding_box.min.z..=self.column_top(xy)).rev()
+      .find(|&z| self.world().block_at(xy.extend(z)))?;
+    let (block, feature) = self.block_material_at_traced(xy.extend(z))?;
+    Some(SurfaceInfo { z, block, feature })
+  }
+
+  /// Finds the topmost block of every column in a chunk, indexed by `y * 16 + x`
+  pub fn column_heights(&self, chunk_pos: ChunkPos) -> Vec<Option<i32>> {
+    chunk_pos.block_range()
+      .map(|column| self.surface_height(column).map(|surface| surface.z))
+      .collect()
+  }
+
+  /// Samples every block in a chunk, collecting statistics about its contents without rendering it
+  pub fn estimate_chunk_complexity(&self, chunk_pos: ChunkPos) -> ChunkStats {
+    let context = self.chunk_context(chunk_pos);
+    let mut recorder = ChunkStatsRecorder::new(chunk_pos);
+    for block_pos in iter_chunk_blocks(self.bounding_box.min.z, self.bounding_box.max.z) {
+      if let Some(block) = context.block_material_at(block_pos + chunk_pos.min_block().extend(0)) {
+        recorder.record(block_pos, &block);
+      };
+    };
+
+    recorder.finish()
+  }
+
+  /// Works out how much rendering every chunk of this generator will take, without rendering any of them or generating
+  /// any of the city's layers. The chunks are counted exactly from the bounds, while the sampling speed is measured on
+  /// a few far-field chunks spread out among them, since those are the only chunks which can be sampled without
+  /// generating layers. The city samples more slowly than the open ocean, so the speed is only a rough guide.
+  pub fn estimate(&self) -> GenerationEstimate {
+    let (min_z, max_z) = (self.bounding_box.min.z, self.bounding_box.max.z);
+    let column_samples = |chunk_pos: ChunkPos| -> u64 {
+      let top = match self.is_far_field_chunk(chunk_pos) {
+        true => self.far_field_max_z(chunk_pos),
+        false => max_z
+      };
+
+      (top - min_z + 1).max(0) as u64 * 256
+    };
+
+    let (mut chunk_count, mut far_field_chunk_count, mut block_samples) = (0, 0, 0);
+    for chunk_pos in self.chunks() {
+      chunk_count += 1;
+      far_field_chunk_count += self.is_far_field_chunk(chunk_pos) as u64;
+      block_samples += column_samples(chunk_pos);
+    };
+
+    let step = (far_field_chunk_count as usize / TIMED_CHUNK_COUNT).max(1);
+    let (mut timed_samples, mut elapsed) = (0, Duration::ZERO);
+    let far_field_chunks = self.chunks().filter(|&chunk_pos| self.is_far_field_chunk(chunk_pos));
+    for chunk_pos in far_field_chunks.step_by(step).take(TIMED_CHUNK_COUNT) {
+      let started = Instant::now();
+      SampledChunk::sample(self, chunk_pos);
+      elapsed += started.elapsed();
+      timed_samples += column_samples(chunk_pos);
+    };
+
+    GenerationEstimate {
+      chunk_count,
+      far_field_chunk_count,
+      block_samples,
+      output_bytes: chunk_count * TYPICAL_CHUNK_BYTES,
+      blocks_per_second: (timed_samples as f64 / elapsed.as_secs_f64().max(1e-6)) as u64
+    }
+  }
+}
+
+/// The topmost block of a column of the world, found by `Generator::surface_height`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SurfaceInfo {
+  pub z: i32,
+  pub block: Block,
+  pub feature: FeatureTag
+}
+
+/// The world as returned by `GenerationHooks::wrap_final`, cut off at the same edge as the world it wraps
+#[derive(Clone)]
+struct WrappedWorld {
+  bounding_box: BoundingBox,
+  inner: Arc<dyn MaterialGeometry + Send + Sync>
+}
+
+impl fmt::Debug for WrappedWorld {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("WrappedWorld").field("bounding_box", &self.bounding_box).finish_non_exhaustive()
+  }
+}
+
+impl Geometry for WrappedWorld {
+  fn bounding_box(&self) -> BoundingBox {
+    self.bounding_box
+  }
+
+  fn block_at(&self, pos: IVec3) -> bool {
+    self.bounding_box.contains(pos) && self.inner.block_at(pos)
+  }
+}
+
+impl MaterialGeometry for WrappedWorld {
+  fn block_material_at(&self, pos: IVec3) -> Option<Cow<'_, Block>> {
+    if !self.bounding_box.contains(pos) { return None };
+    self.inner.block_material_at(pos)
+  }
+
+  fn possible_blocks(&self, out: &mut BTreeSet<Block>) {
+    self.inner.possible_blocks(out);
+  }
+}
+
+/// The counters for each top-level feature of a generator
+#[derive(Debug, Clone)]
+struct GeneratorProfile {
+  table: ProfileTable,
+  bedrock: Arc<ProfileCounters>,
+  layers: Vec<Arc<ProfileCounters>>,
+  outcrops: Arc<ProfileCounters>,
+  bubble_lifts: Arc<ProfileCounters>,
+  ocean: Arc<ProfileCounters>
+}
+
+impl GeneratorProfile {
+  fn new(layer_count: usize) -> Self {
+    let mut table = ProfileTable::new();
+    let bedrock = table.register("bedrock");
+    let layers = (0..layer_count)
+      .map(|i| table.register(format!("layer {}", i)))
+      .collect();
+    let outcrops = table.register("outcrops");
+    let bubble_lifts = table.register("bubble lifts");
+    let ocean = table.register("ocean");
+    GeneratorProfile { table, bedrock, layers, outcrops, bubble_lifts, ocean }
+  }
+}
+
+#[cfg(feature = "serve")]
+fn start_preview_server(generator: &Generator, addr: SocketAddr) -> Option<PreviewServer> {
+  match PreviewServer::start(addr, generator.bounding_box, generator.chunk_count()) {
+    Ok(server) => {
+      log::info!("serving previews at http://{}/status and http://{}/map.png", addr, addr);
+      Some(server)
+    },
+    Err(err) => {
+      log::warn!("failed to start the preview server: {}", err);
+      None
+    }
+  }
+}
+
+/// Runs the command given by the process' arguments, as the `glt-mc-generator` binary does.
+/// Only public so that the binary can call it.
+#[doc(hidden)]
+pub fn main() -> PyResult<()> {
+  match Command::parse(std::env::args().skip(1)) {
+    Ok(Command::Generate(args)) => generate(args),
+    Ok(Command::Preview(args)) => preview(args),
+    Ok(Command::Inspect(args)) => inspect(args),
+    Ok(Command::Validate(args)) => validate(args),
+    Ok(Command::Export(args)) => export(args),
+    Ok(Command::Goldens(args)) => goldens(args),
+    Ok(Command::Explore(args)) => explore(args),
+    Ok(Command::Help(usage)) => {
+      println!("{}", usage);
+      Ok(())
+    },
+    Err(err) => {
+      eprintln!("{}", err);
+      std::process::exit(2);
+    }
+  }
+}
+
+/// Installs the logger, using `--log-level` if it was given, or else the `RUST_LOG` environment variable
+fn init_logger(args: &GenerationArgs) {
+  let mut env_error = None;
+  let filter = args.log_filter.clone().unwrap_or_else(|| {
+    match std::env::var("RUST_LOG").ok().filter(|var| !var.trim().is_empty()) {
+      Some(var) => var.parse::<LogFilter>().unwrap_or_else(|err| {
+        env_error = Some(err);
+        LogFilter::default()
+      }),
+      None => LogFilter::default()
+    }
+  });
+
+  logger::init(filter).expect("the logger was already initialized");
+  if let Some(err) = env_error {
+    log::warn!("ignoring `RUST_LOG`: {}", err);
+  };
+}
+
+/// Builds the generator described by the given arguments,
+/// whose configuration was already validated when they were parsed
+fn build_generator(args: &GenerationArgs) -> io::Result<Generator> {
+  log::debug!("generating with the configuration:\n{}", args.builder.to_toml());
+  args.builder.build().map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))
+}
+
+fn generate(args: GenerateArgs) -> PyResult<()> {
+  init_logger(&args.generation);
+  if args.legacy_seed {
+    log::warn!("`glt-mc-generator <seed>` is deprecated, use `glt-mc-generator generate --seed <seed>` instead");
+  };
+
+  // Resolved before generating anything, so that a bad output directory is reported straight away
+  let output = OutputPath::resolve(args.output.clone())?;
+  log::info!("rendering into {} ({})", output.path.display(), output.source);
+  let level_path = output.path;
+
+  log::info!("generating features...");
+  let generator = build_generator(&args.generation)?;
+
+  if args.format == WorldFormat::Bedrock && !fits_bedrock_height(generator.bounding_box) {
+    log::warn!(
+      "the city spans z {} to {}, but bedrock worlds only go from {} to {}, so parts of it will be cut off",
+      generator.bounding_box.min.z, generator.bounding_box.max.z, BEDROCK_MIN_Z, BEDROCK_MAX_Z
+    );
+  };
+
+  log::info!("estimating the size of the render...");
+  let estimate = generator.estimate();
+  log::info!("estimate: {}", estimate);
+  if needs_confirmation(&args, &estimate) {
+    let confirm_above = args.confirm_above.unwrap_or(DEFAULT_CONFIRM_ABOVE);
+    let question = format!("this world has more than {} chunks, render it anyway?", confirm_above);
+    if !confirm(&question)? {
+      log::info!("not rendering, pass --yes to skip this question");
+      return Ok(());
+    };
+  };
+
+  let seed = args.generation.builder.seed();
+  let grid = match args.split_output {
+    Some(grid) => grid,
+    None => {
+      reset_level(&level_path, generator.bounding_box, generator.world_height, args.format, seed, &args.keep)?;
+      return render_world(&args, &generator, &estimate, &level_path, generator.bounding_box, generator.chunks());
+    }
+  };
+
+  let (min, max) = generator.bounding_box.to_chunks();
+  let parts = grid.parts(min, max);
+  let (bounding_box, world_height) = (generator.bounding_box, generator.world_height);
+  reset_split_level(&level_path, &parts, bounding_box, world_height, args.format, seed, &args.keep)?;
+  for (i, part) in parts.iter().enumerate() {
+    log::info!("rendering part {} of {} into {}...", i + 1, parts.len(), part.name());
+    let part_path = level_path.join(part.name());
+    render_world(&args, &generator, &estimate, &part_path, part.clip(bounding_box), part.chunks())?;
+  };
+
+  log::info!("writing {}...", MANIFEST_FILE_NAME);
+  let manifest = manifest_json(seed, grid, &parts);
+  fs::write(level_path.join(MANIFEST_FILE_NAME), manifest)?;
+  Ok(())
+}
+
+/// Whether to ask before rendering, which is only done for worlds with more chunks than `--confirm-above`,
+/// and never with `--yes`
+fn needs_confirmation(args: &GenerateArgs, estimate: &GenerationEstimate) -> bool {
+  !args.yes && estimate.needs_confirmation(args.confirm_above.unwrap_or(DEFAULT_CONFIRM_ABOVE))
+}
+
+/// Asks on the terminal whether to go ahead, taking anything but a yes as a no, including the end of stdin
+fn confirm(question: &str) -> io::Result<bool> {
+  eprint!("{} [y/N] ", question);
+  let mut answer = String::new();
+  io::stdin().read_line(&mut answer)?;
+  Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Renders the given chunks into the world at the given path, once `reset_level` has readied it, placing its spawn in
+/// the middle of the given bounding box. This is the whole world, unless it is being split into parts, which all
+/// record the whole world's estimate.
+fn render_world(
+  args: &GenerateArgs, generator: &Generator, estimate: &GenerationEstimate, level_path: &Path,
+  bounding_box: BoundingBox, chunks: impl ExactSizeIterator<Item = ChunkPos> + Send
+) -> PyResult<()> {
+  let spawn_column = (bounding_box.min.xy() + bounding_box.max.xy()) / 2;
+  if let Some(surface) = generator.surface_height(spawn_column) {
+    log::debug!(
+      "the spawn point is above {} at z={}, placed by the {}",
+      surface.block.base_blockstate(), surface.z, surface.feature.name()
+    );
+  };
+
+  log::info!("rendering chunks...");
+  if args.render_workers > 0 {
+    render_chunks_with_workers(generator, chunks, level_path, &args.limits, args.serve, args.render_workers)?;
+  } else {
+    Python::with_gil(|py| {
+      configure_python_logging(py)?;
+      let level = load_level(py, level_path, args.format)?;
+      let lookahead = args.lookahead.map_or(DEFAULT_LOOKAHEAD, NonZeroUsize::get);
+      render_chunks(py, generator, chunks, level, args.format, &args.limits, args.serve, lookahead)
+    })?;
+  };
+
+  // Written once Amulet is done with the world, since it rewrites level.dat when saving
+  log::info!("recording world info...");
+  let builder = &args.generation.builder;
+  let info = WorldInfo::new(builder.seed(), builder.options(), generator.landmass_sizes(), Some(*estimate));
+  info.write_to_world(level_path)?;
+  Ok(())
+}
+
+/// Prints what the generator recorded about a world it rendered
+fn inspect(args: InspectArgs) -> PyResult<()> {
+  match args {
+    InspectArgs::Info(world) => match WorldInfo::read_from_world(&world)? {
+      Some(info) => println!("{}", info),
+      None => {
+        eprintln!(
+          "{} has no world info, it was not rendered by a version of glt-mc-generator which records it",
+          world.display()
+        );
+        std::process::exit(1);
+      }
+    }
+  };
+
+  Ok(())
+}
+
+/// Checks that the world's features agree about where they have blocks, exiting with an error at the first position
+/// where they don't. Half of the positions are sampled within the city, where most of the features are.
+fn validate(args: ValidateArgs) -> PyResult<()> {
+  init_logger(&args.generation);
+  log::info!("generating features...");
+  let generator = build_generator(&args.generation)?;
+  let seed = args.generation.builder.seed();
+
+  log::info!("checking {} positions...", args.samples);
+  // Sampled without the checks of `CheckedGeometry`, so that a disagreement is reported rather than panicking
+  let features: &WorldFeatures = &generator.inner;
+  let city_samples = args.samples / 2;
+  let result = validate_consistency(features, generator.city_bounding_box, city_samples, seed)
+    .and_then(|()| validate_consistency(features, generator.bounding_box, args.samples - city_samples, !seed));
+  if let Err(pos) = result {
+    eprintln!("the world's features disagree about whether there is a block at {}", pos);
+    std::process::exit(1);
+  };
+
+  log::info!("all {} positions are consistent", args.samples);
+  Ok(())
+}
+
+/// Writes a file describing the world, without rendering it
+fn export(args: ExportArgs) -> PyResult<()> {
+  match args {
+    ExportArgs::Datapack { generation, output } => {
+      init_logger(&generation);
+      log::info!("generating features...");
+      let generator = build_generator(&generation)?;
+      log::info!("writing {}...", output.display());
+      fs::write(&output, build_world_datapack(generator.bounding_box, generator.world_height))?;
+    }
+  };
+
+  Ok(())
+}
+
+/// Flips through seeds in the terminal, starting from the given seed, then renders the one chosen, if any
+#[cfg(feature = "explore")]
+fn explore(mut args: GenerateArgs) -> PyResult<()> {
+  let builder = &mut args.generation.builder;
+  if let Some(seed) = explore::explore(builder.seed(), builder.options())? {
+    builder.reseed(seed);
+    generate(args)?;
+  };
+
+  Ok(())
+}
+
+#[cfg(not(feature = "explore"))]
+fn explore(_: GenerateArgs) -> PyResult<()> {
+  eprintln!("`explore` requires the `explore` feature");
+  std::process::exit(2);
+}
+
+/// Checks that generation still matches the golden samples, exiting with an error listing every sample which changed,
+/// or rewrites the golden sample file if asked to
+fn goldens(args: GoldensArgs) -> PyResult<()> {
+  init_logger(&GenerationArgs::default());
+  if args.regenerate {
+    let cases = goldens::record_goldens()?;
+    goldens::write_goldens(&args.file, &cases)?;
+    let sample_count = cases.iter().map(|case| case.samples.len()).sum::<usize>();
+    log::info!("recorded {} golden samples across {} seeds into {}", sample_count, cases.len(), args.file.display());
+    return Ok(());
+  };
+
+  let cases = goldens::read_goldens(&args.file)?;
+  let (mut sample_count, mut changed_count) = (0, 0);
+  for case in &cases {
+    log::info!("checking seed {} ({})", case.seed, case.flags);
+    for (sample, found) in case.check()? {
+      println!("{}", sample.describe_change(case, found.as_deref()));
+      changed_count += 1;
+    };
+
+    sample_count += case.samples.len();
+  };
+
+  if changed_count > 0 {
+    eprintln!(
+      "{} of {} golden samples changed, run with --regenerate-goldens if this is intentional",
+      changed_count, sample_count
+    );
+    std::process::exit(1);
+  };
+
+  log::info!("all {} golden samples match", sample_count);
+  Ok(())
+}
+
+/// Writes a heightmap (and optionally an isometric view) of the city to an image, without touching Python or a world
+fn preview(args: PreviewArgs) -> PyResult<()> {
+  init_logger(&args.generation);
+  log::info!("generating features...");
+  let generator = build_generator(&args.generation)?;
+
+  let chunk_count = generator.chunk_count();
+  let mut heightmap = Heightmap::new(generator.bounding_box);
+  for (i, chunk_pos) in generator.chunks().enumerate() {
+    if i % 64 == 0 {
+      log::info!("sampling chunks: {:>5.2}%", i as f32 / chunk_count as f32 * 100.0);
+    };
+
+    heightmap.insert(chunk_pos, generator.column_heights(chunk_pos));
+  };
+
+  if let Some(profile_table) = generator.profile_table() {
+    log::info!("geometry profile:\n{}", profile_table);
+  };
+
+  if let Some(sampling) = args.stats {
+    log::info!("sampling blocks for statistics...");
+    let stats = generator.block_stats(sampling, args.generation.builder.seed());
+    log::info!("block statistics:\n{}", stats.to_table_string());
+  };
+
+  log::info!("writing {}...", args.output.display());
+  fs::write(&args.output, heightmap.to_png())?;
+
+  if let Some(iso_output) = &args.iso_output {
+    log::info!("drawing isometric view...");
+    render_isometric(&generator, iso_output, args.iso_scale)?;
+  };
+
+  Ok(())
+}
+
+/// Renders chunks into a world loaded in-process, sampling them up to `lookahead` chunks ahead of the one being
+/// written so that sampling isn't held up by Amulet
+#[allow(clippy::too_many_arguments)]
+fn render_chunks(
+  py: Python, generator: &Generator, chunks: impl ExactSizeIterator<Item = ChunkPos> + Send, level: &PyAny,
+  format: WorldFormat, limits: &ChunkLimits, serve: Option<SocketAddr>, lookahead: usize
+) -> PyResult<()> {
+  let translator = block_translator(level, format)?;
+  // Every block is registered with the world's palette up front, once, so that rendering never has to go through
+  // Amulet's slow `BlockManager.get_add_block`
+  let census = generator.block_census();
+  log::info!("registering {} blocks with the world's palette...", census.len());
+  let level_palette = level.getattr("block_palette")?;
+  let block_nums = census.into_iter()
+    .map(|block| {
+      let amulet_block = to_amulet_block(py, block.clone(), translator)?;
+      let block_num = level_palette.call_method1("get_add_block", (amulet_block,))?.extract::<u32>()?;
+      Ok((block, block_num))
+    })
+    .collect::<PyResult<HashMap<Block, u32>>>()?;
+  let far_field_block_nums = generator.far_field_palette().iter()
+    .map(|block| census_block_num(&block_nums, block))
+    .collect::<PyResult<Vec<u32>>>()?;
+  let chunk_count = chunks.len();
+  let mut far_field_count = 0;
+  let mut oversized_chunks = Vec::new();
+
+  #[cfg(feature = "serve")]
+  let preview_server = serve.and_then(|addr| start_preview_server(generator, addr));
+  #[cfg(not(feature = "serve"))]
+  if serve.is_some() {
+    log::warn!("`--serve` requires the `serve` feature, ignoring it");
+  };
+
+  // Sampling happens on other threads, which never touch Python, so the GIL can stay held for the writes
+  let sample = |chunk_pos| SampledChunk::sample(generator, chunk_pos);
+  let write = |(chunk, stats): (SampledChunk, ChunkStats), progress: PipelineProgress| -> PyResult<()> {
+    let chunk_pos = stats.chunk;
+    let percent = (progress.written + 1) as f32 / progress.total as f32 * 100.0;
+    log::debug!(
+      "rendering chunk: {:>3}, {:>3}  {:>5.2}%  ({} sampled, {} written)",
+      chunk_pos.0.x, chunk_pos.0.y, percent, progress.sampled, progress.written
+    );
+    #[cfg(feature = "serve")]
+    if let Some(preview_server) = &preview_server {
+      preview_server.chunk_started(chunk_pos);
+    };
+
+    match chunk {
+      SampledChunk::FarField(far_field_chunk) => {
+        far_field_count += 1;
+        render_far_field_chunk(py, level, &far_field_block_nums, far_field_chunk)?;
+      },
+      SampledChunk::Full { sections, block_entities } => {
+        render_chunk(py, level, &block_nums, chunk_pos, sections, block_entities)?;
+      }
+    };
+    #[cfg(feature = "serve")]
+    if let Some(preview_server) = &preview_server {
+      preview_server.chunk_finished(&stats);
+    };
+
+    if stats.exceeds(limits) {
+      log::warn!(
+        "chunk {}, {} exceeds limits ({} distinct blocks, {} in its largest section)",
+        chunk_pos.0.x, chunk_pos.0.y, stats.chunk_palette_size, stats.max_section_palette_size()
+      );
+      oversized_chunks.push(stats);
+    };
+
+    Ok(())
+  };
+
+  let timings = pipeline_chunks(chunks, lookahead, sample, write)?;
+  report_oversized_chunks(&oversized_chunks);
+  report_far_field_chunks(far_field_count, chunk_count);
+  timings.report(chunk_count);
+
+  if let Some(profile_table) = generator.profile_table() {
+    log::info!("geometry profile:\n{}", profile_table);
+  };
+
+  #[cfg(feature = "serve")]
+  if let Some(preview_server) = preview_server {
+    preview_server.finish();
+  };
+
+  log::info!("saving chunks...");
+  level.call_method0("save")?;
+  level.call_method0("close")?;
+
+  Ok(())
+}
+
+/// Renders chunks using a pool of Python worker processes, each of which saves its own regions of the world
+fn render_chunks_with_workers(
+  generator: &Generator, chunks: impl ExactSizeIterator<Item = ChunkPos>, level_path: &Path, limits: &ChunkLimits,
+  serve: Option<SocketAddr>, worker_count: usize
+) -> io::Result<()> {
+  /// The number of chunks sampled in parallel before they are sent to the workers
+  const BATCH_SIZE: usize = 64;
+
+  let chunk_count = chunks.len();
+  let mut far_field_count = 0;
+  let mut oversized_chunks = Vec::new();
+
+  #[cfg(feature = "serve")]
+  let preview_server = serve.and_then(|addr| start_preview_server(generator, addr));
+  #[cfg(not(feature = "serve"))]
+  if serve.is_some() {
+    log::warn!("`--serve` requires the `serve` feature, ignoring it");
+  };
+
+  let census = generator.block_census();
+  log::info!("registering {} blocks with each render worker's palette...", census.len());
+  let mut workers = RenderWorkers::spawn(level_path, worker_count, &census, log::max_level() < LevelFilter::Debug)?;
+  for (i, batch) in chunk_windows(chunks, BATCH_SIZE).enumerate() {
+    let sampled = batch.par_iter()
+      .map(|&chunk_pos| EncodedChunk::sample(generator, chunk_pos))
+      .collect::<Vec<(EncodedChunk, ChunkStats)>>();
+    for (j, (chunk, stats)) in sampled.into_iter().enumerate() {
+      let progress = (i * BATCH_SIZE + j + 1) as f32 / chunk_count as f32 * 100.0;
+      log::debug!("rendering chunk: {:>3}, {:>3}  {:>5.2}%", chunk.pos.0.x, chunk.pos.0.y, progress);
+      #[cfg(feature = "serve")]
+      if let Some(preview_server) = &preview_server {
+        preview_server.chunk_started(chunk.pos);
+      };
+
+      workers.send(&chunk)?;
+      far_field_count += chunk.far_field as usize;
+      #[cfg(feature = "serve")]
+      if let Some(preview_server) = &preview_server {
+        preview_server.chunk_finished(&stats);
+      };
+
+      if stats.exceeds(limits) {
+        log::warn!(
+          "chunk {}, {} exceeds limits ({} distinct blocks, {} in its largest section)",
+          chunk.pos.0.x, chunk.pos.0.y, stats.chunk_palette_size, stats.max_section_palette_size()
+        );
+        oversized_chunks.push(stats);
+      };
+    };
+  };
+
+  report_oversized_chunks(&oversized_chunks);
+  report_far_field_chunks(far_field_count, chunk_count);
+
+  if let Some(profile_table) = generator.profile_table() {
+    log::info!("geometry profile:\n{}", profile_table);
+  };
+
+  #[cfg(feature = "serve")]
+  if let Some(preview_server) = preview_server {
+    preview_server.finish();
+  };
+
+  log::info!("waiting for the render workers to save their chunks...");
+  workers.finish()
+}
+
+fn report_oversized_chunks(oversized_chunks: &[ChunkStats]) {
+  if !oversized_chunks.is_empty() {
+    let summary = oversized_chunks.iter()
+      .map(|stats| format!(
+        "\n  {:>3}, {:>3}  palette: {:>4}  section palette: {:>4}  blocks: {:>6}",
+        stats.chunk.0.x, stats.chunk.0.y, stats.chunk_palette_size,
+        stats.max_section_palette_size(), stats.block_count
+      ))
+      .collect::<String>();
+    log::warn!("{} chunk(s) exceeded limits:{}", oversized_chunks.len(), summary);
+  };
+}
+
+fn report_far_field_chunks(far_field_count: usize, chunk_count: usize) {
+  log::info!("{} of {} chunks only held bedrock and ocean, and took the fast path", far_field_count, chunk_count);
+}
+
+/// Amulet's translator from the generator's Java edition blocks to its own universal blocks, if the world needs one.
+/// Java edition worlds don't, since Amulet's universal blocks are close enough to Java edition's to be used as-is.
+fn block_translator(level: &PyAny, format: WorldFormat) -> PyResult<Option<&PyAny>> {
+  match format {
+    WorldFormat::Java => Ok(None),
+    WorldFormat::Bedrock => {
+      let version = level.getattr("translation_manager")?.call_method1("get_version", ("java", JAVA_VERSION))?;
+      Ok(Some(version.getattr("block")?))
+    }
+  }
+}
+
+/// Writes a chunk sampled by `SampledChunk::sample` into the world, given the number of each block of the census
+/// in the world's palette
+fn render_chunk(
+  py: Python, level: &PyAny, block_nums: &HashMap<Block, u32>, chunk_pos: ChunkPos,
+  chunk_sections: ChunkSections, block_entities: Vec<BlockEntity>
+) -> PyResult<()> {
+  let (cx, cz) = chunk_to_minecraft(chunk_pos);
+  let chunk = level.call_method1("create_chunk", (cx, cz, "minecraft:overworld"))?;
+  let chunk_block_nums = chunk_sections.palette.iter()
+    .map(|block| census_block_num(block_nums, block))
+    .collect::<PyResult<Vec<u32>>>()?;
+  add_sections(py, chunk, &chunk_block_nums, chunk_sections.sections())?;
+
+  let amulet_block_entities = chunk.getattr("block_entities")?;
+  for block_entity in block_entities {
+    debug_assert_in_chunk(chunk_pos, to_minecraft(block_entity.pos));
+    amulet_block_entities.call_method1("insert", (block_entity.into_amulet_block_entity(py)?,))?;
+  };
+
+  Ok(())
+}
+
+/// Writes a far-field chunk a section at a time, given the number of each block of the far-field palette
+/// in the world's palette
+fn render_far_field_chunk(
+  py: Python, level: &PyAny, far_field_block_nums: &[u32], far_field_chunk: FarFieldChunk
+) -> PyResult<()> {
+  let (cx, cz) = chunk_to_minecraft(far_field_chunk.pos);
+  let chunk = level.call_method1("create_chunk", (cx, cz, "minecraft:overworld"))?;
+  add_sections(py, chunk, far_field_block_nums, far_field_chunk.sections())
+}
+
+/// Looks up the number of a block in the world's palette, as registered by the census.
+/// A block missing from the census means that some feature's `possible_blocks` left it out, which is an error.
+fn census_block_num(block_nums: &HashMap<Block, u32>, block: &Block) -> PyResult<u32> {
+  block_nums.get(block).copied().ok_or_else(|| PyRuntimeError::new_err(format!(
+    "`{}` was placed without being in the block census, some feature's `possible_blocks` is incomplete",
+    block.base_blockstate()
+  )))
+}
+
+/// Hands each section of a chunk to Amulet as a whole array, given as indices into a palette
+/// along with the number of each block of the palette in the chunk's block palette
+fn add_sections<'a>(
+  py: Python, chunk: &PyAny, block_nums: &[u32], sections: impl IntoIterator<Item = (i32, &'a [u32])>
+) -> PyResult<()> {
+  let numpy = py.import("numpy")?;
+  let blocks = chunk.getattr("blocks")?;
+  let mut section_data = Vec::with_capacity(SECTION_VOLUME * 4);
+  for (section, indices) in sections {
+    debug_assert_section_in_world(section);
+    section_data.clear();
+    section_data.extend(indices.iter().flat_map(|&index| block_nums[index as usize].to_le_bytes()));
+    // Copied by `astype`, since arrays made from bytes are read-only
+    let array = numpy
+      .call_method1("frombuffer", (PyBytes::new(py, &section_data), "<u4"))?
+      .call_method1("reshape", ((16, 16, 16),))?
+      .call_method1("astype", ("uint32",))?;
+    blocks.call_method1("add_sub_chunk", (section, array))?;
+  };
+
+  Ok(())
+}
+
+/// Converts a block to Amulet's, translating it to Amulet's universal blocks if the world needs it
+fn to_amulet_block(py: Python, block: Block, translator: Option<&PyAny>) -> PyResult<PyObject> {
+  let amulet_block = block.into_amulet_block(py)?;
+  match translator {
+    // Returns the universal block, along with a block entity and extra information which aren't needed
+    Some(translator) => Ok(translator.call_method1("to_universal", (amulet_block,))?.get_item(0)?.into()),
+    None => Ok(amulet_block)
+  }
+}
+
+/// Loads an `amulet.api.level.world.World` instance at the given path, with the Amulet format matching the world's
+fn load_level<'py>(py: Python<'py>, level_path: &Path, format: WorldFormat) -> PyResult<&'py PyAny> {
+  let amulet = py.import("amulet").expect("failed to import `amulet`");
+  let formats = amulet
+    .getattr("level").expect("failed to import `amulet.level`")
+    .getattr("formats").expect("failed to import `amulet.level.formats`");
+  let format_class = match format {
+    WorldFormat::Java => formats
+      .getattr("anvil_world").expect("failed to import `amulet.level.formats.anvil_world`")
+      .getattr("AnvilFormat").expect("failed to import `amulet.level.formats.anvil_world.AnvilFormat`"),
+    WorldFormat::Bedrock => formats
+      .getattr("leveldb_world").expect("failed to import `amulet.level.formats.leveldb_world`")
+      .getattr("LevelDBFormat").expect("failed to import `amulet.level.formats.leveldb_world.LevelDBFormat`")
+  };
+  let world_class = amulet
+    .getattr("api").expect("failed to import `amulet.api`")
+    .getattr("level").expect("failed to import `amulet.api.level`")
+    .getattr("world").expect("failed to import `amulet.api.level.world`")
+    .getattr("World").expect("failed to import `amulet.api.level.world.World`");
+  world_class.call1((level_path, format_class.call1((level_path,))?))
+}
+
+/// Silences Python's logging (including Amulet's warnings), unless logging at the debug level or above
+fn configure_python_logging(py: Python) -> PyResult<()> {
+  if log::max_level() < LevelFilter::Debug {
+    let logging = py.import("logging")?;
+    logging.call_method1("disable", (logging.getattr("WARNING")?,))?;
+  };
+
+  Ok(())
+}
+
+/// Creates a new template world at the given path, ready for amulet to load, keeping anything in the old world which
+/// matches one of the keep patterns. Java edition worlds get a world border fitted to the given bounding box,
+/// and are given the world's height.
+fn reset_level(
+  path: &Path, bounding_box: BoundingBox, world_height: WorldHeight, format: WorldFormat, seed: u64,
+  keep: &[KeepPattern]
+) -> io::Result<()> {
+  const TEMPLATE_ICON_PNG: &[u8] = include_bytes!("../world-template/icon.png");
+  const TEMPLATE_LEVEL_DAT: &[u8] = include_bytes!("../world-template/level.dat");
+
+  let kept = KeptFiles::stash(path, keep)?;
+  fs::remove_dir_all(&path).ignore_err(io::ErrorKind::NotFound)?;
+  fs::create_dir_all(&path)?;
+  if format == WorldFormat::Bedrock {
+    // Bedrock edition has no datapacks, so there is no world border.
+    // The chunks themselves are stored in a LevelDB database in `db`, which Amulet fills in.
+    let level_name = format!("glt {}", seed);
+    fs::create_dir(path.join("db"))?;
+    fs::write(path.join("level.dat"), bedrock_level_dat(&level_name, seed, bounding_box))?;
+    fs::write(path.join("levelname.txt"), level_name)?;
+  } else {
+    let datapacks_path = path.join("datapacks");
+    fs::create_dir(&datapacks_path).ignore_err(io::ErrorKind::AlreadyExists)?;
+    fs::write(datapacks_path.join(WORLD_DATAPACK_FILE_NAME), build_world_datapack(bounding_box, world_height))?;
+    fs::write(path.join("icon.png"), TEMPLATE_ICON_PNG)?;
+    fs::write(path.join("level.dat"), TEMPLATE_LEVEL_DAT)?;
+  };
+
+  match kept {
+    Some(kept) => kept.restore(path),
+    None => Ok(())
+  }
+}
+
+/// Readies the world of every part of a split world with `reset_level`, each fitted to its part of the bounding box.
+/// Only the parts' own worlds are cleared, and they keep their files in the same way as a whole world does, so nothing
+/// else in the output directory is lost. Parts left over from an earlier split into more parts are left as they are.
+fn reset_split_level(
+  level_path: &Path, parts: &[OutputPart], bounding_box: BoundingBox, world_height: WorldHeight, format: WorldFormat,
+  seed: u64, keep: &[KeepPattern]
+) -> io::Result<()> {
+  fs::create_dir_all(level_path)?;
+  for entry in fs::read_dir(level_path)? {
+    let name = entry?.file_name().to_string_lossy().into_owned();
+    if is_part_name(&name) && !parts.iter().any(|part| part.name() == name) {
+      log::warn!("{} is left over from an earlier split, and isn't listed in the new {}", name, MANIFEST_FILE_NAME);
+    };
+  };
+
+  for part in parts {
+    reset_level(&level_path.join(part.name()), part.clip(bounding_box), world_height, format, seed, keep)?;
+  };
+
+  Ok(())
+}
+
+/// Iterates through every block in a chunk
+fn iter_chunk_blocks(min_z: i32, max_z: i32) -> impl Iterator<Item = IVec3> {
+  (min_z..=max_z).flat_map(|z| {
+    (0..16).flat_map(move |x| {
+      (0..16).map(move |y| {
+        IVec3::new(x, y, z)
+      })
+    })
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+  use std::sync::{Mutex, PoisonError};
+
+  use once_cell::sync::Lazy;
+
+  use super::*;
+  use crate::builder::BoxedWorld;
+  use crate::chunk_sections::section_block_pos;
+  use crate::chunk_stats::SECTION_HEIGHT;
+  use crate::generation::city::Layer;
+  use crate::generation::fixtures::SolidBox;
+  use crate::generation::transform::rotate_xy;
+  use crate::split_output::SplitGrid;
+  use crate::world_coords::{column_to_minecraft, from_minecraft};
+
+  /// A one-layer city with a few of the optional features turned on
+  fn small_options() -> GeneratorOptions {
+    GeneratorOptions {
+      layer_count: 1,
+      no_cache: true,
+      details: true,
+      seabed_response: true,
+      bubble_lifts: true,
+      ..Default::default()
+    }
+  }
+
+  fn small_generator(seed: u64) -> Generator {
+    Generator::new(seed, &small_options(), GenerationHooks::default())
+  }
+
+  /// The small generator for seed 0, generated once and shared between tests
+  static SEED_ZERO: Lazy<Generator> = Lazy::new(|| small_generator(0));
+
+  /// A chunk in the middle of the city, one on its edge and one in the open ocean
+  fn sample_chunks(generator: &Generator) -> [ChunkPos; 3] {
+    let city = generator.city_bounding_box;
+    [
+      ChunkPos::containing((city.min.xy() + city.max.xy()) / 2),
+      ChunkPos::containing(city.min.xy()),
+      ChunkPos::containing(generator.bounding_box.min.xy())
+    ]
+  }
+
+  #[test]
+  fn chunk_contexts_match_the_whole_world() {
+    let generator = &*SEED_ZERO;
+    for chunk_pos in sample_chunks(generator) {
+      let context = generator.chunk_context(chunk_pos);
+      for block_pos in iter_chunk_blocks(generator.bounding_box.min.z, generator.bounding_box.max.z) {
+        let pos = block_pos + chunk_pos.min_block().extend(0);
+        let expected = generator.block_at(pos);
+        let found = context.block_material_at(pos).map(Cow::into_owned);
+        assert_eq!(found, expected, "chunk context disagrees with the world at {}", pos);
+      };
+    };
+  }
+
+  /// Sampling a chunk into sections looks blocks up in its palette by reference, so check that every block
+  /// read back out of the palette is the same as the world's own block, which is cloned out of the generator
+  #[test]
+  fn chunk_sections_match_the_whole_world() {
+    let generator = &*SEED_ZERO;
+    for chunk_pos in sample_chunks(generator) {
+      let (sections, _) = ChunkSections::sample(generator, &generator.chunk_context(chunk_pos), chunk_pos);
+      assert_eq!(sections.palette[0], blocks::AIR);
+      for (i, &index) in sections.indices.iter().enumerate() {
+        let section = sections.min_section + (i / SECTION_VOLUME) as i32;
+        let block_pos = section_block_pos(i % SECTION_VOLUME) + IVec3::new(0, 0, section * SECTION_HEIGHT);
+        let pos = block_pos + chunk_pos.min_block().extend(0);
+        // Air is index 0, whether it was placed or not
+        let expected = generator.block_at(pos).unwrap_or(blocks::AIR);
+        assert_eq!(sections.palette[index as usize], expected, "chunk sections disagree with the world at {}", pos);
+      };
+    };
+  }
+
+  /// Reads the block at a position within a chunk back out of the indices of its sections, which are laid out the same
+  /// way for both `ChunkSections` and `FarFieldChunk`. Positions above or below every section are air.
+  fn block_in_sections<'a>(palette: &'a [Block], min_section: i32, indices: &[u32], block_pos: IVec3) -> &'a Block {
+    let section = block_pos.z.div_euclid(SECTION_HEIGHT);
+    let (x, y, z) = world_coords::to_minecraft(block_pos - IVec3::new(0, 0, section * SECTION_HEIGHT));
+    let index = usize::try_from(section - min_section).ok()
+      .and_then(|section| indices.get(section * SECTION_VOLUME + (x * 256 + y * 16 + z) as usize));
+    index.map_or(&palette[0], |&index| &palette[index as usize])
+  }
+
+  #[test]
+  fn far_field_chunks_match_sampling_them_block_by_block() {
+    let generator = &*SEED_ZERO;
+    let mut far_field_chunks = generator.chunks().filter(|&chunk_pos| generator.is_far_field_chunk(chunk_pos));
+    // The nearest far-field chunks to the city, where the outcrops are, along with some spread out across the ocean
+    let chunks = far_field_chunks.by_ref().take(8).collect::<Vec<_>>().into_iter().chain(far_field_chunks.step_by(97));
+    let mut chunk_count = 0;
+    for chunk_pos in chunks {
+      let (far_field, far_field_stats) = FarFieldChunk::sample(generator, chunk_pos);
+      let context = generator.chunk_context(chunk_pos);
+      let (sections, stats) = ChunkSections::sample(generator, &context, chunk_pos);
+      assert_eq!(far_field_stats, stats, "chunk {}", chunk_pos.0);
+      for block_pos in iter_chunk_blocks(generator.bounding_box.min.z, generator.bounding_box.max.z) {
+        let expected = block_in_sections(&sections.palette, sections.min_section, &sections.indices, block_pos);
+        let palette = generator.far_field_palette();
+        let found = block_in_sections(palette, far_field.min_section, &far_field.indices, block_pos);
+        let pos = block_pos + chunk_pos.min_block().extend(0);
+        assert_eq!(found, expected, "the far-field chunk disagrees with sampling it block by block at {}", pos);
+      };
+
+      chunk_count += 1;
+    };
+
+    assert!(chunk_count > 8, "only {} far-field chunks were checked", chunk_count);
+  }
+
+  #[test]
+  fn building_a_generator_generates_no_layers() {
+    let options = GeneratorOptions { no_cache: true, seabed_response: true, bubble_lifts: true, ..Default::default() };
+    let generator = Generator::new(3, &options, GenerationHooks::default());
+    generator.chunk_count();
+    let far_chunk = ChunkPos::containing(generator.bounding_box.min.xy());
+    assert!(generator.is_far_field_chunk(far_chunk));
+    generator.chunk_context(far_chunk);
+    generator.block_at(generator.bounding_box.min);
+
+    let (_, city, _, bubble_lifts, _) = &***generator.inner;
+    assert!(city.generation_counts().iter().all(|&count| count == 0));
+    assert!(!bubble_lifts.is_built());
+  }
+
+  /// The lifts beside the lowest layer's pillars take the place of the ocean around them, all the way up to its surface
+  #[test]
+  fn bubble_lifts_are_placed_over_the_ocean() {
+    let generator = &*SEED_ZERO;
+    let (_, _, _, bubble_lifts, _) = &***generator.inner;
+    let feet = bubble_lifts.get().feet().filter(|&pos| generator.bounding_box.contains(pos)).collect::<Vec<IVec3>>();
+    assert!(!feet.is_empty(), "no lifts were placed");
+    for foot in feet {
+      let block = generator.block_at(foot);
+      assert!([Some(blocks::SOUL_SAND), Some(blocks::MAGMA_BLOCK)].contains(&block), "{:?} at {}", block, foot);
+      for z in foot.z + 1..=0 {
+        let pos = foot.truncate().extend(z);
+        assert_eq!(generator.block_at(pos), Some(blocks::WATER), "in the lift at {}", pos);
+        for side in [IVec2::X, IVec2::NEG_X] {
+          assert_eq!(generator.block_at(pos + side.extend(0)), Some(blocks::GLASS), "beside the lift at {}", pos);
+        };
+      };
+    };
+  }
+
+  #[test]
+  fn estimating_counts_every_chunk_without_generating_layers() {
+    let generator = small_generator(4);
+    let estimate = generator.estimate();
+    let (min, max) = generator.bounding_box.to_chunks();
+    let size = max.0 - min.0 + IVec2::ONE;
+    assert_eq!(estimate.chunk_count, size.x as u64 * size.y as u64);
+    assert_eq!(estimate.chunk_count, generator.chunk_count() as u64);
+    let far_field_chunk_count = generator.chunks().filter(|&chunk_pos| generator.is_far_field_chunk(chunk_pos)).count();
+    assert_eq!(estimate.far_field_chunk_count, far_field_chunk_count as u64);
+    assert!(estimate.far_field_chunk_count > 0 && estimate.far_field_chunk_count < estimate.chunk_count);
+    assert!(estimate.blocks_per_second > 0);
+
+    let (_, city, _, bubble_lifts, _) = &***generator.inner;
+    assert!(city.generation_counts().iter().all(|&count| count == 0));
+    assert!(!bubble_lifts.is_built());
+  }
+
+  #[test]
+  fn only_large_worlds_wait_for_confirmation_unless_told_yes() {
+    let estimate = |chunk_count| GenerationEstimate {
+      chunk_count,
+      far_field_chunk_count: 0,
+      block_samples: 0,
+      output_bytes: 0,
+      blocks_per_second: 0
+    };
+    let args = |args: &str| match Command::parse(args.split_whitespace().map(str::to_owned)) {
+      Ok(Command::Generate(generate)) => generate,
+      other => panic!("`{}` parsed as {:?}", args, other)
+    };
+
+    let default = args("generate");
+    assert!(!needs_confirmation(&default, &estimate(DEFAULT_CONFIRM_ABOVE)));
+    assert!(needs_confirmation(&default, &estimate(DEFAULT_CONFIRM_ABOVE + 1)));
+    let lowered = args("generate --confirm-above 10");
+    assert!(!needs_confirmation(&lowered, &estimate(10)));
+    assert!(needs_confirmation(&lowered, &estimate(11)));
+    for yes in ["generate --yes", "generate -y --confirm-above 10"] {
+      assert!(!needs_confirmation(&args(yes), &estimate(u64::MAX)), "`{}`", yes);
+    };
+  }
+
+  /// The number of every other column of the city whose topmost block belongs to a building.
+  /// Buildings are at least 5 blocks across, so none are missed.
+  fn building_columns(generator: &Generator) -> usize {
+    let city = generator.city_bounding_box;
+    (city.min.y..=city.max.y).step_by(2)
+      .flat_map(|y| (city.min.x..=city.max.x).step_by(2).map(move |x| IVec2::new(x, y)))
+      .filter_map(|xy| generator.surface_height(xy))
+      .filter(|surface| surface.feature == FeatureTag::Building)
+      .count()
+  }
+
+  #[test]
+  fn a_hook_can_remove_every_building_from_a_layer() {
+    let after_layers = |layers: &mut Vec<Layer>| layers[0].retain_buildings(|_| false);
+    let hooks = GenerationHooks { after_layers: Some(Box::new(after_layers)), ..Default::default() };
+    let generator = Generator::new(0, &small_options(), hooks);
+    assert!(building_columns(&SEED_ZERO) > 0, "the unedited world has no buildings to remove");
+    assert_eq!(building_columns(&generator), 0);
+    let features: &WorldFeatures = &generator.inner;
+    assert_eq!(validate_consistency(features, generator.city_bounding_box, 20_000, 0), Ok(()));
+  }
+
+  #[test]
+  fn wrapping_the_world_replaces_what_is_sampled() {
+    // A box poking out of the top of the world, above the middle of the city
+    let top = SEED_ZERO.bounding_box.max.z;
+    let center = (SEED_ZERO.city_bounding_box.min.xy() + SEED_ZERO.city_bounding_box.max.xy()) / 2;
+    let marker_box = BoundingBox::try_new(center.extend(top - 2), (center + 3).extend(top + 8)).unwrap();
+    let marker = SolidBox::new(marker_box, Block::from("minecraft:gold_block"));
+    let wrap_final = {
+      let marker = marker.clone();
+      move |world| -> BoxedWorld { Box::new(Union::new((marker.clone(), world))) }
+    };
+    let hooks = GenerationHooks { wrap_final: Some(Box::new(wrap_final)), ..Default::default() };
+    let generator = Generator::new(0, &small_options(), hooks);
+
+    assert_eq!(generator.block_at(center.extend(top)), Some(marker.block.clone()));
+    assert_eq!(generator.block_at(center.extend(top + 1)), None, "the wrapped world reaches past the top of the world");
+    assert!(generator.block_census().contains(&marker.block));
+    for (label, pos) in SEED_ZERO.feature_probes() {
+      if marker_box.contains(pos) { continue };
+      assert_eq!(generator.block_at(pos), SEED_ZERO.block_at(pos), "{} at {}", label, pos);
+    };
+
+    // Chunks are sampled through the wrapped world, and none are left to the far field
+    let chunk_pos = ChunkPos::containing(center);
+    let context = generator.chunk_context(chunk_pos);
+    for block_pos in iter_chunk_blocks(generator.bounding_box.min.z, generator.bounding_box.max.z) {
+      let pos = block_pos + chunk_pos.min_block().extend(0);
+      assert_eq!(context.block_material_at(pos).map(Cow::into_owned), generator.block_at(pos), "at {}", pos);
+    };
+
+    assert!(!generator.chunks().any(|chunk_pos| generator.is_far_field_chunk(chunk_pos)));
+  }
+
+  #[test]
+  fn world_features_are_consistent() {
+    let generator = &*SEED_ZERO;
+    // Sampled without the checks of `CheckedGeometry`, so that a disagreement is reported rather than panicking
+    let features: &WorldFeatures = &generator.inner;
+    let city = generator.city_bounding_box;
+    assert_eq!(validate_consistency(features, city, 200_000, 0), Ok(()));
+    assert_eq!(validate_consistency(features, generator.bounding_box, 50_000, 1), Ok(()));
+  }
+
+  /// A two-layer city with every optional feature turned on and a theme swapping some of the materials, so that as many
+  /// kinds of block as possible get placed
+  fn every_feature_generator(seed: u64) -> Generator {
+    let mut theme = Theme::new("harbor");
+    theme.set_role("dark-shade", "minecraft:cyan_terracotta").unwrap();
+    theme.set_role("light", "minecraft:glowstone").unwrap();
+    let options = GeneratorOptions {
+      layer_count: 2,
+      no_cache: true,
+      shapes: Some(ShapeConfig::default()),
+      erosion: Some(ErosionConfig::default()),
+      caves: true,
+      pillar_clusters: true,
+      wall_distance: Some(12),
+      baked_shadows: true,
+      details: true,
+      scatter: true,
+      paving: Some(PavingConfig::default()),
+      lighting: Lighting::Full,
+      wavy_ocean: true,
+      deep_ocean: true,
+      edge_beach: true,
+      seabed_response: true,
+      strata: true,
+      bubble_lifts: true,
+      trench_distance: Some(40),
+      edge_style: EdgeStyle::Fade(8),
+      theme,
+      ..Default::default()
+    };
+    Generator::new(seed, &options, GenerationHooks::default())
+  }
+
+  #[test]
+  fn every_sampled_block_is_in_the_census() {
+    for generator in [&*SEED_ZERO, &every_feature_generator(3)] {
+      let census = generator.block_census();
+      let (min, max) = generator.city_bounding_box.to_chunks();
+      let city_chunks = ChunkPos::rings_in_range(min, max).collect::<Vec<ChunkPos>>();
+      // Spread across the city, along with the chunks at the edge of the world and out in the ocean
+      let chunks = city_chunks.iter().copied().step_by(city_chunks.len() / 40 + 1)
+        .chain(sample_chunks(generator))
+        .chain([ChunkPos::containing(generator.bounding_box.max.xy())]);
+      let mut sampled = BTreeSet::new();
+      for chunk_pos in chunks {
+        let (sections, _) = ChunkSections::sample(generator, &generator.chunk_context(chunk_pos), chunk_pos);
+        for block in sections.palette {
+          assert!(census.contains(&block), "{:?} in chunk {} is missing from the census", block, chunk_pos.0);
+          sampled.insert(block);
+        };
+      };
+
+      // Not every block the census allows for turns up, but enough do that the sampling covered the city
+      assert!(sampled.len() > 12, "only {} kinds of block were sampled", sampled.len());
+    };
+  }
+
+  /// The block at a position among the world's features, found by asking each of them in turn,
+  /// without first checking that the position is within their bounding boxes the way `Union` does
+  fn ungated_block_material_at(features: &WorldFeatures, pos: IVec3) -> Option<Cow<'_, Block>> {
+    let (bedrock, city, outcrops, bubble_lifts, ocean) = &**features;
+    bedrock.block_material_at(pos)
+      .or_else(|| city.block_material_at(pos))
+      .or_else(|| outcrops.block_material_at(pos))
+      .or_else(|| bubble_lifts.block_material_at(pos))
+      .or_else(|| ocean.block_material_at(pos))
+  }
+
+  #[test]
+  fn gating_members_by_their_bounding_boxes_changes_nothing() {
+    let others = [1, 2].map(small_generator);
+    for (seed, generator) in [&*SEED_ZERO].into_iter().chain(&others).enumerate() {
+      let features: &WorldFeatures = &generator.inner;
+      // Most samples are in the city, where the members' bounding boxes overlap
+      for (bounds, samples, salt) in [(generator.city_bounding_box, 100_000, 0), (generator.bounding_box, 20_000, 1)] {
+        let axis = |i: u64, min: i32, max: i32| {
+          let len = (max as i64 - min as i64 + 1) as u64;
+          (min as i64 + (mix64(salt << 32 ^ i) % len) as i64) as i32
+        };
+
+        for sample in 0..samples {
+          let (i, min, max) = (sample * 3, bounds.min, bounds.max);
+          let pos = IVec3::new(axis(i, min.x, max.x), axis(i + 1, min.y, max.y), axis(i + 2, min.z, max.z));
+          let expected = ungated_block_material_at(features, pos);
+          assert_eq!(features.block_material_at(pos), expected, "seed {}: at {}", seed, pos);
+          assert_eq!(features.block_at(pos), expected.is_some(), "seed {}: at {}", seed, pos);
+        };
+      };
+    };
+  }
+
+  /// The topmost block of a column, found by sampling every block of it from the top of the world downwards
+  fn scanned_surface(generator: &Generator, xy: IVec2) -> Option<SurfaceInfo> {
+    let (min_z, max_z) = (generator.bounding_box.min.z, generator.bounding_box.max.z);
+    (min_z..=max_z).rev().find_map(|z| {
+      generator.block_material_at_traced(xy.extend(z))
+        .map(|(block, feature)| SurfaceInfo { z, block, feature })
+    })
+  }
+
+  /// Checks the surface height of columns spread across the city and the rest of the world against scanning them
+  fn assert_surface_heights_match_scanning(generator: &Generator) {
+    let (city, world) = (generator.city_bounding_box, generator.bounding_box);
+    // Most columns are in the city, where the most features overlap
+    for (bounds, columns, salt) in [(city, 3000, 0), (world, 1000, 1)] {
+      let axis = |i: u64, min: i32, max: i32| {
+        let len = (max as i64 - min as i64 + 1) as u64;
+        (min as i64 + (mix64(salt << 32 ^ i) % len) as i64) as i32
+      };
+
+      for column in 0..columns {
+        let (i, min, max) = (column * 2, bounds.min, bounds.max);
+        let xy = IVec2::new(axis(i, min.x, max.x), axis(i + 1, min.y, max.y));
+        assert_eq!(generator.surface_height(xy), scanned_surface(generator, xy), "at {}", xy);
+      };
+    };
+
+    // Columns outside of the world have no surface
+    assert_eq!(generator.surface_height(world.min.xy() - 1), None);
+    assert_eq!(generator.surface_height(world.max.xy() + IVec2::new(1, 0)), None);
+  }
+
+  #[test]
+  fn surface_heights_match_scanning_every_column() {
+    assert_surface_heights_match_scanning(&SEED_ZERO);
+  }
+
+  /// A skirted layer has no pillars, which every feature of the layer has to cope with when finding its reach
+  #[test]
+  fn surface_heights_of_skirted_cities_match_scanning_every_column() {
+    let options = GeneratorOptions { foundation: Foundation::Skirt, ..small_options() };
+    assert_surface_heights_match_scanning(&Generator::new(0, &options, GenerationHooks::default()));
+  }
+
+  #[test]
+  fn nothing_is_generated_at_the_build_limit() {
+    // Low enough that the buildings reach past it
+    let world_height = WorldHeight { min_z: WORLD_MIN_Z, max_z: 64 };
+    let options = GeneratorOptions { world_height, ..small_options() };
+    let generator = Generator::new(0, &options, GenerationHooks::default());
+    assert_eq!(generator.bounding_box.max.z, world_height.max_z - 1);
+    let city = generator.city_bounding_box;
+    let columns = || (city.min.x..=city.max.x).flat_map(|x| (city.min.y..=city.max.y).map(move |y| IVec2::new(x, y)));
+    assert!(columns().all(|xy| generator.block_at(xy.extend(world_height.max_z)).is_none()));
+    assert!(columns().any(|xy| generator.block_at(xy.extend(world_height.max_z - 1)).is_some()));
+  }
+
+  /// Hashes the blocks of every 4th column and every 2nd z value across the city's bounding box and a strip of the
+  /// ocean beside it, so that a change to any feature is almost certain to change the digest
+  fn world_digest(generator: &Generator) -> u64 {
+    let city = generator.city_bounding_box;
+    let min = city.min.xy().min(generator.bounding_box.min.xy() + 64);
+    let (max, step) = (city.max.xy(), 4);
+    let mut digest = 0;
+    for y in (min.y..=max.y).step_by(step) {
+      for x in (min.x..=max.x).step_by(step) {
+        for z in (generator.bounding_box.min.z..=generator.bounding_box.max.z).step_by(2) {
+          let pos = IVec3::new(x, y, z);
+          let block = goldens::block_string(generator.block_at(pos)).unwrap_or_default();
+          digest = block.bytes().fold(hash3(digest, pos), |digest, byte| mix64(digest ^ byte as u64));
+        };
+      };
+    };
+
+    digest
+  }
+
+  /// Pinned so that generation which differs between platforms, or changes by accident, is caught.
+  /// A change to generation which is intended has to update these.
+  #[test]
+  fn world_digests_are_pinned() {
+    assert_eq!(world_digest(&SEED_ZERO), 15741957742326218927, "seed 0");
+    assert_eq!(world_digest(&small_generator(1)), 16436717801205972522, "seed 1");
+  }
+
+  #[test]
+  fn the_city_is_moved_into_place_by_its_placement() {
+    let placement = small_options().city_placement(0);
+    assert_ne!(placement, CityPlacement::default());
+    let centered = Generator::new(0, &GeneratorOptions { center: true, ..small_options() }, GenerationHooks::default());
+    let moved = &*SEED_ZERO;
+    assert_eq!(moved.city_bounding_box, placement.to_world_box(centered.city_bounding_box));
+    // Twice the center of each box, which is turned about 0,0 and then offset along with the city
+    let doubled_center = |bounding_box: BoundingBox| bounding_box.min.xy() + bounding_box.max.xy();
+    let turned = rotate_xy(doubled_center(centered.city_bounding_box), placement.quarter_turns);
+    assert_eq!(doubled_center(moved.city_bounding_box), turned + placement.offset * 2);
+
+    // The tops of the columns around the middle of the centered city are found in the moved one, turned with it
+    let city = centered.city_bounding_box;
+    let mut found = 0;
+    for y in (-64..=64).step_by(8) {
+      for x in (-64..=64).step_by(8) {
+        let top = (1..=city.max.z).rev()
+          .map(|z| IVec3::new(x, y, z))
+          .find_map(|pos| centered.block_at(pos).map(|block| (pos, block)));
+        let (pos, block) = match top {
+          Some(top) => top,
+          None => continue
+        };
+
+        let expected = rotate_block(Cow::Owned(block), placement.quarter_turns).into_owned();
+        assert_eq!(moved.block_at(placement.to_world(pos.xy()).extend(pos.z)), Some(expected), "at {}", pos);
+        found += 1;
+      };
+    };
+
+    assert!(found > 0, "nothing stands above the water around the middle of the city");
+  }
+
+  /// Held by tests which run Python, since they share the interpreter, and the environment which render workers inherit
+  static PYTHON: Mutex<()> = Mutex::new(());
+
+  /// A directory for a test to render into, emptied beforehand
+  fn scratch_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("glt-mc-generator-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&path).ignore_err(io::ErrorKind::NotFound).unwrap();
+    fs::create_dir_all(&path).unwrap();
+    path
+  }
+
+  /// Puts the Amulet stub in `tests/amulet_stub` ahead of any real Amulet, both in this process and in the render
+  /// workers it starts, and clears the calls it has recorded. Fails if numpy, which the stub leaves to the real thing,
+  /// isn't installed.
+  fn use_amulet_stub(py: Python) -> PyResult<()> {
+    let stub_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("amulet_stub");
+    let python_path = std::env::var_os("PYTHONPATH").unwrap_or_default();
+    let mut paths = std::env::split_paths(&python_path).collect::<Vec<PathBuf>>();
+    if !paths.contains(&stub_path) {
+      paths.insert(0, stub_path.clone());
+      std::env::set_var("PYTHONPATH", std::env::join_paths(paths).unwrap());
+    };
+
+    let sys_path = py.import("sys")?.getattr("path")?;
+    if !sys_path.extract::<Vec<PathBuf>>()?.contains(&stub_path) {
+      sys_path.call_method1("insert", (0, &stub_path))?;
+    };
+
+    py.import("numpy")?;
+
+    // Kept in memory rather than written out, so that they can be read back with `stub_calls`
+    py.import("os")?.getattr("environ")?.call_method1("pop", ("AMULET_STUB_LOG", py.None()))?;
+    py.import("amulet._stub")?.getattr("calls")?.call_method0("clear")?;
+    Ok(())
+  }
+
+  /// The calls recorded by the Amulet stub in this process, one per line as they would be written to its log
+  fn stub_calls(py: Python) -> PyResult<Vec<String>> {
+    py.import("amulet._stub")?.call_method0("lines")?.extract()
+  }
+
+  /// The calls which write the contents of chunks, in a consistent order
+  fn chunk_writes(calls: &[String]) -> Vec<&str> {
+    let mut writes = calls.iter()
+      .map(String::as_str)
+      .filter(|call| ["create_chunk ", "add_sub_chunk ", "insert "].iter().any(|name| call.starts_with(name)))
+      .collect::<Vec<&str>>();
+    writes.sort_unstable();
+    writes
+  }
+
+  #[test]
+  #[ignore = "needs numpy, which the Amulet stub leaves to the real thing"]
+  fn render_workers_match_rendering_in_process() {
+    let _python = PYTHON.lock().unwrap_or_else(PoisonError::into_inner);
+    Python::with_gil(use_amulet_stub).unwrap();
+    let generator = &*SEED_ZERO;
+    let chunks = sample_chunks(generator);
+
+    let in_process = Python::with_gil(|py| -> PyResult<Vec<String>> {
+      // Nothing is written there, since the stub only records what it is asked to do
+      let level = load_level(py, &std::env::temp_dir(), WorldFormat::Java)?;
+      let limits = ChunkLimits::default();
+      render_chunks(py, generator, chunks.into_iter(), level, WorldFormat::Java, &limits, None, DEFAULT_LOOKAHEAD)?;
+      stub_calls(py)
+    }).unwrap();
+
+    let level_path = scratch_dir("render-workers");
+    fs::write(level_path.join("level.dat"), "level.dat").unwrap();
+    let log_path = level_path.with_extension("log");
+    fs::remove_file(&log_path).ignore_err(io::ErrorKind::NotFound).unwrap();
+    std::env::set_var("AMULET_STUB_LOG", &log_path);
+    let mut workers = RenderWorkers::spawn(&level_path, 2, &generator.block_census(), true).unwrap();
+    for chunk_pos in chunks {
+      workers.send(&EncodedChunk::sample(generator, chunk_pos).0).unwrap();
+    };
+    let finished = workers.finish();
+    std::env::remove_var("AMULET_STUB_LOG");
+    finished.unwrap();
+    let from_workers = fs::read_to_string(&log_path).unwrap().lines().map(str::to_owned).collect::<Vec<String>>();
+
+    assert_eq!(chunk_writes(&from_workers), chunk_writes(&in_process));
+    assert!(chunk_writes(&in_process).iter().any(|call| call.starts_with("add_sub_chunk ")));
+    assert_eq!(in_process.iter().filter(|call| *call == "save").count(), 1);
+
+    // Each worker opens and saves a scratch world of its own, none of which are left behind, and only the world's own
+    // level.dat is left in it
+    let opened = from_workers.iter().filter(|call| call.starts_with("World ")).collect::<Vec<&String>>();
+    assert_eq!(opened.len(), 2);
+    assert!(opened.iter().all(|call| call.contains(render_workers::SCRATCH_WORLD_PREFIX)), "{:?}", opened);
+    assert_eq!(from_workers.iter().filter(|call| *call == "save").count(), 2);
+    let left = fs::read_dir(&level_path).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+    assert_eq!(left, ["level.dat"]);
+    assert_eq!(fs::read_to_string(level_path.join("level.dat")).unwrap(), "level.dat");
+    fs::remove_dir_all(&level_path).unwrap();
+    fs::remove_file(&log_path).unwrap();
+  }
+
+  /// Renders a few chunks into the Amulet stub, checking the calls made to it and that every block sampled from a few
+  /// columns of each chunk was written where Minecraft expects it, with y vertical
+  #[test]
+  #[ignore = "needs numpy, which the Amulet stub leaves to the real thing"]
+  fn rendering_writes_every_block_through_amulet() {
+    let _python = PYTHON.lock().unwrap_or_else(PoisonError::into_inner);
+    Python::with_gil(use_amulet_stub).unwrap();
+    let generator = &*SEED_ZERO;
+    let chunks = sample_chunks(generator);
+    let census = generator.block_census().into_iter().collect::<Vec<Block>>();
+    Python::with_gil(|py| -> PyResult<()> {
+      let level = load_level(py, &std::env::temp_dir(), WorldFormat::Java)?;
+      let limits = ChunkLimits::default();
+      render_chunks(py, generator, chunks.into_iter(), level, WorldFormat::Java, &limits, None, DEFAULT_LOOKAHEAD)?;
+      let calls = stub_calls(py)?;
+
+      // The world is opened, then every block of the census is registered with its palette, in order
+      assert!(calls[0].starts_with("World ") && calls[0].ends_with(" AnvilFormat"), "{}", calls[0]);
+      for (i, block) in census.iter().enumerate() {
+        let call = &calls[i + 1];
+        let registered = call.starts_with(&format!("get_add_block {}", block.base_name()));
+        assert!(registered && call.ends_with(&format!(" {}", i)), "`{}` registering {:?}", call, block);
+      };
+
+      // Then each chunk is created in turn, followed by its sections from the bottom up, before it is saved once
+      let mut current = None;
+      let mut created = Vec::new();
+      for call in &calls[census.len() + 1..] {
+        let args = call.split(' ').collect::<Vec<&str>>();
+        match args[0] {
+          "create_chunk" => {
+            assert_eq!(args[3], "minecraft:overworld");
+            created.push((args[1].parse::<i32>().unwrap(), args[2].parse::<i32>().unwrap()));
+            current = None;
+          },
+          "add_sub_chunk" => {
+            let (cx, cy, cz) = (args[1].parse().unwrap(), args[2].parse::<i32>().unwrap(), args[3].parse().unwrap());
+            assert_eq!(Some(&(cx, cz)), created.last(), "`{}` isn't in the chunk last created", call);
+            assert!(current.is_none_or(|previous| cy > previous), "`{}` is out of order", call);
+            current = Some(cy);
+          },
+          _ => ()
+        };
+      };
+
+      assert_eq!(created, chunks.map(chunk_to_minecraft));
+      assert_eq!(calls[calls.len() - 2..], ["save", "close"]);
+      assert_eq!(calls.iter().filter(|call| *call == "save").count(), 1);
+
+      for chunk_pos in chunks {
+        let (cx, cz) = chunk_to_minecraft(chunk_pos);
+        let written = level.call_method1("get_chunk", (cx, cz, "minecraft:overworld"))?.getattr("blocks")?;
+        let (min_x, min_z) = column_to_minecraft(chunk_pos.min_block());
+        for (x, z) in [(0, 0), (5, 11), (15, 15)] {
+          for y in generator.bounding_box.min.z..=generator.bounding_box.max.z {
+            let pos = from_minecraft(min_x + x, y, min_z + z);
+            let found = written.get_item((x, y, z))?.extract::<Option<usize>>()?;
+            let found = found.map_or(&blocks::AIR, |num| &census[num]);
+            assert_eq!(found, &generator.block_at(pos).unwrap_or(blocks::AIR), "at {}", pos);
+          };
+        };
+      };
+
+      Ok(())
+    }).unwrap();
+  }
+
+  /// Samples a geometry into sections of a chunk at 0,0, laid out as `ChunkSections::sample` lays out a generator's
+  fn fixture_sections(geometry: &impl MaterialGeometry, min_section: i32, section_count: usize) -> ChunkSections {
+    let mut palette = vec![blocks::AIR];
+    let indices = (0..section_count * SECTION_VOLUME)
+      .map(|i| {
+        let section = min_section + (i / SECTION_VOLUME) as i32;
+        let pos = section_block_pos(i % SECTION_VOLUME) + IVec3::new(0, 0, section * SECTION_HEIGHT);
+        let block = geometry.block_material_at(pos).map_or(blocks::AIR, Cow::into_owned);
+        match palette.iter().position(|found| *found == block) {
+          Some(index) => index as u32,
+          None => {
+            palette.push(block);
+            palette.len() as u32 - 1
+          }
+        }
+      })
+      .collect();
+    ChunkSections { palette, min_section, indices }
+  }
+
+  /// The sections of a column taller than it is wide, which should stand upright in Minecraft,
+  /// with the generator's z on Minecraft's y
+  fn upright_column_sections() -> ChunkSections {
+    let column = SolidBox::new(BoundingBox::new(IVec3::new(2, 5, 0), IVec3::new(3, 5, 39)), blocks::SAND);
+    fixture_sections(&column, 0, 3)
+  }
+
+  /// Whether the upright column covers a position, in Minecraft's coordinates
+  fn is_upright(x: i32, y: i32, z: i32) -> bool {
+    (2..=3).contains(&x) && (0..40).contains(&y) && z == 5
+  }
+
+  /// Every position of the upright column's sections, in Minecraft's coordinates
+  fn upright_positions() -> impl Iterator<Item = (i32, i32, i32)> {
+    (0..16).flat_map(|x| (0..48).flat_map(move |y| (0..16).map(move |z| (x, y, z))))
+  }
+
+  #[test]
+  fn the_vertical_axis_ends_up_on_minecrafts_y_in_memory() {
+    let sections = upright_column_sections();
+    // Sections are indexed as Amulet's arrays are, by x, then y, then z
+    for (x, y, z) in upright_positions() {
+      let index = (y / SECTION_HEIGHT) as usize * SECTION_VOLUME + (x * 256 + y % SECTION_HEIGHT * 16 + z) as usize;
+      assert_eq!(sections.indices[index] != 0, is_upright(x, y, z), "in memory at minecraft {}, {}, {}", x, y, z);
+    };
+  }
+
+  #[test]
+  #[ignore = "needs numpy, which the Amulet stub leaves to the real thing"]
+  fn the_vertical_axis_ends_up_on_minecrafts_y_in_amulet() {
+    let _python = PYTHON.lock().unwrap_or_else(PoisonError::into_inner);
+    Python::with_gil(use_amulet_stub).unwrap();
+    Python::with_gil(|py| -> PyResult<()> {
+      let level = load_level(py, &std::env::temp_dir(), WorldFormat::Java)?;
+      let block_nums = HashMap::from([(blocks::AIR, 0), (blocks::SAND, 1)]);
+      render_chunk(py, level, &block_nums, ChunkPos::new(0, 0), upright_column_sections(), Vec::new())?;
+      let written = level.call_method1("get_chunk", (0, 0, "minecraft:overworld"))?.getattr("blocks")?;
+      for (x, y, z) in upright_positions() {
+        let found = written.get_item((x, y, z))?.extract::<Option<u32>>()?;
+        assert_eq!(found == Some(1), is_upright(x, y, z), "in amulet at minecraft {}, {}, {}", x, y, z);
+      };
+
+      Ok(())
+    }).unwrap();
+  }
+
+  /// Every chest and sign is handed out with the chunk it is in once moved into Minecraft's coordinates,
+  /// with the generator's z on Minecraft's y, and nearly all of them sit on their own blocks
+  #[test]
+  fn block_entities_are_positioned_in_their_chunks() {
+    let generator = &*SEED_ZERO;
+    let (mut ids, mut placed) = (Vec::new(), 0);
+    for chunk_pos in generator.chunks().filter(|&chunk_pos| !generator.is_far_field_chunk(chunk_pos)) {
+      for block_entity in generator.block_entities_in_chunk(chunk_pos) {
+        let pos = block_entity.pos;
+        let (x, y, z) = to_minecraft(pos);
+        assert_eq!((x, y, z), (pos.x, pos.z, pos.y));
+        assert_eq!(from_minecraft(x, y, z), pos);
+        let label = format!("{} at {}", block_entity.id, pos);
+        assert_eq!((x.div_euclid(16), z.div_euclid(16)), chunk_to_minecraft(chunk_pos), "{}", label);
+        assert!((MINECRAFT_MIN_Z..=MINECRAFT_MAX_Z).contains(&y), "{}", label);
+        placed += (generator.block_at(pos).as_ref() == Some(&block_entity.block)) as usize;
+        ids.push(block_entity.id);
+      };
+    };
+
+    assert!(ids.contains(&"minecraft:sign") && ids.contains(&"minecraft:chest"), "found only {:?}", ids);
+    assert!(placed * 2 > ids.len(), "only {} of {} block entities sit on their blocks", placed, ids.len());
+  }
+
+  #[test]
+  #[ignore = "needs numpy, which the Amulet stub leaves to the real thing"]
+  fn dropping_render_workers_stops_them() {
+    let _python = PYTHON.lock().unwrap_or_else(PoisonError::into_inner);
+    Python::with_gil(use_amulet_stub).unwrap();
+    let level_path = scratch_dir("dropped-render-workers");
+    fs::write(level_path.join("level.dat"), "level.dat").unwrap();
+    let workers = RenderWorkers::spawn(&level_path, 2, &BTreeSet::from([blocks::AIR]), true).unwrap();
+    drop(workers);
+    let left = fs::read_dir(&level_path).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+    assert_eq!(left, ["level.dat"]);
+    fs::remove_dir_all(&level_path).unwrap();
+  }
+
+  /// Rendering a split world again clears the parts, keeping what they were asked to keep, and nothing else
+  #[test]
+  fn resetting_split_output_keeps_kept_files() {
+    let level_path = scratch_dir("split-output");
+    let bounding_box = BoundingBox::new(IVec3::new(-100, -100, -64), IVec3::new(100, 100, 100));
+    let (min, max) = bounding_box.to_chunks();
+    let parts = SplitGrid { columns: 2, rows: 1 }.parts(min, max);
+    let keep = [KeepPattern::datapacks()];
+    let reset = || {
+      reset_split_level(&level_path, &parts, bounding_box, WorldHeight::default(), WorldFormat::Java, 0, &keep).unwrap()
+    };
+
+    reset();
+    let part_path = level_path.join(parts[0].name());
+    fs::create_dir_all(part_path.join("region")).unwrap();
+    fs::write(part_path.join("region").join("r.0.0.mca"), "rendered").unwrap();
+    fs::write(part_path.join("datapacks").join("added.zip"), "added").unwrap();
+    fs::write(level_path.join("notes.txt"), "notes").unwrap();
+    reset();
+
+    assert!(!part_path.join("region").exists());
+    assert_eq!(fs::read_to_string(part_path.join("datapacks").join("added.zip")).unwrap(), "added");
+    assert_eq!(fs::read_to_string(level_path.join("notes.txt")).unwrap(), "notes");
+    for part in &parts {
+      let part_path = level_path.join(part.name());
+      assert!(part_path.join("level.dat").is_file(), "{} has no level.dat", part.name());
+      assert!(part_path.join("datapacks").join(WORLD_DATAPACK_FILE_NAME).is_file());
+    };
+
+    let mut left = fs::read_dir(&level_path).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+    left.sort();
+    assert_eq!(left, ["notes.txt", "part_0_0", "part_1_0"]);
+    fs::remove_dir_all(&level_path).unwrap();
+  }
+}
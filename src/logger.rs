@@ -0,0 +1,100 @@
+//! A minimal logger for the `log` facade, with `RUST_LOG`-style per-module filtering
+
+use std::str::FromStr;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+
+
+/// Installs the logger, which can only be done once per process
+pub fn init(filter: LogFilter) -> Result<(), SetLoggerError> {
+  log::set_max_level(filter.max_level());
+  log::set_boxed_logger(Box::new(Logger { filter }))
+}
+
+/// Which messages get logged, as a default level plus overrides for specific modules.
+///
+/// Parsed from a comma separated list such as `warn,glt_mc_generator::generation=debug`,
+/// where a bare level sets the default and `<module>=<level>` applies to that module and its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+  default: LevelFilter,
+  modules: Vec<(String, LevelFilter)>
+}
+
+impl LogFilter {
+  /// The most verbose level enabled anywhere
+  pub fn max_level(&self) -> LevelFilter {
+    self.modules.iter().map(|&(_, level)| level).fold(self.default, Ord::max)
+  }
+
+  /// The level enabled for the given target, using the most specific module which contains it
+  fn level_for(&self, target: &str) -> LevelFilter {
+    self.modules.iter()
+      .filter(|(module, _)| is_within_module(target, module))
+      .max_by_key(|(module, _)| module.len())
+      .map_or(self.default, |&(_, level)| level)
+  }
+}
+
+impl Default for LogFilter {
+  fn default() -> Self {
+    LogFilter { default: LevelFilter::Info, modules: Vec::new() }
+  }
+}
+
+impl FromStr for LogFilter {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, String> {
+    let mut filter = LogFilter { default: LevelFilter::Info, modules: Vec::new() };
+    for directive in s.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+      match directive.split_once('=') {
+        Some((module, level)) => filter.modules.push((module.trim().to_owned(), parse_level(level)?)),
+        None => filter.default = parse_level(directive)?
+      };
+    };
+
+    Ok(filter)
+  }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+  level.trim().parse::<LevelFilter>()
+    .map_err(|_| {
+      format!("unknown log level `{}`, expected one of off, error, warn, info, debug or trace", level.trim())
+    })
+}
+
+fn is_within_module(target: &str, module: &str) -> bool {
+  match target.strip_prefix(module) {
+    Some(rest) => rest.is_empty() || rest.starts_with("::"),
+    None => false
+  }
+}
+
+
+
+/// Writes warnings and errors to stderr, and everything else to stdout
+struct Logger {
+  filter: LogFilter
+}
+
+impl Log for Logger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.filter.level_for(metadata.target())
+  }
+
+  fn log(&self, record: &Record) {
+    if !self.enabled(record.metadata()) { return };
+    match record.level() {
+      Level::Error => eprintln!("error: {}", record.args()),
+      Level::Warn => eprintln!("warning: {}", record.args()),
+      Level::Info => println!("{}", record.args()),
+      Level::Debug => println!("debug: {}", record.args()),
+      Level::Trace => println!("trace [{}]: {}", record.target(), record.args())
+    };
+  }
+
+  fn flush(&self) {}
+}
@@ -0,0 +1,177 @@
+//! Decides which directory a world is rendered into, checking that it can be written to before any work is done
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The environment variable which sets the output directory when `--output` isn't given
+pub const OUTPUT_ENV_VAR: &str = "GLT_OUTPUT";
+/// Read by debug builds only, naming a directory in which the world is rendered into `glt`
+const DEBUG_OUTPUT_LOCATION: &str = "debug-output-location.txt";
+const DEFAULT_OUTPUT: &str = "./output";
+
+
+
+/// Where the path to the output directory came from, in order of precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSource {
+  Flag,
+  Env,
+  DebugFile,
+  Default
+}
+
+impl fmt::Display for OutputSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      OutputSource::Flag => f.write_str("from --output"),
+      OutputSource::Env => write!(f, "from ${}", OUTPUT_ENV_VAR),
+      OutputSource::DebugFile => write!(f, "from {}", DEBUG_OUTPUT_LOCATION),
+      OutputSource::Default => f.write_str("default")
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputPath {
+  /// The output directory, within a parent directory which has been canonicalized
+  pub path: PathBuf,
+  pub source: OutputSource
+}
+
+impl OutputPath {
+  /// Picks the output directory from `--output`, then `$GLT_OUTPUT`, then `debug-output-location.txt` in debug builds,
+  /// then `./output`, and checks that the directory containing it exists and can be written to
+  pub fn resolve(flag: Option<PathBuf>) -> io::Result<Self> {
+    let debug_location = match cfg!(debug_assertions) {
+      true => read_debug_location(Path::new(DEBUG_OUTPUT_LOCATION))?,
+      false => None
+    };
+
+    OutputPath::resolve_from(flag, std::env::var_os(OUTPUT_ENV_VAR), debug_location)
+  }
+
+  /// Picks the output directory from the given sources, where an empty environment variable counts as unset
+  pub fn resolve_from(
+    flag: Option<PathBuf>, env: Option<OsString>, debug_location: Option<PathBuf>
+  ) -> io::Result<Self> {
+    let env = env.filter(|var| !var.is_empty()).map(PathBuf::from);
+    let (path, source) = flag.map(|path| (path, OutputSource::Flag))
+      .or_else(|| env.map(|path| (path, OutputSource::Env)))
+      .or_else(|| debug_location.map(|location| (location.join("glt"), OutputSource::DebugFile)))
+      .unwrap_or_else(|| (PathBuf::from(DEFAULT_OUTPUT), OutputSource::Default));
+    let path = validate_parent(&path).map_err(|err| {
+      io::Error::new(err.kind(), format!("cannot render into {} ({}): {}", path.display(), source, err))
+    })?;
+
+    Ok(OutputPath { path, source })
+  }
+}
+
+/// Reads the directory named by the debug output location file, if there is one.
+/// Surrounding whitespace is ignored,
+/// and a file which is empty or not valid UTF-8 is an error rather than being ignored.
+fn read_debug_location(file: &Path) -> io::Result<Option<PathBuf>> {
+  let contents = match fs::read_to_string(file) {
+    Ok(contents) => contents,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+    Err(err) => return Err(io::Error::new(err.kind(), format!("failed to read {}: {}", file.display(), err)))
+  };
+
+  match contents.trim() {
+    "" => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is empty", file.display()))),
+    location => Ok(Some(PathBuf::from(location)))
+  }
+}
+
+/// Canonicalizes the directory containing the path and checks that it can be written to,
+/// by creating and removing a file in it. Returns the path within the canonicalized directory.
+fn validate_parent(path: &Path) -> io::Result<PathBuf> {
+  let file_name = path.file_name()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the path does not end in a directory name"))?;
+  let parent = match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent,
+    _ => Path::new(".")
+  };
+
+  let parent = parent.canonicalize()
+    .map_err(|err| io::Error::new(err.kind(), format!("{} does not exist: {}", parent.display(), err)))?;
+  if !parent.is_dir() {
+    let message = format!("{} is not a directory", parent.display());
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+  };
+
+  let probe = parent.join(format!(".glt-write-probe-{}", std::process::id()));
+  fs::write(&probe, [])
+    .and_then(|()| fs::remove_file(&probe))
+    .map_err(|err| io::Error::new(err.kind(), format!("{} is not writable: {}", parent.display(), err)))?;
+  Ok(parent.join(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// An empty directory for a test to resolve paths within
+  fn scratch_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("glt-mc-generator-output-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path.canonicalize().unwrap()
+  }
+
+  #[test]
+  fn sources_are_taken_in_order_of_precedence() {
+    let dir = scratch_dir("precedence");
+    let (flag, env, debug) = (dir.join("flag"), dir.join("env"), dir.join("debug"));
+    let resolve = |flag: Option<&PathBuf>, env: Option<&PathBuf>, debug: Option<&PathBuf>| {
+      let env = env.map(|path| path.clone().into_os_string());
+      let resolved = OutputPath::resolve_from(flag.cloned(), env, debug.cloned()).unwrap();
+      (resolved.path, resolved.source)
+    };
+
+    assert_eq!(resolve(Some(&flag), Some(&env), Some(&debug)), (flag.clone(), OutputSource::Flag));
+    assert_eq!(resolve(None, Some(&env), Some(&debug)), (env.clone(), OutputSource::Env));
+    assert_eq!(resolve(None, None, Some(&debug)), (debug.join("glt"), OutputSource::DebugFile));
+    let (path, source) = resolve(None, None, None);
+    assert_eq!((path.file_name(), source), (Some(std::ffi::OsStr::new("output")), OutputSource::Default));
+
+    // An empty environment variable counts as unset
+    let resolved = OutputPath::resolve_from(None, Some(OsString::new()), Some(debug.clone())).unwrap();
+    assert_eq!(resolved.source, OutputSource::DebugFile);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn a_missing_parent_is_reported_with_the_resolved_path() {
+    let dir = scratch_dir("missing-parent");
+    let bogus = dir.join("no-such-directory").join("world");
+    let err = OutputPath::resolve_from(None, Some(bogus.clone().into_os_string()), None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    let message = err.to_string();
+    assert!(message.contains(&bogus.display().to_string()), "{}", message);
+    assert!(message.contains(&OutputSource::Env.to_string()), "{}", message);
+
+    // A parent which is a file rather than a directory is rejected too
+    fs::write(dir.join("file"), []).unwrap();
+    assert!(OutputPath::resolve_from(Some(dir.join("file").join("world")), None, None).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn the_debug_location_file_is_trimmed_and_checked() {
+    let dir = scratch_dir("debug-location");
+    let file = dir.join(DEBUG_OUTPUT_LOCATION);
+    assert_eq!(read_debug_location(&file).unwrap(), None);
+
+    fs::write(&file, "  /some/where  \r\n").unwrap();
+    assert_eq!(read_debug_location(&file).unwrap(), Some(PathBuf::from("/some/where")));
+    fs::write(&file, " \n").unwrap();
+    assert_eq!(read_debug_location(&file).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    fs::write(&file, [0x2f, 0xff, 0xfe]).unwrap();
+    assert_eq!(read_debug_location(&file).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,91 @@
+use crate::utility::crc32;
+
+/// The eight byte signature every PNG file starts with
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// The largest amount of data a single stored deflate block may hold
+const MAX_STORED_BLOCK: usize = 0xffff;
+
+
+
+/// Encodes an 8-bit grayscale image as a PNG.
+/// The image data is stored uncompressed, which is wasteful but needs no dependencies.
+pub fn encode_grayscale(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+  encode(width, height, 0, 1, pixels)
+}
+
+/// Encodes an 8-bit RGB image as a PNG, with each pixel taking up three bytes
+pub fn encode_rgb(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+  encode(width, height, 2, 3, pixels)
+}
+
+fn encode(width: u32, height: u32, color_type: u8, channels: usize, pixels: &[u8]) -> Vec<u8> {
+  let row_len = width as usize * channels;
+  assert_eq!(pixels.len(), row_len * height as usize);
+
+  let mut header = Vec::with_capacity(13);
+  header.extend_from_slice(&width.to_be_bytes());
+  header.extend_from_slice(&height.to_be_bytes());
+  // Bit depth 8, then default compression, filtering and no interlacing
+  header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+  // Every scanline is prefixed with its filter type, which is always 0 (none) here
+  let mut scanlines = Vec::with_capacity(pixels.len() + height as usize);
+  if row_len > 0 {
+    for row in pixels.chunks(row_len) {
+      scanlines.push(0);
+      scanlines.extend_from_slice(row);
+    };
+  };
+
+  let mut out = Vec::new();
+  out.extend_from_slice(&SIGNATURE);
+  write_chunk(&mut out, b"IHDR", &header);
+  write_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines));
+  write_chunk(&mut out, b"IEND", &[]);
+  out
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  let start = out.len();
+  out.extend_from_slice(kind);
+  out.extend_from_slice(data);
+  let crc = crc32(&out[start..]);
+  out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps the data in a zlib stream made up of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+  let block_count = data.len() / MAX_STORED_BLOCK + 1;
+  let mut out = Vec::with_capacity(data.len() + block_count * 5 + 6);
+  // Deflate with a 32K window, no preset dictionary, fastest compression level
+  out.extend_from_slice(&[0x78, 0x01]);
+
+  let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+  if blocks.peek().is_none() {
+    out.extend_from_slice(&[1, 0x00, 0x00, 0xff, 0xff]);
+  };
+
+  while let Some(block) = blocks.next() {
+    let is_final = blocks.peek().is_none();
+    let len = block.len() as u16;
+    out.push(is_final as u8);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&(!len).to_le_bytes());
+    out.extend_from_slice(block);
+  };
+
+  out.extend_from_slice(&adler32(data).to_be_bytes());
+  out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  const MODULUS: u32 = 65521;
+  let (mut a, mut b) = (1u32, 0u32);
+  for &byte in data {
+    a = (a + byte as u32) % MODULUS;
+    b = (b + a) % MODULUS;
+  };
+
+  (b << 16) | a
+}
@@ -0,0 +1,55 @@
+//! Everything needed to build a generator and sample the world it generates, for use from other crates.
+//! The rest of the crate is internal, and may change without notice.
+pub use crate::builder::{BoxedWorld, ConfigError, ConfigProblem, GenerationHooks, GeneratorBuilder};
+pub use crate::generation::chunk_pos::ChunkPos;
+pub use crate::generation::city::{Building, City, Layer};
+pub use crate::generation::intersection::Intersect;
+pub use crate::generation::limit_bounds::LimitBounds;
+pub use crate::generation::transform::{Rotate90, Translate};
+pub use crate::generation::union::Union;
+pub use crate::generation::{Block, BoundingBox, BoxError, ColumnTop, Geometry, MaterialGeometry};
+pub use crate::{Generator, GeneratorOptions, SurfaceInfo, WorldHeight};
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeSet;
+
+  use super::*;
+
+  /// Every name the prelude exports. Adding to or removing from the prelude is a change to the crate's public API,
+  /// so this list has to be changed along with it.
+  const EXPORTS: [&str; 24] = [
+    "Block", "BoundingBox", "BoxError", "BoxedWorld", "Building", "ChunkPos", "City", "ColumnTop", "ConfigError",
+    "ConfigProblem", "GenerationHooks", "Generator", "GeneratorBuilder", "GeneratorOptions", "Geometry", "Intersect",
+    "Layer", "LimitBounds", "MaterialGeometry", "Rotate90", "SurfaceInfo", "Translate", "Union", "WorldHeight"
+  ];
+
+  /// The names exported by the `pub use` items at the top of this file
+  fn exported_names() -> BTreeSet<&'static str> {
+    let source = include_str!("prelude.rs");
+    let items = &source[..source.find("#[cfg(test)]").unwrap()];
+    items.lines()
+      .filter_map(|line| line.strip_prefix("pub use ")?.strip_suffix(';'))
+      .flat_map(|path| {
+        let names = &path[path.rfind("::").map_or(0, |i| i + 2)..];
+        names.trim_start_matches('{').trim_end_matches('}').split(", ")
+      })
+      .collect()
+  }
+
+  #[test]
+  fn the_prelude_exports_exactly_the_listed_names() {
+    let listed = EXPORTS.iter().copied().collect::<BTreeSet<&str>>();
+    let exported = exported_names();
+    assert_eq!(exported.difference(&listed).collect::<Vec<_>>(), Vec::<&&str>::new(), "exported but not listed");
+    assert_eq!(listed.difference(&exported).collect::<Vec<_>>(), Vec::<&&str>::new(), "listed but not exported");
+  }
+
+  #[test]
+  fn generators_can_be_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Generator>();
+    assert_send_sync::<GeneratorBuilder>();
+    assert_send_sync::<BoxedWorld>();
+  }
+}
@@ -0,0 +1,134 @@
+# Renders chunks streamed from glt-mc-generator into a world, as one of several worker processes.
+# Usage: python render_worker.py <scratch world path> [--quiet]
+#
+# Each message on stdin starts with a one byte tag, 0 for the end of the stream, 1 for a chunk, 2 for a far-field
+# chunk, which only holds the bedrock, ocean and outcrops, or 3 for the block census. All integers are little endian,
+# and strings are a u32 byte length followed by UTF-8.
+#
+#   block census: u32 palette length, palette entries (string base blockstate, string extra blockstate or empty)
+#
+#   chunk: i32 chunk x, i32 chunk z,
+#          u32 palette length, palette entries (as above),
+#          u32 section count, sections (i32 section y, 4096 u32 palette indices, ordered by x, y, then z),
+#          u32 block entity count, block entities (string namespace, string base name, i32 x, i32 y, i32 z,
+#          u32 length of uncompressed big endian NBT, NBT)
+#
+#   far-field chunk: i32 chunk x, i32 chunk z,
+#          u32 palette length, palette entries (as above),
+#          u32 section count, sections (as above)
+#
+# The block census is always sent first, and lists every block that any chunk's palette can hold. Sections which are
+# entirely air are left out.
+#
+# Every chunk of a region file is sent to the same worker, so no two workers ever write to the same file. Each worker
+# renders into a scratch world of its own, whose files the generator moves into the real world once every worker is
+# done, so that only one process ever holds the real world's lock or writes its level.dat.
+import logging
+import struct
+import sys
+
+import amulet_nbt
+import numpy
+from amulet.api.block import Block
+from amulet.api.block_entity import BlockEntity
+from amulet.api.level import World
+from amulet.level.formats.anvil_world import AnvilFormat
+
+
+def read_exact(stream, length):
+    data = stream.read(length)
+    if len(data) != length:
+        raise EOFError("the generator closed the stream in the middle of a message")
+    return data
+
+
+def read_u8(stream):
+    return read_exact(stream, 1)[0]
+
+
+def read_i32(stream):
+    return struct.unpack("<i", read_exact(stream, 4))[0]
+
+
+def read_u32(stream):
+    return struct.unpack("<I", read_exact(stream, 4))[0]
+
+
+def read_string(stream):
+    return read_exact(stream, read_u32(stream)).decode("utf-8")
+
+
+def read_census(level, stream):
+    # Every block is registered once, up front, bypassing a performance bottleneck within
+    # Amulet's `BlockManager.get_add_block`
+    census = {}
+    for _ in range(read_u32(stream)):
+        blockstates = (read_string(stream), read_string(stream))
+        block = Block.from_string_blockstate(blockstates[0])
+        if blockstates[1]:
+            block._extra_blocks = (Block.from_string_blockstate(blockstates[1]),)
+        census[blockstates] = level.block_palette.get_add_block(block)
+    return census
+
+
+def read_palette(census, stream):
+    palette = []
+    for _ in range(read_u32(stream)):
+        blockstates = (read_string(stream), read_string(stream))
+        if blockstates not in census:
+            raise KeyError(f"{blockstates[0]} was placed without being in the block census")
+        palette.append(census[blockstates])
+    return palette
+
+
+def read_sections(chunk, stream, palette):
+    palette = numpy.array(palette, dtype=numpy.uint32)
+    for _ in range(read_u32(stream)):
+        cy = read_i32(stream)
+        indices = numpy.frombuffer(read_exact(stream, 4096 * 4), dtype="<u4").reshape((16, 16, 16))
+        chunk.blocks.add_sub_chunk(cy, palette[indices])
+
+
+def render_chunk(level, census, stream):
+    cx, cz = read_i32(stream), read_i32(stream)
+    chunk = level.create_chunk(cx, cz, "minecraft:overworld")
+    read_sections(chunk, stream, read_palette(census, stream))
+
+    for _ in range(read_u32(stream)):
+        namespace, base_name = read_string(stream), read_string(stream)
+        x, y, z = read_i32(stream), read_i32(stream), read_i32(stream)
+        nbt = amulet_nbt.load(read_exact(stream, read_u32(stream)), compressed=False)
+        chunk.block_entities.insert(BlockEntity(namespace, base_name, x, y, z, nbt))
+
+
+def render_far_field_chunk(level, census, stream):
+    cx, cz = read_i32(stream), read_i32(stream)
+    chunk = level.create_chunk(cx, cz, "minecraft:overworld")
+    read_sections(chunk, stream, read_palette(census, stream))
+
+
+def main():
+    level_path = sys.argv[1]
+    if "--quiet" in sys.argv[2:]:
+        logging.disable(logging.WARNING)
+
+    level = World(level_path, AnvilFormat(level_path))
+    stream = sys.stdin.buffer
+    census = {}
+    while True:
+        tag = read_u8(stream)
+        if tag == 1:
+            render_chunk(level, census, stream)
+        elif tag == 2:
+            render_far_field_chunk(level, census, stream)
+        elif tag == 3:
+            census = read_census(level, stream)
+        else:
+            break
+
+    level.save()
+    level.close()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,312 @@
+//! Renders chunks with a pool of Python worker processes, so that Amulet's calls are not serialized on a single GIL.
+//!
+//! Chunks are sampled on the Rust side and streamed to the workers over their stdin in a simple binary format,
+//! described in `render_worker.py`. Each region file is owned by a single worker, which saves it independently.
+//! Every worker is first sent the block census, so that it can register every block with its palette up front.
+//!
+//! Amulet takes a lock on the world it opens and rewrites its level.dat when saving, so rather than every worker
+//! opening the world, each renders into a scratch world of its own inside it. Once every worker has saved,
+//! the files they wrote are moved into the world, which keeps its own level.dat throughout.
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use glam::IVec2;
+
+use crate::chunk_sections::{ChunkSections, SECTION_VOLUME};
+use crate::chunk_stats::ChunkStats;
+use crate::far_field::FarFieldChunk;
+use crate::generation::block_entity::Nbt;
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::{Block, MaterialGeometry};
+use crate::utility::Ignore;
+use crate::world_coords::{chunk_to_minecraft, debug_assert_in_chunk, debug_assert_section_in_world, to_minecraft};
+use crate::Generator;
+
+
+
+const WORKER_SCRIPT: &str = include_str!("render_worker.py");
+/// The name of the scratch world of each worker within the world, followed by the worker's index
+pub const SCRATCH_WORLD_PREFIX: &str = "glt-render-worker-";
+/// The width of the square of chunks stored in each region file
+const REGION_SIZE: i32 = 32;
+
+/// A chunk sampled and encoded as a message for a render worker
+#[derive(Debug, Clone)]
+pub struct EncodedChunk {
+  pub pos: ChunkPos,
+  /// Whether the chunk was sampled as a far-field chunk, see `Generator::is_far_field_chunk`
+  pub far_field: bool,
+  data: Vec<u8>
+}
+
+impl EncodedChunk {
+  /// Samples every block and block entity in a chunk, collecting its statistics along the way.
+  /// Blocks are sent whole sections at a time, with far-field chunks using the far-field palette.
+  pub fn sample(generator: &Generator, chunk_pos: ChunkPos) -> (Self, ChunkStats) {
+    if generator.is_far_field_chunk(chunk_pos) {
+      let (far_field_chunk, stats) = FarFieldChunk::sample(generator, chunk_pos);
+      return (EncodedChunk::encode_far_field(&far_field_chunk, generator.far_field_palette()), stats);
+    };
+
+    let context = generator.chunk_context(chunk_pos);
+    let (chunk_sections, stats) = ChunkSections::sample(generator, &context, chunk_pos);
+    let mut data = vec![1];
+    put_chunk_pos(&mut data, chunk_pos);
+    put_palette(&mut data, chunk_sections.palette.iter());
+    put_sections(&mut data, chunk_sections.sections().collect());
+
+    // Only attach block entities whose blocks weren't covered up by some other feature
+    let block_entities = generator.block_entities_in_chunk(chunk_pos).into_iter()
+      .filter(|block_entity| context.block_material_at(block_entity.pos).as_deref() == Some(&block_entity.block))
+      .collect::<Vec<_>>();
+    put_u32(&mut data, block_entities.len() as u32);
+    for block_entity in block_entities {
+      let (namespace, base_name) = block_entity.split_id();
+      put_string(&mut data, namespace);
+      put_string(&mut data, base_name);
+      let (x, y, z) = to_minecraft(block_entity.pos);
+      debug_assert_in_chunk(chunk_pos, (x, y, z));
+      for coordinate in [x, y, z] {
+        put_i32(&mut data, coordinate);
+      };
+
+      let mut nbt = Vec::new();
+      Nbt::Compound(block_entity.nbt).write_binary(&mut nbt);
+      put_u32(&mut data, nbt.len() as u32);
+      data.extend_from_slice(&nbt);
+    };
+
+    (EncodedChunk { pos: chunk_pos, far_field: false, data }, stats)
+  }
+
+  fn encode_far_field(chunk: &FarFieldChunk, palette: &[Block]) -> Self {
+    let mut data = vec![2];
+    put_chunk_pos(&mut data, chunk.pos);
+    put_palette(&mut data, palette.iter());
+    put_sections(&mut data, chunk.sections().collect());
+    EncodedChunk { pos: chunk.pos, far_field: true, data }
+  }
+}
+
+/// A pool of Python processes, each rendering the chunks of the regions assigned to it
+pub struct RenderWorkers {
+  level_path: PathBuf,
+  workers: Vec<Worker>,
+  /// The worker that each region has been assigned to
+  regions: HashMap<IVec2, usize>
+}
+
+impl RenderWorkers {
+  /// Starts `count` workers rendering into the world at the given path, sending each of them the block census.
+  /// Workers are run with the interpreter named by the `PYTHON` environment variable, or `python3` by default.
+  /// The world must already have its level.dat, which is copied into each worker's scratch world.
+  pub fn spawn(level_path: &Path, count: usize, census: &BTreeSet<Block>, quiet: bool) -> io::Result<Self> {
+    let mut census_data = vec![3];
+    put_palette(&mut census_data, census.iter());
+    let python = std::env::var_os("PYTHON").unwrap_or_else(|| OsString::from("python3"));
+    let mut render_workers = RenderWorkers {
+      level_path: level_path.to_owned(),
+      workers: Vec::with_capacity(count),
+      regions: HashMap::new()
+    };
+    // Any workers already started are stopped when `render_workers` is dropped, if a later one fails to start
+    for index in 0..count {
+      let scratch_path = level_path.join(format!("{}{}", SCRATCH_WORLD_PREFIX, index));
+      fs::remove_dir_all(&scratch_path).ignore_err(io::ErrorKind::NotFound)?;
+      fs::create_dir(&scratch_path)?;
+      fs::copy(level_path.join("level.dat"), scratch_path.join("level.dat"))?;
+
+      let mut command = Command::new(&python);
+      command.arg("-c").arg(WORKER_SCRIPT).arg(&scratch_path).stdin(Stdio::piped());
+      if quiet {
+        command.arg("--quiet");
+      };
+
+      let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+          fs::remove_dir_all(&scratch_path)?;
+          return Err(err);
+        }
+      };
+      let stdin = BufWriter::new(child.stdin.take().expect("unreachable"));
+      render_workers.workers.push(Worker { child, stdin, scratch_path, chunk_count: 0 });
+      render_workers.workers.last_mut().unwrap().stdin.write_all(&census_data)?;
+    };
+
+    Ok(render_workers)
+  }
+
+  /// Sends a chunk to the worker that owns its region.
+  /// Regions are assigned as they are first encountered, to whichever worker has been sent the fewest chunks.
+  pub fn send(&mut self, chunk: &EncodedChunk) -> io::Result<()> {
+    let region = IVec2::new(chunk.pos.0.x.div_euclid(REGION_SIZE), chunk.pos.0.y.div_euclid(REGION_SIZE));
+    let workers = &self.workers;
+    let index = *self.regions.entry(region).or_insert_with(|| {
+      (0..workers.len()).min_by_key(|&i| workers[i].chunk_count).expect("there must be at least one worker")
+    });
+
+    let worker = &mut self.workers[index];
+    worker.chunk_count += 1;
+    worker.stdin.write_all(&chunk.data)
+      .map_err(|err| io::Error::new(err.kind(), format!("render worker {} stopped unexpectedly: {}", index, err)))
+  }
+
+  /// Tells every worker that there are no more chunks, then waits for them to save their regions,
+  /// moving what they saved into the world once every one of them has succeeded
+  pub fn finish(mut self) -> io::Result<()> {
+    let mut result = Ok(());
+    let workers = std::mem::take(&mut self.workers);
+    let mut scratch_paths = Vec::with_capacity(workers.len());
+    for (index, mut worker) in workers.into_iter().enumerate() {
+      // Dropping the pipe closes it, in case the worker is waiting for more input
+      let sent = worker.stdin.write_all(&[0]).and_then(|()| worker.stdin.flush());
+      drop(worker.stdin);
+      let status = worker.child.wait();
+      scratch_paths.push(worker.scratch_path);
+      if result.is_ok() {
+        result = match (sent, status) {
+          (_, Err(err)) => Err(err),
+          (_, Ok(status)) if !status.success() => {
+            Err(io::Error::other(format!("render worker {} failed ({})", index, status)))
+          },
+          (Err(err), Ok(_)) => Err(err),
+          (Ok(()), Ok(_)) => Ok(())
+        };
+      };
+    };
+
+    for scratch_path in scratch_paths {
+      if result.is_ok() {
+        result = merge_scratch_world(&scratch_path, &self.level_path);
+      };
+
+      fs::remove_dir_all(&scratch_path).ignore_err(io::ErrorKind::NotFound)?;
+    };
+
+    result
+  }
+}
+
+impl Drop for RenderWorkers {
+  /// Stops any workers which were never finished, such as when rendering is cut short by an error,
+  /// so that they don't outlive the generator
+  fn drop(&mut self) {
+    for mut worker in self.workers.drain(..) {
+      let _ = worker.child.kill();
+      let _ = worker.child.wait();
+      let _ = fs::remove_dir_all(&worker.scratch_path);
+    };
+  }
+}
+
+struct Worker {
+  child: Child,
+  stdin: BufWriter<ChildStdin>,
+  /// The world that this worker renders into, within the real world
+  scratch_path: PathBuf,
+  chunk_count: usize
+}
+
+/// Moves every file within the directories of a worker's scratch world, such as its region files, into the same
+/// place in the world. The files directly within the scratch world, like its level.dat, are left behind.
+/// Each region file is only ever written by one worker, so no worker's files replace another's.
+fn merge_scratch_world(scratch_path: &Path, level_path: &Path) -> io::Result<()> {
+  for entry in fs::read_dir(scratch_path)? {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      move_dir_contents(&entry.path(), &level_path.join(entry.file_name()))?;
+    };
+  };
+
+  Ok(())
+}
+
+fn move_dir_contents(from: &Path, to: &Path) -> io::Result<()> {
+  fs::create_dir_all(to)?;
+  for entry in fs::read_dir(from)? {
+    let entry = entry?;
+    let to = to.join(entry.file_name());
+    if entry.file_type()?.is_dir() {
+      move_dir_contents(&entry.path(), &to)?;
+    } else {
+      fs::rename(entry.path(), to)?;
+    };
+  };
+
+  Ok(())
+}
+
+fn put_i32(data: &mut Vec<u8>, value: i32) {
+  data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+  data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_palette<'a>(data: &mut Vec<u8>, palette: impl ExactSizeIterator<Item = &'a Block>) {
+  put_u32(data, palette.len() as u32);
+  for block in palette {
+    put_string(data, block.base_blockstate());
+    put_string(data, block.extra_blockstate().unwrap_or(""));
+  };
+}
+
+/// Puts each section's index followed by its indices, skipping sections the iterator skips
+fn put_chunk_pos(data: &mut Vec<u8>, chunk_pos: ChunkPos) {
+  let (cx, cz) = chunk_to_minecraft(chunk_pos);
+  put_i32(data, cx);
+  put_i32(data, cz);
+}
+
+fn put_sections(data: &mut Vec<u8>, sections: Vec<(i32, &[u32])>) {
+  put_u32(data, sections.len() as u32);
+  data.reserve(sections.len() * (SECTION_VOLUME * 4 + 4));
+  for (section, indices) in sections {
+    debug_assert_section_in_world(section);
+    put_i32(data, section);
+    for &index in indices {
+      put_u32(data, index);
+    };
+  };
+}
+
+fn put_string(data: &mut Vec<u8>, value: &str) {
+  put_u32(data, value.len() as u32);
+  data.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn scratch_worlds_are_merged_without_their_level_dat() {
+    let root = std::env::temp_dir().join(format!("glt-mc-generator-merge-{}", std::process::id()));
+    let (level_path, scratch_path) = (root.join("world"), root.join("world").join("glt-render-worker-0"));
+    fs::remove_dir_all(&root).ignore_err(io::ErrorKind::NotFound).unwrap();
+    for dir in [level_path.join("region"), scratch_path.join("region"), scratch_path.join("entities")] {
+      fs::create_dir_all(dir).unwrap();
+    };
+    fs::write(level_path.join("level.dat"), "world").unwrap();
+    fs::write(level_path.join("region").join("r.1.0.mca"), "kept").unwrap();
+    fs::write(scratch_path.join("level.dat"), "scratch").unwrap();
+    fs::write(scratch_path.join("session.lock"), "scratch").unwrap();
+    fs::write(scratch_path.join("region").join("r.0.0.mca"), "rendered").unwrap();
+    fs::write(scratch_path.join("entities").join("r.0.0.mca"), "rendered").unwrap();
+
+    merge_scratch_world(&scratch_path, &level_path).unwrap();
+    let read = |path: &[&str]| fs::read_to_string(path.iter().fold(level_path.clone(), |path, part| path.join(part)));
+    assert_eq!(read(&["level.dat"]).unwrap(), "world");
+    assert!(read(&["session.lock"]).is_err());
+    assert_eq!(read(&["region", "r.0.0.mca"]).unwrap(), "rendered");
+    assert_eq!(read(&["region", "r.1.0.mca"]).unwrap(), "kept");
+    assert_eq!(read(&["entities", "r.0.0.mca"]).unwrap(), "rendered");
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
@@ -0,0 +1,190 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::chunk_stats::ChunkStats;
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::BoundingBox;
+use crate::heightmap::Heightmap;
+use crate::world_coords::chunk_to_minecraft;
+
+/// How long the server thread sleeps between polls when it has nothing to do
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+/// How long a client has to send its request before being dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+
+#[derive(Debug)]
+enum ServerEvent {
+  ChunkStarted(ChunkPos),
+  ChunkFinished(ChunkPos, Vec<Option<i32>>),
+  Finished
+}
+
+/// A small blocking HTTP server which reports on rendering while it is in progress.
+/// Serves `/status` (progress as JSON) and `/map.png` (a heightmap of the chunks rendered so far).
+#[derive(Debug)]
+pub struct PreviewServer {
+  sender: Sender<ServerEvent>,
+  handle: JoinHandle<()>
+}
+
+impl PreviewServer {
+  /// Binds to the given address and starts serving from a background thread.
+  /// The bounding box determines the extent of the preview image and the range of its shading.
+  pub fn start(addr: SocketAddr, bounding_box: BoundingBox, chunk_count: usize) -> io::Result<Self> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let (sender, receiver) = mpsc::channel();
+    let state = PreviewState::new(bounding_box, chunk_count);
+    let handle = thread::Builder::new()
+      .name("preview-server".to_owned())
+      .spawn(move || serve(listener, receiver, state))?;
+    Ok(PreviewServer { sender, handle })
+  }
+
+  pub fn chunk_started(&self, chunk: ChunkPos) {
+    // The server thread only hangs up if it panicked, which `finish` reports
+    let _ = self.sender.send(ServerEvent::ChunkStarted(chunk));
+  }
+
+  pub fn chunk_finished(&self, stats: &ChunkStats) {
+    let _ = self.sender.send(ServerEvent::ChunkFinished(stats.chunk, stats.heightmap.clone()));
+  }
+
+  /// Stops the server, waiting for any request currently being answered
+  pub fn finish(self) {
+    let _ = self.sender.send(ServerEvent::Finished);
+    if self.handle.join().is_err() {
+      log::warn!("the preview server panicked");
+    };
+  }
+}
+
+fn serve(listener: TcpListener, receiver: Receiver<ServerEvent>, mut state: PreviewState) {
+  loop {
+    loop {
+      match receiver.try_recv() {
+        Ok(ServerEvent::Finished) | Err(TryRecvError::Disconnected) => return,
+        Ok(event) => state.apply(event),
+        Err(TryRecvError::Empty) => break
+      };
+    };
+
+    match listener.accept() {
+      Ok((stream, _)) => if let Err(err) = respond(stream, &state) {
+        log::warn!("failed to answer a preview request: {}", err);
+      },
+      Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+      Err(err) => log::warn!("failed to accept a preview connection: {}", err)
+    };
+  };
+}
+
+fn respond(stream: TcpStream, state: &PreviewState) -> io::Result<()> {
+  // Accepted streams inherit non-blocking mode from the listener on some platforms
+  stream.set_nonblocking(false)?;
+  stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+  let mut reader = BufReader::new(stream);
+  let mut request_line = String::new();
+  reader.read_line(&mut request_line)?;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+      break;
+    };
+  };
+
+  let mut stream = reader.into_inner();
+  let mut parts = request_line.split_whitespace();
+  let method = parts.next();
+  let path = parts.next().map(|path| path.split('?').next().unwrap_or(path));
+  match (method, path) {
+    (Some("GET"), Some("/status")) => {
+      write_response(&mut stream, "200 OK", "application/json", state.to_status_json().as_bytes())
+    },
+    (Some("GET"), Some("/map.png")) => {
+      write_response(&mut stream, "200 OK", "image/png", &state.heightmap.to_png())
+    },
+    (Some("GET"), _) => {
+      write_response(&mut stream, "404 Not Found", "text/plain", b"not found")
+    },
+    _ => {
+      write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"method not allowed")
+    }
+  }
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+  write!(
+    stream,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+    status, content_type, body.len()
+  )?;
+  stream.write_all(body)?;
+  stream.flush()
+}
+
+
+
+#[derive(Debug)]
+struct PreviewState {
+  chunk_count: usize,
+  chunks_done: usize,
+  current_chunk: Option<ChunkPos>,
+  started: Instant,
+  heightmap: Heightmap
+}
+
+impl PreviewState {
+  fn new(bounding_box: BoundingBox, chunk_count: usize) -> Self {
+    PreviewState {
+      chunk_count,
+      chunks_done: 0,
+      current_chunk: None,
+      started: Instant::now(),
+      heightmap: Heightmap::new(bounding_box)
+    }
+  }
+
+  fn apply(&mut self, event: ServerEvent) {
+    match event {
+      ServerEvent::ChunkStarted(chunk) => {
+        self.current_chunk = Some(chunk);
+      },
+      ServerEvent::ChunkFinished(chunk, heights) => {
+        self.chunks_done += 1;
+        self.heightmap.insert(chunk, heights);
+      },
+      ServerEvent::Finished => ()
+    };
+  }
+
+  fn to_status_json(&self) -> String {
+    let elapsed = self.started.elapsed().as_secs_f64();
+    let eta = match self.chunks_done {
+      0 => "null".to_owned(),
+      done => {
+        let remaining = self.chunk_count.saturating_sub(done);
+        format!("{:.1}", elapsed / done as f64 * remaining as f64)
+      }
+    };
+
+    let current_chunk = match self.current_chunk {
+      Some(chunk) => {
+        let (cx, cz) = chunk_to_minecraft(chunk);
+        format!("{{\"x\":{},\"z\":{}}}", cx, cz)
+      },
+      None => "null".to_owned()
+    };
+
+    format!(
+      "{{\"chunks_done\":{},\"chunks_total\":{},\"current_chunk\":{},\"elapsed_secs\":{:.1},\"eta_secs\":{}}}",
+      self.chunks_done, self.chunk_count, current_chunk, elapsed, eta
+    )
+  }
+}
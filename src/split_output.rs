@@ -0,0 +1,227 @@
+//! Splits the rendered world into a grid of smaller worlds, one for each area of the city, so that each of them
+//! can be hosted by a different server. Every chunk goes to exactly one part, so the parts fit back together
+//! into the world that would have been rendered whole, and a manifest records where each of them came from.
+use glam::IVec2;
+
+use crate::generation::chunk_pos::ChunkPos;
+use crate::generation::BoundingBox;
+use crate::utility::json_string;
+use crate::world_coords::column_to_minecraft;
+
+
+
+/// The name of the manifest written alongside the parts
+pub const MANIFEST_FILE_NAME: &str = "parts.json";
+/// The start of the name of every part's directory
+const PART_PREFIX: &str = "part_";
+
+/// The number of parts to split the world into along the x and y axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitGrid {
+  pub columns: u32,
+  pub rows: u32
+}
+
+impl SplitGrid {
+  /// Divides the chunks in the given range (inclusive) into the parts of this grid, as evenly as possible.
+  /// If the grid has more columns or rows than there are chunks, the extra parts would be empty and are left out.
+  pub fn parts(&self, min: ChunkPos, max: ChunkPos) -> Vec<OutputPart> {
+    let xs = split_range(min.0.x, max.0.x, self.columns);
+    let ys = split_range(min.0.y, max.0.y, self.rows);
+    ys.iter().enumerate()
+      .flat_map(|(row, &(min_y, max_y))| {
+        xs.iter().enumerate().map(move |(column, &(min_x, max_x))| OutputPart {
+          cell: IVec2::new(column as i32, row as i32),
+          min: ChunkPos::new(min_x, min_y),
+          max: ChunkPos::new(max_x, max_y)
+        })
+      })
+      .collect()
+  }
+}
+
+/// Splits a range of chunk coordinates (inclusive) into `count` consecutive ranges of nearly equal length,
+/// leaving out any which would be empty
+fn split_range(min: i32, max: i32, count: u32) -> Vec<(i32, i32)> {
+  let len = (max - min + 1) as i64;
+  let boundary = |i: u32| min + (len * i as i64 / count as i64) as i32;
+  (0..count)
+    .map(|i| (boundary(i), boundary(i + 1) - 1))
+    .filter(|&(start, end)| start <= end)
+    .collect()
+}
+
+/// One of the worlds that the output is split into, covering a rectangle of whole chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputPart {
+  /// The column and row of this part in the grid
+  pub cell: IVec2,
+  pub min: ChunkPos,
+  pub max: ChunkPos
+}
+
+impl OutputPart {
+  /// The name of the directory this part's world is rendered into
+  pub fn name(&self) -> String {
+    format!("{}{}_{}", PART_PREFIX, self.cell.x, self.cell.y)
+  }
+
+  /// Iterates through every chunk of this part, in rings expanding out from chunk 0,0
+  pub fn chunks(&self) -> impl ExactSizeIterator<Item = ChunkPos> {
+    ChunkPos::rings_in_range(self.min, self.max)
+  }
+
+  /// The part of the given bounding box within this part's chunks, which sizes its world border and places its spawn
+  pub fn clip(&self, bounding_box: BoundingBox) -> BoundingBox {
+    let min = bounding_box.min.truncate().max(self.min.min_block());
+    let max = bounding_box.max.truncate().min(self.max.max_block());
+    BoundingBox::try_new(min.extend(bounding_box.min.z), max.extend(bounding_box.max.z)).unwrap()
+  }
+}
+
+/// Whether a directory is named like the world of a part, of this split or an earlier one
+pub fn is_part_name(name: &str) -> bool {
+  name.strip_prefix(PART_PREFIX)
+    .and_then(|cell| cell.split_once('_'))
+    .is_some_and(|(column, row)| column.parse::<u32>().is_ok() && row.parse::<u32>().is_ok())
+}
+
+/// A JSON document listing each part with the rectangle of blocks it covers, in Minecraft's x and z coordinates,
+/// for whatever stitches the worlds back together
+pub fn manifest_json(seed: u64, grid: SplitGrid, parts: &[OutputPart]) -> String {
+  let parts = parts.iter()
+    .map(|part| {
+      let (min_x, min_z) = column_to_minecraft(part.min.min_block());
+      let (max_x, max_z) = column_to_minecraft(part.max.max_block());
+      format!(
+        "    {{\"name\": {}, \"column\": {}, \"row\": {}, \"min_x\": {}, \"min_z\": {}, \"max_x\": {}, \"max_z\": {}}}",
+        json_string(&part.name()), part.cell.x, part.cell.y, min_x, min_z, max_x, max_z
+      )
+    })
+    .collect::<Vec<String>>();
+  format!(
+    "{{\n  \"seed\": {},\n  \"columns\": {},\n  \"rows\": {},\n  \"parts\": [\n{}\n  ]\n}}\n",
+    seed, grid.columns, grid.rows, parts.join(",\n")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use glam::IVec3;
+
+  use super::*;
+
+  const GRIDS: [SplitGrid; 5] = [
+    SplitGrid { columns: 1, rows: 1 },
+    SplitGrid { columns: 2, rows: 1 },
+    SplitGrid { columns: 2, rows: 3 },
+    SplitGrid { columns: 4, rows: 4 },
+    SplitGrid { columns: 20, rows: 2 }
+  ];
+
+  fn test_ranges() -> impl Iterator<Item = (ChunkPos, ChunkPos)> {
+    [([0, 0], [0, 0]), ([-7, -7], [6, 6]), ([-5, -2], [1, 7]), ([4, 2], [9, 6]), ([-12, -9], [-7, -8])]
+      .into_iter()
+      .map(|(min, max)| (ChunkPos(IVec2::from(min)), ChunkPos(IVec2::from(max))))
+  }
+
+  #[test]
+  fn the_parts_cover_every_chunk_exactly_once() {
+    for grid in GRIDS {
+      for (min, max) in test_ranges() {
+        let expected = ChunkPos::rings_in_range(min, max).collect::<HashSet<ChunkPos>>();
+        let mut covered = HashSet::new();
+        for part in grid.parts(min, max) {
+          for chunk in part.chunks() {
+            assert!(covered.insert(chunk), "{:?}: chunk {:?} is in two parts of {:?} to {:?}", grid, chunk, min, max);
+          };
+        };
+
+        assert_eq!(covered, expected, "{:?}: chunks of {:?} to {:?}", grid, min, max);
+      };
+    };
+  }
+
+  #[test]
+  fn the_parts_are_laid_out_in_their_cells_without_empty_ones() {
+    let (min, max) = (ChunkPos::new(-5, -2), ChunkPos::new(1, 7));
+    let parts = SplitGrid { columns: 2, rows: 3 }.parts(min, max);
+    assert_eq!(parts.len(), 6);
+    for part in &parts {
+      assert!(part.min.0.cmple(part.max.0).all(), "{} is empty", part.name());
+      let width = part.max.0 - part.min.0 + IVec2::ONE;
+      assert!((3..=4).contains(&width.x) && (3..=4).contains(&width.y), "{} is {} chunks", part.name(), width);
+      for other in &parts {
+        if other.cell.x < part.cell.x && other.cell.y == part.cell.y {
+          assert!(other.max.0.x < part.min.0.x, "{} is not left of {}", other.name(), part.name());
+        };
+        if other.cell.y < part.cell.y && other.cell.x == part.cell.x {
+          assert!(other.max.0.y < part.min.0.y, "{} is not before {}", other.name(), part.name());
+        };
+      };
+    };
+
+    // Seven columns of chunks can only fill seven of twenty columns of parts
+    let parts = SplitGrid { columns: 20, rows: 1 }.parts(min, max);
+    assert_eq!(parts.len(), 7);
+    assert!(parts.iter().all(|part| part.min.0.x == part.max.0.x));
+  }
+
+  #[test]
+  fn split_ranges_are_consecutive_and_nearly_equal() {
+    for (min, max, count) in [(0, 0, 1), (-10, 10, 3), (-3, 4, 8), (5, 7, 5), (-100, -1, 7)] {
+      let ranges = split_range(min, max, count);
+      assert_eq!(ranges.first().unwrap().0, min, "{}..={} into {}", min, max, count);
+      assert_eq!(ranges.last().unwrap().1, max, "{}..={} into {}", min, max, count);
+      for pair in ranges.windows(2) {
+        assert_eq!(pair[0].1 + 1, pair[1].0, "{}..={} into {}", min, max, count);
+      };
+
+      let lengths = ranges.iter().map(|&(start, end)| end - start + 1).collect::<Vec<i32>>();
+      let (shortest, longest) = (*lengths.iter().min().unwrap(), *lengths.iter().max().unwrap());
+      assert!(shortest >= 1 && longest - shortest <= 1, "{}..={} into {}: {:?}", min, max, count, ranges);
+    };
+  }
+
+  #[test]
+  fn clipping_keeps_the_bounding_box_within_the_part() {
+    let bounding_box = BoundingBox::new(IVec3::new(-100, -70, -64), IVec3::new(90, 120, 100));
+    let (min, max) = bounding_box.to_chunks();
+    for part in SplitGrid { columns: 3, rows: 2 }.parts(min, max) {
+      let clipped = part.clip(bounding_box);
+      assert!(clipped.min.truncate().cmpge(part.min.min_block()).all(), "{}", part.name());
+      assert!(clipped.max.truncate().cmple(part.max.max_block()).all(), "{}", part.name());
+      assert!(clipped.min.truncate().cmpge(bounding_box.min.truncate()).all(), "{}", part.name());
+      assert!(clipped.max.truncate().cmple(bounding_box.max.truncate()).all(), "{}", part.name());
+      assert_eq!((clipped.min.z, clipped.max.z), (bounding_box.min.z, bounding_box.max.z), "{}", part.name());
+    };
+  }
+
+  #[test]
+  fn only_part_directories_are_taken_for_parts() {
+    for name in ["part_0_0", "part_3_12"] {
+      assert!(is_part_name(name), "{}", name);
+    };
+    for name in ["part_", "part_0", "part_0_", "part_-1_0", "part_a_0", "parts_0_0", "level.dat", "datapacks"] {
+      assert!(!is_part_name(name), "{}", name);
+    };
+  }
+
+  #[test]
+  fn the_manifest_lists_every_part_with_its_blocks() {
+    let grid = SplitGrid { columns: 2, rows: 1 };
+    let parts = grid.parts(ChunkPos::new(-2, -1), ChunkPos::new(1, 0));
+    let manifest = manifest_json(7, grid, &parts);
+    assert!(manifest.contains("\"seed\": 7,") && manifest.contains("\"columns\": 2,"), "{}", manifest);
+    let expected = [("part_0_0", 0, -32, -1), ("part_1_0", 1, 0, 31)];
+    for (name, column, min_x, max_x) in expected {
+      let part = format!(
+        "{{\"name\": \"{}\", \"column\": {}, \"row\": 0, \"min_x\": {}, \"min_z\": -16, \"max_x\": {}, \"max_z\": 15}}",
+        name, column, min_x, max_x
+      );
+      assert!(manifest.contains(&part), "{} is not in {}", part, manifest);
+    };
+  }
+}
@@ -1,6 +1,6 @@
 use std::io;
 
-use glam::IVec2;
+use glam::{IVec2, IVec3};
 
 #[macro_export]
 macro_rules! ret_if_some {
@@ -65,15 +65,358 @@ impl Ignore for io::Result<()> {
 
 
 
-/// Creates a list of points along a square ring of a certain radius
-pub fn ring(radius: usize) -> Vec<IVec2> {
-  if radius == 0 { return vec![IVec2::ZERO] };
-  let r = radius as i32;
-  let mut pos = IVec2::new(-r, -r);
-  let mut out = Vec::with_capacity(radius * 8);
-  while pos.x < r { pos.x += 1; out.push(pos); };
-  while pos.y < r { pos.y += 1; out.push(pos); };
-  while pos.x > -r { pos.x -= 1; out.push(pos); };
-  while pos.y > -r { pos.y -= 1; out.push(pos); };
+/// Lazily iterates through every point in the given box (inclusive), in square rings expanding out from 0,0.
+/// Every ring between the nearest and furthest rings intersects the box, so no empty rings are visited.
+pub fn rings_in_box(min: IVec2, max: IVec2) -> impl ExactSizeIterator<Item = IVec2> {
+  let (len, min_radius, max_radius) = if min.cmple(max).all() {
+    // Computed in 64 bits, since boxes near the limits of `i32` would overflow otherwise,
+    // and the ring through `i32::MIN` has a radius one past `i32::MAX`
+    let (min64, max64) = ([min.x as i64, min.y as i64], [max.x as i64, max.y as i64]);
+    let size = [max64[0] - min64[0] + 1, max64[1] - min64[1] + 1];
+    let len = (size[0] as u64).saturating_mul(size[1] as u64).try_into().unwrap_or(usize::MAX);
+    let nearest = (0..2).map(|i| min64[i].max(-max64[i]).max(0)).max().unwrap();
+    let furthest = (0..2).map(|i| min64[i].abs().max(max64[i].abs())).max().unwrap();
+    (len, nearest, furthest)
+  } else {
+    (0, 1, 0)
+  };
+
+  let iter = (min_radius..=max_radius)
+    .flat_map(ring_sides)
+    .flat_map(move |(start, step, len)| clip_ring_side(start, step, len, min, max));
+  WithLen { iter, len }
+}
+
+/// The four sides of a square ring, as a start point, a step direction and a length.
+/// Computed in 64 bits, since the rings around boxes near the limits of `i32` reach past them.
+fn ring_sides(radius: i64) -> Vec<([i64; 2], IVec2, i64)> {
+  if radius == 0 { return vec![([0, 0], IVec2::X, 1)] };
+  let (r, len) = (radius, radius * 2);
+  vec![
+    ([-r + 1, -r], IVec2::X, len),
+    ([r, -r + 1], IVec2::Y, len),
+    ([r - 1, r], -IVec2::X, len),
+    ([-r, r - 1], -IVec2::Y, len)
+  ]
+}
+
+fn clip_ring_side(start: [i64; 2], step: IVec2, len: i64, min: IVec2, max: IVec2) -> impl Iterator<Item = IVec2> {
+  let (mut t_min, mut t_max) = (0, len - 1);
+  for axis in 0..2 {
+    let (s, lo, hi) = (start[axis], min[axis] as i64, max[axis] as i64);
+    match step[axis] {
+      1 => { t_min = t_min.max(lo - s); t_max = t_max.min(hi - s); },
+      -1 => { t_min = t_min.max(s - hi); t_max = t_max.min(s - lo); },
+      _ => if s < lo || s > hi { t_max = -1 }
+    };
+  };
+
+  // Every point left lies within the box, so fits in an `i32`
+  (t_min..=t_max).map(move |t| {
+    IVec2::new((start[0] + step.x as i64 * t) as i32, (start[1] + step.y as i64 * t) as i32)
+  })
+}
+
+/// An iterator with a length which is known ahead of time
+struct WithLen<I> {
+  iter: I,
+  len: usize
+}
+
+impl<I: Iterator> Iterator for WithLen<I> {
+  type Item = I::Item;
+
+  fn next(&mut self) -> Option<I::Item> {
+    let item = self.iter.next()?;
+    self.len -= 1;
+    Some(item)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<I: Iterator> ExactSizeIterator for WithLen<I> {}
+
+
+
+/// The finalizer from SplitMix64, scrambles the bits of a value
+#[inline]
+pub fn mix64(mut x: u64) -> u64 {
+  x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+  x ^ (x >> 31)
+}
+
+/// Deterministically hashes a seed and a 2D position into a pseudorandom value
+#[inline]
+pub fn hash2(seed: u64, pos: IVec2) -> u64 {
+  let h = mix64(seed ^ 0x9e3779b97f4a7c15);
+  let h = mix64(h ^ pos.x as u32 as u64);
+  mix64(h ^ pos.y as u32 as u64)
+}
+
+/// Deterministically hashes a seed and a 3D position into a pseudorandom value
+#[inline]
+pub fn hash3(seed: u64, pos: IVec3) -> u64 {
+  mix64(hash2(seed, pos.truncate()) ^ pos.z as u32 as u64)
+}
+
+/// Converts a hash into a float in the range `[0, 1)`
+#[inline]
+pub fn hash_to_unit(hash: u64) -> f64 {
+  (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Picks one of the given values, each with a chance proportional to its weight, by a roll such as a hash which is
+/// spread evenly over a range far larger than the total weight. Returns `None` if every weight is zero.
+pub fn weighted_pick<T>(roll: u64, entries: &[(T, u32)]) -> Option<&T> {
+  let total = entries.iter().map(|&(_, weight)| weight as u64).sum::<u64>();
+  if total == 0 { return None };
+  let mut roll = roll % total;
+  for (value, weight) in entries {
+    if roll < *weight as u64 { return Some(value) };
+    roll -= *weight as u64;
+  };
+
+  unreachable!()
+}
+
+/// The CRC-32 checksum used by both PNG and ZIP files
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = 0xffffffffu32;
+  for &byte in data {
+    crc ^= byte as u32;
+    for _ in 0..8 {
+      crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+    };
+  };
+
+  !crc
+}
+
+/// Quotes a string for JSON, escaping quotes, backslashes and control characters
+pub fn json_string(s: &str) -> String {
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c)
+    };
+  };
+
+  out.push('"');
   out
 }
+
+
+
+// Generation must be bit-for-bit reproducible across platforms. Basic float arithmetic and `sqrt` are exactly
+// specified by IEEE 754, but transcendental functions (`sin`, `cos`, `atan2`, `powi`, etc.) are left to the
+// platform's math library and may differ in their last bits, so the functions below only use the former.
+//
+// The noise crate's internals are outside of this crate's control, so noise outputs are passed through
+// `quantize` before they are used to make any discrete decision (thresholds, `floor`, etc.), which absorbs
+// any tiny differences unless a value lies almost exactly on a rounding boundary.
+
+/// The granularity which `quantize` rounds to
+const QUANTUM: f64 = 1.0 / (1u64 << 24) as f64;
+
+/// Rounds a value to a fixed granularity, to be used on any float before it influences a discrete decision
+#[inline]
+pub fn quantize(value: f64) -> f64 {
+  (value / QUANTUM).round() * QUANTUM
+}
+
+/// Floors a value after quantizing it
+#[inline]
+pub fn quantized_floor(value: f64) -> i32 {
+  quantize(value).floor() as i32
+}
+
+/// Computes the sine and cosine of an angle given in turns, using only basic arithmetic
+pub fn sin_cos_turns(turns: f64) -> (f64, f64) {
+  use std::f64::consts::FRAC_PI_2;
+
+  // Reduces the angle to the range [-1/8, 1/8] turns around the nearest quarter turn
+  let quarters = turns.rem_euclid(1.0) * 4.0;
+  let quadrant = quarters.round();
+  let x = (quarters - quadrant) * FRAC_PI_2;
+  let x2 = x * x;
+
+  // Taylor series, accurate to well below f64 precision within the reduced range
+  let (mut sin, mut cos) = (0.0, 0.0);
+  let (mut sin_term, mut cos_term) = (x, 1.0);
+  for n in 0..10 {
+    sin += sin_term;
+    cos += cos_term;
+    sin_term *= -x2 / ((2 * n + 2) * (2 * n + 3)) as f64;
+    cos_term *= -x2 / ((2 * n + 1) * (2 * n + 2)) as f64;
+  };
+
+  match quadrant as i32 % 4 {
+    0 => (sin, cos),
+    1 => (cos, -sin),
+    2 => (-sin, -cos),
+    _ => (-cos, sin)
+  }
+}
+
+/// Computes the angle of a vector in turns, in the range [-1/2, 1/2], using only basic arithmetic
+pub fn atan2_turns(y: f64, x: f64) -> f64 {
+  use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+  if x == 0.0 && y == 0.0 { return 0.0 };
+  let (ax, ay) = (x.abs(), y.abs());
+  let mut angle = if ay <= ax {
+    atan_unit(ay / ax)
+  } else {
+    FRAC_PI_2 - atan_unit(ax / ay)
+  };
+
+  if x < 0.0 { angle = PI - angle };
+  if y < 0.0 { angle = -angle };
+  angle / TAU
+}
+
+/// The arctangent of a value in the range [0, 1], in radians
+fn atan_unit(z: f64) -> f64 {
+  // Each halving uses the identity `atan(z) = 2 * atan(z / (1 + sqrt(1 + z^2)))`,
+  // bringing `z` down to at most ~0.2 where the Taylor series converges quickly
+  let z = z / (1.0 + (1.0 + z * z).sqrt());
+  let z = z / (1.0 + (1.0 + z * z).sqrt());
+  let z2 = z * z;
+  let (mut sum, mut power) = (0.0, z);
+  for n in 0..24 {
+    let term = power / (2 * n + 1) as f64;
+    sum += if n % 2 == 0 { term } else { -term };
+    power *= z2;
+  };
+
+  sum * 4.0
+}
+
+#[cfg(test)]
+pub mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+
+  /// Boxes on and off the origin, including ones lying entirely to one side of it, single points, empty boxes
+  /// and a box at the limits of `i32`
+  pub fn test_boxes() -> impl Iterator<Item = (IVec2, IVec2)> {
+    [
+    ([0, 0], [0, 0]),
+    ([-3, -3], [3, 3]),
+    ([-5, -2], [1, 7]),
+    ([4, 2], [9, 6]),
+    ([-12, -9], [-7, -8]),
+    ([-4, 6], [4, 11]),
+    ([3, -10], [3, 10]),
+    ([-8, 5], [-8, 5]),
+    ([2, 2], [1, 5]),
+    ([0, 3], [6, 2]),
+    ([i32::MAX - 2, -i32::MAX], [i32::MAX, -i32::MAX + 3]),
+    ([i32::MIN, i32::MAX - 1], [i32::MIN + 2, i32::MAX]),
+    ([-1, i32::MIN], [1, i32::MIN])
+    ].into_iter().map(|(min, max)| (IVec2::from(min), IVec2::from(max)))
+  }
+
+  /// The radius of the square ring around 0,0 that a point lies on, which is past `i32::MAX` for `i32::MIN`
+  pub fn ring_radius(pos: IVec2) -> i64 {
+    (pos.x as i64).abs().max((pos.y as i64).abs())
+  }
+
+  #[test]
+  fn rings_in_box_visits_every_point_once() {
+    for (min, max) in test_boxes() {
+      let points = rings_in_box(min, max);
+      let len = points.len();
+      let points = points.collect::<Vec<IVec2>>();
+      let expected = (min.y..=max.y)
+        .flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+        .collect::<HashSet<IVec2>>();
+
+      assert_eq!(len, expected.len(), "length of {} to {}", min, max);
+      assert_eq!(points.len(), expected.len(), "points visited in {} to {}", min, max);
+      assert_eq!(points.iter().copied().collect::<HashSet<IVec2>>(), expected, "points in {} to {}", min, max);
+    };
+  }
+
+  #[test]
+  fn rings_in_box_expands_outwards() {
+    for (min, max) in test_boxes() {
+      let radii = rings_in_box(min, max).map(ring_radius).collect::<Vec<i64>>();
+      assert!(radii.windows(2).all(|pair| pair[0] <= pair[1]), "rings out of order in {} to {}", min, max);
+    };
+  }
+
+  #[test]
+  fn rings_in_box_counts_down() {
+    let (min, max) = test_boxes().nth(2).unwrap();
+    let mut points = rings_in_box(min, max);
+    for remaining in (0..points.len()).rev() {
+      points.next().unwrap();
+      assert_eq!(points.len(), remaining);
+    };
+    assert_eq!(points.next(), None);
+  }
+
+  #[test]
+  fn rings_in_box_starts_at_the_origin_of_an_unbounded_box() {
+    let points = rings_in_box(IVec2::splat(i32::MIN), IVec2::splat(i32::MAX));
+    assert_eq!(points.len(), usize::MAX);
+    let first = points.take(25).collect::<HashSet<IVec2>>();
+    let expected = (-2..=2).flat_map(|y| (-2..=2).map(move |x| IVec2::new(x, y))).collect::<HashSet<IVec2>>();
+    assert_eq!(first, expected);
+  }
+
+  #[test]
+  fn turn_functions_match_the_platform() {
+    for i in -100..=100_i32 {
+      let turns = i as f64 / 37.0;
+      let (sin, cos) = sin_cos_turns(turns);
+      let radians = turns * std::f64::consts::TAU;
+      assert!((sin - radians.sin()).abs() < 1e-12 && (cos - radians.cos()).abs() < 1e-12, "{} turns", turns);
+
+      let (x, y) = (cos * (1.0 + i.abs() as f64), sin * (1.0 + i.abs() as f64));
+      let turns = atan2_turns(y, x);
+      assert!((turns * std::f64::consts::TAU - y.atan2(x)).abs() < 1e-12, "atan2 of {}, {}", y, x);
+    };
+  }
+
+  #[test]
+  fn turn_functions_are_exact_at_quarter_turns() {
+    assert_eq!(sin_cos_turns(0.0), (0.0, 1.0));
+    assert_eq!(sin_cos_turns(0.25), (1.0, -0.0));
+    assert_eq!(sin_cos_turns(0.5), (-0.0, -1.0));
+    assert_eq!(sin_cos_turns(-0.25), (-1.0, 0.0));
+    assert_eq!(atan2_turns(0.0, 0.0), 0.0);
+    assert_eq!(atan2_turns(1.0, 0.0), 0.25);
+    assert_eq!(atan2_turns(0.0, -1.0), 0.5);
+    assert_eq!(atan2_turns(-1.0, 0.0), -0.25);
+  }
+
+  #[test]
+  fn quantize_absorbs_tiny_differences() {
+    assert_eq!(quantize(0.3), quantize(0.3 + 1e-12));
+    assert_eq!(quantized_floor(2.0 - 1e-12), 2);
+    assert_eq!(quantized_floor(-1e-12), 0);
+    assert_eq!(quantized_floor(-0.01), -1);
+  }
+
+  #[test]
+  fn weighted_picks_split_the_rolls_by_weight() {
+    let entries = [('a', 3), ('b', 0), ('c', 1), ('d', 6)];
+    let picks = (0..20).map(|roll| *weighted_pick(roll, &entries).unwrap()).collect::<String>();
+    assert_eq!(picks, "aaacddddddaaacdddddd");
+    assert_eq!(weighted_pick(u64::MAX, &entries), Some(&'d'));
+    assert_eq!(weighted_pick(7, &[('a', 0), ('b', 0)]), None);
+    assert_eq!(weighted_pick::<char>(7, &[]), None);
+  }
+}
@@ -0,0 +1,63 @@
+//! The one place where the generator's coordinates are turned into Minecraft's, and back.
+//!
+//! The generator has z as its vertical axis throughout, as in `WORLD_MIN_Z` and every comparison of `pos.z`, with x and
+//! y spanning the horizontal plane. Minecraft, Amulet and every file format they read have y as their vertical axis
+//! instead, with x and z spanning the horizontal plane. So a block at `(x, y, z)` here is at `(x, z, y)` in Minecraft,
+//! and a column or chunk at `(x, y)` here is at `(x, z)` in Minecraft.
+//!
+//! Anything handed to Minecraft or Amulet, or read back from them, goes through these functions rather than swapping
+//! the axes itself, so that a new output path can't forget the swap or make it twice.
+use glam::{IVec2, IVec3};
+
+use crate::generation::chunk_pos::ChunkPos;
+use crate::{MINECRAFT_MAX_Z, MINECRAFT_MIN_Z};
+
+
+
+/// A block position in Minecraft's coordinates, as `(x, y, z)` with y vertical
+pub fn to_minecraft(pos: IVec3) -> (i32, i32, i32) {
+  (pos.x, pos.z, pos.y)
+}
+
+/// A block position given in Minecraft's coordinates, with y vertical
+pub fn from_minecraft(x: i32, y: i32, z: i32) -> IVec3 {
+  IVec3::new(x, z, y)
+}
+
+/// A column's position in Minecraft's coordinates, as `(x, z)`
+pub fn column_to_minecraft(xy: IVec2) -> (i32, i32) {
+  (xy.x, xy.y)
+}
+
+/// A chunk's position in Minecraft's chunk coordinates, as `(cx, cz)`
+pub fn chunk_to_minecraft(chunk_pos: ChunkPos) -> (i32, i32) {
+  column_to_minecraft(chunk_pos.0)
+}
+
+/// A chunk given in Minecraft's chunk coordinates
+#[cfg(feature = "ffi")]
+pub fn chunk_from_minecraft(cx: i32, cz: i32) -> ChunkPos {
+  ChunkPos::new(cx, cz)
+}
+
+/// Checks that a position in Minecraft's coordinates, about to be written into the given chunk, lies within the
+/// heights that any world can have and within the chunk's columns. A position which was never swapped, or was swapped
+/// twice, nearly always fails one of these, rather than quietly ending up somewhere else in the world.
+pub fn debug_assert_in_chunk(chunk_pos: ChunkPos, (x, y, z): (i32, i32, i32)) {
+  debug_assert!(
+    (MINECRAFT_MIN_Z..=MINECRAFT_MAX_Z).contains(&y),
+    "minecraft y {} is outside of the world's height, were the axes swapped?", y
+  );
+  debug_assert!(
+    (x.div_euclid(16), z.div_euclid(16)) == chunk_to_minecraft(chunk_pos),
+    "minecraft x {} and z {} are outside of chunk {}, were the axes swapped?", x, z, chunk_pos.0
+  );
+}
+
+/// Checks that a section about to be written lies within the heights that any world can have
+pub fn debug_assert_section_in_world(section: i32) {
+  debug_assert!(
+    (MINECRAFT_MIN_Z.div_euclid(16)..=MINECRAFT_MAX_Z.div_euclid(16)).contains(&section),
+    "section {} is outside of the world's height, were the axes swapped?", section
+  );
+}
@@ -0,0 +1,164 @@
+//! The editions of Minecraft that a world can be rendered for, and the files that a new world of each starts with.
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::generation::block_entity::Nbt;
+use crate::generation::BoundingBox;
+use crate::world_coords::to_minecraft;
+
+
+
+/// The version of the Bedrock edition level.dat format written, which Amulet also reads from its header
+const BEDROCK_STORAGE_VERSION: i32 = 10;
+/// The version of Bedrock edition that new worlds claim to have last been opened with
+const BEDROCK_GAME_VERSION: [i32; 5] = [1, 20, 0, 0, 0];
+const BEDROCK_INVENTORY_VERSION: &str = "1.20.0";
+/// The protocol version of `BEDROCK_GAME_VERSION`
+const BEDROCK_NETWORK_VERSION: i32 = 589;
+/// Bedrock edition's overworld height limits, which unlike Java edition's can't be raised with a datapack
+pub const BEDROCK_MIN_Z: i32 = -64;
+pub const BEDROCK_MAX_Z: i32 = 319;
+/// Tells Bedrock edition to find a safe spawn height itself
+const BEDROCK_SPAWN_ON_SURFACE: i32 = 32767;
+/// The same layers as the flat world in the Java edition template, so that chunks outside the city match
+const BEDROCK_FLAT_WORLD_LAYERS: &str = concat!(
+  r#"{"biome_id":0,"block_layers":["#,
+  r#"{"block_name":"minecraft:bedrock","count":1},"#,
+  r#"{"block_name":"minecraft:deepslate","count":30},"#,
+  r#"{"block_name":"minecraft:gravel","count":2},"#,
+  r#"{"block_name":"minecraft:water","count":32}"#,
+  r#"],"encoding_version":6,"structure_options":null,"world_version":"version.post_1_18"}"#
+);
+/// The two bytes every gzip file starts with
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorldFormat {
+  /// A Java edition world, written by Amulet's Anvil format
+  #[default]
+  Java,
+  /// A Bedrock edition world, written by Amulet's LevelDB format
+  Bedrock
+}
+
+/// The contents of a new Bedrock edition level.dat: an eight byte header holding the storage version and the length
+/// of the rest of the file, followed by little endian NBT.
+/// The world is a creative mode flat world, with its spawn point above the middle of the given bounding box.
+pub fn bedrock_level_dat(level_name: &str, seed: u64, bounding_box: BoundingBox) -> Vec<u8> {
+  let center = (bounding_box.min + bounding_box.max) / 2;
+  let (spawn_x, spawn_y, spawn_z) = to_minecraft(center.truncate().extend(BEDROCK_SPAWN_ON_SURFACE));
+  let version = Nbt::list(BEDROCK_GAME_VERSION.iter().map(|&part| Nbt::Int(part)).collect())
+    .expect("every part of the version is an int");
+  let nbt = Nbt::compound([
+    ("LevelName", Nbt::String(level_name.to_owned())),
+    ("StorageVersion", Nbt::Int(BEDROCK_STORAGE_VERSION)),
+    ("NetworkVersion", Nbt::Int(BEDROCK_NETWORK_VERSION)),
+    ("InventoryVersion", Nbt::String(BEDROCK_INVENTORY_VERSION.to_owned())),
+    ("lastOpenedWithVersion", version.clone()),
+    ("MinimumCompatibleClientVersion", version),
+    ("RandomSeed", Nbt::Long(seed as i64)),
+    // Flat
+    ("Generator", Nbt::Int(2)),
+    ("FlatWorldLayers", Nbt::String(BEDROCK_FLAT_WORLD_LAYERS.to_owned())),
+    // Creative
+    ("GameType", Nbt::Int(1)),
+    ("Difficulty", Nbt::Int(0)),
+    ("commandsEnabled", Nbt::Byte(1)),
+    ("hasBeenLoadedInCreative", Nbt::Byte(1)),
+    ("LastPlayed", Nbt::Long(0)),
+    ("SpawnX", Nbt::Int(spawn_x)),
+    ("SpawnY", Nbt::Int(spawn_y)),
+    ("SpawnZ", Nbt::Int(spawn_z))
+  ]);
+
+  let mut body = Vec::new();
+  nbt.write_binary_little_endian(&mut body);
+  let mut level_dat = Vec::with_capacity(body.len() + 8);
+  level_dat.extend_from_slice(&BEDROCK_STORAGE_VERSION.to_le_bytes());
+  level_dat.extend_from_slice(&(body.len() as i32).to_le_bytes());
+  level_dat.extend_from_slice(&body);
+  level_dat
+}
+
+/// Whether a bounding box fits within Bedrock edition's height limits
+pub fn fits_bedrock_height(bounding_box: BoundingBox) -> bool {
+  bounding_box.min.z >= BEDROCK_MIN_Z && bounding_box.max.z <= BEDROCK_MAX_Z
+}
+
+/// An existing world's level.dat, which can be edited and written back without losing any of its fields.
+/// Java edition's is gzipped big endian NBT with everything under a `Data` compound, and
+/// Bedrock edition's is little endian NBT behind the same header that `bedrock_level_dat` writes.
+#[derive(Debug, Clone)]
+pub struct LevelDat {
+  format: WorldFormat,
+  /// The storage version from the header of a Bedrock edition level.dat
+  storage_version: i32,
+  root: Nbt
+}
+
+impl LevelDat {
+  /// Reads a level.dat of either edition, telling them apart by whether it is gzipped
+  pub fn decode(data: &[u8]) -> Option<Self> {
+    if data.starts_with(&GZIP_MAGIC) {
+      let root = Nbt::read_binary(&gunzip(data)?)?;
+      root.get("Data")?;
+      return Some(LevelDat { format: WorldFormat::Java, storage_version: 0, root });
+    };
+
+    let storage_version = i32::from_le_bytes(data.get(0..4)?.try_into().unwrap());
+    let root = Nbt::read_binary_little_endian(data.get(8..)?)?;
+    Some(LevelDat { format: WorldFormat::Bedrock, storage_version, root })
+  }
+
+  pub fn encode(&self) -> Vec<u8> {
+    match self.format {
+      WorldFormat::Java => {
+        let mut body = Vec::new();
+        self.root.write_binary(&mut body);
+        gzip(&body)
+      },
+      WorldFormat::Bedrock => {
+        let mut body = Vec::new();
+        self.root.write_binary_little_endian(&mut body);
+        let mut level_dat = Vec::with_capacity(body.len() + 8);
+        level_dat.extend_from_slice(&self.storage_version.to_le_bytes());
+        level_dat.extend_from_slice(&(body.len() as i32).to_le_bytes());
+        level_dat.extend_from_slice(&body);
+        level_dat
+      }
+    }
+  }
+
+  /// The compound holding the world's properties, such as its name and seed
+  pub fn data(&self) -> &Nbt {
+    match self.format {
+      WorldFormat::Java => self.root.get("Data").expect("checked when decoded"),
+      WorldFormat::Bedrock => &self.root
+    }
+  }
+
+  /// The entries of the compound holding the world's properties
+  pub fn data_mut(&mut self) -> Option<&mut BTreeMap<String, Nbt>> {
+    match self.format {
+      WorldFormat::Java => self.root.entries_mut()?.get_mut("Data")?.entries_mut(),
+      WorldFormat::Bedrock => self.root.entries_mut()
+    }
+  }
+}
+
+/// Decompresses a gzip file, checking it against its checksum
+fn gunzip(data: &[u8]) -> Option<Vec<u8>> {
+  let mut out = Vec::new();
+  GzDecoder::new(data).read_to_end(&mut out).ok()?;
+  Some(out)
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+  let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+  encoder.write_all(data).expect("writing to a vec can't fail");
+  encoder.finish().expect("writing to a vec can't fail")
+}
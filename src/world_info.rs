@@ -0,0 +1,284 @@
+//! A record of which version of the generator rendered a world, and what it was told to generate,
+//! kept in the world's level.dat and alongside it as JSON so that a world can be traced back to what produced it.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cli::generation_flags;
+use crate::estimate::GenerationEstimate;
+use crate::generation::block_entity::Nbt;
+use crate::generation::theme::DEFAULT_THEME_NAME;
+use crate::utility::json_string;
+use crate::world_format::LevelDat;
+use crate::GeneratorOptions;
+
+/// The key of the compound written into level.dat, next to the world's own properties
+pub const INFO_TAG: &str = "glt_generator";
+/// The name of the JSON file written into the world's directory
+pub const INFO_JSON: &str = "glt_generator.json";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldInfo {
+  /// The version of the generator, followed by the commit it was built from if that is known
+  pub version: String,
+  pub seed: u64,
+  pub layers: u32,
+  /// The number of columns covered by the landmass of each layer, from the bottom up
+  pub landmass_sizes: Vec<u32>,
+  /// When the world was rendered, in seconds since the Unix epoch
+  pub generated_at: u64,
+  /// The flags which, along with the seed, generate the same city, see `cli::generation_flags`
+  pub options: String,
+  /// The name of the theme the world's materials were swapped for, which the flags leave out.
+  /// Worlds recorded before themes existed are read as using the default theme.
+  pub theme: String,
+  /// How much work rendering the world was estimated to be before it started, if it was estimated
+  pub estimate: Option<GenerationEstimate>
+}
+
+impl WorldInfo {
+  /// Describes a world generated just now by this build of the generator
+  pub fn new(
+    seed: u64, options: &GeneratorOptions, landmass_sizes: Vec<u32>, estimate: Option<GenerationEstimate>
+  ) -> Self {
+    let generated_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    WorldInfo {
+      version: generator_version(),
+      seed,
+      layers: options.layer_count as u32,
+      landmass_sizes,
+      generated_at,
+      options: generation_flags(options).join(" "),
+      theme: options.theme.name.clone(),
+      estimate
+    }
+  }
+
+  /// Adds this information to the level.dat of the world at the given path, keeping everything already in it,
+  /// then writes the same information to a JSON file beside it
+  pub fn write_to_world(&self, path: &Path) -> io::Result<()> {
+    let level_dat_path = path.join("level.dat");
+    let mut level_dat = LevelDat::decode(&fs::read(&level_dat_path)?).ok_or_else(invalid_level_dat)?;
+    self.write_to_level_dat(&mut level_dat)?;
+    fs::write(&level_dat_path, level_dat.encode())?;
+    fs::write(path.join(INFO_JSON), self.to_json())
+  }
+
+  pub fn write_to_level_dat(&self, level_dat: &mut LevelDat) -> io::Result<()> {
+    let data = level_dat.data_mut().ok_or_else(invalid_level_dat)?;
+    data.insert(INFO_TAG.to_owned(), self.to_nbt());
+    Ok(())
+  }
+
+  /// Reads the information from the level.dat of the world at the given path,
+  /// or `None` if the world wasn't rendered by a version of the generator which recorded it
+  pub fn read_from_world(path: &Path) -> io::Result<Option<Self>> {
+    let level_dat = LevelDat::decode(&fs::read(path.join("level.dat"))?).ok_or_else(invalid_level_dat)?;
+    Ok(WorldInfo::read_from_level_dat(&level_dat))
+  }
+
+  pub fn read_from_level_dat(level_dat: &LevelDat) -> Option<Self> {
+    WorldInfo::from_nbt(level_dat.data().get(INFO_TAG)?)
+  }
+
+  fn to_nbt(&self) -> Nbt {
+    let landmass_sizes = self.landmass_sizes.iter().map(|&size| size as i32).collect();
+    let mut nbt = Nbt::compound([
+      ("version", Nbt::String(self.version.clone())),
+      ("seed", Nbt::Long(self.seed as i64)),
+      ("layers", Nbt::Int(self.layers as i32)),
+      ("landmass_sizes", Nbt::IntArray(landmass_sizes)),
+      ("generated_at", Nbt::Long(self.generated_at as i64)),
+      ("options", Nbt::String(self.options.clone())),
+      ("theme", Nbt::String(self.theme.clone()))
+    ]);
+    if let (Some(estimate), Some(entries)) = (self.estimate, nbt.entries_mut()) {
+      entries.insert("estimate".to_owned(), estimate.to_nbt());
+    };
+
+    nbt
+  }
+
+  fn from_nbt(nbt: &Nbt) -> Option<Self> {
+    let theme = match nbt.get("theme") {
+      Some(Nbt::String(theme)) => theme.clone(),
+      Some(_) => return None,
+      None => DEFAULT_THEME_NAME.to_owned()
+    };
+
+    let estimate = match nbt.get("estimate") {
+      Some(estimate) => Some(GenerationEstimate::from_nbt(estimate)?),
+      None => None
+    };
+
+    match (
+      nbt.get("version")?, nbt.get("seed")?, nbt.get("layers")?,
+      nbt.get("landmass_sizes")?, nbt.get("generated_at")?, nbt.get("options")?
+    ) {
+      (
+        Nbt::String(version), &Nbt::Long(seed), &Nbt::Int(layers),
+        Nbt::IntArray(landmass_sizes), &Nbt::Long(generated_at), Nbt::String(options)
+      ) => Some(WorldInfo {
+        version: version.clone(),
+        seed: seed as u64,
+        layers: layers as u32,
+        landmass_sizes: landmass_sizes.iter().map(|&size| size as u32).collect(),
+        generated_at: generated_at as u64,
+        options: options.clone(),
+        theme,
+        estimate
+      }),
+      _ => None
+    }
+  }
+
+  fn to_json(&self) -> String {
+    let landmass_sizes = self.landmass_sizes.iter().map(u32::to_string).collect::<Vec<String>>();
+    let estimate = self.estimate.map_or_else(|| "null".to_owned(), |estimate| estimate.to_json());
+    format!(
+      concat!(
+        "{{\n  \"version\": {},\n  \"seed\": {},\n  \"layers\": {},\n  \"landmass_sizes\": [{}],\n",
+        "  \"generated_at\": {},\n  \"options\": {},\n  \"theme\": {},\n  \"estimate\": {}\n}}\n"
+      ),
+      json_string(&self.version), self.seed, self.layers, landmass_sizes.join(", "),
+      self.generated_at, json_string(&self.options), json_string(&self.theme), estimate
+    )
+  }
+}
+
+impl fmt::Display for WorldInfo {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let landmass_sizes = self.landmass_sizes.iter().map(u32::to_string).collect::<Vec<String>>();
+    let options = match self.options.is_empty() {
+      true => "(defaults)",
+      false => &self.options
+    };
+
+    writeln!(f, "version:        {}", self.version)?;
+    writeln!(f, "seed:           {}", self.seed)?;
+    writeln!(f, "layers:         {}", self.layers)?;
+    writeln!(f, "landmass sizes: {}", landmass_sizes.join(", "))?;
+    writeln!(f, "generated at:   {} (unix time)", self.generated_at)?;
+    writeln!(f, "options:        {}", options)?;
+    writeln!(f, "theme:          {}", self.theme)?;
+    match &self.estimate {
+      Some(estimate) => write!(f, "estimate:       {}", estimate),
+      None => write!(f, "estimate:       (not recorded)")
+    }
+  }
+}
+
+/// The version of this build of the generator, followed by the commit it was built from if that was known,
+/// which `build.rs` finds when building from a git checkout
+pub fn generator_version() -> String {
+  match option_env!("GLT_GIT_HASH") {
+    Some(hash) => format!("{}+{}", env!("CARGO_PKG_VERSION"), hash),
+    None => env!("CARGO_PKG_VERSION").to_owned()
+  }
+}
+
+fn invalid_level_dat() -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, "level.dat is not a valid java or bedrock edition level.dat")
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::IVec3;
+
+  use super::*;
+  use crate::generation::BoundingBox;
+  use crate::world_format::bedrock_level_dat;
+
+  const TEMPLATE_LEVEL_DAT: &[u8] = include_bytes!("../world-template/level.dat");
+
+  fn test_info(estimate: Option<GenerationEstimate>) -> WorldInfo {
+    WorldInfo {
+      version: "0.1.0+0123abcd".to_owned(),
+      seed: u64::MAX - 5,
+      layers: 3,
+      landmass_sizes: vec![467_850, 120_000, 9_001],
+      generated_at: 1_700_000_000,
+      options: "--layers 3 --details".to_owned(),
+      theme: "sandstone".to_owned(),
+      estimate
+    }
+  }
+
+  fn test_estimate() -> GenerationEstimate {
+    GenerationEstimate {
+      chunk_count: 4096,
+      far_field_chunk_count: 3000,
+      block_samples: 400_000_000,
+      output_bytes: 50_000_000,
+      blocks_per_second: 2_000_000
+    }
+  }
+
+  fn nbt_bytes(nbt: &Nbt) -> Vec<u8> {
+    let mut out = Vec::new();
+    nbt.write_binary(&mut out);
+    out
+  }
+
+  /// Writes the information into a level.dat, encodes it and reads it back, checking that every other entry of the
+  /// world's properties comes back exactly as it was
+  fn check_round_trip(original: &[u8], info: &WorldInfo) {
+    let before = LevelDat::decode(original).expect("the original level.dat is valid");
+    assert_eq!(WorldInfo::read_from_level_dat(&before), None);
+
+    let mut level_dat = before.clone();
+    info.write_to_level_dat(&mut level_dat).unwrap();
+    let after = LevelDat::decode(&level_dat.encode()).expect("the written level.dat is valid");
+    assert_eq!(WorldInfo::read_from_level_dat(&after).as_ref(), Some(info));
+
+    let (before, after) = match (before.data(), after.data()) {
+      (Nbt::Compound(before), Nbt::Compound(after)) => (before, after),
+      _ => panic!("level.dat has no compound of properties")
+    };
+
+    assert_eq!(after.len(), before.len() + 1);
+    for (key, value) in before {
+      let written = after.get(key).unwrap_or_else(|| panic!("{} was dropped", key));
+      assert_eq!(nbt_bytes(written), nbt_bytes(value), "{} was changed", key);
+    };
+  }
+
+  #[test]
+  fn world_info_round_trips_through_the_template_level_dat() {
+    let level_dat = LevelDat::decode(TEMPLATE_LEVEL_DAT).unwrap();
+    assert!(matches!(level_dat.data().get("LevelName"), Some(Nbt::String(_))));
+    check_round_trip(TEMPLATE_LEVEL_DAT, &test_info(None));
+    check_round_trip(TEMPLATE_LEVEL_DAT, &test_info(Some(test_estimate())));
+  }
+
+  #[test]
+  fn damaged_java_level_dats_are_rejected() {
+    // A changed byte of the gzip checksum, then of the length, then the file cut off partway through
+    let trailer = TEMPLATE_LEVEL_DAT.len() - 8;
+    for index in [trailer, trailer + 4] {
+      let mut damaged = TEMPLATE_LEVEL_DAT.to_vec();
+      damaged[index] ^= 1;
+      assert!(LevelDat::decode(&damaged).is_none(), "byte {} of the trailer was changed", index - trailer);
+    };
+
+    assert!(LevelDat::decode(&TEMPLATE_LEVEL_DAT[..TEMPLATE_LEVEL_DAT.len() / 2]).is_none());
+  }
+
+  #[test]
+  fn world_info_round_trips_through_a_bedrock_level_dat() {
+    let bounding_box = BoundingBox::new(IVec3::new(-500, -300, -64), IVec3::new(700, 200, 319));
+    let original = bedrock_level_dat("test world", 5, bounding_box);
+    check_round_trip(&original, &test_info(Some(test_estimate())));
+  }
+
+  #[test]
+  fn worlds_recorded_before_themes_use_the_default_theme() {
+    let info = test_info(None);
+    let mut nbt = info.to_nbt();
+    nbt.entries_mut().unwrap().remove("theme");
+    let read = WorldInfo::from_nbt(&nbt).unwrap();
+    assert_eq!(read, WorldInfo { theme: DEFAULT_THEME_NAME.to_owned(), ..info });
+  }
+}
@@ -0,0 +1,8 @@
+# A stand-in for Amulet-Core, implementing only the parts of it that glt-mc-generator uses.
+#
+# Nothing is written to the world. Instead, every call the generator makes is recorded in `amulet._stub.calls`, and
+# written out one per line to the file named by the `AMULET_STUB_LOG` environment variable (if it is set) when the
+# world is closed. Put this directory on `PYTHONPATH` to render against it without Amulet installed:
+#
+#   PYTHONPATH=tests/amulet_stub AMULET_STUB_LOG=calls.txt cargo run -- generate --layers 1
+from . import api, level
@@ -0,0 +1,22 @@
+import os
+
+calls = []
+
+
+def record(name, *args):
+    calls.append((name,) + args)
+
+
+def lines():
+    """Every call recorded so far, written out as it is in the log"""
+    return [" ".join(str(part) for part in call) for call in calls]
+
+
+def write_log():
+    path = os.environ.get("AMULET_STUB_LOG")
+    if path:
+        # Appended to, since each render worker process writes its own calls
+        with open(path, "a", encoding="utf-8") as log:
+            for line in lines():
+                log.write(line + "\n")
+        calls.clear()
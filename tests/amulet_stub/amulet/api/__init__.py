@@ -0,0 +1 @@
+from . import block, block_entity, level
@@ -0,0 +1,37 @@
+class Block:
+    def __init__(self, namespace, base_name, properties=None):
+        self.namespace = namespace
+        self.base_name = base_name
+        self.properties = dict(properties or {})
+        self._extra_blocks = ()
+
+    @classmethod
+    def from_string_blockstate(cls, blockstate):
+        name, _, properties = blockstate.partition("[")
+        namespace, _, base_name = name.rpartition(":")
+        properties = dict(
+            property.split("=", 1) for property in properties.rstrip("]").split(",") if property
+        )
+        return cls(namespace or "minecraft", base_name, properties)
+
+    @property
+    def blockstate(self):
+        properties = ",".join(f"{key}={value}" for key, value in sorted(self.properties.items()))
+        name = f"{self.namespace}:{self.base_name}"
+        return f"{name}[{properties}]" if properties else name
+
+    @property
+    def extra_blocks(self):
+        return self._extra_blocks
+
+    def __eq__(self, other):
+        return isinstance(other, Block) and self._key() == other._key()
+
+    def __hash__(self):
+        return hash(self._key())
+
+    def __str__(self):
+        return " + ".join([self.blockstate] + [block.blockstate for block in self._extra_blocks])
+
+    def _key(self):
+        return (self.blockstate, tuple(block.blockstate for block in self._extra_blocks))
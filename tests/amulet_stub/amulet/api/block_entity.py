@@ -0,0 +1,9 @@
+class BlockEntity:
+    def __init__(self, namespace, base_name, x, y, z, nbt):
+        self.namespace = namespace
+        self.base_name = base_name
+        self.x, self.y, self.z = x, y, z
+        self.nbt = nbt
+
+    def __str__(self):
+        return f"{self.namespace}:{self.base_name}@{self.x},{self.y},{self.z}"
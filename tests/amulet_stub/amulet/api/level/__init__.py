@@ -0,0 +1,2 @@
+from . import world
+from .world import World
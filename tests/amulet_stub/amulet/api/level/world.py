@@ -0,0 +1,107 @@
+import zlib
+
+from amulet import _stub
+
+# The height of the world the generator renders into, as `WORLD_MIN_Z` and `WORLD_MAX_Z` in src/main.rs
+MIN_Y = -64
+MAX_Y = MIN_Y + 64 + 512
+
+class World:
+    def __init__(self, path, format_wrapper):
+        self.path = str(path)
+        self.format_wrapper = format_wrapper
+        self.block_palette = BlockManager()
+        self.translation_manager = TranslationManager()
+        self.chunks = {}
+        _stub.record("World", self.path, type(format_wrapper).__name__)
+
+    def create_chunk(self, cx, cz, dimension):
+        _stub.record("create_chunk", cx, cz, dimension)
+        chunk = self.chunks[(cx, cz, dimension)] = Chunk(cx, cz, self.block_palette)
+        return chunk
+
+    def get_chunk(self, cx, cz, dimension):
+        # Not recorded, since it is only used to look at what was written
+        return self.chunks[(cx, cz, dimension)]
+
+    def save(self):
+        _stub.record("save")
+
+    def close(self):
+        _stub.record("close")
+        _stub.write_log()
+
+
+class BlockManager:
+    """Numbers each distinct block in the order it is first added, as Amulet's does"""
+
+    def __init__(self):
+        self._blocks = {}
+
+    def get_add_block(self, block):
+        num = self._blocks.setdefault(block, len(self._blocks))
+        _stub.record("get_add_block", block, num)
+        return num
+
+
+class Chunk:
+    def __init__(self, cx, cz, block_palette):
+        self.cx, self.cz = cx, cz
+        self.block_palette = block_palette
+        self.blocks = Blocks(cx, cz)
+        self.block_entities = BlockEntities(cx, cz)
+
+
+class Blocks:
+    def __init__(self, cx, cz):
+        self.cx, self.cz = cx, cz
+        self.sub_chunks = {}
+
+    def add_sub_chunk(self, cy, array):
+        if tuple(array.shape) != (16, 16, 16):
+            raise ValueError(f"sub-chunk {cy} has shape {array.shape}, not (16, 16, 16)")
+        if not MIN_Y >> 4 <= cy <= MAX_Y >> 4:
+            raise ValueError(f"sub-chunk {cy} is outside of the world's height")
+        self.sub_chunks[cy] = array
+        # Sections are laid out by x, y, then z, with y being the vertical axis.
+        # Recorded with the number of distinct blocks and a checksum of the whole section, so that renders can be compared
+        checksum = zlib.crc32(array.astype("<u4").tobytes())
+        _stub.record("add_sub_chunk", self.cx, cy, self.cz, len(set(array.flat)), checksum)
+
+    def __getitem__(self, pos):
+        # Indexed as Amulet's are, by x and z within the chunk and the world's y, giving the number of the block there,
+        # or None where no sub-chunk was written
+        x, y, z = pos
+        sub_chunk = self.sub_chunks.get(y >> 4)
+        return None if sub_chunk is None else int(sub_chunk[x, y & 15, z])
+
+
+class BlockEntities:
+    def __init__(self, cx, cz):
+        self.cx, self.cz = cx, cz
+        self.entities = []
+
+    def insert(self, block_entity):
+        if (block_entity.x >> 4, block_entity.z >> 4) != (self.cx, self.cz):
+            raise ValueError(f"block entity {block_entity} is outside of chunk {self.cx}, {self.cz}")
+        if not MIN_Y <= block_entity.y <= MAX_Y:
+            raise ValueError(f"block entity {block_entity} is outside of the world's height")
+        self.entities.append(block_entity)
+        _stub.record("insert", block_entity)
+
+
+class TranslationManager:
+    def get_version(self, platform, version):
+        _stub.record("get_version", platform, version)
+        return Version()
+
+
+class Version:
+    def __init__(self):
+        self.block = BlockTranslator()
+
+
+class BlockTranslator:
+    def to_universal(self, block):
+        # The generator's blocks are passed through unchanged, along with no block entity and no extra information
+        return block, None, False
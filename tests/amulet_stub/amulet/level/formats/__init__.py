@@ -0,0 +1 @@
+from . import anvil_world, leveldb_world
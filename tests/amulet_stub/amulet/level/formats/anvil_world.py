@@ -0,0 +1,3 @@
+class AnvilFormat:
+    def __init__(self, path):
+        self.path = str(path)
@@ -0,0 +1,34 @@
+# A stand-in for amulet-nbt, see `amulet/__init__.py`. Tags only hold onto their values.
+
+
+class _Tag:
+    def __init__(self, value=None):
+        self.value = value
+
+    def __repr__(self):
+        return f"{type(self).__name__}({self.value!r})"
+
+
+class TAG_Byte(_Tag): pass
+class TAG_Short(_Tag): pass
+class TAG_Int(_Tag): pass
+class TAG_Long(_Tag): pass
+class TAG_Float(_Tag): pass
+class TAG_Double(_Tag): pass
+class TAG_Byte_Array(_Tag): pass
+class TAG_String(_Tag): pass
+class TAG_List(_Tag): pass
+class TAG_Compound(_Tag): pass
+class TAG_Int_Array(_Tag): pass
+class TAG_Long_Array(_Tag): pass
+
+
+class NBTFile:
+    def __init__(self, value=None, name=""):
+        self.value = value
+        self.name = name
+
+
+def load(data, compressed=True):
+    """Keeps the raw NBT as sent by the generator, without decoding it"""
+    return NBTFile(bytes(data))